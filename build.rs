@@ -0,0 +1,19 @@
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        use std::path::PathBuf;
+
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo"));
+        let config = cbindgen::Config::from_file(dir.join("cbindgen.toml"))
+            .expect("Failed to read cbindgen.toml");
+        // Only the C surface, not every public item in the crate.
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(dir.join("src/ffi.rs"))
+            .generate()
+            .expect("Failed to generate the C header")
+            .write_to_file(dir.join("include/qrbrand.h"));
+    }
+}
@@ -0,0 +1,59 @@
+#ifndef QRBRAND_H
+#define QRBRAND_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * The call succeeded.
+ */
+#define QRBRAND_OK 0
+
+/**
+ * The options were rejected or the render failed; see `qrbrand_last_error`.
+ */
+#define QRBRAND_ERROR 1
+
+/**
+ * A required pointer was null or the options were not UTF-8.
+ */
+#define QRBRAND_INVALID_ARGUMENT 2
+
+/**
+ * PNG bytes owned by qrbrand; release them with `qrbrand_buffer_free`.
+ */
+typedef struct QrbrandBuffer {
+  uint8_t *data;
+  size_t len;
+} QrbrandBuffer;
+
+/**
+ * Render the code described by `options_json` (a batch record as a JSON object) into
+ * `out_buf` as PNG bytes. Returns `QRBRAND_OK` or an error code.
+ *
+ * # Safety
+ *
+ * `options_json` must be a NUL-terminated string and `out_buf` must point to writable
+ * memory for a `QrbrandBuffer`. Both only need to live for the duration of the call.
+ */
+int32_t qrbrand_render(const char *options_json, struct QrbrandBuffer *out_buf);
+
+/**
+ * Release a buffer filled by `qrbrand_render` and reset it to empty. Null and empty
+ * buffers are ignored.
+ *
+ * # Safety
+ *
+ * `buf` must be null or point to a buffer filled by `qrbrand_render` and not yet freed.
+ */
+void qrbrand_buffer_free(struct QrbrandBuffer *buf);
+
+/**
+ * The message for the last failed call on this thread, or null if none has failed.
+ * The string stays valid until the next failing call on the same thread.
+ */
+const char *qrbrand_last_error(void);
+
+#endif  /* QRBRAND_H */
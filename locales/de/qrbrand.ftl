@@ -0,0 +1,148 @@
+# German. Untranslated messages fall back to locales/en/qrbrand.ftl.
+
+error-prefix = Fehler
+warning-prefix = Warnung
+row-prefix = Zeile { $row }
+
+wrote = { $path } geschrieben
+batch-summary = { $dry_run ->
+        [yes] Würde { $written } von { $total } Dateien schreiben
+       *[no] { $written } von { $total } Dateien geschrieben
+    } ({ $skipped } aktuell, übersprungen)
+wrote-animation = { $path } geschrieben ({ $codes } Codes, je { $seconds } s)
+
+about = Erzeugt aus einer URL einen scanbaren QR-Code als PNG, wahlweise mit Logo in der Mitte.
+help-url = Zu kodierende URL (z. B. https://github.com/softwarewrighter/speed-kings)
+help-size = Seitenlänge des QR-Teils: Pixel (1024, 2048px) oder eine Länge bei einer Auflösung (50mm@300dpi, 2in@600dpi)
+help-alt-text = Beschriftung unter dem Code; mit --caption-lang einmal je Sprache
+about-batch = Rendert einen QR-Code je CSV-Zeile, parallel
+about-serve = Rendert Codes über HTTP: GET oder POST /render, Antwort als PNG, SVG oder PDF
+about-verify = Dekodiert ein Verzeichnis von Proofs und prüft sie gegen ein Batch-Manifest
+about-completions = Gibt ein Shell-Vervollständigungsskript aus (bash, zsh, fish oder powershell)
+
+no-payload = { $command ->
+        [barcode] Ein Barcode hat keinen QR-Inhalt
+        [batch] Der Batch-Modus hat keinen einzelnen Inhalt
+        [diff] diff liest Codes und hat keinen Inhalt
+        [verify] verify liest Codes und hat keinen Inhalt
+        [series] Eine Serie hat keinen einzelnen Inhalt
+        [daemon] Der Daemon-Modus hat keinen einzelnen Inhalt
+        [serve] Der Server-Modus hat keinen einzelnen Inhalt
+        [bench] Der Benchmark-Modus hat keinen einzelnen Inhalt
+        [animate] Eine Animation hat mehrere Inhalte
+        [run] Eine Job-Datei hat keinen einzelnen Inhalt
+        [completions] Vervollständigungen haben keinen Inhalt
+        [wizard] Der Assistent hat keinen einzelnen Inhalt
+       *[exit-codes] exit-codes hat keinen Inhalt
+    }
+url-required = --url fehlt
+url-required-clipboard = --url fehlt (oder --url-from-clipboard)
+url-invalid = Ungültige URL: { $url } (mit https:// angegeben?)
+clipboard-not-url = Die Zwischenablage enthält keine URL: { $start }
+job-invalid = Ungültige Job-Datei { $path }
+theme-failed = Theme '{ $theme }' lässt sich nicht anwenden
+alt-text-template-invalid = Ungültige Vorlage für --alt-text
+several-outs-single = Nur einzelne Codes nehmen mehr als ein --out
+lang-files-single = --lang-layout files gilt nur für einzelne Codes
+format-several-outs = --format wählt ein Format; bei mehreren --out bestimmt jeweils die Endung das Format
+out-extension = { $path } kann nicht geschrieben werden: .{ $ext } ist kein Ausgabeformat (png, jpg, gif, ico, eps, tif, svg oder pdf)
+favicon-needs-ico = --favicon-png gehört zu --format ico
+favicon-zero = Größen für --favicon-png müssen mindestens 1 sein
+print-size-format = --print-size gehört zu --format eps, tiff oder pdf oder zu --print
+needs-physical = { $flag } gehört zu --format eps, tiff oder pdf
+colorspace-needs-tiff = --colorspace gehört zu --format tiff
+icc-format = --icc gehört zu --format png oder tiff
+needs-cmyk = { $flag } gehört zu --colorspace cmyk
+eps-unsupported = EPS enthält nur das Modulraster; { $flag } lässt sich darin nicht zeichnen
+svg-unsupported = SVG enthält nur das Modulraster; { $flag } lässt sich darin nicht zeichnen
+label-unsupported = Ein Etikett setzt seinen eigenen Text neben den Code; { $flag } lässt sich damit nicht kombinieren
+stream-one-out = --stream schreibt ein PNG; --out nur einmal angeben
+print-too-small = Ein Druck von { $size } ist zu klein, um aus { $distance } gescannt zu werden; mindestens { $needed } breit drucken
+size-too-small = Die Größe { $size } ist zu klein für { $modules } Module (ppm={ $ppm }). --size erhöhen.
+label-no-room = Kein Platz für die Beschriftung in einer Ruhezone von { $quiet } Modulen bei dieser Größe; --quiet oder --size erhöhen
+place-lower-third = --place gehört zu einem --template-Bild; lower-third platziert den Code selbst
+place-needs-template = --place braucht ein --template-Bild
+template-needs-place = --template braucht --place
+invert-quiet = --invert braucht eine Ruhezone von mindestens { $modules } Modulen
+fill-invert = --fill-texture lässt sich nicht mit --invert kombinieren
+outline-no-decode = Mit umrandeten Modulen ist der Code nicht mehr lesbar; --module-gap oder das Logo weglassen oder die Module füllen
+gap-no-decode = Mit --module-gap { $gap } ist der Code nicht mehr lesbar; einen kleineren Abstand oder ein kleineres Logo verwenden
+eye-glyph-no-decode = Mit --eye-glyph finden Scanner die Suchmuster nicht; eine einfachere Form oder eine dunklere --eye-glyph-color wählen
+corner-radius-too-big = --corner-radius { $radius } schneidet in die Suchmuster; bei dieser Größe und Ruhezone höchstens { $max } px
+auto-color-needs-image = --auto-color nimmt die Farbe aus dem Logo; --image angeben
+ecc-margin-range = --ecc-margin muss mindestens 0 und kleiner als 1 sein
+logo-scale-range = --logo-scale sollte für sicheres Scannen zwischen etwa 0.05 und 0.35 liegen
+logo-too-big = Das Logo passt selbst bei --logo-scale { $scale } nicht ins Fehlerkorrekturbudget ({ $coverage }); --ec-level erhöhen oder --logo-pad verringern
+
+size-needs-dpi = Bitte eine Auflösung zu '{ $size }' angeben, z. B. { $size }@300dpi
+size-expected = Erwartet Pixel (1024, 2048px) oder eine Länge bei einer Auflösung (50mm@300dpi), erhalten '{ $size }'
+size-expected-dpi = Erwartet eine Auflösung wie 300dpi, erhalten '{ $dpi }'
+size-under-pixel = '{ $size }' ist kleiner als ein Pixel
+
+pdf417-single = --symbology pdf417 gilt nur für einzelne Codes
+pdf417-one-out = --symbology pdf417 schreibt ein einziges --out
+pdf417-unsupported = { $flag } gilt nicht für --symbology pdf417
+pdf417-format = --symbology pdf417 schreibt png (oder jpg oder gif) oder pdf
+pdf417-print-size = --print-size gehört zu einem --out mit der Endung .pdf
+pdf417-size-too-small = --size { $size } ist zu klein für die { $modules } Module, die das PDF417-Symbol breit ist
+pdf417-too-long = Inhalt zu lang für PDF417 ({ $codewords } Datencodewörter, höchstens { $max })
+
+param-expected = Erwartet Schlüssel=Wert, erhalten '{ $param }'
+phone-invalid = Ungültige Telefonnummer: { $number }
+email-invalid = Ungültige E-Mail-Adresse: { $address }
+totp-colon = TOTP-Aussteller und -Konto dürfen kein ':' enthalten
+totp-account-empty = Das TOTP-Konto darf nicht leer sein
+totp-digits = --digits muss 6 oder 8 sein (erhalten { $digits })
+totp-period = --period muss größer als null sein
+totp-secret-empty = Das TOTP-Geheimnis darf nicht leer sein
+totp-secret-base32 = Das TOTP-Geheimnis ist kein gültiges Base32 (unerwartetes '{ $char }')
+length-out-of-range = { $flag } muss { $min } bis { $max } Zeichen lang sein (erhalten { $count })
+epc-purpose = --purpose muss ein Code aus 4 Zeichen sein (erhalten { $purpose })
+epc-line-break = EPC-Felder dürfen keine Zeilenumbrüche enthalten
+epc-too-long = Der EPC-Inhalt ist { $bytes } Bytes lang; der Standard erlaubt höchstens { $max }
+epc-amount-invalid = Ungültiger Betrag für --amount: { $amount } (erwartet z. B. 12.50)
+epc-amount-too-small = --amount muss mindestens 0.01 sein
+iban-invalid = Ungültige IBAN: { $iban }
+iban-country = Ungültiger IBAN-Ländercode: { $country }
+iban-checksum = Ungültige IBAN-Prüfsumme: { $iban }
+bic-invalid = Ungültige BIC (erwartet 8 oder 11 Buchstaben/Ziffern): { $bic }
+event-title-empty = --title darf nicht leer sein
+event-tz-invalid = Ungültige --tz: { $tz }
+event-start-invalid = Ungültiger --start
+event-end-invalid = Ungültiges --end
+event-time-invalid = Ungültige Zeitangabe: { $time } (erwartet YYYY-MM-DD, YYYY-MM-DDTHH:MM oder RFC 3339)
+event-mixed-forms = --start und --end müssen dieselbe Form haben (Datum, Ortszeit oder UTC/Offset)
+event-end-before-start = --end muss nach --start liegen
+geo-lat = --lat muss zwischen -90 und 90 liegen (erhalten { $lat })
+geo-lon = --lon muss zwischen -180 und 180 liegen (erhalten { $lon })
+gs1-required = --element-string oder --digital-link fehlt
+gs1-expected-paren = Erwartet '(' vor einem AI im GS1-Element-String: { $rest }
+gs1-unclosed-paren = Nicht geschlossene '(' im GS1-Element-String
+gs1-empty = Der GS1-Element-String ist leer
+gs1-ai-invalid = Ungültiger GS1-AI ({ $ai }): erwartet 2 bis 4 Ziffern
+gs1-ai-length = GS1-AI ({ $ai }) muss { $digits } Ziffern haben
+gs1-ai-no-value = GS1-AI ({ $ai }) hat keinen Wert
+gs1-ai-character = GS1-AI ({ $ai }) enthält { $char }, das GS1 nicht erlaubt
+gs1-ai-fixed = GS1-AI ({ $ai }) braucht genau { $digits } Ziffern, erhalten '{ $value }'
+gs1-ai-check-digit = GS1-AI ({ $ai }) hat eine falsche Prüfziffer: { $value }
+digital-link-invalid = Ungültige Digital-Link-URI: { $uri }
+digital-link-scheme = Eine Digital-Link-URI muss https:// verwenden (erhalten { $scheme })
+digital-link-gtin = Digital Link ({ $ai }) ist keine gültige GTIN: { $gtin }
+digital-link-sscc = Digital Link ({ $ai }) ist keine gültige SSCC: { $sscc }
+digital-link-no-key = Die Digital-Link-URI hat keinen GS1-Primärschlüssel (z. B. /01/<GTIN>): { $uri }
+amount-invalid-decimals = Ungültiger Betrag für --amount: { $amount } (erwartet { $unit } mit höchstens { $decimals } Nachkommastellen)
+amount-not-positive = --amount muss größer als null sein
+bitcoin-invalid = Ungültige Bitcoin-Adresse ({ $reason ->
+        [not-base58] kein Base58
+        [wrong-length] falsche Länge
+        [checksum] Prüfsumme stimmt nicht
+        [mixed-case] gemischte Groß-/Kleinschreibung
+        [no-separator] kein Trennzeichen
+        [bad-character] ungültiges Zeichen
+        [too-short] zu kurz
+       *[witness-program] ungültiges Witness-Programm
+    }): { $address }
+bitcoin-version = Keine Bitcoin-Adresse (Versionsbyte { $version }): { $address }
+ethereum-invalid = Ungültige Ethereum-Adresse (erwartet 0x und 40 Hex-Ziffern): { $address }
+ethereum-checksum = Ungültige Ethereum-Adresse (EIP-55-Prüfsumme stimmt nicht): { $address }
+ethereum-no-checksum = Die Ethereum-Adresse { $address } hat keine EIP-55-Prüfsumme; vor dem Druck genau prüfen
@@ -0,0 +1,177 @@
+# Messages qrbrand prints on the command line, in English. Every other locale
+# falls back to this file for anything it leaves out.
+#
+# Help for an option or subcommand is taken from the source and needs no entry
+# here. A translation overrides it with `help-<option>` for an option of the
+# main command, `help-<subcommand>-<option>` for an option of a subcommand, and
+# `about-<subcommand>` for the subcommand itself, with `_` in names as `-`:
+# `help-print-size`, `help-serve-rate-limit`, `about-batch`.
+
+## Prefixes of diagnostics on stderr. JSON log lines are not translated.
+
+# Before the message of the error that ends the run.
+error-prefix = Error
+# Before a warning.
+warning-prefix = warning
+# Before anything about one batch row; $row counts from 1.
+row-prefix = row { $row }
+
+## Progress
+
+# $path is the file written.
+wrote = Wrote { $path }
+# After a batch; $dry_run is "yes" with --dry-run, when nothing was written.
+batch-summary = { $dry_run ->
+        [yes] Would write
+       *[no] Wrote
+    } { $written } of { $total } files ({ $skipped } up to date, skipped)
+# Animated output; $codes is how many codes it cycles through, each for $seconds.
+wrote-animation = Wrote { $path } ({ $codes } codes, { $seconds } s each)
+
+## Usage and validation errors. Option names stay as typed on the command line.
+
+# The payload subcommand or mode that has no single QR payload, as named in
+# `qrbrand --help`.
+no-payload = { $command ->
+        [barcode] a barcode has no QR payload
+        [batch] batch mode has no single payload
+        [diff] diff reads codes; it has no payload
+        [verify] verify reads codes; it has no payload
+        [series] a series has no single payload
+        [daemon] daemon mode has no single payload
+        [serve] serve mode has no single payload
+        [bench] bench mode has no single payload
+        [animate] an animation has several payloads
+        [run] a job file has no single payload
+        [completions] completions have no payload
+        [wizard] the wizard has no single payload
+       *[exit-codes] exit-codes has no payload
+    }
+url-required = --url is required
+url-required-clipboard = --url is required (or --url-from-clipboard)
+url-invalid = Invalid URL: { $url } (did you include https:// ?)
+# $start is the first 60 characters on the clipboard.
+clipboard-not-url = The clipboard does not hold a URL: { $start }
+job-invalid = Invalid job file { $path }
+theme-failed = Failed to apply theme '{ $theme }'
+alt-text-template-invalid = Invalid --alt-text template
+several-outs-single = only single codes take more than one --out
+lang-files-single = --lang-layout files is for single codes
+format-several-outs = --format picks one format; with several --out each is named by its extension
+# $ext is the extension of the --out path, lower-cased.
+out-extension = cannot write { $path }: .{ $ext } is not an output format (png, jpg, gif, ico, eps, tif, svg or pdf)
+favicon-needs-ico = --favicon-png goes with --format ico
+favicon-zero = --favicon-png sizes must be at least 1
+print-size-format = --print-size goes with --format eps, tiff or pdf, or --print
+# $flag is --bleed or --crop-marks.
+needs-physical = { $flag } goes with --format eps, tiff or pdf
+colorspace-needs-tiff = --colorspace goes with --format tiff
+icc-format = --icc goes with --format png or tiff
+# $flag is --dark-ink or --light-ink.
+needs-cmyk = { $flag } goes with --colorspace cmyk
+eps-unsupported = EPS output holds the module grid only; { $flag } cannot be drawn in it
+svg-unsupported = SVG output holds the module grid only; { $flag } cannot be drawn in it
+label-unsupported = a label lays out its own text beside the code; { $flag } cannot be combined with it
+stream-one-out = --stream writes one PNG; give --out once
+# $size, $distance and $needed are lengths such as "2.5 cm" or "1 m".
+print-too-small = a { $size } print is too small to scan from { $distance }; print it at least { $needed } wide
+size-too-small = Requested size { $size } too small for total modules { $modules } (ppm={ $ppm }). Increase --size.
+label-no-room = no room for the label in a { $quiet } module quiet zone at this size; raise --quiet or --size
+place-lower-third = --place goes with a --template image; lower-third places the code itself
+place-needs-template = --place needs a --template image
+template-needs-place = --template needs --place
+invert-quiet = --invert needs a quiet zone of at least { $modules } modules
+fill-invert = --fill-texture cannot be combined with --invert
+outline-no-decode = outlined modules leave a code that does not decode; drop --module-gap or the logo, or fill the modules
+gap-no-decode = --module-gap { $gap } leaves a code that does not decode; use a smaller gap or logo
+eye-glyph-no-decode = --eye-glyph leaves finder patterns scanners cannot find; use a simpler glyph or a darker --eye-glyph-color
+corner-radius-too-big = --corner-radius { $radius } would cut into the finder patterns; use at most { $max } px at this size and quiet zone
+auto-color-needs-image = --auto-color takes the color from the logo; add --image
+ecc-margin-range = --ecc-margin must be at least 0 and below 1
+logo-scale-range = --logo-scale should be between ~0.05 and 0.35 for scan reliability
+# $coverage says how many codewords the logo hides, in English.
+logo-too-big = the logo does not fit the error correction budget even at --logo-scale { $scale } ({ $coverage }); raise --ec-level or lower --logo-pad
+
+## --size
+
+size-needs-dpi = give a resolution for '{ $size }', e.g. { $size }@300dpi
+size-expected = expected pixels (1024, 2048px) or a length at a resolution (50mm@300dpi), got '{ $size }'
+size-expected-dpi = expected a resolution such as 300dpi, got '{ $dpi }'
+size-under-pixel = '{ $size }' is less than a pixel
+
+## --symbology pdf417
+
+pdf417-single = --symbology pdf417 is for single codes
+pdf417-one-out = --symbology pdf417 writes one --out
+pdf417-unsupported = { $flag } does not apply to --symbology pdf417
+pdf417-format = --symbology pdf417 writes png (or jpg or gif) or pdf
+pdf417-print-size = --print-size goes with a .pdf --out
+pdf417-size-too-small = --size { $size } is too small for the { $modules } modules across the PDF417 symbol
+pdf417-too-long = Payload is too long for PDF417 ({ $codewords } data codewords, at most { $max })
+
+## Payload subcommands
+
+param-expected = expected key=value, got '{ $param }'
+phone-invalid = Invalid phone number: { $number }
+email-invalid = Invalid email address: { $address }
+totp-colon = TOTP issuer and account must not contain ':'
+totp-account-empty = TOTP account must not be empty
+totp-digits = --digits must be 6 or 8 (got { $digits })
+totp-period = --period must be greater than zero
+totp-secret-empty = TOTP secret must not be empty
+totp-secret-base32 = TOTP secret is not valid base32 (unexpected '{ $char }')
+# $flag is the option, $min and $max the allowed length and $count the length given.
+length-out-of-range = { $flag } must be { $min }..={ $max } characters (got { $count })
+epc-purpose = --purpose must be a 4-character code (got { $purpose })
+epc-line-break = EPC fields must not contain line breaks
+epc-too-long = EPC payload is { $bytes } bytes; the standard allows at most { $max }
+epc-amount-invalid = Invalid --amount: { $amount } (expected e.g. 12.50)
+epc-amount-too-small = --amount must be at least 0.01
+iban-invalid = Invalid IBAN: { $iban }
+iban-country = Invalid IBAN country code: { $country }
+iban-checksum = Invalid IBAN checksum: { $iban }
+bic-invalid = Invalid BIC (expected 8 or 11 letters/digits): { $bic }
+event-title-empty = --title must not be empty
+event-tz-invalid = Invalid --tz: { $tz }
+event-start-invalid = Invalid --start
+event-end-invalid = Invalid --end
+event-time-invalid = Invalid event time: { $time } (expected YYYY-MM-DD, YYYY-MM-DDTHH:MM or RFC 3339)
+event-mixed-forms = --start and --end must use the same form (date, local time, or UTC/offset)
+event-end-before-start = --end must be after --start
+geo-lat = --lat must be between -90 and 90 (got { $lat })
+geo-lon = --lon must be between -180 and 180 (got { $lon })
+gs1-required = --element-string or --digital-link is required
+gs1-expected-paren = expected '(' before an AI in GS1 element string: { $rest }
+gs1-unclosed-paren = unclosed '(' in GS1 element string
+gs1-empty = GS1 element string is empty
+gs1-ai-invalid = invalid GS1 AI ({ $ai }): expected 2 to 4 digits
+gs1-ai-length = GS1 AI ({ $ai }) must have { $digits } digits
+gs1-ai-no-value = GS1 AI ({ $ai }) has no value
+# $char is quoted, e.g. 'é'.
+gs1-ai-character = GS1 AI ({ $ai }) contains { $char }, which GS1 does not allow
+gs1-ai-fixed = GS1 AI ({ $ai }) needs exactly { $digits } digits, got '{ $value }'
+gs1-ai-check-digit = GS1 AI ({ $ai }) has a wrong check digit: { $value }
+digital-link-invalid = Invalid Digital Link URI: { $uri }
+digital-link-scheme = Digital Link URI must use https:// (got { $scheme })
+digital-link-gtin = Digital Link ({ $ai }) is not a valid GTIN: { $gtin }
+digital-link-sscc = Digital Link ({ $ai }) is not a valid SSCC: { $sscc }
+digital-link-no-key = Digital Link URI has no GS1 primary key (e.g. /01/<GTIN>): { $uri }
+# $unit is ETH or BTC.
+amount-invalid-decimals = Invalid --amount: { $amount } (expected { $unit } with at most { $decimals } decimals)
+amount-not-positive = --amount must be greater than zero
+bitcoin-invalid = Invalid Bitcoin address ({ $reason ->
+        [not-base58] not Base58
+        [wrong-length] wrong length
+        [checksum] checksum mismatch
+        [mixed-case] mixed case
+        [no-separator] no separator
+        [bad-character] bad character
+        [too-short] too short
+       *[witness-program] bad witness program
+    }): { $address }
+# $version is the first byte in hex, e.g. 0x1e.
+bitcoin-version = Not a Bitcoin address (version byte { $version }): { $address }
+ethereum-invalid = Invalid Ethereum address (expected 0x and 40 hex digits): { $address }
+ethereum-checksum = Invalid Ethereum address (EIP-55 checksum mismatch): { $address }
+# A warning; the code is still written.
+ethereum-no-checksum = Ethereum address { $address } has no EIP-55 checksum; double-check it before printing
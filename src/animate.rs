@@ -0,0 +1,271 @@
+//! `qrbrand animate`: one GIF or APNG that cycles through several codes, for signage
+//! that rotates between, say, a URL per language.
+//!
+//! Every frame is drawn with the same options and, unless `--qr-version` is given, at
+//! the version the longest payload needs, so the modules keep their size and place
+//! from one frame to the next. Each frame passes the checks a single render does.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::raster::{self, Canvas};
+use crate::template::{self, TemplateVars};
+use crate::{
+    Args, Assets, RenderOptions, caption_text, compose, i18n, lang, log, meta, payload, policy,
+    query_params, render_payload, symbol,
+};
+use anyhow::{Context, Result, anyhow};
+use clap::Args as ClapArgs;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use qrcode::Version;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use url::Url;
+
+/// Longest a frame may stay up: APNG delays are hundredths of a second in 16 bits.
+const MAX_DWELL: f32 = 600.0;
+
+#[derive(ClapArgs, Debug)]
+pub struct AnimateArgs {
+    /// The URLs to cycle through, in order
+    #[arg(required = true, num_args = 2..)]
+    pub urls: Vec<String>,
+
+    /// Seconds each code stays up
+    #[arg(long = "dwell", value_name = "SECONDS", default_value_t = 3.0)]
+    pub dwell: f32,
+
+    /// Write each frame's place in the cycle ("2/3") in the corner of its quiet zone
+    #[arg(long = "index-label")]
+    pub index_label: bool,
+}
+
+/// The first flag given that has no meaning for an animation.
+fn unsupported(args: &Args) -> Option<&'static str> {
+    [
+        (args.format.is_some(), "--format"),
+        (args.display_url.is_some(), "--display-url"),
+        (args.print_size.is_some(), "--print-size"),
+        (args.bleed.is_some(), "--bleed"),
+        (args.crop_marks, "--crop-marks"),
+        (args.guides, "--guides"),
+        (!args.favicon_png.is_empty(), "--favicon-png"),
+        (args.stream, "--stream"),
+        (args.stress_test, "--stress-test"),
+        (args.meta_out.is_some(), "--meta-out"),
+        (args.terminal, "--terminal"),
+        (args.clipboard, "--clipboard"),
+        (args.preview, "--preview"),
+    ]
+    .into_iter()
+    .find_map(|(given, flag)| given.then_some(flag))
+}
+
+pub fn run(args: &Args, animate: &AnimateArgs, out_given: bool) -> Result<()> {
+    if let Some(flag) = unsupported(args) {
+        return Err(anyhow!("{} does not apply to qrbrand animate", flag)).code(ErrorCode::Usage);
+    }
+    if !(animate.dwell >= 0.01 && animate.dwell <= MAX_DWELL) {
+        return Err(anyhow!(
+            "--dwell must be between 0.01 and {} seconds",
+            MAX_DWELL
+        ))
+        .code(ErrorCode::Usage);
+    }
+    let payloads = animate
+        .urls
+        .iter()
+        .map(|url| {
+            let mut parsed = Url::parse(url)
+                .with_context(|| format!("Invalid URL: {} (did you include https:// ?)", url))?;
+            payload::link::set_query_params(&mut parsed, &query_params(args));
+            let payload = parsed.to_string();
+            for warning in policy::check(&payload, args.require_https)? {
+                log::warn(warning);
+            }
+            Ok(payload)
+        })
+        .collect::<Result<Vec<_>>>()
+        .code(ErrorCode::InvalidPayload)?;
+
+    let mut opts = RenderOptions::from_args(args);
+    if opts.encode.version.is_none() {
+        let mut largest = 1;
+        for payload in &payloads {
+            if let Version::Normal(v) = symbol::encode(payload, &opts.encode)?.version() {
+                largest = largest.max(v);
+            }
+        }
+        opts.encode.version = Some(largest);
+    }
+    let assets = Assets::load(
+        args.image.as_deref(),
+        args.font_family.as_deref(),
+        args.template.as_ref().and_then(compose::Template::image),
+    )?;
+
+    let date = template::today();
+    let mut frames = Vec::with_capacity(payloads.len());
+    for (i, payload) in payloads.iter().enumerate() {
+        let hash = meta::sha256_hex(payload.as_bytes());
+        let vars = TemplateVars {
+            row: None,
+            payload,
+            payload_hash: &hash,
+            index: i + 1,
+            date: &date,
+            serial: None,
+        };
+        let alt_text = lang::stacked(&args.alt_text)
+            .map(|t| template::render(&t, &vars, false))
+            .transpose()
+            .context("Invalid --alt-text template")?;
+        let caption = caption_text(args.show_url, alt_text.as_deref(), payload);
+        let mut rendered = render_payload(payload, caption, &opts, &assets)
+            .with_context(|| format!("Failed to render frame {} ({})", i + 1, payload))?;
+        if animate.index_label {
+            let label = format!("{}/{}", i + 1, payloads.len());
+            rendered.draw_label(&label, &opts, &assets.font)?;
+        }
+        for warning in rendered.contrast(opts.quiet).check(opts.strict_contrast)? {
+            log::warn(format!("frame {}: {}", i + 1, warning));
+        }
+        rendered.orient(opts.orientation);
+        frames.push(rendered.image);
+    }
+    let frames = same_size(frames);
+
+    let out = if out_given {
+        args.out.as_str()
+    } else {
+        "animation.gif"
+    };
+    let delay_ms = (animate.dwell * 1000.0).round() as u32;
+    let gif = Path::new(out)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    match gif {
+        true => save_gif(&frames, delay_ms, out)?,
+        false => save_apng(&frames, delay_ms, out)?,
+    }
+    log::info(i18n::tr(
+        "wrote-animation",
+        &[
+            ("path", out.into()),
+            ("codes", frames.len().into()),
+            ("seconds", animate.dwell.into()),
+        ],
+    ));
+    Ok(())
+}
+
+/// Center every frame on a transparent canvas the size of the largest, should a
+/// caption or decoration have made one bigger.
+fn same_size(frames: Vec<Canvas>) -> Vec<Canvas> {
+    let width = frames.iter().map(Canvas::width).max().unwrap_or(0);
+    let height = frames.iter().map(Canvas::height).max().unwrap_or(0);
+    frames
+        .into_iter()
+        .map(|frame| {
+            if frame.dimensions() == (width, height) {
+                return frame;
+            }
+            let mut padded = Canvas::new(width, height);
+            let x = (width - frame.width()) / 2;
+            let y = (height - frame.height()) / 2;
+            raster::replace(&mut padded, &frame, x, y);
+            padded
+        })
+        .collect()
+}
+
+/// A looping GIF. Colors are cut to a 256-entry palette per frame, which the flat
+/// colors of a code survive; soft shadows and photos band.
+fn save_gif(frames: &[Canvas], delay_ms: u32, out: &str) -> Result<()> {
+    let file = File::create(out).with_context(|| format!("Failed to create {}", out))?;
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .with_context(|| format!("Failed to write {}", out))?;
+    for frame in frames {
+        let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+        encoder
+            .encode_frame(Frame::from_parts(frame.clone(), 0, 0, delay))
+            .with_context(|| format!("Failed to write {}", out))?;
+    }
+    Ok(())
+}
+
+/// A looping animated PNG, lossless, tagged sRGB like a single PNG.
+fn save_apng(frames: &[Canvas], delay_ms: u32, out: &str) -> Result<()> {
+    let file = File::create(out).with_context(|| format!("Failed to create {}", out))?;
+    let (width, height) = frames[0].dimensions();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let failed = || format!("Failed to write {}", out);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .with_context(failed)?;
+    encoder
+        .set_frame_delay((delay_ms / 10) as u16, 100)
+        .with_context(failed)?;
+    let mut writer = encoder.write_header().with_context(failed)?;
+    for frame in frames {
+        writer
+            .write_image_data(frame.as_raw())
+            .with_context(failed)?;
+    }
+    writer.finish().with_context(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_frames_keep_their_version() {
+        let dir = std::env::temp_dir().join(format!("qrbrand-animate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("cycle.png");
+        let out = out.to_str().unwrap();
+        let args = Args::try_parse_from([
+            "qrbrand",
+            "--size",
+            "400",
+            "--out",
+            out,
+            "animate",
+            "https://example.com/en",
+            "https://example.com/a/much/longer/path/for/the/french/page",
+            "--dwell",
+            "2",
+            "--index-label",
+        ])
+        .unwrap();
+        let Some(crate::Command::Animate(animate)) = &args.command else {
+            panic!("expected animate command");
+        };
+        run(&args, animate, true).unwrap();
+
+        let decoder = png::Decoder::new(std::io::BufReader::new(File::open(out).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!(control.num_frames, 2);
+        assert_eq!(control.num_plays, 0);
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let mut versions = Vec::new();
+        for _ in 0..2 {
+            reader.next_frame(&mut buf).unwrap();
+            let frame = reader.info().frame_control.unwrap();
+            assert_eq!((frame.delay_num, frame.delay_den), (200, 100));
+            let (w, h) = (reader.info().width, reader.info().height);
+            let img = Canvas::from_raw(w, h, buf.clone()).unwrap();
+            versions.push(crate::decode::decode(&img).unwrap().version);
+        }
+        assert_eq!(versions[0], versions[1]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,350 @@
+//! `qrbrand barcode`: an EAN-13, UPC-A or EAN-8 retail barcode with its digits printed
+//! under the bars, as PNG, TIFF or EPS.
+//!
+//! The raster is `--size` pixels wide at most, a whole number of pixels per module.
+//! For print, `--print-size` is the width with the quiet zones; it defaults to 100%
+//! magnification (0.33 mm modules), and sizes outside the 80% to 200% GS1 allows are
+//! written with a warning.
+
+use crate::color::Color;
+use crate::ean::{self, Barcode, Kind};
+use crate::eps;
+use crate::error::{ErrorCode, WithCode};
+use crate::fonts::FontChain;
+use crate::pdf::MM;
+use crate::raster::{self, Canvas};
+use crate::{
+    Args, cache, draw_text_rgba, i18n, icc, log, measure_text_width, orient, output, preview, tif,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Args as ClapArgs;
+use rusttype::Scale;
+use std::fmt::Write as _;
+use std::path::Path;
+
+#[derive(ClapArgs, Debug)]
+pub struct BarcodeArgs {
+    /// Symbology
+    #[arg(long = "kind", value_enum, default_value_t = Kind::Ean13)]
+    pub kind: Kind,
+
+    /// The digits, with or without the check digit (added if left off, verified if not)
+    #[arg(long = "digits")]
+    pub digits: String,
+}
+
+/// The first flag given that belongs to QR rendering and has nothing to apply to here.
+fn unsupported(args: &Args) -> Option<&'static str> {
+    [
+        (args.image.is_some(), "--image"),
+        (args.show_url, "--show-url"),
+        (args.display_url.is_some(), "--display-url"),
+        (!args.alt_text.is_empty(), "--alt-text"),
+        (args.template.is_some(), "--template"),
+        (args.corner_radius > 0, "--corner-radius"),
+        (args.circle_crop, "--circle-crop"),
+        (args.shape == crate::round::Shape::Circle, "--shape circle"),
+        (args.border.is_some(), "--border"),
+        (args.shadow.is_some(), "--shadow"),
+        (args.module_gap > 0.0, "--module-gap"),
+        (args.module_style.is_hollow(), "--module-style"),
+        (args.fill_texture.is_some(), "--fill-texture"),
+        (args.invert, "--invert"),
+        (args.eye_color.is_some(), "--eye-color"),
+        (args.eye_inner_color.is_some(), "--eye-inner-color"),
+        (!args.eye_glyph.is_empty(), "--eye-glyph"),
+        (args.bleed.is_some(), "--bleed"),
+        (args.crop_marks, "--crop-marks"),
+        (args.guides, "--guides"),
+        (!args.favicon_png.is_empty(), "--favicon-png"),
+        (args.terminal, "--terminal"),
+        (args.clipboard, "--clipboard"),
+        (args.stress_test, "--stress-test"),
+        (args.meta_out.is_some(), "--meta-out"),
+    ]
+    .into_iter()
+    .find_map(|(given, flag)| given.then_some(flag))
+}
+
+pub fn run(args: &Args, barcode: &BarcodeArgs, out_given: bool) -> Result<()> {
+    if let Some(flag) = unsupported(args) {
+        return Err(anyhow!("{} does not apply to qrbrand barcode", flag)).code(ErrorCode::Usage);
+    }
+    let symbol = Barcode::new(barcode.kind, &barcode.digits).code(ErrorCode::InvalidPayload)?;
+    let format = match args.format {
+        Some(format) => format,
+        None if out_given => output::Format::of_path(Path::new(&args.out))?,
+        None => output::Format::Png,
+    };
+    let physical = matches!(format, output::Format::Eps | output::Format::Tiff);
+    if format == output::Format::Eps && (args.rotate.is_some() || args.flip.is_some()) {
+        return Err(anyhow!(
+            "--rotate and --flip apply to png and tiff barcodes"
+        ))
+        .code(ErrorCode::Usage);
+    }
+    if matches!(
+        format,
+        output::Format::Ico | output::Format::Svg | output::Format::Pdf
+    ) {
+        return Err(anyhow!("a barcode is written as png, tiff or eps")).code(ErrorCode::Usage);
+    }
+    if args.print_size.is_some() && !physical {
+        return Err(anyhow!("--print-size goes with --format eps or tiff")).code(ErrorCode::Usage);
+    }
+    if args.colorspace != icc::ColorSpace::Rgb && format != output::Format::Tiff {
+        return Err(anyhow!("--colorspace goes with --format tiff")).code(ErrorCode::Usage);
+    }
+
+    let nominal = symbol.width() as f64 * ean::NOMINAL_X_MM * MM;
+    let width = args.print_size.map_or(nominal, |length| length.0);
+    let magnification = width / nominal;
+    if physical && !(0.8..=2.0).contains(&magnification) {
+        log::warn(format!(
+            "{} at {:.0}% magnification; GS1 allows 80% to 200% ({:.1} to {:.1} mm wide)",
+            barcode.kind.name(),
+            magnification * 100.0,
+            0.8 * nominal / MM,
+            2.0 * nominal / MM
+        ));
+    }
+
+    let out = if out_given {
+        args.out.clone()
+    } else {
+        format!("barcode.{}", format.extension())
+    };
+    let out = output::claim_path(
+        Path::new(&out),
+        &symbol.digits,
+        args.no_clobber,
+        args.unique,
+        Path::exists,
+    )?;
+    let out = out.to_string_lossy();
+    let icc = args
+        .icc
+        .as_deref()
+        .map(|path| icc::load(path, args.colorspace))
+        .transpose()?;
+
+    if format == output::Format::Eps {
+        let ps = document(&symbol, width, args.fg, args.quiet_color, args.text_color);
+        std::fs::write(out.as_ref(), ps)
+            .with_context(|| format!("Failed to write EPS: {}", out))?;
+    } else {
+        let font = cache::font(args.font_family.as_deref())?;
+        let img = render(&symbol, args.size.px, args, &font)?;
+        if args.verbose {
+            log::info(format!(
+                "{} {}, {} modules of {} px",
+                barcode.kind.name(),
+                symbol.digits,
+                symbol.width(),
+                img.width() / symbol.width() as u32
+            ));
+        }
+        // The resolution follows the bars, whichever way the label turns them.
+        let dpi = img.width() as f64 / (width / 72.0);
+        let orientation = orient::Orientation {
+            rotate: args.rotate,
+            flip: args.flip,
+        };
+        let img = match orientation.is_set() {
+            true => orientation.apply(&img),
+            false => img,
+        };
+        if format == output::Format::Tiff {
+            let inks = tif::Inks {
+                dark: args.dark_ink,
+                light: args.light_ink,
+                fg: args.fg,
+                quiet: args.quiet_color,
+            };
+            // Colors given as RGB are sRGB; CMYK has no default profile.
+            let srgb = (args.colorspace == icc::ColorSpace::Rgb).then(icc::srgb);
+            tif::save(
+                &img,
+                &out,
+                args.colorspace,
+                &inks,
+                dpi,
+                icc.as_deref().or(srgb.as_deref()),
+            )?;
+        } else if let Some(raster) = output::other_raster(out.as_ref()) {
+            output::save_raster(&img, &out, raster, icc.as_deref(), None)?;
+        } else {
+            output::save_png_with_text(&img, &out, &[], icc.as_deref(), None)?;
+        }
+    }
+    log::info(i18n::tr("wrote", &[("path", out.as_ref().into())]));
+    if args.open
+        && let Err(e) = preview::open(&out)
+    {
+        log::warn(format!("{:#}", e));
+    }
+    Ok(())
+}
+
+/// The barcode at the largest whole number of pixels per module that fits in `size`:
+/// `--fg` bars on `--quiet-color`, and the digits in the caption font and `--text-color`.
+fn render(symbol: &Barcode, size: u32, args: &Args, font: &FontChain) -> Result<Canvas> {
+    let x_dim = size / symbol.width() as u32;
+    if x_dim == 0 {
+        bail!(
+            "--size {} is too small for the {} modules of the barcode",
+            size,
+            symbol.width()
+        );
+    }
+    let px = |modules: f64| (modules * x_dim as f64).round() as u32;
+    let mut img = Canvas::from_pixel(
+        x_dim * symbol.width() as u32,
+        px(symbol.height()),
+        args.quiet_color.0,
+    );
+    let bar_height = symbol.kind.bar_height();
+    let (short, long) = (px(bar_height), px(bar_height + ean::GUARD_EXTRA));
+    let (quiet, _) = symbol.kind.quiet();
+    for (i, _) in symbol.modules.iter().enumerate().filter(|(_, bar)| **bar) {
+        let x = (quiet + i) as u32 * x_dim;
+        let height = if symbol.long[i] { long } else { short };
+        for y in 0..height {
+            raster::fill_row(&mut img, y, x..x + x_dim, args.fg.0);
+        }
+    }
+
+    let baseline = (symbol.baseline() * x_dim as f64) as f32;
+    for (centre, digit, em) in symbol.text() {
+        let scale = Scale::uniform((em * x_dim as f64) as f32);
+        let digit = digit.to_string();
+        let left = (centre * x_dim as f64) as f32 - measure_text_width(font, scale, &digit) / 2.0;
+        draw_text_rgba(
+            &mut img,
+            font,
+            scale,
+            left,
+            baseline,
+            &digit,
+            args.text_color.0,
+        );
+    }
+    Ok(img)
+}
+
+/// The EPS document for `symbol`, `width` points wide with its quiet zones, with the
+/// bars as filled rectangles and the digits set in Helvetica.
+fn document(symbol: &Barcode, width: f64, fg: Color, quiet_color: Color, text: Color) -> String {
+    let module = width / symbol.width() as f64;
+    let height = symbol.height();
+    let (quiet, _) = symbol.kind.quiet();
+    let title = format!("{} {}", symbol.kind.name(), symbol.digits);
+
+    let mut ps = String::new();
+    let _ = writeln!(ps, "%!PS-Adobe-3.0 EPSF-3.0");
+    let _ = writeln!(
+        ps,
+        "%%BoundingBox: 0 0 {} {}",
+        width.ceil() as u64,
+        (height * module).ceil() as u64
+    );
+    let _ = writeln!(
+        ps,
+        "%%HiResBoundingBox: 0 0 {:.4} {:.4}",
+        width,
+        height * module
+    );
+    let _ = writeln!(ps, "%%Title: {}", title);
+    let _ = writeln!(ps, "%%Creator: qrbrand {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(ps, "%%DocumentNeededResources: font Helvetica");
+    let _ = writeln!(ps, "%%Pages: 1");
+    let _ = writeln!(ps, "%%EndComments");
+    let _ = writeln!(
+        ps,
+        "% {}, {} modules of {:.4} pt",
+        title,
+        symbol.width(),
+        module
+    );
+    let _ = writeln!(ps, "gsave");
+    let _ = writeln!(ps, "{0:.6} {0:.6} scale", module);
+    let _ = writeln!(ps, "/R {{ rectfill }} bind def");
+    let _ = writeln!(ps, "{} setrgbcolor", eps::rgb(quiet_color));
+    let _ = writeln!(ps, "0 0 {} {:.4} R", symbol.width(), height);
+    let _ = writeln!(ps, "{} setrgbcolor", eps::rgb(fg));
+    // PostScript counts up from the bottom; the bars hang from the top edge.
+    let bar_height = symbol.kind.bar_height();
+    let mut i = 0;
+    while i < symbol.modules.len() {
+        let run = symbol.modules[i..]
+            .iter()
+            .zip(&symbol.long[i..])
+            .take_while(|&(&bar, &long)| bar == symbol.modules[i] && long == symbol.long[i])
+            .count();
+        if symbol.modules[i] {
+            let h = bar_height
+                + if symbol.long[i] {
+                    ean::GUARD_EXTRA
+                } else {
+                    0.0
+                };
+            let _ = writeln!(ps, "{} {:.4} {} {:.4} R", quiet + i, height - h, run, h);
+        }
+        i += run;
+    }
+    let _ = writeln!(ps, "{} setrgbcolor", eps::rgb(text));
+    let baseline = height - symbol.baseline();
+    for (centre, digit, em) in symbol.text() {
+        let _ = writeln!(
+            ps,
+            "/Helvetica findfont {} scalefont setfont ({}) dup stringwidth pop 2 div neg {} add {:.4} moveto show",
+            em, digit, centre, baseline
+        );
+    }
+    let _ = writeln!(ps, "grestore");
+    let _ = writeln!(ps, "showpage");
+    let _ = writeln!(ps, "%%EOF");
+    ps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_barcode() {
+        let args = Args::try_parse_from([
+            "qrbrand",
+            "--size",
+            "300",
+            "barcode",
+            "--digits",
+            "400638133393",
+        ])
+        .unwrap();
+        let Some(crate::Command::Barcode(barcode)) = &args.command else {
+            panic!("expected barcode command");
+        };
+        let symbol = Barcode::new(barcode.kind, &barcode.digits).unwrap();
+        let font = cache::font(None).unwrap();
+        let img = render(&symbol, args.size.px, &args, &font).unwrap();
+
+        // Two pixels per module; the start guard begins after the 11-module quiet zone
+        // and runs 5 modules past the data bars, which the first digit bar does not.
+        assert_eq!(img.width(), 2 * 113);
+        assert_eq!(*img.get_pixel(22, 0), Color::BLACK.0);
+        assert_eq!(*img.get_pixel(21, 0), Color::WHITE.0);
+        assert_eq!(*img.get_pixel(22, 2 * 72), Color::BLACK.0);
+        // 0 in L: the first bar is the fourth module of the digit.
+        let digit = 2 * (11 + 3 + 3);
+        assert_eq!(*img.get_pixel(digit, 2 * 68), Color::BLACK.0);
+        assert_eq!(*img.get_pixel(digit, 2 * 70), Color::WHITE.0);
+        assert!(render(&symbol, 100, &args, &font).is_err());
+
+        let ps = document(&symbol, 113.0, Color::BLACK, Color::WHITE, Color::BLACK);
+        assert!(ps.contains("%%Title: EAN-13 4006381333931"));
+        assert!(ps.contains("11 4.8000 1 74.0000 R"));
+        assert_eq!(ps.matches(") dup stringwidth").count(), 13);
+    }
+}
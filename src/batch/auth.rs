@@ -0,0 +1,149 @@
+//! Who may call `GET` and `POST /render` in `qrbrand serve`.
+//!
+//! A request gets through with one of the `--api-key` values in `X-API-Key` or
+//! `Authorization: Bearer`, or, for `GET`, with a URL signed with `--signing-key`. A
+//! signed URL ends in `expires` (Unix seconds) and `signature`, the hex HMAC-SHA256
+//! of the path and query up to `&signature=`, so a page can carry
+//! `<img src="/render?url=...&expires=...&signature=...">` without handing out a key
+//! that renders anything.
+
+use crate::meta::hmac_sha256;
+
+#[derive(Default)]
+pub(super) struct Auth {
+    pub(super) api_keys: Vec<String>,
+    pub(super) signing_key: Option<String>,
+}
+
+/// Why a request was turned away.
+#[derive(Debug, PartialEq)]
+pub(super) enum Denied {
+    /// No credentials at all: 401.
+    Missing,
+    /// Credentials that do not hold: 403.
+    Invalid(&'static str),
+}
+
+impl Auth {
+    /// Whether any credentials are required.
+    pub(super) fn required(&self) -> bool {
+        !self.api_keys.is_empty() || self.signing_key.is_some()
+    }
+
+    /// Check a request for `target` (path and query) carrying `api_key`, at `now`
+    /// in Unix seconds. Only `GET` may use a signed URL.
+    pub(super) fn check(
+        &self,
+        get: bool,
+        target: &str,
+        api_key: Option<&str>,
+        now: u64,
+    ) -> Result<(), Denied> {
+        if !self.required() {
+            return Ok(());
+        }
+        if let Some(key) = api_key {
+            return match self
+                .api_keys
+                .iter()
+                .any(|k| same(k.as_bytes(), key.as_bytes()))
+            {
+                true => Ok(()),
+                false => Err(Denied::Invalid("unknown API key")),
+            };
+        }
+        let Some((signed, signature)) = target.rsplit_once("&signature=") else {
+            return Err(Denied::Missing);
+        };
+        let Some(secret) = &self.signing_key else {
+            return Err(Denied::Invalid("signed URLs are not accepted here"));
+        };
+        if !get {
+            return Err(Denied::Invalid("signed URLs are for GET requests"));
+        }
+        let expires = signed
+            .split_once('?')
+            .map_or("", |(_, query)| query)
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("expires="))
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or(Denied::Invalid("signed URL has no expires"))?;
+        if !same(
+            signature.as_bytes(),
+            hex_signature(secret, signed).as_bytes(),
+        ) {
+            return Err(Denied::Invalid("bad signature"));
+        }
+        if expires <= now {
+            return Err(Denied::Invalid("signed URL has expired"));
+        }
+        Ok(())
+    }
+}
+
+/// `target` with `expires` and the signature appended, valid until `expires`.
+pub(super) fn sign(secret: &str, target: &str, expires: u64) -> String {
+    let separator = if target.contains('?') { '&' } else { '?' };
+    let signed = format!("{}{}expires={}", target, separator, expires);
+    let signature = hex_signature(secret, &signed);
+    format!("{}&signature={}", signed, signature)
+}
+
+fn hex_signature(secret: &str, signed: &str) -> String {
+    hmac_sha256(secret.as_bytes(), signed.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Compare without stopping at the first difference, so timing does not leak how
+/// much of a guess was right.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let open = Auth::default();
+        assert_eq!(open.check(true, "/render?url=x", None, 0), Ok(()));
+
+        let auth = Auth {
+            api_keys: vec!["k1".to_string(), "k2".to_string()],
+            signing_key: Some("secret".to_string()),
+        };
+        let target = "/render?url=https%3A%2F%2Fexample.com&size=400";
+        assert_eq!(auth.check(false, target, Some("k2"), 0), Ok(()));
+        assert_eq!(
+            auth.check(true, target, Some("k3"), 0),
+            Err(Denied::Invalid("unknown API key"))
+        );
+        assert_eq!(auth.check(true, target, None, 0), Err(Denied::Missing));
+
+        let signed = sign("secret", target, 1_000);
+        assert!(signed.starts_with(&format!("{}&expires=1000&signature=", target)));
+        assert_eq!(auth.check(true, &signed, None, 999), Ok(()));
+        assert_eq!(
+            auth.check(true, &signed, None, 1_000),
+            Err(Denied::Invalid("signed URL has expired"))
+        );
+        assert_eq!(
+            auth.check(false, &signed, None, 999),
+            Err(Denied::Invalid("signed URLs are for GET requests"))
+        );
+        let tampered = signed.replace("size=400", "size=4000");
+        assert_eq!(
+            auth.check(true, &tampered, None, 999),
+            Err(Denied::Invalid("bad signature"))
+        );
+        let extended = signed.replace("expires=1000", "expires=9999");
+        assert_eq!(
+            auth.check(true, &extended, None, 999),
+            Err(Denied::Invalid("bad signature"))
+        );
+        assert!(sign("secret", "/render", 5).starts_with("/render?expires=5&signature="));
+    }
+}
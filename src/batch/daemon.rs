@@ -0,0 +1,335 @@
+//! `qrbrand daemon`: a long-running renderer for editor plugins and local apps.
+//!
+//! Speaks JSON-RPC 2.0, one request per line, on stdin/stdout or on every
+//! connection to a Unix socket (`--socket`), answering each request with one line.
+//! `render` takes a batch record as its params: `url`, `alt_text` and the render
+//! options named like their flags, flat or under `style`. With `out` the PNG is
+//! written under `--out-dir` and its path returned; without it the PNG comes back
+//! base64-encoded. With `--upload` a written PNG is also put in object storage and
+//! its `public_url` returned; with `--webhook` it is announced there too. The font
+//! and template are loaded once at startup and each logo the first time a request
+//! uses it (again only if the file changes), so requests skip the process start-up
+//! costs.
+
+use super::input::{self, field};
+use super::{BatchArgs, plan_record, reject_single_code_flags, render_job, render_png};
+use crate::error::{ErrorCode, WithCode};
+use crate::fonts::FontChain;
+use crate::meta::sha256_hex;
+use crate::output;
+use crate::raster::Canvas;
+use crate::{Args, Assets, cache, compose, template, webhook};
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use clap::Args as ClapArgs;
+use image::DynamicImage;
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(ClapArgs, Debug)]
+pub struct DaemonArgs {
+    /// Listen on this Unix socket instead of reading stdin
+    #[arg(long = "socket")]
+    pub socket: Option<PathBuf>,
+
+    /// Directory that `out` paths in requests are relative to
+    #[arg(long = "out-dir", default_value = ".")]
+    pub out_dir: String,
+}
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+/// A render that failed: bad options, unreadable logo, refused contrast and the like.
+const RENDER_FAILED: i64 = -32000;
+
+pub(super) struct Server<'a> {
+    pub(super) args: &'a Args,
+    /// Where `out` paths go, in the form [`plan_record`] expects.
+    pub(super) batch: BatchArgs,
+    font: Arc<FontChain>,
+    template: Option<Arc<Canvas>>,
+    /// Requests seen so far, for `{index}` in captions.
+    pub(super) requests: AtomicUsize,
+}
+
+pub fn run(args: &Args, daemon: &DaemonArgs) -> Result<()> {
+    reject_single_code_flags(args, "the daemon").code(ErrorCode::Usage)?;
+    let server = Server::new(args, "daemon", &daemon.out_dir)?;
+
+    match &daemon.socket {
+        Some(path) => listen(&server, path),
+        None => server.serve(std::io::stdin().lock(), std::io::stdout().lock()),
+    }
+}
+
+#[cfg(unix)]
+fn listen(server: &Server, path: &std::path::Path) -> Result<()> {
+    use crate::log;
+    use std::io::{BufReader, BufWriter};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("{} is in use by another daemon", path.display());
+        }
+        // Left behind by a daemon that did not shut down cleanly.
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket: {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    log::info(format!("Listening on {}", path.display()));
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    scope.spawn(move || {
+                        let reader = BufReader::new(&stream);
+                        if let Err(e) = server.serve(reader, BufWriter::new(&stream)) {
+                            log::warn(format!("connection closed: {:#}", e));
+                        }
+                    });
+                }
+                Err(e) => log::warn(format!("failed to accept a connection: {}", e)),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_server: &Server, _path: &std::path::Path) -> Result<()> {
+    bail!("--socket needs a Unix platform; use stdin/stdout instead")
+}
+
+impl<'a> Server<'a> {
+    /// Load the font and template once for every request of `mode`; `out` paths in
+    /// requests go under `out_dir`.
+    pub(super) fn new(args: &'a Args, mode: &str, out_dir: &str) -> Result<Self> {
+        Ok(Self {
+            args,
+            batch: BatchArgs {
+                input: mode.to_string(),
+                out_dir: out_dir.to_string(),
+                out_template: None,
+                manifest: None,
+                skip_existing: false,
+                force: false,
+                jobs: None,
+            },
+            font: cache::font(args.font_family.as_deref())?,
+            template: args
+                .template
+                .as_ref()
+                .and_then(compose::Template::image)
+                .map(compose::load_template)
+                .transpose()?
+                .map(Arc::new),
+            requests: AtomicUsize::new(0),
+        })
+    }
+
+    /// Answer requests line by line until the input ends.
+    fn serve(&self, input: impl BufRead, mut output: impl Write) -> Result<()> {
+        for line in input.lines() {
+            let line = line.context("Failed to read request")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                writeln!(output, "{}", response).context("Failed to write response")?;
+                output.flush().context("Failed to write response")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to one request line; None for notifications (requests without an id).
+    fn handle(&self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(error(
+                    Value::Null,
+                    PARSE_ERROR,
+                    format!("invalid JSON: {}", e),
+                ));
+            }
+        };
+        let id = request.get("id").cloned();
+        let reply = |result: Result<Value, (i64, String)>| {
+            let id = id.clone()?;
+            Some(match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => error(id, code, message),
+            })
+        };
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            let id = id.unwrap_or(Value::Null);
+            return Some(error(id, INVALID_REQUEST, "missing method".to_string()));
+        };
+        let params = request.get("params").cloned().unwrap_or(json!({}));
+        match method {
+            "ping" => reply(Ok(json!("pong"))),
+            "render" => reply(
+                self.render(params)
+                    .map_err(|e| (RENDER_FAILED, format!("{:#}", e))),
+            ),
+            other => reply(Err((
+                METHOD_NOT_FOUND,
+                format!("unknown method '{}' (expected render or ping)", other),
+            ))),
+        }
+    }
+
+    fn render(&self, params: Value) -> Result<Value> {
+        let record = input::json_record(params)?;
+        let index = self.requests.fetch_add(1, Ordering::Relaxed);
+        let mut job = plan_record(self.args, &self.batch, &template::today(), index, &record)?;
+        let assets = self.assets(&job.logo)?;
+
+        if field(&record, "out").is_some() {
+            job.out = output::claim_path(
+                &job.out,
+                &sha256_hex(job.payload.as_bytes())[..8],
+                self.args.no_clobber,
+                self.args.unique,
+                Path::exists,
+            )?;
+            let out = render_job(&assets, &job)?;
+            let mut response = json!({
+                "output": job.out,
+                "meta": out.meta,
+                "verification": out.check,
+                "warnings": out.warnings,
+            });
+            if let Some(target) = &self.args.upload {
+                let name = job
+                    .out
+                    .strip_prefix(&self.batch.out_dir)
+                    .unwrap_or(&job.out);
+                response["public_url"] = json!(target.put_file(&job.out, &name.to_string_lossy())?);
+            }
+            if let Some(hook) = &self.args.webhook {
+                let url = response["public_url"].as_str();
+                let body = webhook::body(&job.out, &job.payload, &out.meta, url);
+                if let Err(e) = body.and_then(|body| webhook::send(hook, &body))
+                    && let Some(warnings) = response["warnings"].as_array_mut()
+                {
+                    warnings.push(json!(format!("{:#}", e)));
+                }
+            }
+            return Ok(response);
+        }
+        let (png, out) = render_png(&assets, &job)?;
+        Ok(json!({
+            "png_base64": STANDARD.encode(png),
+            "meta": out.meta,
+            "verification": out.check,
+            "warnings": out.warnings,
+        }))
+    }
+
+    /// The shared font and template with `logo`, decoded once per version of the file.
+    fn assets(&self, logo: &Option<String>) -> Result<Assets> {
+        let logo = logo.as_deref().map(cache::logo).transpose()?;
+        Ok(self.assets_with(logo))
+    }
+
+    /// The shared font and template with an already decoded `logo`.
+    pub(super) fn assets_with(&self, logo: Option<Arc<DynamicImage>>) -> Assets {
+        Assets {
+            font: Arc::clone(&self.font),
+            logo,
+            template: self.template.clone(),
+        }
+    }
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn server(args: &Args) -> Server<'_> {
+        Server {
+            args,
+            batch: BatchArgs {
+                input: "daemon".to_string(),
+                out_dir: std::env::temp_dir().to_string_lossy().into_owned(),
+                out_template: None,
+                manifest: None,
+                skip_existing: false,
+                force: false,
+                jobs: None,
+            },
+            font: cache::font(None).unwrap(),
+            template: None,
+            requests: AtomicUsize::new(0),
+        }
+    }
+
+    #[test]
+    fn test_serve() {
+        let args = Args::try_parse_from(["qrbrand", "--url", "https://example.com"]).unwrap();
+        let server = server(&args);
+        let requests = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"render","params":{"url":"https://example.com","style":{"size":300}}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"render","params":{"url":"https://example.com","out":"qrbrand_daemon.png"}}"#,
+            r#"{"jsonrpc":"2.0","method":"ping"}"#,
+            r#"{"jsonrpc":"2.0","id":"x","method":"render","params":{"url":"not a url"}}"#,
+            r#"{"jsonrpc":"2.0","id":4,"method":"resize"}"#,
+            "{oops",
+            r#"{"jsonrpc":"2.0","id":6,"method":"render","params":{"url":"https://example.com","out":"/tmp/qrbrand_escaped.png"}}"#,
+            r#"{"jsonrpc":"2.0","id":7,"method":"render","params":{"url":"https://example.com","out":"../qrbrand_escaped.png"}}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        server.serve(requests.as_bytes(), &mut output).unwrap();
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        // The notification gets no response.
+        assert_eq!(responses.len(), 7);
+
+        let png = STANDARD
+            .decode(responses[0]["result"]["png_base64"].as_str().unwrap())
+            .unwrap();
+        let img = image::load_from_memory(&png).unwrap();
+        assert_eq!(img.width(), responses[0]["result"]["meta"]["width"]);
+        assert!(img.width() <= 300);
+
+        let written = responses[1]["result"]["output"].as_str().unwrap();
+        assert!(written.ends_with("qrbrand_daemon.png"));
+        assert!(std::path::Path::new(written).exists());
+
+        assert_eq!(responses[2]["id"], "x");
+        assert_eq!(responses[2]["error"]["code"], RENDER_FAILED);
+        assert_eq!(responses[3]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[4]["error"]["code"], PARSE_ERROR);
+        assert_eq!(responses[4]["id"], Value::Null);
+
+        // `out` cannot leave --out-dir.
+        for escape in &responses[5..] {
+            assert_eq!(escape["error"]["code"], RENDER_FAILED);
+            assert!(
+                escape["error"]["message"]
+                    .as_str()
+                    .unwrap()
+                    .contains("inside")
+            );
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Batch job readers: CSV, JSON arrays and JSON Lines.
+//!
+//! Every format is normalized into a flat [`Record`]; nested JSON objects
+//! become dotted keys (`{"style": {"size": 800}}` -> `style.size`).
+
+#[cfg(feature = "cli")]
+use anyhow::Context;
+use anyhow::{Result, bail};
+use serde_json::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "cli")]
+use std::path::Path;
+
+/// One input row, keyed by column name.
+pub type Record = BTreeMap<String, String>;
+
+/// Non-empty value of a column.
+pub fn field<'a>(record: &'a Record, key: &str) -> Option<&'a str> {
+    record.get(key).map(|v| v.trim()).filter(|v| !v.is_empty())
+}
+
+/// Read records, choosing the format from the file extension
+/// (`.json`, `.jsonl`/`.ndjson`, anything else is CSV).
+#[cfg(feature = "cli")]
+pub fn read_records(path: &str) -> Result<Vec<Record>> {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match ext.as_deref() {
+        Some("json") => read_json(path),
+        Some("jsonl" | "ndjson") => read_jsonl(path),
+        _ => read_csv(path),
+    }
+}
+
+#[cfg(feature = "cli")]
+pub fn read_csv(path: &str) -> Result<Vec<Record>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::Headers)
+        .from_path(path)
+        .with_context(|| format!("Failed to open batch CSV: {}", path))?;
+
+    let headers = reader
+        .headers()
+        .with_context(|| format!("Failed to read CSV header: {}", path))?
+        .clone();
+
+    reader
+        .records()
+        .enumerate()
+        .map(|(i, row)| {
+            let row = row.with_context(|| format!("Failed to parse CSV row {}", i + 1))?;
+            Ok(headers
+                .iter()
+                .zip(row.iter())
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect())
+        })
+        .collect()
+}
+
+#[cfg(feature = "cli")]
+fn read_json(path: &str) -> Result<Vec<Record>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read batch JSON: {}", path))?;
+    let value: Value =
+        serde_json::from_str(&text).with_context(|| format!("Failed to parse JSON: {}", path))?;
+    let Value::Array(items) = value else {
+        bail!("Batch JSON must be an array of objects: {}", path);
+    };
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| json_record(item).with_context(|| format!("record {}", i + 1)))
+        .collect()
+}
+
+#[cfg(feature = "cli")]
+fn read_jsonl(path: &str) -> Result<Vec<Record>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read batch JSONL: {}", path))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let value: Value = serde_json::from_str(line)
+                .with_context(|| format!("Failed to parse JSONL line {}", i + 1))?;
+            json_record(value).with_context(|| format!("JSONL line {}", i + 1))
+        })
+        .collect()
+}
+
+pub(super) fn json_record(value: Value) -> Result<Record> {
+    if !value.is_object() {
+        bail!("each batch record must be a JSON object");
+    }
+    let mut record = Record::new();
+    flatten("", value, &mut record);
+    Ok(record)
+}
+
+fn flatten(prefix: &str, value: Value, record: &mut Record) {
+    let key = |k: &str| {
+        if prefix.is_empty() {
+            k.to_string()
+        } else {
+            format!("{}.{}", prefix, k)
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                flatten(&key(&k), v, record);
+            }
+        }
+        Value::Null => {}
+        Value::String(s) => {
+            record.insert(prefix.to_string(), s);
+        }
+        other => {
+            record.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_csv() {
+        let path = std::env::temp_dir().join("qrbrand_batch_test.csv");
+        std::fs::write(
+            &path,
+            "url, alt_text ,out\nhttps://a.example,Table 1,a.png\nhttps://b.example,,\n",
+        )
+        .unwrap();
+
+        let records = read_csv(path.to_str().unwrap()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(field(&records[0], "alt_text"), Some("Table 1"));
+        assert_eq!(field(&records[1], "alt_text"), None);
+        assert_eq!(field(&records[1], "url"), Some("https://b.example"));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_read_json_and_jsonl() {
+        let dir = std::env::temp_dir();
+        let json = dir.join("qrbrand_batch_test.json");
+        std::fs::write(
+            &json,
+            r#"[{"url": "https://a.example", "style": {"size": 800, "logo_plate": false}}]"#,
+        )
+        .unwrap();
+        let records = read_records(json.to_str().unwrap()).unwrap();
+        assert_eq!(field(&records[0], "url"), Some("https://a.example"));
+        assert_eq!(field(&records[0], "style.size"), Some("800"));
+        assert_eq!(field(&records[0], "style.logo_plate"), Some("false"));
+
+        let jsonl = dir.join("qrbrand_batch_test.jsonl");
+        std::fs::write(
+            &jsonl,
+            "{\"url\": \"https://a.example\"}\n\n{\"url\": \"https://b.example\", \"size\": 512}\n",
+        )
+        .unwrap();
+        let records = read_records(jsonl.to_str().unwrap()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(field(&records[1], "size"), Some("512"));
+
+        std::fs::write(&json, "[1, 2]").unwrap();
+        assert!(read_records(json.to_str().unwrap()).is_err());
+
+        let _ = std::fs::remove_file(json);
+        let _ = std::fs::remove_file(jsonl);
+    }
+}
@@ -0,0 +1,202 @@
+//! Counters behind `GET /metrics` in `qrbrand serve`, in the Prometheus text
+//! exposition format.
+//!
+//! Render latency is a histogram over the buckets below; the cache hit rate is
+//! `qrbrand_cache_hits_total` over hits plus `qrbrand_cache_misses_total`.
+
+use crate::cache;
+use crate::error::ErrorCode;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the render latency buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+pub(super) struct Metrics(Mutex<Counts>);
+
+#[derive(Default)]
+struct Counts {
+    /// Responses by endpoint and status.
+    responses: BTreeMap<(&'static str, u16), u64>,
+    /// Successful renders by format, cache hits included.
+    renders: BTreeMap<&'static str, u64>,
+    /// Failed renders by error code.
+    errors: BTreeMap<&'static str, u64>,
+    cache_hits: u64,
+    cache_misses: u64,
+    verification_failures: u64,
+    /// Renders that took at most each bucket's bound, not cumulative.
+    latency: [u64; BUCKETS.len()],
+    latency_sum: f64,
+    latency_count: u64,
+}
+
+impl Metrics {
+    /// Count a response to `endpoint`.
+    pub(super) fn response(&self, endpoint: &'static str, status: u16) {
+        *cache::lock(&self.0)
+            .responses
+            .entry((endpoint, status))
+            .or_default() += 1;
+    }
+
+    /// Count a render of `format` that took `elapsed`, answered from the cache or not.
+    pub(super) fn rendered(&self, format: &'static str, elapsed: Duration, hit: bool) {
+        let mut counts = cache::lock(&self.0);
+        *counts.renders.entry(format).or_default() += 1;
+        match hit {
+            true => counts.cache_hits += 1,
+            false => counts.cache_misses += 1,
+        }
+        counts.observe(elapsed);
+    }
+
+    /// Count a render that failed with `code` after `elapsed`.
+    pub(super) fn failed(&self, code: ErrorCode, elapsed: Duration) {
+        let mut counts = cache::lock(&self.0);
+        *counts.errors.entry(code.name()).or_default() += 1;
+        counts.observe(elapsed);
+    }
+
+    /// Count a code whose modules did not read back as drawn.
+    pub(super) fn verification_failed(&self) {
+        cache::lock(&self.0).verification_failures += 1;
+    }
+
+    /// Everything counted so far, in the text exposition format.
+    pub(super) fn export(&self) -> String {
+        let counts = cache::lock(&self.0);
+        let mut out = String::new();
+        family(
+            &mut out,
+            "qrbrand_http_responses_total",
+            "counter",
+            "HTTP responses by endpoint and status.",
+        );
+        for ((endpoint, status), n) in &counts.responses {
+            let _ = writeln!(
+                out,
+                "qrbrand_http_responses_total{{endpoint=\"{}\",status=\"{}\"}} {}",
+                endpoint, status, n
+            );
+        }
+        family(
+            &mut out,
+            "qrbrand_renders_total",
+            "counter",
+            "Codes served by format, cache hits included.",
+        );
+        for (format, n) in &counts.renders {
+            let _ = writeln!(out, "qrbrand_renders_total{{format=\"{}\"}} {}", format, n);
+        }
+        family(
+            &mut out,
+            "qrbrand_render_errors_total",
+            "counter",
+            "Render requests refused, by error code.",
+        );
+        for (code, n) in &counts.errors {
+            let _ = writeln!(
+                out,
+                "qrbrand_render_errors_total{{code=\"{}\"}} {}",
+                code, n
+            );
+        }
+        let totals = [
+            (
+                "qrbrand_cache_hits_total",
+                "Renders answered from the response cache.",
+                counts.cache_hits,
+            ),
+            (
+                "qrbrand_cache_misses_total",
+                "Renders drawn because the response cache had no entry.",
+                counts.cache_misses,
+            ),
+            (
+                "qrbrand_verification_failures_total",
+                "Codes whose modules did not read back as drawn.",
+                counts.verification_failures,
+            ),
+        ];
+        for (name, help, n) in totals {
+            family(&mut out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, n);
+        }
+
+        let name = "qrbrand_render_duration_seconds";
+        family(
+            &mut out,
+            name,
+            "histogram",
+            "Time to answer a render request.",
+        );
+        let mut cumulative = 0;
+        for (bound, n) in BUCKETS.iter().zip(counts.latency) {
+            cumulative += n;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let count = counts.latency_count;
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, counts.latency_sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+impl Counts {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.latency[i] += 1;
+        }
+        self.latency_sum += seconds;
+        self.latency_count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export() {
+        let metrics = Metrics::default();
+        metrics.response("/render", 200);
+        metrics.response("/render", 200);
+        metrics.response("/render", 422);
+        metrics.rendered("png", Duration::from_millis(20), false);
+        metrics.rendered("png", Duration::from_micros(100), true);
+        metrics.failed(ErrorCode::InvalidPayload, Duration::from_secs(20));
+        metrics.verification_failed();
+
+        let text = metrics.export();
+        for line in [
+            "# TYPE qrbrand_render_duration_seconds histogram",
+            "qrbrand_http_responses_total{endpoint=\"/render\",status=\"200\"} 2",
+            "qrbrand_http_responses_total{endpoint=\"/render\",status=\"422\"} 1",
+            "qrbrand_renders_total{format=\"png\"} 2",
+            "qrbrand_render_errors_total{code=\"invalid_payload\"} 1",
+            "qrbrand_cache_hits_total 1",
+            "qrbrand_cache_misses_total 1",
+            "qrbrand_verification_failures_total 1",
+            "qrbrand_render_duration_seconds_bucket{le=\"0.005\"} 1",
+            "qrbrand_render_duration_seconds_bucket{le=\"0.025\"} 2",
+            "qrbrand_render_duration_seconds_bucket{le=\"10\"} 2",
+            "qrbrand_render_duration_seconds_bucket{le=\"+Inf\"} 3",
+            "qrbrand_render_duration_seconds_count 3",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+}
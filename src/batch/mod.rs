@@ -0,0 +1,1122 @@
+//! Batch rendering from CSV, JSON or JSONL, parallelized across cores with rayon.
+//!
+//! Each record may override the command-line render options. The font, the
+//! template and each distinct logo are decoded once and shared by every job.
+
+#[cfg(feature = "cli")]
+mod auth;
+#[cfg(feature = "cli")]
+pub mod daemon;
+mod input;
+#[cfg(feature = "cli")]
+mod metrics;
+#[cfg(feature = "cli")]
+pub mod series;
+#[cfg(feature = "cli")]
+pub mod serve;
+
+pub use input::Record;
+
+#[cfg(feature = "cli")]
+use crate::compose;
+use crate::error::{ErrorCode, WithCode};
+use crate::fonts::FontChain;
+#[cfg(feature = "cli")]
+use crate::i18n;
+use crate::idn::{self, HomographMode};
+#[cfg(feature = "cli")]
+use crate::log;
+use crate::meta::{Metadata, sha256_hex};
+#[cfg(feature = "cli")]
+use crate::output;
+use crate::raster::Canvas;
+#[cfg(feature = "cli")]
+use crate::save_image;
+use crate::template::{self, TemplateVars};
+#[cfg(feature = "cli")]
+use crate::upload::Target;
+use crate::verify::ModuleCheck;
+#[cfg(feature = "cli")]
+use crate::webhook;
+use crate::{
+    Args, Assets, RenderOptions, Rendered, cache, caption_text, display_url, encode_png, jws, lang,
+    payload, policy, query_params, render_payload, units,
+};
+use anyhow::anyhow;
+#[cfg(feature = "cli")]
+use anyhow::bail;
+use anyhow::{Context, Result};
+use clap::Args as ClapArgs;
+#[cfg(feature = "cli")]
+use clap::{CommandFactory, Parser};
+use input::field;
+#[cfg(feature = "cli")]
+use rayon::prelude::*;
+#[cfg(feature = "cli")]
+use serde::Serialize;
+#[cfg(feature = "cli")]
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use url::Url;
+
+#[derive(ClapArgs, Debug)]
+pub struct BatchArgs {
+    /// Job file: CSV with a header row, a JSON array of objects, or JSONL (by extension).
+    /// Each record needs `url`; `out`, `alt_text`, `label` and render options named like their flags
+    /// (`size`, `logo`, `logo_scale`, `plate_color`, ..., also accepted under `style.`)
+    /// override the command line
+    #[arg(long = "input")]
+    pub input: String,
+
+    /// Directory for generated files (created if missing)
+    #[arg(long = "out-dir", default_value = "qrcodes")]
+    pub out_dir: String,
+
+    /// Output path template inside --out-dir, e.g. "badges/{row.id}-{hash8}.png" (vars:
+    /// {row.<col>}, {url}, {hash}, {hash8}, {index}, {date}); parent directories are created
+    /// as needed
+    #[arg(long = "out-template")]
+    pub out_template: Option<String>,
+
+    /// Where to write the JSON manifest of generated files (default: <out-dir>/manifest.json)
+    #[arg(long = "manifest")]
+    pub manifest: Option<String>,
+
+    /// Skip rows whose output exists and was rendered from the same payload and options
+    /// (missing or out-of-date outputs are still regenerated)
+    #[arg(long = "skip-existing", overrides_with = "force")]
+    pub skip_existing: bool,
+
+    /// Regenerate every output, ignoring existing files (overrides --skip-existing)
+    #[arg(long = "force", overrides_with = "skip_existing")]
+    pub force: bool,
+
+    /// Number of worker threads (default: one per CPU core)
+    #[arg(long = "jobs")]
+    pub jobs: Option<usize>,
+}
+
+/// PNG text chunk recording what an output was rendered from, for --skip-existing.
+#[cfg(feature = "cli")]
+const FINGERPRINT_KEY: &str = "qrbrand-fingerprint";
+
+/// Logo coverage above this share of the symbol is flagged in the manifest.
+const COVERAGE_WARN_PCT: f32 = 20.0;
+
+/// Summary of a batch run for downstream reconciliation.
+#[cfg(feature = "cli")]
+#[derive(Serialize, Debug)]
+struct Manifest {
+    input: String,
+    date: String,
+    total: usize,
+    succeeded: usize,
+    skipped: usize,
+    failed: usize,
+    entries: Vec<ManifestEntry>,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Serialize, Debug)]
+struct ManifestEntry {
+    /// 1-based record number (excluding any CSV header).
+    row: usize,
+    /// `written`, `skipped` (up to date), `valid` (--dry-run) or `failed`.
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    /// Where `--upload` put the file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_url: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    meta: Option<Metadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verification: Option<ModuleCheck>,
+    warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// e.g. `logo_unreadable`; see "Exit Codes" in the README.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<ErrorCode>,
+}
+
+/// What rendering one job produced.
+struct JobOutput {
+    meta: Metadata,
+    check: ModuleCheck,
+    warnings: Vec<String>,
+}
+
+/// A validated row, ready to render.
+struct Job {
+    payload: String,
+    /// The payload as --show-url renders it.
+    display: String,
+    /// Caption with template variables resolved.
+    alt_text: Option<String>,
+    out: PathBuf,
+    /// Command-line options with this record's overrides applied.
+    opts: RenderOptions,
+    logo: Option<String>,
+    /// Small text in the corner of the quiet zone, e.g. a serial number.
+    label: Option<String>,
+    show_url: bool,
+    embed_alt: bool,
+    /// Problems found while planning that do not stop the row.
+    warnings: Vec<String>,
+    /// Hash of the payload, caption and render settings.
+    fingerprint: String,
+}
+
+#[cfg(feature = "cli")]
+pub fn run(args: &Args, batch: &BatchArgs) -> Result<()> {
+    reject_single_code_flags(args, "batch").code(ErrorCode::Usage)?;
+    let records = input::read_records(&batch.input)?;
+    if records.is_empty() {
+        bail!("Batch input has no rows: {}", batch.input);
+    }
+    run_records(args, batch, &records)
+}
+
+/// Render `records` as `batch` says and write the manifest.
+#[cfg(feature = "cli")]
+fn run_records(args: &Args, batch: &BatchArgs, records: &[Record]) -> Result<()> {
+    let date = template::today();
+    let mut planned: Vec<Result<Job>> = records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| plan_record(args, batch, &date, index, record))
+        .collect();
+    if !args.unique {
+        check_collisions(&planned).code(ErrorCode::Usage)?;
+    }
+    if args.no_clobber || args.unique {
+        claim_outputs(&mut planned, args, batch.skip_existing);
+    }
+
+    let font = cache::font(args.font_family.as_deref())?;
+    let template = args
+        .template
+        .as_ref()
+        .and_then(compose::Template::image)
+        .map(compose::load_template)
+        .transpose()?
+        .map(Arc::new);
+    let assets = load_assets(&planned, &font, template.as_ref());
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(batch.jobs.unwrap_or(0))
+        .build()
+        .context("Failed to start worker threads")?;
+
+    let entries: Vec<ManifestEntry> = pool.install(|| {
+        planned
+            .par_iter()
+            .enumerate()
+            .map(|(index, job)| {
+                let upload = args.upload.as_ref().filter(|_| !args.dry_run);
+                let webhook = args.webhook.as_ref().filter(|_| !args.dry_run);
+                manifest_entry(index, job, batch, &assets, args.dry_run, upload, webhook)
+            })
+            .collect()
+    });
+
+    let mut failed = 0;
+    let mut skipped = 0;
+    for entry in &entries {
+        if let Some(e) = &entry.error {
+            failed += 1;
+            log::row_error(entry.row, entry.error_code.unwrap_or(ErrorCode::Error), e);
+        }
+        if entry.status == "skipped" {
+            skipped += 1;
+        }
+        for warning in &entry.warnings {
+            log::row_warn(entry.row, warning);
+        }
+    }
+
+    let written = entries.len() - failed - skipped;
+    log::info(i18n::tr(
+        "batch-summary",
+        &[
+            ("dry_run", if args.dry_run { "yes" } else { "no" }.into()),
+            ("written", written.into()),
+            ("total", entries.len().into()),
+            ("skipped", skipped.into()),
+        ],
+    ));
+
+    let manifest_path = match &batch.manifest {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(&batch.out_dir).join("manifest.json"),
+    };
+    let manifest = Manifest {
+        input: batch.input.clone(),
+        date,
+        total: entries.len(),
+        succeeded: written,
+        skipped,
+        failed,
+        entries,
+    };
+    if args.dry_run {
+        let json =
+            serde_json::to_string_pretty(&manifest).context("Failed to serialize manifest")?;
+        println!("{}", json);
+    } else {
+        write_manifest(&manifest, &manifest_path)?;
+        log::info(i18n::tr(
+            "wrote",
+            &[("path", manifest_path.display().to_string().into())],
+        ));
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} batch rows failed",
+            failed,
+            manifest.total
+        ))
+        .code(ErrorCode::BatchRowsFailed);
+    }
+    Ok(())
+}
+
+/// Refuse flags that only make sense for one code rendered from the command line.
+#[cfg(feature = "cli")]
+fn reject_single_code_flags(args: &Args, mode: &str) -> Result<()> {
+    if args.stress_test {
+        bail!(
+            "--stress-test checks a single code; run it without {}",
+            mode
+        );
+    }
+    if args.open || args.preview || args.clipboard || args.terminal {
+        bail!(
+            "--open, --preview, --clipboard and --terminal handle a single code; run them without {}",
+            mode
+        );
+    }
+    if args.shorten.is_some() || args.check_url.is_some() {
+        bail!(
+            "--shorten and --check-url handle a single --url; run them without {}",
+            mode
+        );
+    }
+    Ok(())
+}
+
+/// Validate a row and work out where its output goes.
+fn plan_record(
+    args: &Args,
+    batch: &BatchArgs,
+    date: &str,
+    index: usize,
+    record: &Record,
+) -> Result<Job> {
+    let payload = match typed_payload(record)? {
+        Some(payload) => payload,
+        None => {
+            let url = field(record, "url")
+                .context("missing `url` column value")
+                .code(ErrorCode::InvalidPayload)?;
+            let mut parsed = Url::parse(url)
+                .with_context(|| format!("Invalid URL: {} (did you include https:// ?)", url))
+                .code(ErrorCode::InvalidPayload)?;
+            payload::link::set_query_params(&mut parsed, &query_params(args));
+            parsed.to_string()
+        }
+    };
+    let mut warnings = match args.homograph_check {
+        HomographMode::Off => Vec::new(),
+        HomographMode::Warn => idn::homograph_warnings(&payload)
+            .into_iter()
+            .map(|w| format!("possible homograph domain: {}", w))
+            .collect(),
+        HomographMode::Fail => {
+            idn::check_homographs(&payload, HomographMode::Fail).code(ErrorCode::InvalidPayload)?;
+            Vec::new()
+        }
+    };
+    warnings.extend(policy::check(&payload, args.require_https).code(ErrorCode::InvalidPayload)?);
+
+    let hash = sha256_hex(payload.as_bytes());
+    let vars = TemplateVars {
+        row: Some(record),
+        payload: &payload,
+        payload_hash: &hash,
+        index: index + 1,
+        date,
+        serial: None,
+    };
+
+    let out = match (&batch.out_template, field(record, "out")) {
+        (_, Some(name)) => output_path(&batch.out_dir, index, Some(name))?,
+        (Some(tpl), None) => {
+            let name = template::render(tpl, &vars, true)?;
+            output_path(&batch.out_dir, index, Some(&name))?
+        }
+        (None, None) => output_path(&batch.out_dir, index, None)?,
+    };
+
+    let alt_text = field(record, "alt_text")
+        .map(str::to_string)
+        .or_else(|| lang::stacked(&args.alt_text))
+        .map(|t| template::render(&t, &vars, false))
+        .transpose()
+        .context("Invalid caption template")?;
+
+    let mut opts = RenderOptions::from_args(args);
+    let mut size = units::Size {
+        px: opts.size,
+        dpi: opts.dpi,
+    };
+    override_opt(record, "size", &mut size)?;
+    (opts.size, opts.dpi) = (size.px, size.dpi);
+    override_opt(record, "quiet", &mut opts.quiet)?;
+    override_opt(record, "logo_scale", &mut opts.logo_scale)?;
+    override_opt(record, "logo_plate", &mut opts.logo_plate)?;
+    override_opt(record, "logo_pad", &mut opts.logo_pad)?;
+    override_opt(record, "logo_prep", &mut opts.logo_prep)?;
+    override_opt(record, "logo_fit", &mut opts.logo_fit)?;
+    override_opt(record, "ecc_margin", &mut opts.ecc_margin)?;
+    override_opt(record, "module_gap", &mut opts.module_gap)?;
+    override_opt(record, "module_style", &mut opts.module_style)?;
+    override_opt(record, "invert", &mut opts.invert)?;
+    override_some(record, "rotate", &mut opts.orientation.rotate)?;
+    override_some(record, "flip", &mut opts.orientation.flip)?;
+    override_some(record, "fill_texture", &mut opts.fill)?;
+    override_opt(record, "plate_color", &mut opts.plate.color)?;
+    override_opt(record, "plate_border_color", &mut opts.plate.border_color)?;
+    override_opt(record, "plate_border_width", &mut opts.plate.border_width)?;
+    override_opt(record, "plate_radius", &mut opts.plate.radius)?;
+    override_opt(record, "text_color", &mut opts.caption.text_color)?;
+    override_opt(record, "band_color", &mut opts.caption.band_color)?;
+    override_opt(record, "fg", &mut opts.fg)?;
+    override_opt(record, "auto_color", &mut opts.auto_color)?;
+    override_some(record, "eye_color", &mut opts.eyes.outer)?;
+    override_some(record, "eye_inner_color", &mut opts.eyes.inner)?;
+    override_some(record, "eye_glyph_color", &mut opts.eye_glyph_color)?;
+    override_opt(record, "quiet_color", &mut opts.quiet_color)?;
+    override_opt(record, "corner_radius", &mut opts.corner_radius)?;
+    override_opt(record, "circle_crop", &mut opts.circle_crop)?;
+    override_opt(record, "shape", &mut opts.shape)?;
+    override_some(record, "border", &mut opts.border)?;
+    override_some(record, "place", &mut opts.place)?;
+    override_some(record, "shadow", &mut opts.shadow)?;
+    override_some(record, "plate_shadow", &mut opts.plate.shadow)?;
+    override_some(record, "logo_ring", &mut opts.plate.ring)?;
+    override_some(record, "logo_shadow", &mut opts.plate.logo_shadow)?;
+    override_opt(record, "canvas_color", &mut opts.canvas_color)?;
+    override_opt(record, "strict_contrast", &mut opts.strict_contrast)?;
+    override_some(record, "band_padding", &mut opts.caption.padding)?;
+    let mut show_url = args.show_url || args.display_url.is_some();
+    override_opt(record, "show_url", &mut show_url)?;
+    let mut embed_alt = args.embed_alt;
+    override_opt(record, "embed_alt", &mut embed_alt)?;
+    let logo = style_field(record, "logo")
+        .or(args.image.as_deref())
+        .map(str::to_string);
+    let label = field(record, "label").map(str::to_string);
+
+    let display = match style_field(record, "display_url") {
+        Some(text) => {
+            show_url = true;
+            text.to_string()
+        }
+        None => display_url(args, &payload),
+    };
+    let payload = jws::sign(args, payload)?;
+    let caption = caption_text(show_url, alt_text.as_deref(), &display);
+    let fingerprint = sha256_hex(
+        format!(
+            "{}\n{:?}\n{:?}\n{:?}\n{}\n{:?}\n{:?}",
+            payload, caption, opts, logo, embed_alt, args.font_family, label
+        )
+        .as_bytes(),
+    );
+
+    Ok(Job {
+        payload,
+        display,
+        alt_text,
+        out,
+        opts,
+        logo,
+        label,
+        show_url,
+        embed_alt,
+        warnings,
+        fingerprint,
+    })
+}
+
+/// Payload subcommands a record can name as `payload.type`.
+#[cfg(feature = "cli")]
+const PAYLOAD_TYPES: [&str; 10] = [
+    "email", "sms", "tel", "geo", "event", "epc", "gs1", "totp", "bitcoin", "ethereum",
+];
+
+/// The payload a record's `payload.type` names, with that subcommand's options as
+/// `payload.<option>` (e.g. `payload.type=email`, `payload.to`, `payload.subject`),
+/// encoded as the subcommand would encode it.
+#[cfg(feature = "cli")]
+fn typed_payload(record: &Record) -> Result<Option<String>> {
+    let Some(kind) = field(record, "payload.type") else {
+        return Ok(None);
+    };
+    let root = Args::command();
+    let command = root
+        .find_subcommand(kind)
+        .filter(|_| PAYLOAD_TYPES.contains(&kind))
+        .ok_or_else(|| {
+            anyhow!(
+                "`payload.type` {:?} is not one of {}",
+                kind,
+                PAYLOAD_TYPES.join(", ")
+            )
+        })
+        .code(ErrorCode::InvalidPayload)?;
+    let mut argv = vec!["qrbrand".to_string(), kind.to_string()];
+    for (key, value) in record {
+        let Some(option) = key.strip_prefix("payload.").filter(|&o| o != "type") else {
+            continue;
+        };
+        let long = option.replace('_', "-");
+        let arg = command
+            .get_arguments()
+            .find(|a| a.get_long() == Some(&long))
+            .ok_or_else(|| anyhow!("`payload.{}` is not an option of {}", option, kind))
+            .code(ErrorCode::InvalidPayload)?;
+        if arg.get_action().takes_values() {
+            argv.push(format!("--{}={}", long, value));
+        } else if value
+            .parse::<bool>()
+            .map_err(|_| anyhow!("`payload.{}` is a flag: use true or false", option))
+            .code(ErrorCode::InvalidPayload)?
+        {
+            argv.push(format!("--{}", long));
+        }
+    }
+    let args = Args::try_parse_from(argv)
+        .map_err(|e| {
+            let message = e.to_string();
+            let first = message.lines().next().unwrap_or_default();
+            anyhow!("{}", first.trim_start_matches("error: "))
+        })
+        .code(ErrorCode::InvalidPayload)?;
+    crate::resolve_payload(&args)
+        .code(ErrorCode::InvalidPayload)
+        .map(Some)
+}
+
+#[cfg(not(feature = "cli"))]
+fn typed_payload(record: &Record) -> Result<Option<String>> {
+    match field(record, "payload.type") {
+        Some(_) => Err(anyhow!(
+            "`payload.type` needs qrbrand built with the `cli` feature; send `url`"
+        )),
+        None => Ok(None),
+    }
+}
+
+/// A render option from a record, either top-level or nested under `style`.
+fn style_field<'a>(record: &'a Record, key: &str) -> Option<&'a str> {
+    field(record, key).or_else(|| field(record, &format!("style.{}", key)))
+}
+
+/// Replace `value` with the record's setting for `key`, if it has one.
+fn override_opt<T: std::str::FromStr>(record: &Record, key: &str, value: &mut T) -> Result<()>
+where
+    T::Err: std::fmt::Display,
+{
+    if let Some(raw) = style_field(record, key) {
+        *value = raw
+            .parse()
+            .map_err(|e| anyhow!("invalid `{}` value {:?}: {}", key, raw, e))?;
+    }
+    Ok(())
+}
+
+/// Like [`override_opt`], for settings that are off unless given.
+fn override_some<T: std::str::FromStr>(
+    record: &Record,
+    key: &str,
+    value: &mut Option<T>,
+) -> Result<()>
+where
+    T::Err: std::fmt::Display,
+{
+    if let Some(raw) = style_field(record, key) {
+        let parsed = raw
+            .parse()
+            .map_err(|e| anyhow!("invalid `{}` value {:?}: {}", key, raw, e))?;
+        *value = Some(parsed);
+    }
+    Ok(())
+}
+
+/// Pair the font and template with every distinct logo the jobs use, decoding each logo once.
+/// Load failures are kept per logo so only the rows that reference it fail.
+#[cfg(feature = "cli")]
+fn load_assets(
+    planned: &[Result<Job>],
+    font: &Arc<FontChain>,
+    template: Option<&Arc<Canvas>>,
+) -> HashMap<Option<String>, Result<Assets>> {
+    let mut assets = HashMap::new();
+    for job in planned.iter().flatten() {
+        if !assets.contains_key(&job.logo) {
+            let logo = job.logo.as_deref().map(cache::logo).transpose();
+            let loaded = logo.map(|logo| Assets {
+                font: Arc::clone(font),
+                logo,
+                template: template.cloned(),
+            });
+            assets.insert(job.logo.clone(), loaded);
+        }
+    }
+    assets
+}
+
+/// Refuse to run if two rows would write the same file.
+#[cfg(feature = "cli")]
+fn check_collisions(planned: &[Result<Job>]) -> Result<()> {
+    let mut seen: HashMap<&Path, usize> = HashMap::new();
+    for (index, job) in planned.iter().enumerate() {
+        if let Ok(job) = job
+            && let Some(first) = seen.insert(&job.out, index)
+        {
+            bail!(
+                "rows {} and {} both write {}; make --out-template unique (e.g. add {{index}} or {{hash8}})",
+                first + 1,
+                index + 1,
+                job.out.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Apply --no-clobber and --unique to every row's output, in row order. Under --unique
+/// a later row that shares an earlier row's name is renamed too. Outputs that
+/// --skip-existing will skip keep their name.
+#[cfg(feature = "cli")]
+fn claim_outputs(planned: &mut [Result<Job>], args: &Args, skip_existing: bool) {
+    let mut claimed: HashSet<PathBuf> = HashSet::new();
+    for planned in planned.iter_mut() {
+        let Ok(job) = planned else { continue };
+        if skip_existing && is_up_to_date(job) {
+            claimed.insert(job.out.clone());
+            continue;
+        }
+        let claim = output::claim_path(
+            &job.out,
+            &sha256_hex(job.payload.as_bytes())[..8],
+            args.no_clobber,
+            args.unique,
+            |path| claimed.contains(path) || path.exists(),
+        );
+        match claim {
+            Ok(out) => {
+                claimed.insert(out.clone());
+                job.out = out;
+            }
+            Err(e) => *planned = Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+fn manifest_entry(
+    index: usize,
+    job: &Result<Job>,
+    batch: &BatchArgs,
+    assets: &HashMap<Option<String>, Result<Assets>>,
+    dry_run: bool,
+    upload: Option<&Target>,
+    webhook: Option<&Url>,
+) -> ManifestEntry {
+    let mut entry = ManifestEntry {
+        row: index + 1,
+        status: "failed",
+        payload: None,
+        output: None,
+        public_url: None,
+        meta: None,
+        verification: None,
+        warnings: Vec::new(),
+        error: None,
+        error_code: None,
+    };
+
+    let job = match job {
+        Ok(job) => job,
+        Err(e) => {
+            entry.fail(e);
+            return entry;
+        }
+    };
+    entry.payload = Some(job.payload.clone());
+    entry.warnings.extend(job.warnings.iter().cloned());
+    entry.output = Some(job.out.to_string_lossy().into_owned());
+
+    if batch.skip_existing && is_up_to_date(job) {
+        entry.status = "skipped";
+        return entry;
+    }
+
+    let rendered = match &assets[&job.logo] {
+        Ok(assets) if dry_run => render_checked(assets, job).map(|(_, out)| out),
+        Ok(assets) => render_job(assets, job),
+        Err(e) => Err(anyhow!("{:#}", e)).code(ErrorCode::of(e)),
+    };
+    match rendered {
+        Ok(out) => {
+            entry.status = if dry_run { "valid" } else { "written" };
+            entry.warnings.extend(out.warnings);
+            if out.meta.logo_coverage_pct > COVERAGE_WARN_PCT {
+                entry.warnings.push(format!(
+                    "logo covers {:.1}% of the symbol",
+                    out.meta.logo_coverage_pct
+                ));
+            }
+            if !out.check.passed {
+                entry.warnings.push(format!(
+                    "module check failed: {} of {} modules mismatched",
+                    out.check.mismatched_modules, out.check.total_modules
+                ));
+            }
+            entry.meta = Some(out.meta);
+            entry.verification = Some(out.check);
+        }
+        Err(e) => {
+            entry.fail(&e);
+            return entry;
+        }
+    }
+    if let Some(target) = upload {
+        // The key mirrors the file's place under --out-dir.
+        let name = job.out.strip_prefix(&batch.out_dir).unwrap_or(&job.out);
+        match target.put_file(&job.out, &name.to_string_lossy()) {
+            Ok(url) => entry.public_url = Some(url),
+            Err(e) => {
+                entry.status = "failed";
+                entry.fail(&e);
+                return entry;
+            }
+        }
+    }
+    if let (Some(hook), Some(meta)) = (webhook, &entry.meta) {
+        let body = webhook::body(&job.out, &job.payload, meta, entry.public_url.as_deref());
+        let sent = body.and_then(|mut body| {
+            body["row"] = entry.row.into();
+            webhook::send(hook, &body)
+        });
+        if let Err(e) = sent {
+            entry.warnings.push(format!("{:#}", e));
+        }
+    }
+    entry
+}
+
+#[cfg(feature = "cli")]
+impl ManifestEntry {
+    fn fail(&mut self, err: &anyhow::Error) {
+        self.error = Some(format!("{:#}", err));
+        self.error_code = Some(ErrorCode::of(err));
+    }
+}
+
+/// The output exists and records the same fingerprint as this job.
+#[cfg(feature = "cli")]
+fn is_up_to_date(job: &Job) -> bool {
+    output::read_png_text(&job.out, FINGERPRINT_KEY).as_deref() == Some(job.fingerprint.as_str())
+}
+
+#[cfg(feature = "cli")]
+fn write_manifest(manifest: &Manifest, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    let json = serde_json::to_string_pretty(manifest).context("Failed to serialize manifest")?;
+    std::fs::write(path, json + "\n")
+        .with_context(|| format!("Failed to write manifest: {}", path.display()))
+}
+
+#[cfg(feature = "cli")]
+fn render_job(assets: &Assets, job: &Job) -> Result<JobOutput> {
+    let (rendered, out) = render_checked(assets, job)?;
+    if let Some(parent) = job.out.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create output directory: {}", parent.display()))?;
+    }
+    save_image(
+        &rendered.image,
+        &job.out.to_string_lossy(),
+        job.embed_alt,
+        job.alt_text.as_deref(),
+        &job.payload,
+        &fingerprint_chunk(job),
+        None,
+        job.opts.dpi,
+    )?;
+    Ok(out)
+}
+
+/// Render a job and check the result, without writing it anywhere.
+fn render_checked(assets: &Assets, job: &Job) -> Result<(Rendered, JobOutput)> {
+    let opts = &job.opts;
+    let caption = caption_text(job.show_url, job.alt_text.as_deref(), &job.display);
+    let mut rendered = render_payload(&job.payload, caption, opts, assets)?;
+    if let Some(label) = &job.label {
+        rendered.draw_label(label, opts, &assets.font)?;
+    }
+    let mut warnings = rendered.contrast(opts.quiet).check(opts.strict_contrast)?;
+    warnings.extend(rendered.logo_shrink_warning(opts.logo_scale));
+    warnings.extend(rendered.density_advice(&job.payload, opts));
+
+    let check = rendered.check_modules(opts.quiet);
+    rendered.orient(opts.orientation);
+
+    let (w, h) = rendered.image.dimensions();
+    let coverage = rendered.logo_coverage(opts.quiet);
+    let out = JobOutput {
+        meta: Metadata::new(&rendered.code, &job.payload, w, h, coverage).with_dpi(opts.dpi),
+        check,
+        warnings,
+    };
+    Ok((rendered, out))
+}
+
+/// Render a job to PNG bytes carrying the same text chunks as a saved file, minus the fingerprint.
+fn render_png(assets: &Assets, job: &Job) -> Result<(Vec<u8>, JobOutput)> {
+    let (rendered, out) = render_checked(assets, job)?;
+    let png = encode_png(
+        &rendered.image,
+        job.embed_alt,
+        job.alt_text.as_deref(),
+        &job.payload,
+    )?;
+    Ok((png, out))
+}
+
+/// Render one JSON record to PNG bytes for the library API. `logo` is an encoded image
+/// that takes the place of the record's logo path.
+pub(crate) fn render_record(
+    args: &Args,
+    options: serde_json::Value,
+    font: Arc<FontChain>,
+    logo: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    let (assets, job) = library_job(args, options, font, logo)?;
+    Ok(render_png(&assets, &job)?.0)
+}
+
+/// Render one JSON record to an RGBA image for the library API, without the PNG
+/// round trip.
+pub(crate) fn render_record_rgba(
+    args: &Args,
+    options: serde_json::Value,
+    font: Arc<FontChain>,
+    logo: Option<&[u8]>,
+) -> Result<Canvas> {
+    let (assets, job) = library_job(args, options, font, logo)?;
+    Ok(render_checked(&assets, &job)?.0.image)
+}
+
+/// The job for one JSON record from the library API, and the assets to draw it with.
+fn library_job(
+    args: &Args,
+    options: serde_json::Value,
+    font: Arc<FontChain>,
+    logo: Option<&[u8]>,
+) -> Result<(Assets, Job)> {
+    let record = input::json_record(options)?;
+    let batch = BatchArgs {
+        input: "library".to_string(),
+        out_dir: ".".to_string(),
+        out_template: None,
+        manifest: None,
+        skip_existing: false,
+        force: false,
+        jobs: None,
+    };
+    let job = plan_record(args, &batch, &template::today(), 0, &record)?;
+    let logo = match logo {
+        Some(bytes) => Some(Arc::new(
+            image::load_from_memory(bytes).context("Failed to decode logo image")?,
+        )),
+        None => job.logo.as_deref().map(cache::logo).transpose()?,
+    };
+    let assets = Assets {
+        font,
+        logo,
+        template: None,
+    };
+    Ok((assets, job))
+}
+
+/// Only PNG outputs can carry the fingerprint chunk.
+#[cfg(feature = "cli")]
+fn fingerprint_chunk(job: &Job) -> Vec<(&'static str, &str)> {
+    let is_png = job
+        .out
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if is_png {
+        vec![(FINGERPRINT_KEY, job.fingerprint.as_str())]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Output path for a row: its `out` column or rendered `--out-template` inside `out_dir`,
+/// or a 1-based numbered file. An `out` that is absolute or climbs out with `..` is
+/// refused, so rows, templates and daemon requests cannot write outside `out_dir`.
+fn output_path(out_dir: &str, index: usize, out: Option<&str>) -> Result<PathBuf> {
+    let Some(name) = out else {
+        return Ok(Path::new(out_dir).join(format!("{:04}.png", index + 1)));
+    };
+    let inside = Path::new(name)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return Err(anyhow!(
+            "output path must be relative and inside the output directory, got '{}'",
+            name
+        ))
+        .code(ErrorCode::Usage);
+    }
+    Ok(Path::new(out_dir).join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_check_collisions() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from(["qrbrand", "--url", "https://example.com"]).unwrap();
+        let job = |out: &str| {
+            Ok(Job {
+                payload: String::new(),
+                display: String::new(),
+                alt_text: None,
+                out: PathBuf::from(out),
+                opts: RenderOptions::from_args(&args),
+                logo: None,
+                label: None,
+                show_url: false,
+                embed_alt: false,
+                warnings: Vec::new(),
+                fingerprint: String::new(),
+            })
+        };
+        assert!(check_collisions(&[job("a.png"), job("b.png")]).is_ok());
+        assert!(check_collisions(&[job("a.png"), job("b.png"), job("a.png")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_plan_record_caption_template() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from([
+            "qrbrand",
+            "batch",
+            "--input",
+            "unused.csv",
+            "--alt-text",
+            "Table {row.table} - {row.name}",
+        ])
+        .unwrap();
+        let Some(crate::Command::Batch(batch)) = &args.command else {
+            panic!("expected batch command");
+        };
+        let record = Record::from([
+            ("url".to_string(), "https://example.com/t/4".to_string()),
+            ("table".to_string(), "4".to_string()),
+            ("name".to_string(), "Ada".to_string()),
+        ]);
+
+        let job = plan_record(&args, batch, "2025-06-01", 0, &record).unwrap();
+        assert_eq!(job.alt_text.as_deref(), Some("Table 4 - Ada"));
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_reject_single_code_flags() {
+        use clap::Parser;
+
+        let parse = |flags: &[&str]| {
+            let argv = ["qrbrand"]
+                .iter()
+                .chain(flags)
+                .chain(&["batch", "--input", "in.csv"]);
+            Args::try_parse_from(argv).unwrap()
+        };
+        assert!(reject_single_code_flags(&parse(&[]), "batch").is_ok());
+        assert!(reject_single_code_flags(&parse(&["--shorten", "tinyurl"]), "batch").is_err());
+        assert!(reject_single_code_flags(&parse(&["--check-url", "warn"]), "batch").is_err());
+        assert!(reject_single_code_flags(&parse(&["--open"]), "batch").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_plan_record_out_template() {
+        use clap::Parser;
+
+        let plan = |template: &str| {
+            let args = Args::try_parse_from([
+                "qrbrand",
+                "batch",
+                "--input",
+                "unused.csv",
+                "--out-dir",
+                "codes",
+                "--out-template",
+                template,
+            ])
+            .unwrap();
+            let Some(crate::Command::Batch(batch)) = &args.command else {
+                panic!("expected batch command");
+            };
+            let record = Record::from([
+                ("url".to_string(), "https://example.com".to_string()),
+                ("id".to_string(), "../7".to_string()),
+            ]);
+            plan_record(&args, batch, "2025-06-01", 0, &record).map(|job| job.out)
+        };
+        assert_eq!(
+            plan("badges/{row.id}.png").unwrap(),
+            Path::new("codes").join("badges/.._7.png")
+        );
+        assert!(plan("/tmp/{row.id}.png").is_err());
+        assert!(plan("../{row.id}.png").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_plan_record_query_params() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from([
+            "qrbrand",
+            "--utm-source",
+            "table",
+            "--param",
+            "ref=store42",
+            "batch",
+            "--input",
+            "unused.csv",
+        ])
+        .unwrap();
+        let Some(crate::Command::Batch(batch)) = &args.command else {
+            panic!("expected batch command");
+        };
+        let record = Record::from([(
+            "url".to_string(),
+            "https://example.com/t/4?utm_source=old".to_string(),
+        )]);
+
+        let job = plan_record(&args, batch, "2025-06-01", 0, &record).unwrap();
+        assert_eq!(
+            job.payload,
+            "https://example.com/t/4?utm_source=table&ref=store42"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_plan_record_overrides() {
+        use clap::Parser;
+
+        let args = Args::try_parse_from([
+            "qrbrand",
+            "batch",
+            "--input",
+            "unused.json",
+            "--size",
+            "512",
+            "-i",
+            "logo.png",
+        ])
+        .unwrap();
+        let Some(crate::Command::Batch(batch)) = &args.command else {
+            panic!("expected batch command");
+        };
+        let record = Record::from([
+            ("url".to_string(), "https://example.com".to_string()),
+            ("style.size".to_string(), "900".to_string()),
+            ("logo_plate".to_string(), "false".to_string()),
+            ("style.logo".to_string(), "other.png".to_string()),
+            ("style.border".to_string(), "2px #000".to_string()),
+            ("shadow".to_string(), "blur=6".to_string()),
+        ]);
+
+        let job = plan_record(&args, batch, "2025-06-01", 0, &record).unwrap();
+        assert_eq!(job.opts.size, 900);
+        assert!(!job.opts.logo_plate);
+        assert_eq!(job.logo.as_deref(), Some("other.png"));
+        assert!(!job.show_url);
+        assert_eq!(job.opts.border.map(|b| b.width), Some(2));
+        assert_eq!(job.opts.shadow.map(|s| s.blur), Some(6.0));
+
+        let default = Record::from([("url".to_string(), "https://example.com".to_string())]);
+        let job_default = plan_record(&args, batch, "2025-06-01", 0, &default).unwrap();
+        assert_eq!(job_default.opts.size, 512);
+        assert_eq!(job_default.logo.as_deref(), Some("logo.png"));
+        assert_ne!(job.fingerprint, job_default.fingerprint);
+
+        let bad = Record::from([
+            ("url".to_string(), "https://example.com".to_string()),
+            ("size".to_string(), "big".to_string()),
+        ]);
+        assert!(plan_record(&args, batch, "2025-06-01", 0, &bad).is_err());
+
+        let short = Record::from([
+            ("url".to_string(), "https://example.com/p?id=9".to_string()),
+            ("display_url".to_string(), "example.com/spring".to_string()),
+        ]);
+        let job_short = plan_record(&args, batch, "2025-06-01", 0, &short).unwrap();
+        assert!(job_short.show_url);
+        assert_eq!(job_short.display, "example.com/spring");
+
+        // A typed payload is encoded as its subcommand would encode it.
+        let typed = |fields: &[(&str, &str)]| {
+            let record: Record = fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            plan_record(&args, batch, "2025-06-01", 0, &record)
+        };
+        let job_tel = typed(&[("payload.type", "tel"), ("payload.to", "+44 20 7946 0000")]);
+        assert_eq!(job_tel.unwrap().payload, "tel:+442079460000");
+        assert!(typed(&[("payload.type", "tel"), ("payload.size", "9")]).is_err());
+        assert!(typed(&[("payload.type", "batch")]).is_err());
+    }
+
+    #[test]
+    fn test_output_path() {
+        assert_eq!(
+            output_path("out", 0, None).unwrap(),
+            Path::new("out").join("0001.png")
+        );
+        assert_eq!(
+            output_path("out", 4, Some("signs/menu.png")).unwrap(),
+            Path::new("out").join("signs/menu.png")
+        );
+        assert!(output_path("out", 0, Some("/tmp/escaped.png")).is_err());
+        assert!(output_path("out", 0, Some("../escaped.png")).is_err());
+        assert!(output_path("out", 0, Some("signs/../../escaped.png")).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! `qrbrand series`: a numbered run of codes without writing a CSV first.
+//!
+//! Each serial `n` from `--start` fills `{n}` in the URL template, the corner label and
+//! the file name, and the run goes through the batch renderer as one record per serial,
+//! with the same manifest.
+
+use super::{BatchArgs, Record, reject_single_code_flags, run_records};
+use crate::Args;
+use crate::error::{ErrorCode, WithCode};
+use crate::meta::sha256_hex;
+use crate::template::{self, TemplateVars};
+use anyhow::{Context, Result, anyhow};
+use clap::Args as ClapArgs;
+
+#[derive(ClapArgs, Debug)]
+pub struct SeriesArgs {
+    /// How many codes to render
+    #[arg(long = "count")]
+    pub count: u64,
+
+    /// First serial number
+    #[arg(long = "start", default_value_t = 1)]
+    pub start: u64,
+
+    /// URL for each code, with {n} for the serial, e.g. "https://ex.com/t/{n:04}"
+    #[arg(long = "url-template")]
+    pub url_template: String,
+
+    /// Corner label template (vars: {n}, {url}, {hash8}, {index}, {date}); "" for none
+    #[arg(long = "label", default_value = "{n}")]
+    pub label: String,
+
+    /// Directory for generated files (created if missing)
+    #[arg(long = "out-dir", default_value = "qrcodes")]
+    pub out_dir: String,
+
+    /// File name template inside --out-dir [default: {n}.png, zero-padded to the last serial]
+    #[arg(long = "name-template")]
+    pub name_template: Option<String>,
+
+    /// Where to write the JSON manifest of generated files (default: <out-dir>/manifest.json)
+    #[arg(long = "manifest")]
+    pub manifest: Option<String>,
+
+    /// Number of worker threads (default: one per CPU core)
+    #[arg(long = "jobs")]
+    pub jobs: Option<usize>,
+}
+
+pub fn run(args: &Args, series: &SeriesArgs) -> Result<()> {
+    reject_single_code_flags(args, "series").code(ErrorCode::Usage)?;
+    let records = records(series, &template::today()).code(ErrorCode::Usage)?;
+    let batch = BatchArgs {
+        input: series.url_template.clone(),
+        out_dir: series.out_dir.clone(),
+        out_template: None,
+        manifest: series.manifest.clone(),
+        skip_existing: false,
+        force: false,
+        jobs: series.jobs,
+    };
+    run_records(args, &batch, &records)
+}
+
+/// One batch record per serial: its `url`, `label` and `out` file name.
+fn records(series: &SeriesArgs, date: &str) -> Result<Vec<Record>> {
+    if series.count == 0 {
+        return Err(anyhow!("--count must be at least 1"));
+    }
+    let last = series
+        .start
+        .checked_add(series.count - 1)
+        .context("--start plus --count is too large")?;
+    let width = last.to_string().len();
+    (0..series.count)
+        .map(|i| {
+            let n = series.start + i;
+            let mut vars = TemplateVars {
+                row: None,
+                payload: "",
+                payload_hash: "",
+                index: i as usize + 1,
+                date,
+                serial: Some(n),
+            };
+            let url = template::render(&series.url_template, &vars, false)
+                .context("Invalid --url-template")?;
+            let hash = sha256_hex(url.as_bytes());
+            vars.payload = &url;
+            vars.payload_hash = &hash;
+            let label = template::render(&series.label, &vars, false)
+                .context("Invalid --label template")?;
+            let out = match &series.name_template {
+                Some(name) => {
+                    template::render(name, &vars, true).context("Invalid --name-template")?
+                }
+                None => format!("{:0width$}.png", n, width = width),
+            };
+            Ok(Record::from([
+                ("url".to_string(), url),
+                ("label".to_string(), label),
+                ("out".to_string(), out),
+            ]))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn series(argv: &[&str]) -> SeriesArgs {
+        let argv = ["qrbrand", "series"].iter().chain(argv);
+        match Args::try_parse_from(argv).unwrap().command {
+            Some(crate::Command::Series(series)) => series,
+            _ => panic!("expected series command"),
+        }
+    }
+
+    #[test]
+    fn test_series_records() {
+        let url = "https://ex.com/t/{n:04}";
+        let padded = series(&["--count", "3", "--start", "98", "--url-template", url]);
+        let rows = records(&padded, "2025-06-01").unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["url"], "https://ex.com/t/0098");
+        assert_eq!(rows[0]["label"], "98");
+        assert_eq!(rows[0]["out"], "098.png");
+        assert_eq!(rows[2]["out"], "100.png");
+
+        let named = series(&[
+            "--count",
+            "1",
+            "--url-template",
+            url,
+            "--label",
+            "#{n:05}",
+            "--name-template",
+            "ticket-{n}-{hash8}.png",
+        ]);
+        let rows = records(&named, "2025-06-01").unwrap();
+        assert_eq!(rows[0]["label"], "#00001");
+        assert!(rows[0]["out"].starts_with("ticket-1-"));
+
+        assert!(records(&series(&["--count", "0", "--url-template", url]), "").is_err());
+        assert!(records(&series(&["--count", "1", "--url-template", "{row.x}"]), "").is_err());
+    }
+}
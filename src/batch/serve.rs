@@ -0,0 +1,1239 @@
+//! `qrbrand serve`: render codes over HTTP, for backends that would rather not shell out.
+//!
+//! `GET /render?url=...&size=...` takes a batch record as query parameters and
+//! `POST /render` takes one as a JSON body, flat or with the options under `style`, as
+//! a daemon request does. Instead of `url` a record may give a typed payload under
+//! `payload`: `type` names a payload subcommand and the rest are its options. The
+//! logo is the image itself (`logo_base64`), or with the flags below a path on the
+//! server (`logo`) or an image to fetch (`logo_url`). The response is PNG, SVG or
+//! PDF as the `Accept` header asks (PNG without one); failures come back as JSON
+//! with `error` and `error_code`. Each connection carries one request.
+//!
+//! Responses are kept in a least-recently-used cache keyed by the job fingerprint
+//! (payload, caption and parsed options), the format and the logo, so a repeated
+//! request skips rendering whichever way it spells its options. `--rate-limit` gives
+//! each client address a token bucket, and `GET /healthz` answers without touching
+//! either, for load balancers and uptime checks. `GET /metrics` is the same for
+//! Prometheus: response, render, cache and error counts and a render latency histogram.
+//!
+//! With `--api-key` or `--signing-key`, `/render` wants credentials; see [`super::auth`].
+//! They are checked from the request head, so a request without them is refused
+//! before its body is read.
+//!
+//! Requests are refused (422) above `--max-size` pixels across, counting the caption,
+//! border and shadows, or with more than [`MAX_CAPTION_LINES`] caption lines; the
+//! finished image is checked against `--max-size` once more, as the caption band makes
+//! it taller. Requests may not name files on the server (`logo`, `fill_texture`)
+//! or have it fetch `logo_url` unless `--allow-paths` or `--allow-logo-urls` says so.
+
+use super::auth::{self, Auth, Denied};
+use super::daemon::Server;
+use super::input::{self, Record};
+use super::metrics::Metrics;
+use super::{Job, plan_record, reject_single_code_flags, render_checked, style_field};
+use crate::decor::Shadow;
+use crate::error::{ErrorCode, WithCode};
+use crate::marks::Marks;
+use crate::meta::sha256_hex;
+use crate::modules::Fill;
+use crate::raster::Canvas;
+use crate::{
+    Args, RenderOptions, alt_text_chunks, cache, caption_text, encode_png, log, pdf, round, svg,
+    template,
+};
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use clap::Args as ClapArgs;
+use image::DynamicImage;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(ClapArgs, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long = "listen", default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// Rendered responses to keep for repeated requests (0 turns the cache off)
+    #[arg(long = "cache-size", default_value_t = 256)]
+    pub cache_size: usize,
+
+    /// Requests a minute to allow from each client address (0 for no limit)
+    #[arg(long = "rate-limit", value_name = "PER_MINUTE", default_value_t = 0)]
+    pub rate_limit: u32,
+
+    /// Require this key in X-API-Key or Authorization: Bearer to render (repeatable)
+    #[arg(long = "api-key", value_name = "KEY")]
+    pub api_keys: Vec<String>,
+
+    /// Also accept GET /render URLs signed with this secret (see --sign)
+    #[arg(long = "signing-key", value_name = "SECRET")]
+    pub signing_key: Option<String>,
+
+    /// Print TARGET (e.g. "/render?url=...") signed with --signing-key, and exit
+    #[arg(long = "sign", value_name = "TARGET", requires = "signing_key")]
+    pub sign: Option<String>,
+
+    /// Connections to serve at once; more wait their turn
+    #[arg(long = "workers", value_name = "N", default_value_t = 32)]
+    pub workers: usize,
+
+    /// Seconds a URL from --sign stays valid
+    #[arg(long = "expires-in", value_name = "SECONDS", default_value_t = 3600)]
+    pub expires_in: u64,
+
+    /// Largest output side, in pixels, a request may ask for
+    #[arg(long = "max-size", value_name = "PX", default_value_t = 8192)]
+    pub max_size: u32,
+
+    /// Let requests name files on this machine (`logo`, `fill_texture`)
+    #[arg(long = "allow-paths")]
+    pub allow_paths: bool,
+
+    /// Let requests have the server fetch `logo_url`
+    #[arg(long = "allow-logo-urls")]
+    pub allow_logo_urls: bool,
+}
+
+/// Largest request line plus headers, and largest body, in bytes.
+const MAX_HEAD: usize = 16 * 1024;
+const MAX_BODY: usize = 16 << 20;
+/// Largest logo fetched for `logo_url`, in bytes.
+#[cfg(feature = "net")]
+const MAX_LOGO: u64 = 8 << 20;
+/// Most lines a caption may have, one per language.
+const MAX_CAPTION_LINES: usize = 8;
+/// A client that sends nothing for this long is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Clients the rate limiter tracks before it forgets those with a full allowance.
+const MAX_CLIENTS: usize = 10_000;
+/// Each endpoint and the methods it answers.
+const ENDPOINTS: [(&str, &str); 3] = [
+    ("/render", "GET, POST"),
+    ("/healthz", "GET"),
+    ("/metrics", "GET"),
+];
+
+/// The formats `Accept` can ask for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Png,
+    Svg,
+    Pdf,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Svg => "image/svg+xml",
+            Self::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// A logo sent with the request rather than named by path.
+enum Logo {
+    Inline(Vec<u8>),
+    Url(String),
+}
+
+/// The server with its response cache and per-client rate limits.
+struct Service<'a> {
+    server: Server<'a>,
+    cache: Mutex<Lru>,
+    limiter: Mutex<Limiter>,
+    metrics: Metrics,
+    auth: Auth,
+    limits: Limits,
+}
+
+/// What a request may ask of the server.
+struct Limits {
+    max_size: u32,
+    allow_paths: bool,
+    allow_logo_urls: bool,
+}
+
+impl Limits {
+    /// Refuse a request that names a server file or `logo_url` without the flag for
+    /// it, or whose output could be over `max_size` across.
+    fn check(&self, record: &Record, logo: &Option<Logo>, job: &Job) -> Result<()> {
+        let given = |key: &str| style_field(record, key).is_some();
+        let path = match &job.opts.fill {
+            Some(Fill::Image(_)) if given("fill_texture") => Some("fill_texture"),
+            _ => given("logo").then_some("logo"),
+        };
+        if let Some(key) = path.filter(|_| !self.allow_paths) {
+            return Err(anyhow!(
+                "`{}` names a file on the server, which needs serve --allow-paths; send logo_base64 instead",
+                key
+            ))
+            .code(ErrorCode::Usage);
+        }
+        if matches!(logo, Some(Logo::Url(_))) && !self.allow_logo_urls {
+            return Err(anyhow!(
+                "`logo_url` needs serve --allow-logo-urls; send logo_base64 instead"
+            ))
+            .code(ErrorCode::Usage);
+        }
+        let extent = extent(&job.opts);
+        if extent > self.max_size as f64 {
+            return Err(anyhow!(
+                "the output could be {:.0} px across, over this server's --max-size of {} px",
+                extent,
+                self.max_size
+            ))
+            .code(ErrorCode::Usage);
+        }
+        // Each caption line makes the band taller.
+        let caption = caption_text(job.show_url, job.alt_text.as_deref(), &job.display);
+        let lines = caption.map_or(0, |caption| caption.lines().count());
+        if lines > MAX_CAPTION_LINES {
+            return Err(anyhow!(
+                "the caption has {} lines; this server draws at most {}",
+                lines,
+                MAX_CAPTION_LINES
+            ))
+            .code(ErrorCode::Usage);
+        }
+        Ok(())
+    }
+
+    /// Refuse a finished image over `max_size` on either side, which a tall caption
+    /// band can make it even when [`Limits::check`] passed.
+    fn check_rendered(&self, img: &Canvas) -> Result<()> {
+        let (w, h) = img.dimensions();
+        if w.max(h) > self.max_size {
+            return Err(anyhow!(
+                "the output is {} x {} px, over this server's --max-size of {} px",
+                w,
+                h,
+                self.max_size
+            ))
+            .code(ErrorCode::Usage);
+        }
+        Ok(())
+    }
+}
+
+/// The widest `opts` can make the output: the code with the caption padding, border
+/// and shadows around it, all of which grow the canvas.
+fn extent(opts: &RenderOptions) -> f64 {
+    let shadow = |shadow: &Option<Shadow>| {
+        shadow.as_ref().map_or(0.0, |s| {
+            s.blur as f64 + s.offset.0.unsigned_abs().max(s.offset.1.unsigned_abs()) as f64
+        })
+    };
+    let around = opts.border.as_ref().map_or(0, |b| b.width) as f64
+        + opts.caption.padding.unwrap_or(0) as f64
+        + shadow(&opts.shadow)
+        + shadow(&opts.plate.shadow)
+        + shadow(&opts.plate.logo_shadow)
+        + opts.plate.ring.as_ref().map_or(0, |r| r.width) as f64
+        + opts.plate.border_width as f64;
+    opts.size as f64 + 2.0 * around
+}
+
+/// Response bodies by request key, dropping the least recently used when full.
+struct Lru {
+    capacity: usize,
+    /// Body and the tick it was last used at.
+    entries: HashMap<String, (Vec<u8>, u64)>,
+    tick: u64,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<Vec<u8>> {
+        self.tick += 1;
+        let (body, used) = self.entries.get_mut(key)?;
+        *used = self.tick;
+        Some(body.clone())
+    }
+
+    fn insert(&mut self, key: String, body: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (body, self.tick));
+    }
+}
+
+/// A token bucket per client address: `per_minute` requests in a burst, refilled
+/// evenly over the minute.
+struct Limiter {
+    per_minute: u32,
+    /// Tokens left and when they were counted.
+    buckets: HashMap<IpAddr, (f64, Instant)>,
+}
+
+impl Limiter {
+    /// Spend a token for `client`, or return the seconds until one is free.
+    fn take(&mut self, client: IpAddr, now: Instant) -> Result<(), u64> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let full = self.per_minute as f64;
+        let per_second = full / 60.0;
+        let refill = |(tokens, at): (f64, Instant)| {
+            (tokens + now.duration_since(at).as_secs_f64() * per_second).min(full)
+        };
+        if self.buckets.len() >= MAX_CLIENTS {
+            self.buckets.retain(|_, bucket| refill(*bucket) < full);
+        }
+        let bucket = self.buckets.entry(client).or_insert((full, now));
+        let tokens = refill(*bucket);
+        if tokens < 1.0 {
+            *bucket = (tokens, now);
+            return Err(((1.0 - tokens) / per_second).ceil() as u64);
+        }
+        *bucket = (tokens - 1.0, now);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Request {
+    method: String,
+    /// Path and query, as sent.
+    target: String,
+    /// Names lowercased.
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(format: Format, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type: format.content_type(),
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    fn json(status: u16, body: &serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: (body.to_string() + "\n").into_bytes(),
+        }
+    }
+
+    /// A JSON error body: `error`, and `error_code` when the failure has one.
+    fn error(status: u16, message: impl Into<String>, code: Option<ErrorCode>) -> Self {
+        let mut body = json!({ "error": message.into() });
+        if let Some(code) = code {
+            body["error_code"] = json!(code);
+        }
+        Self::json(status, &body)
+    }
+
+    fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        for (name, value) in &self.headers {
+            write!(out, "{}: {}\r\n", name, value)?;
+        }
+        out.write_all(b"\r\n")?;
+        out.write_all(&self.body)?;
+        out.flush()
+    }
+}
+
+pub fn run(args: &Args, serve: &ServeArgs) -> Result<()> {
+    reject_single_code_flags(args, "serve").code(ErrorCode::Usage)?;
+    if let (Some(target), Some(secret)) = (&serve.sign, &serve.signing_key) {
+        let expires = unix_now() + serve.expires_in;
+        println!("{}", auth::sign(secret, target, expires));
+        return Ok(());
+    }
+    let service = Service {
+        server: Server::new(args, "serve", ".")?,
+        cache: Mutex::new(Lru::new(serve.cache_size)),
+        limiter: Mutex::new(Limiter {
+            per_minute: serve.rate_limit,
+            buckets: HashMap::new(),
+        }),
+        metrics: Metrics::default(),
+        auth: Auth {
+            api_keys: serve.api_keys.clone(),
+            signing_key: serve.signing_key.clone(),
+        },
+        limits: Limits {
+            max_size: serve.max_size,
+            allow_paths: serve.allow_paths,
+            allow_logo_urls: serve.allow_logo_urls,
+        },
+    };
+    let listener = TcpListener::bind(&serve.listen)
+        .with_context(|| format!("Failed to listen on {}", serve.listen))?;
+    log::info(format!("Listening on http://{}", serve.listen));
+    // A fixed pool of workers takes connections in turn, so a flood of clients
+    // waits in the queue and the listen backlog rather than each holding a thread
+    // and up to MAX_BODY of memory.
+    let workers = serve.workers.max(1);
+    let (queue, connections) = mpsc::sync_channel::<TcpStream>(workers);
+    let connections = Mutex::new(connections);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let Ok(stream) = cache::lock(&connections).recv() else {
+                        return;
+                    };
+                    service.connection(&stream);
+                }
+            });
+        }
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if queue.send(stream).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => log::warn(format!("failed to accept a connection: {}", e)),
+            }
+        }
+        drop(queue);
+    });
+    Ok(())
+}
+
+/// Read one request: the head line by line, then, if `admit` lets it in, a
+/// `Content-Length` body. `None` when the client closed the connection without
+/// sending anything.
+fn read_request(
+    reader: &mut impl BufRead,
+    mut writer: impl Write,
+    admit: impl FnOnce(&Request) -> Result<(), Response>,
+) -> Result<Option<Request>, Response> {
+    let bad = |message: &str| Response::error(400, message, None);
+    let mut head = String::new();
+    loop {
+        let start = head.len();
+        let read = reader
+            .by_ref()
+            .take((MAX_HEAD + 1 - start) as u64)
+            .read_line(&mut head)
+            .map_err(|_| bad("unreadable request"))?;
+        if head.len() > MAX_HEAD {
+            return Err(Response::error(431, "request head too large", None));
+        }
+        if read == 0 {
+            return match head.trim().is_empty() {
+                true => Ok(None),
+                false => Err(bad("request ended inside its head")),
+            };
+        }
+        // The head ends at the first blank line after the request line.
+        if head[start..].trim().is_empty() && !head[..start].trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut lines = head.lines().map(str::trim).filter(|l| !l.is_empty());
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(bad("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(Response::error(505, "only HTTP/1.x is supported", None));
+    }
+    let mut headers = Vec::new();
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| bad("malformed header"))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+    let mut request = Request {
+        method: method.to_string(),
+        target: target.to_string(),
+        headers,
+        body: Vec::new(),
+    };
+
+    if request.header("transfer-encoding").is_some() {
+        return Err(Response::error(
+            411,
+            "send the body with a Content-Length",
+            None,
+        ));
+    }
+    let length = match request.header("content-length") {
+        Some(length) => length.parse().map_err(|_| bad("invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        let message = format!("request body over {} MB", MAX_BODY >> 20);
+        return Err(Response::error(413, message, None));
+    }
+    admit(&request)?;
+    if length > 0 {
+        let expects = request.header("expect");
+        if expects.is_some_and(|e| e.eq_ignore_ascii_case("100-continue")) {
+            let _ = writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+            let _ = writer.flush();
+        }
+        request.body = vec![0; length];
+        reader
+            .read_exact(&mut request.body)
+            .map_err(|_| bad("request ended inside its body"))?;
+    }
+    Ok(Some(request))
+}
+
+impl Service<'_> {
+    /// Read and answer the request on `stream`.
+    fn connection(&self, stream: &TcpStream) {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let Ok(peer) = stream.peer_addr() else {
+            return;
+        };
+        let mut reader = BufReader::new(stream);
+        if let Some(response) = self.handle(&mut reader, stream, peer.ip())
+            && let Err(e) = response.write(stream)
+        {
+            log::warn(format!("failed to send a response: {}", e));
+        }
+    }
+
+    /// Read a request from `client` and answer it, turning away a rate-limited or
+    /// unauthenticated client before reading its body. `None` when the client sent
+    /// nothing.
+    fn handle(
+        &self,
+        reader: &mut impl BufRead,
+        writer: impl Write,
+        client: IpAddr,
+    ) -> Option<Response> {
+        let mut turned_away = false;
+        let admit = |request: &Request| {
+            self.admit(request, client).inspect_err(|response| {
+                turned_away = true;
+                self.metrics.response(endpoint(request), response.status);
+            })
+        };
+        match read_request(reader, writer, admit) {
+            Ok(Some(request)) => Some(self.respond(&request)),
+            Ok(None) => None,
+            Err(response) => {
+                if !turned_away {
+                    self.metrics.response("other", response.status);
+                }
+                Some(response)
+            }
+        }
+    }
+
+    /// Spend a token of `client`'s rate limit on a render request, then check its
+    /// API key or URL signature.
+    fn admit(&self, request: &Request, client: IpAddr) -> Result<(), Response> {
+        if endpoint(request) != "/render" || !matches!(request.method.as_str(), "GET" | "POST") {
+            return Ok(());
+        }
+        if let Err(wait) = cache::lock(&self.limiter).take(client, Instant::now()) {
+            let message = format!("too many requests; retry in {} s", wait);
+            let mut response = Response::error(429, message, None);
+            response.headers.push(("Retry-After", wait.to_string()));
+            return Err(response);
+        }
+        let api_key = request.header("x-api-key").or_else(|| {
+            request
+                .header("authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+        });
+        let get = request.method == "GET";
+        match self.auth.check(get, &request.target, api_key, unix_now()) {
+            Ok(()) => Ok(()),
+            Err(Denied::Missing) => {
+                let message = "send an API key or use a signed URL";
+                let mut response = Response::error(401, message, None);
+                response
+                    .headers
+                    .push(("WWW-Authenticate", "Bearer".to_string()));
+                Err(response)
+            }
+            Err(Denied::Invalid(why)) => Err(Response::error(403, why, None)),
+        }
+    }
+
+    /// Answer a request, counting the response.
+    fn respond(&self, request: &Request) -> Response {
+        let response = self.answer(request);
+        self.metrics.response(endpoint(request), response.status);
+        response
+    }
+
+    /// Route a request and answer it.
+    fn answer(&self, request: &Request) -> Response {
+        let (path, query) = request
+            .target
+            .split_once('?')
+            .unwrap_or((&request.target, ""));
+        let Some(&(_, allowed)) = ENDPOINTS.iter().find(|(endpoint, _)| *endpoint == path) else {
+            return Response::error(404, format!("no such endpoint: {}", path), None);
+        };
+        if !allowed.split(", ").any(|m| m == request.method) {
+            let mut response = Response::error(405, format!("use {}", allowed), None);
+            response.headers.push(("Allow", allowed.to_string()));
+            return response;
+        }
+        if path == "/healthz" {
+            let status = json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") });
+            return Response::json(200, &status);
+        }
+        if path == "/metrics" {
+            return Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4",
+                headers: Vec::new(),
+                body: self.metrics.export().into_bytes(),
+            };
+        }
+
+        let record = match request.method.as_str() {
+            "GET" => Ok(url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .filter(|(key, _)| key != "expires" && key != "signature")
+                .collect()),
+            _ => serde_json::from_slice(&request.body)
+                .context("invalid JSON body")
+                .and_then(input::json_record),
+        };
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => return Response::error(400, format!("{:#}", e), None),
+        };
+        let Some(format) = negotiate(request.header("accept")) else {
+            return Response::error(
+                406,
+                "expected image/png, image/svg+xml or application/pdf",
+                None,
+            );
+        };
+        let start = Instant::now();
+        match self.render(record, format) {
+            Ok((body, hit)) => {
+                self.metrics.rendered(format.name(), start.elapsed(), hit);
+                let mut response = Response::ok(format, body);
+                let cache = if hit { "HIT" } else { "MISS" };
+                response.headers.push(("X-Cache", cache.to_string()));
+                response
+            }
+            Err(e) => {
+                let code = ErrorCode::of(&e);
+                self.metrics.failed(code, start.elapsed());
+                Response::error(422, format!("{:#}", e), Some(code))
+            }
+        }
+    }
+
+    /// Render `record` as `format`, or answer from the cache when the same code was
+    /// rendered before. Returns the body and whether it came from the cache.
+    fn render(&self, mut record: Record, format: Format) -> Result<(Vec<u8>, bool)> {
+        let server = &self.server;
+        let logo = take_logo(&mut record)?;
+        let index = server.requests.fetch_add(1, Ordering::Relaxed);
+        let job = plan_record(
+            server.args,
+            &server.batch,
+            &template::today(),
+            index,
+            &record,
+        )?;
+        self.limits.check(&record, &logo, &job)?;
+
+        // The job fingerprint covers the payload, caption and parsed options, so
+        // requests that spell the same options differently share an entry.
+        let logo_key = match (&logo, &job.logo) {
+            (Some(Logo::Inline(bytes)), _) => sha256_hex(bytes),
+            (Some(Logo::Url(url)), _) => url.clone(),
+            (None, Some(path)) => {
+                let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+                format!("{:?}", modified)
+            }
+            (None, None) => String::new(),
+        };
+        let texture_key = match &job.opts.fill {
+            Some(crate::modules::Fill::Image(path)) => {
+                let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+                format!("{:?}", modified)
+            }
+            _ => String::new(),
+        };
+        let key = sha256_hex(
+            format!(
+                "{}\n{:?}\n{}\n{}",
+                job.fingerprint, format, logo_key, texture_key
+            )
+            .as_bytes(),
+        );
+        if let Some(body) = cache::lock(&self.cache).get(&key) {
+            return Ok((body, true));
+        }
+
+        let decode = |bytes: &[u8]| -> Result<_> {
+            let logo = image::load_from_memory(bytes).context("Failed to decode logo image")?;
+            Ok(Some(Arc::new(logo)))
+        };
+        let logo = match logo {
+            Some(Logo::Inline(bytes)) => decode(&bytes)?,
+            Some(Logo::Url(url)) => decode(&fetch_logo(&url)?)?,
+            None => job.logo.as_deref().map(cache::logo).transpose()?,
+        };
+        let (body, verified) = draw(server, &self.limits, &job, logo, format)?;
+        if !verified {
+            self.metrics.verification_failed();
+        }
+        cache::lock(&self.cache).insert(key, body.clone());
+        Ok((body, false))
+    }
+}
+
+/// The endpoint `request` is for, or `other`, as metrics label it.
+fn endpoint(request: &Request) -> &'static str {
+    let path = request.target.split('?').next().unwrap_or_default();
+    ENDPOINTS
+        .iter()
+        .map(|&(endpoint, _)| endpoint)
+        .find(|&endpoint| endpoint == path)
+        .unwrap_or("other")
+}
+
+/// The format `accept` prefers, by quality and then by order.
+fn negotiate(accept: Option<&str>) -> Option<Format> {
+    let Some(accept) = accept.filter(|a| !a.trim().is_empty()) else {
+        return Some(Format::Png);
+    };
+    let mut best: Option<(f32, Format)> = None;
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let quality = params
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        let format = match media.as_str() {
+            "image/png" | "image/*" | "*/*" => Format::Png,
+            "image/svg+xml" => Format::Svg,
+            "application/pdf" => Format::Pdf,
+            _ => continue,
+        };
+        if quality > 0.0 && best.is_none_or(|(q, _)| quality > q) {
+            best = Some((quality, format));
+        }
+    }
+    best.map(|(_, format)| format)
+}
+
+/// Draw `job` with `logo` as `format`. Returns the body and whether the module
+/// check passed.
+fn draw(
+    server: &Server,
+    limits: &Limits,
+    job: &Job,
+    logo: Option<Arc<DynamicImage>>,
+    format: Format,
+) -> Result<(Vec<u8>, bool)> {
+    let assets = server.assets_with(logo);
+    let (rendered, out) = render_checked(&assets, job)?;
+    limits.check_rendered(&rendered.image)?;
+    let body = match format {
+        Format::Png => encode_png(
+            &rendered.image,
+            job.embed_alt,
+            job.alt_text.as_deref(),
+            &job.payload,
+        )?,
+        Format::Svg => {
+            // SVG has the modules and quiet zone only, as EPS does.
+            let caption = caption_text(job.show_url, job.alt_text.as_deref(), &job.display);
+            let unsupported = [
+                (rendered.logo_rect.is_some(), "a logo"),
+                (caption.is_some(), "a caption"),
+                (job.label.is_some(), "a label"),
+                (job.opts.corner_radius > 0, "corner_radius"),
+                (job.opts.circle_crop, "circle_crop"),
+                (job.opts.shape == round::Shape::Circle, "shape"),
+                (job.opts.border.is_some(), "border"),
+                (job.opts.shadow.is_some(), "shadow"),
+                (job.opts.module_gap > 0.0, "module_gap"),
+                (job.opts.module_style.is_hollow(), "module_style"),
+                (job.opts.fill.is_some(), "fill_texture"),
+                (job.opts.invert, "invert"),
+                (job.opts.eyes.is_set(), "eye colors"),
+                (!job.opts.eye_glyphs.is_empty(), "eye glyphs"),
+                (job.opts.orientation.is_set(), "rotate or flip"),
+                (
+                    assets.template.is_some() || job.opts.lower_third.is_some(),
+                    "--template",
+                ),
+            ]
+            .into_iter()
+            .find_map(|(given, what)| given.then_some(what));
+            if let Some(what) = unsupported {
+                return Err(anyhow!(
+                    "SVG output draws the bare code and cannot include {}; ask for image/png",
+                    what
+                ))
+                .code(ErrorCode::Usage);
+            }
+            let (_, _, side) = rendered.qr_rect;
+            let opts = &job.opts;
+            let svg = svg::document(
+                &rendered.code,
+                opts.quiet,
+                side,
+                rendered.fg,
+                opts.quiet_color,
+                &alt_text_chunks(job.embed_alt, job.alt_text.as_deref(), &job.payload),
+            );
+            svg.into_bytes()
+        }
+        Format::Pdf => {
+            // One page the size of the image, at --print-size if given.
+            let (_, _, side) = rendered.qr_rect;
+            let pt_per_px = server
+                .args
+                .print_size
+                .map_or(1.0, |length| length.0 / side as f64);
+            pdf::image_page(&rendered.image, pt_per_px, &Marks::default(), &job.payload)
+        }
+    };
+    Ok((body, out.check.passed))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Take `logo_base64` or `logo_url` out of `record`.
+fn take_logo(record: &mut Record) -> Result<Option<Logo>> {
+    let mut take = |key: &str| {
+        let value = record.remove(key);
+        let styled = record.remove(&format!("style.{}", key));
+        value.or(styled).filter(|v| !v.trim().is_empty())
+    };
+    let (inline, url) = (take("logo_base64"), take("logo_url"));
+    if let Some(data) = inline {
+        let bytes = STANDARD
+            .decode(data.trim())
+            .context("`logo_base64` is not valid base64")
+            .code(ErrorCode::Usage)?;
+        return Ok(Some(Logo::Inline(bytes)));
+    }
+    Ok(url.map(Logo::Url))
+}
+
+#[cfg(feature = "net")]
+fn fetch_logo(url: &str) -> Result<Vec<u8>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(15))
+        .build();
+    let response = agent
+        .get(url)
+        .call()
+        .with_context(|| format!("Failed to fetch logo {}", url))
+        .code(ErrorCode::Network)?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_LOGO + 1)
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("Failed to fetch logo {}", url))?;
+    if bytes.len() as u64 > MAX_LOGO {
+        return Err(anyhow!("logo at {} is over {} MB", url, MAX_LOGO >> 20))
+            .code(ErrorCode::LogoUnreadable);
+    }
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "net"))]
+fn fetch_logo(_url: &str) -> Result<Vec<u8>> {
+    anyhow::bail!("`logo_url` needs qrbrand built with the `net` feature; send `logo_base64`")
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        422 => "Unprocessable Content",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        505 => "HTTP Version Not Supported",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    fn service(args: &Args, rate_limit: u32) -> Service<'_> {
+        Service {
+            server: Server::new(args, "serve", ".").unwrap(),
+            cache: Mutex::new(Lru::new(8)),
+            limiter: Mutex::new(Limiter {
+                per_minute: rate_limit,
+                buckets: HashMap::new(),
+            }),
+            metrics: Metrics::default(),
+            auth: Auth::default(),
+            limits: Limits {
+                max_size: 8192,
+                allow_paths: false,
+                allow_logo_urls: false,
+            },
+        }
+    }
+
+    fn request(raw: &str) -> Request {
+        read_request(&mut raw.as_bytes(), std::io::sink(), |_| Ok(()))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(None), Some(Format::Png));
+        assert_eq!(negotiate(Some("image/svg+xml")), Some(Format::Svg));
+        assert_eq!(
+            negotiate(Some("image/png;q=0.5, application/pdf")),
+            Some(Format::Pdf)
+        );
+        assert_eq!(
+            negotiate(Some("text/html, image/svg+xml;q=0.9, */*;q=0.8")),
+            Some(Format::Svg)
+        );
+        assert_eq!(negotiate(Some("text/html")), None);
+        assert_eq!(negotiate(Some("application/pdf;q=0")), None);
+    }
+
+    #[test]
+    fn test_respond() {
+        let args = Args::try_parse_from(["qrbrand", "--url", ""]).unwrap();
+        let service = service(&args, 0);
+        let respond = |request: &Request| service.respond(request);
+
+        let get = request("GET /render?url=https%3A%2F%2Fexample.com&size=200 HTTP/1.1\r\n\r\n");
+        let response = respond(&get);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type, "image/png");
+        let img = image::load_from_memory(&response.body).unwrap();
+        assert!(img.width() <= 200);
+
+        let body = r##"{"url": "https://example.com", "style": {"size": 200, "fg": "#1d4ed8"}}"##;
+        let post = |accept: &str| {
+            request(&format!(
+                "POST /render HTTP/1.1\r\nAccept: {}\r\nContent-Length: {}\r\n\r\n{}",
+                accept,
+                body.len(),
+                body
+            ))
+        };
+        let svg = respond(&post("image/svg+xml"));
+        assert_eq!(svg.content_type, "image/svg+xml");
+        assert!(
+            String::from_utf8(svg.body)
+                .unwrap()
+                .contains(r##"fill="#1d4ed8""##)
+        );
+        let pdf = respond(&post("application/pdf"));
+        assert!(pdf.body.starts_with(b"%PDF-1.4"));
+        assert_eq!(respond(&post("text/html")).status, 406);
+
+        let mut logo = Vec::new();
+        image::RgbaImage::from_pixel(40, 40, image::Rgba([200, 0, 0, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut logo),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        let body = json!({ "url": "https://example.com", "logo_base64": STANDARD.encode(&logo) });
+        let body = body.to_string();
+        let with_logo = |accept: &str| {
+            request(&format!(
+                "POST /render HTTP/1.1\r\nAccept: {}\r\nContent-Length: {}\r\n\r\n{}",
+                accept,
+                body.len(),
+                body
+            ))
+        };
+        let png = respond(&with_logo("image/png"));
+        let img = image::load_from_memory(&png.body).unwrap().to_rgba8();
+        let (cx, cy) = (img.width() / 2, img.height() / 2);
+        assert_eq!(img.get_pixel(cx, cy).0, [200, 0, 0, 255]);
+        let refused = respond(&with_logo("image/svg+xml"));
+        assert_eq!(refused.status, 422);
+        assert!(String::from_utf8(refused.body).unwrap().contains("a logo"));
+
+        // A typed payload instead of `url`, with the subcommand's options.
+        let body = json!({
+            "payload": {"type": "email", "to": "ada@example.com", "subject": "Hi"},
+            "style": {"size": 200}
+        })
+        .to_string();
+        let email = respond(&request(&format!(
+            "POST /render HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )));
+        assert_eq!(email.status, 200);
+        let body = json!({"payload": {"type": "serve"}}).to_string();
+        let refused = respond(&request(&format!(
+            "POST /render HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )));
+        assert_eq!(refused.status, 422);
+
+        let bad = respond(&request("GET /render?url=nope HTTP/1.1\r\n\r\n"));
+        assert_eq!(bad.status, 422);
+        let bad: serde_json::Value = serde_json::from_slice(&bad.body).unwrap();
+        assert_eq!(bad["error_code"], "invalid_payload");
+        assert_eq!(respond(&request("GET /nope HTTP/1.1\r\n\r\n")).status, 404);
+        let delete = respond(&request("DELETE /render HTTP/1.1\r\n\r\n"));
+        assert_eq!((delete.status, delete.headers[0].0), (405, "Allow"));
+        let err = read_request(
+            &mut "POST /render HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n".as_bytes(),
+            std::io::sink(),
+            |_| Ok(()),
+        )
+        .unwrap_err();
+        assert_eq!(err.status, 413);
+    }
+
+    #[test]
+    fn test_limits() {
+        let args = Args::try_parse_from(["qrbrand", "--url", ""]).unwrap();
+        let mut service = service(&args, 0);
+        let get = |service: &Service, query: &str| {
+            let raw = format!(
+                "GET /render?url=https://example.com&{} HTTP/1.1\r\n\r\n",
+                query
+            );
+            let response = service.respond(&request(&raw));
+            let body = String::from_utf8_lossy(&response.body).into_owned();
+            (response.status, body)
+        };
+        let (status, body) = get(&service, "size=200000");
+        assert_eq!(status, 422);
+        assert!(body.contains("--max-size"), "{}", body);
+        let (status, _) = get(&service, "size=8000&border=400px");
+        assert_eq!(status, 422);
+        let (status, _) = get(&service, "shadow=blur=100000,offset=4");
+        assert_eq!(status, 422);
+        // A caption grows the image downwards, a band line at a time.
+        let lines = "a%0A".repeat(1000);
+        let (status, body) = get(&service, &format!("size=200&alt_text={}", lines));
+        assert_eq!(status, 422);
+        assert!(body.contains("at most"), "{}", body);
+        let (status, _) = get(&service, "size=200&alt_text=Menu%0ASpeisekarte");
+        assert_eq!(status, 200);
+        assert!(
+            service
+                .limits
+                .check_rendered(&Canvas::new(200, 9000))
+                .is_err()
+        );
+        assert!(
+            service
+                .limits
+                .check_rendered(&Canvas::new(200, 400))
+                .is_ok()
+        );
+
+        let logo = std::env::temp_dir().join("qrbrand_serve_logo.png");
+        image::RgbaImage::from_pixel(40, 40, image::Rgba([200, 0, 0, 255]))
+            .save(&logo)
+            .unwrap();
+        let by_path = format!("logo={}", logo.display());
+        let (status, body) = get(&service, &by_path);
+        assert_eq!(status, 422);
+        assert!(body.contains("--allow-paths"), "{}", body);
+        let (status, body) = get(&service, "size=200&fill_texture=/etc/hostname");
+        assert_eq!(status, 422);
+        assert!(body.contains("fill_texture"), "{}", body);
+        let (status, body) = get(&service, "size=200&logo_url=http://169.254.169.254/");
+        assert_eq!(status, 422);
+        assert!(body.contains("--allow-logo-urls"), "{}", body);
+
+        service.limits.allow_paths = true;
+        let (status, body) = get(&service, &by_path);
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    #[test]
+    fn test_cache_and_limit() {
+        let args = Args::try_parse_from(["qrbrand", "--url", ""]).unwrap();
+        let service = service(&args, 2);
+        let handle = |raw: &str| {
+            let response = service.handle(&mut raw.as_bytes(), std::io::sink(), CLIENT);
+            response.unwrap()
+        };
+        let respond = |target: &str| handle(&format!("GET {} HTTP/1.1\r\n\r\n", target));
+        for _ in 0..3 {
+            assert_eq!(respond("/healthz").status, 200);
+        }
+        // The same options spelled two ways are one cache entry.
+        let first = respond("/render?url=https://example.com&size=200");
+        assert_eq!(first.headers, [("X-Cache", "MISS".to_string())]);
+        let second = respond("/render?url=https://example.com&style.size=200");
+        assert_eq!(second.headers, [("X-Cache", "HIT".to_string())]);
+        assert_eq!(first.body, second.body);
+        let limited = respond("/render?url=https://example.com");
+        assert_eq!(limited.status, 429);
+        assert_eq!(limited.headers, [("Retry-After", "30".to_string())]);
+        // Turned away before the body is read: this one never sends its body.
+        let post = handle("POST /render HTTP/1.1\r\nContent-Length: 1000\r\n\r\n");
+        assert_eq!(post.status, 429);
+        let metrics = respond("/metrics");
+        assert_eq!(metrics.status, 200);
+        let text = String::from_utf8(metrics.body).unwrap();
+        for line in [
+            "qrbrand_http_responses_total{endpoint=\"/healthz\",status=\"200\"} 3",
+            "qrbrand_http_responses_total{endpoint=\"/render\",status=\"429\"} 2",
+            "qrbrand_renders_total{format=\"png\"} 2",
+            "qrbrand_cache_hits_total 1",
+            "qrbrand_verification_failures_total 0",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {}", line);
+        }
+
+        let mut lru = Lru::new(2);
+        lru.insert("a".to_string(), vec![1]);
+        lru.insert("b".to_string(), vec![2]);
+        assert_eq!(lru.get("a"), Some(vec![1]));
+        lru.insert("c".to_string(), vec![3]);
+        assert_eq!(lru.get("b"), None);
+        assert_eq!(lru.get("a"), Some(vec![1]));
+
+        let mut limiter = Limiter {
+            per_minute: 60,
+            buckets: HashMap::new(),
+        };
+        let now = Instant::now();
+        for _ in 0..60 {
+            assert_eq!(limiter.take(CLIENT, now), Ok(()));
+        }
+        assert_eq!(limiter.take(CLIENT, now), Err(1));
+        assert_eq!(limiter.take(CLIENT, now + Duration::from_secs(1)), Ok(()));
+    }
+
+    #[test]
+    fn test_auth() {
+        let args = Args::try_parse_from(["qrbrand", "--url", ""]).unwrap();
+        let mut service = service(&args, 0);
+        service.auth = Auth {
+            api_keys: vec!["k1".to_string()],
+            signing_key: Some("secret".to_string()),
+        };
+        let target = "/render?url=https://example.com";
+        let get = |raw: String| {
+            let response = service.handle(&mut raw.as_bytes(), std::io::sink(), CLIENT);
+            response.unwrap().status
+        };
+        assert_eq!(get(format!("GET {} HTTP/1.1\r\n\r\n", target)), 401);
+        assert_eq!(
+            get(format!("GET {} HTTP/1.1\r\nX-API-Key: k1\r\n\r\n", target)),
+            200
+        );
+        assert_eq!(
+            get(format!(
+                "GET {} HTTP/1.1\r\nAuthorization: Bearer k2\r\n\r\n",
+                target
+            )),
+            403
+        );
+        let signed = auth::sign("secret", target, unix_now() + 60);
+        assert_eq!(get(format!("GET {} HTTP/1.1\r\n\r\n", signed)), 200);
+        assert_eq!(get("GET /healthz HTTP/1.1\r\n\r\n".to_string()), 200);
+
+        // Turned away before the body is read: this one never sends its body, and
+        // is not told to go ahead with it.
+        let mut written = Vec::new();
+        let raw =
+            "POST /render HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 4000000\r\n\r\n";
+        let post = service.handle(&mut raw.as_bytes(), &mut written, CLIENT);
+        assert_eq!(post.unwrap().status, 401);
+        assert!(written.is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! `qrbrand bench`: time each rendering stage across a payload and size matrix.
+//!
+//! Stages are timed separately (symbol encoding, module rendering, logo composite,
+//! caption layout and PNG encoding) so a regression points at the code that caused it.
+//! Each cell is the median of `--iterations` runs after one untimed warm-up. Style
+//! flags apply as usual; without `--image` a generated logo stands in, so the logo
+//! stage is always measured.
+
+use crate::log::LogFormat;
+use crate::{
+    Args, LogoPlacement, RenderOptions, add_url_text_below, budget, cache, encode_png,
+    overlay_logo_center, render_qr_rgba, symbol,
+};
+use anyhow::{Result, bail};
+use clap::Args as ClapArgs;
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(ClapArgs, Debug)]
+pub struct BenchArgs {
+    /// QR sizes in pixels to render at
+    #[arg(long = "sizes", value_delimiter = ',', default_values_t = [512, 2048, 8192])]
+    pub sizes: Vec<u32>,
+
+    /// Timed runs per cell; the median is reported
+    #[arg(long = "iterations", default_value_t = 5)]
+    pub iterations: usize,
+}
+
+/// Payloads timed when no `--url` is given: typical, tracked and worst-case lengths.
+const PAYLOADS: &[(&str, &str)] = &[
+    ("short", "https://example.com"),
+    (
+        "utm",
+        "https://www.example.com/spring/promo?utm_source=poster&utm_medium=print&utm_campaign=spring-sale",
+    ),
+    (
+        "long",
+        "https://www.example.com/catalog/outdoor/tents/ultralight/two-person/?color=forest-green&size=regular&ref=store-42&utm_source=in-store-poster&utm_medium=print&utm_campaign=spring-sale-2025&utm_content=aisle-endcap-b&session=8f3a9c2e1d7b4a6f9e0c3b5a7d2f4e6a8c0b1d3f5e7a9c2e4b6d8f0a1c3e5b7d9f",
+    ),
+];
+
+/// One cell of the matrix, times in milliseconds.
+#[derive(Serialize, Debug)]
+struct Row {
+    payload: String,
+    size: u32,
+    /// Modules per side.
+    modules: usize,
+    encode_ms: f64,
+    render_ms: f64,
+    logo_ms: f64,
+    caption_ms: f64,
+    png_ms: f64,
+    total_ms: f64,
+}
+
+pub fn run(args: &Args, bench: &BenchArgs) -> Result<()> {
+    let rows = measure(args, bench)?;
+    if args.log_format == LogFormat::Json {
+        for row in &rows {
+            println!("{}", serde_json::to_string(row)?);
+        }
+        return Ok(());
+    }
+    println!(
+        "{:<8} {:>6} {:>7} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "payload", "size", "modules", "encode", "render", "logo", "caption", "png", "total"
+    );
+    for row in &rows {
+        println!(
+            "{:<8} {:>6} {:>7} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            row.payload,
+            row.size,
+            row.modules,
+            row.encode_ms,
+            row.render_ms,
+            row.logo_ms,
+            row.caption_ms,
+            row.png_ms,
+            row.total_ms
+        );
+    }
+    println!(
+        "Median of {} runs per cell, in milliseconds",
+        bench.iterations
+    );
+    Ok(())
+}
+
+fn measure(args: &Args, bench: &BenchArgs) -> Result<Vec<Row>> {
+    if bench.iterations == 0 {
+        bail!("--iterations must be at least 1");
+    }
+    let payloads: Vec<(&str, &str)> = match args.url.as_deref().filter(|u| !u.is_empty()) {
+        Some(url) => vec![("url", url)],
+        None => PAYLOADS.to_vec(),
+    };
+    let opts = RenderOptions::from_args(args);
+    let font = cache::font(args.font_family.as_deref())?;
+    let logo = match args.image.as_deref() {
+        Some(path) => cache::logo(path)?,
+        None => Arc::new(sample_logo()),
+    };
+    let scale = match opts.logo_scale {
+        budget::LogoScale::Fixed(scale) => scale,
+        budget::LogoScale::Auto => 0.2,
+    };
+
+    let mut rows = Vec::new();
+    for &(name, payload) in &payloads {
+        for &size in &bench.sizes {
+            let mut times: [Vec<Duration>; 5] = Default::default();
+            let mut modules = 0;
+            // The first run warms caches and is not counted.
+            for run in 0..=bench.iterations {
+                let mut stage = 0;
+                let mut time = |times: &mut [Vec<Duration>; 5], start: Instant| {
+                    if run > 0 {
+                        times[stage].push(start.elapsed());
+                    }
+                    stage += 1;
+                };
+
+                let start = Instant::now();
+                let code = symbol::encode(payload, &opts.encode)?;
+                time(&mut times, start);
+                modules = code.width();
+
+                let start = Instant::now();
+                let mut img = render_qr_rgba(&code, size, opts.quiet, opts.fg)?;
+                time(&mut times, start);
+
+                let start = Instant::now();
+                let placement = LogoPlacement::new(
+                    img.width(),
+                    &logo,
+                    scale,
+                    opts.logo_plate,
+                    opts.logo_pad,
+                    &opts.plate,
+                )?;
+                overlay_logo_center(&mut img, &logo, &placement, &opts.plate);
+                time(&mut times, start);
+
+                let start = Instant::now();
+                let img = add_url_text_below(&img, payload, &font, &opts.caption)?;
+                time(&mut times, start);
+
+                let start = Instant::now();
+                encode_png(&img, false, None, payload)?;
+                time(&mut times, start);
+            }
+            let [encode, render, logo, caption, png] = times.map(median_ms);
+            rows.push(Row {
+                payload: name.to_string(),
+                size,
+                modules,
+                encode_ms: encode,
+                render_ms: render,
+                logo_ms: logo,
+                caption_ms: caption,
+                png_ms: png,
+                total_ms: encode + render + logo + caption + png,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+fn median_ms(mut times: Vec<Duration>) -> f64 {
+    times.sort();
+    times[times.len() / 2].as_secs_f64() * 1000.0
+}
+
+/// A 256 px two-tone badge standing in for a real logo.
+fn sample_logo() -> DynamicImage {
+    let img = RgbaImage::from_fn(256, 256, |x, y| {
+        if (x / 32 + y / 32) % 2 == 0 {
+            Rgba([29, 78, 216, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    DynamicImage::ImageRgba8(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_measure() {
+        let args = Args::try_parse_from(["qrbrand", "--url", ""]).unwrap();
+        let bench = BenchArgs {
+            sizes: vec![200, 400],
+            iterations: 1,
+        };
+        let rows = measure(&args, &bench).unwrap();
+        assert_eq!(rows.len(), PAYLOADS.len() * 2);
+        assert!(rows[0].modules < rows[rows.len() - 1].modules);
+        assert!(rows.iter().all(|r| r.total_ms > 0.0));
+    }
+}
@@ -0,0 +1,278 @@
+//! Logo coverage measured against the error correction budget.
+//!
+//! An area percentage says little about scannability: hidden modules matter only
+//! through the codewords they belong to, and each Reed-Solomon block repairs its own
+//! codewords independently. This maps the modules under the logo or plate onto
+//! their codewords and compares the damage in each block with what that block can
+//! correct, keeping a reserve for smudges, glare and print defects.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::layout::Layout;
+use crate::raster::Shape;
+use crate::symbol::Symbol;
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Share of each block's correction capacity kept free of logo damage.
+pub const DEFAULT_RESERVE: f32 = 0.4;
+
+/// `--logo-scale`: a fraction of the QR width, or the largest that fits the budget.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogoScale {
+    Auto,
+    Fixed(f32),
+}
+
+impl FromStr for LogoScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+        s.trim()
+            .parse()
+            .map(Self::Fixed)
+            .map_err(|_| format!("expected a fraction such as 0.2, or auto, got '{}'", s))
+    }
+}
+
+impl fmt::Display for LogoScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Fixed(scale) => write!(f, "{}", scale),
+        }
+    }
+}
+
+/// What to do when the logo would hide more than error correction can safely repair.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogoFit {
+    /// Refuse to render
+    Fail,
+    /// Shrink the logo until it fits the budget
+    Shrink,
+}
+
+impl FromStr for LogoFit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coverage {
+    /// Symbol modules under the logo or plate.
+    pub hidden_modules: usize,
+    /// Hidden modules of the finder patterns, separators, timing patterns or format information.
+    pub function_modules: usize,
+    /// Damaged codewords in each error correction block.
+    pub damaged: Vec<usize>,
+    /// Codewords each block can correct.
+    pub correctable: usize,
+}
+
+impl Coverage {
+    pub fn damaged_codewords(&self) -> usize {
+        self.damaged.iter().sum()
+    }
+
+    /// Share of the worst block's correction capacity the logo uses up.
+    pub fn worst_share(&self) -> f32 {
+        let worst = self.damaged.iter().copied().max().unwrap_or(0);
+        if self.correctable == 0 {
+            return if worst == 0 { 0.0 } else { f32::INFINITY };
+        }
+        worst as f32 / self.correctable as f32
+    }
+
+    /// Whether every block keeps `reserve` of its correction capacity for other damage.
+    pub fn fits(&self, reserve: f32) -> bool {
+        self.function_modules == 0 && self.worst_share() <= 1.0 - reserve
+    }
+
+    /// Refuse coverage that does not fit, explaining which limit it broke.
+    pub fn check(&self, reserve: f32) -> Result<()> {
+        if self.function_modules > 0 {
+            return Err(anyhow!(
+                "the logo hides {} modules of the finder, timing or format patterns; scanners cannot read the code",
+                self.function_modules
+            ))
+            .code(ErrorCode::LogoTooLarge);
+        }
+        if !self.fits(reserve) {
+            return Err(anyhow!(
+                "{}, over the safe {:.0}%; lower --logo-scale or --logo-pad, raise --ec-level, or use --logo-scale auto or --logo-fit shrink",
+                self.describe(),
+                (1.0 - reserve) * 100.0
+            ))
+            .code(ErrorCode::LogoTooLarge);
+        }
+        Ok(())
+    }
+
+    /// e.g. `logo hides 14 codewords; the worst block loses 5 of the 11 it can correct (45%)`.
+    pub fn describe(&self) -> String {
+        let worst = self.damaged.iter().copied().max().unwrap_or(0);
+        format!(
+            "logo hides {} codewords; the worst block loses {} of the {} it can correct ({:.0}%)",
+            self.damaged_codewords(),
+            worst,
+            self.correctable,
+            self.worst_share() * 100.0
+        )
+    }
+}
+
+/// Find the codewords hidden by `shape` (in pixels of the QR square, which has
+/// `quiet_modules` of quiet zone and `ppm` pixels per module). A module counts as
+/// hidden once the shape reaches the middle half of it, where scanners sample.
+pub fn analyze(code: &Symbol, quiet_modules: u32, ppm: u32, shape: &impl Shape) -> Coverage {
+    let version = code.version();
+    let n = code.width();
+    let layout = Layout::new(version, code.error_correction_level());
+    let (bx0, by0, bx1, by1) = shape.bounds();
+    let quarter = ppm as f32 / 4.0;
+    let hidden = |x: usize, y: usize| {
+        let cx = ((x as u32 + quiet_modules) * ppm) as f32 + ppm as f32 / 2.0;
+        let cy = ((y as u32 + quiet_modules) * ppm) as f32 + ppm as f32 / 2.0;
+        if cx + quarter < bx0 || cx - quarter > bx1 || cy + quarter < by0 || cy - quarter > by1 {
+            return false;
+        }
+        [
+            (0.0, 0.0),
+            (-1.0, -1.0),
+            (1.0, -1.0),
+            (-1.0, 1.0),
+            (1.0, 1.0),
+        ]
+        .iter()
+        .any(|&(dx, dy)| shape.contains(cx + dx * quarter, cy + dy * quarter))
+    };
+
+    let mut damaged_codewords = BTreeSet::new();
+    for ((x, y), cw) in layout.module_codewords() {
+        if hidden(x, y) {
+            damaged_codewords.insert(cw);
+        }
+    }
+
+    let w = n as i16;
+    let mut hidden_modules = 0;
+    let mut function_modules = 0;
+    for y in 0..n {
+        for x in 0..n {
+            if !hidden(x, y) {
+                continue;
+            }
+            hidden_modules += 1;
+            // The dark module next to the lower format copy carries no information.
+            let dark_module = (x, y) == (8, n - 8);
+            let critical = x == 6
+                || y == 6
+                || (x < 9 && y < 9)
+                || (x < 9 && y >= n - 8)
+                || (x >= n - 8 && y < 9);
+            if critical
+                && !dark_module
+                && qrcode::canvas::is_functional(version, w, x as i16, y as i16)
+            {
+                function_modules += 1;
+            }
+        }
+    }
+
+    let mut damaged = vec![0; layout.blocks()];
+    for cw in damaged_codewords {
+        damaged[layout.block_of(cw).0] += 1;
+    }
+    Coverage {
+        hidden_modules,
+        function_modules,
+        damaged,
+        correctable: layout.correctable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster::RoundedRect;
+    use crate::symbol::{self, EncodeOptions};
+    use qrcode::EcLevel;
+
+    fn centred(code: &Symbol, ppm: u32, side_modules: f32) -> RoundedRect {
+        let qr = (code.width() as u32 + 8) * ppm;
+        let side = side_modules * ppm as f32;
+        let at = (qr as f32 - side) / 2.0;
+        RoundedRect::new(at, at, side, side, 0.0)
+    }
+
+    #[test]
+    fn test_analyze_counts_blocks() {
+        let code = symbol::encode("https://example.com/products/42", &Default::default()).unwrap();
+        let none = analyze(&code, 4, 10, &RoundedRect::new(0.0, 0.0, 0.0, 0.0, 0.0));
+        assert_eq!(none.damaged_codewords(), 0);
+        assert!(none.fits(DEFAULT_RESERVE));
+
+        let small = analyze(&code, 4, 10, &centred(&code, 10, 5.0));
+        assert_eq!(small.hidden_modules, 25);
+        assert_eq!(small.function_modules, 0);
+        // Every hidden data module belongs to some damaged codeword, 8 modules at most each.
+        assert!(small.damaged_codewords() >= 25 / 8);
+        assert!(small.fits(DEFAULT_RESERVE), "{}", small.describe());
+        small.check(DEFAULT_RESERVE).unwrap();
+
+        let huge = analyze(&code, 4, 10, &centred(&code, 10, code.width() as f32 * 0.4));
+        assert!(!huge.fits(DEFAULT_RESERVE));
+        let err = huge.check(DEFAULT_RESERVE).unwrap_err().to_string();
+        assert!(err.contains("--logo-fit shrink"), "{}", err);
+    }
+
+    #[test]
+    fn test_low_ec_has_less_room() {
+        let text = "https://example.com/products/42";
+        let low = EncodeOptions {
+            ec_level: EcLevel::L,
+            ec_boost: false,
+            ..Default::default()
+        };
+        let strong = symbol::encode(text, &Default::default()).unwrap();
+        let weak = symbol::encode(text, &low).unwrap();
+        let side = 0.25;
+        let on_strong = analyze(
+            &strong,
+            4,
+            10,
+            &centred(&strong, 10, strong.width() as f32 * side),
+        );
+        let on_weak = analyze(
+            &weak,
+            4,
+            10,
+            &centred(&weak, 10, weak.width() as f32 * side),
+        );
+        assert!(on_weak.worst_share() > on_strong.worst_share());
+        assert!(!on_weak.fits(DEFAULT_RESERVE));
+    }
+
+    #[test]
+    fn test_function_patterns_are_fatal() {
+        let code = symbol::encode("https://example.com", &Default::default()).unwrap();
+        // A band across the timing row.
+        let band = RoundedRect::new(0.0, 100.0, 330.0, 10.0, 0.0);
+        let coverage = analyze(&code, 4, 10, &band);
+        assert!(coverage.function_modules > 0);
+        assert!(coverage.check(0.0).is_err());
+        assert_eq!("SHRINK".parse::<LogoFit>(), Ok(LogoFit::Shrink));
+        assert_eq!("auto".parse::<LogoScale>(), Ok(LogoScale::Auto));
+        assert_eq!("0.25".parse::<LogoScale>(), Ok(LogoScale::Fixed(0.25)));
+        assert!("big".parse::<LogoScale>().is_err());
+    }
+}
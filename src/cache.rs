@@ -0,0 +1,123 @@
+//! Process-wide caches of decoded fonts, logos, fill textures and eye glyphs, shared by single
+//! renders, batch jobs, daemon requests and the library API.
+//!
+//! A font is parsed once per family for the life of the process. Images are kept by
+//! path and decoded again only when the file's modification time changes, so a
+//! long-running daemon picks up an edited logo without a restart.
+
+use crate::fonts::{self, FontChain};
+use crate::glyph;
+use crate::{load_embedded_font, load_logo, load_texture};
+use anyhow::Result;
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::SystemTime;
+
+/// Decoded images by path, with the modification time they were read at.
+type Images = HashMap<PathBuf, (SystemTime, Arc<DynamicImage>)>;
+
+static EMBEDDED: OnceLock<Arc<FontChain>> = OnceLock::new();
+static FAMILIES: OnceLock<Mutex<HashMap<String, Arc<FontChain>>>> = OnceLock::new();
+static LOGOS: OnceLock<Mutex<Images>> = OnceLock::new();
+static TEXTURES: OnceLock<Mutex<Images>> = OnceLock::new();
+static GLYPHS: OnceLock<Mutex<Images>> = OnceLock::new();
+
+/// The installed `family` if given, otherwise the embedded font.
+pub fn font(family: Option<&str>) -> Result<Arc<FontChain>> {
+    let Some(name) = family else {
+        if let Some(font) = EMBEDDED.get() {
+            return Ok(Arc::clone(font));
+        }
+        let font = Arc::new(FontChain::new(load_embedded_font()?));
+        return Ok(Arc::clone(EMBEDDED.get_or_init(|| font)));
+    };
+    let mut families = lock(FAMILIES.get_or_init(Default::default));
+    if let Some(font) = families.get(name) {
+        return Ok(Arc::clone(font));
+    }
+    let font = Arc::new(FontChain::new(fonts::load_family(name)?));
+    families.insert(name.to_string(), Arc::clone(&font));
+    Ok(font)
+}
+
+/// The logo at `path`, decoded again if the file changed since it was cached. Files
+/// without a modification time (or on platforms without one) are not cached.
+pub fn logo(path: &str) -> Result<Arc<DynamicImage>> {
+    image(&LOGOS, path, load_logo)
+}
+
+/// The --fill-texture image at `path`, cached as logos are.
+pub fn texture(path: &str) -> Result<Arc<DynamicImage>> {
+    image(&TEXTURES, path, load_texture)
+}
+
+/// The --eye-glyph at `path`, SVG rasterized, cached as logos are.
+pub fn glyph(path: &str) -> Result<Arc<DynamicImage>> {
+    image(&GLYPHS, path, glyph::load)
+}
+
+fn image(
+    cache: &OnceLock<Mutex<Images>>,
+    path: &str,
+    load: fn(&str) -> Result<DynamicImage>,
+) -> Result<Arc<DynamicImage>> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let images = cache.get_or_init(Default::default);
+    if let Some(modified) = modified
+        && let Some((at, image)) = lock(images).get(Path::new(path))
+        && *at == modified
+    {
+        return Ok(Arc::clone(image));
+    }
+    // Decode outside the lock so batch workers do not wait on each other.
+    let image = Arc::new(load(path)?);
+    if let Some(modified) = modified {
+        lock(images).insert(PathBuf::from(path), (modified, Arc::clone(&image)));
+    }
+    Ok(image)
+}
+
+/// A panic while a cache was locked leaves it consistent, so keep using it.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cache() {
+        assert!(Arc::ptr_eq(&font(None).unwrap(), &font(None).unwrap()));
+
+        let path = std::env::temp_dir().join("qrbrand_cache_logo.png");
+        let write = |color: u8, age: u64| {
+            image::RgbaImage::from_pixel(4, 4, image::Rgba([color, 0, 0, 255]))
+                .save(&path)
+                .unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(age);
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let red = |logo: &DynamicImage| logo.to_rgba8().get_pixel(0, 0)[0];
+        let name = path.to_str().unwrap();
+
+        write(10, 60);
+        let first = logo(name).unwrap();
+        assert!(Arc::ptr_eq(&first, &logo(name).unwrap()));
+        write(20, 30);
+        let edited = logo(name).unwrap();
+        assert_eq!((red(&first), red(&edited)), (10, 20));
+
+        let _ = std::fs::remove_file(&path);
+        assert!(logo(name).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! `qrbrand card`: a print-ready business card as PDF.
+//!
+//! One face at 85 x 55 mm (or 3.5 x 2 in): the code on one side, and on the other the
+//! `--image` logo, the name, the title and any contact lines. Everything stays inside a
+//! 4 mm safe margin; the `--canvas-color` background runs into the bleed, and crop marks
+//! sit outside it. The code is drawn as vector modules and the text is set in the caption
+//! font, embedded.
+
+use crate::color::Color;
+use crate::eps::Length;
+use crate::error::{ErrorCode, WithCode};
+use crate::marks::{self, HAIRLINE, Marks};
+use crate::pdf::{self, Content, Document, MM, Style};
+use crate::symbol::Symbol;
+use crate::{Args, Assets, RenderOptions};
+use anyhow::{Result, anyhow};
+use clap::{Args as ClapArgs, ValueEnum};
+use image::DynamicImage;
+
+#[derive(ClapArgs, Debug)]
+pub struct CardArgs {
+    /// Name, the largest text on the card
+    #[arg(long = "name")]
+    pub name: String,
+
+    /// Job title under the name
+    #[arg(long = "title")]
+    pub title: Option<String>,
+
+    /// Contact line (email, phone, address) at the bottom; repeat for more
+    #[arg(long = "line", value_name = "TEXT")]
+    pub lines: Vec<String>,
+
+    /// Card format: eu (85 x 55 mm) or us (3.5 x 2 in)
+    #[arg(long = "card-size", value_enum, default_value_t = CardSize::Eu)]
+    pub card_size: CardSize,
+
+    /// Side of the card the code goes on
+    #[arg(long = "qr-side", value_enum, default_value_t = Side::Right)]
+    pub qr_side: Side,
+
+    /// How far the background runs past the trim: 3mm, 0.125in, ...
+    #[arg(long = "bleed", value_name = "LENGTH", default_value = "3mm")]
+    pub bleed: Length,
+
+    /// Leave out the crop marks
+    #[arg(long = "no-crop-marks", default_value_t = false)]
+    pub no_crop_marks: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CardSize {
+    /// 85 x 55 mm, usual in Europe
+    Eu,
+    /// 3.5 x 2 in, usual in North America
+    Us,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl CardSize {
+    /// Trimmed width and height in points.
+    fn trim(self) -> (f64, f64) {
+        match self {
+            Self::Eu => (85.0 * MM, 55.0 * MM),
+            Self::Us => (3.5 * 72.0, 2.0 * 72.0),
+        }
+    }
+}
+
+/// Distance kept between the trim and anything printed.
+const SAFE: f64 = 4.0 * MM;
+/// Largest type size for the name, title and contact lines; each shrinks to fit.
+const NAME_PT: f64 = 12.0;
+const TITLE_PT: f64 = 8.0;
+const LINE_PT: f64 = 7.0;
+
+pub fn run(args: &Args, card: &CardArgs, payload: &str, out_given: bool) -> Result<()> {
+    let opts = RenderOptions::from_args(args);
+    let code = pdf::encode_checked(payload, &opts)?;
+    let texts = [Some(&card.name), card.title.as_ref()];
+    pdf::warn_unencodable(texts.into_iter().flatten().chain(&card.lines));
+
+    let assets = Assets::load(args.image.as_deref(), args.font_family.as_deref(), None)?;
+    let style = Style::new(args, &opts);
+    let bytes = document(
+        card,
+        &code,
+        opts.quiet,
+        &style,
+        assets.logo.as_deref(),
+        &assets.font.face(0),
+    )?;
+
+    let out = if out_given {
+        args.out.as_str()
+    } else {
+        "card.pdf"
+    };
+    pdf::save(args, payload, out, &bytes)
+}
+
+/// Lay out the card and return the PDF.
+fn document(
+    card: &CardArgs,
+    code: &Symbol,
+    quiet: u32,
+    style: &Style,
+    logo: Option<&DynamicImage>,
+    face: &crate::fonts::FontFace,
+) -> Result<Vec<u8>> {
+    let (w, h) = card.card_size.trim();
+    let bleed = card.bleed.0;
+    let marks = Marks {
+        bleed,
+        crop: !card.no_crop_marks,
+    };
+    let margin = marks.margin();
+    let (left, bottom) = (margin, margin);
+    let (right, top) = (left + w, bottom + h);
+    let mut doc = Document::default();
+    let font = doc.font(face)?;
+    let mut content = Content::default();
+
+    if style.background.0[3] > 0 {
+        content.fill_color(style.background);
+        content.fill_rect(
+            left - bleed,
+            bottom - bleed,
+            w + 2.0 * bleed,
+            h + 2.0 * bleed,
+        );
+    }
+
+    // The code fills the card's height inside the safe margin.
+    let side = h - 2.0 * SAFE;
+    let (qr_x, text_left, text_right) = match card.qr_side {
+        Side::Right => (right - SAFE - side, left + SAFE, right - 2.0 * SAFE - side),
+        Side::Left => (left + SAFE, left + 2.0 * SAFE + side, right - SAFE),
+    };
+    content.symbol(
+        code,
+        quiet,
+        qr_x,
+        bottom + SAFE,
+        side,
+        style.fg,
+        style.quiet,
+    );
+
+    let width = text_right - text_left;
+    let mut y = top - SAFE;
+    if let Some(logo) = logo {
+        let (image, dw, dh) = doc.logo(logo, width, h * 0.28);
+        content.image(&image, text_left, y - dh, dw, dh);
+        y -= dh + 3.0 * MM;
+    }
+
+    content.fill_color(style.text);
+    let mut set = |text: &str, max_pt: f64, baseline: f64| {
+        let size = font.fit(text, max_pt, width);
+        content.text(&font, size, text_left, baseline, text);
+    };
+    y -= font.ascent / 1000.0 * NAME_PT;
+    set(&card.name, NAME_PT, y);
+    if let Some(title) = &card.title {
+        y -= TITLE_PT * 1.4;
+        set(title, TITLE_PT, y);
+    }
+    let upper = y + font.descent / 1000.0 * TITLE_PT;
+
+    // Contact lines stack up from the bottom of the safe area.
+    let mut baseline = bottom + SAFE - font.descent / 1000.0 * LINE_PT;
+    for line in card.lines.iter().rev() {
+        set(line, LINE_PT, baseline);
+        baseline += LINE_PT * 1.3;
+    }
+    let lines_top = baseline - LINE_PT * 1.3 + font.ascent / 1000.0 * LINE_PT;
+    if !card.lines.is_empty() && lines_top > upper {
+        return Err(anyhow!(
+            "the card text does not fit; use fewer --line entries or a smaller logo"
+        ))
+        .code(ErrorCode::Usage);
+    }
+
+    let trim = [left, bottom, right, top];
+    if marks.crop {
+        content.stroke_color(Color::BLACK, HAIRLINE);
+        for [x0, y0, x1, y1] in marks::crop_lines(trim, bleed) {
+            content.line(x0, y0, x1, y1);
+        }
+    }
+    let bleed_box = [left - bleed, bottom - bleed, right + bleed, top + bleed];
+    doc.page(right + margin, top + margin, trim, bleed_box, content);
+    Ok(doc.finish(&card.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_embedded_font;
+
+    fn card(lines: &[&str]) -> CardArgs {
+        CardArgs {
+            name: "Ada Lovelace".to_string(),
+            title: Some("Analyst".to_string()),
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+            card_size: CardSize::Eu,
+            qr_side: Side::Right,
+            bleed: "3mm".parse().unwrap(),
+            no_crop_marks: false,
+        }
+    }
+
+    #[test]
+    fn test_card_layout() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let style = Style {
+            fg: Color::BLACK,
+            quiet: Color::WHITE,
+            text: Color::BLACK,
+            background: "#fef3c7".parse().unwrap(),
+        };
+        let face = load_embedded_font().unwrap();
+        let pdf = document(&card(&["ada@example.com"]), &code, 4, &style, None, &face).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        let page_box = |name: &str| -> Vec<f64> {
+            let start = text.find(&format!("/{} [", name)).unwrap() + name.len() + 3;
+            let end = start + text[start..].find(']').unwrap();
+            text[start..end]
+                .split(' ')
+                .map(|v| (v.parse::<f64>().unwrap() / MM * 100.0).round() / 100.0)
+                .collect()
+        };
+        // 85 x 55 mm trimmed, 3 mm of bleed, then 6 mm of slug for the marks.
+        assert_eq!(page_box("MediaBox"), [0.0, 0.0, 103.0, 73.0]);
+        assert_eq!(page_box("BleedBox"), [6.0, 6.0, 97.0, 67.0]);
+        assert_eq!(page_box("TrimBox"), [9.0, 9.0, 94.0, 64.0]);
+
+        // Too many contact lines to fit beside a 47 mm code are refused.
+        let crowded = card(&["a line"; 16]);
+        assert!(document(&crowded, &code, 4, &style, None, &face).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Code 128 barcodes for printable ASCII, for scanners that only read 1D symbols.
+//!
+//! Text goes in code set B, switching to code set C for runs of digits long enough
+//! to save space (two digits per symbol), as the GS1 general specifications advise:
+//! four or more at the start or end, six or more in the middle.
+
+use anyhow::{Result, bail};
+
+/// Bar and space widths of each symbol value, bar first; 106 is the stop pattern.
+const PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+
+const CODE_C: u8 = 99;
+const CODE_B: u8 = 100;
+const START_B: u8 = 104;
+const START_C: u8 = 105;
+const STOP: u8 = 106;
+
+/// Quiet zone required on each side, in modules.
+pub const QUIET: usize = 10;
+
+/// The bars of `text` from start to stop pattern, one entry per module (`true` is a
+/// bar), without the quiet zone.
+pub fn encode(text: &str) -> Result<Vec<bool>> {
+    let mut modules = Vec::new();
+    for value in values(text)? {
+        let mut bar = true;
+        for width in PATTERNS[value as usize].bytes() {
+            modules.extend(std::iter::repeat_n(bar, (width - b'0') as usize));
+            bar = !bar;
+        }
+    }
+    Ok(modules)
+}
+
+/// Symbol values for `text`: start, data, check symbol and stop.
+fn values(text: &str) -> Result<Vec<u8>> {
+    if text.is_empty() {
+        bail!("Code 128 needs at least one character");
+    }
+    if let Some(c) = text.chars().find(|c| !(' '..='~').contains(c)) {
+        bail!("Code 128 takes printable ASCII only; cannot encode {:?}", c);
+    }
+    let bytes = text.as_bytes();
+    let digits_from = |i: usize| bytes[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+
+    let mut values = Vec::with_capacity(bytes.len() + 3);
+    let mut in_c = digits_from(0) >= 4 || (digits_from(0) == 2 && bytes.len() == 2);
+    values.push(if in_c { START_C } else { START_B });
+    let mut i = 0;
+    while i < bytes.len() {
+        let run = digits_from(i);
+        if in_c {
+            if run >= 2 {
+                values.push((bytes[i] - b'0') * 10 + (bytes[i + 1] - b'0'));
+                i += 2;
+                continue;
+            }
+            values.push(CODE_B);
+            in_c = false;
+        } else if run >= 6 || (run >= 4 && i + run == bytes.len()) {
+            // An odd run keeps its first digit in set B.
+            if run % 2 == 1 {
+                values.push(bytes[i] - b' ');
+                i += 1;
+            }
+            values.push(CODE_C);
+            in_c = true;
+            continue;
+        }
+        values.push(bytes[i] - b' ');
+        i += 1;
+    }
+
+    let check = values
+        .iter()
+        .enumerate()
+        .map(|(pos, &v)| pos.max(1) * v as usize)
+        .sum::<usize>()
+        % 103;
+    values.push(check as u8);
+    values.push(STOP);
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code128() {
+        // Every symbol is 11 modules (13 for stop), starts with a bar, has an even
+        // number of bar modules, and no two are alike.
+        for (value, pattern) in PATTERNS.iter().enumerate() {
+            let widths: Vec<u32> = pattern.bytes().map(|b| (b - b'0') as u32).collect();
+            let modules: u32 = widths.iter().sum();
+            assert_eq!(modules, if value == 106 { 13 } else { 11 }, "{}", value);
+            let bars: u32 = widths.iter().step_by(2).sum();
+            assert_eq!(bars % 2, 0, "{}", value);
+        }
+        let unique: std::collections::HashSet<_> = PATTERNS.iter().collect();
+        assert_eq!(unique.len(), PATTERNS.len());
+
+        assert_eq!(
+            values("PJJ123C").unwrap(),
+            [START_B, 48, 42, 42, 17, 18, 19, 35, 55, STOP]
+        );
+        // Leading digits start in set C; an odd run at the end keeps one digit in B.
+        assert_eq!(values("1234").unwrap()[..3], [START_C, 12, 34]);
+        assert_eq!(
+            values("A-00042").unwrap()[..7],
+            [START_B, 33, 13, 16, CODE_C, 0, 42]
+        );
+        assert_eq!(values("0042AB").unwrap()[..5], [START_C, 0, 42, CODE_B, 33]);
+
+        let bars = encode("PJJ123C").unwrap();
+        assert_eq!(bars.len(), 11 * 9 + 13);
+        assert!(bars[0] && bars[bars.len() - 1]);
+        assert!(encode("").is_err());
+        assert!(encode("café").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! Color values accepted on the command line and in batch records.
+
+use image::Rgba;
+use std::fmt;
+use std::str::FromStr;
+
+/// An RGBA color parsed from `#rgb`, `#rrggbb`, `#rrggbbaa` or a few common names.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color(pub Rgba<u8>);
+
+impl Color {
+    pub const WHITE: Color = Color(Rgba([255, 255, 255, 255]));
+    pub const BLACK: Color = Color(Rgba([0, 0, 0, 255]));
+    pub const TRANSPARENT: Color = Color(Rgba([0, 0, 0, 0]));
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let named = match s.to_ascii_lowercase().as_str() {
+            "white" => Some([255, 255, 255, 255]),
+            "black" => Some([0, 0, 0, 255]),
+            "transparent" => Some([0, 0, 0, 0]),
+            _ => None,
+        };
+        if let Some(rgba) = named {
+            return Ok(Color(Rgba(rgba)));
+        }
+
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let invalid = || {
+            format!(
+                "invalid color '{}' (expected #rrggbb, #rrggbbaa or #rgb)",
+                s
+            )
+        };
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        let nibble = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap() * 17;
+        match hex.len() {
+            3 => Ok(Color(Rgba([nibble(0), nibble(1), nibble(2), 255]))),
+            6 => Ok(Color(Rgba([byte(0), byte(2), byte(4), 255]))),
+            8 => Ok(Color(Rgba([byte(0), byte(2), byte(4), byte(6)]))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.0.0;
+        if a == 255 {
+            write!(f, "#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            write!(f, "#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!("#1a2B3c".parse(), Ok(Color(Rgba([0x1a, 0x2b, 0x3c, 255]))));
+        assert_eq!("fff".parse(), Ok(Color::WHITE));
+        assert_eq!("#00000080".parse(), Ok(Color(Rgba([0, 0, 0, 0x80]))));
+        assert_eq!("Black".parse(), Ok(Color::BLACK));
+        assert!("#12345".parse::<Color>().is_err());
+        assert!("#gggggg".parse::<Color>().is_err());
+        assert_eq!(Color(Rgba([0x1a, 0x2b, 0x3c, 255])).to_string(), "#1a2b3c");
+    }
+}
@@ -0,0 +1,91 @@
+//! `qrbrand completions <shell>`: tab completion for flags, subcommands and values.
+//!
+//! The printed script calls back into `qrbrand` on each Tab (with `COMPLETE=<shell>`
+//! set), so values that live outside the binary, such as the theme names in the
+//! config file, complete as they are now rather than as they were when the script
+//! was generated.
+
+use crate::{Args, theme};
+use anyhow::{Context, Result};
+use clap::{Args as ClapArgs, CommandFactory, ValueEnum};
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{CompleteEnv, Shells};
+use std::io::Write;
+
+/// Environment variable that switches `qrbrand` into answering completions.
+const VAR: &str = "COMPLETE";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to print the completion script for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+/// Answer a completion request from a script printed by [`run`], then exit. Does
+/// nothing on a normal run, so call it before parsing the command line.
+pub fn complete() {
+    CompleteEnv::with_factory(Args::command).var(VAR).complete();
+}
+
+/// `--theme` candidates: the built-ins, then the config file's own themes.
+pub fn themes() -> Vec<CompletionCandidate> {
+    let user = theme::user_names(theme::config_path().as_deref());
+    theme::builtin_names()
+        .map(|name| CompletionCandidate::new(name).help(Some("built-in".into())))
+        .chain(
+            user.into_iter()
+                .map(|name| CompletionCandidate::new(name).help(Some("config file".into()))),
+        )
+        .collect()
+}
+
+/// Print the completion script for `args.shell` to stdout.
+pub fn run(args: &CompletionsArgs) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    write_script(args.shell, &mut out)?;
+    Ok(out.flush()?)
+}
+
+fn write_script(shell: Shell, out: &mut dyn Write) -> Result<()> {
+    let name = shell
+        .to_possible_value()
+        .context("shell has no name")?
+        .get_name()
+        .to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .with_context(|| format!("no completion support for {}", name))?;
+    completer
+        .write_registration(VAR, "qrbrand", "qrbrand", "qrbrand", out)
+        .context("Failed to write the completion script")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts() {
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            write_script(*shell, &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains(VAR), "{:?}: {}", shell, script);
+        }
+        let names: Vec<_> = themes()
+            .iter()
+            .map(|c| c.get_value().to_string_lossy().into_owned())
+            .collect();
+        assert!(names.starts_with(&["classic".to_string(), "night".to_string()]));
+    }
+}
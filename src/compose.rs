@@ -0,0 +1,196 @@
+//! Compositing the finished code into a larger design (`--template`, `--place`).
+
+use crate::raster::{self, Canvas};
+use anyhow::{Context, Result, bail};
+use std::fmt;
+use std::str::FromStr;
+
+/// `--template`: a design image, or a built-in layout by name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Template {
+    Image(String),
+    /// A video frame with the code in a title-safe corner (see [`crate::lowerthird`]).
+    LowerThird,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Err("expected an image path or lower-third".into()),
+            name if name.eq_ignore_ascii_case("lower-third") => Ok(Self::LowerThird),
+            path => Ok(Self::Image(path.to_string())),
+        }
+    }
+}
+
+impl Template {
+    /// The design image to load, if this is one.
+    pub fn image(&self) -> Option<&str> {
+        match self {
+            Self::Image(path) => Some(path),
+            Self::LowerThird => None,
+        }
+    }
+}
+
+/// Where the code goes on the template, e.g. `--place "x=120,y=500,w=800"`.
+/// The code is sized to fit `w` and/or `h` and centered in that box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub x: u32,
+    pub y: u32,
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut x, mut y, mut w, mut h) = (None, None, None, None);
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value in placement, got '{}'", pair))?;
+            let value = value.trim();
+            let px: u32 = value
+                .strip_suffix("px")
+                .unwrap_or(value)
+                .parse()
+                .map_err(|_| format!("invalid placement {} '{}'", key, value))?;
+            match key.trim() {
+                "x" => x = Some(px),
+                "y" => y = Some(px),
+                "w" => w = Some(px),
+                "h" => h = Some(px),
+                other => {
+                    return Err(format!(
+                        "unknown placement key '{}' (expected x, y, w or h)",
+                        other
+                    ));
+                }
+            }
+        }
+        let (Some(x), Some(y)) = (x, y) else {
+            return Err(format!(
+                "placement needs x and y, e.g. \"x=120,y=500,w=800\", got '{}'",
+                s
+            ));
+        };
+        if w == Some(0) || h == Some(0) {
+            return Err(format!(
+                "placement width and height must be positive, got '{}'",
+                s
+            ));
+        }
+        Ok(Self { x, y, w, h })
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "x={},y={}", self.x, self.y)?;
+        if let Some(w) = self.w {
+            write!(f, ",w={}", w)?;
+        }
+        if let Some(h) = self.h {
+            write!(f, ",h={}", h)?;
+        }
+        Ok(())
+    }
+}
+
+impl Placement {
+    /// Render size to start from: the box's width, else its height.
+    pub fn target_size(&self) -> Option<u32> {
+        self.w.or(self.h)
+    }
+
+    /// Factor by which a `w`×`h` image has to shrink to fit the box (>= 1.0 if it fits).
+    pub fn fit_scale(&self, w: u32, h: u32) -> f32 {
+        let sx = self.w.map_or(f32::INFINITY, |bw| bw as f32 / w as f32);
+        let sy = self.h.map_or(f32::INFINITY, |bh| bh as f32 / h as f32);
+        sx.min(sy)
+    }
+}
+
+pub fn load_template(path: &str) -> Result<Canvas> {
+    Ok(image::open(path)
+        .with_context(|| format!("Failed to open template image: {}", path))?
+        .to_rgba8())
+}
+
+/// Composite `img` onto a copy of `template`, centered in the placement box.
+/// Returns the combined image and the position of `img` in it.
+pub fn place(
+    template: &Canvas,
+    img: &Canvas,
+    placement: &Placement,
+) -> Result<(Canvas, (u32, u32))> {
+    let (w, h) = img.dimensions();
+    let box_w = placement.w.unwrap_or(w);
+    let box_h = placement.h.unwrap_or(h);
+    let (tw, th) = template.dimensions();
+    if placement.x + box_w > tw || placement.y + box_h > th {
+        bail!(
+            "--place {} ({}x{} code) falls outside the {}x{} template",
+            placement,
+            w,
+            h,
+            tw,
+            th
+        );
+    }
+
+    let x = placement.x + box_w.saturating_sub(w) / 2;
+    let y = placement.y + box_h.saturating_sub(h) / 2;
+    let mut out = template.clone();
+    raster::overlay(&mut out, img, x, y);
+    Ok((out, (x, y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgba};
+
+    #[test]
+    fn test_parse_placement() {
+        assert_eq!(
+            "x=120,y=500,w=800".parse::<Placement>().unwrap(),
+            Placement {
+                x: 120,
+                y: 500,
+                w: Some(800),
+                h: None
+            }
+        );
+        let p: Placement = "x=0, y=10px, h=300".parse().unwrap();
+        assert_eq!((p.w, p.h), (None, Some(300)));
+        assert_eq!(p.to_string().parse::<Placement>().unwrap(), p);
+        assert!("x=1,w=100".parse::<Placement>().is_err());
+        assert!("x=1,y=2,w=0".parse::<Placement>().is_err());
+        assert!("x=1,y=2,r=5".parse::<Placement>().is_err());
+    }
+
+    #[test]
+    fn test_place_centers_in_box() {
+        let red = Rgba([255, 0, 0, 255]);
+        let template: Canvas = ImageBuffer::from_pixel(100, 100, Rgba([0, 0, 255, 255]));
+        let img: Canvas = ImageBuffer::from_pixel(20, 30, red);
+        let placement: Placement = "x=10,y=10,w=40,h=40".parse().unwrap();
+        assert_eq!(placement.fit_scale(20, 30), 4.0 / 3.0);
+        assert_eq!(placement.fit_scale(80, 20), 0.5);
+
+        let (out, pos) = place(&template, &img, &placement).unwrap();
+        assert_eq!(out.dimensions(), (100, 100));
+        assert_eq!(pos, (20, 15));
+        assert_eq!(*out.get_pixel(20, 15), red);
+        assert_eq!(*out.get_pixel(19, 15), Rgba([0, 0, 255, 255]));
+
+        let outside: Placement = "x=90,y=0,w=40".parse().unwrap();
+        assert!(place(&template, &img, &outside).is_err());
+    }
+}
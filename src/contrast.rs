@@ -0,0 +1,217 @@
+//! Contrast check of the finished code.
+//!
+//! Samples the centre of every module outside the logo area, plus the ring of quiet
+//! zone next to the symbol, as the final pixels (so quiet zone colors, shadows and
+//! template backgrounds all count). The lightest dark module and the darkest light
+//! sample give the worst-case reflectance difference, graded with the ISO/IEC 15415
+//! symbol contrast thresholds (A >= 70%, B >= 55%, C >= 40%, D >= 20%). For an
+//! inverted code the roles swap: the modules are the light side and the ground the
+//! dark one.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::symbol::Symbol;
+use anyhow::{Result, anyhow};
+use image::{GenericImageView, Rgba};
+
+/// Below this scanners commonly fail (grade D and worse).
+pub const WARN_CONTRAST: f32 = 0.40;
+/// Below this the code is refused even without --strict-contrast (grade F).
+pub const MIN_CONTRAST: f32 = 0.20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contrast {
+    /// Highest reflectance (0..1) sampled on a dark module.
+    pub dark: f32,
+    /// Lowest reflectance (0..1) sampled on a light module or the quiet zone.
+    pub light: f32,
+    /// Some light samples were not opaque; they were judged over white.
+    pub transparent: bool,
+}
+
+impl Contrast {
+    /// Worst-case reflectance difference between light and dark.
+    pub fn symbol_contrast(&self) -> f32 {
+        self.light - self.dark
+    }
+
+    pub fn grade(&self) -> char {
+        match self.symbol_contrast() {
+            sc if sc >= 0.70 => 'A',
+            sc if sc >= 0.55 => 'B',
+            sc if sc >= 0.40 => 'C',
+            sc if sc >= 0.20 => 'D',
+            _ => 'F',
+        }
+    }
+
+    /// Refuse grade F (and, with `strict`, anything below grade C); otherwise return
+    /// the warnings worth showing.
+    pub fn check(&self, strict: bool) -> Result<Vec<String>> {
+        let sc = self.symbol_contrast();
+        let summary = format!(
+            "light/dark contrast is {:.0}% (ISO/IEC 15415 grade {})",
+            sc.max(0.0) * 100.0,
+            self.grade()
+        );
+        if sc < MIN_CONTRAST {
+            return Err(anyhow!(
+                "{}; scanners need at least {:.0}%. Use a lighter quiet zone and background.",
+                summary,
+                MIN_CONTRAST * 100.0
+            ))
+            .code(ErrorCode::LowContrast);
+        }
+        let mut warnings = Vec::new();
+        if sc < WARN_CONTRAST {
+            if strict {
+                return Err(anyhow!(
+                    "{}, below the {:.0}% --strict-contrast minimum",
+                    summary,
+                    WARN_CONTRAST * 100.0
+                ))
+                .code(ErrorCode::LowContrast);
+            }
+            warnings.push(format!("{}; many scanners will struggle", summary));
+        }
+        if self.transparent {
+            warnings.push(
+                "the quiet zone is partly transparent; contrast assumes a white background"
+                    .to_string(),
+            );
+        }
+        Ok(warnings)
+    }
+}
+
+/// Measure `img` (the QR square with `quiet_modules` of quiet zone, dark modules drawn
+/// light if `inverted`), skipping modules whose centre lies in `skip` (x, y, w, h), the
+/// area hidden by the logo or plate.
+pub fn measure(
+    img: &impl GenericImageView<Pixel = Rgba<u8>>,
+    code: &Symbol,
+    quiet_modules: u32,
+    skip: Option<(u32, u32, u32, u32)>,
+    inverted: bool,
+) -> Contrast {
+    let mut contrast = Contrast::default();
+    contrast.sample(img, 0, code, quiet_modules, skip, inverted);
+    contrast
+}
+
+/// No samples yet: perfect contrast until [`Contrast::sample`] finds otherwise.
+impl Default for Contrast {
+    fn default() -> Self {
+        Self {
+            dark: 0.0,
+            light: 1.0,
+            transparent: false,
+        }
+    }
+}
+
+impl Contrast {
+    /// Take the [`measure`] samples that fall in `strip`, the full-width rows of the
+    /// QR square starting at `top`; a code streamed in strips is measured strip by strip.
+    pub fn sample(
+        &mut self,
+        strip: &impl GenericImageView<Pixel = Rgba<u8>>,
+        top: u32,
+        code: &Symbol,
+        quiet_modules: u32,
+        skip: Option<(u32, u32, u32, u32)>,
+        inverted: bool,
+    ) {
+        let n = code.width() as i64;
+        let ppm = strip.width() / (code.width() as u32 + 2 * quiet_modules);
+        let rows = top..top + strip.height();
+        let skipped = |px: u32, py: u32| {
+            skip.is_some_and(|(x, y, w, h)| px >= x && py >= y && px < x + w && py < y + h)
+        };
+
+        // Modules -1 and n are the quiet zone ring around the symbol.
+        let ring = if quiet_modules > 0 { 1 } else { 0 };
+        for my in -ring..n + ring {
+            let py = ((my + quiet_modules as i64) as u32) * ppm + ppm / 2;
+            if !rows.contains(&py) {
+                continue;
+            }
+            for mx in -ring..n + ring {
+                let px = ((mx + quiet_modules as i64) as u32) * ppm + ppm / 2;
+                if px >= strip.width() || skipped(px, py) {
+                    continue;
+                }
+                let inside = (0..n).contains(&mx) && (0..n).contains(&my);
+                let module =
+                    inside && matches!(code[(mx as usize, my as usize)], qrcode::Color::Dark);
+                let dark = module != inverted;
+                let pixel = strip.get_pixel(px, py - top);
+                let r = reflectance(pixel);
+                if dark {
+                    self.dark = self.dark.max(r);
+                } else {
+                    self.light = self.light.min(r);
+                    self.transparent |= pixel[3] < 255;
+                }
+            }
+        }
+    }
+}
+
+/// Relative luminance of `p` composited over white.
+pub fn reflectance(p: Rgba<u8>) -> f32 {
+    let a = p[3] as f32 / 255.0;
+    let linear = |c: u8| {
+        let c = (c as f32 / 255.0) * a + (1.0 - a);
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(p[0]) + 0.7152 * linear(p[1]) + 0.0722 * linear(p[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::decor;
+    use crate::render_qr_rgba;
+
+    #[test]
+    fn test_reflectance() {
+        assert_eq!(reflectance(Rgba([0, 0, 0, 255])), 0.0);
+        assert!((reflectance(Rgba([255, 255, 255, 255])) - 1.0).abs() < 1e-6);
+        assert!((reflectance(Rgba([0, 0, 0, 0])) - 1.0).abs() < 1e-6);
+        let mid = reflectance(Rgba([128, 128, 128, 255]));
+        assert!((mid - 0.216).abs() < 0.01, "{}", mid);
+    }
+
+    #[test]
+    fn test_measure_and_grade() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let mut img = render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap();
+        let clean = measure(&img, &code, 4, None, false);
+        assert_eq!(clean.grade(), 'A');
+        assert!(clean.check(true).unwrap().is_empty());
+
+        let ppm = img.width() / (code.width() as u32 + 8);
+        decor::paint_quiet_zone(&mut img, 4 * ppm, "#bbbbbb".parse().unwrap());
+        let grey = measure(&img, &code, 4, None, false);
+        assert_eq!(grey.grade(), 'C');
+
+        decor::paint_quiet_zone(&mut img, 4 * ppm, "#999999".parse().unwrap());
+        let dim = measure(&img, &code, 4, None, false);
+        assert_eq!(dim.grade(), 'D');
+        assert_eq!(dim.check(false).unwrap().len(), 1);
+        assert!(dim.check(true).is_err());
+
+        decor::paint_quiet_zone(&mut img, 4 * ppm, "#333333".parse().unwrap());
+        assert!(measure(&img, &code, 4, None, false).check(false).is_err());
+
+        decor::paint_quiet_zone(&mut img, 4 * ppm, "#ffffff80".parse().unwrap());
+        let see_through = measure(&img, &code, 4, None, false);
+        assert!(see_through.transparent);
+        assert_eq!(see_through.check(false).unwrap().len(), 1);
+    }
+}
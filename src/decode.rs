@@ -0,0 +1,1063 @@
+//! A small QR decoder for checking rendered codes (`--stress-test`, `qrbrand verify`
+//! and `qrbrand diff`).
+//!
+//! Binarises the image, locates the three finder patterns by their 1:1:3:1:1 runs,
+//! maps the module grid from their centres (refined into a homography when the
+//! bottom-right alignment pattern is found), then reads the format information,
+//! unmasks, corrects each Reed-Solomon block and parses the segments. A code that
+//! does not read is tried again with dark and light swapped, for light modules on a
+//! dark ground (`--invert`), and a grid whose format information does not read is
+//! tried transposed, as a mirrored code (`--flip`) samples. It copes with
+//! the damage the stress test applies to codes this tool makes; it is not a
+//! general camera scanner.
+
+use crate::layout::{self, Layout};
+use crate::raster::Canvas;
+use anyhow::{Result, anyhow, bail};
+use encoding_rs::SHIFT_JIS;
+use qrcode::{EcLevel, Version};
+
+#[derive(Debug)]
+pub struct Decoded {
+    pub text: String,
+    /// Codewords repaired by error correction.
+    pub corrected: usize,
+    pub version: Version,
+    pub ec_level: EcLevel,
+    pub mask: u8,
+    /// The modules as sampled (before error correction), row by row, `true` for dark.
+    pub grid: Vec<bool>,
+    /// Read as light modules on a dark ground; `grid` is then `true` for light.
+    pub inverted: bool,
+}
+
+/// Decode the QR code in `img`, as drawn or inverted.
+pub fn decode(img: &Canvas) -> Result<Decoded> {
+    let bin = Binary::new(img);
+    decode_binary(&bin).or_else(|err| match decode_binary(&bin.inverted()) {
+        Ok(decoded) => Ok(Decoded {
+            inverted: true,
+            ..decoded
+        }),
+        // The first error says more about a code that is simply unreadable.
+        Err(_) => Err(err),
+    })
+}
+
+fn decode_binary(bin: &Binary) -> Result<Decoded> {
+    let finders = find_finders(bin);
+    let triples = finder_triples(&finders);
+    if triples.is_empty() {
+        bail!("no finder patterns found");
+    }
+
+    let mut first_err = None;
+    for &(tl, tr, bl) in triples.iter().take(3) {
+        // Finder runs are measured along rows and columns, so a rotated code's modules
+        // look wider by 1 / max(|cos|, |sin|) of its angle.
+        let angle = (tr.y - tl.y).atan2(tr.x - tl.x);
+        let upright = angle.cos().abs().max(angle.sin().abs());
+        let [tl, tr, bl] = [tl, tr, bl].map(|f| Finder {
+            module: f.module * upright,
+            ..f
+        });
+        let module = (tl.module + tr.module + bl.module) / 3.0;
+        let across = (dist(tl, tr) + dist(tl, bl)) / 2.0 / module + 7.0;
+        let estimate = ((across - 17.0) / 4.0).round() as i16;
+        for v in [
+            estimate,
+            estimate - 1,
+            estimate + 1,
+            estimate - 2,
+            estimate + 2,
+        ] {
+            if !(1..=40).contains(&v) {
+                continue;
+            }
+            let grid = read_grid(bin, [tl, tr, bl], v);
+            let version = Version::Normal(v);
+            let decoded = decode_grid(&grid, version)
+                .or_else(|err| decode_grid(&transposed(&grid), version).map_err(|_| err));
+            match decoded {
+                Ok(decoded) => return Ok(decoded),
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+    }
+    Err(first_err.unwrap_or_else(|| anyhow!("finder patterns do not fit any QR version")))
+}
+
+/// Dark/light pixels, thresholded with Otsu's method on luminance over white.
+struct Binary {
+    width: i64,
+    height: i64,
+    dark: Vec<bool>,
+}
+
+impl Binary {
+    fn new(img: &Canvas) -> Self {
+        let luma: Vec<u8> = img
+            .pixels()
+            .map(|p| {
+                let a = p[3] as f32 / 255.0;
+                let l = 0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32;
+                (l * a + 255.0 * (1.0 - a)).round() as u8
+            })
+            .collect();
+        let threshold = otsu(&luma);
+        Self {
+            width: img.width() as i64,
+            height: img.height() as i64,
+            dark: luma.iter().map(|&l| l <= threshold).collect(),
+        }
+    }
+
+    /// Dark and light swapped.
+    fn inverted(&self) -> Self {
+        Self {
+            dark: self.dark.iter().map(|d| !d).collect(),
+            ..*self
+        }
+    }
+
+    /// Outside the image counts as light.
+    fn get(&self, x: i64, y: i64) -> bool {
+        x >= 0
+            && y >= 0
+            && x < self.width
+            && y < self.height
+            && self.dark[(y * self.width + x) as usize]
+    }
+
+    fn sample(&self, (x, y): (f64, f64)) -> bool {
+        self.get(x.floor() as i64, y.floor() as i64)
+    }
+}
+
+/// Threshold that best separates the two classes of the luminance histogram.
+fn otsu(luma: &[u8]) -> u8 {
+    let mut hist = [0u64; 256];
+    for &l in luma {
+        hist[l as usize] += 1;
+    }
+    let total = luma.len() as f64;
+    let sum: f64 = hist
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| i as f64 * n as f64)
+        .sum();
+    let (mut below, mut sum_below) = (0.0, 0.0);
+    let (mut best, mut best_var) = (127, -1.0);
+    for (t, &n) in hist.iter().enumerate() {
+        below += n as f64;
+        sum_below += t as f64 * n as f64;
+        let above = total - below;
+        if below == 0.0 || above == 0.0 {
+            continue;
+        }
+        let diff = sum_below / below - (sum - sum_below) / above;
+        let var = below * above * diff * diff;
+        if var > best_var {
+            best_var = var;
+            best = t as u8;
+        }
+    }
+    best
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Finder {
+    x: f64,
+    y: f64,
+    module: f64,
+    /// Scan lines that confirmed it.
+    hits: u32,
+}
+
+fn dist(a: Finder, b: Finder) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+/// Runs of dark, light, dark, light, dark in proportion 1:1:3:1:1.
+fn finder_ratio(runs: [i64; 5]) -> bool {
+    let total: i64 = runs.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module = total as f64 / 7.0;
+    let slack = module / 2.0;
+    runs.iter()
+        .zip([1.0, 1.0, 3.0, 1.0, 1.0])
+        .all(|(&run, k)| (run as f64 - k * module).abs() < k * slack)
+}
+
+/// Scan every row for finder runs, confirm each vertically and horizontally through
+/// its centre, and cluster the confirmed centres.
+fn find_finders(bin: &Binary) -> Vec<Finder> {
+    let mut found: Vec<Finder> = Vec::new();
+    let mut runs: Vec<(i64, i64)> = Vec::new();
+    for y in 0..bin.height {
+        // (start, length) of alternating runs, starting with light.
+        runs.clear();
+        let mut start = 0;
+        let mut dark = false;
+        for x in 0..=bin.width {
+            let d = x < bin.width && bin.get(x, y);
+            if d != dark || x == bin.width {
+                runs.push((start, x - start));
+                start = x;
+                dark = d;
+            }
+        }
+        // Odd indices are dark runs.
+        for i in (1..runs.len().saturating_sub(4)).step_by(2) {
+            let lens = [0, 1, 2, 3, 4].map(|k| runs[i + k].1);
+            if !finder_ratio(lens) {
+                continue;
+            }
+            let cx = runs[i + 2].0 as f64 + runs[i + 2].1 as f64 / 2.0;
+            let total: i64 = lens.iter().sum();
+            let Some((cy, vertical)) = cross_check(bin, cx, y as f64 + 0.5, (0, 1), total) else {
+                continue;
+            };
+            let Some((cx, horizontal)) = cross_check(bin, cx, cy, (1, 0), total) else {
+                continue;
+            };
+            let candidate = Finder {
+                x: cx,
+                y: cy,
+                module: (vertical + horizontal) as f64 / 14.0,
+                hits: 1,
+            };
+            match found
+                .iter_mut()
+                .find(|f| dist(**f, candidate) < 2.0 * f.module.max(candidate.module))
+            {
+                Some(f) => {
+                    let n = f.hits as f64;
+                    f.x = (f.x * n + candidate.x) / (n + 1.0);
+                    f.y = (f.y * n + candidate.y) / (n + 1.0);
+                    f.module = (f.module * n + candidate.module) / (n + 1.0);
+                    f.hits += 1;
+                }
+                None => found.push(candidate),
+            }
+        }
+    }
+    found
+}
+
+/// Measure the finder runs through (x, y) along `step`. Returns the centre coordinate
+/// along that axis and the pattern's total length, which must be close to `expected`.
+fn cross_check(
+    bin: &Binary,
+    x: f64,
+    y: f64,
+    step: (i64, i64),
+    expected: i64,
+) -> Option<(f64, i64)> {
+    let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+    if !bin.get(x0, y0) {
+        return None;
+    }
+    let limit = 2 * expected;
+    // Runs outward from the centre: dark (centre), light, dark.
+    let walk = |dx: i64, dy: i64| -> Option<[i64; 3]> {
+        let mut runs = [0i64; 3];
+        let (mut px, mut py) = (x0, y0);
+        let mut state = 0;
+        loop {
+            if bin.get(px, py) != (state != 1) {
+                state += 1;
+                if state == 3 {
+                    return Some(runs);
+                }
+                continue;
+            }
+            runs[state] += 1;
+            if runs[state] > limit {
+                return None;
+            }
+            px += dx;
+            py += dy;
+        }
+    };
+    let back = walk(-step.0, -step.1)?;
+    let fwd = walk(step.0, step.1)?;
+    let runs = [back[2], back[1], back[0] + fwd[0] - 1, fwd[1], fwd[2]];
+    let total: i64 = runs.iter().sum();
+    if !finder_ratio(runs) || 5 * (total - expected).abs() >= 2 * expected {
+        return None;
+    }
+    let along = if step.0 != 0 { x0 } else { y0 };
+    let centre_start = along - (back[0] - 1);
+    let centre_end = along + fwd[0];
+    Some(((centre_start + centre_end) as f64 / 2.0, total))
+}
+
+/// Plausible (top-left, top-right, bottom-left) finder triples, best first.
+fn finder_triples(found: &[Finder]) -> Vec<(Finder, Finder, Finder)> {
+    let mut candidates: Vec<Finder> = found.iter().copied().filter(|f| f.hits >= 2).collect();
+    if candidates.len() < 3 {
+        candidates = found.to_vec();
+    }
+    candidates.sort_by_key(|f| std::cmp::Reverse(f.hits));
+    candidates.truncate(12);
+
+    let mut triples = Vec::new();
+    for i in 0..candidates.len() {
+        for j in i + 1..candidates.len() {
+            for k in j + 1..candidates.len() {
+                let [a, b, c] = [candidates[i], candidates[j], candidates[k]];
+                let modules = [a.module, b.module, c.module];
+                let (small, large) = (
+                    modules.iter().copied().fold(f64::INFINITY, f64::min),
+                    modules.iter().copied().fold(0.0, f64::max),
+                );
+                if large > 2.0 * small {
+                    continue;
+                }
+                // The top-left finder sits opposite the longest side.
+                let (corner, p, q) = if dist(b, c) >= dist(a, b) && dist(b, c) >= dist(a, c) {
+                    (a, b, c)
+                } else if dist(a, c) >= dist(a, b) {
+                    (b, a, c)
+                } else {
+                    (c, a, b)
+                };
+                let (legp, legq) = (dist(corner, p), dist(corner, q));
+                let cos = ((p.x - corner.x) * (q.x - corner.x)
+                    + (p.y - corner.y) * (q.y - corner.y))
+                    / (legp * legq);
+                let module = (a.module + b.module + c.module) / 3.0;
+                if cos.abs() > 0.35
+                    || legp.max(legq) > 1.5 * legp.min(legq)
+                    || legp.min(legq) < 10.0 * module
+                {
+                    continue;
+                }
+                // Clockwise from top-left in image coordinates (y down) is top-right.
+                let cross =
+                    (p.x - corner.x) * (q.y - corner.y) - (p.y - corner.y) * (q.x - corner.x);
+                let (tr, bl) = if cross > 0.0 { (p, q) } else { (q, p) };
+                triples.push((a.hits + b.hits + c.hits, cos.abs(), (corner, tr, bl)));
+            }
+        }
+    }
+    triples.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.total_cmp(&b.1)));
+    triples.into_iter().map(|(_, _, t)| t).collect()
+}
+
+/// Projective map from module coordinates to image pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct Homography([f64; 9]);
+
+impl Homography {
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let h = &self.0;
+        let w = h[6] * x + h[7] * y + h[8];
+        (
+            (h[0] * x + h[1] * y + h[2]) / w,
+            (h[3] * x + h[4] * y + h[5]) / w,
+        )
+    }
+
+    /// The map taking each of `from` to the matching point of `to`, if the points
+    /// are in general position.
+    pub fn from_points(from: [(f64, f64); 4], to: [(f64, f64); 4]) -> Option<Self> {
+        // Solve the 8x8 system for h0..h7 (h8 = 1) by Gaussian elimination.
+        let mut m = [[0.0f64; 9]; 8];
+        for (i, (&(x, y), &(u, v))) in from.iter().zip(&to).enumerate() {
+            m[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u, u];
+            m[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -x * v, -y * v, v];
+        }
+        for col in 0..8 {
+            let pivot = (col..8).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+            if m[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(col, pivot);
+            let pivot = m[col];
+            for (r, row) in m.iter_mut().enumerate() {
+                if r != col {
+                    let f = row[col] / pivot[col];
+                    for (a, b) in row.iter_mut().zip(pivot).skip(col) {
+                        *a -= f * b;
+                    }
+                }
+            }
+        }
+        let mut h = [1.0; 9];
+        for (i, row) in m.iter().enumerate() {
+            h[i] = row[8] / row[i];
+        }
+        Some(Self(h))
+    }
+
+    /// The affine map taking each of `from` to the matching point of `to`.
+    fn affine(from: [(f64, f64); 3], to: [(f64, f64); 3]) -> Option<Self> {
+        // Complete both triangles to parallelograms; the projective solution is then affine.
+        let fourth = |[a, b, c]: [(f64, f64); 3]| (b.0 + c.0 - a.0, b.1 + c.1 - a.1);
+        Self::from_points(
+            [from[0], from[1], from[2], fourth(from)],
+            [to[0], to[1], to[2], fourth(to)],
+        )
+    }
+}
+
+/// Sample the module grid of a version `v` symbol located by the three finders.
+///
+/// Alignment patterns are located one at a time outward from the finders, each
+/// predicted from its nearest located neighbours, and every cell between them is
+/// sampled through the homography of its four corners, so perspective error stays
+/// local to a cell.
+fn read_grid(bin: &Binary, [tl, tr, bl]: [Finder; 3], v: i16) -> Vec<bool> {
+    let dim = 17 + 4 * v as usize;
+    let n = dim as f64;
+    let Some(global) = Homography::affine(
+        [(3.5, 3.5), (n - 3.5, 3.5), (3.5, n - 3.5)],
+        [(tl.x, tl.y), (tr.x, tr.y), (bl.x, bl.y)],
+    ) else {
+        return vec![false; dim * dim];
+    };
+    let lines: Vec<f64> = layout::alignment_positions(v)
+        .iter()
+        .map(|&p| p as f64 + 0.5)
+        .collect();
+    let k = lines.len();
+
+    // Control points where the alignment rows and columns cross. The three under the
+    // finders are placed 3 modules in from the finder centres, using each finder's
+    // own module size.
+    let mut points: Vec<Option<(f64, f64)>> = vec![None; k * k];
+    if k > 0 {
+        let unit = |a: Finder, b: Finder| ((b.x - a.x) / dist(a, b), (b.y - a.y) / dist(a, b));
+        let (u, w) = (unit(tl, tr), unit(tl, bl));
+        let inward = |f: Finder, du: f64, dw: f64| {
+            (
+                f.x + f.module * (du * u.0 + dw * w.0),
+                f.y + f.module * (du * u.1 + dw * w.1),
+            )
+        };
+        points[0] = Some(inward(tl, 3.0, 3.0));
+        points[k - 1] = Some(inward(tr, -3.0, 3.0));
+        points[(k - 1) * k] = Some(inward(bl, 3.0, -3.0));
+    }
+    let mut order: Vec<usize> = (0..k * k).filter(|&i| points[i].is_none()).collect();
+    order.sort_by_key(|&i| (i % k + i / k, i));
+    for idx in order {
+        let at = (lines[idx % k], lines[idx / k]);
+        let mut known: Vec<((f64, f64), (f64, f64))> = points
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.map(|p| ((lines[i % k], lines[i / k]), p)))
+            .collect();
+        known.sort_by(|a, b| {
+            let da = (a.0.0 - at.0).hypot(a.0.1 - at.1);
+            let db = (b.0.0 - at.0).hypot(b.0.1 - at.1);
+            da.total_cmp(&db)
+        });
+        // The nearest two plus the next one off their line.
+        let (a, b) = (known[0], known[1]);
+        let Some(&c) = known[2..].iter().find(|c| {
+            let cross = (b.0.0 - a.0.0) * (c.0.1 - a.0.1) - (b.0.1 - a.0.1) * (c.0.0 - a.0.0);
+            cross.abs() > 1e-6
+        }) else {
+            continue;
+        };
+        let map = Homography::affine([a.0, b.0, c.0], [a.1, b.1, c.1]).unwrap_or(global);
+        let reach = (a.0.0 - at.0).hypot(a.0.1 - at.1);
+        points[idx] = find_alignment(bin, &map, at, 3.0 + 0.15 * reach).or(Some(map.apply(at)));
+    }
+
+    // One homography per cell; modules outside the outer lines use the edge cells.
+    let cells = k.saturating_sub(1);
+    let mut maps = Vec::with_capacity(cells * cells);
+    for cj in 0..cells {
+        for ci in 0..cells {
+            let corner = |i: usize, j: usize| {
+                points[j * k + i].unwrap_or_else(|| global.apply((lines[i], lines[j])))
+            };
+            let map = Homography::from_points(
+                [
+                    (lines[ci], lines[cj]),
+                    (lines[ci + 1], lines[cj]),
+                    (lines[ci], lines[cj + 1]),
+                    (lines[ci + 1], lines[cj + 1]),
+                ],
+                [
+                    corner(ci, cj),
+                    corner(ci + 1, cj),
+                    corner(ci, cj + 1),
+                    corner(ci + 1, cj + 1),
+                ],
+            );
+            maps.push(map.unwrap_or(global));
+        }
+    }
+    let cell = |c: f64| {
+        lines
+            .iter()
+            .rposition(|&l| l <= c)
+            .unwrap_or(0)
+            .min(cells.saturating_sub(1))
+    };
+
+    let mut grid = Vec::with_capacity(dim * dim);
+    for y in 0..dim {
+        for x in 0..dim {
+            let at = (x as f64 + 0.5, y as f64 + 0.5);
+            let map = if cells == 0 {
+                &global
+            } else {
+                &maps[cell(at.1) * cells + cell(at.0)]
+            };
+            grid.push(bin.sample(map.apply(at)));
+        }
+    }
+    grid
+}
+
+/// Search within `radius` modules of where `map` puts `at` for the 5x5 alignment
+/// pattern: a dark centre, a light ring and a dark ring.
+fn find_alignment(
+    bin: &Binary,
+    map: &Homography,
+    at: (f64, f64),
+    radius: f64,
+) -> Option<(f64, f64)> {
+    let origin = map.apply(at);
+    let right = map.apply((at.0 + 1.0, at.1));
+    let down = map.apply((at.0, at.1 + 1.0));
+    let u = (right.0 - origin.0, right.1 - origin.1);
+    let v = (down.0 - origin.0, down.1 - origin.1);
+    let module = u.0.hypot(u.1).max(v.0.hypot(v.1));
+    let radius = (radius * module).ceil() as i64;
+    let dirs = [
+        (1.0, 0.0),
+        (-1.0, 0.0),
+        (0.0, 1.0),
+        (0.0, -1.0),
+        (1.0, 1.0),
+        (1.0, -1.0),
+        (-1.0, 1.0),
+        (-1.0, -1.0),
+    ];
+
+    let score = |x: f64, y: f64| {
+        let at = |k: f64, (a, b): (f64, f64)| {
+            bin.sample((x + k * (a * u.0 + b * v.0), y + k * (a * u.1 + b * v.1)))
+        };
+        usize::from(bin.sample((x, y)))
+            + dirs.iter().filter(|&&d| !at(1.0, d)).count()
+            + dirs.iter().filter(|&&d| at(2.0, d)).count()
+    };
+
+    let (ox, oy) = (origin.0.floor() as i64, origin.1.floor() as i64);
+    let mut best = 15;
+    let mut hits: Vec<(f64, f64)> = Vec::new();
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let (x, y) = ((ox + dx) as f64 + 0.5, (oy + dy) as f64 + 0.5);
+            let s = score(x, y);
+            if s > best {
+                best = s;
+                hits.clear();
+            }
+            if s == best {
+                hits.push((x, y));
+            }
+        }
+    }
+    // Several spots of the centre module score the same; average those next to the
+    // one nearest the prediction.
+    let nearest = *hits.iter().min_by(|a, b| {
+        let da = (a.0 - origin.0).hypot(a.1 - origin.1);
+        let db = (b.0 - origin.0).hypot(b.1 - origin.1);
+        da.total_cmp(&db)
+    })?;
+    let near: Vec<&(f64, f64)> = hits
+        .iter()
+        .filter(|h| (h.0 - nearest.0).hypot(h.1 - nearest.1) <= module)
+        .collect();
+    let count = near.len() as f64;
+    Some((
+        near.iter().map(|h| h.0).sum::<f64>() / count,
+        near.iter().map(|h| h.1).sum::<f64>() / count,
+    ))
+}
+
+/// A square module grid with rows and columns swapped.
+fn transposed(grid: &[bool]) -> Vec<bool> {
+    let dim = grid.len().isqrt();
+    (0..grid.len())
+        .map(|i| grid[(i % dim) * dim + i / dim])
+        .collect()
+}
+
+/// Read format information, unmask, correct and parse a sampled module grid.
+fn decode_grid(grid: &[bool], version: Version) -> Result<Decoded> {
+    let dim = version.width() as usize;
+    let (ec_level, mask) =
+        read_format(grid, dim).ok_or_else(|| anyhow!("format information is unreadable"))?;
+    let layout = Layout::new(version, ec_level);
+
+    let mut stream = vec![0u8; layout.codewords()];
+    for (i, ((x, y), cw)) in layout.module_codewords().enumerate() {
+        if grid[y * dim + x] ^ mask_bit(mask, x, y) {
+            stream[cw] |= 0x80 >> (i % 8);
+        }
+    }
+
+    let mut data = Vec::new();
+    let mut corrected = 0;
+    for (b, mut block) in layout.deinterleave(&stream).into_iter().enumerate() {
+        let errors = correct(&mut block, layout.ec_len)
+            .filter(|&e| e <= layout.correctable)
+            .ok_or_else(|| {
+                anyhow!(
+                    "too many damaged codewords in block {} of {}",
+                    b + 1,
+                    layout.blocks()
+                )
+            })?;
+        corrected += errors;
+        data.extend_from_slice(&block[..layout.data_len[b]]);
+    }
+
+    Ok(Decoded {
+        text: parse_segments(&data, version)?,
+        corrected,
+        version,
+        ec_level,
+        mask,
+        grid: grid.to_vec(),
+        inverted: false,
+    })
+}
+
+/// The two copies of the 15-bit format information, least significant bit first.
+fn read_format(grid: &[bool], dim: usize) -> Option<(EcLevel, u8)> {
+    let at = |x: usize, y: usize| u32::from(grid[y * dim + x]);
+    let mut first = 0;
+    let mut second = 0;
+    for i in 0..15 {
+        let (x, y) = match i {
+            0..=5 => (8, i),
+            6 => (8, 7),
+            7 => (8, 8),
+            8 => (7, 8),
+            _ => (14 - i, 8),
+        };
+        first |= at(x, y) << i;
+        let (x, y) = if i < 8 {
+            (dim - 1 - i, 8)
+        } else {
+            (8, dim - 15 + i)
+        };
+        second |= at(x, y) << i;
+    }
+
+    // Nearest valid BCH(15,5) word, within the code's correction distance.
+    let (distance, data) = (0u32..32)
+        .map(|data| {
+            let mut rem = data;
+            for _ in 0..10 {
+                rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+            }
+            let word = (data << 10 | rem) ^ 0x5412;
+            let distance = (word ^ first)
+                .count_ones()
+                .min((word ^ second).count_ones());
+            (distance, data)
+        })
+        .min()?;
+    if distance > 3 {
+        return None;
+    }
+    let ec_level = match data >> 3 {
+        1 => EcLevel::L,
+        0 => EcLevel::M,
+        3 => EcLevel::Q,
+        _ => EcLevel::H,
+    };
+    Some((ec_level, (data & 7) as u8))
+}
+
+/// Whether mask pattern `mask` inverts the module at column `x`, row `y`.
+fn mask_bit(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (y / 2 + x / 3).is_multiple_of(2),
+        5 => (x * y) % 2 + (x * y) % 3 == 0,
+        6 => ((x * y) % 2 + (x * y) % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + (x * y) % 3).is_multiple_of(2),
+    }
+}
+
+/// GF(256) exponent and log tables for the QR polynomial x^8 + x^4 + x^3 + x^2 + 1.
+const GF: ([u8; 512], [u8; 256]) = {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        exp[i + 255] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    (exp, log)
+};
+
+fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        GF.0[GF.1[a as usize] as usize + GF.1[b as usize] as usize]
+    }
+}
+
+fn div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        0
+    } else {
+        GF.0[GF.1[a as usize] as usize + 255 - GF.1[b as usize] as usize]
+    }
+}
+
+/// α^`power`.
+fn alpha(power: usize) -> u8 {
+    GF.0[power % 255]
+}
+
+/// Evaluate a polynomial stored lowest degree first at `x`.
+fn eval(poly: &[u8], x: u8) -> u8 {
+    poly.iter().rev().fold(0, |acc, &c| mul(acc, x) ^ c)
+}
+
+/// Correct `block` (data then `ec_len` EC codewords) in place with
+/// Berlekamp-Massey, Chien search and Forney. Returns the number of codewords
+/// fixed, or `None` when the damage is beyond repair.
+fn correct(block: &mut [u8], ec_len: usize) -> Option<usize> {
+    let n = block.len();
+    // The first codeword is the highest-degree coefficient; the generator's roots are α^0..α^(ec_len-1).
+    let syndromes: Vec<u8> = (0..ec_len)
+        .map(|j| block.iter().fold(0, |acc, &c| mul(acc, alpha(j)) ^ c))
+        .collect();
+    if syndromes.iter().all(|&s| s == 0) {
+        return Some(0);
+    }
+
+    let mut locator = vec![0u8; ec_len + 1];
+    locator[0] = 1;
+    let mut prev = locator.clone();
+    let (mut errors, mut shift, mut prev_disc) = (0, 1, 1u8);
+    for k in 0..ec_len {
+        let mut disc = syndromes[k];
+        for i in 1..=errors {
+            disc ^= mul(locator[i], syndromes[k - i]);
+        }
+        if disc == 0 {
+            shift += 1;
+            continue;
+        }
+        let coef = div(disc, prev_disc);
+        let saved = locator.clone();
+        for i in 0..=ec_len - shift {
+            locator[i + shift] ^= mul(coef, prev[i]);
+        }
+        if 2 * errors <= k {
+            errors = k + 1 - errors;
+            prev = saved;
+            prev_disc = disc;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+    if 2 * errors > ec_len {
+        return None;
+    }
+
+    let positions: Vec<usize> = (0..n)
+        .filter(|&i| eval(&locator, alpha(255 - (n - 1 - i) % 255)) == 0)
+        .collect();
+    if positions.len() != errors {
+        return None;
+    }
+
+    let evaluator: Vec<u8> = (0..ec_len)
+        .map(|k| (0..=k).fold(0, |acc, i| acc ^ mul(syndromes[k - i], locator[i])))
+        .collect();
+    for &i in &positions {
+        let x = alpha(n - 1 - i);
+        let x_inv = alpha(255 - (n - 1 - i) % 255);
+        // Formal derivative: only the odd terms survive in characteristic 2.
+        let derivative = (1..locator.len()).step_by(2).fold(0, |acc, k| {
+            acc ^ mul(
+                locator[k],
+                GF.0[(GF.1[x_inv as usize] as usize * (k - 1)) % 255],
+            )
+        });
+        if derivative == 0 {
+            return None;
+        }
+        block[i] ^= mul(x, div(eval(&evaluator, x_inv), derivative));
+    }
+    Some(errors)
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, bits: usize) -> Result<u32> {
+        if bits > self.remaining() {
+            bail!("segment data runs past the end of the symbol");
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            value = value << 1 | bit as u32;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+}
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Parse the segments in the corrected data codewords.
+fn parse_segments(data: &[u8], version: Version) -> Result<String> {
+    let group = match version {
+        Version::Normal(1..=9) | Version::Micro(_) => 0,
+        Version::Normal(10..=26) => 1,
+        Version::Normal(_) => 2,
+    };
+    let mut reader = BitReader { data, pos: 0 };
+    let mut out: Vec<u8> = Vec::new();
+    let mut fnc1 = false;
+    while reader.remaining() >= 4 {
+        match reader.read(4)? {
+            0 => break,
+            1 => {
+                let mut count = reader.read([10, 12, 14][group])?;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = reader.read([4, 7, 10][digits as usize - 1])?;
+                    if value >= 10u32.pow(digits) {
+                        bail!("invalid numeric segment");
+                    }
+                    out.extend(format!("{:0width$}", value, width = digits as usize).bytes());
+                    count -= digits;
+                }
+            }
+            2 => {
+                let mut count = reader.read([9, 11, 13][group])?;
+                let mut chars = Vec::new();
+                while count > 0 {
+                    if count >= 2 {
+                        let value = reader.read(11)? as usize;
+                        if value >= 45 * 45 {
+                            bail!("invalid alphanumeric segment");
+                        }
+                        chars.extend([ALPHANUMERIC[value / 45], ALPHANUMERIC[value % 45]]);
+                        count -= 2;
+                    } else {
+                        let value = reader.read(6)? as usize;
+                        chars.push(
+                            *ALPHANUMERIC
+                                .get(value)
+                                .ok_or_else(|| anyhow!("invalid alphanumeric segment"))?,
+                        );
+                        count -= 1;
+                    }
+                }
+                if fnc1 {
+                    // `%` stands for the GS1 separator; a literal `%` is doubled.
+                    let mut i = 0;
+                    while i < chars.len() {
+                        if chars[i] == b'%' {
+                            if chars.get(i + 1) == Some(&b'%') {
+                                out.push(b'%');
+                                i += 1;
+                            } else {
+                                out.push(0x1d);
+                            }
+                        } else {
+                            out.push(chars[i]);
+                        }
+                        i += 1;
+                    }
+                } else {
+                    out.extend(chars);
+                }
+            }
+            4 => {
+                let count = reader.read([8, 16, 16][group])?;
+                for _ in 0..count {
+                    out.push(reader.read(8)? as u8);
+                }
+            }
+            8 => {
+                let count = reader.read([8, 10, 12][group])?;
+                let mut sjis = Vec::new();
+                for _ in 0..count {
+                    let value = reader.read(13)?;
+                    let code = ((value / 0xc0) << 8) | (value % 0xc0);
+                    let code = if code + 0x8140 <= 0x9ffc {
+                        code + 0x8140
+                    } else {
+                        code + 0xc140
+                    };
+                    sjis.extend([(code >> 8) as u8, code as u8]);
+                }
+                let (text, _) = SHIFT_JIS.decode_without_bom_handling(&sjis);
+                out.extend(text.as_bytes());
+            }
+            7 => {
+                // ECI designator; the text is taken as UTF-8 either way.
+                let first = reader.read(8)?;
+                if first & 0x80 != 0 {
+                    reader.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                }
+            }
+            5 => fnc1 = true,
+            9 => {
+                reader.read(8)?;
+                fnc1 = true;
+            }
+            3 => {
+                reader.read(16)?;
+            }
+            mode => bail!("unknown segment mode {}", mode),
+        }
+    }
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::render_qr_rgba;
+    use crate::symbol::{self, EncodeOptions};
+
+    #[test]
+    fn test_correct_repairs_codewords() {
+        let data: Vec<u8> = b"reed solomon".to_vec();
+        let version = Version::Normal(1);
+        let mut bits = qrcode::bits::Bits::new(version);
+        bits.push_byte_data(&data).unwrap();
+        bits.push_terminator(EcLevel::M).unwrap();
+        let bytes = bits.into_bytes();
+        let (_, ec) = qrcode::ec::construct_codewords(&bytes, version, EcLevel::M).unwrap();
+        let clean = [bytes, ec].concat();
+
+        let mut block = clean.clone();
+        assert_eq!(correct(&mut block, 10), Some(0));
+        for (i, flip) in [(0, 0xff), (7, 0x01), (15, 0x80), (20, 0x3c)] {
+            block[i] ^= flip;
+        }
+        assert_eq!(correct(&mut block, 10), Some(4));
+        assert_eq!(block, clean);
+
+        for i in [1, 3, 5, 9, 11, 17] {
+            block[i] ^= 0x55;
+        }
+        assert_ne!(correct(&mut block, 10), Some(6));
+    }
+
+    #[test]
+    fn test_decode_rendered_codes() {
+        let opts = EncodeOptions::default();
+        for (text, size) in [
+            ("https://example.com", 200),
+            ("0123456789012345678901234567890123456789", 240),
+            ("HELLO WORLD 1234 / ABC:DEF", 240),
+            ("日本語のテキスト", 240),
+            (
+                &"https://example.com/a-much-longer-path?with=query&and=more".repeat(4),
+                600,
+            ),
+        ] {
+            let code = symbol::encode(text, &opts).unwrap();
+            let img = render_qr_rgba(&code, size, 4, Color::BLACK).unwrap();
+            let decoded = decode(&img).unwrap();
+            assert_eq!(decoded.text, text);
+            assert_eq!(decoded.corrected, 0);
+        }
+
+        let gs1 = EncodeOptions {
+            fnc1: true,
+            ..Default::default()
+        };
+        let text = "0101234567890128\u{1d}10ABC%1";
+        let code = symbol::encode(text, &gs1).unwrap();
+        assert_eq!(
+            decode(&render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap())
+                .unwrap()
+                .text,
+            text
+        );
+
+        // Light modules on a dark ground read too, and say so.
+        let code = symbol::encode("https://example.com", &opts).unwrap();
+        let img = crate::render_qr_on(&code, 200, 4, Color::WHITE, Color::BLACK).unwrap();
+        let decoded = decode(&img).unwrap();
+        assert_eq!(decoded.text, "https://example.com");
+        assert!(decoded.inverted);
+        let img = render_qr_rgba(&code, 200, 4, Color::BLACK).unwrap();
+        assert!(!decode(&img).unwrap().inverted);
+
+        // So does a mirrored code, whichever way it was flipped.
+        for flip in [
+            image::imageops::flip_horizontal,
+            image::imageops::flip_vertical,
+        ] {
+            assert_eq!(decode(&flip(&img)).unwrap().text, "https://example.com");
+        }
+    }
+
+    #[test]
+    fn test_decode_rotated_and_damaged() {
+        let code = symbol::encode("https://example.com/rotated", &Default::default()).unwrap();
+        let img = render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap();
+        let turned = image::imageops::rotate90(&img);
+        assert_eq!(decode(&turned).unwrap().text, "https://example.com/rotated");
+
+        // Knock out a few data modules; EC repairs them.
+        let mut damaged = img.clone();
+        let ppm = img.width() / (code.width() as u32 + 8);
+        for (mx, my) in [(12, 12), (14, 15), (16, 10)] {
+            for y in 0..ppm {
+                for x in 0..ppm {
+                    let p = damaged.get_pixel_mut((mx + 4) * ppm + x, (my + 4) * ppm + y);
+                    p.0[..3].iter_mut().for_each(|c| *c = 255 - *c);
+                }
+            }
+        }
+        let decoded = decode(&damaged).unwrap();
+        assert_eq!(decoded.text, "https://example.com/rotated");
+        assert!(decoded.corrected > 0);
+
+        let blank: Canvas = image::ImageBuffer::from_pixel(100, 100, image::Rgba([255; 4]));
+        assert!(decode(&blank).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! Decorations applied around the finished code: quiet zone color, rounded or
+//! circular crops, the keyline border drawn outside it and drop shadows.
+
+use crate::color::Color;
+use crate::raster::{self, Canvas, RoundedRect};
+use image::{GrayImage, ImageBuffer, Luma, imageops};
+use std::f32::consts::SQRT_2;
+use std::fmt;
+use std::str::FromStr;
+
+/// A solid keyline around the whole output, e.g. `--border "2px #000"`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Border {
+    pub width: u32,
+    pub color: Color,
+}
+
+impl FromStr for Border {
+    type Err = String;
+
+    /// Accepts a width (`2px` or `2`) and an optional color, in either order.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut width = None;
+        let mut color = None;
+        for token in s.split_whitespace() {
+            let digits = token.strip_suffix("px").unwrap_or(token);
+            if let Ok(px) = digits.parse::<u32>() {
+                width = Some(px);
+            } else {
+                color = Some(token.parse::<Color>()?);
+            }
+        }
+        match width {
+            Some(0) | None => Err(format!(
+                "expected a border width such as \"2px #000\", got '{}'",
+                s
+            )),
+            Some(width) => Ok(Self {
+                width,
+                color: color.unwrap_or(Color::BLACK),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Border {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}px {}", self.width, self.color)
+    }
+}
+
+/// A soft shadow, e.g. `--shadow "blur=12,offset=4,opacity=0.3"`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shadow {
+    /// Blur radius in pixels (about two standard deviations of the Gaussian).
+    pub blur: f32,
+    /// Displacement of the shadow in pixels, (x, y).
+    pub offset: (i32, i32),
+    pub opacity: f32,
+    pub color: Color,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Self {
+            blur: 12.0,
+            offset: (0, 4),
+            opacity: 0.3,
+            color: Color::BLACK,
+        }
+    }
+}
+
+impl FromStr for Shadow {
+    type Err = String;
+
+    /// Comma-separated `key=value` pairs; omitted keys keep their defaults.
+    /// `offset=N` drops the shadow N pixels straight down, `offset=X:Y` moves it on both axes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut shadow = Self::default();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value in shadow spec, got '{}'", pair))?;
+            let number = |v: &str| {
+                v.trim()
+                    .strip_suffix("px")
+                    .unwrap_or(v.trim())
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid shadow {} '{}'", key, value))
+            };
+            match key.trim() {
+                "blur" => shadow.blur = number(value)?.max(0.0),
+                "offset" => {
+                    shadow.offset = match value.split_once(':') {
+                        Some((x, y)) => (number(x)?.round() as i32, number(y)?.round() as i32),
+                        None => (0, number(value)?.round() as i32),
+                    }
+                }
+                "opacity" => {
+                    let opacity = number(value)?;
+                    if !(0.0..=1.0).contains(&opacity) {
+                        return Err(format!("shadow opacity must be 0..1, got {}", value));
+                    }
+                    shadow.opacity = opacity;
+                }
+                "color" => shadow.color = value.parse()?,
+                other => {
+                    return Err(format!(
+                        "unknown shadow key '{}' (expected blur, offset, opacity or color)",
+                        other
+                    ));
+                }
+            }
+        }
+        Ok(shadow)
+    }
+}
+
+impl fmt::Display for Shadow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blur={},offset={}:{},opacity={},color={}",
+            self.blur, self.offset.0, self.offset.1, self.opacity, self.color
+        )
+    }
+}
+
+impl Shadow {
+    /// How far the shadow can reach beyond the shape that casts it (3 sigma plus the offset).
+    pub fn extent(&self) -> u32 {
+        let (dx, dy) = self.offset;
+        (self.blur * 1.5).ceil() as u32 + dx.unsigned_abs().max(dy.unsigned_abs())
+    }
+
+    /// Composite the blurred, offset silhouette of `shape` (its alpha channel) onto `img`.
+    /// `shape` must have the same dimensions as `img`.
+    pub fn cast(&self, img: &mut Canvas, shape: &Canvas) {
+        let alpha: GrayImage = ImageBuffer::from_fn(shape.width(), shape.height(), |x, y| {
+            Luma([shape.get_pixel(x, y)[3]])
+        });
+        let blurred = match self.blur > 0.0 {
+            true => imageops::blur(&alpha, self.blur / 2.0),
+            false => alpha,
+        };
+        let (w, h) = (blurred.width() as i64, blurred.height() as i64);
+        let (dx, dy) = self.offset;
+        for (x, y, px) in img.enumerate_pixels_mut() {
+            let sx = x as i64 - dx as i64;
+            let sy = y as i64 - dy as i64;
+            if sx < 0 || sy < 0 || sx >= w || sy >= h {
+                continue;
+            }
+            let a = blurred.get_pixel(sx as u32, sy as u32)[0];
+            if a > 0 {
+                let coverage = a as f32 / 255.0 * self.opacity;
+                *px = raster::composite(*px, self.color.0, coverage);
+            }
+        }
+    }
+}
+
+/// Place `img` on a `background` canvas grown to fit its drop shadow.
+/// Returns the new canvas and the margin at which `img` was drawn.
+pub fn add_drop_shadow(img: &Canvas, shadow: &Shadow, background: Color) -> (Canvas, u32) {
+    let m = shadow.extent();
+    let (w, h) = (img.width() + 2 * m, img.height() + 2 * m);
+    let mut silhouette = ImageBuffer::new(w, h);
+    raster::replace(&mut silhouette, img, m, m);
+
+    let mut out = ImageBuffer::from_pixel(w, h, background.0);
+    shadow.cast(&mut out, &silhouette);
+    raster::overlay(&mut out, img, m, m);
+    (out, m)
+}
+
+/// Paint everything outside the symbol (the outer `margin` pixels) with `color`.
+pub fn paint_quiet_zone(img: &mut Canvas, margin: u32, color: Color) {
+    let (w, h) = img.dimensions();
+    for (x, y, px) in img.enumerate_pixels_mut() {
+        let inside = x >= margin && y >= margin && x < w - margin && y < h - margin;
+        if !inside {
+            *px = color.0;
+        }
+    }
+}
+
+/// Round the corners of `img` to transparency, anti-aliasing the curve.
+pub fn round_corners(img: &mut Canvas, radius: u32) {
+    let (w, h) = img.dimensions();
+    let outline = RoundedRect::new(0.0, 0.0, w as f32, h as f32, radius as f32);
+    raster::clip(img, &outline);
+}
+
+/// Largest corner radius that stays inside a quiet zone of `margin` pixels: the curve
+/// must pass outside the outer corner of each finder pattern, `(r - m)·√2 <= r`.
+pub fn max_corner_radius(margin: u32) -> u32 {
+    (margin as f32 * (2.0 + SQRT_2)).floor() as u32
+}
+
+/// Cut `img` to a circle around its center that keeps the symbol square `symbol`
+/// (x, y, side) plus `margin` pixels inside. Where the circle reaches past `img` the
+/// canvas grows with `fill`. Returns the new canvas and the offset of `img` in it.
+pub fn circle_crop(
+    img: &Canvas,
+    symbol: (u32, u32, u32),
+    margin: u32,
+    fill: Color,
+) -> (Canvas, (u32, u32)) {
+    let (w, h) = img.dimensions();
+    let (x, y, side) = symbol;
+    let reach = |start: u32, center: f32| {
+        (start as f32 - center)
+            .abs()
+            .max(((start + side) as f32 - center).abs())
+    };
+    let radius = reach(x, w as f32 / 2.0).hypot(reach(y, h as f32 / 2.0)) + margin as f32;
+
+    let d = ((2.0 * radius).ceil() as u32).max(w).max(h);
+    let (ox, oy) = ((d - w) / 2, (d - h) / 2);
+    let mut out = ImageBuffer::from_pixel(d, d, fill.0);
+    raster::replace(&mut out, img, ox, oy);
+    let circle = RoundedRect::new(0.0, 0.0, d as f32, d as f32, d as f32 / 2.0);
+    raster::clip(&mut out, &circle);
+    (out, (ox, oy))
+}
+
+/// Surround `img` with a `border.width` pixel frame, growing the canvas. When `img`
+/// has rounded corners of `radius` the frame follows them.
+pub fn add_border(img: &Canvas, border: Border, radius: f32) -> Canvas {
+    let b = border.width;
+    let (w, h) = (img.width() + 2 * b, img.height() + 2 * b);
+    if radius <= 0.0 {
+        let mut out = ImageBuffer::from_pixel(w, h, border.color.0);
+        raster::replace(&mut out, img, b, b);
+        return out;
+    }
+    let mut out = ImageBuffer::new(w, h);
+    let outline = RoundedRect::new(0.0, 0.0, w as f32, h as f32, radius + b as f32);
+    raster::fill(&mut out, &outline, border.color.0);
+    raster::overlay(&mut out, img, b, b);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_parse_border() {
+        assert_eq!(
+            "2px #ff0000".parse::<Border>().unwrap(),
+            Border {
+                width: 2,
+                color: Color(Rgba([255, 0, 0, 255]))
+            }
+        );
+        assert_eq!("#000 3".parse::<Border>().unwrap().width, 3);
+        assert_eq!("1px".parse::<Border>().unwrap().color, Color::BLACK);
+        assert!("#000".parse::<Border>().is_err());
+        assert!("0px #000".parse::<Border>().is_err());
+        assert!("2px notacolor".parse::<Border>().is_err());
+    }
+
+    #[test]
+    fn test_border_and_quiet_zone() {
+        let white = Color::WHITE.0;
+        let red = Rgba([255, 0, 0, 255]);
+        let mut img: Canvas = ImageBuffer::from_pixel(10, 10, white);
+        paint_quiet_zone(&mut img, 2, Color(red));
+        assert_eq!(*img.get_pixel(1, 5), red);
+        assert_eq!(*img.get_pixel(2, 2), white);
+        assert_eq!(*img.get_pixel(7, 7), white);
+        assert_eq!(*img.get_pixel(8, 7), red);
+
+        let framed = add_border(
+            &img,
+            Border {
+                width: 3,
+                color: Color::BLACK,
+            },
+            0.0,
+        );
+        assert_eq!(framed.dimensions(), (16, 16));
+        assert_eq!(*framed.get_pixel(2, 8), Color::BLACK.0);
+        assert_eq!(*framed.get_pixel(3, 8), red);
+        assert_eq!(*framed.get_pixel(8, 8), white);
+    }
+
+    #[test]
+    fn test_rounded_and_circle_crop() {
+        let white = Color::WHITE.0;
+        let mut img: Canvas = ImageBuffer::from_pixel(40, 40, white);
+        round_corners(&mut img, 8);
+        assert_eq!(img.get_pixel(0, 0)[3], 0);
+        assert_eq!(*img.get_pixel(20, 0), white);
+        assert_eq!(max_corner_radius(10), 34);
+
+        // A 20px symbol centered in 40px needs a radius of 10·√2 + 4, so the canvas grows.
+        let img: Canvas = ImageBuffer::from_pixel(40, 40, white);
+        let (circle, (ox, oy)) = circle_crop(&img, (10, 10, 20), 4, Color::TRANSPARENT);
+        assert_eq!(circle.dimensions(), (40, 40));
+        assert_eq!((ox, oy), (0, 0));
+        assert_eq!(circle.get_pixel(0, 0)[3], 0);
+        assert_eq!(*circle.get_pixel(10, 10), white);
+
+        let (circle, (ox, oy)) = circle_crop(&img, (2, 2, 36), 4, Color::BLACK);
+        assert_eq!(circle.dimensions(), (59, 59));
+        assert_eq!((ox, oy), (9, 9));
+        assert_eq!(*circle.get_pixel(30, 5), Color::BLACK.0);
+        assert_eq!(*circle.get_pixel(12, 12), white);
+
+        // The keyline follows the rounded outline.
+        let mut img: Canvas = ImageBuffer::from_pixel(40, 40, white);
+        round_corners(&mut img, 8);
+        let border = Border {
+            width: 2,
+            color: Color::BLACK,
+        };
+        let framed = add_border(&img, border, 8.0);
+        assert_eq!(framed.get_pixel(0, 0)[3], 0);
+        assert_eq!(*framed.get_pixel(22, 0), Color::BLACK.0);
+    }
+
+    #[test]
+    fn test_parse_shadow() {
+        let shadow: Shadow = "blur=12,offset=4,opacity=0.3".parse().unwrap();
+        assert_eq!(shadow, Shadow::default());
+        let shadow: Shadow = "offset=-2:6, color=#123456".parse().unwrap();
+        assert_eq!(shadow.offset, (-2, 6));
+        assert_eq!(shadow.color, "#123456".parse().unwrap());
+        assert_eq!(shadow.to_string().parse::<Shadow>().unwrap(), shadow);
+        assert!("opacity=1.5".parse::<Shadow>().is_err());
+        assert!("spread=3".parse::<Shadow>().is_err());
+        assert!("blur".parse::<Shadow>().is_err());
+    }
+
+    #[test]
+    fn test_drop_shadow() {
+        let white = Color::WHITE.0;
+        let img: Canvas = ImageBuffer::from_pixel(20, 20, white);
+        let shadow = Shadow {
+            blur: 4.0,
+            offset: (0, 3),
+            ..Default::default()
+        };
+        let (out, m) = add_drop_shadow(&img, &shadow, Color::TRANSPARENT);
+        assert_eq!(m, 9);
+        assert_eq!(out.dimensions(), (38, 38));
+        assert_eq!(*out.get_pixel(m + 10, m + 10), white);
+        // The shadow falls below the image, not above it, and fades out by the edge.
+        let below = out.get_pixel(m + 10, m + 21)[3];
+        let above = out.get_pixel(m + 10, m - 2)[3];
+        assert!(below > above, "{} vs {}", below, above);
+        assert!(below as f32 <= 255.0 * shadow.opacity);
+        assert_eq!(out.get_pixel(0, 0)[3], 0);
+    }
+}
@@ -0,0 +1,122 @@
+//! How big a code has to be printed to scan from a given distance, and a warning
+//! when the payload makes that impractical.
+//!
+//! The estimate is the signage 10:1 rule: a code scans from ten times its width.
+//! The rule was drawn from short links of about 25 modules a side; a denser code
+//! packs more modules into the same width, so it is scaled by the module count.
+
+use crate::meta;
+use crate::symbol::{self, EncodeOptions, Symbol};
+use qrcode::{EcLevel, Version};
+
+/// Distance to width ratio of the 10:1 rule.
+const DISTANCE_RATIO: f32 = 10.0;
+/// Modules a side of the codes the rule was drawn from.
+const REFERENCE_MODULES: usize = 25;
+/// From this version on, the density warning is given.
+const DENSE_VERSION: i16 = 10;
+/// Distances quoted in the warning: hand-held, across a counter, a sign.
+const TYPICAL_DISTANCES_MM: [f32; 3] = [300.0, 1000.0, 3000.0];
+
+/// Smallest printed width of `modules` modules a side, quiet zone not included,
+/// to scan from `distance_mm`.
+pub fn min_width_mm(distance_mm: f32, modules: usize) -> f32 {
+    distance_mm / DISTANCE_RATIO * modules.max(REFERENCE_MODULES) as f32 / REFERENCE_MODULES as f32
+}
+
+/// Smallest printed side of `code` with its `quiet` zone to scan from `distance_mm`.
+pub fn min_print_mm(distance_mm: f32, code: &Symbol, quiet: u32) -> f32 {
+    let modules = code.width();
+    min_width_mm(distance_mm, modules) * (modules + 2 * quiet as usize) as f32 / modules as f32
+}
+
+/// A length in millimetres as people say it: `8.5 cm`, `31 cm`, `1.2 m`.
+pub fn describe_mm(mm: f32) -> String {
+    match mm {
+        mm if mm < 100.0 => format!("{:.1} cm", mm / 10.0),
+        mm if mm < 1000.0 => format!("{:.0} cm", mm / 10.0),
+        mm if (mm / 100.0).round() % 10.0 == 0.0 => format!("{:.0} m", mm / 1000.0),
+        mm => format!("{:.1} m", mm / 1000.0),
+    }
+}
+
+/// A warning when `payload` needed a dense `code`, with print sizes for typical
+/// distances and what would make it smaller. `with_logo` holds back the advice to
+/// lower error correction, which the logo needs.
+pub fn advice(
+    code: &Symbol,
+    payload: &str,
+    encode: &EncodeOptions,
+    with_logo: bool,
+) -> Option<String> {
+    let Version::Normal(version) = code.version() else {
+        return None;
+    };
+    if version < DENSE_VERSION || encode.version.is_some() {
+        return None;
+    }
+    let modules = code.width();
+    let sizes: Vec<String> = TYPICAL_DISTANCES_MM
+        .iter()
+        .map(|&d| {
+            format!(
+                "{} wide to scan from {}",
+                describe_mm(min_width_mm(d, modules)),
+                describe_mm(d)
+            )
+        })
+        .collect();
+    let mut fixes = vec!["shorten the link (--shorten)".to_string()];
+    if !with_logo && matches!(encode.ec_level, EcLevel::Q | EcLevel::H) {
+        let lower = EncodeOptions {
+            ec_level: EcLevel::M,
+            ec_boost: false,
+            ..*encode
+        };
+        if let Ok(smaller) = symbol::encode(payload, &lower)
+            && smaller.width() < modules
+        {
+            fixes.push(format!("use --ec-level M ({} modules)", smaller.width()));
+        }
+    }
+    Some(format!(
+        "a {}-byte payload needs QR version {}-{}, {} modules a side; print it at least {}; {}",
+        payload.len(),
+        version,
+        meta::ec_level_label(code.error_correction_level()),
+        modules,
+        sizes.join(", "),
+        fixes.join(" or ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advice() {
+        assert_eq!(min_width_mm(3000.0, 21), 300.0);
+        assert_eq!(min_width_mm(1000.0, 50), 200.0);
+        assert_eq!(describe_mm(85.0), "8.5 cm");
+        assert_eq!(describe_mm(1234.0), "1.2 m");
+        assert_eq!(describe_mm(3000.0), "3 m");
+
+        let opts = EncodeOptions::default();
+        let short = "https://example.com/menu";
+        // Version 3: 29 modules, 37 with the quiet zone, from 3 m.
+        let code = symbol::encode(short, &opts).unwrap();
+        assert!((min_print_mm(3000.0, &code, 4) - 444.0).abs() < 0.01);
+        assert!(advice(&symbol::encode(short, &opts).unwrap(), short, &opts, false).is_none());
+
+        let long = format!("https://example.com/{}", "campaign-tracking-".repeat(15));
+        let code = symbol::encode(&long, &opts).unwrap();
+        let warning = advice(&code, &long, &opts, false).unwrap();
+        assert!(warning.contains("--ec-level M"), "{}", warning);
+        assert!(
+            !advice(&code, &long, &opts, true)
+                .unwrap()
+                .contains("--ec-level")
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! `qrbrand diff`: where two codes differ, module by module.
+//!
+//! Both images are decoded and their sampled module grids compared. The picture drawn
+//! is the second code with unchanged modules in grey and white, and changed ones in
+//! red (solid where the second code is dark, pale where it is light), so a mask or
+//! encoder change shows up as a pattern rather than as "the PNGs differ".
+
+use crate::decode::{self, Decoded};
+use crate::meta::{ec_level_label, version_label};
+use crate::raster::{self, Canvas};
+use crate::{Args, i18n, log, output};
+use anyhow::{Context, Result, bail};
+use clap::Args as ClapArgs;
+use image::Rgba;
+use std::path::{Path, PathBuf};
+
+#[derive(ClapArgs, Debug)]
+pub struct DiffArgs {
+    /// The code before the change
+    pub a: PathBuf,
+
+    /// The code after the change
+    pub b: PathBuf,
+}
+
+const SAME_DARK: Rgba<u8> = Rgba([96, 96, 96, 255]);
+const SAME_LIGHT: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const NOW_DARK: Rgba<u8> = Rgba([220, 38, 38, 255]);
+const NOW_LIGHT: Rgba<u8> = Rgba([254, 202, 202, 255]);
+
+pub fn run(args: &Args, diff: &DiffArgs, out_given: bool) -> Result<()> {
+    let a = read(&diff.a)?;
+    let b = read(&diff.b)?;
+    for (path, code) in [(&diff.a, &a), (&diff.b, &b)] {
+        println!(
+            "{}: version {}, EC {}, mask {}: {}",
+            path.display(),
+            version_label(code.version),
+            ec_level_label(code.ec_level),
+            code.mask,
+            code.text
+        );
+    }
+    if a.version != b.version {
+        bail!(
+            "version {} and version {} codes cannot be compared module by module",
+            version_label(a.version),
+            version_label(b.version)
+        );
+    }
+
+    let changed = a.grid.iter().zip(&b.grid).filter(|(a, b)| a != b).count();
+    let payload = if a.text == b.text {
+        "same payload"
+    } else {
+        "different payloads"
+    };
+    println!(
+        "{} of {} modules differ ({})",
+        changed,
+        b.grid.len(),
+        payload
+    );
+
+    let out = if out_given { &args.out } else { "diff.png" };
+    let img = render(&a, &b, args.size.px, args.quiet)?;
+    output::save_png_with_text(&img, out, &[], None, None)?;
+    log::info(i18n::tr("wrote", &[("path", out.into())]));
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Decoded> {
+    let img = image::open(path)
+        .with_context(|| format!("Failed to open image: {}", path.display()))?
+        .to_rgba8();
+    decode::decode(&img).with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+/// The module grid of `b` with the modules that differ from `a` in red, `quiet`
+/// modules of border, at most `size` pixels square.
+fn render(a: &Decoded, b: &Decoded, size: u32, quiet: u32) -> Result<Canvas> {
+    let n = b.version.width() as u32;
+    let ppm = size / (n + 2 * quiet);
+    if ppm == 0 {
+        bail!("--size {} is too small for {} modules", size, n + 2 * quiet);
+    }
+    let side = ppm * (n + 2 * quiet);
+    let mut img = Canvas::from_pixel(side, side, SAME_LIGHT);
+    for (i, (&was, &now)) in a.grid.iter().zip(&b.grid).enumerate() {
+        let color = match (was, now) {
+            (true, true) => SAME_DARK,
+            (false, false) => continue,
+            (false, true) => NOW_DARK,
+            (true, false) => NOW_LIGHT,
+        };
+        let (x, y) = (i as u32 % n + quiet, i as u32 / n + quiet);
+        for row in y * ppm..(y + 1) * ppm {
+            raster::fill_row(&mut img, row, x * ppm..(x + 1) * ppm, color);
+        }
+    }
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::render_qr_rgba;
+    use crate::symbol::{self, EncodeOptions};
+
+    #[test]
+    fn test_render() {
+        let url = "https://example.com";
+        let decoded = |mask| {
+            let opts = EncodeOptions {
+                mask: Some(mask),
+                ..Default::default()
+            };
+            let code = symbol::encode(url, &opts).unwrap();
+            decode::decode(&render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap()).unwrap()
+        };
+        let (a, b) = (decoded(2), decoded(5));
+        assert_eq!((a.mask, b.mask), (2, 5));
+        assert_eq!(a.text, b.text);
+
+        let img = render(&a, &b, 300, 4).unwrap();
+        let n = b.version.width() as usize;
+        let ppm = img.width() as usize / (n + 8);
+        let changed: Vec<usize> = (0..n * n).filter(|&i| a.grid[i] != b.grid[i]).collect();
+        assert!(!changed.is_empty());
+        let first = changed[0];
+        let now = if b.grid[first] { NOW_DARK } else { NOW_LIGHT };
+        // A finder corner, the separator beside it and the first changed module.
+        for (i, expected) in [(0, SAME_DARK), (7, SAME_LIGHT), (first, now)] {
+            let (x, y) = ((i % n + 4) * ppm, (i / n + 4) * ppm);
+            assert_eq!(*img.get_pixel(x as u32, y as u32), expected, "module {}", i);
+        }
+        let same = render(&a, &a, 300, 4).unwrap();
+        assert!(same.pixels().all(|p| *p == SAME_DARK || *p == SAME_LIGHT));
+    }
+}
@@ -0,0 +1,294 @@
+//! EAN-13, UPC-A and EAN-8 retail barcodes, as the GS1 General Specifications lay
+//! them out.
+//!
+//! Each digit is seven modules in two bars and two spaces. Left-hand digits use the
+//! odd-parity L set or, in EAN-13, the even-parity G set in the pattern that encodes
+//! the leading digit; right-hand digits use the R set. Guard patterns frame the two
+//! halves and run longer than the data bars, with the digits printed under the rest.
+//! UPC-A is an EAN-13 with a leading zero, printed with its first and last digits
+//! outside the bars.
+
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+
+/// Set L, bar = 1. Set R is the complement and set G the mirror of R.
+const L: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011",
+    "0110111", "0001011",
+];
+
+/// Set for each left-hand digit of an EAN-13, by leading digit.
+const PARITY: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL",
+    "LGGLGL",
+];
+
+const EDGE_GUARD: &str = "101";
+const CENTRE_GUARD: &str = "01010";
+
+/// How far the guard bars run past the data bars, in modules.
+pub const GUARD_EXTRA: f64 = 5.0;
+/// Gap between the data bars and the top of the digits, in modules.
+const TEXT_GAP: f64 = 1.0;
+/// Type size of the digits, and of the UPC-A digits outside the bars, in modules.
+pub const TEXT_EM: f64 = 10.0;
+pub const SMALL_EM: f64 = 7.0;
+/// Digit height as a share of the type size, for common sans faces.
+pub const DIGIT_HEIGHT: f64 = 0.73;
+/// Space under the digits, in modules.
+const TEXT_FOOT: f64 = 1.5;
+/// Module width at 100% magnification, in millimetres.
+pub const NOMINAL_X_MM: f64 = 0.33;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// 13 digits, used worldwide
+    Ean13,
+    /// 12 digits, used in North America
+    UpcA,
+    /// 8 digits, for small packs
+    Ean8,
+}
+
+impl Kind {
+    /// Number of digits, the check digit included.
+    pub fn digits(self) -> usize {
+        match self {
+            Self::Ean13 => 13,
+            Self::UpcA => 12,
+            Self::Ean8 => 8,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ean13 => "EAN-13",
+            Self::UpcA => "UPC-A",
+            Self::Ean8 => "EAN-8",
+        }
+    }
+
+    /// Quiet zone on the left and right, in modules.
+    pub fn quiet(self) -> (usize, usize) {
+        match self {
+            Self::Ean13 => (11, 7),
+            Self::UpcA => (9, 9),
+            Self::Ean8 => (7, 7),
+        }
+    }
+
+    /// Height of the data bars at 100% magnification, in modules.
+    pub fn bar_height(self) -> f64 {
+        match self {
+            Self::Ean13 | Self::UpcA => 69.0,
+            Self::Ean8 => 55.0,
+        }
+    }
+
+    /// `digits` with the check digit appended, or verified if it is already there.
+    pub fn complete(self, digits: &str) -> Result<String> {
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_digit()) {
+            bail!("{} takes digits only; cannot encode {:?}", self.name(), c);
+        }
+        let n = self.digits();
+        if digits.len() == n - 1 {
+            return Ok(format!("{}{}", digits, check_digit(digits)));
+        }
+        if digits.len() != n {
+            bail!(
+                "{} takes {} digits, or {} without the check digit; got {}",
+                self.name(),
+                n,
+                n - 1,
+                digits.len()
+            );
+        }
+        let (data, given) = digits.split_at(n - 1);
+        let check = check_digit(data);
+        if given.as_bytes()[0] - b'0' != check {
+            bail!(
+                "check digit of {} is {}, not {}; leave it off to have it added",
+                data,
+                check,
+                given
+            );
+        }
+        Ok(digits.to_string())
+    }
+}
+
+/// The GS1 check digit for `data`: weights of 3 and 1 alternating from the right.
+pub fn check_digit(data: &str) -> u8 {
+    let sum: u32 = data
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| (b - b'0') as u32 * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// An encoded symbol, without its quiet zones.
+#[derive(Debug)]
+pub struct Barcode {
+    pub kind: Kind,
+    /// All digits, the check digit included.
+    pub digits: String,
+    /// One entry per module, `true` for a bar.
+    pub modules: Vec<bool>,
+    /// Modules whose bars run the guard length rather than the data bar height.
+    pub long: Vec<bool>,
+}
+
+impl Barcode {
+    /// Encode `digits` (with or without the check digit) as `kind`.
+    pub fn new(kind: Kind, digits: &str) -> Result<Self> {
+        let digits = kind.complete(digits)?;
+        let values: Vec<usize> = digits.bytes().map(|b| (b - b'0') as usize).collect();
+        let (parity, left, right) = match kind {
+            Kind::Ean13 => (PARITY[values[0]], &values[1..7], &values[7..]),
+            Kind::UpcA => (PARITY[0], &values[..6], &values[6..]),
+            Kind::Ean8 => ("LLLL", &values[..4], &values[4..]),
+        };
+
+        let mut pattern = String::from(EDGE_GUARD);
+        for (&d, set) in left.iter().zip(parity.chars()) {
+            let l = L[d];
+            match set {
+                'G' => pattern.extend(l.chars().rev().map(invert)),
+                _ => pattern.push_str(l),
+            }
+        }
+        pattern.push_str(CENTRE_GUARD);
+        for &d in right {
+            pattern.extend(L[d].chars().map(invert));
+        }
+        pattern.push_str(EDGE_GUARD);
+
+        let modules: Vec<bool> = pattern.chars().map(|c| c == '1').collect();
+        let half = left.len() * 7;
+        let mut guards = vec![0..3, 3 + half..8 + half, modules.len() - 3..modules.len()];
+        if kind == Kind::UpcA {
+            // The first and last digits' bars run long too, as they are printed outside.
+            guards.extend([3..10, modules.len() - 10..modules.len() - 3]);
+        }
+        let long = (0..modules.len())
+            .map(|i| guards.iter().any(|g| g.contains(&i)))
+            .collect();
+        Ok(Self {
+            kind,
+            digits,
+            modules,
+            long,
+        })
+    }
+
+    /// Width in modules, quiet zones included.
+    pub fn width(&self) -> usize {
+        let (left, right) = self.kind.quiet();
+        left + self.modules.len() + right
+    }
+
+    /// Height in modules, from the top of the bars to below the digits.
+    pub fn height(&self) -> f64 {
+        self.baseline() + TEXT_FOOT
+    }
+
+    /// Baseline of the digits, in modules down from the top of the bars.
+    pub fn baseline(&self) -> f64 {
+        self.kind.bar_height() + TEXT_GAP + TEXT_EM * DIGIT_HEIGHT
+    }
+
+    /// Each printed digit with the centre it sits at, in modules from the left edge of
+    /// the quiet zone, and its type size in modules.
+    pub fn text(&self) -> Vec<(f64, char, f64)> {
+        let (quiet, _) = self.kind.quiet();
+        let origin = quiet as f64;
+        let slot = |start: usize, i: usize| origin + (start + 7 * i) as f64 + 3.5;
+        // The right half starts after the start guard, the left digits and the centre guard.
+        let right_start = 8 + (self.modules.len() - 11) / 2;
+        let chars: Vec<char> = self.digits.chars().collect();
+        let end = origin + self.modules.len() as f64;
+        let mut text = Vec::with_capacity(chars.len());
+        match self.kind {
+            Kind::Ean13 => {
+                text.push((origin - 4.0, chars[0], TEXT_EM));
+                text.extend((0..6).map(|i| (slot(3, i), chars[1 + i], TEXT_EM)));
+                text.extend((0..6).map(|i| (slot(right_start, i), chars[7 + i], TEXT_EM)));
+            }
+            Kind::UpcA => {
+                text.push((origin - 4.5, chars[0], SMALL_EM));
+                text.extend((1..6).map(|i| (slot(3, i), chars[i], TEXT_EM)));
+                text.extend((0..5).map(|i| (slot(right_start, i), chars[6 + i], TEXT_EM)));
+                text.push((end + 4.5, chars[11], SMALL_EM));
+            }
+            Kind::Ean8 => {
+                text.extend((0..4).map(|i| (slot(3, i), chars[i], TEXT_EM)));
+                text.extend((0..4).map(|i| (slot(right_start, i), chars[4 + i], TEXT_EM)));
+            }
+        }
+        text
+    }
+}
+
+fn invert(c: char) -> char {
+    if c == '1' { '0' } else { '1' }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(modules: &[bool]) -> String {
+        modules.iter().map(|&b| if b { '1' } else { '0' }).collect()
+    }
+
+    #[test]
+    fn test_ean() {
+        assert_eq!(check_digit("400638133393"), 1);
+        assert_eq!(check_digit("03600029145"), 2);
+        assert_eq!(check_digit("9638507"), 4);
+        assert_eq!(
+            Kind::Ean13.complete("400638133393").unwrap(),
+            "4006381333931"
+        );
+        assert_eq!(Kind::UpcA.complete("036000291452").unwrap(), "036000291452");
+        assert!(Kind::UpcA.complete("036000291453").is_err());
+        assert!(Kind::Ean8.complete("963850").is_err());
+        assert!(Kind::Ean8.complete("9638507a").is_err());
+
+        // Leading 4 is LGLLGG: 0 in L, 0 in G, 6 in L, 3 in L, 8 in G, 1 in G.
+        let ean = Barcode::new(Kind::Ean13, "400638133393").unwrap();
+        assert_eq!(ean.modules.len(), 95);
+        assert_eq!(ean.width(), 113);
+        assert_eq!(
+            bits(&ean.modules[..45]),
+            "101".to_string()
+                + "0001101"
+                + "0100111"
+                + "0101111"
+                + "0111101"
+                + "0001001"
+                + "0110011"
+        );
+        assert_eq!(bits(&ean.modules[45..50]), "01010");
+        // Right-hand 1 in R.
+        assert_eq!(bits(&ean.modules[85..92]), "1100110");
+        assert!(ean.long[1] && ean.long[47] && ean.long[94] && !ean.long[3]);
+        assert_eq!(ean.text()[0].1, '4');
+        assert_eq!(ean.text()[1].0, 11.0 + 6.5);
+
+        // UPC-A is the EAN-13 with a leading zero, and runs its outer digits long.
+        let upc = Barcode::new(Kind::UpcA, "03600029145").unwrap();
+        let as_ean = Barcode::new(Kind::Ean13, "003600029145").unwrap();
+        assert_eq!(upc.modules, as_ean.modules);
+        assert!(upc.long[5] && upc.long[88] && !upc.long[10]);
+        assert_eq!(upc.text().len(), 12);
+
+        let ean8 = Barcode::new(Kind::Ean8, "9638507").unwrap();
+        assert_eq!(ean8.digits, "96385074");
+        assert_eq!(ean8.modules.len(), 67);
+        assert_eq!(bits(&ean8.modules[31..36]), "01010");
+        assert_eq!(ean8.text()[4].0, 7.0 + 36.0 + 3.5);
+    }
+}
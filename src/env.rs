@@ -0,0 +1,62 @@
+//! `QRBRAND_*` environment variables for every long option, for containers that
+//! configure serve and batch mode through the environment.
+//!
+//! The variable is `QRBRAND_` followed by the option's name in upper case with `-`
+//! as `_`: `--rate-limit` is `QRBRAND_RATE_LIMIT`, `--out-dir` is
+//! `QRBRAND_OUT_DIR`. Flags take `true` or `false`. The command line wins over the
+//! environment, which wins over a theme from the config file, since
+//! [`crate::theme::apply`] leaves both alone.
+
+use clap::{Arg, Command};
+
+/// Prefix of every variable.
+const PREFIX: &str = "QRBRAND_";
+
+/// `command` with each long option, its subcommands' included, also read from its
+/// `QRBRAND_*` variable. Options that already name a variable keep it.
+pub fn layer(command: Command) -> Command {
+    command.mut_args(with_env).mut_subcommands(layer)
+}
+
+fn with_env(arg: Arg) -> Arg {
+    if arg.get_env().is_some() {
+        return arg;
+    }
+    let Some(long) = arg.get_long() else {
+        return arg;
+    };
+    let name = format!("{}{}", PREFIX, long.to_uppercase().replace('-', "_"));
+    // The variables follow one rule, documented once, rather than crowd --help.
+    arg.env(name).hide_env(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_layer() {
+        let command = layer(Args::command());
+        command.clone().debug_assert();
+        let env = |command: &Command, id: &str| {
+            let arg = command.get_arguments().find(|a| a.get_id() == id).unwrap();
+            arg.get_env()
+                .map(|name| name.to_string_lossy().into_owned())
+        };
+        assert_eq!(env(&command, "size").as_deref(), Some("QRBRAND_SIZE"));
+        assert_eq!(
+            env(&command, "shorten_token").as_deref(),
+            Some("QRBRAND_SHORTEN_TOKEN")
+        );
+        let serve = command.find_subcommand("serve").unwrap();
+        assert_eq!(
+            env(serve, "rate_limit").as_deref(),
+            Some("QRBRAND_RATE_LIMIT")
+        );
+        assert_eq!(env(serve, "api_keys").as_deref(), Some("QRBRAND_API_KEY"));
+        let completions = command.find_subcommand("completions").unwrap();
+        assert_eq!(env(completions, "shell"), None);
+    }
+}
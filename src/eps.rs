@@ -0,0 +1,277 @@
+//! Encapsulated PostScript output for print RIPs that take nothing else.
+//!
+//! The module grid is written as filled rectangles (one per horizontal run of dark
+//! modules) in module units, scaled so the symbol and its quiet zone come out at the
+//! exact `--print-size`. Only the code itself is vector data here: a logo, caption or
+//! other decoration would have to be raster, so those flags are refused. With
+//! `--bleed` the quiet zone color runs past the trim, and `--crop-marks` adds hairline
+//! marks outside that.
+
+use crate::color::Color;
+use crate::marks::{self, HAIRLINE, Marks};
+use crate::symbol::Symbol;
+use crate::{Args, meta};
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// A physical length, kept in PostScript points (1/72 in).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Length(pub f64);
+
+impl FromStr for Length {
+    type Err = String;
+
+    /// A positive number with a unit: `50mm`, `5cm`, `3m`, `2in` or `144pt`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let points_per = match unit.to_ascii_lowercase().as_str() {
+            "mm" => 72.0 / 25.4,
+            "cm" => 72.0 / 2.54,
+            "m" => 72.0 / 0.0254,
+            "in" => 72.0,
+            "pt" => 1.0,
+            _ => {
+                return Err(format!(
+                    "expected a unit of mm, cm, m, in or pt, got '{}'",
+                    s
+                ));
+            }
+        };
+        match number.trim().parse::<f64>() {
+            Ok(n) if n > 0.0 && n.is_finite() => Ok(Self(n * points_per)),
+            _ => Err(format!(
+                "expected a positive length such as 50mm, got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+impl Length {
+    pub fn from_mm(mm: f64) -> Self {
+        Self(mm * 72.0 / 25.4)
+    }
+
+    pub fn mm(self) -> f64 {
+        self.0 * 25.4 / 72.0
+    }
+}
+
+/// The first flag given that draws something EPS output leaves out.
+pub fn unsupported(args: &Args) -> Option<&'static str> {
+    [
+        (args.image.is_some(), "--image"),
+        (args.show_url, "--show-url"),
+        (args.display_url.is_some(), "--display-url"),
+        (!args.alt_text.is_empty(), "--alt-text"),
+        (args.template.is_some(), "--template"),
+        (args.corner_radius > 0, "--corner-radius"),
+        (args.circle_crop, "--circle-crop"),
+        (args.shape == crate::round::Shape::Circle, "--shape circle"),
+        (args.border.is_some(), "--border"),
+        (args.shadow.is_some(), "--shadow"),
+        (args.module_gap > 0.0, "--module-gap"),
+        (args.module_style.is_hollow(), "--module-style"),
+        (args.fill_texture.is_some(), "--fill-texture"),
+        (args.invert, "--invert"),
+        (args.eye_color.is_some(), "--eye-color"),
+        (args.eye_inner_color.is_some(), "--eye-inner-color"),
+        (!args.eye_glyph.is_empty(), "--eye-glyph"),
+        (args.debug_overlay, "--debug-overlay"),
+        (args.rotate.is_some(), "--rotate"),
+        (args.flip.is_some(), "--flip"),
+        #[cfg(feature = "cli")]
+        (
+            matches!(args.command, Some(crate::Command::Label(_))),
+            "qrbrand label",
+        ),
+    ]
+    .into_iter()
+    .find_map(|(given, flag)| given.then_some(flag))
+}
+
+/// The EPS document for `code` with `quiet` modules of quiet zone, `side` points
+/// square, dark modules in `fg` on `quiet_color`, with `marks` around it. Alpha is
+/// ignored, as PostScript has none.
+pub fn document(
+    code: &Symbol,
+    quiet: u32,
+    side: Length,
+    marks: &Marks,
+    fg: Color,
+    quiet_color: Color,
+    title: &str,
+) -> String {
+    let n = code.width() as u32;
+    let total = n + 2 * quiet;
+    let module = side.0 / total as f64;
+    let (margin, bleed) = (marks.margin(), marks.bleed);
+    let page = side.0 + 2.0 * margin;
+    // DSC comment lines are printable ASCII and at most 255 characters.
+    let title: String = title
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() || c == ' ' {
+                c
+            } else {
+                '?'
+            }
+        })
+        .take(240)
+        .collect();
+
+    let mut ps = String::new();
+    let _ = writeln!(ps, "%!PS-Adobe-3.0 EPSF-3.0");
+    let _ = writeln!(ps, "%%BoundingBox: 0 0 {0} {0}", page.ceil() as u64);
+    let _ = writeln!(ps, "%%HiResBoundingBox: 0 0 {0:.4} {0:.4}", page);
+    let _ = writeln!(ps, "%%Title: {}", title);
+    let _ = writeln!(ps, "%%Creator: qrbrand {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(ps, "%%Pages: 1");
+    let _ = writeln!(ps, "%%EndComments");
+    let _ = writeln!(
+        ps,
+        "% QR {} {}, {} modules of {:.4} pt",
+        meta::version_label(code.version()),
+        meta::ec_level_label(code.error_correction_level()),
+        n,
+        module
+    );
+    let _ = writeln!(ps, "gsave");
+    if margin > 0.0 {
+        let _ = writeln!(ps, "{0:.4} {0:.4} translate", margin);
+    }
+    if bleed > 0.0 {
+        let _ = writeln!(ps, "{} setrgbcolor", rgb(quiet_color));
+        let _ = writeln!(
+            ps,
+            "{0:.4} {0:.4} {1:.4} {1:.4} rectfill",
+            -bleed,
+            side.0 + 2.0 * bleed
+        );
+    }
+    if marks.crop {
+        let _ = writeln!(ps, "0 setgray {} setlinewidth", HAIRLINE);
+        for [x0, y0, x1, y1] in marks::crop_lines([0.0, 0.0, side.0, side.0], bleed) {
+            let _ = writeln!(
+                ps,
+                "{:.4} {:.4} moveto {:.4} {:.4} lineto stroke",
+                x0, y0, x1, y1
+            );
+        }
+    }
+    let _ = writeln!(ps, "{0:.6} {0:.6} scale", module);
+    let _ = writeln!(ps, "/R {{ rectfill }} bind def");
+    let _ = writeln!(ps, "{} setrgbcolor", rgb(quiet_color));
+    let _ = writeln!(ps, "0 0 {0} {0} R", total);
+    let _ = writeln!(ps, "{} setrgbcolor", rgb(fg));
+    // PostScript counts rows up from the bottom.
+    for (x, y, len) in code.dark_runs() {
+        let row = total - 1 - (y as u32 + quiet);
+        let _ = writeln!(ps, "{} {} {} 1 R", x as u32 + quiet, row, len);
+    }
+    let _ = writeln!(ps, "grestore");
+    let _ = writeln!(ps, "showpage");
+    let _ = writeln!(ps, "%%EOF");
+    ps
+}
+
+pub fn rgb(color: Color) -> String {
+    let [r, g, b, _] = color.0.0;
+    let c = |v: u8| v as f64 / 255.0;
+    format!("{:.4} {:.4} {:.4}", c(r), c(g), c(b))
+}
+
+/// Write [`document`] to `path`.
+#[allow(clippy::too_many_arguments)]
+pub fn save(
+    path: &str,
+    code: &Symbol,
+    quiet: u32,
+    side: Length,
+    marks: &Marks,
+    fg: Color,
+    quiet_color: Color,
+    title: &str,
+) -> Result<()> {
+    let ps = document(code, quiet, side, marks, fg, quiet_color, title);
+    std::fs::write(path, ps).with_context(|| format!("Failed to write EPS: {}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length() {
+        assert_eq!("2in".parse::<Length>(), Ok(Length(144.0)));
+        assert_eq!("144pt".parse::<Length>(), Ok(Length(144.0)));
+        assert!(("50.8mm".parse::<Length>().unwrap().0 - 144.0).abs() < 1e-9);
+        assert!(("5.08 cm".parse::<Length>().unwrap().0 - 144.0).abs() < 1e-9);
+        assert!(("3m".parse::<Length>().unwrap().mm() - 3000.0).abs() < 1e-9);
+        assert!("50".parse::<Length>().is_err());
+        assert!("-2in".parse::<Length>().is_err());
+    }
+
+    #[test]
+    fn test_document() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let ps = document(
+            &code,
+            4,
+            "50mm".parse().unwrap(),
+            &Marks::default(),
+            "#1d4ed8".parse().unwrap(),
+            Color::WHITE,
+            "https://example.com",
+        );
+        assert!(ps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 142 142\n"));
+        assert!(ps.contains("%%HiResBoundingBox: 0 0 141.7323 141.7323\n"));
+        assert!(ps.contains("0.1137 0.3059 0.8471 setrgbcolor\n"));
+        assert!(ps.ends_with("showpage\n%%EOF\n"));
+
+        // 3 mm of bleed and the crop marks widen the page; the code keeps its size.
+        let marks = Marks {
+            bleed: "3mm".parse::<Length>().unwrap().0,
+            crop: true,
+        };
+        let marked = document(
+            &code,
+            4,
+            "50mm".parse().unwrap(),
+            &marks,
+            Color::BLACK,
+            Color::WHITE,
+            "",
+        );
+        assert!(marked.contains("%%HiResBoundingBox: 0 0 192.7559 192.7559\n"));
+        assert!(marked.contains("25.5118 25.5118 translate\n"));
+        assert!(marked.contains("-8.5039 -8.5039 158.7402 158.7402 rectfill\n"));
+        assert_eq!(marked.matches(" lineto stroke\n").count(), 8);
+
+        // Every dark module is covered by exactly one run, and nothing else is.
+        let n = code.width() as u32;
+        let total = n + 8;
+        let mut painted = vec![false; (n * n) as usize];
+        for line in ps.lines().filter(|l| l.ends_with(" 1 R")) {
+            let v: Vec<u32> = line
+                .split(' ')
+                .take(3)
+                .map(|t| t.parse().unwrap())
+                .collect();
+            let y = total - 1 - v[1] - 4;
+            for x in v[0] - 4..v[0] - 4 + v[2] {
+                assert!(!painted[(y * n + x) as usize]);
+                painted[(y * n + x) as usize] = true;
+            }
+        }
+        for y in 0..n {
+            for x in 0..n {
+                let dark = matches!(code[(x as usize, y as usize)], qrcode::Color::Dark);
+                assert_eq!(painted[(y * n + x) as usize], dark, "module {},{}", x, y);
+            }
+        }
+    }
+}
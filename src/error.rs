@@ -0,0 +1,259 @@
+//! Error codes: a stable name and exit status for each kind of failure, so scripts can
+//! tell bad input (fix and resubmit) from environmental trouble (retry) without parsing
+//! messages.
+//!
+//! | Exit | Code                 | Category    | Meaning                                          |
+//! |------|----------------------|-------------|--------------------------------------------------|
+//! | 1    | `error`              | input       | Any other failure                                |
+//! | 2    | `usage`              | input       | Invalid flags, options or theme                  |
+//! | 3    | `invalid_payload`    | input       | The payload is malformed, refused, or its URL returns 4xx |
+//! | 4    | `logo_unreadable`    | input       | The logo file is missing or not an image         |
+//! | 5    | `size_too_small`     | input       | `--size` leaves under 2 pixels per module        |
+//! | 6    | `logo_too_large`     | input       | Error correction cannot repair what the logo hides |
+//! | 7    | `low_contrast`       | input       | The colors are refused as unscannable            |
+//! | 8    | `batch_rows_failed`  | input       | Some batch rows failed; the manifest has each code |
+//! | 9    | `output_exists`      | input       | `--no-clobber` refused to overwrite an output    |
+//! | 10   | `io`                 | environment | Reading or writing a file failed                 |
+//! | 11   | `network`            | environment | A network request failed (including HTTP 5xx)    |
+//! | 12   | `proofs_rejected`    | input       | `qrbrand verify` found proofs that do not match  |
+//! | 13   | `warnings`           | input       | `--warnings-as-errors` and something warned      |
+//!
+//! Command-line parse errors exit with 2 before any of this runs. `qrbrand exit-codes`
+//! prints this table, as JSON with `--log-format json`.
+
+use serde::Serialize;
+use std::fmt;
+use std::process::ExitCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Error,
+    Usage,
+    InvalidPayload,
+    LogoUnreadable,
+    SizeTooSmall,
+    LogoTooLarge,
+    LowContrast,
+    BatchRowsFailed,
+    OutputExists,
+    Io,
+    Network,
+    ProofsRejected,
+    Warnings,
+}
+
+impl ErrorCode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Usage => "usage",
+            Self::InvalidPayload => "invalid_payload",
+            Self::LogoUnreadable => "logo_unreadable",
+            Self::SizeTooSmall => "size_too_small",
+            Self::LogoTooLarge => "logo_too_large",
+            Self::LowContrast => "low_contrast",
+            Self::BatchRowsFailed => "batch_rows_failed",
+            Self::OutputExists => "output_exists",
+            Self::Io => "io",
+            Self::Network => "network",
+            Self::ProofsRejected => "proofs_rejected",
+            Self::Warnings => "warnings",
+        }
+    }
+
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Error => 1,
+            Self::Usage => 2,
+            Self::InvalidPayload => 3,
+            Self::LogoUnreadable => 4,
+            Self::SizeTooSmall => 5,
+            Self::LogoTooLarge => 6,
+            Self::LowContrast => 7,
+            Self::BatchRowsFailed => 8,
+            Self::OutputExists => 9,
+            Self::Io => 10,
+            Self::Network => 11,
+            Self::ProofsRejected => 12,
+            Self::Warnings => 13,
+        }
+    }
+
+    pub fn meaning(self) -> &'static str {
+        match self {
+            Self::Error => "Any other failure",
+            Self::Usage => "Invalid flags, options or theme",
+            Self::InvalidPayload => "The payload is malformed, refused, or its URL returns 4xx",
+            Self::LogoUnreadable => "The logo file is missing or not an image",
+            Self::SizeTooSmall => "--size leaves under 2 pixels per module",
+            Self::LogoTooLarge => "Error correction cannot repair what the logo hides",
+            Self::LowContrast => "The colors are refused as unscannable",
+            Self::BatchRowsFailed => "Some batch rows failed; the manifest has each code",
+            Self::OutputExists => "--no-clobber refused to overwrite an output",
+            Self::Io => "Reading or writing a file failed",
+            Self::Network => "A network request failed (including HTTP 5xx)",
+            Self::ProofsRejected => "qrbrand verify found proofs that do not match",
+            Self::Warnings => "--warnings-as-errors and something warned",
+        }
+    }
+
+    /// Environmental failures may succeed on retry; input failures will not.
+    pub fn is_environmental(self) -> bool {
+        matches!(self, Self::Io | Self::Network)
+    }
+
+    /// The code for `err`: the outermost one attached with [`WithCode::code`], else
+    /// one inferred from the underlying error type.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(coded) = err.chain().find_map(|e| e.downcast_ref::<Coded>()) {
+            return coded.code;
+        }
+        #[cfg(feature = "net")]
+        if err.chain().any(|e| e.is::<ureq::Error>()) {
+            return Self::Network;
+        }
+        // `image` reports file errors as its own type and skips them in the source chain.
+        let is_io = |e: &(dyn std::error::Error + 'static)| {
+            e.is::<std::io::Error>()
+                || matches!(e.downcast_ref(), Some(image::ImageError::IoError(_)))
+        };
+        if err.chain().any(is_io) {
+            return Self::Io;
+        }
+        Self::Error
+    }
+}
+
+/// Every code, in exit status order.
+pub const ALL: [ErrorCode; 13] = [
+    ErrorCode::Error,
+    ErrorCode::Usage,
+    ErrorCode::InvalidPayload,
+    ErrorCode::LogoUnreadable,
+    ErrorCode::SizeTooSmall,
+    ErrorCode::LogoTooLarge,
+    ErrorCode::LowContrast,
+    ErrorCode::BatchRowsFailed,
+    ErrorCode::OutputExists,
+    ErrorCode::Io,
+    ErrorCode::Network,
+    ErrorCode::ProofsRejected,
+    ErrorCode::Warnings,
+];
+
+/// `qrbrand exit-codes`: the table above, or with `json` an array of objects with the
+/// fields an error's JSON log line carries, plus its `meaning`.
+pub fn table(json: bool) -> String {
+    if json {
+        let codes: Vec<_> = ALL
+            .iter()
+            .map(|code| {
+                serde_json::json!({
+                    "exit_code": code.exit_code(),
+                    "code": code,
+                    "retryable": code.is_environmental(),
+                    "meaning": code.meaning(),
+                })
+            })
+            .collect();
+        return format!("{}\n", serde_json::Value::Array(codes));
+    }
+    let mut table = format!(
+        "{:<5} {:<18} {:<6} {}\n",
+        "Exit", "Code", "Retry", "Meaning"
+    );
+    for code in ALL {
+        let retry = if code.is_environmental() { "yes" } else { "no" };
+        table.push_str(&format!(
+            "{:<5} {:<18} {:<6} {}\n",
+            code.exit_code(),
+            code.name(),
+            retry,
+            code.meaning()
+        ));
+    }
+    table
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<ErrorCode> for ExitCode {
+    fn from(code: ErrorCode) -> Self {
+        ExitCode::from(code.exit_code())
+    }
+}
+
+/// An error tagged with its code. It displays as the wrapped error, so tagging does
+/// not change any message.
+#[derive(Debug)]
+struct Coded {
+    code: ErrorCode,
+    inner: anyhow::Error,
+}
+
+impl fmt::Display for Coded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl std::error::Error for Coded {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+pub trait WithCode<T> {
+    /// Tag the error, if any, with `code`.
+    fn code(self, code: ErrorCode) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> WithCode<T> for Result<T, E> {
+    fn code(self, code: ErrorCode) -> anyhow::Result<T> {
+        self.map_err(|e| {
+            anyhow::Error::new(Coded {
+                code,
+                inner: e.into(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn test_code_of() {
+        let tagged: anyhow::Result<()> =
+            Err(anyhow!("no such file")).code(ErrorCode::LogoUnreadable);
+        let err = tagged.context("Failed to render").unwrap_err();
+        assert_eq!(ErrorCode::of(&err), ErrorCode::LogoUnreadable);
+        // Tagging leaves the message chain as it was.
+        assert_eq!(format!("{:#}", err), "Failed to render: no such file");
+
+        let io = std::fs::read("/nonexistent/qrbrand").context("Failed to read");
+        assert_eq!(ErrorCode::of(&io.unwrap_err()), ErrorCode::Io);
+        assert_eq!(ErrorCode::of(&anyhow!("something")), ErrorCode::Error);
+        assert!(ErrorCode::Network.is_environmental());
+    }
+
+    #[test]
+    fn test_table() {
+        // Exit statuses are stable and distinct, in order.
+        for (i, code) in ALL.iter().enumerate() {
+            assert_eq!(code.exit_code() as usize, i + 1, "{}", code);
+        }
+        let json: serde_json::Value = serde_json::from_str(&table(true)).unwrap();
+        assert_eq!(json[3]["code"], "logo_unreadable");
+        assert_eq!(json[3]["exit_code"], 4);
+        assert_eq!(json[9]["retryable"], true);
+        assert!(table(false).contains("13    warnings"));
+    }
+}
@@ -0,0 +1,147 @@
+//! C ABI for linking the renderer into other languages (the `ffi` feature).
+//!
+//! `include/qrbrand.h` declares these; the build regenerates it when the feature is on.
+//! Every call is independent and thread-safe. On failure the message is kept per thread
+//! for [`qrbrand_last_error`].
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// The call succeeded.
+pub const QRBRAND_OK: i32 = 0;
+/// The options were rejected or the render failed; see `qrbrand_last_error`.
+pub const QRBRAND_ERROR: i32 = 1;
+/// A required pointer was null or the options were not UTF-8.
+pub const QRBRAND_INVALID_ARGUMENT: i32 = 2;
+
+/// PNG bytes owned by qrbrand; release them with `qrbrand_buffer_free`.
+#[repr(C)]
+pub struct QrbrandBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    // A NUL inside the message would truncate it; never fail here over one.
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Render the code described by `options_json` (a batch record as a JSON object) into
+/// `out_buf` as PNG bytes. Returns `QRBRAND_OK` or an error code.
+///
+/// # Safety
+///
+/// `options_json` must be a NUL-terminated string and `out_buf` must point to writable
+/// memory for a `QrbrandBuffer`. Both only need to live for the duration of the call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qrbrand_render(
+    options_json: *const c_char,
+    out_buf: *mut QrbrandBuffer,
+) -> i32 {
+    if options_json.is_null() || out_buf.is_null() {
+        set_error("options_json and out_buf must not be null".to_string());
+        return QRBRAND_INVALID_ARGUMENT;
+    }
+    // SAFETY: checked for null above; the caller guarantees NUL termination.
+    let Ok(options) = unsafe { CStr::from_ptr(options_json) }.to_str() else {
+        set_error("options_json is not valid UTF-8".to_string());
+        return QRBRAND_INVALID_ARGUMENT;
+    };
+    // SAFETY: checked for null above; the caller guarantees it is writable.
+    unsafe {
+        out_buf.write(QrbrandBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        })
+    };
+
+    // A panic must not unwind into the caller's frames.
+    match panic::catch_unwind(AssertUnwindSafe(|| crate::api::render(options, None))) {
+        Ok(Ok(png)) => {
+            let png = Box::into_raw(png.into_boxed_slice());
+            // SAFETY: as above.
+            unsafe {
+                out_buf.write(QrbrandBuffer {
+                    data: png.cast(),
+                    len: png.len(),
+                })
+            };
+            QRBRAND_OK
+        }
+        Ok(Err(e)) => {
+            set_error(format!("{:#}", e));
+            QRBRAND_ERROR
+        }
+        Err(_) => {
+            set_error("internal error: the renderer panicked".to_string());
+            QRBRAND_ERROR
+        }
+    }
+}
+
+/// Release a buffer filled by `qrbrand_render` and reset it to empty. Null and empty
+/// buffers are ignored.
+///
+/// # Safety
+///
+/// `buf` must be null or point to a buffer filled by `qrbrand_render` and not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qrbrand_buffer_free(buf: *mut QrbrandBuffer) {
+    // SAFETY: the caller guarantees `buf` is null or valid.
+    let Some(buf) = (unsafe { buf.as_mut() }) else {
+        return;
+    };
+    if !buf.data.is_null() {
+        // SAFETY: `data` and `len` came from the boxed slice in `qrbrand_render`.
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buf.data, buf.len)) });
+    }
+    buf.data = ptr::null_mut();
+    buf.len = 0;
+}
+
+/// The message for the last failed call on this thread, or null if none has failed.
+/// The string stays valid until the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn qrbrand_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let options = CString::new(r#"{"url": "https://example.com"}"#).unwrap();
+        let mut buf = QrbrandBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        unsafe {
+            assert_eq!(qrbrand_render(options.as_ptr(), &mut buf), QRBRAND_OK);
+            let png = std::slice::from_raw_parts(buf.data, buf.len);
+            assert!(image::load_from_memory(png).is_ok());
+            qrbrand_buffer_free(&mut buf);
+        }
+        assert!(buf.data.is_null());
+
+        let options = CString::new(r#"{"url": "no scheme"}"#).unwrap();
+        unsafe {
+            assert_eq!(qrbrand_render(options.as_ptr(), &mut buf), QRBRAND_ERROR);
+            assert!(buf.data.is_null());
+            let message = CStr::from_ptr(qrbrand_last_error()).to_str().unwrap();
+            assert!(message.contains("Invalid URL"), "{}", message);
+            assert_eq!(
+                qrbrand_render(ptr::null(), &mut buf),
+                QRBRAND_INVALID_ARGUMENT
+            );
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Installed font lookup by family name (fontconfig directories on Linux,
+//! the system font folders on macOS and Windows), and per-character fallback
+//! for captions the primary font cannot draw (CJK, emoji, other scripts).
+
+use crate::log;
+use anyhow::{Context, Result, bail};
+use fontdb::{Database, Family, ID, Query};
+use rusttype::{Font, Scale};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+/// A parsed font plus its raw data, which the shaper needs.
+#[derive(Clone)]
+pub struct FontFace {
+    pub font: Font<'static>,
+    pub data: Arc<[u8]>,
+    pub index: u32,
+}
+
+impl FontFace {
+    pub fn from_vec(data: Vec<u8>, index: u32) -> Option<Self> {
+        let font = Font::try_from_vec_and_index(data.clone(), index)?;
+        Some(Self {
+            font,
+            data: data.into(),
+            index,
+        })
+    }
+}
+
+/// Load the regular face of an installed font family, e.g. "Inter" or "DejaVu Serif".
+/// The generic names `serif`, `sans-serif` and `monospace` are also accepted.
+pub fn load_family(name: &str) -> Result<FontFace> {
+    let mut db = Database::new();
+    db.load_system_fonts();
+
+    let generic = match name.to_ascii_lowercase().as_str() {
+        "serif" => Some(SERIF),
+        "sans-serif" | "sans" => Some(SANS_SERIF),
+        "monospace" | "mono" => Some(MONOSPACE),
+        _ => None,
+    };
+    let family = match generic.and_then(|candidates| first_installed(&db, candidates)) {
+        Some(installed) => Family::Name(installed),
+        None => Family::Name(name),
+    };
+    let query = Query {
+        families: &[family],
+        ..Query::default()
+    };
+
+    let Some(id) = db.query(&query) else {
+        let similar = similar_families(&db, name);
+        if similar.is_empty() {
+            bail!("font family not found: {}", name);
+        }
+        bail!(
+            "font family not found: {} (installed families include: {})",
+            name,
+            similar.join(", ")
+        );
+    };
+
+    db.with_face_data(id, |data, index| FontFace::from_vec(data.to_vec(), index))
+        .flatten()
+        .with_context(|| format!("Failed to load font family: {}", name))
+}
+
+/// Common families tried, in order, for the generic names.
+const SERIF: &[&str] = &[
+    "Noto Serif",
+    "DejaVu Serif",
+    "Liberation Serif",
+    "Times New Roman",
+    "Times",
+];
+const SANS_SERIF: &[&str] = &[
+    "Noto Sans",
+    "DejaVu Sans",
+    "Liberation Sans",
+    "Arial",
+    "Helvetica",
+];
+const MONOSPACE: &[&str] = &[
+    "Noto Sans Mono",
+    "DejaVu Sans Mono",
+    "Liberation Mono",
+    "Consolas",
+    "Menlo",
+    "Courier New",
+];
+
+fn first_installed(db: &Database, candidates: &[&'static str]) -> Option<&'static str> {
+    candidates.iter().copied().find(|candidate| {
+        db.faces()
+            .any(|face| face.families.iter().any(|(family, _)| family == candidate))
+    })
+}
+
+/// Installed family names that share a word with `name`, for error hints.
+fn similar_families(db: &Database, name: &str) -> Vec<String> {
+    let words: Vec<String> = name
+        .split_whitespace()
+        .map(|w| w.to_ascii_lowercase())
+        .collect();
+    let mut names: Vec<String> = db
+        .faces()
+        .flat_map(|face| face.families.iter().map(|(family, _)| family.clone()))
+        .filter(|family| {
+            let lower = family.to_ascii_lowercase();
+            words.iter().any(|w| lower.contains(w.as_str()))
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names.truncate(8);
+    names
+}
+
+/// Families searched first for fallback glyphs, before every other installed face.
+/// Color bitmap emoji fonts have no outlines and are skipped by [`covers`].
+const FALLBACK_FAMILIES: &[&str] = &[
+    "Noto Sans CJK JP",
+    "Noto Sans CJK SC",
+    "Noto Sans CJK TC",
+    "Noto Sans CJK KR",
+    "Source Han Sans",
+    "WenQuanYi Micro Hei",
+    "Hiragino Sans",
+    "PingFang SC",
+    "Apple SD Gothic Neo",
+    "Microsoft YaHei",
+    "Yu Gothic",
+    "Malgun Gothic",
+    "Noto Emoji",
+    "Segoe UI Emoji",
+    "Segoe UI Symbol",
+    "Symbola",
+    "Noto Sans",
+    "DejaVu Sans",
+];
+
+/// A primary font plus fallbacks discovered on demand from installed fonts.
+/// Shared across batch workers; each missing character is searched for once.
+pub struct FontChain {
+    primary: FontFace,
+    fallbacks: RwLock<Vec<FontFace>>,
+    searched: Mutex<HashSet<char>>,
+    db: OnceLock<Database>,
+}
+
+impl FontChain {
+    pub fn new(primary: FontFace) -> Self {
+        Self {
+            primary,
+            fallbacks: RwLock::new(Vec::new()),
+            searched: Mutex::new(HashSet::new()),
+            db: OnceLock::new(),
+        }
+    }
+
+    pub fn primary(&self) -> &Font<'static> {
+        &self.primary.font
+    }
+
+    /// Font number `index` as returned by [`resolve`](Self::resolve) (0 = primary).
+    pub fn face(&self, index: usize) -> FontFace {
+        match index {
+            0 => self.primary.clone(),
+            i => self.fallbacks.read().unwrap()[i - 1].clone(),
+        }
+    }
+
+    /// Pick a font for every character of `text`: the primary font if it has the glyph,
+    /// otherwise the first fallback that does. Returns one font index per character.
+    pub fn resolve(&self, text: &str) -> Vec<usize> {
+        let missing: String = text
+            .chars()
+            .filter(|&ch| !covers(&self.primary.font, ch) && self.fallback_index(ch).is_none())
+            .filter(|&ch| !self.search(ch))
+            .collect();
+        if !missing.is_empty() {
+            log::warn(format!(
+                "no installed font can draw {:?} in the caption (install e.g. Noto Sans CJK or Noto Emoji)",
+                missing
+            ));
+        }
+
+        text.chars()
+            .map(|ch| match self.fallback_index(ch) {
+                Some(i) if !covers(&self.primary.font, ch) => i + 1,
+                _ => 0,
+            })
+            .collect()
+    }
+
+    fn fallback_index(&self, ch: char) -> Option<usize> {
+        let fallbacks = self.fallbacks.read().unwrap();
+        fallbacks.iter().position(|face| covers(&face.font, ch))
+    }
+
+    /// Look through installed fonts for one that draws `ch` and add it to the chain.
+    /// Returns false only the first time a character turns out to be unavailable.
+    fn search(&self, ch: char) -> bool {
+        if !self.searched.lock().unwrap().insert(ch) {
+            return true;
+        }
+
+        let db = self.db.get_or_init(|| {
+            let mut db = Database::new();
+            db.load_system_fonts();
+            db
+        });
+
+        let found = fallback_order(db).into_iter().find_map(|id| {
+            db.with_face_data(id, |data, index| {
+                let font = Font::try_from_bytes_and_index(data, index)?;
+                covers(&font, ch).then(|| FontFace::from_vec(data.to_vec(), index))?
+            })
+            .flatten()
+        });
+
+        match found {
+            Some(font) => {
+                self.fallbacks.write().unwrap().push(font);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Preferred fallback families first, then every other installed face.
+fn fallback_order(db: &Database) -> Vec<ID> {
+    let preferred: Vec<ID> = FALLBACK_FAMILIES
+        .iter()
+        .filter_map(|family| {
+            db.query(&Query {
+                families: &[Family::Name(family)],
+                ..Query::default()
+            })
+        })
+        .collect();
+    let rest = db
+        .faces()
+        .map(|face| face.id)
+        .filter(|id| !preferred.contains(id));
+    preferred.iter().copied().chain(rest).collect()
+}
+
+/// The font has an outline glyph for `ch` (whitespace always counts as covered).
+pub fn covers(font: &Font<'_>, ch: char) -> bool {
+    if ch.is_whitespace() || ch.is_control() {
+        return true;
+    }
+    let glyph = font.glyph(ch);
+    glyph.id().0 != 0
+        && glyph
+            .scaled(Scale::uniform(32.0))
+            .positioned(rusttype::point(0.0, 0.0))
+            .pixel_bounding_box()
+            .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_family_is_an_error() {
+        let err = load_family("Qrbrand Missing Family").err().unwrap();
+        assert!(err.to_string().contains("font family not found"));
+    }
+
+    #[test]
+    fn test_covers() {
+        let font = crate::load_embedded_font().unwrap().font;
+        assert!(covers(&font, 'A'));
+        assert!(covers(&font, 'é'));
+        assert!(covers(&font, ' '));
+        assert!(!covers(&font, '日'));
+    }
+
+    #[test]
+    fn test_chain_prefers_primary() {
+        let chain = FontChain::new(crate::load_embedded_font().unwrap());
+        assert_eq!(chain.resolve("Menu"), vec![0, 0, 0, 0]);
+        assert!(
+            chain.db.get().is_none(),
+            "no font search when the primary covers the text"
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! `--eye-glyph`: a small monochrome mark in the centre of each finder pattern, the
+//! agency trick of a star or monogram in the code's eyes.
+//!
+//! A scanner finds the code by the 1:1:3:1:1 runs across each finder pattern, and a
+//! light mark in the centre splits its 3-module run in two: even a one-module cut-out
+//! loses the eye. So the glyph is a tone rather than a hole. Its color is darkened
+//! to at most [`MAX_GLYPH_REFLECTANCE`], which scanners still count as dark, and it
+//! is kept to the middle [`GLYPH_SCALE`] of the centre, so every line through the
+//! centre starts and ends in solid dark and the ring around it is untouched.
+//!
+//! The glyph is painted as a mask, from its alpha or, in an opaque image, its
+//! darkness, in `--eye-glyph-color` (by default the ground color, darkened to the
+//! limit: grey on white). The finished code must still decode.
+
+use crate::color::Color;
+use crate::error::{ErrorCode, WithCode};
+use crate::modules;
+use crate::raster::Canvas;
+use crate::symbol::Symbol;
+use anyhow::{Context, Result, anyhow, bail};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+use std::sync::Arc;
+
+/// Side of the glyph as a fraction of the 3-module centre: two modules, leaving half
+/// a module of solid dark on each side.
+pub const GLYPH_SCALE: f32 = 2.0 / 3.0;
+
+/// Lightest a glyph may be, as reflectance; the centre must still binarize as dark.
+pub const MAX_GLYPH_REFLECTANCE: f32 = 0.12;
+
+/// Side an SVG glyph is rasterized at before it is scaled to the centre.
+const SVG_PX: u32 = 256;
+
+/// Smallest glyph side, in pixels, worth drawing.
+const MIN_GLYPH_PX: u32 = 4;
+
+/// The glyph at `path`: SVG, or any image `--image` takes.
+pub fn load(path: &str) -> Result<DynamicImage> {
+    let is_svg = std::path::Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    let glyph = match is_svg {
+        true => std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| rasterize(&data)),
+        false => image::open(path).map_err(anyhow::Error::from),
+    };
+    glyph
+        .with_context(|| format!("Failed to open eye glyph: {}", path))
+        .code(ErrorCode::LogoUnreadable)
+}
+
+/// An SVG document as an image [`SVG_PX`] on its longer side.
+fn rasterize(data: &[u8]) -> Result<DynamicImage> {
+    let tree = resvg::usvg::Tree::from_data(data, &resvg::usvg::Options::default())?;
+    let size = tree.size();
+    let scale = SVG_PX as f32 / size.width().max(size.height());
+    let (w, h) = (
+        ((size.width() * scale).round() as u32).max(1),
+        ((size.height() * scale).round() as u32).max(1),
+    );
+    let mut pixmap =
+        resvg::tiny_skia::Pixmap::new(w, h).ok_or_else(|| anyhow!("empty SVG canvas"))?;
+    let transform = resvg::tiny_skia::Transform::from_scale(scale, scale);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    let mut img = RgbaImage::new(w, h);
+    for (pixel, out) in pixmap.pixels().iter().zip(img.pixels_mut()) {
+        let c = pixel.demultiply();
+        *out = Rgba([c.red(), c.green(), c.blue(), c.alpha()]);
+    }
+    Ok(DynamicImage::ImageRgba8(img))
+}
+
+/// Refuse a number of glyphs other than one for every eye or one for each.
+pub fn check_count(glyphs: usize) -> Result<()> {
+    if matches!(glyphs, 0 | 1 | 3) {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "--eye-glyph takes one glyph for every eye or three (top left, top right, bottom left), not {}",
+        glyphs
+    ))
+    .code(ErrorCode::Usage)
+}
+
+/// How much of each pixel of `glyph`, scaled to fit `side` square, is glyph: its
+/// alpha if it has any transparency, else its darkness.
+fn mask(glyph: &DynamicImage, side: u32) -> GrayImage {
+    let scaled = glyph.resize(side, side, FilterType::Lanczos3).to_rgba8();
+    let transparent = scaled.pixels().any(|p| p.0[3] < 255);
+    let mut mask = GrayImage::new(side, side);
+    let (x0, y0) = ((side - scaled.width()) / 2, (side - scaled.height()) / 2);
+    for (x, y, p) in scaled.enumerate_pixels() {
+        let [r, g, b, a] = p.0;
+        let coverage = match transparent {
+            true => a,
+            false => 255 - ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8,
+        };
+        mask.put_pixel(x0 + x, y0 + y, Luma([coverage]));
+    }
+    mask
+}
+
+/// Paint `glyphs`, one for every eye or one each, into the finder pattern centres of
+/// `code`, drawn in `img` at `ppm` pixels per module after `quiet` modules of border,
+/// in `color` darkened to [`MAX_GLYPH_REFLECTANCE`].
+pub fn paint(
+    img: &mut Canvas,
+    code: &Symbol,
+    quiet: u32,
+    ppm: u32,
+    glyphs: &[Arc<DynamicImage>],
+    color: Color,
+) -> Result<()> {
+    let side = (3.0 * ppm as f32 * GLYPH_SCALE).round() as u32;
+    if side < MIN_GLYPH_PX {
+        bail!(
+            "--eye-glyph needs at least {} px per module to draw; raise --size",
+            (MIN_GLYPH_PX as f32 / 3.0 / GLYPH_SCALE).ceil()
+        );
+    }
+    let n = code.width() as u32;
+    let eyes = [(0, 0), (n - 7, 0), (0, n - 7)];
+    for (i, (fx, fy)) in eyes.into_iter().enumerate() {
+        let glyph = &glyphs[i % glyphs.len()];
+        let mask = mask(glyph, side);
+        // The centre's middle, in pixels.
+        let centre = (fx + quiet + 2) * ppm + (3 * ppm - side) / 2;
+        let middle = (fy + quiet + 2) * ppm + (3 * ppm - side) / 2;
+        let mut layer = RgbaImage::new(side, side);
+        for (x, y, coverage) in mask.enumerate_pixels() {
+            let [r, g, b, a] = modules::floor(color.0, MAX_GLYPH_REFLECTANCE).0;
+            let alpha = (a as u32 * coverage.0[0] as u32 / 255) as u8;
+            layer.put_pixel(x, y, Rgba([r, g, b, alpha]));
+        }
+        imageops::overlay(img, &layer, centre as i64, middle as i64);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode;
+    use crate::symbol::{self, EncodeOptions};
+
+    const STAR: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24"><path d="M12 2l3 7h7l-6 4 2 8-6-5-6 5 2-8-6-4h7z"/></svg>"#;
+
+    #[test]
+    fn test_eye_glyph() {
+        let star = Arc::new(rasterize(STAR.as_bytes()).unwrap());
+        assert_eq!(star.width(), SVG_PX);
+        let mask = mask(&star, 16);
+        // Solid in the middle of the star, empty in the corners.
+        assert!(mask.get_pixel(8, 9).0[0] > 200);
+        assert_eq!(mask.get_pixel(0, 15).0[0], 0);
+
+        assert!(check_count(3).is_ok());
+        assert!(check_count(2).is_err());
+
+        let code = symbol::encode("https://example.com/menu", &EncodeOptions::default()).unwrap();
+        let ppm = 12;
+        let quiet = 4;
+        let mut img = crate::render_qr_rgba(&code, 37 * ppm, quiet, Color::BLACK).unwrap();
+        let plain = img.clone();
+        paint(&mut img, &code, quiet, ppm, &[star], Color::WHITE).unwrap();
+        // The middle of the top-left eye is grey, its edge still black.
+        let centre = (quiet + 3) * ppm + ppm / 2;
+        let grey = *img.get_pixel(centre, centre + 1);
+        assert!(grey.0[0] > 60, "{:?}", grey);
+        assert!(crate::contrast::reflectance(grey) <= MAX_GLYPH_REFLECTANCE + 0.01);
+        assert_eq!(img.get_pixel((quiet + 2) * ppm + 1, centre).0[0], 0);
+        assert!(decode::decode(&img).is_ok());
+
+        // Cut out in white, a square the glyph's size loses the eyes.
+        let mut cut = plain;
+        let hole = RgbaImage::from_pixel(2 * ppm, 2 * ppm, Rgba([255; 4]));
+        let n = code.width() as u32;
+        for (x, y) in [(0, 0), (n - 7, 0), (0, n - 7)] {
+            let (x, y) = (
+                (x + quiet + 2) * ppm + ppm / 2,
+                (y + quiet + 2) * ppm + ppm / 2,
+            );
+            imageops::overlay(&mut cut, &hole, x as i64, y as i64);
+        }
+        assert!(decode::decode(&cut).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Messages on the command line in the user's language, from the Fluent files
+//! under `locales/`.
+//!
+//! The language is the first of `QRBRAND_LANG`, `LC_ALL`, `LC_MESSAGES` and `LANG`
+//! that is set: `de`, `de_DE.UTF-8` and `de-AT` all pick German, `C` and `POSIX`
+//! English. A locale that leaves a message out falls back to English, and English
+//! to the message id, so a half-finished translation is still usable.
+//!
+//! Help for options and subcommands stays in the doc comments clap reads; a locale
+//! overrides it through [`layer`]. JSON log lines, the HTTP API and the files
+//! written are not translated, so scripts can rely on them.
+//!
+//! Errors go through [`tr`] where they are about what the user typed: the usage and
+//! validation errors of the main command, `--size`, `--symbology pdf417` and the
+//! payload subcommands. Still English are the errors of reading and writing files,
+//! fonts and images, of the network and the servers, of batch, series and job runs,
+//! of the output formats and of color, length and theme parsing, as well as clap's
+//! own parse errors, the `--verbose` details and the causes libraries attach.
+//!
+//! A translation is a `locales/<tag>/qrbrand.ftl` beside the English one, added to
+//! [`LOCALES`].
+
+use clap::Command;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// Every locale built in, English first.
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en/qrbrand.ftl")),
+    ("de", include_str!("../locales/de/qrbrand.ftl")),
+];
+
+/// Variables naming the language, in the order POSIX reads them.
+const LANG_VARS: [&str; 4] = ["QRBRAND_LANG", "LC_ALL", "LC_MESSAGES", "LANG"];
+
+type Bundle = FluentBundle<FluentResource>;
+
+static BUNDLES: OnceLock<Vec<Bundle>> = OnceLock::new();
+
+/// Message `id` in the user's language with `args` filled in.
+pub fn tr(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let bundles = BUNDLES.get_or_init(|| bundles(requested().as_ref()));
+    lookup(bundles, id, args).unwrap_or_else(|| id.to_string())
+}
+
+/// `command` with the help of its options and subcommands in the user's language,
+/// where the locale has it.
+pub fn layer(command: Command) -> Command {
+    let bundles = BUNDLES.get_or_init(|| bundles(requested().as_ref()));
+    localize(command, "", bundles)
+}
+
+/// `prefix` is empty for the main command and `<subcommand>-` for a subcommand, as
+/// the message ids have it: `about`, `help-size`, `about-serve`, `help-serve-port`.
+fn localize(command: Command, prefix: &str, bundles: &[Bundle]) -> Command {
+    let about = match prefix.strip_suffix('-') {
+        None => lookup(bundles, "about", &[]),
+        Some(name) => lookup(bundles, &format!("about-{}", name), &[]),
+    };
+    let command = match about {
+        Some(about) => command.about(about).long_about(None::<&str>),
+        None => command,
+    };
+    command
+        .mut_args(|arg| {
+            let id = format!("help-{}{}", prefix, arg.get_id().as_str().replace('_', "-"));
+            match lookup(bundles, &id, &[]) {
+                Some(help) => arg.help(help).long_help(None::<&str>),
+                None => arg,
+            }
+        })
+        .mut_subcommands(|sub| {
+            let prefix = format!("{}{}-", prefix, sub.get_name());
+            localize(sub, &prefix, bundles)
+        })
+}
+
+/// The language the environment asks for, if any.
+fn requested() -> Option<LanguageIdentifier> {
+    LANG_VARS
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+        .and_then(|value| parse_locale(&value))
+}
+
+/// `de_DE.UTF-8@euro` as `de-DE`; `C` and `POSIX` as no language.
+fn parse_locale(value: &str) -> Option<LanguageIdentifier> {
+    let tag = value.split(['.', '@']).next()?.replace('_', "-");
+    match tag.as_str() {
+        "C" | "POSIX" => None,
+        tag => tag.parse().ok(),
+    }
+}
+
+/// The built-in locale closest to `requested`, then English.
+fn bundles(requested: Option<&LanguageIdentifier>) -> Vec<Bundle> {
+    let parse = |tag: &str| {
+        tag.parse::<LanguageIdentifier>()
+            .expect("a valid locale tag")
+    };
+    let chosen = requested.and_then(|requested| {
+        LOCALES
+            .iter()
+            .find(|(tag, _)| parse(tag) == *requested)
+            .or_else(|| {
+                LOCALES
+                    .iter()
+                    .find(|(tag, _)| parse(tag).language == requested.language)
+            })
+    });
+    let mut locales: Vec<_> = chosen.into_iter().collect();
+    if chosen.is_none_or(|(tag, _)| *tag != "en") {
+        locales.push(&LOCALES[0]);
+    }
+    locales
+        .into_iter()
+        .map(|(tag, source)| bundle(parse(tag), source))
+        .collect()
+}
+
+fn bundle(lang: LanguageIdentifier, source: &str) -> Bundle {
+    let resource =
+        FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, _)| resource);
+    let mut bundle = FluentBundle::new_concurrent(vec![lang]);
+    // Messages go to a terminal, where the bidi isolation marks only show as noise.
+    bundle.set_use_isolating(false);
+    bundle.add_resource_overriding(resource);
+    bundle
+}
+
+fn lookup(bundles: &[Bundle], id: &str, args: &[(&str, FluentValue)]) -> Option<String> {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    bundles.iter().find_map(|bundle| {
+        let pattern = bundle.get_message(id)?.value()?;
+        let mut errors = Vec::new();
+        Some(
+            bundle
+                .format_pattern(pattern, Some(&fluent_args), &mut errors)
+                .into_owned(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(parse_locale("de_DE.UTF-8@euro"), "de-DE".parse().ok());
+        assert_eq!(parse_locale("C.UTF-8"), None);
+        assert_eq!(parse_locale("POSIX"), None);
+
+        let english = bundles(None);
+        assert_eq!(english.len(), 1);
+        let path = [("path", "menu.png".into())];
+        assert_eq!(lookup(&english, "wrote", &path).unwrap(), "Wrote menu.png");
+
+        let german = bundles(parse_locale("de_AT.UTF-8").as_ref());
+        assert_eq!(german.len(), 2);
+        assert_eq!(
+            lookup(&german, "wrote", &path).unwrap(),
+            "menu.png geschrieben"
+        );
+        assert_eq!(lookup(&german, "nonexistent", &[]), None);
+        let reason = |reason: &str| {
+            let args = [("reason", reason.into()), ("address", "1Bad".into())];
+            lookup(&german, "bitcoin-invalid", &args).unwrap()
+        };
+        assert_eq!(
+            reason("too-short"),
+            "Ungültige Bitcoin-Adresse (zu kurz): 1Bad"
+        );
+        assert_eq!(
+            lookup(&english, "no-payload", &[("command", "batch".into())]).unwrap(),
+            "batch mode has no single payload"
+        );
+        assert_eq!(bundles(parse_locale("ja_JP").as_ref()).len(), 1);
+
+        let summary = |bundles: &[Bundle], dry_run: &str| {
+            let args = [
+                ("dry_run", dry_run.into()),
+                ("written", 3.into()),
+                ("total", 4.into()),
+                ("skipped", 1.into()),
+            ];
+            lookup(bundles, "batch-summary", &args).unwrap()
+        };
+        assert_eq!(
+            summary(&english, "yes"),
+            "Would write 3 of 4 files (1 up to date, skipped)"
+        );
+        assert_eq!(
+            summary(&german, "no"),
+            "3 von 4 Dateien geschrieben (1 aktuell, übersprungen)"
+        );
+
+        let command = localize(Args::command(), "", &german);
+        let help = |command: &Command, id: &str| {
+            let arg = command.get_arguments().find(|a| a.get_id() == id).unwrap();
+            arg.get_help().unwrap().to_string()
+        };
+        assert!(help(&command, "url").starts_with("Zu kodierende URL"));
+        assert!(
+            help(&command, "out").starts_with("Output"),
+            "{}",
+            help(&command, "out")
+        );
+        #[cfg(feature = "cli")]
+        {
+            let batch = command.find_subcommand("batch").unwrap();
+            assert!(
+                batch
+                    .get_about()
+                    .unwrap()
+                    .to_string()
+                    .starts_with("Rendert")
+            );
+        }
+    }
+
+    /// Every locale parses, and names only messages English has or options and
+    /// subcommands that exist, so a typo does not silently leave text untranslated.
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_locale_files() {
+        fn help_ids(command: &Command, prefix: &str, ids: &mut Vec<String>) {
+            ids.push(match prefix.strip_suffix('-') {
+                None => "about".to_string(),
+                Some(name) => format!("about-{}", name),
+            });
+            for arg in command.get_arguments() {
+                let id = arg.get_id().as_str().replace('_', "-");
+                ids.push(format!("help-{}{}", prefix, id));
+            }
+            for sub in command.get_subcommands() {
+                help_ids(sub, &format!("{}{}-", prefix, sub.get_name()), ids);
+            }
+        }
+        let mut known = Vec::new();
+        help_ids(&Args::command(), "", &mut known);
+        // A message starts a line with its id; comments, variants and continuations
+        // do not.
+        let message_ids = |source: &str| -> Vec<String> {
+            source
+                .lines()
+                .filter(|line| line.starts_with(|c: char| c.is_ascii_alphabetic()))
+                .filter_map(|line| line.split_once(" =").map(|(id, _)| id.to_string()))
+                .collect()
+        };
+        known.extend(message_ids(LOCALES[0].1));
+
+        for (tag, source) in LOCALES {
+            if let Err((_, errors)) = FluentResource::try_new(source.to_string()) {
+                panic!("{}: {:?}", tag, errors);
+            }
+            for id in message_ids(source) {
+                assert!(known.contains(&id), "{} names unknown message {}", tag, id);
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! ICC profiles embedded in outputs for color-managed workflows.
+//!
+//! Colors given on the command line are sRGB, so RGB outputs are tagged as sRGB unless
+//! `--icc` names another profile. PNG has a chunk for that; TIFF needs a real profile,
+//! which [`srgb`] builds.
+
+use crate::error::{ErrorCode, WithCode};
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+
+/// Color space of the written pixels, and of any profile describing them.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    #[default]
+    Rgb,
+    Cmyk,
+}
+
+impl ColorSpace {
+    /// The data color space field of an ICC header for this space.
+    fn signature(self) -> &'static [u8; 4] {
+        match self {
+            Self::Rgb => b"RGB ",
+            Self::Cmyk => b"CMYK",
+        }
+    }
+}
+
+/// Read the profile at `path`, checking that it is an ICC profile for `space`.
+pub fn load(path: &str, space: ColorSpace) -> Result<Vec<u8>> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read ICC profile: {}", path))?;
+    check(&bytes, space).with_context(|| format!("Unusable ICC profile: {}", path))?;
+    Ok(bytes)
+}
+
+/// A compact ICC v2 display profile for sRGB: D50-adapted primaries and the sRGB tone
+/// curve as a 1024-entry table.
+pub fn srgb() -> Vec<u8> {
+    let xyz = |[x, y, z]: [f64; 3]| {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for v in [x, y, z] {
+            tag.extend_from_slice(&s15_fixed16(v));
+        }
+        tag
+    };
+    let mut desc = b"desc\0\0\0\0".to_vec();
+    let name = b"sRGB\0";
+    desc.extend_from_slice(&(name.len() as u32).to_be_bytes());
+    desc.extend_from_slice(name);
+    // Empty Unicode and ScriptCode descriptions.
+    desc.extend_from_slice(&[0; 8 + 3 + 67]);
+    let mut cprt = b"text\0\0\0\0".to_vec();
+    cprt.extend_from_slice(b"No copyright, use freely\0");
+    let mut trc = b"curv\0\0\0\0".to_vec();
+    let entries = 1024u32;
+    trc.extend_from_slice(&entries.to_be_bytes());
+    for i in 0..entries {
+        let v = i as f64 / (entries - 1) as f64;
+        let linear = if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        };
+        trc.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    let tags: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"desc", desc),
+        (b"cprt", cprt),
+        (b"wtpt", xyz(D50)),
+        (b"rXYZ", xyz([0.436_074_7, 0.222_504_5, 0.013_932_2])),
+        (b"gXYZ", xyz([0.385_064_9, 0.716_878_6, 0.097_104_5])),
+        (b"bXYZ", xyz([0.143_080_4, 0.060_616_9, 0.714_173_3])),
+        (b"rTRC", trc),
+        // The green and blue curves share the red one's data.
+        (b"gTRC", Vec::new()),
+        (b"bTRC", Vec::new()),
+    ];
+
+    let mut header = vec![0u8; 128];
+    header[8..12].copy_from_slice(&[2, 0x10, 0, 0]);
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(ColorSpace::Rgb.signature());
+    header[20..24].copy_from_slice(b"XYZ ");
+    header[24..36].copy_from_slice(&[0, 2000, 1, 1, 0, 0].map(u16::to_be_bytes).concat());
+    header[36..40].copy_from_slice(b"acsp");
+    header[68..80].copy_from_slice(&D50.map(s15_fixed16).concat());
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let mut offset = 128 + 4 + 12 * tags.len();
+    let mut last = (0, 0);
+    for (signature, tag) in &tags {
+        if !tag.is_empty() {
+            last = (offset, tag.len());
+            data.extend_from_slice(tag);
+            // Tag data starts on a four-byte boundary.
+            data.resize(data.len().next_multiple_of(4), 0);
+            offset = 128 + 4 + 12 * tags.len() + data.len();
+        }
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(last.0 as u32).to_be_bytes());
+        table.extend_from_slice(&(last.1 as u32).to_be_bytes());
+    }
+
+    let mut profile = [header, table, data].concat();
+    let size = (profile.len() as u32).to_be_bytes();
+    profile[..4].copy_from_slice(&size);
+    profile
+}
+
+/// The PCS illuminant.
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+fn s15_fixed16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn check(bytes: &[u8], space: ColorSpace) -> Result<()> {
+    if bytes.len() < 128 || &bytes[36..40] != b"acsp" {
+        return Err(anyhow!("not an ICC profile")).code(ErrorCode::Usage);
+    }
+    let declared = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    if declared != bytes.len() {
+        return Err(anyhow!(
+            "the header says {} bytes but the file has {}",
+            declared,
+            bytes.len()
+        ))
+        .code(ErrorCode::Usage);
+    }
+    if &bytes[16..20] != space.signature() {
+        return Err(anyhow!(
+            "it describes {} data, not {:?}",
+            String::from_utf8_lossy(&bytes[16..20]).trim(),
+            space
+        ))
+        .code(ErrorCode::Usage);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let mut profile = vec![0u8; 132];
+        profile[..4].copy_from_slice(&132u32.to_be_bytes());
+        profile[16..20].copy_from_slice(b"CMYK");
+        profile[36..40].copy_from_slice(b"acsp");
+        assert!(check(&profile, ColorSpace::Cmyk).is_ok());
+        assert!(check(&profile, ColorSpace::Rgb).is_err());
+        assert!(check(&profile[..130], ColorSpace::Cmyk).is_err());
+        assert!(check(b"not a profile", ColorSpace::Cmyk).is_err());
+    }
+
+    #[test]
+    fn test_srgb() {
+        let profile = srgb();
+        assert!(check(&profile, ColorSpace::Rgb).is_ok());
+        let u32_at = |at: usize| u32::from_be_bytes(profile[at..at + 4].try_into().unwrap());
+        let mut tags = Vec::new();
+        for i in 0..u32_at(128) as usize {
+            let entry = 132 + 12 * i;
+            let (offset, size) = (u32_at(entry + 4) as usize, u32_at(entry + 8) as usize);
+            assert_eq!(offset % 4, 0);
+            assert!(offset + size <= profile.len());
+            tags.push((
+                &profile[entry..entry + 4],
+                &profile[offset..offset + 4],
+                offset,
+            ));
+        }
+        let find = |sig: &[u8]| *tags.iter().find(|t| t.0 == sig).unwrap();
+        assert_eq!(find(b"rXYZ").1, b"XYZ ");
+        assert_eq!(find(b"desc").1, b"desc");
+        assert_eq!(find(b"gTRC").2, find(b"rTRC").2);
+
+        // The tone curve runs from 0 to 65535 through sRGB mid grey.
+        let curve = find(b"rTRC").2;
+        let entry = |i: usize| {
+            u16::from_be_bytes([profile[curve + 12 + 2 * i], profile[curve + 13 + 2 * i]])
+        };
+        assert_eq!(u32_at(curve + 8), 1024);
+        assert_eq!((entry(0), entry(1023)), (0, 65535));
+        // 0.5 encodes about 21.4% linear light.
+        assert!((entry(511) as f64 / 65535.0 - 0.214).abs() < 0.002);
+    }
+}
@@ -0,0 +1,187 @@
+//! Internationalized domain names: Unicode display of punycode hosts and a
+//! homograph (look-alike domain) check.
+
+use crate::log;
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use unicode_script::{Script, UnicodeScript};
+use url::{Position, Url};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum IdnDisplay {
+    /// Show the host as encoded (xn--...)
+    Punycode,
+    /// Show the host in its Unicode form; the QR still encodes punycode
+    Unicode,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum HomographMode {
+    /// Do not check
+    Off,
+    /// Print a warning for suspicious hosts and continue
+    Warn,
+    /// Refuse to write output for suspicious hosts
+    Fail,
+}
+
+/// Cyrillic and Greek letters that are visually identical to Latin ones.
+const LATIN_LOOKALIKES: &str = "аеорсухіјѕԁһӏԛԝαονικρυχ";
+
+/// `url` with its host converted to Unicode, or `url` unchanged when it has no IDN labels.
+pub fn unicode_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    let Some(host) = parsed.host_str().filter(|h| has_idn_label(h)) else {
+        return url.to_string();
+    };
+    let (unicode, result) = idna::domain_to_unicode(host);
+    if result.is_err() {
+        return url.to_string();
+    }
+    format!(
+        "{}{}{}",
+        &parsed[..Position::BeforeHost],
+        unicode,
+        &parsed[Position::AfterHost..]
+    )
+}
+
+fn has_idn_label(host: &str) -> bool {
+    host.split('.')
+        .any(|label| label.len() > 4 && label[..4].eq_ignore_ascii_case("xn--"))
+}
+
+/// Reasons the host of `url` may be a look-alike of another domain.
+pub fn homograph_warnings(url: &str) -> Vec<String> {
+    let Ok(parsed) = Url::parse(url) else {
+        return Vec::new();
+    };
+    let Some(host) = parsed.host_str().filter(|h| has_idn_label(h)) else {
+        return Vec::new();
+    };
+    let (unicode, _) = idna::domain_to_unicode(host);
+
+    let mut warnings = Vec::new();
+    for label in unicode.split('.') {
+        let scripts = label_scripts(label);
+        if scripts.len() > 1 && !is_allowed_mix(&scripts) {
+            let names: Vec<&str> = scripts.iter().map(|s| s.full_name()).collect();
+            warnings.push(format!(
+                "host label '{}' mixes scripts ({})",
+                label,
+                names.join(", ")
+            ));
+        } else if !label.is_empty()
+            && label
+                .chars()
+                .all(|c| is_latin_lookalike(c) || !c.is_alphabetic())
+            && label.chars().any(is_latin_lookalike)
+        {
+            warnings.push(format!(
+                "host label '{}' is made of letters that look Latin but are not",
+                label
+            ));
+        }
+    }
+    warnings
+}
+
+/// Check `url` and either print warnings or fail, depending on `mode`.
+pub fn check_homographs(url: &str, mode: HomographMode) -> Result<()> {
+    if mode == HomographMode::Off {
+        return Ok(());
+    }
+    let warnings = homograph_warnings(url);
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    // Show both forms: the Unicode one is what a victim sees, punycode is what is encoded.
+    let ascii = Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let (unicode, _) = idna::domain_to_unicode(&ascii);
+    let shown = format!("{} ({})", unicode, ascii);
+    if mode == HomographMode::Fail {
+        bail!(
+            "possible homograph domain {}: {}",
+            shown,
+            warnings.join("; ")
+        );
+    }
+    for warning in &warnings {
+        log::warn(format!("possible homograph domain {}: {}", shown, warning));
+    }
+    Ok(())
+}
+
+/// Distinct scripts used by a label, in order of first appearance.
+fn label_scripts(label: &str) -> Vec<Script> {
+    let mut scripts = Vec::new();
+    for script in label.chars().map(|c| c.script()) {
+        if !matches!(script, Script::Common | Script::Inherited | Script::Unknown)
+            && !scripts.contains(&script)
+        {
+            scripts.push(script);
+        }
+    }
+    scripts
+}
+
+/// Script combinations that are normal in real domain names (Latin with CJK).
+fn is_allowed_mix(scripts: &[Script]) -> bool {
+    const JAPANESE: &[Script] = &[
+        Script::Latin,
+        Script::Han,
+        Script::Hiragana,
+        Script::Katakana,
+    ];
+    const CHINESE: &[Script] = &[Script::Latin, Script::Han, Script::Bopomofo];
+    const KOREAN: &[Script] = &[Script::Latin, Script::Han, Script::Hangul];
+    [JAPANESE, CHINESE, KOREAN]
+        .iter()
+        .any(|allowed| scripts.iter().all(|s| allowed.contains(s)))
+}
+
+fn is_latin_lookalike(c: char) -> bool {
+    LATIN_LOOKALIKES.contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_url() {
+        let url = Url::parse("https://bücher.example/menü?q=1")
+            .unwrap()
+            .to_string();
+        assert!(url.contains("xn--bcher-kva"));
+        assert_eq!(unicode_url(&url), "https://bücher.example/men%C3%BC?q=1");
+        assert_eq!(
+            unicode_url("https://example.com/a"),
+            "https://example.com/a"
+        );
+        assert_eq!(unicode_url("mailto:a@b.c"), "mailto:a@b.c");
+    }
+
+    #[test]
+    fn test_homograph_warnings() {
+        // Cyrillic "а" in an otherwise Latin label.
+        let mixed = Url::parse("https://pаypal.com").unwrap().to_string();
+        assert_eq!(homograph_warnings(&mixed).len(), 1);
+        // Entirely Cyrillic, but every letter looks Latin.
+        let whole = Url::parse("https://аррӏе.com").unwrap().to_string();
+        assert_eq!(homograph_warnings(&whole).len(), 1);
+        assert!(check_homographs(&whole, HomographMode::Fail).is_err());
+
+        let german = Url::parse("https://bücher.example").unwrap().to_string();
+        assert!(homograph_warnings(&german).is_empty());
+        let japanese = Url::parse("https://日本語ドメイン.jp").unwrap().to_string();
+        assert!(homograph_warnings(&japanese).is_empty());
+        let russian = Url::parse("https://пример.рф").unwrap().to_string();
+        assert!(homograph_warnings(&russian).is_empty());
+    }
+}
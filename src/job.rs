@@ -0,0 +1,223 @@
+//! `qrbrand run job.yaml`: a checked-in file that describes a whole render, so the
+//! asset is defined once and reviewed like code rather than rebuilt from a long
+//! command line in a Makefile.
+//!
+//! The file (YAML, or TOML for a `.toml` name) is a table of command-line options
+//! under their long names, `-` or `_` alike. They may sit at the top level or be
+//! grouped under `payload`, `style`, `output` and `verify`; the grouping is for the
+//! reader. `command` names a payload subcommand (`email`, `event`, ...) whose own
+//! options then go alongside the rest. `outputs` lists the files to write from the
+//! one render, as `--out` given for each. A flag is `true` or `false`, an option
+//! given several times is a list.
+//!
+//! ```yaml
+//! payload:
+//!   url: https://example.com/menu
+//!   utm_source: table
+//! style:
+//!   theme: corporate
+//!   image: brand/logo.png
+//!   show_url: true
+//! verify:
+//!   strict_contrast: true
+//! outputs: [build/menu.png, build/menu.tiff]
+//! ```
+
+use crate::Args;
+use crate::error::{ErrorCode, WithCode};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{Args as ClapArgs, CommandFactory};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Tables that group options.
+const SECTIONS: [&str; 4] = ["payload", "style", "output", "verify"];
+/// Subcommands a job cannot run.
+const NOT_PAYLOADS: [&str; 11] = [
+    "run",
+    "animate",
+    "batch",
+    "series",
+    "daemon",
+    "serve",
+    "diff",
+    "verify",
+    "bench",
+    "completions",
+    "wizard",
+];
+
+#[derive(ClapArgs, Debug)]
+pub struct RunArgs {
+    /// Job file (YAML, or TOML with a .toml name) describing the payload, style and outputs
+    pub job: PathBuf,
+
+    /// Render again whenever the job file or a file it names changes, replacing each
+    /// output whole so a viewer never reads half an image
+    #[arg(long = "watch")]
+    pub watch: bool,
+
+    /// With --watch, serve the newest PNG on ADDR (e.g. 127.0.0.1:8091): /latest.png
+    /// for an OBS image source, / for a browser source. Implies --watch
+    #[arg(long = "live", value_name = "ADDR")]
+    pub live: Option<String>,
+}
+
+/// Read the job at `path`.
+pub fn load(path: &Path) -> Result<Value> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read job file {}", path.display()))?;
+    let toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    let job = match toml {
+        true => toml::from_str(&text).map_err(anyhow::Error::from),
+        false => serde_yaml_ng::from_str(&text).map_err(anyhow::Error::from),
+    };
+    job.with_context(|| format!("Failed to parse job file {}", path.display()))
+        .code(ErrorCode::Usage)
+}
+
+/// The command line (without the program name) that `job` stands for.
+pub fn argv(job: &Value) -> Result<Vec<String>> {
+    let Value::Object(job) = job else {
+        bail!("a job file must be a table of options");
+    };
+    let root = Args::command();
+    let mut argv = Vec::new();
+    let command = match job.get("command") {
+        None => None,
+        Some(Value::String(name)) => {
+            let sub = root
+                .find_subcommand(name)
+                .filter(|_| !NOT_PAYLOADS.contains(&name.as_str()))
+                .ok_or_else(|| anyhow!("`command: {}` is not a payload subcommand", name))?;
+            argv.push(name.clone());
+            Some(sub)
+        }
+        Some(_) => bail!("`command` must be a subcommand name"),
+    };
+    let mut options = Vec::new();
+    for (key, value) in job {
+        match key.as_str() {
+            "command" => {}
+            "outputs" => options.push(("out", value)),
+            section if SECTIONS.contains(&section) => {
+                let Value::Object(table) = value else {
+                    bail!("`{}` must be a table of options", section);
+                };
+                options.extend(table.iter().map(|(key, value)| (key.as_str(), value)));
+            }
+            _ => options.push((key.as_str(), value)),
+        }
+    }
+    for (key, value) in options {
+        let long = key.replace('_', "-");
+        let arg = command
+            .into_iter()
+            .chain([&root])
+            .find_map(|c| c.get_arguments().find(|a| a.get_long() == Some(&long)))
+            .ok_or_else(|| anyhow!("unknown option `{}`", key))?;
+        let flag = !arg.get_action().takes_values();
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            Value::Null => Vec::new(),
+            one => vec![one],
+        };
+        for value in values {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) if flag => {
+                    if *b {
+                        argv.push(format!("--{}", long));
+                    }
+                    continue;
+                }
+                Value::Bool(b) => b.to_string(),
+                _ => bail!("`{}` must be a string, number, true/false or a list", key),
+            };
+            if flag {
+                bail!("`{}` is a flag: use true or false", key);
+            }
+            argv.push(format!("--{}={}", long, text));
+        }
+    }
+    Ok(argv)
+}
+
+/// Options given on the command line next to `run`, which belong in the job file.
+pub fn reject_options(matches: &clap::ArgMatches) -> Result<()> {
+    let given = matches.ids().find(|id| {
+        matches.value_source(id.as_str()) == Some(clap::parser::ValueSource::CommandLine)
+    });
+    match given {
+        Some(id) => Err(anyhow!(
+            "put `{}` in the job file; `qrbrand run` takes only the file",
+            id
+        ))
+        .code(ErrorCode::Usage),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_argv() {
+        let job: Value = serde_yaml_ng::from_str(
+            "payload:\n  url: https://example.com/menu\n  utm_source: table\n\
+             style:\n  size: 600\n  show-url: true\n  circle_crop: false\n\
+             verify:\n  strict_contrast: true\n\
+             outputs: [menu.png, menu.eps]\n",
+        )
+        .unwrap();
+        let menu = argv(&job).unwrap();
+        assert_eq!(
+            menu,
+            [
+                "--out=menu.png",
+                "--out=menu.eps",
+                "--url=https://example.com/menu",
+                "--utm-source=table",
+                "--show-url",
+                "--size=600",
+                "--strict-contrast",
+            ]
+        );
+        Args::try_parse_from(std::iter::once("qrbrand").chain(menu.iter().map(|a| a.as_str())))
+            .unwrap();
+
+        let email: Value = toml::from_str(
+            "command = \"email\"\nto = [\"a@example.com\", \"b@example.com\"]\nsubject = \"Hi\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            argv(&email).unwrap(),
+            [
+                "email",
+                "--subject=Hi",
+                "--to=a@example.com",
+                "--to=b@example.com"
+            ]
+        );
+
+        let err = |yaml: &str| {
+            let job: Value = serde_yaml_ng::from_str(yaml).unwrap();
+            argv(&job).unwrap_err().to_string()
+        };
+        assert_eq!(err("colour: red"), "unknown option `colour`");
+        assert_eq!(
+            err("command: batch"),
+            "`command: batch` is not a payload subcommand"
+        );
+        assert_eq!(
+            err("show_url: yes please"),
+            "`show_url` is a flag: use true or false"
+        );
+        assert_eq!(err("style: 3"), "`style` must be a table of options");
+    }
+}
@@ -0,0 +1,141 @@
+//! `--sign-hs256` and `--sign-ed25519`: the payload wrapped in a compact JWS
+//! (RFC 7515) before encoding, so a scanning app holding the key can tell our codes
+//! from counterfeit stickers pasted over them.
+//!
+//! The JWS payload is the payload exactly as it would otherwise be encoded. The
+//! header carries only `alg` and, with `--sign-kid`, `kid`, so keys can be rotated.
+//! Both algorithms are deterministic: the same payload and key always give the same
+//! code, and a batch re-run with `--skip-existing` still skips.
+
+use crate::Args;
+use crate::error::{ErrorCode, WithCode};
+use crate::meta::hmac_sha256;
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::Signer as _;
+use ed25519_dalek::SigningKey;
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+
+/// Shortest HS256 secret RFC 7518 allows: as long as the hash.
+const MIN_SECRET: usize = 32;
+
+pub enum Key {
+    Hs256(Vec<u8>),
+    Ed25519(Box<SigningKey>),
+}
+
+impl Key {
+    /// The key the signing flags name, if any.
+    pub fn from_args(args: &Args) -> Result<Option<Self>> {
+        match (&args.sign_hs256, &args.sign_ed25519) {
+            (Some(secret), None) => {
+                if secret.len() < MIN_SECRET {
+                    return Err(anyhow!(
+                        "--sign-hs256 needs a secret of at least {} bytes",
+                        MIN_SECRET
+                    ))
+                    .code(ErrorCode::Usage);
+                }
+                Ok(Some(Self::Hs256(secret.as_bytes().to_vec())))
+            }
+            (None, Some(path)) => {
+                let pem = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read signing key {}", path))?;
+                let key = SigningKey::from_pkcs8_pem(&pem)
+                    .map_err(|e| anyhow!("{}", e))
+                    .with_context(|| format!("{} is not a PKCS#8 Ed25519 private key", path))
+                    .code(ErrorCode::Usage)?;
+                Ok(Some(Self::Ed25519(Box::new(key))))
+            }
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => unreachable!("clap keeps the signing flags apart"),
+        }
+    }
+
+    fn alg(&self) -> &'static str {
+        match self {
+            Self::Hs256(_) => "HS256",
+            Self::Ed25519(_) => "EdDSA",
+        }
+    }
+
+    fn sign(&self, input: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Hs256(secret) => hmac_sha256(secret, input).to_vec(),
+            Self::Ed25519(key) => key.sign(input).to_bytes().to_vec(),
+        }
+    }
+}
+
+/// `payload` as `header.payload.signature`, each part base64url without padding.
+pub fn wrap(payload: &str, key: &Key, kid: Option<&str>) -> String {
+    let mut header = serde_json::Map::new();
+    header.insert("alg".into(), key.alg().into());
+    if let Some(kid) = kid {
+        header.insert("kid".into(), kid.into());
+    }
+    let header = serde_json::Value::Object(header).to_string();
+    compact(header.as_bytes(), payload.as_bytes(), key)
+}
+
+fn compact(header: &[u8], payload: &[u8], key: &Key) -> String {
+    let input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header),
+        URL_SAFE_NO_PAD.encode(payload)
+    );
+    let signature = URL_SAFE_NO_PAD.encode(key.sign(input.as_bytes()));
+    format!("{}.{}", input, signature)
+}
+
+/// `payload` signed with the key the flags name, or as it was without them.
+pub fn sign(args: &Args, payload: String) -> Result<String> {
+    Ok(match Key::from_args(args)? {
+        Some(key) => wrap(&payload, &key, args.sign_kid.as_deref()),
+        None => payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc_vectors() {
+        // RFC 7515 appendix A.1.
+        let secret = URL_SAFE_NO_PAD
+            .decode("AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow")
+            .unwrap();
+        let jws = compact(
+            b"{\"typ\":\"JWT\",\r\n \"alg\":\"HS256\"}",
+            b"{\"iss\":\"joe\",\r\n \"exp\":1300819380,\r\n \"http://example.com/is_root\":true}",
+            &Key::Hs256(secret),
+        );
+        assert!(jws.ends_with(".dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"));
+
+        // RFC 8037 appendix A.4.
+        let d = URL_SAFE_NO_PAD
+            .decode("nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A")
+            .unwrap();
+        let key = Key::Ed25519(Box::new(SigningKey::from_bytes(&d.try_into().unwrap())));
+        assert_eq!(
+            wrap("Example of Ed25519 signing", &key, None),
+            "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc.hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg"
+        );
+    }
+
+    #[test]
+    fn test_kid_and_short_secret() {
+        let key = Key::Hs256(vec![7; 32]);
+        let jws = wrap("https://example.com", &key, Some("2026-q3"));
+        let header = URL_SAFE_NO_PAD
+            .decode(jws.split('.').next().unwrap())
+            .unwrap();
+        assert_eq!(header, br#"{"alg":"HS256","kid":"2026-q3"}"#);
+
+        use clap::Parser;
+        let args = Args::try_parse_from(["qrbrand", "--sign-hs256", "too short"]).unwrap();
+        assert!(Key::from_args(&args).is_err());
+    }
+}
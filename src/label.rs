@@ -0,0 +1,147 @@
+//! `qrbrand label`: one identifier as a QR code and a Code 128 barcode side by side,
+//! with the identifier printed under the bars, for fleets of mixed 2D and 1D scanners.
+//!
+//! The barcode uses the QR module size as its bar width (X dimension), sits to the
+//! right of the code's quiet zone with its own 10-module quiet zone, and spans the
+//! height of the symbol with the text in the caption font below it.
+
+use crate::code128;
+use crate::fonts::FontChain;
+use crate::raster::{self, Canvas};
+use crate::{Args, RenderOptions, Rendered, draw_text_rgba, measure_text_width};
+use anyhow::{Result, bail};
+use clap::Args as ClapArgs;
+use rusttype::Scale;
+
+#[derive(ClapArgs, Debug)]
+pub struct LabelArgs {
+    /// Identifier for both symbols and the printed text, e.g. an asset tag (printable ASCII)
+    #[arg(long = "id")]
+    pub id: String,
+}
+
+/// Height of the printed identifier, and of the band it sits in, in modules.
+const TEXT_MODULES: f32 = 2.5;
+const BAND_MODULES: f32 = 3.5;
+
+impl LabelArgs {
+    pub fn payload(&self) -> Result<String> {
+        // Checked here so a bad identifier fails before anything is rendered.
+        code128::encode(&self.id)?;
+        Ok(self.id.clone())
+    }
+}
+
+/// The first flag given that changes the frame the barcode is laid out against.
+pub fn unsupported(args: &Args) -> Option<&'static str> {
+    [
+        (args.show_url, "--show-url"),
+        (args.display_url.is_some(), "--display-url"),
+        (!args.alt_text.is_empty(), "--alt-text"),
+        (args.template.is_some(), "--template"),
+        (args.corner_radius > 0, "--corner-radius"),
+        (args.circle_crop, "--circle-crop"),
+        (args.shape == crate::round::Shape::Circle, "--shape circle"),
+        (args.border.is_some(), "--border"),
+        (args.shadow.is_some(), "--shadow"),
+    ]
+    .into_iter()
+    .find_map(|(given, flag)| given.then_some(flag))
+}
+
+/// Widen the rendered code with the barcode and text for `id`.
+pub fn attach(
+    rendered: &mut Rendered,
+    id: &str,
+    opts: &RenderOptions,
+    font: &FontChain,
+) -> Result<()> {
+    let bars = code128::encode(id)?;
+    let side = rendered.image.height();
+    let modules = rendered.code.width() as u32 + 2 * opts.quiet;
+    let x_dim = side / modules;
+    let top = x_dim * opts.quiet;
+    let band = (BAND_MODULES * x_dim as f32).round() as u32;
+    if side < 2 * top + 2 * band {
+        bail!("no room for the barcode at this size; raise --size");
+    }
+    let bars_h = side - 2 * top - band;
+    let left = rendered.image.width() + x_dim * code128::QUIET as u32;
+    let width = x_dim * (bars.len() + 2 * code128::QUIET) as u32;
+
+    let mut out = Canvas::from_pixel(rendered.image.width() + width, side, opts.quiet_color.0);
+    raster::replace(&mut out, &rendered.image, 0, 0);
+    for (i, _) in bars.iter().enumerate().filter(|(_, bar)| **bar) {
+        let x = left + i as u32 * x_dim;
+        for y in top..top + bars_h {
+            raster::fill_row(&mut out, y, x..x + x_dim, rendered.fg.0);
+        }
+    }
+
+    let max_w = (bars.len() as u32 * x_dim) as f32;
+    let mut scale = Scale::uniform(TEXT_MODULES * x_dim as f32);
+    let text_w = measure_text_width(font, scale, id);
+    if text_w > max_w {
+        scale = Scale::uniform(scale.y * max_w / text_w);
+    }
+    let v_metrics = font.primary().v_metrics(scale);
+    let centre = (top + bars_h) as f32 + band as f32 / 2.0;
+    let baseline = centre + (v_metrics.ascent + v_metrics.descent) / 2.0;
+    let start_x = left as f32 + (max_w - measure_text_width(font, scale, id)) / 2.0;
+    draw_text_rgba(
+        &mut out,
+        font,
+        scale,
+        start_x,
+        baseline,
+        id,
+        opts.caption.text_color.0,
+    );
+    rendered.image = out;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use clap::Parser;
+
+    #[test]
+    fn test_attach() {
+        let args =
+            Args::try_parse_from(["qrbrand", "--size", "370", "label", "--id", "A-00042"]).unwrap();
+        let Some(crate::Command::Label(label)) = &args.command else {
+            panic!("expected label command");
+        };
+        let opts = RenderOptions::from_args(&args);
+        let assets = crate::Assets::load(None, None, None).unwrap();
+        let mut rendered =
+            crate::render_payload(&label.payload().unwrap(), None, &opts, &assets).unwrap();
+        let side = rendered.image.height();
+        let x_dim = side / (rendered.code.width() as u32 + 8);
+        attach(&mut rendered, &label.id, &opts, &assets.font).unwrap();
+
+        // Start B, "A", "-", "0", code C, "00", "42" and the check symbol of 11 modules
+        // each, 13 for stop, plus the quiet zones.
+        let bars = 8 * 11 + 13;
+        assert_eq!(rendered.image.width(), side + (bars + 20) * x_dim);
+        assert_eq!(rendered.image.height(), side);
+        // The first bar of the start pattern sits after the quiet zones, level with the symbol top.
+        let first = side + 10 * x_dim;
+        assert_eq!(*rendered.image.get_pixel(first, 4 * x_dim), Color::BLACK.0);
+        assert_eq!(
+            *rendered.image.get_pixel(first - 1, 4 * x_dim),
+            Color::WHITE.0
+        );
+        assert_eq!(
+            *rendered.image.get_pixel(first, 4 * x_dim - 1),
+            Color::WHITE.0
+        );
+
+        let bad = LabelArgs {
+            id: "Größe".to_string(),
+        };
+        assert!(bad.payload().is_err());
+    }
+}
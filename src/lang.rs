@@ -0,0 +1,147 @@
+//! `--caption-lang`: one `--alt-text` per language, for signage that ships in several.
+//!
+//! The captions are stacked under one code, a line each in the order given, or with
+//! `--lang-layout files` written one file per language, the language before the
+//! extension. Each line is laid out on its own, so an Arabic or Hebrew caption reads
+//! right to left under an English one.
+
+use crate::Args;
+use crate::error::{ErrorCode, WithCode};
+#[cfg(feature = "cli")]
+use crate::log;
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+#[cfg(feature = "cli")]
+use clap::{ArgMatches, FromArgMatches};
+use std::path::Path;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LangLayout {
+    /// One caption line per language under the code
+    #[default]
+    Stack,
+    /// One output file per language
+    Files,
+}
+
+/// A language tag such as `en`, `fr-CA` or `zh-Hant`.
+pub fn parse(s: &str) -> Result<String, String> {
+    let mut subtags = s.trim().split('-');
+    let primary = subtags.next().unwrap_or_default();
+    let valid = (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags
+            .all(|t| (1..=8).contains(&t.len()) && t.chars().all(|c| c.is_ascii_alphanumeric()));
+    match valid {
+        true => Ok(s.trim().to_string()),
+        false => Err(format!(
+            "expected a language tag such as en or fr-CA, got '{}'",
+            s
+        )),
+    }
+}
+
+/// Refuse `--alt-text` and `--caption-lang` that do not pair up.
+pub fn check(args: &Args) -> Result<()> {
+    let (texts, langs) = (args.alt_text.len(), args.caption_lang.len());
+    let problem = if langs == 0 && texts > 1 {
+        Some("several --alt-text need --caption-lang, one language each".to_string())
+    } else if langs > 0 && langs != texts {
+        Some(format!(
+            "--caption-lang names {} languages for {} --alt-text",
+            langs, texts
+        ))
+    } else if let Some(lang) = duplicate(&args.caption_lang) {
+        Some(format!("--caption-lang lists {} twice", lang))
+    } else if args.lang_layout == LangLayout::Files && langs == 0 {
+        Some("--lang-layout files goes with --caption-lang".to_string())
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => Err(anyhow!(problem)).code(ErrorCode::Usage),
+        None => Ok(()),
+    }
+}
+
+fn duplicate(langs: &[String]) -> Option<&str> {
+    langs
+        .iter()
+        .enumerate()
+        .find(|(i, lang)| langs[..*i].iter().any(|l| l.eq_ignore_ascii_case(lang)))
+        .map(|(_, lang)| lang.as_str())
+}
+
+/// The caption for `alt_text`: the one given, or one line per language.
+pub fn stacked(alt_text: &[String]) -> Option<String> {
+    (!alt_text.is_empty()).then(|| alt_text.join("\n"))
+}
+
+/// `out` with `lang` before its extension: `menu.png` becomes `menu.fr.png`.
+pub fn out_path(out: &str, lang: &str) -> String {
+    let path = Path::new(out);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, lang, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, lang),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// `--lang-layout files`: the single code rendered once per language, each with its
+/// own caption and output path.
+#[cfg(feature = "cli")]
+pub fn run_each(args: &Args, matches: &ArgMatches, out_given: bool, outs: usize) -> Result<()> {
+    if outs > 1 {
+        return Err(anyhow!("--lang-layout files takes one --out")).code(ErrorCode::Usage);
+    }
+    let base = match args.format {
+        Some(format) if !out_given => format!("qrcode.{}", format.extension()),
+        _ => args.out.clone(),
+    };
+    for (lang, text) in args.caption_lang.iter().zip(&args.alt_text) {
+        let mut each = Args::from_arg_matches(matches)?;
+        each.alt_text = vec![text.clone()];
+        each.caption_lang = Vec::new();
+        each.lang_layout = LangLayout::Stack;
+        each.out = out_path(&base, lang);
+        if !out_given {
+            // The extension of the per-language path now names the format.
+            each.format = None;
+        }
+        log::info(format!("Caption in {}", lang));
+        crate::execute(each, matches)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_lang() {
+        assert_eq!(parse("fr-CA"), Ok("fr-CA".to_string()));
+        assert!(parse("zh-Hant-TW").is_ok());
+        assert!(parse("french").is_err());
+        assert!(parse("en_US").is_err());
+        assert_eq!(out_path("signs/menu.png", "de"), "signs/menu.de.png");
+        assert_eq!(out_path("menu", "de"), "menu.de");
+
+        let check_args = |argv: &[&str]| {
+            let args = Args::try_parse_from(["qrbrand"].iter().chain(argv)).unwrap();
+            check(&args).map_err(|e| e.to_string())
+        };
+        assert!(check_args(&["-a", "Menu", "-a", "Carte", "--caption-lang", "en,fr"]).is_ok());
+        assert_eq!(
+            check_args(&["-a", "Menu", "-a", "Carte"]).unwrap_err(),
+            "several --alt-text need --caption-lang, one language each"
+        );
+        assert_eq!(
+            check_args(&["-a", "Menu", "--caption-lang", "en,fr"]).unwrap_err(),
+            "--caption-lang names 2 languages for 1 --alt-text"
+        );
+        assert!(check_args(&["-a", "A", "-a", "B", "--caption-lang", "en,EN"]).is_err());
+    }
+}
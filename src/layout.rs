@@ -0,0 +1,253 @@
+//! Codeword layout of a QR symbol (ISO/IEC 18004 7.5-7.7): how data and error
+//! correction codewords split into Reed-Solomon blocks, how the blocks are
+//! interleaved, and which modules hold each codeword.
+
+use qrcode::bits::Bits;
+use qrcode::canvas::{Canvas, Module};
+use qrcode::{EcLevel, Version};
+
+/// Error correction blocks per version (1..=40) and level (L, M, Q, H), table 9.
+const BLOCKS: [[u8; 4]; 40] = [
+    [1, 1, 1, 1],
+    [1, 1, 1, 1],
+    [1, 1, 2, 2],
+    [1, 2, 2, 4],
+    [1, 2, 4, 4],
+    [2, 4, 4, 4],
+    [2, 4, 6, 5],
+    [2, 4, 6, 6],
+    [2, 5, 8, 8],
+    [4, 5, 8, 8],
+    [4, 5, 8, 11],
+    [4, 8, 10, 11],
+    [4, 9, 12, 16],
+    [4, 9, 16, 16],
+    [6, 10, 12, 18],
+    [6, 10, 17, 16],
+    [6, 11, 16, 19],
+    [6, 13, 18, 21],
+    [7, 14, 21, 25],
+    [8, 16, 20, 25],
+    [8, 17, 23, 25],
+    [9, 17, 23, 34],
+    [9, 18, 25, 30],
+    [10, 20, 27, 32],
+    [12, 21, 29, 35],
+    [12, 23, 34, 37],
+    [12, 25, 34, 40],
+    [13, 26, 35, 42],
+    [14, 28, 38, 45],
+    [15, 29, 40, 48],
+    [16, 31, 43, 51],
+    [17, 33, 45, 54],
+    [18, 35, 48, 57],
+    [19, 37, 51, 60],
+    [19, 38, 53, 63],
+    [20, 40, 56, 66],
+    [21, 43, 59, 70],
+    [22, 45, 62, 74],
+    [24, 47, 65, 77],
+    [25, 49, 68, 81],
+];
+
+/// Where every codeword of a (version, EC level) symbol lives.
+pub struct Layout {
+    /// Data codewords in each block; later blocks may hold one more.
+    pub data_len: Vec<usize>,
+    /// EC codewords per block.
+    pub ec_len: usize,
+    /// Codewords each block can correct (ISO/IEC 18004 table 9, after misdecode protection).
+    pub correctable: usize,
+    /// Block and offset within the block (data, then EC) of each interleaved codeword.
+    order: Vec<(usize, usize)>,
+    /// Data modules in placement order; bit `i` belongs to codeword `i / 8`.
+    modules: Vec<(usize, usize)>,
+}
+
+impl Layout {
+    pub fn new(version: Version, ec_level: EcLevel) -> Self {
+        let modules = data_modules(version, ec_level);
+        let total = modules.len() / 8;
+        let data_total = Bits::new(version).max_len(ec_level).unwrap_or(0) / 8;
+        let level = match ec_level {
+            EcLevel::L => 0,
+            EcLevel::M => 1,
+            EcLevel::Q => 2,
+            EcLevel::H => 3,
+        };
+        let v = match version {
+            Version::Normal(v) => v as usize,
+            Version::Micro(_) => 1,
+        };
+        let blocks = BLOCKS[v - 1][level] as usize;
+        let ec_len = (total - data_total) / blocks;
+        let short = data_total / blocks;
+        let long = data_total % blocks;
+        let data_len: Vec<usize> = (0..blocks)
+            .map(|b| short + usize::from(b >= blocks - long))
+            .collect();
+
+        // Misdecode protection codewords for the smallest symbols.
+        let protected = match (v, ec_level) {
+            (1, EcLevel::L) => 3,
+            (1, EcLevel::M) | (2, EcLevel::L) => 2,
+            (1, _) | (3, EcLevel::L) => 1,
+            _ => 0,
+        };
+
+        let mut order = Vec::with_capacity(total);
+        for i in 0..short + 1 {
+            for (b, &len) in data_len.iter().enumerate() {
+                if i < len {
+                    order.push((b, i));
+                }
+            }
+        }
+        for i in 0..ec_len {
+            for (b, &len) in data_len.iter().enumerate() {
+                order.push((b, len + i));
+            }
+        }
+
+        Self {
+            data_len,
+            ec_len,
+            correctable: (ec_len - protected) / 2,
+            order,
+            modules,
+        }
+    }
+
+    /// Codewords in the symbol, data and EC.
+    pub fn codewords(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.data_len.len()
+    }
+
+    /// Block and offset within the block of interleaved codeword `i`.
+    pub fn block_of(&self, i: usize) -> (usize, usize) {
+        self.order[i]
+    }
+
+    /// Every data module with the interleaved codeword it belongs to (remainder bits excluded).
+    pub fn module_codewords(&self) -> impl Iterator<Item = ((usize, usize), usize)> + '_ {
+        self.modules
+            .iter()
+            .take(self.codewords() * 8)
+            .enumerate()
+            .map(|(bit, &xy)| (xy, bit / 8))
+    }
+
+    /// Split the interleaved codeword stream into blocks, each data followed by EC.
+    pub fn deinterleave(&self, stream: &[u8]) -> Vec<Vec<u8>> {
+        let mut blocks: Vec<Vec<u8>> = self
+            .data_len
+            .iter()
+            .map(|&len| vec![0; len + self.ec_len])
+            .collect();
+        for (&byte, &(b, i)) in stream.iter().zip(&self.order) {
+            blocks[b][i] = byte;
+        }
+        blocks
+    }
+}
+
+/// Row and column coordinates of the alignment pattern centres (ISO/IEC 18004
+/// annex E); empty for version 1.
+pub fn alignment_positions(version: i16) -> Vec<usize> {
+    if version < 2 {
+        return Vec::new();
+    }
+    let v = version as usize;
+    let count = v / 7 + 2;
+    let step = if v == 32 {
+        26
+    } else {
+        (v * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let last = 17 + 4 * v - 7;
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| last - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// Modules left for data once the function patterns are drawn, in the two-column
+/// zigzag from the bottom right, skipping the vertical timing pattern.
+fn data_modules(version: Version, ec_level: EcLevel) -> Vec<(usize, usize)> {
+    let mut canvas = Canvas::new(version, ec_level);
+    canvas.draw_all_functional_patterns();
+    let width = version.width();
+    let mut modules = Vec::new();
+    let mut right = width - 1;
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        let upward = (right + 1) & 2 == 0;
+        for vert in 0..width {
+            let y = if upward { width - 1 - vert } else { vert };
+            for x in [right, right - 1] {
+                if canvas.get(x, y) == Module::Empty {
+                    modules.push((x as usize, y as usize));
+                }
+            }
+        }
+        right -= 2;
+    }
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_match_qrcode_interleaving() {
+        for v in 1..=40 {
+            for level in [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H] {
+                let version = Version::Normal(v);
+                let layout = Layout::new(version, level);
+                let data_total: usize = layout.data_len.iter().sum();
+                assert_eq!(
+                    data_total + layout.blocks() * layout.ec_len,
+                    layout.codewords(),
+                    "v{} {:?}",
+                    v,
+                    level
+                );
+
+                // Number each data codeword and check our interleaving against the encoder's.
+                let data: Vec<u8> = (0..data_total).map(|i| i as u8).collect();
+                let (interleaved, ec) =
+                    qrcode::ec::construct_codewords(&data, version, level).unwrap();
+                assert_eq!(ec.len(), layout.blocks() * layout.ec_len);
+                let blocks = layout.deinterleave(&[interleaved, ec].concat());
+                let mut next = 0u8;
+                for (b, block) in blocks.iter().enumerate() {
+                    for &byte in &block[..layout.data_len[b]] {
+                        assert_eq!(byte, next, "v{} {:?} block {}", v, level, b);
+                        next = next.wrapping_add(1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_module_counts() {
+        // Remainder bits: 0 for version 1, 7 for 2-6, 0 for 7-13, 3 for 14-20 ...
+        for (v, remainder) in [(1, 0), (2, 7), (7, 0), (14, 3), (21, 4), (28, 3), (35, 0)] {
+            let layout = Layout::new(Version::Normal(v), EcLevel::M);
+            assert_eq!(layout.modules.len(), layout.codewords() * 8 + remainder);
+        }
+        assert_eq!(Layout::new(Version::Normal(1), EcLevel::H).codewords(), 26);
+        assert_eq!(
+            Layout::new(Version::Normal(40), EcLevel::L).codewords(),
+            3706
+        );
+    }
+}
@@ -0,0 +1,2919 @@
+// Without `cli` the library keeps the option types `Args` names (formats, payloads,
+// upload targets) but none of the writers and subcommands that use them.
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
+
+use anyhow::{Context, Result, anyhow, bail};
+#[cfg(feature = "cli")]
+use clap::parser::ValueSource;
+use clap::{ArgAction, Parser};
+#[cfg(feature = "cli")]
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Subcommand};
+#[cfg(feature = "cli")]
+use clap_complete::engine::ArgValueCandidates;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba, SubImage, imageops};
+use rusttype::{GlyphId, Scale, point};
+#[cfg(feature = "cli")]
+use serde::Serialize;
+use std::borrow::Cow;
+#[cfg(feature = "cli")]
+use std::path::Path;
+#[cfg(feature = "cli")]
+use std::process::ExitCode;
+use std::sync::Arc;
+use url::Url;
+
+#[cfg(feature = "cli")]
+mod animate;
+mod api;
+#[cfg(feature = "cli")]
+mod barcode;
+mod batch;
+#[cfg(feature = "cli")]
+mod bench;
+mod budget;
+mod cache;
+#[cfg(feature = "cli")]
+mod card;
+#[cfg(feature = "cli")]
+mod code128;
+mod color;
+#[cfg(feature = "cli")]
+mod completions;
+mod compose;
+mod contrast;
+mod decode;
+mod decor;
+mod density;
+#[cfg(feature = "cli")]
+mod diff;
+#[cfg(feature = "cli")]
+mod ean;
+#[cfg(feature = "cli")]
+mod env;
+mod eps;
+mod error;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fonts;
+mod glyph;
+mod i18n;
+mod icc;
+mod idn;
+#[cfg(feature = "cli")]
+mod job;
+mod jws;
+#[cfg(feature = "cli")]
+mod label;
+mod lang;
+mod layout;
+#[cfg(feature = "cli")]
+mod live;
+mod log;
+mod logo;
+mod lowerthird;
+mod marks;
+mod meta;
+mod modules;
+mod orient;
+mod output;
+#[cfg(feature = "cli")]
+mod overlay;
+mod payload;
+mod pdf;
+#[cfg(feature = "cli")]
+mod pdf417;
+mod policy;
+#[cfg(feature = "cli")]
+mod poster;
+mod preview;
+#[cfg(feature = "cli")]
+mod print;
+#[cfg(feature = "python")]
+mod python;
+mod raster;
+mod round;
+mod segment;
+mod shorten;
+#[cfg(feature = "cli")]
+mod stream;
+#[cfg(feature = "cli")]
+mod stress;
+mod svg;
+mod symbol;
+mod template;
+#[cfg(feature = "cli")]
+mod terminal;
+mod text;
+mod theme;
+mod tif;
+mod units;
+mod upload;
+mod urlcheck;
+mod verify;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod webhook;
+#[cfg(feature = "cli")]
+mod wizard;
+
+pub use api::{render, render_rgba};
+
+use color::Color;
+use error::{ErrorCode, WithCode};
+use fonts::{FontChain, FontFace};
+use log::LogFormat;
+#[cfg(feature = "cli")]
+use meta::Metadata;
+use raster::Shape;
+use symbol::Symbol;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "qrbrand",
+    about = "Generate a scannable QR code PNG from a URL, optionally with a centered logo.",
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[cfg(feature = "cli")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// URL to encode (e.g. https://github.com/softwarewrighter/speed-kings)
+    #[arg(short = 'u', long = "url", global = true)]
+    url: Option<String>,
+
+    /// Encode the URL currently on the clipboard instead of --url
+    #[arg(
+        short = 'c',
+        long = "url-from-clipboard",
+        conflicts_with = "url",
+        global = true
+    )]
+    url_from_clipboard: bool,
+
+    /// Append utm_source=<VALUE> to --url
+    #[arg(long = "utm-source")]
+    utm_source: Option<String>,
+
+    /// Append utm_medium=<VALUE> to --url
+    #[arg(long = "utm-medium")]
+    utm_medium: Option<String>,
+
+    /// Append utm_campaign=<VALUE> to --url
+    #[arg(long = "utm-campaign")]
+    utm_campaign: Option<String>,
+
+    /// Append an arbitrary query parameter to --url (repeatable), e.g. --param ref=poster.
+    /// The UTM flags and --param apply to each batch, daemon and serve record's url too
+    #[arg(long = "param", value_parser = payload::link::parse_key_val)]
+    params: Vec<(String, String)>,
+
+    /// Shorten --url (after adding parameters) before encoding to reduce QR density.
+    /// Single codes only; batch, daemon and serve refuse it
+    #[arg(long = "shorten", value_enum)]
+    shorten: Option<shorten::Shortener>,
+
+    /// Endpoint template for --shorten custom; {url} is replaced with the encoded long URL
+    #[arg(long = "shorten-endpoint", env = "QRBRAND_SHORTEN_ENDPOINT")]
+    shorten_endpoint: Option<String>,
+
+    /// API token for the shortener (sent as a Bearer token)
+    #[arg(
+        long = "shorten-token",
+        env = "QRBRAND_SHORTEN_TOKEN",
+        hide_env_values = true
+    )]
+    shorten_token: Option<String>,
+
+    /// Request the final URL (following redirects) before rendering; fail or warn if unreachable.
+    /// Single codes only; batch, daemon and serve refuse it
+    #[arg(
+        long = "check-url",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "fail",
+        global = true
+    )]
+    check_url: Option<urlcheck::CheckMode>,
+
+    /// Wrap the payload in a compact JWS signed with this HS256 secret (32 bytes or more)
+    #[arg(
+        long = "sign-hs256",
+        value_name = "SECRET",
+        env = "QRBRAND_SIGN_HS256",
+        hide_env_values = true,
+        global = true
+    )]
+    sign_hs256: Option<String>,
+
+    /// Wrap the payload in a compact JWS signed (EdDSA) with this PKCS#8 Ed25519 key
+    #[arg(
+        long = "sign-ed25519",
+        value_name = "PEM_FILE",
+        conflicts_with = "sign_hs256",
+        global = true
+    )]
+    sign_ed25519: Option<String>,
+
+    /// Key ID to put in the JWS header, so verifiers can pick the key
+    #[arg(long = "sign-kid", value_name = "ID", global = true)]
+    sign_kid: Option<String>,
+
+    /// Style preset: classic, night, candy, corporate or a theme from the config file.
+    /// Flags given on the command line override it.
+    #[arg(long = "theme", value_name = "NAME", global = true)]
+    #[cfg_attr(feature = "cli", arg(add = ArgValueCandidates::new(completions::themes)))]
+    theme: Option<String>,
+
+    /// Optional center image/logo (png/jpg)
+    #[arg(short = 'i', long = "image", global = true)]
+    image: Option<String>,
+
+    /// Output path; repeat to write several formats from one render (code.png, code.eps, ...)
+    #[arg(
+        short = 'o',
+        long = "out",
+        default_value = "qrcode.png",
+        global = true,
+        action = ArgAction::Append
+    )]
+    out: String,
+
+    /// Output format (default: from the --out extension, else PNG). With ico and no --out,
+    /// writes qrcode.ico.
+    #[arg(long = "format", value_enum, global = true)]
+    format: Option<output::Format>,
+
+    /// With --format ico, also write PNGs of these sizes beside the icon, e.g. 180,192,512
+    /// (favicon-180.png, ...).
+    #[arg(long = "favicon-png", value_name = "SIZES", value_delimiter = ',')]
+    favicon_png: Vec<u32>,
+
+    /// With --format eps, tiff or pdf, or --print, the printed side of the code, quiet zone
+    /// included: 50mm, 5cm, 2in or 144pt (default: one point per pixel, 72 dpi).
+    #[arg(long = "print-size", value_name = "LENGTH", global = true)]
+    print_size: Option<eps::Length>,
+
+    /// Distance the code is scanned from, e.g. 3m: reports the smallest print size that
+    /// scans (10:1 rule) and refuses a smaller --print-size
+    #[arg(long = "scan-distance", value_name = "LENGTH")]
+    scan_distance: Option<eps::Length>,
+
+    /// With --format eps, tiff or pdf, run the quiet zone (TIFF, PDF: the edge pixels) this far
+    /// past the trim: 3mm, 0.125in, ...
+    #[arg(long = "bleed", value_name = "LENGTH")]
+    bleed: Option<eps::Length>,
+
+    /// With --format eps, tiff or pdf, add crop marks at the trim, outside the bleed.
+    #[arg(long = "crop-marks", default_value_t = false)]
+    crop_marks: bool,
+
+    /// Also write <out>-guides.png: the image with the quiet zone boundary, the video
+    /// title-safe area and the logo plate outlined.
+    #[arg(long = "guides", default_value_t = false)]
+    guides: bool,
+
+    /// Draw the module grid over the output, tint the finder, alignment and timing patterns
+    /// and shade the logo plate area.
+    #[arg(long = "debug-overlay", default_value_t = false)]
+    debug_overlay: bool,
+
+    /// Turn the finished image clockwise by 90, 180 or 270 degrees, caption and all, for
+    /// label stock that feeds sideways.
+    #[arg(long = "rotate", value_name = "DEGREES", value_enum, global = true)]
+    rotate: Option<orient::Rotate>,
+
+    /// Mirror the finished image, after any --rotate: h swaps left and right, v top and
+    /// bottom.
+    #[arg(long = "flip", value_enum, global = true)]
+    flip: Option<orient::Flip>,
+
+    /// With --format tiff, write RGB or CMYK pixels.
+    #[arg(long = "colorspace", value_enum, default_value_t = icc::ColorSpace::Rgb, global = true)]
+    colorspace: icc::ColorSpace,
+
+    /// CMYK ink percentages for the dark modules; the default is solid K, never a rich black.
+    #[arg(long = "dark-ink", value_name = "C,M,Y,K", default_value_t = tif::Ink::SOLID_BLACK, global = true)]
+    dark_ink: tif::Ink,
+
+    /// CMYK ink percentages for the light modules and quiet zone.
+    #[arg(long = "light-ink", value_name = "C,M,Y,K", default_value_t = tif::Ink::PAPER, global = true)]
+    light_ink: tif::Ink,
+
+    /// ICC profile to embed in place of sRGB (PNG, TIFF); with --colorspace cmyk, the press
+    /// profile the inks are meant for (e.g. FOGRA39 or GRACoL).
+    #[arg(long = "icc", value_name = "FILE", global = true)]
+    icc: Option<String>,
+
+    /// Size of the QR portion (square): pixels (1024, 2048px) or a length at a resolution
+    /// (50mm@300dpi, 2in@600dpi). Higher is better for video.
+    #[arg(long = "size", default_value_t = units::Size::px(1024), global = true)]
+    size: units::Size,
+
+    /// Render and encode the PNG in horizontal strips instead of in memory, for print
+    /// sizes like 32768 (automatic above 16384). Colors, quiet zone and logo only.
+    #[arg(long = "stream", default_value_t = false)]
+    stream: bool,
+
+    /// Quiet zone size in modules (border). 4 is the usual minimum.
+    #[arg(long = "quiet", default_value_t = 4, global = true)]
+    quiet: u32,
+
+    /// Color of the dark modules.
+    #[arg(long = "fg", value_name = "COLOR", default_value_t = Color::BLACK, global = true)]
+    fg: Color,
+
+    /// Take the module color from the logo: its dominant color, darkened until it
+    /// contrasts well with the quiet zone. Overrides --fg.
+    #[arg(long = "auto-color", default_value_t = false, global = true)]
+    auto_color: bool,
+
+    /// Color of the three finder patterns ("eyes"); data modules keep --fg.
+    #[arg(long = "eye-color", value_name = "COLOR", global = true)]
+    eye_color: Option<Color>,
+
+    /// Color of the 3x3 centre of each finder pattern, when it differs from the outer
+    /// ring (--eye-color, or --fg).
+    #[arg(long = "eye-inner-color", value_name = "COLOR", global = true)]
+    eye_inner_color: Option<Color>,
+
+    /// Small monochrome mark (SVG or image) in the centre of each finder pattern; give
+    /// three for top left, top right and bottom left. The code must still decode.
+    #[arg(
+        long = "eye-glyph",
+        value_name = "FILE",
+        action = ArgAction::Append,
+        global = true
+    )]
+    eye_glyph: Vec<String>,
+
+    /// Color of --eye-glyph, darkened as needed so the eye still reads as dark [default: grey]
+    #[arg(long = "eye-glyph-color", value_name = "COLOR", global = true)]
+    eye_glyph_color: Option<Color>,
+
+    /// Color of the quiet zone; keep it light so scanners still find the code.
+    #[arg(long = "quiet-color", default_value_t = Color::WHITE, global = true)]
+    quiet_color: Color,
+
+    /// Keyline drawn just outside the quiet zone around the whole output, e.g. "2px #000".
+    #[arg(long = "border", value_name = "SPEC", global = true)]
+    border: Option<decor::Border>,
+
+    /// Round the corners of the whole output (QR plus caption band) to transparency.
+    #[arg(
+        long = "corner-radius",
+        value_name = "PX",
+        default_value_t = 0,
+        global = true
+    )]
+    corner_radius: u32,
+
+    /// Crop the whole output to a circle, growing the quiet zone so the symbol stays whole.
+    #[arg(long = "circle-crop", conflicts_with = "corner_radius", global = true)]
+    circle_crop: bool,
+
+    /// Outline of the whole output: square, or circle for round stickers and coasters,
+    /// with a round quiet zone, the caption on a ring and transparency outside.
+    #[arg(
+        long = "shape",
+        value_enum,
+        default_value_t = round::Shape::Square,
+        conflicts_with_all = ["corner_radius", "circle_crop"],
+        global = true
+    )]
+    shape: round::Shape,
+
+    /// Soft shadow under the whole output, e.g. "blur=12,offset=4,opacity=0.3".
+    #[arg(long = "shadow", value_name = "SPEC", global = true)]
+    shadow: Option<decor::Shadow>,
+
+    /// Background the shadow falls on (default: transparent).
+    #[arg(long = "canvas-color", default_value_t = Color::TRANSPARENT, global = true)]
+    canvas_color: Color,
+
+    /// Design to composite the code into (the output takes its size); needs --place. Or
+    /// lower-third: a transparent video frame with the code in a title-safe corner and the
+    /// caption beside it.
+    #[arg(long = "template", value_name = "IMAGE|lower-third", global = true)]
+    template: Option<compose::Template>,
+
+    /// Box on the --template for the code, e.g. "x=120,y=500,w=800" (w and/or h size it).
+    #[arg(
+        long = "place",
+        value_name = "SPEC",
+        requires = "template",
+        global = true
+    )]
+    place: Option<compose::Placement>,
+
+    /// With --template lower-third, the frame size.
+    #[arg(long = "frame-size", value_name = "WxH", default_value_t = lowerthird::FrameSize::default(), global = true)]
+    frame_size: lowerthird::FrameSize,
+
+    /// With --template lower-third, the corner of the title-safe area for the code.
+    #[arg(
+        long = "anchor",
+        value_enum,
+        default_value = "bottom-left",
+        global = true
+    )]
+    anchor: lowerthird::Anchor,
+
+    /// With --template lower-third, a bar of this color behind the code and caption,
+    /// running in from the frame edge.
+    #[arg(long = "brand-bar", value_name = "COLOR", global = true)]
+    brand_bar: Option<Color>,
+
+    /// Fix the QR version (1..=40) so module size stays the same across a family of codes
+    /// (default: smallest version that fits).
+    #[arg(long = "qr-version", value_name = "N", value_parser = clap::value_parser!(i16).range(1..=40), global = true)]
+    qr_version: Option<i16>,
+
+    /// Symbol to draw: a QR code, or PDF417 for driver's-license-style and shipping
+    /// labels (png, or pdf when --out ends in .pdf)
+    #[arg(long = "symbology", value_enum, default_value_t = symbol::Symbology::Qr)]
+    symbology: symbol::Symbology,
+
+    /// Minimum error correction level: L (7%), M (15%), Q (25%) or H (30%).
+    #[arg(long = "ec-level", value_name = "LEVEL", default_value = "H", value_parser = symbol::parse_ec_level, global = true)]
+    ec_level: qrcode::EcLevel,
+
+    /// Keep the requested EC level instead of raising it when a higher one fits
+    /// in the same QR version.
+    #[arg(long = "no-ec-boost", default_value_t = false, global = true)]
+    no_ec_boost: bool,
+
+    /// Force mask pattern N (0..=7) instead of the lowest-penalty one.
+    #[arg(long = "mask", value_name = "N", value_parser = clap::value_parser!(u8).range(0..=7), global = true)]
+    mask: Option<u8>,
+
+    /// Fail instead of warning when light/dark contrast is below ISO/IEC 15415 grade C (40%).
+    #[arg(long = "strict-contrast", default_value_t = false, global = true)]
+    strict_contrast: bool,
+
+    /// Logo size as a fraction of QR width (0.10..0.30 recommended), or `auto` for the
+    /// largest that keeps the --ecc-margin.
+    #[arg(long = "logo-scale", default_value_t = budget::LogoScale::Fixed(0.20), global = true)]
+    logo_scale: budget::LogoScale,
+
+    /// Draw a white plate behind the logo for scan reliability.
+    #[arg(long = "logo-plate", default_value_t = true, global = true)]
+    logo_plate: bool,
+
+    /// Extra padding around the logo plate (fraction of logo size).
+    #[arg(long = "logo-pad", default_value_t = 0.18, global = true)]
+    logo_pad: f32,
+
+    /// Clean up the logo before placing it, e.g. "strip-white,tint=#1d4ed8":
+    /// strip-white[=TOLERANCE] clears a near-white background, mono converts to
+    /// grayscale, tint=COLOR recolors the dark areas.
+    #[arg(long = "logo-prep", value_name = "STEPS", global = true)]
+    logo_prep: Option<logo::Prep>,
+
+    /// When the logo hides more codewords than error correction can safely repair:
+    /// fail, or shrink the logo until it fits.
+    #[arg(long = "logo-fit", value_enum, default_value = "fail", global = true)]
+    logo_fit: budget::LogoFit,
+
+    /// Share of each error correction block's capacity the logo must leave free for
+    /// smudges, glare and print defects (0..1).
+    #[arg(long = "ecc-margin", default_value_t = budget::DEFAULT_RESERVE, global = true)]
+    ecc_margin: f32,
+
+    /// Shrink each dark module by this fraction of its cell for a separated-dot look
+    /// (0..0.3); finder patterns stay solid and the result must still decode.
+    #[arg(
+        long = "module-gap",
+        value_name = "FRACTION",
+        default_value_t = 0.0,
+        global = true
+    )]
+    module_gap: f32,
+
+    /// Draw dark modules filled, or as hollow outlines; outlines only read once a camera
+    /// blurs them, so contrast is judged on each module's average.
+    #[arg(
+        long = "module-style",
+        value_enum,
+        default_value = "square",
+        global = true
+    )]
+    module_style: modules::ModuleStyle,
+
+    /// Light modules on a dark ground, for dark slides: --quiet-color draws the modules
+    /// and --fg the ground and quiet zone (at least 4 modules). Not every scanner reads
+    /// inverted codes.
+    #[arg(long = "invert", default_value_t = false, global = true)]
+    invert: bool,
+
+    /// Paint the dark modules with a tiled image, or a built-in pattern (stripes,
+    /// diagonal, carbon) in --fg and a tint of it; pale pixels are darkened to keep
+    /// contrast.
+    #[arg(long = "fill-texture", value_name = "IMAGE|PATTERN", global = true)]
+    fill_texture: Option<modules::Fill>,
+
+    /// Logo plate fill color (#rrggbb, #rrggbbaa or #rgb).
+    #[arg(long = "plate-color", default_value_t = Color::WHITE, global = true)]
+    plate_color: Color,
+
+    /// Color of the line drawn around the logo plate.
+    #[arg(long = "plate-border-color", default_value_t = Color::BLACK, global = true)]
+    plate_border_color: Color,
+
+    /// Width of the logo plate border in pixels (0 = no border).
+    #[arg(long = "plate-border-width", default_value_t = 0, global = true)]
+    plate_border_width: u32,
+
+    /// Corner radius of the logo plate in pixels (clamped to a pill/circle).
+    #[arg(long = "plate-radius", default_value_t = 0, global = true)]
+    plate_radius: u32,
+
+    /// Soft shadow under the logo plate, same syntax as --shadow.
+    #[arg(long = "plate-shadow", value_name = "SPEC", global = true)]
+    plate_shadow: Option<decor::Shadow>,
+
+    /// Stroke around the logo: width, color and rounded or circle, e.g. "4px #1d4ed8 circle".
+    #[arg(long = "logo-ring", value_name = "SPEC", global = true)]
+    logo_ring: Option<logo::Ring>,
+
+    /// Soft shadow under the logo itself, falling on the plate; same syntax as --shadow.
+    #[arg(long = "logo-shadow", value_name = "SPEC", global = true)]
+    logo_shadow: Option<decor::Shadow>,
+
+    /// How --show-url displays internationalized domains (the QR always encodes punycode).
+    #[arg(
+        long = "idn-display",
+        value_enum,
+        default_value = "punycode",
+        global = true
+    )]
+    idn_display: idn::IdnDisplay,
+
+    /// Check internationalized domains for look-alike (homograph) characters.
+    #[arg(
+        long = "homograph-check",
+        value_enum,
+        default_value = "warn",
+        global = true
+    )]
+    homograph_check: idn::HomographMode,
+
+    /// Refuse http:// links instead of warning about them
+    #[arg(long = "require-https", global = true)]
+    require_https: bool,
+
+    /// Render the URL as text below the QR code.
+    #[arg(
+        short = 's',
+        long = "show-url",
+        default_value_t = false,
+        conflicts_with = "alt_text",
+        global = true
+    )]
+    show_url: bool,
+
+    /// Caption a short form of the URL (e.g. example.com/promo) while the QR encodes the
+    /// full URL; pass TEXT to caption that instead.
+    #[arg(
+        long = "display-url",
+        value_name = "TEXT",
+        num_args = 0..=1,
+        conflicts_with_all = ["alt_text", "show_url"],
+        global = true
+    )]
+    display_url: Option<Option<String>>,
+
+    /// Caption font by installed family name (e.g. "Inter", "serif"); default: embedded DejaVu Sans.
+    #[arg(long = "font-family", global = true)]
+    font_family: Option<String>,
+
+    /// Caption text color.
+    #[arg(long = "text-color", default_value_t = Color::BLACK, global = true)]
+    text_color: Color,
+
+    /// Background color of the caption band.
+    #[arg(long = "band-color", default_value_t = Color::WHITE, global = true)]
+    band_color: Color,
+
+    /// Padding in pixels around the caption; the band is sized to the text plus this padding
+    /// (default: band height proportional to the QR size).
+    #[arg(long = "band-padding", global = true)]
+    band_padding: Option<u32>,
+
+    /// Render alternate text below the QR code instead of the URL. Supports {url}, {date},
+    /// {hash8} and, in batch mode, {row.<column>} placeholders ({{ and }} for literal braces).
+    /// Repeat with --caption-lang for one caption per language.
+    #[arg(
+        short = 'a',
+        long = "alt-text",
+        conflicts_with = "show_url",
+        action = ArgAction::Append,
+        global = true
+    )]
+    alt_text: Vec<String>,
+
+    /// The language of each --alt-text, in order, e.g. en,fr,de
+    #[arg(
+        long = "caption-lang",
+        value_name = "LANGS",
+        value_delimiter = ',',
+        value_parser = lang::parse,
+        global = true
+    )]
+    caption_lang: Vec<String>,
+
+    /// With several --caption-lang: stack the captions under one code, or write one file
+    /// per language (menu.png -> menu.fr.png)
+    #[arg(
+        long = "lang-layout",
+        value_enum,
+        default_value = "stack",
+        global = true
+    )]
+    lang_layout: lang::LangLayout,
+
+    /// Print the chosen QR version, EC level, mode segmentation and contrast grade.
+    #[arg(short = 'v', long = "verbose", default_value_t = false, global = true)]
+    verbose: bool,
+
+    /// Diagnostics on stderr as text or one JSON object per line; errors also carry a code.
+    #[arg(long = "log-format", value_enum, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+
+    /// Exit with status 13 (warnings) if anything warned, after writing as usual
+    #[arg(long = "warnings-as-errors", global = true)]
+    warnings_as_errors: bool,
+
+    /// Blur, downscale, re-compress, rotate and skew the finished image and report where it stops decoding.
+    #[arg(long = "stress-test", default_value_t = false, global = true)]
+    stress_test: bool,
+
+    /// Open the written image in the system's default viewer.
+    #[arg(long = "open", default_value_t = false, global = true)]
+    open: bool,
+
+    /// Print the code to the terminal with Unicode half blocks. Nothing is written to
+    /// disk unless -o/--out is given too.
+    #[arg(long = "terminal", default_value_t = false, global = true)]
+    terminal: bool,
+
+    /// Copy the finished image to the system clipboard. Nothing is written to disk
+    /// unless -o/--out is given too.
+    #[arg(long = "clipboard", default_value_t = false, global = true)]
+    clipboard: bool,
+
+    /// Show the result in a window first; Enter saves, Esc discards (needs the `preview` feature).
+    #[arg(long = "preview", default_value_t = false, global = true)]
+    preview: bool,
+
+    /// Send the written file to this CUPS printer queue (IPP printers included) with `lp`,
+    /// on a page of the --print-size if given.
+    #[arg(long = "print", value_name = "PRINTER")]
+    print: Option<String>,
+
+    /// With --print, how many copies to print.
+    #[arg(
+        long = "copies",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "print"
+    )]
+    copies: u32,
+
+    /// Write a JSON sidecar with QR version, EC level, dimensions, logo coverage and payload hash.
+    #[arg(long = "meta-out", global = true)]
+    meta_out: Option<String>,
+
+    /// Copy each written file to object storage: s3://bucket/prefix/, gs://bucket/prefix/ or
+    /// az://account/container/prefix/ (credentials from the environment; see the README).
+    #[arg(long = "upload", value_name = "URL", global = true)]
+    upload: Option<upload::Target>,
+
+    /// POST a JSON summary (metadata plus the file base64-encoded, or its --upload URL) to
+    /// this URL after each generated file.
+    #[arg(long = "webhook", value_name = "URL", value_parser = webhook::parse_url, global = true)]
+    webhook: Option<Url>,
+
+    /// Validate everything and print what would be generated (version, EC level, size, logo
+    /// coverage, contrast) without writing any file. In batch mode, prints the manifest.
+    #[arg(
+        long = "dry-run",
+        default_value_t = false,
+        global = true,
+        conflicts_with_all = ["open", "preview", "clipboard", "terminal", "stress_test", "meta_out"]
+    )]
+    dry_run: bool,
+
+    /// Refuse to overwrite an existing output file (batch rows fail individually).
+    #[arg(long = "no-clobber", default_value_t = false, global = true)]
+    no_clobber: bool,
+
+    /// If the output file exists, write to a new name instead: the payload hash is
+    /// appended (qrcode-1a2b3c4d.png), then a counter if that is taken too.
+    #[arg(long = "unique", default_value_t = false, global = true)]
+    unique: bool,
+
+    /// Embed the payload and caption as PNG text chunks (Title/Description), or SVG
+    /// `<title>`/`<desc>`, for accessibility tools.
+    #[arg(long = "embed-alt", default_value_t = false, global = true)]
+    embed_alt: bool,
+}
+
+/// Structured payload modes. Without a subcommand, `--url` is encoded.
+#[cfg(feature = "cli")]
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Encode an otpauth://totp/ provisioning URI for authenticator apps
+    Totp(payload::totp::TotpArgs),
+    /// Encode an EPC069-12 (GiroCode) SEPA credit transfer
+    Epc(payload::epc::EpcArgs),
+    /// Encode an iCalendar VEVENT so phones offer "Add to calendar"
+    Event(payload::event::EventArgs),
+    /// Encode a mailto: link with recipients, subject and body
+    Email(payload::contact::EmailArgs),
+    /// Encode a pre-filled SMS message
+    Sms(payload::contact::SmsArgs),
+    /// Encode a tel: phone number
+    Tel(payload::contact::TelArgs),
+    /// Encode a geo: map location
+    Geo(payload::geo::GeoArgs),
+    /// Encode GS1 element strings (with FNC1) or a GS1 Digital Link URI for retail scanners
+    Gs1(payload::gs1::Gs1Args),
+    /// Encode a BIP-21 bitcoin: payment request (address checksum verified)
+    Bitcoin(payload::crypto::BitcoinArgs),
+    /// Encode an EIP-681 ethereum: payment request (EIP-55 checksum verified)
+    Ethereum(payload::crypto::EthereumArgs),
+    /// Draw an EAN-13, UPC-A or EAN-8 retail barcode with its check digit and printed digits
+    Barcode(barcode::BarcodeArgs),
+    /// Print one identifier as a QR code and a Code 128 barcode, with the text under the bars
+    Label(label::LabelArgs),
+    /// Lay out a print-ready business card PDF: the code, a logo, name, title and contact lines
+    Card(card::CardArgs),
+    /// Lay out a "scan for the menu" sign or table tent PDF on A5, A4 or letter
+    Poster(poster::PosterArgs),
+    /// Render one QR code per CSV row, in parallel
+    Batch(batch::BatchArgs),
+    /// Render a numbered series of codes from a URL template, each labelled with its serial
+    Series(batch::series::SeriesArgs),
+    /// Serve JSON-RPC render requests over stdio or a Unix socket
+    Daemon(batch::daemon::DaemonArgs),
+    /// Render codes over HTTP: GET or POST /render, answering with PNG, SVG or PDF
+    Serve(batch::serve::ServeArgs),
+    /// Draw where two codes differ, module by module, with the changed modules in red
+    Diff(diff::DiffArgs),
+    /// Decode a directory of proofs and check them against a batch manifest
+    Verify(verify::VerifyArgs),
+    /// Time each rendering stage across payloads and sizes, and print a table
+    Bench(bench::BenchArgs),
+    /// Cycle through several URLs in one looping GIF or APNG, a few seconds per code
+    Animate(animate::AnimateArgs),
+    /// Render what a YAML or TOML job file describes: payload, style and outputs
+    Run(job::RunArgs),
+    /// Print a shell completion script (bash, zsh, fish or powershell)
+    Completions(completions::CompletionsArgs),
+    /// Answer a few questions to build a code and the equivalent command line
+    Wizard,
+    /// Print each exit status and the failure it means, as JSON with --log-format json
+    ExitCodes,
+}
+
+/// The `qrbrand` command line: parse arguments, run the requested mode and report
+/// any failure with its exit code (see [`error`]).
+#[cfg(feature = "cli")]
+pub fn run() -> ExitCode {
+    completions::complete();
+    let matches = i18n::layer(env::layer(Args::command())).get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    log::init(args.log_format);
+    match execute(args, &matches).and_then(|()| log::warnings_failure()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            log::error(&e);
+            ErrorCode::of(&e).into()
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+fn execute(mut args: Args, matches: &ArgMatches) -> Result<()> {
+    if args.warnings_as_errors {
+        log::fail_on_warnings();
+    }
+    match &args.command {
+        Some(Command::Completions(completions)) => return completions::run(completions),
+        Some(Command::ExitCodes) => {
+            print!("{}", error::table(args.log_format == LogFormat::Json));
+            return Ok(());
+        }
+        Some(Command::Wizard) => {
+            return match wizard::run()? {
+                Some(argv) => execute_argv(argv),
+                None => Ok(()),
+            };
+        }
+        Some(Command::Run(run)) => return run_job(run, matches),
+        _ => {}
+    }
+    let out_given = matches!(
+        matches.value_source("out"),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    );
+    // Further --out paths, written from the same render as the first.
+    let mut extra_outs: Vec<String> = match out_given {
+        true => matches
+            .get_many::<String>("out")
+            .into_iter()
+            .flatten()
+            .skip(1)
+            .cloned()
+            .collect(),
+        false => Vec::new(),
+    };
+    let single = !matches!(
+        args.command,
+        Some(
+            Command::Batch(_)
+                | Command::Series(_)
+                | Command::Daemon(_)
+                | Command::Serve(_)
+                | Command::Diff(_)
+                | Command::Verify(_)
+                | Command::Bench(_)
+                | Command::Barcode(_)
+                | Command::Card(_)
+                | Command::Poster(_)
+                | Command::Animate(_)
+        )
+    );
+    check_outs(&args, single, &extra_outs)?;
+    lang::check(&args)?;
+    if args.lang_layout == lang::LangLayout::Files {
+        if !single {
+            return Err(anyhow!(i18n::tr("lang-files-single", &[]))).code(ErrorCode::Usage);
+        }
+        return lang::run_each(&args, matches, out_given, 1 + extra_outs.len());
+    }
+    apply_theme(&mut args, matches)?;
+    warn_hard_to_scan(&mut args);
+
+    match &args.command {
+        Some(Command::Batch(batch)) => return batch::run(&args, batch),
+        Some(Command::Series(series)) => return batch::series::run(&args, series),
+        Some(Command::Daemon(daemon)) => return batch::daemon::run(&args, daemon),
+        Some(Command::Serve(serve)) => return batch::serve::run(&args, serve),
+        Some(Command::Diff(diff)) => return diff::run(&args, diff, out_given),
+        Some(Command::Verify(verify)) => return verify::run(&args, verify),
+        Some(Command::Bench(bench)) => return bench::run(&args, bench),
+        Some(Command::Barcode(barcode)) => return barcode::run(&args, barcode, out_given),
+        Some(Command::Animate(animate)) => return animate::run(&args, animate, out_given),
+        _ => {}
+    }
+
+    let (payload, shown) = checked_payload(&args)?;
+    match &args.command {
+        Some(Command::Card(card)) => return card::run(&args, card, &payload, out_given),
+        Some(Command::Poster(poster)) => return poster::run(&args, poster, &payload, out_given),
+        _ => {}
+    }
+
+    let payload_hash = meta::sha256_hex(payload.as_bytes());
+    let date = template::today();
+    let vars = template::TemplateVars {
+        row: None,
+        payload: &payload,
+        payload_hash: &payload_hash,
+        index: 1,
+        date: &date,
+        serial: None,
+    };
+    let alt_text = lang::stacked(&args.alt_text)
+        .map(|t| template::render(&t, &vars, false))
+        .transpose()
+        .with_context(|| i18n::tr("alt-text-template-invalid", &[]))?;
+
+    let show_url = args.show_url || args.display_url.is_some();
+    let caption = caption_text(show_url, alt_text.as_deref(), &shown);
+    if args.symbology == symbol::Symbology::Pdf417 {
+        return pdf417::run(&args, &payload, caption, alt_text.as_deref(), out_given);
+    }
+
+    let assets = Assets::load(
+        args.image.as_deref(),
+        args.font_family.as_deref(),
+        args.template.as_ref().and_then(compose::Template::image),
+    )?;
+    let formats = output_formats(&mut args, &extra_outs, out_given)?;
+    let physical = formats.iter().any(|format| format.is_physical());
+    let marks = marks::Marks {
+        bleed: args.bleed.map_or(0.0, |length| length.0),
+        crop: args.crop_marks,
+    };
+    check_formats(&args, &formats, &marks)?;
+    let icc = args
+        .icc
+        .as_deref()
+        .map(|path| icc::load(path, args.colorspace))
+        .transpose()?;
+    let writes_file =
+        out_given || args.open || args.print.is_some() || !(args.clipboard || args.terminal);
+    let side_files = match writes_file {
+        true => claim_outputs(&mut args, &mut extra_outs, &formats, &payload_hash)?,
+        false => SideFiles::default(),
+    };
+    let outputs: Vec<(String, output::Format)> = std::iter::once(args.out.clone())
+        .chain(extra_outs)
+        .zip(formats)
+        .collect();
+    let opts = RenderOptions::from_args(&args);
+    if args.stream && outputs.len() > 1 {
+        return Err(anyhow!(i18n::tr("stream-one-out", &[]))).code(ErrorCode::Usage);
+    }
+    if outputs.len() == 1 && stream::wanted(&args)? {
+        return stream::run(&args, &payload, &assets, &opts, icc.as_deref());
+    }
+    let mut rendered = render_payload(&payload, caption, &opts, &assets)?;
+    if let Some(Command::Label(label)) = &args.command {
+        label::attach(&mut rendered, &label.id, &opts, &assets.font)?;
+    }
+    let contrast = rendered.contrast(opts.quiet);
+    for warning in contrast.check(opts.strict_contrast)? {
+        log::warn(warning);
+    }
+    if let Some(warning) = rendered.logo_shrink_warning(opts.logo_scale) {
+        log::warn(warning);
+    }
+    if let Some(warning) = rendered.density_advice(&payload, &opts) {
+        log::warn(warning);
+    }
+    set_print_size(&mut args, &rendered, opts.quiet, physical)?;
+    if args.debug_overlay {
+        overlay::draw(
+            &mut rendered.image,
+            &rendered.code,
+            rendered.qr_rect,
+            opts.quiet,
+            rendered.logo_rect,
+        );
+    }
+    rendered.orient(opts.orientation);
+    let output = CodeOutput {
+        args: &args,
+        payload: &payload,
+        alt_text: alt_text.as_deref(),
+        rendered: &rendered,
+        opts: &opts,
+        icc: icc.as_deref(),
+        marks: &marks,
+    };
+    if args.verbose {
+        output.log_details(&contrast);
+    }
+    if args.dry_run {
+        let logo = rendered.logo_budget.as_ref().zip(rendered.logo_scale);
+        let outs: Vec<&str> = outputs.iter().map(|(out, _)| out.as_str()).collect();
+        print_dry_run(
+            &outs,
+            output.metadata(),
+            &rendered.code,
+            logo,
+            &contrast,
+            args.log_format,
+        );
+        return Ok(());
+    }
+
+    if args.preview && !preview::confirm(&rendered.image)? {
+        log::info("Preview discarded; nothing written");
+        return Ok(());
+    }
+    if writes_file {
+        for (out, format) in &outputs {
+            output.save(out, *format)?;
+            log::info(i18n::tr("wrote", &[("path", out.into())]));
+            output.publish(out)?;
+        }
+        if let Some(printer) = &args.print {
+            output.print(printer)?;
+        }
+        output.write_side_files(&side_files)?;
+    }
+    if args.open
+        && let Err(e) = preview::open(&args.out)
+    {
+        log::warn(format!("{:#}", e));
+    }
+    output.report()
+}
+
+/// Parse `argv` as a command line of its own and run it, as the wizard and job files do.
+#[cfg(feature = "cli")]
+fn execute_argv(argv: Vec<String>) -> Result<()> {
+    let matches = env::layer(Args::command())
+        .try_get_matches_from(std::iter::once("qrbrand".to_string()).chain(argv))?;
+    execute(Args::from_arg_matches(&matches)?, &matches)
+}
+
+/// `qrbrand run`: the job file's command line, from the job file's directory, or a
+/// watch on it with `--watch` and `--live`.
+#[cfg(feature = "cli")]
+fn run_job(run: &job::RunArgs, matches: &ArgMatches) -> Result<()> {
+    job::reject_options(matches)?;
+    if run.watch || run.live.is_some() {
+        return live::run(&run.job, run.live.as_deref());
+    }
+    let job = job::load(&run.job)?;
+    let argv = job::argv(&job)
+        .with_context(|| {
+            i18n::tr(
+                "job-invalid",
+                &[("path", run.job.display().to_string().into())],
+            )
+        })
+        .code(ErrorCode::Usage)?;
+    // Paths in the job are relative to the file, wherever it is run from.
+    if let Some(dir) = run.job.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::env::set_current_dir(dir)
+            .with_context(|| format!("Failed to enter {}", dir.display()))?;
+    }
+    execute_argv(argv)
+}
+
+/// Refuse several `--out` paths, or PDF417, where one code is not what gets written.
+#[cfg(feature = "cli")]
+fn check_outs(args: &Args, single: bool, extra_outs: &[String]) -> Result<()> {
+    if !extra_outs.is_empty() && !single {
+        return Err(anyhow!(i18n::tr("several-outs-single", &[]))).code(ErrorCode::Usage);
+    }
+    if args.symbology == symbol::Symbology::Pdf417 {
+        if !single {
+            return Err(anyhow!(i18n::tr("pdf417-single", &[]))).code(ErrorCode::Usage);
+        }
+        if !extra_outs.is_empty() {
+            return Err(anyhow!(i18n::tr("pdf417-one-out", &[]))).code(ErrorCode::Usage);
+        }
+    }
+    Ok(())
+}
+
+/// Fill the options `--theme` sets and the command line and environment leave alone.
+#[cfg(feature = "cli")]
+fn apply_theme(args: &mut Args, matches: &ArgMatches) -> Result<()> {
+    let Some(name) = args.theme.clone() else {
+        return Ok(());
+    };
+    let theme = theme::lookup(&name, theme::config_path().as_deref()).code(ErrorCode::Usage)?;
+    theme::apply(args, matches, &theme)
+        .with_context(|| i18n::tr("theme-failed", &[("theme", name.as_str().into())]))
+        .code(ErrorCode::Usage)
+}
+
+/// Warn about `--invert` and `--flip`, which not every scanner reads, and widen the
+/// quiet zone an inverted code needs.
+#[cfg(feature = "cli")]
+fn warn_hard_to_scan(args: &mut Args) {
+    if args.invert {
+        if args.quiet < INVERTED_QUIET {
+            log::warn(format!(
+                "--invert needs the full quiet zone; using --quiet {}",
+                INVERTED_QUIET
+            ));
+            args.quiet = INVERTED_QUIET;
+        }
+        log::warn(
+            "inverted codes (light on dark) are not read by every scanner; older phone camera apps and many dedicated readers skip them",
+        );
+    }
+    if args.flip.is_some() {
+        log::warn(
+            "a flipped code reads mirrored until seen through the stock; not every scanner tries mirror images",
+        );
+    }
+}
+
+/// The payload of a single code, checked against the link policy (and `--check-url`)
+/// and signed with `--sign-*`, and the URL a `--show-url` caption shows for it.
+#[cfg(feature = "cli")]
+fn checked_payload(args: &Args) -> Result<(String, String)> {
+    let wants_url = matches!(
+        args.command,
+        None | Some(Command::Card(_) | Command::Poster(_))
+    );
+    if wants_url && args.url.is_none() && !args.url_from_clipboard {
+        return Err(anyhow!(i18n::tr("url-required-clipboard", &[]))).code(ErrorCode::Usage);
+    }
+    let payload = resolve_payload(args).code(ErrorCode::InvalidPayload)?;
+    idn::check_homographs(&payload, args.homograph_check).code(ErrorCode::InvalidPayload)?;
+    for warning in policy::check(&payload, args.require_https).code(ErrorCode::InvalidPayload)? {
+        log::warn(warning);
+    }
+
+    if let Some(mode) = args.check_url {
+        if urlcheck::is_checkable(&payload) {
+            urlcheck::check_url(&payload, mode)?;
+        } else {
+            log::warn("--check-url skipped (payload is not an http(s) URL)");
+        }
+    }
+
+    // --show-url captions the link a person would follow, not the token around it.
+    let shown = display_url(args, &payload);
+    let payload = jws::sign(args, payload)?;
+    Ok((payload, shown))
+}
+
+/// The format of the first `--out` (recorded in `args.format`, naming the default
+/// output after it) followed by those of `extra_outs`, by extension.
+#[cfg(feature = "cli")]
+fn output_formats(
+    args: &mut Args,
+    extra_outs: &[String],
+    out_given: bool,
+) -> Result<Vec<output::Format>> {
+    let format = match args.format {
+        Some(format) if !out_given => {
+            args.out = format!("qrcode.{}", format.extension());
+            format
+        }
+        Some(_) if !extra_outs.is_empty() => {
+            return Err(anyhow!(i18n::tr("format-several-outs", &[]))).code(ErrorCode::Usage);
+        }
+        Some(format) => format,
+        None => output::Format::of_path(Path::new(&args.out))?,
+    };
+    args.format = Some(format);
+    std::iter::once(Ok(format))
+        .chain(
+            extra_outs
+                .iter()
+                .map(|out| output::Format::of_path(Path::new(out))),
+        )
+        .collect()
+}
+
+/// Refuse options that none of `formats` can carry out.
+#[cfg(feature = "cli")]
+fn check_formats(args: &Args, formats: &[output::Format], marks: &marks::Marks) -> Result<()> {
+    let writes = |format| formats.contains(&format);
+    if !args.favicon_png.is_empty() && !writes(output::Format::Ico) {
+        return Err(anyhow!(i18n::tr("favicon-needs-ico", &[]))).code(ErrorCode::Usage);
+    }
+    if args.favicon_png.contains(&0) {
+        return Err(anyhow!(i18n::tr("favicon-zero", &[]))).code(ErrorCode::Usage);
+    }
+    let physical = formats.iter().any(|format| format.is_physical());
+    if args.print_size.is_some() && !physical && args.print.is_none() {
+        return Err(anyhow!(i18n::tr("print-size-format", &[]))).code(ErrorCode::Usage);
+    }
+    if !marks.is_empty() && !physical {
+        let flag = if args.bleed.is_some() {
+            "--bleed"
+        } else {
+            "--crop-marks"
+        };
+        return Err(anyhow!(i18n::tr(
+            "needs-physical",
+            &[("flag", flag.into())]
+        )))
+        .code(ErrorCode::Usage);
+    }
+    if args.colorspace != icc::ColorSpace::Rgb && !writes(output::Format::Tiff) {
+        return Err(anyhow!(i18n::tr("colorspace-needs-tiff", &[]))).code(ErrorCode::Usage);
+    }
+    if args.icc.is_some() && !(writes(output::Format::Png) || writes(output::Format::Tiff)) {
+        return Err(anyhow!(i18n::tr("icc-format", &[]))).code(ErrorCode::Usage);
+    }
+    if args.colorspace != icc::ColorSpace::Cmyk {
+        let cmyk_only = [
+            (args.dark_ink != tif::Ink::SOLID_BLACK, "--dark-ink"),
+            (args.light_ink != tif::Ink::PAPER, "--light-ink"),
+        ];
+        if let Some((_, flag)) = cmyk_only.iter().find(|(given, _)| *given) {
+            return Err(anyhow!(i18n::tr("needs-cmyk", &[("flag", (*flag).into())])))
+                .code(ErrorCode::Usage);
+        }
+    }
+    if writes(output::Format::Eps)
+        && let Some(flag) = eps::unsupported(args)
+    {
+        return Err(anyhow!(i18n::tr(
+            "eps-unsupported",
+            &[("flag", flag.into())]
+        )))
+        .code(ErrorCode::Usage);
+    }
+    // SVG draws the same bare module grid as EPS.
+    if writes(output::Format::Svg)
+        && let Some(flag) = eps::unsupported(args)
+    {
+        return Err(anyhow!(i18n::tr(
+            "svg-unsupported",
+            &[("flag", flag.into())]
+        )))
+        .code(ErrorCode::Usage);
+    }
+    if let Some(Command::Label(_)) = &args.command
+        && let Some(flag) = label::unsupported(args)
+    {
+        return Err(anyhow!(i18n::tr(
+            "label-unsupported",
+            &[("flag", flag.into())]
+        )))
+        .code(ErrorCode::Usage);
+    }
+    Ok(())
+}
+
+/// Files written beside the outputs: `--favicon-png` sizes and the `--guides` proof.
+#[cfg(feature = "cli")]
+#[derive(Default)]
+struct SideFiles {
+    favicons: Vec<(u32, String)>,
+    guides: Option<String>,
+}
+
+/// Claim `args.out` and `extra_outs` under `--no-clobber` and `--unique`, rewriting
+/// them to the names claimed, and the side files beside them.
+#[cfg(feature = "cli")]
+fn claim_outputs(
+    args: &mut Args,
+    extra_outs: &mut [String],
+    formats: &[output::Format],
+    payload_hash: &str,
+) -> Result<SideFiles> {
+    let out = output::claim_path(
+        Path::new(&args.out),
+        &payload_hash[..8],
+        args.no_clobber,
+        args.unique,
+        Path::exists,
+    )?;
+    args.out = out.to_string_lossy().into_owned();
+    for extra in extra_outs.iter_mut() {
+        let out = output::claim_path(
+            Path::new(extra.as_str()),
+            &payload_hash[..8],
+            args.no_clobber,
+            args.unique,
+            Path::exists,
+        )?;
+        *extra = out.to_string_lossy().into_owned();
+    }
+    // Favicons go beside the icon, guides beside the first file.
+    let beside = |out: &str, suffix: &str| {
+        let out = Path::new(out);
+        let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+        let path = out.with_file_name(format!("{}-{}.png", stem, suffix));
+        output::claim_path(&path, "", args.no_clobber, false, Path::exists)
+            .map(|path| path.to_string_lossy().into_owned())
+    };
+    let mut side_files = SideFiles::default();
+    let icon = std::iter::once(&args.out)
+        .chain(extra_outs.iter())
+        .zip(formats)
+        .find(|(_, format)| **format == output::Format::Ico);
+    if let Some((icon, _)) = icon {
+        for &size in &args.favicon_png {
+            side_files
+                .favicons
+                .push((size, beside(icon, &size.to_string())?));
+        }
+    }
+    if args.guides {
+        side_files.guides = Some(beside(&args.out, "guides")?);
+    }
+    Ok(side_files)
+}
+
+/// Settle `args.print_size` for EPS, TIFF and `--print`: a `--size` given at a
+/// resolution sets it, and `--scan-distance` checks it or, when nothing else does,
+/// picks the smallest size that scans.
+#[cfg(feature = "cli")]
+fn set_print_size(args: &mut Args, rendered: &Rendered, quiet: u32, physical: bool) -> Result<()> {
+    // A --size given at a resolution sets the printed side as --print-size would.
+    let printed = args.size.printed(rendered.qr_rect.2);
+    if args.print_size.is_none() && (physical || args.print.is_some()) {
+        args.print_size = printed;
+    }
+    let Some(distance) = args.scan_distance else {
+        return Ok(());
+    };
+    let needed = density::min_print_mm(distance.mm() as f32, &rendered.code, quiet);
+    let distance = density::describe_mm(distance.mm() as f32);
+    match args.print_size.or(printed) {
+        Some(size) if size.mm() < needed as f64 => {
+            return Err(anyhow!(i18n::tr(
+                "print-too-small",
+                &[
+                    ("size", density::describe_mm(size.mm() as f32).into()),
+                    ("distance", distance.into()),
+                    ("needed", density::describe_mm(needed).into())
+                ]
+            )))
+            .code(ErrorCode::SizeTooSmall);
+        }
+        // Physical outputs need a size; the smallest that scans is a fair default.
+        None if physical || args.print.is_some() => {
+            args.print_size = Some(eps::Length::from_mm(needed.ceil() as f64));
+            log::info(format!(
+                "Printing {} wide (quiet zone included), the least that scans from {}",
+                density::describe_mm(needed.ceil()),
+                distance
+            ));
+        }
+        _ => log::info(format!(
+            "Scanning from {} needs the code printed at least {} wide, quiet zone included",
+            distance,
+            density::describe_mm(needed)
+        )),
+    }
+    Ok(())
+}
+
+/// A rendered single code and what its files are written with.
+#[cfg(feature = "cli")]
+struct CodeOutput<'a> {
+    args: &'a Args,
+    payload: &'a str,
+    alt_text: Option<&'a str>,
+    rendered: &'a Rendered,
+    opts: &'a RenderOptions,
+    icc: Option<&'a [u8]>,
+    marks: &'a marks::Marks,
+}
+
+#[cfg(feature = "cli")]
+impl CodeOutput<'_> {
+    /// The `--verbose` lines: symbol, logo scale, module color and contrast.
+    fn log_details(&self, contrast: &contrast::Contrast) {
+        let rendered = self.rendered;
+        log::info(rendered.code.describe());
+        if let (Some(budget), Some(scale)) = (&rendered.logo_budget, rendered.logo_scale) {
+            log::info(format!("Logo scale {:.2}; {}", scale, budget.describe()));
+        }
+        if self.opts.auto_color {
+            log::info(format!("Module color {} (from the logo)", rendered.fg));
+        }
+        log::info(format!(
+            "Contrast {:.0}% (grade {})",
+            contrast.symbol_contrast() * 100.0,
+            contrast.grade()
+        ));
+    }
+
+    /// Write the code to `out` as `format`.
+    fn save(&self, out: &str, format: output::Format) -> Result<()> {
+        let (args, rendered, image) = (self.args, self.rendered, &self.rendered.image);
+        let (_, _, side) = rendered.qr_rect;
+        match format {
+            output::Format::Png => save_image(
+                image,
+                out,
+                args.embed_alt,
+                self.alt_text,
+                self.payload,
+                &[],
+                // A CMYK profile belongs to the TIFF only.
+                self.icc.filter(|_| args.colorspace == icc::ColorSpace::Rgb),
+                self.opts.dpi,
+            ),
+            output::Format::Ico => output::save_ico(image, out),
+            output::Format::Tiff => {
+                let dpi = args
+                    .print_size
+                    .map_or(72.0, |length| side as f64 / (length.0 / 72.0));
+                let inks = tif::Inks {
+                    dark: args.dark_ink,
+                    light: args.light_ink,
+                    fg: rendered.fg,
+                    quiet: self.opts.quiet_color,
+                };
+                // Colors given as RGB are sRGB; CMYK has no default profile.
+                let srgb = (args.colorspace == icc::ColorSpace::Rgb).then(icc::srgb);
+                let marked = match self.marks.is_empty() {
+                    true => Cow::Borrowed(image),
+                    false => Cow::Owned(marks::extend(image, self.marks, dpi / 72.0)),
+                };
+                tif::save(
+                    &marked,
+                    out,
+                    args.colorspace,
+                    &inks,
+                    dpi,
+                    self.icc.or(srgb.as_deref()),
+                )
+            }
+            output::Format::Eps => eps::save(
+                out,
+                &rendered.code,
+                self.opts.quiet,
+                args.print_size.unwrap_or(eps::Length(side as f64)),
+                self.marks,
+                rendered.fg,
+                self.opts.quiet_color,
+                self.payload,
+            ),
+            output::Format::Svg => {
+                let svg = svg::document(
+                    &rendered.code,
+                    self.opts.quiet,
+                    side,
+                    rendered.fg,
+                    self.opts.quiet_color,
+                    &alt_text_chunks(args.embed_alt, self.alt_text, self.payload),
+                );
+                std::fs::write(out, svg).with_context(|| format!("Failed to write SVG: {}", out))
+            }
+            output::Format::Pdf => {
+                // One page the size of the image, at --print-size if given.
+                let pt_per_px = args.print_size.map_or(1.0, |length| length.0 / side as f64);
+                let pdf = pdf::image_page(image, pt_per_px, self.marks, self.payload);
+                std::fs::write(out, pdf).with_context(|| format!("Failed to write PDF: {}", out))
+            }
+        }
+    }
+
+    /// Upload the file written to `out` with `--upload` and announce it to `--webhook`.
+    /// A webhook that fails is a warning; the file is already written.
+    fn publish(&self, out: &str) -> Result<()> {
+        let args = self.args;
+        let mut public_url = None;
+        if let Some(target) = &args.upload {
+            let path = Path::new(out);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let url = target.put_file(path, &name)?;
+            log::info(format!("Uploaded {}", url));
+            public_url = Some(url);
+        }
+        if let Some(hook) = &args.webhook {
+            let body = webhook::body(
+                Path::new(out),
+                self.payload,
+                &self.metadata(),
+                public_url.as_deref(),
+            );
+            if let Err(e) = body.and_then(|body| webhook::send(hook, &body)) {
+                log::warn(format!("{:#}", e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the first output to `printer`, at the `--print-size` of its code with the
+    /// marks around it.
+    fn print(&self, printer: &str) -> Result<()> {
+        let (args, image) = (self.args, &self.rendered.image);
+        let page = args.print_size.map(|length| {
+            let (_, _, side) = self.rendered.qr_rect;
+            let pt_per_px = length.0 / side as f64;
+            let margin = 2.0 * self.marks.margin();
+            print::Page {
+                width: image.width() as f64 * pt_per_px + margin,
+                height: image.height() as f64 * pt_per_px + margin,
+                // Vector outputs carry their own size.
+                ppi: match args.format {
+                    Some(output::Format::Eps | output::Format::Svg | output::Format::Pdf) => None,
+                    _ => Some(72.0 / pt_per_px),
+                },
+            }
+        });
+        let report = print::send(printer, args.copies, page, &args.out)?;
+        log::info(format!("Printed {} on {}: {}", args.out, printer, report));
+        Ok(())
+    }
+
+    /// Write the favicons and the guides proof claimed in `side_files`.
+    fn write_side_files(&self, side_files: &SideFiles) -> Result<()> {
+        let (rendered, image) = (self.rendered, &self.rendered.image);
+        for (size, path) in &side_files.favicons {
+            let icon = output::icon(image, *size);
+            save_image(
+                &icon,
+                path,
+                self.args.embed_alt,
+                self.alt_text,
+                self.payload,
+                &[],
+                self.icc,
+                None,
+            )?;
+            log::info(i18n::tr("wrote", &[("path", path.into())]));
+        }
+        if let Some(path) = &side_files.guides {
+            let (_, _, side) = rendered.qr_rect;
+            let quiet = self.opts.quiet;
+            let quiet_px = side / (rendered.code.width() as u32 + 2 * quiet) * quiet;
+            let img = marks::guides(image, rendered.qr_rect, quiet_px, rendered.logo_rect);
+            output::save_png_with_text(&img, path, &[], None, None)?;
+            log::info(i18n::tr("wrote", &[("path", path.into())]));
+        }
+        Ok(())
+    }
+
+    /// What follows the files: `--stress-test`, `--meta-out`, `--terminal` and
+    /// `--clipboard`.
+    fn report(&self) -> Result<()> {
+        let (args, rendered, image) = (self.args, self.rendered, &self.rendered.image);
+        if args.stress_test {
+            let (_, _, qr_size) = rendered.qr_rect;
+            let modules = rendered.code.width();
+            let module_px = qr_size / (modules as u32 + 2 * args.quiet);
+            // --print-size is the side of the code, quiet zone included.
+            let mm_per_px = args
+                .print_size
+                .or(args.size.printed(qr_size))
+                .map(|side| side.mm() / qr_size as f64);
+            match stress::run(image, self.payload, module_px as f32, modules, mm_per_px) {
+                Ok(report) => log::info(report),
+                Err(e) => log::warn(format!("stress test skipped: {:#}", e)),
+            }
+        }
+
+        if let Some(meta_path) = args.meta_out.as_deref() {
+            let coverage = rendered.logo_coverage(args.quiet);
+            Metadata::new(
+                &rendered.code,
+                self.payload,
+                image.width(),
+                image.height(),
+                coverage,
+            )
+            .with_dpi(self.opts.dpi)
+            .write(meta_path)?;
+            log::info(i18n::tr("wrote", &[("path", meta_path.into())]));
+        }
+
+        if args.terminal {
+            print!("{}", terminal::render(&rendered.code, args.quiet));
+        }
+        if args.clipboard {
+            output::copy_to_clipboard(image)?;
+            log::info("Copied the image to the clipboard");
+        }
+        Ok(())
+    }
+
+    /// The metadata `--dry-run` prints and a webhook reports for each file.
+    fn metadata(&self) -> Metadata {
+        let (rendered, image) = (self.rendered, &self.rendered.image);
+        Metadata::new(
+            &rendered.code,
+            self.payload,
+            image.width(),
+            image.height(),
+            rendered.logo_coverage(self.opts.quiet),
+        )
+        .with_dpi(self.opts.dpi)
+    }
+}
+
+/// What `--dry-run` reports: the file that would be written and its parameters.
+#[cfg(feature = "cli")]
+#[derive(Serialize)]
+struct DryRun<'a> {
+    output: &'a str,
+    /// Further files from more than one `--out`.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    also_output: &'a [&'a str],
+    #[serde(flatten)]
+    meta: Metadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logo_scale: Option<f32>,
+    contrast_pct: f32,
+    contrast_grade: char,
+}
+
+/// Print the dry-run report for `outs` on stdout, as JSON with `--log-format json`. `logo` is
+/// what the logo hides and the scale it was drawn at.
+#[cfg(feature = "cli")]
+fn print_dry_run(
+    outs: &[&str],
+    meta: Metadata,
+    code: &Symbol,
+    logo: Option<(&budget::Coverage, f32)>,
+    contrast: &contrast::Contrast,
+    format: LogFormat,
+) {
+    let (w, h) = (meta.width, meta.height);
+    let report = DryRun {
+        output: outs[0],
+        also_output: &outs[1..],
+        meta,
+        logo_scale: logo.map(|(_, scale)| scale),
+        contrast_pct: (contrast.symbol_contrast().max(0.0) * 1000.0).round() / 10.0,
+        contrast_grade: contrast.grade(),
+    };
+    if format == LogFormat::Json {
+        println!("{}", serde_json::to_string(&report).unwrap_or_default());
+        return;
+    }
+    println!("Would write {} ({}x{} px)", outs.join(", "), w, h);
+    println!("{}", code.describe());
+    match logo {
+        Some((budget, scale)) => println!("Logo scale {:.2}; {}", scale, budget.describe()),
+        None => println!("No logo"),
+    }
+    println!(
+        "Contrast {:.0}% (grade {})",
+        report.contrast_pct, report.contrast_grade
+    );
+}
+
+/// Determine the exact string to encode from the subcommand or `--url`.
+#[cfg(feature = "cli")]
+fn resolve_payload(args: &Args) -> Result<String> {
+    let none = |command: &str| {
+        Err(anyhow!(i18n::tr(
+            "no-payload",
+            &[("command", command.into())]
+        )))
+    };
+    match &args.command {
+        Some(Command::Totp(totp)) => totp.payload(),
+        Some(Command::Epc(epc)) => epc.payload(),
+        Some(Command::Event(event)) => event.payload(),
+        Some(Command::Email(email)) => email.payload(),
+        Some(Command::Sms(sms)) => sms.payload(),
+        Some(Command::Tel(tel)) => tel.payload(),
+        Some(Command::Geo(geo)) => geo.payload(),
+        Some(Command::Gs1(gs1)) => gs1.payload(),
+        Some(Command::Bitcoin(bitcoin)) => bitcoin.payload(),
+        Some(Command::Ethereum(ethereum)) => ethereum.payload(),
+        Some(Command::Label(label)) => label.payload(),
+        Some(Command::Barcode(_)) => none("barcode"),
+        Some(Command::Batch(_)) => none("batch"),
+        Some(Command::Diff(_)) => none("diff"),
+        Some(Command::Verify(_)) => none("verify"),
+        Some(Command::Series(_)) => none("series"),
+        Some(Command::Daemon(_)) => none("daemon"),
+        Some(Command::Serve(_)) => none("serve"),
+        Some(Command::Bench(_)) => none("bench"),
+        Some(Command::Animate(_)) => none("animate"),
+        Some(Command::Run(_)) => none("run"),
+        Some(Command::Completions(_)) => none("completions"),
+        Some(Command::Wizard) => none("wizard"),
+        Some(Command::ExitCodes) => none("exit-codes"),
+        // Cards and posters encode the --url like single mode.
+        None | Some(Command::Card(_) | Command::Poster(_)) => {
+            let mut parsed = if args.url_from_clipboard {
+                let text = output::clipboard_text()?;
+                Url::parse(&text).with_context(|| {
+                    let start: String = text.chars().take(60).collect();
+                    i18n::tr("clipboard-not-url", &[("start", start.into())])
+                })?
+            } else {
+                let url = args
+                    .url
+                    .as_deref()
+                    .with_context(|| i18n::tr("url-required", &[]))?;
+                // Validate URL (catches missing scheme; ensures https:// etc.)
+                Url::parse(url).with_context(|| i18n::tr("url-invalid", &[("url", url.into())]))?
+            };
+            payload::link::set_query_params(&mut parsed, &query_params(args));
+            match args.shorten {
+                Some(service) => shorten::shorten(
+                    service,
+                    parsed.as_str(),
+                    args.shorten_endpoint.as_deref(),
+                    args.shorten_token.as_deref(),
+                ),
+                None => Ok(parsed.to_string()),
+            }
+        }
+    }
+}
+
+/// Collect the UTM flags and `--param` pairs, UTM first, in command-line order.
+fn query_params(args: &Args) -> Vec<(String, String)> {
+    let utm = [
+        ("utm_source", &args.utm_source),
+        ("utm_medium", &args.utm_medium),
+        ("utm_campaign", &args.utm_campaign),
+    ];
+    utm.into_iter()
+        .filter_map(|(k, v)| v.as_ref().map(|v| (k.to_string(), v.clone())))
+        .chain(args.params.iter().cloned())
+        .collect()
+}
+
+/// Embedded caption font (DejaVuSans) so no OS font dependency.
+static FONT_BYTES: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+fn load_embedded_font() -> Result<FontFace> {
+    FontFace::from_vec(FONT_BYTES.to_vec(), 0).context("Failed to load embedded font")
+}
+
+/// Decoded inputs shared across renders, so batch jobs decode them only once.
+struct Assets {
+    font: Arc<FontChain>,
+    logo: Option<Arc<DynamicImage>>,
+    template: Option<Arc<raster::Canvas>>,
+}
+
+impl Assets {
+    fn load(
+        logo_path: Option<&str>,
+        font_family: Option<&str>,
+        template_path: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Self {
+            font: cache::font(font_family)?,
+            logo: logo_path.map(cache::logo).transpose()?,
+            template: template_path
+                .map(compose::load_template)
+                .transpose()?
+                .map(Arc::new),
+        })
+    }
+}
+
+fn load_texture(path: &str) -> Result<DynamicImage> {
+    image::open(path).with_context(|| format!("Failed to open texture image: {}", path))
+}
+
+fn load_logo(path: &str) -> Result<DynamicImage> {
+    image::open(path)
+        .with_context(|| format!("Failed to open logo image: {}", path))
+        .code(ErrorCode::LogoUnreadable)
+}
+
+/// Symbol and logo styling shared by single and batch renders.
+#[derive(Clone, Debug)]
+struct RenderOptions {
+    size: u32,
+    /// Resolution --size was given at, recorded in the output.
+    dpi: Option<f32>,
+    quiet: u32,
+    fg: Color,
+    auto_color: bool,
+    eyes: modules::Eyes,
+    eye_glyphs: Vec<String>,
+    eye_glyph_color: Option<Color>,
+    quiet_color: Color,
+    border: Option<decor::Border>,
+    corner_radius: u32,
+    circle_crop: bool,
+    shape: round::Shape,
+    shadow: Option<decor::Shadow>,
+    canvas_color: Color,
+    place: Option<compose::Placement>,
+    lower_third: Option<lowerthird::LowerThird>,
+    strict_contrast: bool,
+    encode: symbol::EncodeOptions,
+    logo_scale: budget::LogoScale,
+    logo_plate: bool,
+    logo_pad: f32,
+    logo_prep: logo::Prep,
+    logo_fit: budget::LogoFit,
+    ecc_margin: f32,
+    module_gap: f32,
+    module_style: modules::ModuleStyle,
+    invert: bool,
+    fill: Option<modules::Fill>,
+    orientation: orient::Orientation,
+    plate: PlateStyle,
+    caption: CaptionStyle,
+}
+
+/// Colors and spacing of the caption band.
+#[derive(Clone, Debug)]
+struct CaptionStyle {
+    text_color: Color,
+    band_color: Color,
+    padding: Option<u32>,
+}
+
+/// Appearance of the plate drawn behind the logo, and of the logo's ring and shadow.
+#[derive(Clone, Debug)]
+struct PlateStyle {
+    color: Color,
+    border_color: Color,
+    border_width: u32,
+    radius: u32,
+    shadow: Option<decor::Shadow>,
+    ring: Option<logo::Ring>,
+    logo_shadow: Option<decor::Shadow>,
+}
+
+impl RenderOptions {
+    fn from_args(args: &Args) -> Self {
+        #[cfg(feature = "cli")]
+        let fnc1 = matches!(&args.command, Some(Command::Gs1(gs1)) if gs1.fnc1());
+        // Library renders take a batch record, never the gs1 subcommand.
+        #[cfg(not(feature = "cli"))]
+        let fnc1 = false;
+        Self {
+            size: args.size.px,
+            dpi: args.size.dpi,
+            quiet: args.quiet,
+            fg: args.fg,
+            auto_color: args.auto_color,
+            eyes: modules::Eyes {
+                outer: args.eye_color,
+                inner: args.eye_inner_color,
+            },
+            eye_glyphs: args.eye_glyph.clone(),
+            eye_glyph_color: args.eye_glyph_color,
+            quiet_color: args.quiet_color,
+            border: args.border,
+            corner_radius: args.corner_radius,
+            circle_crop: args.circle_crop,
+            shape: args.shape,
+            shadow: args.shadow,
+            canvas_color: args.canvas_color,
+            place: args.place,
+            lower_third: (args.template == Some(compose::Template::LowerThird)).then_some(
+                lowerthird::LowerThird {
+                    frame: args.frame_size,
+                    anchor: args.anchor,
+                    bar: args.brand_bar,
+                },
+            ),
+            strict_contrast: args.strict_contrast,
+            encode: symbol::EncodeOptions {
+                ec_level: args.ec_level,
+                ec_boost: !args.no_ec_boost,
+                version: args.qr_version,
+                mask: args.mask,
+                fnc1,
+            },
+            logo_scale: args.logo_scale,
+            logo_plate: args.logo_plate,
+            logo_pad: args.logo_pad,
+            logo_prep: args.logo_prep.unwrap_or_default(),
+            logo_fit: args.logo_fit,
+            ecc_margin: args.ecc_margin,
+            module_gap: args.module_gap,
+            module_style: args.module_style,
+            invert: args.invert,
+            fill: args.fill_texture.clone(),
+            orientation: orient::Orientation {
+                rotate: args.rotate,
+                flip: args.flip,
+            },
+            plate: PlateStyle {
+                color: args.plate_color,
+                border_color: args.plate_border_color,
+                border_width: args.plate_border_width,
+                radius: args.plate_radius,
+                shadow: args.plate_shadow,
+                ring: args.logo_ring,
+                logo_shadow: args.logo_shadow,
+            },
+            caption: CaptionStyle {
+                text_color: args.text_color,
+                band_color: args.band_color,
+                padding: args.band_padding,
+            },
+        }
+    }
+}
+
+/// A fully composed QR image plus the symbol it was rendered from.
+struct Rendered {
+    code: Symbol,
+    image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    /// QR pixels hidden by the logo or plate.
+    covered_px: u32,
+    /// Bounds (x, y, w, h) of the logo or plate within the QR square.
+    logo_rect: Option<(u32, u32, u32, u32)>,
+    /// Codewords the logo or plate hides, by error correction block.
+    logo_budget: Option<budget::Coverage>,
+    /// Logo scale actually drawn; smaller than asked after --logo-fit shrink.
+    logo_scale: Option<f32>,
+    /// Color the dark modules were drawn in (from the logo with --auto-color), or the
+    /// ground with --invert.
+    fg: Color,
+    /// Top-left corner and side length of the QR square (quiet zone included) in `image`.
+    qr_rect: (u32, u32, u32),
+    /// Dark modules drawn as outlines (--module-style), so checks sample their average.
+    hollow: bool,
+    /// Dark modules drawn light on a dark ground (--invert).
+    inverted: bool,
+}
+
+impl Rendered {
+    /// The QR square alone, as [`verify::check_modules`] expects it (a view, not a copy).
+    fn qr_image(&self) -> SubImage<&raster::Canvas> {
+        let (x, y, size) = self.qr_rect;
+        imageops::crop_imm(&self.image, x, y, size, size)
+    }
+
+    /// Fraction of the symbol hidden by the logo or plate.
+    fn logo_coverage(&self, quiet_modules: u32) -> f32 {
+        logo_coverage(&self.code, quiet_modules, self.qr_rect.2, self.covered_px)
+    }
+
+    /// Note for a logo that --logo-fit shrink drew smaller than `asked`.
+    fn logo_shrink_warning(&self, asked: budget::LogoScale) -> Option<String> {
+        logo_shrink_warning(asked, self.logo_scale)
+    }
+
+    /// Warning for a payload dense enough to need a large print.
+    fn density_advice(&self, payload: &str, opts: &RenderOptions) -> Option<String> {
+        density::advice(&self.code, payload, &opts.encode, self.logo_scale.is_some())
+    }
+
+    /// The QR square with each module averaged, as a camera sees hollow modules.
+    fn averaged(&self, quiet_modules: u32) -> raster::Canvas {
+        let ppm = self.qr_rect.2 / (self.code.width() as u32 + 2 * quiet_modules);
+        modules::averaged(&*self.qr_image(), ppm)
+    }
+
+    /// Light/dark contrast of the modules left visible around the logo.
+    fn contrast(&self, quiet_modules: u32) -> contrast::Contrast {
+        let (code, skip, inverted) = (&self.code, self.logo_rect, self.inverted);
+        match self.hollow {
+            true => {
+                let averaged = self.averaged(quiet_modules);
+                contrast::measure(&averaged, code, quiet_modules, skip, inverted)
+            }
+            false => contrast::measure(&*self.qr_image(), code, quiet_modules, skip, inverted),
+        }
+    }
+
+    /// Sample every module and compare with the symbol.
+    fn check_modules(&self, quiet_modules: u32) -> verify::ModuleCheck {
+        let (code, inverted) = (&self.code, self.inverted);
+        match self.hollow {
+            true => {
+                let averaged = self.averaged(quiet_modules);
+                verify::check_modules(&averaged, code, quiet_modules, inverted)
+            }
+            false => verify::check_modules(&*self.qr_image(), code, quiet_modules, inverted),
+        }
+    }
+
+    /// Turn the finished image, and the code and logo rectangles with it. The module
+    /// checks sample the upright symbol, so run them first.
+    fn orient(&mut self, orientation: orient::Orientation) {
+        if !orientation.is_set() {
+            return;
+        }
+        let (w, h) = self.image.dimensions();
+        let (x, y, side) = self.qr_rect;
+        let (x, y, _, _) = orientation.rect((x, y, side, side), w, h);
+        self.qr_rect = (x, y, side);
+        self.logo_rect = self
+            .logo_rect
+            .map(|rect| orientation.rect(rect, side, side));
+        self.image = orientation.apply(&self.image);
+    }
+
+    /// Write `text` small in the bottom-right corner of the quiet zone in the caption
+    /// color, a module clear of the symbol and of the edge.
+    fn draw_label(&mut self, text: &str, opts: &RenderOptions, font: &FontChain) -> Result<()> {
+        let (x, y, side) = self.qr_rect;
+        let module = side as f32 / (self.code.width() as u32 + 2 * opts.quiet) as f32;
+        let room = (opts.quiet as f32 - 2.0) * module;
+        if room < MIN_LABEL_PX {
+            bail!(i18n::tr("label-no-room", &[("quiet", opts.quiet.into())]));
+        }
+        let mut scale = Scale::uniform(room);
+        let max_w = side as f32 - 2.0 * module;
+        let width = measure_text_width(font, scale, text);
+        if width > max_w {
+            scale = Scale::uniform(room * max_w / width);
+        }
+        let v_metrics = font.primary().v_metrics(scale);
+        let centre = (y + side) as f32 - module - room / 2.0;
+        let baseline = centre + (v_metrics.ascent + v_metrics.descent) / 2.0;
+        let start_x = (x + side) as f32 - module - measure_text_width(font, scale, text);
+        draw_text_rgba(
+            &mut self.image,
+            font,
+            scale,
+            start_x,
+            baseline,
+            text,
+            opts.caption.text_color.0,
+        );
+        Ok(())
+    }
+}
+
+/// Note for a logo that --logo-fit shrink drew at `drawn`, smaller than `asked`.
+fn logo_shrink_warning(asked: budget::LogoScale, drawn: Option<f32>) -> Option<String> {
+    let budget::LogoScale::Fixed(asked) = asked else {
+        return None;
+    };
+    drawn.filter(|&drawn| drawn < asked).map(|drawn| {
+        format!(
+            "logo shrunk from --logo-scale {:.2} to {:.2} to stay within the error correction budget",
+            asked, drawn
+        )
+    })
+}
+
+/// The payload as --show-url or --display-url renders it.
+fn display_url(args: &Args, payload: &str) -> String {
+    let url = match args.idn_display {
+        idn::IdnDisplay::Unicode => idn::unicode_url(payload),
+        idn::IdnDisplay::Punycode => payload.to_string(),
+    };
+    match &args.display_url {
+        Some(Some(text)) => text.clone(),
+        Some(None) => payload::link::short_url(&url),
+        None => url,
+    }
+}
+
+/// Text for the caption band: the payload with --show-url, otherwise the alt text.
+fn caption_text<'a>(
+    show_url: bool,
+    alt_text: Option<&'a str>,
+    payload: &'a str,
+) -> Option<&'a str> {
+    if show_url { Some(payload) } else { alt_text }
+}
+
+/// Render `payload` and, with --template, composite it into the design at --place.
+fn render_payload(
+    payload: &str,
+    caption: Option<&str>,
+    opts: &RenderOptions,
+    assets: &Assets,
+) -> Result<Rendered> {
+    if let Some(layout) = &opts.lower_third {
+        if opts.place.is_some() {
+            bail!(i18n::tr("place-lower-third", &[]));
+        }
+        return lowerthird::render(payload, caption, opts, assets, layout);
+    }
+    let (template, placement) = match (&assets.template, &opts.place) {
+        (Some(template), Some(placement)) => (template, placement),
+        (None, None) => return render_code(payload, caption, opts, assets),
+        (None, Some(_)) => bail!(i18n::tr("place-needs-template", &[])),
+        (Some(_), None) => bail!(i18n::tr("template-needs-place", &[])),
+    };
+
+    // Start at the box size and shrink until the caption and decorations fit too;
+    // re-rendering rather than resampling keeps the modules crisp.
+    let mut opts = opts.clone();
+    if let Some(size) = placement.target_size() {
+        opts.size = size;
+    }
+    let mut rendered = loop {
+        let rendered = render_code(payload, caption, &opts, assets)?;
+        let (w, h) = rendered.image.dimensions();
+        let scale = placement.fit_scale(w, h);
+        if scale >= 1.0 {
+            break rendered;
+        }
+        opts.size = ((opts.size as f32 * scale) as u32).min(opts.size - 1);
+    };
+
+    let (image, (x, y)) = compose::place(template, &rendered.image, placement)?;
+    let (qx, qy, size) = rendered.qr_rect;
+    rendered.image = image;
+    rendered.qr_rect = (x + qx, y + qy, size);
+    Ok(rendered)
+}
+
+/// Encode `payload`, then apply the logo overlay, caption band, crop, border and shadow.
+fn render_code(
+    payload: &str,
+    caption: Option<&str>,
+    opts: &RenderOptions,
+    assets: &Assets,
+) -> Result<Rendered> {
+    let code = symbol::encode(payload, &opts.encode)?;
+    let (logo, fg) = prepare_logo(assets.logo.as_deref(), opts)?;
+    let logo = logo.as_deref();
+
+    // With --invert the modules take the quiet zone color and --fg is the ground.
+    let (ink, ground) = match opts.invert {
+        true => (opts.quiet_color, fg),
+        false => (fg, Color::WHITE),
+    };
+    if opts.invert {
+        if opts.quiet < INVERTED_QUIET {
+            return Err(anyhow!(i18n::tr(
+                "invert-quiet",
+                &[("modules", INVERTED_QUIET.into())]
+            )))
+            .code(ErrorCode::Usage);
+        }
+        if opts.fill.is_some() {
+            return Err(anyhow!(i18n::tr("fill-invert", &[]))).code(ErrorCode::Usage);
+        }
+    }
+
+    // Render QR to RGBA image (square).
+    let mut qr_img = render_qr_on(&code, opts.size, opts.quiet, ink, ground)?;
+    let qr_size = qr_img.width();
+    let ppm = qr_size / (code.width() as u32 + 2 * opts.quiet);
+    let margin = ppm * opts.quiet;
+    let light = ground.0;
+    let hollow = opts.module_style.is_hollow();
+    if opts.module_gap > 0.0 {
+        modules::check_gap(opts.module_gap)?;
+    }
+    if hollow {
+        let (style, gap) = (opts.module_style, opts.module_gap);
+        modules::outline(&mut qr_img, &code, opts.quiet, ppm, style, gap, ink, light);
+    } else if opts.module_gap > 0.0
+        && modules::separate(&mut qr_img, &code, opts.quiet, ppm, opts.module_gap, light) == 0
+    {
+        log::warn(format!(
+            "--module-gap {} is under a pixel at {} px per module and has no effect",
+            opts.module_gap, ppm
+        ));
+    }
+    match &opts.fill {
+        Some(modules::Fill::Pattern(pattern)) => {
+            let paint = modules::Paint::Pattern(*pattern);
+            modules::fill_modules(&mut qr_img, margin, ppm, fg, &paint);
+        }
+        Some(modules::Fill::Image(path)) => {
+            let texture = cache::texture(&path.to_string_lossy())?.to_rgba8();
+            let paint = modules::Paint::Texture(&texture);
+            modules::fill_modules(&mut qr_img, margin, ppm, fg, &paint);
+        }
+        None => {}
+    }
+    if opts.eyes.is_set() {
+        modules::paint_eyes(&mut qr_img, &code, opts.quiet, ppm, &opts.eyes, ink);
+    }
+    if !opts.eye_glyphs.is_empty() {
+        glyph::check_count(opts.eye_glyphs.len())?;
+        let glyphs = opts
+            .eye_glyphs
+            .iter()
+            .map(|path| cache::glyph(path))
+            .collect::<Result<Vec<_>>>()?;
+        let color = opts.eye_glyph_color.unwrap_or(ground);
+        glyph::paint(&mut qr_img, &code, opts.quiet, ppm, &glyphs, color)?;
+    }
+    let quiet_color = match opts.invert {
+        true => ground,
+        false => opts.quiet_color,
+    };
+    if quiet_color != ground {
+        decor::paint_quiet_zone(&mut qr_img, margin, quiet_color);
+    }
+
+    // Optional logo overlay.
+    let mut covered_px = 0;
+    let mut logo_rect = None;
+    let mut logo_budget = None;
+    let mut logo_scale = None;
+    if let Some(logo) = logo {
+        let (placement, coverage, scale) = fit_logo(&code, qr_size, ppm, logo, opts)?;
+        let (covered, bounds) = overlay_logo_center(&mut qr_img, logo, &placement, &opts.plate);
+        covered_px = covered;
+        logo_rect = Some(bounds);
+        logo_budget = Some(coverage);
+        logo_scale = Some(scale);
+    }
+
+    // Thinner or hollow modules and a logo each cost the scanner margin; check they
+    // still read.
+    if hollow {
+        if decode::decode(&modules::averaged(&qr_img, ppm)).is_err() {
+            bail!(i18n::tr("outline-no-decode", &[]));
+        }
+    } else if opts.module_gap > 0.0 && decode::decode(&qr_img).is_err() {
+        bail!(i18n::tr(
+            "gap-no-decode",
+            &[("gap", opts.module_gap.to_string().into())]
+        ));
+    } else if !opts.eye_glyphs.is_empty() && decode::decode(&qr_img).is_err() {
+        bail!(i18n::tr("eye-glyph-no-decode", &[]));
+    }
+
+    // Optionally add text below QR by extending the canvas height.
+    // A round code sets its caption on a ring instead.
+    let round = opts.shape == round::Shape::Circle;
+    let mut image = match caption {
+        Some(text) if !round => add_url_text_below(&qr_img, text, &assets.font, &opts.caption)?,
+        _ => qr_img,
+    };
+
+    // Crop the composed image; the keyline then follows its outline.
+    let mut qr_rect = (0, 0, qr_size);
+    let mut outline_radius = 0.0;
+    if round {
+        let (rounded, (x, y)) = round::compose(
+            &image,
+            margin,
+            caption,
+            &assets.font,
+            &opts.caption,
+            quiet_color.0,
+        )?;
+        image = rounded;
+        qr_rect = (x, y, qr_size);
+        outline_radius = image.width() as f32 / 2.0;
+    } else if opts.circle_crop {
+        let symbol = (margin, margin, qr_size - 2 * margin);
+        let (cropped, (x, y)) = decor::circle_crop(&image, symbol, 2 * ppm, quiet_color);
+        image = cropped;
+        qr_rect = (x, y, qr_size);
+        outline_radius = image.width() as f32 / 2.0;
+    } else if opts.corner_radius > 0 {
+        let max = decor::max_corner_radius(margin);
+        if opts.corner_radius > max {
+            bail!(i18n::tr(
+                "corner-radius-too-big",
+                &[("radius", opts.corner_radius.into()), ("max", max.into())]
+            ));
+        }
+        decor::round_corners(&mut image, opts.corner_radius);
+        outline_radius = opts.corner_radius as f32;
+    }
+
+    if let Some(border) = opts.border {
+        image = decor::add_border(&image, border, outline_radius);
+        qr_rect = (qr_rect.0 + border.width, qr_rect.1 + border.width, qr_size);
+    }
+
+    if let Some(shadow) = &opts.shadow {
+        let (shadowed, margin) = decor::add_drop_shadow(&image, shadow, opts.canvas_color);
+        image = shadowed;
+        qr_rect = (qr_rect.0 + margin, qr_rect.1 + margin, qr_size);
+    }
+
+    Ok(Rendered {
+        code,
+        image,
+        covered_px,
+        logo_rect,
+        logo_budget,
+        logo_scale,
+        fg,
+        qr_rect,
+        hollow,
+        inverted: opts.invert,
+    })
+}
+
+/// The logo after --logo-prep, and the color to draw dark modules in (read from the
+/// logo with --auto-color).
+fn prepare_logo<'a>(
+    logo: Option<&'a DynamicImage>,
+    opts: &RenderOptions,
+) -> Result<(Option<Cow<'a, DynamicImage>>, Color)> {
+    // Clean up the logo first, since --auto-color reads the module color from it.
+    let logo = logo.map(|logo| match opts.logo_prep.is_noop() {
+        true => Cow::Borrowed(logo),
+        false => Cow::Owned(opts.logo_prep.apply(logo)),
+    });
+    let fg = match logo.as_deref() {
+        Some(logo) if opts.auto_color => {
+            logo::brand_color(logo, opts.quiet_color).unwrap_or(opts.fg)
+        }
+        None if opts.auto_color => bail!(i18n::tr("auto-color-needs-image", &[])),
+        _ => opts.fg,
+    };
+    Ok((logo, fg))
+}
+
+/// Place the logo on a `qr_size` square of `ppm` pixel modules at --logo-scale,
+/// shrinking it per --logo-fit until the codewords it hides fit the error correction
+/// budget. Returns the placement, what it hides and the scale drawn.
+fn fit_logo(
+    code: &Symbol,
+    qr_size: u32,
+    ppm: u32,
+    logo: &DynamicImage,
+    opts: &RenderOptions,
+) -> Result<(LogoPlacement, budget::Coverage, f32)> {
+    if !(0.0..1.0).contains(&opts.ecc_margin) {
+        bail!(i18n::tr("ecc-margin-range", &[]));
+    }
+    let (mut scale, fit) = match opts.logo_scale {
+        budget::LogoScale::Fixed(scale) => (scale, opts.logo_fit),
+        budget::LogoScale::Auto => (MAX_LOGO_SCALE, budget::LogoFit::Shrink),
+    };
+    loop {
+        let placement = LogoPlacement::new(
+            qr_size,
+            logo,
+            scale,
+            opts.logo_plate,
+            opts.logo_pad,
+            &opts.plate,
+        )?;
+        let coverage = budget::analyze(code, opts.quiet, ppm, &placement.hidden());
+        if coverage.fits(opts.ecc_margin) {
+            return Ok((placement, coverage, scale));
+        }
+        if fit == budget::LogoFit::Fail {
+            coverage.check(opts.ecc_margin)?;
+        }
+        if scale - LOGO_SCALE_STEP < MIN_LOGO_SCALE {
+            bail!(i18n::tr(
+                "logo-too-big",
+                &[
+                    ("scale", format!("{:.2}", scale).into()),
+                    ("coverage", coverage.describe().into())
+                ]
+            ));
+        }
+        scale -= LOGO_SCALE_STEP;
+    }
+}
+
+/// Write the final image, optionally embedding accessibility text chunks.
+/// `extra_text` chunks (e.g. the batch fingerprint) go with them; `dpi` goes in `pHYs`.
+/// A `.jpg` or `.gif` path is written as JPEG or GIF, without the text chunks.
+#[allow(clippy::too_many_arguments)]
+fn save_image(
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    path: &str,
+    embed_alt: bool,
+    alt_text: Option<&str>,
+    payload: &str,
+    extra_text: &[(&str, &str)],
+    icc: Option<&[u8]>,
+    dpi: Option<f32>,
+) -> Result<()> {
+    if let Some(format) = output::other_raster(path) {
+        return output::save_raster(img, path, format, icc, dpi);
+    }
+    let alt = alt_text_chunks(embed_alt, alt_text, payload);
+    let mut text: Vec<(&str, &str)> = alt.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    text.extend_from_slice(extra_text);
+    output::save_png_with_text(img, path, &text, icc, dpi)
+}
+
+/// The final image as PNG bytes, with the same text chunks [`save_image`] would embed.
+fn encode_png(
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    embed_alt: bool,
+    alt_text: Option<&str>,
+    payload: &str,
+) -> Result<Vec<u8>> {
+    let alt = alt_text_chunks(embed_alt, alt_text, payload);
+    let text: Vec<(&str, &str)> = alt.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let mut bytes = Vec::new();
+    output::write_png_with_text(img, &mut bytes, &text, None, None, "in-memory PNG")?;
+    Ok(bytes)
+}
+
+/// `--embed-alt` text chunks: the caption (or payload) as Title, the payload as Description.
+fn alt_text_chunks(
+    embed_alt: bool,
+    alt_text: Option<&str>,
+    payload: &str,
+) -> Vec<(&'static str, String)> {
+    if !embed_alt {
+        return Vec::new();
+    }
+    vec![
+        ("Title", alt_text.unwrap_or(payload).to_string()),
+        ("Description", format!("QR code encoding: {}", payload)),
+    ]
+}
+
+/// Render a QR code into an RGBA ImageBuffer of size (approximately) `size` x `size`,
+/// including a quiet zone of `quiet_modules` around the code.
+/// The output may be slightly smaller than `size` to keep modules crisp.
+fn render_qr_rgba(
+    code: &Symbol,
+    size: u32,
+    quiet_modules: u32,
+    fg: Color,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+    render_qr_on(code, size, quiet_modules, fg, Color::WHITE)
+}
+
+/// Like [`render_qr_rgba`], on a `bg` ground rather than white.
+fn render_qr_on(
+    code: &Symbol,
+    size: u32,
+    quiet_modules: u32,
+    fg: Color,
+    bg: Color,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+    let module_count = code.width() as u32;
+    let total_modules = module_count + 2 * quiet_modules;
+    let ppm = module_px(code, size, quiet_modules)?;
+
+    // Actual output size (may be slightly smaller than requested to preserve crisp modules).
+    let out_w = ppm * total_modules;
+    let out_h = out_w;
+
+    let mut img = ImageBuffer::from_pixel(out_w, out_h, bg.0);
+
+    // Draw modules. Every pixel row of a module row is the same, so draw the first
+    // (offset by the quiet zone) and copy it down.
+    for y in 0..module_count {
+        let top = (y + quiet_modules) * ppm;
+        for x in 0..module_count {
+            if matches!(code[(x as usize, y as usize)], qrcode::Color::Dark) {
+                let left = (x + quiet_modules) * ppm;
+                raster::fill_row(&mut img, top, left..left + ppm, fg.0);
+            }
+        }
+        let row_bytes = out_w as usize * 4;
+        let first = top as usize * row_bytes;
+        for py in 1..ppm as usize {
+            img.copy_within(first..first + row_bytes, first + py * row_bytes);
+        }
+    }
+
+    Ok(img)
+}
+
+/// Pixels per module for a code of `size` pixels, quiet zone included. Floored to an
+/// integer to keep modules crisp, so the code may come out slightly smaller.
+fn module_px(code: &Symbol, size: u32, quiet_modules: u32) -> Result<u32> {
+    let module_count = code.width() as u32;
+    if module_count == 0 {
+        bail!("QR module count is zero");
+    }
+
+    // Total modules including quiet zone border.
+    let total_modules = module_count + 2 * quiet_modules;
+    let ppm = size / total_modules;
+    if ppm < 2 {
+        return Err(anyhow!(i18n::tr(
+            "size-too-small",
+            &[
+                ("size", size.into()),
+                ("modules", total_modules.into()),
+                ("ppm", ppm.into())
+            ]
+        )))
+        .code(ErrorCode::SizeTooSmall);
+    }
+    Ok(ppm)
+}
+
+/// Smallest --logo-scale accepted, and the floor for --logo-fit shrink.
+const MIN_LOGO_SCALE: f32 = 0.05;
+/// Largest --logo-scale accepted, where --logo-scale auto starts.
+const MAX_LOGO_SCALE: f32 = 0.35;
+/// How much --logo-fit shrink and --logo-scale auto take off per attempt.
+const LOGO_SCALE_STEP: f32 = 0.01;
+/// Smallest type, in pixels, a corner label is drawn at.
+const MIN_LABEL_PX: f32 = 8.0;
+/// Quiet zone, in modules, an inverted code is given at least: without the full
+/// dark frame many scanners never find its light finder patterns.
+const INVERTED_QUIET: u32 = 4;
+
+/// Where the logo and its plate go on the (square) QR image.
+struct LogoPlacement {
+    /// Logo size once fitted into `logo_scale` of the QR width, aspect preserved.
+    w: u32,
+    h: u32,
+    x: u32,
+    y: u32,
+    /// The plate behind the logo, with --logo-plate.
+    plate: Option<raster::RoundedRect>,
+    /// The stroke around the logo, with --logo-ring.
+    ring: Option<raster::Band>,
+}
+
+impl LogoPlacement {
+    fn new(
+        qr_w: u32,
+        logo: &DynamicImage,
+        logo_scale: f32,
+        logo_plate: bool,
+        logo_pad: f32,
+        plate: &PlateStyle,
+    ) -> Result<Self> {
+        if !(MIN_LOGO_SCALE..=MAX_LOGO_SCALE).contains(&logo_scale) {
+            bail!(i18n::tr("logo-scale-range", &[]));
+        }
+
+        let target = (qr_w as f32 * logo_scale).round() as u32;
+        let (w, h) = fit_size(logo.dimensions(), target, target);
+        let x = (qr_w - w) / 2;
+        let y = (qr_w - h) / 2;
+
+        let logo_box = raster::RoundedRect::new(x as f32, y as f32, w as f32, h as f32, 0.0);
+        let ring = plate.ring.map(|ring| ring.around(&logo_box));
+        let plate = logo_plate.then(|| {
+            let pad_px = ((w.max(h) as f32) * logo_pad).round() as u32;
+            let plate_w = (w + 2 * pad_px).min(qr_w);
+            let plate_h = (h + 2 * pad_px).min(qr_w);
+            raster::RoundedRect::new(
+                ((qr_w - plate_w) / 2) as f32,
+                ((qr_w - plate_h) / 2) as f32,
+                plate_w as f32,
+                plate_h as f32,
+                plate.radius as f32,
+            )
+        });
+        Ok(Self {
+            w,
+            h,
+            x,
+            y,
+            plate,
+            ring,
+        })
+    }
+
+    /// The area scanners lose: the plate, or the logo's box without one, grown to take
+    /// in the ring when it reaches further.
+    fn hidden(&self) -> raster::RoundedRect {
+        let base = self.plate.unwrap_or_else(|| {
+            raster::RoundedRect::new(
+                self.x as f32,
+                self.y as f32,
+                self.w as f32,
+                self.h as f32,
+                0.0,
+            )
+        });
+        match self.ring.map(|band| band.outer) {
+            Some(ring) if ring.encloses(&base) => ring,
+            Some(ring) if !base.encloses(&ring) => {
+                let (x0, y0, x1, y1) = base.bounds();
+                let (rx0, ry0, rx1, ry1) = ring.bounds();
+                let (x0, y0) = (x0.min(rx0), y0.min(ry0));
+                raster::RoundedRect::new(x0, y0, x1.max(rx1) - x0, y1.max(ry1) - y0, 0.0)
+            }
+            _ => base,
+        }
+    }
+
+    /// QR pixels hidden: the plate's drawn area (given) or the logo's box, or what the
+    /// ring hides if that is more.
+    fn covered_px(&self, plate_area: f32) -> u32 {
+        let covered = match self.plate {
+            Some(_) => plate_area.round() as u32,
+            None => self.w * self.h,
+        };
+        match self.ring {
+            Some(_) => covered.max(self.hidden().area().round() as u32),
+            None => covered,
+        }
+    }
+
+    /// Bounds (x, y, w, h) of what [`overlay_logo_center`] draws: the ring's extent
+    /// with one, else the plate, else the logo.
+    fn bounds(&self) -> (u32, u32, u32, u32) {
+        if self.ring.is_some() {
+            let (x0, y0, x1, y1) = self.hidden().bounds();
+            let (x0, y0) = (x0.max(0.0), y0.max(0.0));
+            return (
+                x0 as u32,
+                y0 as u32,
+                (x1.ceil() - x0) as u32,
+                (y1.ceil() - y0) as u32,
+            );
+        }
+        match self.plate {
+            Some(plate) => (
+                plate.x as u32,
+                plate.y as u32,
+                plate.w as u32,
+                plate.h as u32,
+            ),
+            None => (self.x, self.y, self.w, self.h),
+        }
+    }
+}
+
+/// Draw the logo (and its plate, shadow and ring, if any) at `placement`.
+/// Returns the number of QR pixels hidden by the logo (or plate, if drawn) and
+/// the bounds (x, y, w, h) of that area.
+fn overlay_logo_center(
+    qr_img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    logo: &DynamicImage,
+    placement: &LogoPlacement,
+    plate: &PlateStyle,
+) -> (u32, (u32, u32, u32, u32)) {
+    let resized = resize_fit(logo, placement.w, placement.h);
+    let (x0, y0) = (placement.x, placement.y);
+
+    if let (Some(shape), Some(shadow)) = (placement.plate, &plate.shadow) {
+        let mut silhouette = ImageBuffer::new(qr_img.width(), qr_img.height());
+        raster::fill(&mut silhouette, &shape, Color::BLACK.0);
+        shadow.cast(qr_img, &silhouette);
+    }
+    let plate_area = draw_plate(qr_img, placement, plate, 0);
+
+    // Composite logo onto QR (alpha-aware), over its own shadow.
+    let logo = resized.to_rgba8();
+    if let Some(shadow) = &plate.logo_shadow {
+        let mut silhouette = ImageBuffer::new(qr_img.width(), qr_img.height());
+        imageops::overlay(&mut silhouette, &logo, x0.into(), y0.into());
+        shadow.cast(qr_img, &silhouette);
+    }
+    imageops::overlay(qr_img, &logo, x0.into(), y0.into());
+    (placement.covered_px(plate_area), placement.bounds())
+}
+
+/// Draw the plate (with its border) and the ring at `placement` into `img`, which
+/// holds the rows from `top` down of the QR square. Returns the plate's area drawn.
+fn draw_plate(
+    img: &mut raster::Canvas,
+    placement: &LogoPlacement,
+    plate: &PlateStyle,
+    top: u32,
+) -> f32 {
+    let dy = top as f32;
+    let mut area = 0.0;
+
+    // Optional plate behind logo, with an optional border drawn inside its edge.
+    if let Some(shape) = placement.plate {
+        let (plate_w, plate_h) = (shape.w as u32, shape.h as u32);
+        let border = plate.border_width.min(plate_w / 2).min(plate_h / 2);
+        let outer = if border > 0 {
+            plate.border_color
+        } else {
+            plate.color
+        };
+        area = raster::fill(img, &raster::Shifted::new(shape, dy), outer.0);
+        if border > 0 {
+            let inner = shape.inset(border as f32);
+            raster::fill(img, &raster::Shifted::new(inner, dy), plate.color.0);
+        }
+    }
+
+    if let (Some(band), Some(ring)) = (placement.ring, plate.ring) {
+        raster::fill(img, &raster::Shifted::new(band, dy), ring.color.0);
+    }
+    area
+}
+
+/// Fraction (0..1) of the symbol area, excluding the quiet zone, covered by `covered_px` pixels.
+fn logo_coverage(code: &Symbol, quiet_modules: u32, qr_w: u32, covered_px: u32) -> f32 {
+    let module_count = code.width() as u32;
+    let ppm = qr_w / (module_count + 2 * quiet_modules);
+    let symbol_px = (module_count * ppm) as f32;
+    if symbol_px == 0.0 {
+        return 0.0;
+    }
+    (covered_px as f32 / (symbol_px * symbol_px)).min(1.0)
+}
+
+/// Resize while preserving aspect ratio to fit within (max_w, max_h).
+fn resize_fit(img: &DynamicImage, max_w: u32, max_h: u32) -> DynamicImage {
+    let (w, h) = img.dimensions();
+    if w == 0 || h == 0 {
+        return img.clone();
+    }
+    let (new_w, new_h) = fit_size((w, h), max_w, max_h);
+    img.resize(new_w, new_h, imageops::FilterType::Lanczos3)
+}
+
+/// Size of a `w`x`h` image shrunk (never enlarged) to fit within (max_w, max_h).
+fn fit_size((w, h): (u32, u32), max_w: u32, max_h: u32) -> (u32, u32) {
+    if w == 0 || h == 0 {
+        return (w, h);
+    }
+    let scale_w = max_w as f32 / w as f32;
+    let scale_h = max_h as f32 / h as f32;
+    let scale = scale_w.min(scale_h).min(1.0);
+
+    let new_w = (w as f32 * scale).round().max(1.0) as u32;
+    let new_h = (h as f32 * scale).round().max(1.0) as u32;
+    (new_w, new_h)
+}
+
+/// Add a band below the QR code and render the URL as text.
+fn add_url_text_below(
+    qr_img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    url_text: &str,
+    font: &FontChain,
+    style: &CaptionStyle,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+    let qr_w = qr_img.width();
+    let qr_h = qr_img.height();
+
+    // Band height heuristics: enough for one line of text with padding. Captions in
+    // several languages come one per line and share one font size.
+    let lines: Vec<&str> = url_text.lines().collect();
+    let line_count = lines.len().max(1) as u32;
+    let mut band_h = (qr_h as f32 * 0.18).round().max(120.0) as u32;
+
+    // Determine font size so the URL fits within width with margins.
+    let margin_x = match style.padding {
+        Some(pad) => pad.min(qr_w / 4),
+        None => (qr_w as f32 * 0.06).round().max(24.0) as u32,
+    };
+    let max_text_w = qr_w.saturating_sub(2 * margin_x);
+
+    // Start from a reasonable size and shrink until it fits.
+    let mut font_px = (band_h as f32 * 0.35).round().max(18.0);
+    let min_font_px = 14.0;
+
+    loop {
+        let scale = Scale::uniform(font_px);
+        let text_w = lines
+            .iter()
+            .map(|line| measure_text_width(font, scale, line))
+            .fold(0.0, f32::max);
+
+        if text_w <= max_text_w as f32 || font_px <= min_font_px {
+            break;
+        }
+        font_px *= 0.92;
+    }
+
+    let scale = Scale::uniform(font_px);
+    let v_metrics = font.primary().v_metrics(scale);
+
+    let text_h = (v_metrics.ascent - v_metrics.descent).ceil();
+    let line_h = (text_h + v_metrics.line_gap.max(text_h * 0.2)).ceil();
+    let block_h = text_h + line_h * (line_count - 1) as f32;
+    match style.padding {
+        Some(pad) => band_h = block_h as u32 + 2 * pad,
+        None => band_h += line_h as u32 * (line_count - 1),
+    }
+
+    let mut out = ImageBuffer::from_pixel(qr_w, qr_h + band_h, style.band_color.0);
+
+    // Copy QR into top.
+    raster::overlay(&mut out, qr_img, 0, 0);
+
+    // Baseline positioning: vertically centered in the band; several lines are
+    // centered as a block.
+    let band_y0 = qr_h;
+    let y_center = band_y0 as f32 + (band_h as f32 / 2.0);
+    let first_baseline = match line_count {
+        1 => y_center + (text_h / 2.0) - v_metrics.descent,
+        _ => y_center - (block_h / 2.0) + v_metrics.ascent,
+    };
+
+    for (i, line) in lines.iter().enumerate() {
+        // Horizontally centered.
+        let text_w = measure_text_width(font, scale, line);
+        let start_x = ((qr_w as f32 - text_w) / 2.0).max(margin_x as f32);
+
+        draw_text_rgba(
+            &mut out,
+            font,
+            scale,
+            start_x,
+            first_baseline + line_h * i as f32,
+            line,
+            style.text_color.0,
+        );
+    }
+
+    Ok(out)
+}
+
+/// Measure the width of a string in pixels for a given font/scale.
+fn measure_text_width(font: &FontChain, scale: Scale, text: &str) -> f32 {
+    text::layout(font, text, scale).width
+}
+
+/// Draw a single line of text into an RGBA image buffer.
+/// The text is shaped and bidi-reordered, so RTL and complex scripts render correctly.
+fn draw_text_rgba(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    font: &FontChain,
+    scale: Scale,
+    start_x: f32,
+    baseline_y: f32,
+    text: &str,
+    color: Rgba<u8>,
+) {
+    for shaped in text::layout(font, text, scale).glyphs {
+        let face = font.face(shaped.face);
+        let glyph = face.font.glyph(GlyphId(shaped.id)).scaled(scale);
+        let positioned = glyph.positioned(point(start_x + shaped.x, baseline_y - shaped.y));
+
+        if let Some(bb) = positioned.pixel_bounding_box() {
+            positioned.draw(|gx, gy, v| {
+                let px = bb.min.x + gx as i32;
+                let py = bb.min.y + gy as i32;
+                if px >= 0 && py >= 0 {
+                    let (pxu, pyu) = (px as u32, py as u32);
+                    if pxu < img.width() && pyu < img.height() {
+                        let dst = img.get_pixel(pxu, pyu);
+                        let a = (v * 255.0) as u8;
+                        let blended = blend_over(*dst, color, a);
+                        img.put_pixel(pxu, pyu, blended);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Blend src color over dst with alpha coverage `a` (0..255), honoring src and dst alpha.
+fn blend_over(dst: Rgba<u8>, src: Rgba<u8>, a: u8) -> Rgba<u8> {
+    raster::composite(dst, src, a as f32 / 255.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgba};
+
+    #[test]
+    fn test_blend_over() {
+        let white = Rgba([255, 255, 255, 255]);
+        let black = Rgba([0, 0, 0, 255]);
+        let red = Rgba([255, 0, 0, 255]);
+
+        // Test blending black over white with full opacity
+        let result = blend_over(white, black, 255);
+        assert_eq!(result, black);
+
+        // Test blending red over white with half opacity
+        let result = blend_over(white, red, 128);
+        assert_eq!(result[0], 255); // Red component
+        assert!(result[1] < 255); // Green component reduced
+        assert!(result[2] < 255); // Blue component reduced
+    }
+
+    #[test]
+    fn test_measure_text_width() {
+        static FONT_BYTES: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+        let font = FontChain::new(
+            FontFace::from_vec(FONT_BYTES.to_vec(), 0).expect("Failed to load font"),
+        );
+        let scale = Scale::uniform(20.0);
+
+        let width_a = measure_text_width(&font, scale, "A");
+        let width_aa = measure_text_width(&font, scale, "AA");
+
+        assert!(width_aa > width_a);
+        assert!(width_a > 0.0);
+    }
+
+    #[test]
+    fn test_resize_fit() {
+        // Create a small test image
+        let img = ImageBuffer::from_fn(100, 200, |x, y| Rgba([x as u8, y as u8, 128, 255]));
+        let dynamic_img = image::DynamicImage::ImageRgba8(img);
+
+        // Test fitting within larger bounds (should not resize)
+        let resized = resize_fit(&dynamic_img, 200, 400);
+        assert_eq!(resized.width(), 100);
+        assert_eq!(resized.height(), 200);
+
+        // Test fitting within smaller bounds (should resize)
+        let resized = resize_fit(&dynamic_img, 50, 50);
+        assert!(resized.width() <= 50);
+        assert!(resized.height() <= 50);
+        assert!(resized.width() > 0);
+        assert!(resized.height() > 0);
+    }
+
+    #[test]
+    fn test_url_validation() {
+        // This is an integration test that would be run separately
+        // For now, just verify the URL parsing logic would work
+        use url::Url;
+
+        let valid_url = "https://example.com";
+        let parsed = Url::parse(valid_url);
+        assert!(parsed.is_ok());
+
+        let invalid_url = "not-a-url";
+        let parsed = Url::parse(invalid_url);
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn test_alt_text_feature() {
+        // Test that add_url_text_below works with arbitrary text
+        let img = ImageBuffer::from_pixel(100, 100, Rgba([255, 255, 255, 255]));
+        let test_text = "Test Alt Text";
+
+        // This should not panic
+        let font = FontChain::new(load_embedded_font().expect("Failed to load font"));
+        let result = add_url_text_below(&img, test_text, &font, &default_caption());
+        assert!(result.is_ok());
+
+        let extended_img = result.unwrap();
+        assert!(extended_img.height() > img.height());
+        assert_eq!(extended_img.width(), img.width());
+
+        // A caption per language stacks, each line on its own row of the band.
+        let stacked = add_url_text_below(&img, "Menu\nCarte", &font, &default_caption()).unwrap();
+        assert!(stacked.height() > extended_img.height());
+    }
+
+    fn default_caption() -> CaptionStyle {
+        CaptionStyle {
+            text_color: Color::BLACK,
+            band_color: Color::WHITE,
+            padding: None,
+        }
+    }
+
+    #[test]
+    fn test_caption_band_style() {
+        let img = ImageBuffer::from_pixel(300, 300, Rgba([255, 255, 255, 255]));
+        let font = FontChain::new(load_embedded_font().expect("Failed to load font"));
+        let style = CaptionStyle {
+            text_color: Color::WHITE,
+            band_color: Color(Rgba([20, 20, 30, 255])),
+            padding: Some(10),
+        };
+
+        let out = add_url_text_below(&img, "Dark mode", &font, &style).unwrap();
+        let default = add_url_text_below(&img, "Dark mode", &font, &default_caption()).unwrap();
+        assert!(
+            out.height() < default.height(),
+            "padding sizes the band to the text"
+        );
+        assert_eq!(*out.get_pixel(0, out.height() - 1), Rgba([20, 20, 30, 255]));
+        let band_has_text = (300..out.height())
+            .any(|y| (0..300).any(|x| *out.get_pixel(x, y) == Rgba([255, 255, 255, 255])));
+        assert!(band_has_text, "light text is drawn on the dark band");
+    }
+}
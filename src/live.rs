@@ -0,0 +1,273 @@
+//! `qrbrand run job.yaml --watch`: render a job again each time it, or a file it
+//! names, changes, so a code shown in OBS or a viewer can be tweaked in place.
+//!
+//! Each output is written to a hidden file beside it and renamed over it, so a
+//! reader polling the path never sees half an image. With `--live` the newest PNG is
+//! also served over HTTP: `/latest.png` for an OBS image or media source, and `/` for
+//! a browser source, a page that swaps in the new image once it has loaded. A render
+//! that fails is reported and the last good image stays up.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::{cache, env, job, log};
+use anyhow::{Context, Result, anyhow};
+use clap::{CommandFactory, FromArgMatches};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// How often the watched files are checked.
+const POLL: Duration = Duration::from_millis(250);
+
+/// The page for a browser source: the image, reloaded every second and swapped in
+/// only once it has arrived, on a transparent background.
+const PAGE: &str = r#"<!doctype html>
+<html><head><meta charset="utf-8"><title>qrbrand live</title>
+<style>html,body{margin:0;background:transparent}img{display:block;max-width:100vw;max-height:100vh}</style>
+</head><body><img id="code" src="/latest.png" alt="">
+<script>
+setInterval(() => {
+  const next = new Image();
+  next.onload = () => { document.getElementById("code").src = next.src; };
+  next.src = "/latest.png?t=" + Date.now();
+}, 1000);
+</script></body></html>
+"#;
+
+/// The newest PNG written, shared with the HTTP thread.
+type Latest = Arc<Mutex<Option<Vec<u8>>>>;
+/// Each output's hidden path, and the path it replaces.
+type Staged = Vec<(PathBuf, PathBuf)>;
+
+/// Watch the job at `path` until interrupted, serving the newest PNG on `live`.
+pub fn run(path: &Path, live: Option<&str>) -> Result<()> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read job file {}", path.display()))?;
+    // Paths in the job are relative to the file, as for a single run.
+    if let Some(dir) = path.parent() {
+        std::env::set_current_dir(dir)
+            .with_context(|| format!("Failed to enter {}", dir.display()))?;
+    }
+    let latest = Latest::default();
+    if let Some(addr) = live {
+        let listener =
+            TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+        log::info(format!("Serving the latest image on http://{}/", addr));
+        let latest = Arc::clone(&latest);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let latest = Arc::clone(&latest);
+                std::thread::spawn(move || answer(stream, &latest));
+            }
+        });
+    }
+
+    log::info(format!("Watching {}; Ctrl-C stops", path.display()));
+    let mut seen = None;
+    let mut watched = vec![path.clone()];
+    loop {
+        if seen.as_ref() != Some(&stamps(&watched)) {
+            match job::load(&path) {
+                Ok(job) => {
+                    match render(&job, live.is_some()) {
+                        Ok(Some(png)) => *cache::lock(&latest) = Some(png),
+                        Ok(None) => {}
+                        Err(e) => log::warn(format!("{:#}", e)),
+                    }
+                    watched = std::iter::once(path.clone())
+                        .chain(named_files(&job))
+                        .collect();
+                }
+                Err(e) => log::warn(format!("{:#}", e)),
+            }
+            // Taken after the render, so the outputs just written do not start another.
+            seen = Some(stamps(&watched));
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+/// Render `job` once, replacing each output whole. Returns the first PNG written
+/// when `keep_png` asks for it.
+fn render(job: &Value, keep_png: bool) -> Result<Option<Vec<u8>>> {
+    let argv = job::argv(job).code(ErrorCode::Usage)?;
+    let (argv, staged) = stage(argv)?;
+    let png = staged
+        .iter()
+        .find(|(_, out)| extension(out).eq_ignore_ascii_case("png"));
+    if keep_png && png.is_none() {
+        return Err(anyhow!("--live serves a PNG; list one in `outputs`")).code(ErrorCode::Usage);
+    }
+    let matches = env::layer(crate::Args::command())
+        .try_get_matches_from(std::iter::once("qrbrand".to_string()).chain(argv))?;
+    let result = crate::execute(crate::Args::from_arg_matches(&matches)?, &matches);
+    if let Err(e) = result {
+        for (part, _) in &staged {
+            let _ = std::fs::remove_file(part);
+        }
+        return Err(e);
+    }
+    for (part, out) in &staged {
+        std::fs::rename(part, out)
+            .with_context(|| format!("Failed to replace {}", out.display()))?;
+        log::info(format!("Updated {}", out.display()));
+    }
+    match png.filter(|_| keep_png) {
+        Some((_, out)) => std::fs::read(out)
+            .map(Some)
+            .with_context(|| format!("Failed to read {}", out.display())),
+        None => Ok(None),
+    }
+}
+
+/// `argv` with each `--out` pointed at a hidden file in the same directory (same
+/// extension, so the format is unchanged), and the (hidden, final) path pairs.
+fn stage(argv: Vec<String>) -> Result<(Vec<String>, Staged)> {
+    let mut staged = Vec::new();
+    let mut rewritten = Vec::with_capacity(argv.len());
+    for arg in argv {
+        if arg == "--no-clobber" || arg == "--unique" {
+            return Err(anyhow!(
+                "a watched job replaces its outputs; drop `{}`",
+                &arg[2..]
+            ))
+            .code(ErrorCode::Usage);
+        }
+        match arg.strip_prefix("--out=") {
+            Some(out) => {
+                let out = PathBuf::from(out);
+                let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+                let part = out.with_file_name(format!(".{}.part.{}", stem, extension(&out)));
+                rewritten.push(format!("--out={}", part.display()));
+                staged.push((part, out));
+            }
+            None => rewritten.push(arg),
+        }
+    }
+    if staged.is_empty() {
+        return Err(anyhow!(
+            "a watched job needs `outputs`, the files to keep current"
+        ))
+        .code(ErrorCode::Usage);
+    }
+    Ok((rewritten, staged))
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map_or("png".into(), |ext| ext.to_string_lossy().into_owned())
+}
+
+/// Every string in `job` that names an existing file: logos, templates, fonts.
+fn named_files(job: &Value) -> Vec<PathBuf> {
+    match job {
+        Value::String(s) => Some(PathBuf::from(s))
+            .filter(|path| path.is_file())
+            .into_iter()
+            .collect(),
+        Value::Array(items) => items.iter().flat_map(named_files).collect(),
+        Value::Object(table) => table.values().flat_map(named_files).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn stamps(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Answer one request on `stream` from the newest image.
+fn answer(stream: TcpStream, latest: &Mutex<Option<Vec<u8>>>) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+    // The headers say nothing this server needs.
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 0) && header.trim() != "" {
+        header.clear();
+    }
+    let target = line.split_whitespace().nth(1).unwrap_or("/");
+    let (status, kind, body) = respond(target, cache::lock(latest).as_deref());
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        kind,
+        body.len()
+    );
+    let mut stream = &stream;
+    let _ = stream
+        .write_all(head.as_bytes())
+        .and_then(|()| stream.write_all(&body));
+}
+
+/// Status line, content type and body for `target`.
+fn respond(target: &str, png: Option<&[u8]>) -> (&'static str, &'static str, Vec<u8>) {
+    let path = target.split('?').next().unwrap_or_default();
+    match (path, png) {
+        ("/", _) => ("200 OK", "text/html; charset=utf-8", PAGE.into()),
+        ("/latest.png", Some(png)) => ("200 OK", "image/png", png.to_vec()),
+        ("/latest.png", None) => (
+            "503 Service Unavailable",
+            "text/plain",
+            b"no image yet\n".to_vec(),
+        ),
+        _ => ("404 Not Found", "text/plain", b"not found\n".to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage() {
+        let argv = [
+            "--url=https://example.com",
+            "--out=build/menu.png",
+            "--out=menu.tiff",
+        ];
+        let (argv, staged) = stage(argv.map(String::from).to_vec()).unwrap();
+        assert_eq!(
+            argv,
+            [
+                "--url=https://example.com",
+                "--out=build/.menu.part.png",
+                "--out=.menu.part.tiff"
+            ]
+        );
+        assert_eq!(staged[1], (".menu.part.tiff".into(), "menu.tiff".into()));
+        assert_eq!(
+            crate::output::Format::of_path(&staged[1].0).unwrap(),
+            crate::output::Format::Tiff
+        );
+
+        let err = |argv: &[&str]| {
+            let argv = argv.iter().map(|a| a.to_string()).collect();
+            stage(argv).unwrap_err().to_string()
+        };
+        assert_eq!(
+            err(&["--url=x"]),
+            "a watched job needs `outputs`, the files to keep current"
+        );
+        assert_eq!(
+            err(&["--out=a.png", "--no-clobber"]),
+            "a watched job replaces its outputs; drop `no-clobber`"
+        );
+    }
+
+    #[test]
+    fn test_respond() {
+        assert_eq!(respond("/latest.png?t=1", Some(b"png")).2, b"png");
+        assert_eq!(respond("/latest.png", None).0, "503 Service Unavailable");
+        assert!(respond("/", None).1.starts_with("text/html"));
+        assert_eq!(respond("/other", None).0, "404 Not Found");
+    }
+}
@@ -0,0 +1,123 @@
+//! Diagnostics on stderr: plain text by default, one JSON object per line with
+//! `--log-format json`.
+//!
+//! JSON lines carry `time` (RFC 3339, UTC), `level` (`info`, `warning` or `error`) and
+//! `message`, plus `row` for batch rows and `code`, `exit_code` and `retryable` for
+//! errors (see [`crate::error`]). Text lines are in the user's language (see
+//! [`crate::i18n`]); JSON lines are always English.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::i18n;
+use clap::ValueEnum;
+use serde_json::{Map, Value, json};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+/// Warnings given so far, for `--warnings-as-errors`.
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+static WARNINGS_AS_ERRORS: AtomicBool = AtomicBool::new(false);
+
+pub fn init(format: LogFormat) {
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+}
+
+/// Progress and results, e.g. "Wrote qrcode.png".
+pub fn info(message: impl std::fmt::Display) {
+    emit("info", None, &message.to_string(), Map::new());
+}
+
+/// `--warnings-as-errors`: [`warnings_failure`] fails the run once anything warned.
+/// Set by any run that asks for it, a job file's included.
+pub fn fail_on_warnings() {
+    WARNINGS_AS_ERRORS.store(true, Ordering::Relaxed);
+}
+
+/// With `--warnings-as-errors`, the error the warnings given so far amount to. Files
+/// already written are kept; the exit status is what tells automation.
+pub fn warnings_failure() -> anyhow::Result<()> {
+    let warnings = WARNINGS.load(Ordering::Relaxed);
+    if !WARNINGS_AS_ERRORS.load(Ordering::Relaxed) || warnings == 0 {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "{} {} with --warnings-as-errors",
+        warnings,
+        if warnings == 1 { "warning" } else { "warnings" }
+    ))
+    .code(ErrorCode::Warnings)
+}
+
+pub fn warn(message: impl std::fmt::Display) {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    emit("warning", None, &message.to_string(), Map::new());
+}
+
+pub fn row_warn(row: usize, message: impl std::fmt::Display) {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    emit("warning", Some(row), &message.to_string(), Map::new());
+}
+
+/// A failed batch row; `message` is the row's error with its causes.
+pub fn row_error(row: usize, code: ErrorCode, message: &str) {
+    emit("error", Some(row), message, error_fields(code));
+}
+
+/// The error that ends the run.
+pub fn error(err: &anyhow::Error) {
+    if JSON.load(Ordering::Relaxed) {
+        emit(
+            "error",
+            None,
+            &format!("{:#}", err),
+            error_fields(ErrorCode::of(err)),
+        );
+    } else {
+        // As Rust prints an error returned from main, causes and backtrace included.
+        eprintln!("{}: {:?}", i18n::tr("error-prefix", &[]), err);
+    }
+}
+
+fn error_fields(code: ErrorCode) -> Map<String, Value> {
+    let mut fields = Map::new();
+    fields.insert("code".into(), json!(code));
+    fields.insert("exit_code".into(), json!(code.exit_code()));
+    fields.insert("retryable".into(), json!(code.is_environmental()));
+    fields
+}
+
+fn emit(level: &str, row: Option<usize>, message: &str, fields: Map<String, Value>) {
+    if JSON.load(Ordering::Relaxed) {
+        let mut line = Map::new();
+        line.insert("time".into(), json!(chrono::Utc::now().to_rfc3339()));
+        line.insert("level".into(), json!(level));
+        if let Some(row) = row {
+            line.insert("row".into(), json!(row));
+        }
+        line.insert("message".into(), json!(message));
+        line.extend(fields);
+        eprintln!("{}", Value::Object(line));
+        return;
+    }
+    let prefix = match level {
+        "warning" => format!("{}: ", i18n::tr("warning-prefix", &[])),
+        _ => String::new(),
+    };
+    match row {
+        Some(row) => eprintln!(
+            "{}: {}{}",
+            i18n::tr("row-prefix", &[("row", row.into())]),
+            prefix,
+            message
+        ),
+        None => eprintln!("{}{}", prefix, message),
+    }
+}
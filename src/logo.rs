@@ -0,0 +1,439 @@
+//! Logo clean-up before compositing: `--logo-prep` removes the white box most
+//! JPEG logos arrive in, and can flatten the logo to gray or a single tint.
+//! `--auto-color` takes the module color from the logo.
+
+use crate::color::Color;
+use crate::contrast;
+use crate::raster::{Band, RoundedRect};
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+/// Symbol contrast the `--auto-color` foreground must reach against the quiet zone (grade A).
+const AUTO_COLOR_CONTRAST: f32 = 0.70;
+
+/// Default for `strip-white`: how far from pure white (per channel) still counts as background.
+const DEFAULT_TOLERANCE: u8 = 24;
+
+/// Clean-up steps, applied as strip-white, mono, tint whatever the order given, e.g. `--logo-prep "strip-white,tint=#1d4ed8"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Prep {
+    /// Make near-white pixels connected to the image edge transparent, within this tolerance.
+    pub strip_white: Option<u8>,
+    /// Convert to grayscale.
+    pub mono: bool,
+    /// Recolor: dark areas take this color, light areas stay light.
+    pub tint: Option<Color>,
+}
+
+impl FromStr for Prep {
+    type Err = String;
+
+    /// Comma-separated steps: `strip-white[=TOLERANCE]`, `mono`, `tint=COLOR`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut prep = Self::default();
+        for step in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = match step.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value.trim())),
+                None => (step, None),
+            };
+            match (key, value) {
+                ("strip-white", None) => prep.strip_white = Some(DEFAULT_TOLERANCE),
+                ("strip-white", Some(v)) => {
+                    prep.strip_white = Some(v.parse().map_err(|_| {
+                        format!("strip-white tolerance must be 0..255, got '{}'", v)
+                    })?)
+                }
+                ("mono", None) => prep.mono = true,
+                ("tint", Some(v)) => prep.tint = Some(v.parse()?),
+                ("tint", None) => return Err("tint needs a color, e.g. tint=#1d4ed8".into()),
+                _ => {
+                    return Err(format!(
+                        "unknown logo prep step '{}' (expected strip-white, mono or tint=COLOR)",
+                        step
+                    ));
+                }
+            }
+        }
+        Ok(prep)
+    }
+}
+
+impl fmt::Display for Prep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut steps = Vec::new();
+        if let Some(tolerance) = self.strip_white {
+            steps.push(format!("strip-white={}", tolerance));
+        }
+        if self.mono {
+            steps.push("mono".to_string());
+        }
+        if let Some(tint) = self.tint {
+            steps.push(format!("tint={}", tint));
+        }
+        write!(f, "{}", steps.join(","))
+    }
+}
+
+impl Prep {
+    pub fn is_noop(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, logo: &DynamicImage) -> DynamicImage {
+        let mut img = logo.to_rgba8();
+        if let Some(tolerance) = self.strip_white {
+            strip_white(&mut img, tolerance);
+        }
+        if self.mono || self.tint.is_some() {
+            for px in img.pixels_mut() {
+                let gray = luma(*px);
+                let [r, g, b] = match self.tint {
+                    // Dark areas take the tint, light areas fade to white.
+                    Some(Color(Rgba([tr, tg, tb, _]))) => {
+                        let mix =
+                            |c: u8| (c as f32 + (255.0 - c as f32) * gray / 255.0).round() as u8;
+                        [mix(tr), mix(tg), mix(tb)]
+                    }
+                    None => [gray.round() as u8; 3],
+                };
+                *px = Rgba([r, g, b, px[3]]);
+            }
+        }
+        DynamicImage::ImageRgba8(img)
+    }
+}
+
+/// The logo's most common saturated color, or its most common color for a monochrome
+/// logo. Transparent and near-white pixels are ignored; None when nothing is left.
+pub fn dominant_color(logo: &DynamicImage) -> Option<Color> {
+    // 16 levels per channel: count and channel sums per bin.
+    let mut bins = vec![(0u32, [0u64; 3]); 16 * 16 * 16];
+    for &Rgba([r, g, b, a]) in logo.to_rgba8().pixels() {
+        if a < 128 || (r >= 224 && g >= 224 && b >= 224) {
+            continue;
+        }
+        let bin =
+            &mut bins[((r >> 4) as usize) << 8 | ((g >> 4) as usize) << 4 | (b >> 4) as usize];
+        bin.0 += 1;
+        for (sum, c) in bin.1.iter_mut().zip([r, g, b]) {
+            *sum += c as u64;
+        }
+    }
+    let mean = |&(count, sums): &(u32, [u64; 3])| sums.map(|s| (s / count as u64) as u8);
+    let chroma = |[r, g, b]: [u8; 3]| r.max(g).max(b) - r.min(g).min(b);
+    let filled = || bins.iter().filter(|bin| bin.0 > 0);
+    let best = filled()
+        .filter(|bin| chroma(mean(bin)) >= 40)
+        .max_by_key(|bin| bin.0)
+        .or_else(|| filled().max_by_key(|bin| bin.0))?;
+    let [r, g, b] = mean(best);
+    Some(Color(Rgba([r, g, b, 255])))
+}
+
+/// `--auto-color`: the logo's dominant color, darkened with its hue kept until it reaches
+/// grade A contrast against `light` (the quiet zone color).
+pub fn brand_color(logo: &DynamicImage, light: Color) -> Option<Color> {
+    let Color(Rgba([r, g, b, _])) = dominant_color(logo)?;
+    let target = contrast::reflectance(light.0) - AUTO_COLOR_CONTRAST;
+    let shade = (0..=50)
+        .rev()
+        .map(|step| {
+            let k = step as f32 / 50.0;
+            let darken = |c: u8| (c as f32 * k).round() as u8;
+            Rgba([darken(r), darken(g), darken(b), 255])
+        })
+        .find(|&shade| contrast::reflectance(shade) <= target)
+        .unwrap_or(Color::BLACK.0);
+    Some(Color(shade))
+}
+
+/// `--logo-ring`: a stroke around the logo, e.g. `"4px #1d4ed8 circle"`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ring {
+    pub width: u32,
+    pub color: Color,
+    /// A circle around the logo rather than a rounded rectangle.
+    pub circle: bool,
+}
+
+impl FromStr for Ring {
+    type Err = String;
+
+    /// A width (`4px` or `4`), then optionally a color and `rounded` or `circle`, in any order.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut width = None;
+        let mut color = None;
+        let mut circle = false;
+        for token in s.split_whitespace() {
+            let digits = token.strip_suffix("px").unwrap_or(token);
+            match token {
+                "circle" => circle = true,
+                "rounded" => circle = false,
+                _ => match digits.parse::<u32>() {
+                    Ok(px) => width = Some(px),
+                    Err(_) => color = Some(token.parse::<Color>()?),
+                },
+            }
+        }
+        match width {
+            Some(0) | None => Err(format!(
+                "expected a ring width such as \"4px #1d4ed8 circle\", got '{}'",
+                s
+            )),
+            Some(width) => Ok(Self {
+                width,
+                color: color.unwrap_or(Color::BLACK),
+                circle,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Ring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shape = if self.circle { "circle" } else { "rounded" };
+        write!(f, "{}px {} {}", self.width, self.color, shape)
+    }
+}
+
+impl Ring {
+    /// The stroke around a logo drawn at `logo`, leaving a gap of one stroke width.
+    pub fn around(&self, logo: &RoundedRect) -> Band {
+        let width = self.width as f32;
+        let inner = if self.circle {
+            let r = (logo.w.hypot(logo.h)) / 2.0 + width;
+            let (cx, cy) = (logo.x + logo.w / 2.0, logo.y + logo.h / 2.0);
+            RoundedRect::new(cx - r, cy - r, 2.0 * r, 2.0 * r, r)
+        } else {
+            let rounded =
+                RoundedRect::new(logo.x, logo.y, logo.w, logo.h, logo.w.min(logo.h) / 4.0);
+            rounded.outset(width)
+        };
+        Band {
+            outer: inner.outset(width),
+            inner,
+        }
+    }
+}
+
+/// Rec. 709 luma of a pixel, 0..255.
+fn luma(Rgba([r, g, b, _]): Rgba<u8>) -> f32 {
+    0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32
+}
+
+/// Clear the near-white region reachable from the image edge, then turn the white out
+/// of the anti-aliased pixels bordering it so the logo keeps a smooth edge.
+/// White enclosed by the artwork (letter counters, inner shapes) is kept.
+fn strip_white(img: &mut RgbaImage, tolerance: u8) {
+    let (w, h) = img.dimensions();
+    let floor = 255 - tolerance;
+    let is_background = |px: &Rgba<u8>| px[3] == 0 || px.0[..3].iter().all(|&c| c >= floor);
+
+    let mut background = vec![false; (w * h) as usize];
+    let mut queue = VecDeque::new();
+    for x in 0..w {
+        queue.push_back((x, 0));
+        queue.push_back((x, h - 1));
+    }
+    for y in 0..h {
+        queue.push_back((0, y));
+        queue.push_back((w - 1, y));
+    }
+    while let Some((x, y)) = queue.pop_front() {
+        let i = (y * w + x) as usize;
+        if background[i] || !is_background(img.get_pixel(x, y)) {
+            continue;
+        }
+        background[i] = true;
+        if x > 0 {
+            queue.push_back((x - 1, y));
+        }
+        if x + 1 < w {
+            queue.push_back((x + 1, y));
+        }
+        if y > 0 {
+            queue.push_back((x, y - 1));
+        }
+        if y + 1 < h {
+            queue.push_back((x, y + 1));
+        }
+    }
+
+    let touches_background = |x: u32, y: u32| {
+        [(-1i64, 0i64), (1, 0), (0, -1), (0, 1)]
+            .iter()
+            .any(|&(dx, dy)| {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                nx >= 0
+                    && ny >= 0
+                    && nx < w as i64
+                    && ny < h as i64
+                    && background[(ny as u32 * w + nx as u32) as usize]
+            })
+    };
+    for y in 0..h {
+        for x in 0..w {
+            let px = img.get_pixel_mut(x, y);
+            if background[(y * w + x) as usize] {
+                *px = Rgba([255, 255, 255, 0]);
+            } else if touches_background(x, y) {
+                *px = unmix_white(*px);
+            }
+        }
+    }
+}
+
+/// The most transparent color that, composited over white, gives `px` (color to alpha).
+fn unmix_white(px: Rgba<u8>) -> Rgba<u8> {
+    let [r, g, b, a] = px.0;
+    let coverage = [r, g, b].iter().map(|&c| 255 - c).max().unwrap_or(0);
+    if coverage == 0 {
+        return Rgba([255, 255, 255, 0]);
+    }
+    let alpha = coverage as f32 / 255.0;
+    let unmix = |c: u8| {
+        (255.0 - (255.0 - c as f32) / alpha)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    };
+    Rgba([
+        unmix(r),
+        unmix(g),
+        unmix(b),
+        (alpha * a as f32).round() as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    /// A dark ring on a white box, with white inside the ring.
+    fn boxed_ring() -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(20, 20, |x, y| {
+            let d = ((x as f32 - 9.5).powi(2) + (y as f32 - 9.5).powi(2)).sqrt();
+            if (4.0..7.0).contains(&d) {
+                Rgba([200, 20, 20, 255])
+            } else {
+                Rgba([252, 253, 250, 255])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_parse() {
+        let prep: Prep = "strip-white, tint=#1d4ed8".parse().unwrap();
+        assert_eq!(prep.strip_white, Some(DEFAULT_TOLERANCE));
+        assert_eq!(prep.tint, Some("#1d4ed8".parse().unwrap()));
+        assert!(!prep.mono);
+        assert_eq!(prep.to_string().parse::<Prep>(), Ok(prep));
+        assert_eq!(
+            "strip-white=40,mono".parse::<Prep>().unwrap().strip_white,
+            Some(40)
+        );
+        assert!("tint".parse::<Prep>().is_err());
+        assert!("sharpen".parse::<Prep>().is_err());
+        assert!("".parse::<Prep>().unwrap().is_noop());
+    }
+
+    #[test]
+    fn test_strip_white_keeps_enclosed_white() {
+        let out = Prep {
+            strip_white: Some(DEFAULT_TOLERANCE),
+            ..Default::default()
+        }
+        .apply(&boxed_ring())
+        .to_rgba8();
+        assert_eq!(out.get_pixel(0, 0)[3], 0);
+        assert_eq!(out.get_pixel(19, 10)[3], 0);
+        // Inside the ring is enclosed by artwork, so it stays opaque.
+        assert_eq!(out.get_pixel(10, 10)[3], 255);
+        assert_eq!(*out.get_pixel(10, 4), Rgba([200, 20, 20, 255]));
+    }
+
+    #[test]
+    fn test_unmix_white() {
+        // 50% red over white is (255, 128, 128).
+        let px = unmix_white(Rgba([255, 128, 128, 255]));
+        assert_eq!(px[3], 127);
+        assert!(px[0] == 255 && px[1] < 3 && px[2] < 3, "{:?}", px);
+    }
+
+    #[test]
+    fn test_brand_color() {
+        // Mostly the brand red, with black text and a white box around it.
+        let logo = DynamicImage::ImageRgba8(ImageBuffer::from_fn(30, 10, |x, _| match x {
+            0..5 => Rgba([255, 255, 255, 255]),
+            5..15 => Rgba([0, 0, 0, 255]),
+            _ => Rgba([230, 40, 40, 255]),
+        }));
+        assert_eq!(dominant_color(&logo), Some(Color(Rgba([230, 40, 40, 255]))));
+        let fg = brand_color(&logo, Color::WHITE).unwrap();
+        let Rgba([r, g, b, _]) = fg.0;
+        assert!(r > 2 * g && g == b, "{}", fg);
+        assert!(1.0 - contrast::reflectance(fg.0) >= AUTO_COLOR_CONTRAST);
+
+        let gray = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([40, 40, 40, 255])));
+        assert_eq!(dominant_color(&gray), Some(Color(Rgba([40, 40, 40, 255]))));
+        let blank =
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([250, 250, 250, 255])));
+        assert_eq!(brand_color(&blank, Color::WHITE), None);
+    }
+
+    #[test]
+    fn test_ring() {
+        let ring: Ring = "4px #1d4ed8 circle".parse().unwrap();
+        assert_eq!(ring.to_string(), "4px #1d4ed8 circle");
+        assert!(!"2 white".parse::<Ring>().unwrap().circle);
+        assert!("circle".parse::<Ring>().is_err());
+
+        let logo = RoundedRect::new(40.0, 40.0, 30.0, 40.0, 0.0);
+        let band = ring.around(&logo);
+        // Centred on the logo, clear of its corners by the stroke width.
+        assert_eq!(band.inner.w, 50.0 + 8.0);
+        assert_eq!(band.outer.w, band.inner.w + 8.0);
+        assert!((band.outer.x + band.outer.w / 2.0 - 55.0).abs() < 1e-4);
+        let rounded = Ring {
+            circle: false,
+            ..ring
+        }
+        .around(&logo);
+        assert!(rounded.inner.encloses(&logo));
+        assert_eq!(rounded.outer.w, 30.0 + 16.0);
+    }
+
+    #[test]
+    fn test_mono_and_tint() {
+        let mono = Prep {
+            mono: true,
+            ..Default::default()
+        }
+        .apply(&boxed_ring())
+        .to_rgba8();
+        let [r, g, b, _] = mono.get_pixel(10, 4).0;
+        assert!(r == g && g == b);
+
+        let blue = Color(Rgba([0, 0, 200, 255]));
+        let tinted = Prep {
+            tint: Some(blue),
+            ..Default::default()
+        }
+        .apply(&DynamicImage::ImageRgba8(ImageBuffer::from_fn(
+            2,
+            1,
+            |x, _| {
+                if x == 0 {
+                    Rgba([0, 0, 0, 255])
+                } else {
+                    Rgba([255, 255, 255, 128])
+                }
+            },
+        )))
+        .to_rgba8();
+        assert_eq!(*tinted.get_pixel(0, 0), Rgba([0, 0, 200, 255]));
+        assert_eq!(*tinted.get_pixel(1, 0), Rgba([255, 255, 255, 128]));
+    }
+}
@@ -0,0 +1,238 @@
+//! `--template lower-third`: a video frame, transparent unless `--canvas-color` is
+//! given, with the code in a corner of the title-safe area and its caption beside it
+//! rather than under it, on an optional brand bar running in from the frame edge.
+//!
+//! The code is drawn a quarter of the frame high and kept inside the middle 90% of
+//! the frame, the area `--guides` outlines as title-safe. `--frame-size` and
+//! `--anchor` pick the frame and the corner.
+
+use crate::color::Color;
+use crate::raster::{self, Canvas};
+use crate::{Assets, RenderOptions, Rendered, draw_text_rgba, measure_text_width, render_code};
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use rusttype::Scale;
+use std::fmt;
+use std::str::FromStr;
+
+/// Smallest caption size before the text is judged not to fit beside the code.
+const MIN_FONT_PX: f32 = 14.0;
+
+/// Width and height of the frame, e.g. `1920x1080`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for FrameSize {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+        }
+    }
+}
+
+impl FromStr for FrameSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s
+            .trim()
+            .split_once(['x', 'X'])
+            .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+        match parsed {
+            Some((width, height)) if width > 0 && height > 0 => Ok(Self { width, height }),
+            _ => Err(format!(
+                "expected a frame size such as 1920x1080, got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FrameSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// The corner of the title-safe area the code sits in.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Anchor {
+    #[default]
+    BottomLeft,
+    BottomRight,
+    TopLeft,
+    TopRight,
+}
+
+impl FromStr for Anchor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+impl Anchor {
+    fn right(self) -> bool {
+        matches!(self, Self::BottomRight | Self::TopRight)
+    }
+
+    fn bottom(self) -> bool {
+        matches!(self, Self::BottomLeft | Self::BottomRight)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LowerThird {
+    pub frame: FrameSize,
+    pub anchor: Anchor,
+    /// Band drawn behind the code and caption, from the frame edge.
+    pub bar: Option<Color>,
+}
+
+/// Render `payload` into the frame, with `caption` beside the code.
+pub(crate) fn render(
+    payload: &str,
+    caption: Option<&str>,
+    opts: &RenderOptions,
+    assets: &Assets,
+    layout: &LowerThird,
+) -> Result<Rendered> {
+    let FrameSize { width, height } = layout.frame;
+    let (margin_x, margin_y) = (width / 20, height / 20);
+    let side = height / 4;
+
+    // As with --place: shrink until the border and shadow fit the box too.
+    let mut opts = opts.clone();
+    opts.size = side;
+    let mut rendered = loop {
+        let rendered = render_code(payload, None, &opts, assets)?;
+        let (w, h) = rendered.image.dimensions();
+        if w <= side && h <= side {
+            break rendered;
+        }
+        let scale = side as f32 / w.max(h) as f32;
+        opts.size = ((opts.size as f32 * scale) as u32).min(opts.size - 1);
+    };
+    let (code_w, code_h) = rendered.image.dimensions();
+    let gap = code_h / 6;
+
+    // The caption starts a third of the code high and shrinks to the room beside it.
+    let room = width.saturating_sub(2 * margin_x + code_w + gap) as f32;
+    let text = match caption {
+        Some(text) => {
+            let mut font_px = code_h as f32 * 0.22;
+            while measure_text_width(&assets.font, Scale::uniform(font_px), text) > room {
+                if font_px <= MIN_FONT_PX {
+                    bail!(
+                        "the caption does not fit beside the code in a {} frame; shorten it or widen --frame-size",
+                        layout.frame
+                    );
+                }
+                font_px = (font_px * 0.92).max(MIN_FONT_PX);
+            }
+            let scale = Scale::uniform(font_px);
+            Some((text, scale, measure_text_width(&assets.font, scale, text)))
+        }
+        None => None,
+    };
+
+    let x = match layout.anchor.right() {
+        true => width - margin_x - code_w,
+        false => margin_x,
+    };
+    let y = match layout.anchor.bottom() {
+        true => height - margin_y - code_h,
+        false => margin_y,
+    };
+    let text_x = text.map(|(_, _, text_w)| match layout.anchor.right() {
+        true => x as f32 - gap as f32 - text_w,
+        false => (x + code_w + gap) as f32,
+    });
+
+    let mut frame = Canvas::from_pixel(width, height, opts.canvas_color.0);
+    if let Some(bar) = layout.bar {
+        let reach = text.map_or(0, |(_, _, text_w)| text_w.ceil() as u32 + gap);
+        let span = match layout.anchor.right() {
+            true => (x - gap).saturating_sub(reach)..width,
+            false => 0..(x + code_w + gap + reach).min(width),
+        };
+        let pad = gap / 2;
+        for row in y.saturating_sub(pad)..(y + code_h + pad).min(height) {
+            raster::fill_row(&mut frame, row, span.clone(), bar.0);
+        }
+    }
+    raster::overlay(&mut frame, &rendered.image, x, y);
+    if let (Some((text, scale, _)), Some(text_x)) = (text, text_x) {
+        let v_metrics = assets.font.primary().v_metrics(scale);
+        let centre = y as f32 + code_h as f32 / 2.0;
+        let baseline = centre + (v_metrics.ascent + v_metrics.descent) / 2.0;
+        draw_text_rgba(
+            &mut frame,
+            &assets.font,
+            scale,
+            text_x,
+            baseline,
+            text,
+            opts.caption.text_color.0,
+        );
+    }
+
+    let (qx, qy, qr_side) = rendered.qr_rect;
+    rendered.image = frame;
+    rendered.qr_rect = (x + qx, y + qy, qr_side);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+    use clap::Parser;
+
+    #[test]
+    fn test_render() {
+        assert!("1280".parse::<FrameSize>().is_err());
+        assert!("0x720".parse::<FrameSize>().is_err());
+
+        let args = Args::try_parse_from([
+            "qrbrand",
+            "--template",
+            "lower-third",
+            "--frame-size",
+            "1280x720",
+            "--anchor",
+            "bottom-right",
+            "--brand-bar",
+            "#1e3a8a",
+            "--alt-text",
+            "Scan for the show notes",
+        ])
+        .unwrap();
+        let opts = RenderOptions::from_args(&args);
+        let assets = Assets::load(None, None, None).unwrap();
+        let layout = opts.lower_third.unwrap();
+        let caption = args.alt_text.first().map(String::as_str);
+        let rendered = render("https://example.com", caption, &opts, &assets, &layout).unwrap();
+        assert_eq!(rendered.image.dimensions(), (1280, 720));
+
+        // The code sits in the bottom-right corner of the title-safe area.
+        let (x, y, side) = rendered.qr_rect;
+        assert!(side <= 180);
+        assert_eq!((x + side, y + side), (1280 - 64, 720 - 36));
+        // The bar runs in from the right edge, behind the caption; above it, clear.
+        let bar = Color::from_str("#1e3a8a").unwrap().0;
+        assert_eq!(*rendered.image.get_pixel(1279, y + 2), bar);
+        assert_eq!(rendered.image.get_pixel(1279, 10)[3], 0);
+        let text_row = y + side / 2;
+        let dark = (0..x).filter(|&px| {
+            let p = rendered.image.get_pixel(px, text_row);
+            p != &bar && p[3] > 0
+        });
+        assert!(dark.count() > 0, "caption drawn left of the code");
+    }
+}
@@ -0,0 +1,184 @@
+//! Pre-press marks: bleed past the trim and crop marks outside it for print output, and
+//! the `--guides` preview that outlines the quiet zone, the video title-safe area and
+//! the logo plate.
+//!
+//! Lengths are in points. The geometry is shared by the PDF, EPS and raster writers;
+//! it is symmetric, so it reads the same with y running up or down.
+
+use crate::color::Color;
+use crate::pdf::{MM, Rect};
+use crate::raster::Canvas;
+use image::Rgba;
+
+/// Length of a crop mark.
+pub const MARK: f64 = 5.0 * MM;
+/// Line width of a crop mark.
+pub const HAIRLINE: f64 = 0.25;
+/// Share of the frame inside the title-safe area (SMPTE ST 2046-1).
+const TITLE_SAFE: f32 = 0.9;
+
+const QUIET_GUIDE: Rgba<u8> = Rgba([0, 180, 216, 255]);
+const TITLE_SAFE_GUIDE: Rgba<u8> = Rgba([217, 70, 239, 255]);
+const PLATE_GUIDE: Rgba<u8> = Rgba([249, 115, 22, 255]);
+
+/// How much bleed and whether crop marks go around the trimmed artwork.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Marks {
+    pub bleed: f64,
+    pub crop: bool,
+}
+
+impl Marks {
+    /// Space from the trim to the edge of the page: the bleed, plus the crop marks and
+    /// a millimetre past them.
+    pub fn margin(&self) -> f64 {
+        if self.crop {
+            self.bleed + MARK + 1.0 * MM
+        } else {
+            self.bleed
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Crop mark lines `[x0, y0, x1, y1]` in line with each edge of `trim`, starting
+/// outside the bleed.
+pub fn crop_lines([x0, y0, x1, y1]: Rect, bleed: f64) -> Vec<Rect> {
+    let mut lines = Vec::with_capacity(8);
+    for x in [x0, x1] {
+        lines.push([x, y0 - bleed - MARK, x, y0 - bleed]);
+        lines.push([x, y1 + bleed, x, y1 + bleed + MARK]);
+    }
+    for y in [y0, y1] {
+        lines.push([x0 - bleed - MARK, y, x0 - bleed, y]);
+        lines.push([x1 + bleed, y, x1 + bleed + MARK, y]);
+    }
+    lines
+}
+
+/// `img` as the trimmed artwork at `px_per_pt`, with `marks` around it on white. The
+/// bleed repeats the outermost pixels, so whatever runs to the trim runs past it.
+pub fn extend(img: &Canvas, marks: &Marks, px_per_pt: f64) -> Canvas {
+    let px = |pt: f64| (pt * px_per_pt).round() as u32;
+    let (bleed, margin) = (px(marks.bleed), px(marks.margin()));
+    let (w, h) = img.dimensions();
+    let mut out = Canvas::from_pixel(w + 2 * margin, h + 2 * margin, Color::WHITE.0);
+    let edge = margin - bleed;
+    for y in 0..h + 2 * bleed {
+        let sy = y.saturating_sub(bleed).min(h - 1);
+        for x in 0..w + 2 * bleed {
+            let sx = x.saturating_sub(bleed).min(w - 1);
+            out.put_pixel(edge + x, edge + y, *img.get_pixel(sx, sy));
+        }
+    }
+    if marks.crop {
+        let line = px(HAIRLINE).max(1) as f64;
+        let trim = [margin, margin, margin + w, margin + h].map(|v| v as f64 / px_per_pt);
+        for points in crop_lines(trim, bleed as f64 / px_per_pt) {
+            let [x0, y0, x1, y1] = points.map(|v| v * px_per_pt);
+            let (lw, lh) = ((x1 - x0).abs().max(line), (y1 - y0).abs().max(line));
+            let (cx, cy) = ((x0 + x1) / 2.0, (y0 + y1) / 2.0);
+            fill_rect(
+                &mut out,
+                cx - lw / 2.0,
+                cy - lh / 2.0,
+                lw,
+                lh,
+                Color::BLACK.0,
+            );
+        }
+    }
+    out
+}
+
+/// A copy of `img` with guide lines: the quiet zone boundary (the edge of the symbol)
+/// of the `qr_rect` square, the title-safe area of the frame and the plate or logo
+/// bounds at `plate` within the square.
+pub fn guides(
+    img: &Canvas,
+    (qx, qy, side): (u32, u32, u32),
+    quiet_px: u32,
+    plate: Option<(u32, u32, u32, u32)>,
+) -> Canvas {
+    let mut out = img.clone();
+    let (w, h) = img.dimensions();
+    let line = (w.max(h) / 512).max(1) as f64;
+    let symbol = side - 2 * quiet_px;
+    outline(
+        &mut out,
+        [qx + quiet_px, qy + quiet_px, symbol, symbol],
+        line,
+        QUIET_GUIDE,
+    );
+    let (sw, sh) = (w as f32 * TITLE_SAFE, h as f32 * TITLE_SAFE);
+    let safe = [(w as f32 - sw) / 2.0, (h as f32 - sh) / 2.0, sw, sh].map(|v| v.round() as u32);
+    outline(&mut out, safe, line, TITLE_SAFE_GUIDE);
+    if let Some((px, py, pw, ph)) = plate {
+        outline(&mut out, [qx + px, qy + py, pw, ph], line, PLATE_GUIDE);
+    }
+    out
+}
+
+/// Draw a `line` pixels wide just inside the `[x, y, w, h]` box.
+fn outline(img: &mut Canvas, [x, y, w, h]: [u32; 4], line: f64, color: Rgba<u8>) {
+    let [x, y, w, h] = [x, y, w, h].map(|v| v as f64);
+    fill_rect(img, x, y, w, line, color);
+    fill_rect(img, x, y + h - line, w, line, color);
+    fill_rect(img, x, y, line, h, color);
+    fill_rect(img, x + w - line, y, line, h, color);
+}
+
+/// Fill the pixels of `img` whose centres fall in the box, clipped to the image.
+fn fill_rect(img: &mut Canvas, x: f64, y: f64, w: f64, h: f64, color: Rgba<u8>) {
+    let clamp = |v: f64, max: u32| v.round().clamp(0.0, max as f64) as u32;
+    let (x0, x1) = (clamp(x, img.width()), clamp(x + w, img.width()));
+    for row in clamp(y, img.height())..clamp(y + h, img.height()) {
+        crate::raster::fill_row(img, row, x0..x1, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extend() {
+        // 72 dpi: one pixel per point. 3 pt of bleed, then the marks.
+        let mut img = Canvas::from_pixel(20, 10, Rgba([200, 0, 0, 255]));
+        img.put_pixel(19, 9, Rgba([0, 0, 200, 255]));
+        let marks = Marks {
+            bleed: 3.0,
+            crop: true,
+        };
+        let margin = marks.margin().round() as u32;
+        let out = extend(&img, &marks, 1.0);
+        assert_eq!(out.dimensions(), (20 + 2 * margin, 10 + 2 * margin));
+        // The bleed repeats the edge, corners included; the slug stays white.
+        assert_eq!(out.get_pixel(margin - 3, margin - 3).0, [200, 0, 0, 255]);
+        assert_eq!(out.get_pixel(margin + 22, margin + 12).0, [0, 0, 200, 255]);
+        assert_eq!(out.get_pixel(margin - 4, margin - 4).0, [255; 4]);
+        // A mark in line with the left trim edge, above the bleed, and none inside it.
+        assert_eq!(out.get_pixel(margin, margin - 5).0, [0, 0, 0, 255]);
+        assert_eq!(out.get_pixel(margin, 0).0, [255; 4]);
+
+        let lines = crop_lines([0.0, 0.0, 10.0, 10.0], 2.0);
+        assert_eq!(lines.len(), 8);
+        assert!(lines.contains(&[0.0, -2.0 - MARK, 0.0, -2.0]));
+        assert!(lines.contains(&[12.0, 10.0, 12.0 + MARK, 10.0]));
+    }
+
+    #[test]
+    fn test_guides() {
+        // A 116 px square code with a 16 px quiet zone and a plate in its middle.
+        let img = Canvas::from_pixel(116, 116, Color::WHITE.0);
+        let out = guides(&img, (0, 0, 116), 16, Some((40, 40, 36, 36)));
+        assert_eq!(*out.get_pixel(16, 50), QUIET_GUIDE);
+        assert_eq!(*out.get_pixel(99, 16), QUIET_GUIDE);
+        assert_eq!(*out.get_pixel(6, 60), TITLE_SAFE_GUIDE);
+        assert_eq!(*out.get_pixel(40, 60), PLATE_GUIDE);
+        assert_eq!(*out.get_pixel(58, 58), Color::WHITE.0);
+    }
+}
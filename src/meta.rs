@@ -0,0 +1,126 @@
+//! Metadata sidecar describing a generated QR image.
+//!
+//! Downstream templating needs the symbol parameters (version, EC level,
+//! module count) and the final canvas size without decoding the PNG again.
+
+use crate::symbol::Symbol;
+use anyhow::{Context, Result};
+use qrcode::{EcLevel, Version};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+#[derive(Serialize, Debug)]
+pub struct Metadata {
+    /// QR version (1..=40, or M1..M4 for Micro QR).
+    pub qr_version: String,
+    /// Error correction level (L, M, Q or H).
+    pub ec_level: &'static str,
+    /// Modules per side, excluding the quiet zone.
+    pub module_count: u32,
+    /// Final image width in pixels.
+    pub width: u32,
+    /// Final image height in pixels (includes any caption band).
+    pub height: u32,
+    /// Percentage of the symbol area (excluding quiet zone) hidden by the logo/plate.
+    pub logo_coverage_pct: f32,
+    /// Lowercase hex SHA-256 of the encoded payload bytes.
+    pub payload_sha256: String,
+    /// Resolution given with `--size`, e.g. `50mm@300dpi`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dpi: Option<f32>,
+}
+
+impl Metadata {
+    pub fn new(code: &Symbol, payload: &str, width: u32, height: u32, coverage: f32) -> Self {
+        Self {
+            qr_version: version_label(code.version()),
+            ec_level: ec_level_label(code.error_correction_level()),
+            module_count: code.width() as u32,
+            width,
+            height,
+            logo_coverage_pct: (coverage * 10000.0).round() / 100.0,
+            payload_sha256: sha256_hex(payload.as_bytes()),
+            dpi: None,
+        }
+    }
+
+    pub fn with_dpi(self, dpi: Option<f32>) -> Self {
+        Self { dpi, ..self }
+    }
+
+    /// Write the metadata as pretty-printed JSON.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize metadata")?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write metadata JSON: {}", path))
+    }
+}
+
+pub fn version_label(version: Version) -> String {
+    match version {
+        Version::Normal(v) => v.to_string(),
+        Version::Micro(v) => format!("M{}", v),
+    }
+}
+
+pub fn ec_level_label(level: EcLevel) -> &'static str {
+    match level {
+        EcLevel::L => "L",
+        EcLevel::M => "M",
+        EcLevel::Q => "Q",
+        EcLevel::H => "H",
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// HMAC-SHA256 (RFC 2104).
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(data)
+        .finalize();
+    let outer = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize();
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&outer);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_metadata_fields() {
+        let code = crate::symbol::encode("https://example.com", &Default::default())
+            .expect("Failed to build QR code");
+        let meta = Metadata::new(&code, "https://example.com", 300, 420, 0.0512);
+
+        assert_eq!(meta.ec_level, "H");
+        assert_eq!(meta.module_count, code.width() as u32);
+        assert_eq!(meta.logo_coverage_pct, 5.12);
+        assert_eq!(meta.payload_sha256.len(), 64);
+    }
+}
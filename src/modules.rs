@@ -0,0 +1,501 @@
+//! Styling of the modules themselves, drawn over the plain squares of the bare code.
+//!
+//! `--eye-color` and `--eye-inner-color` recolor the three finder patterns, the
+//! outer ring and the 3x3 centre, while the data modules keep `--fg`.
+//!
+//! `--module-gap` shrinks each dark module within its cell, so neighbours read as
+//! separate dots rather than solid runs. The finder patterns stay solid, since a
+//! scanner finds the code by their 1:1:3:1:1 runs, and the gap is capped: a thin
+//! module on a light ground turns light under blur or a low-resolution camera.
+//!
+//! `--module-style outline` and `outline-circle` stroke each dark module instead of
+//! filling it. A scanner that samples module centres sees those as light, so a hollow
+//! code only reads once the camera blurs each module into its average; contrast and
+//! decoding are checked on that [`averaged`] image rather than the sharp one.
+//!
+//! `--fill-texture` paints the dark modules with a tiled image or a built-in pattern
+//! instead of flat `--fg`, following the module shapes above. Every fill pixel is
+//! darkened to at most [`MAX_FILL_REFLECTANCE`], so a pale texture cannot wash the
+//! code out.
+
+use crate::color::Color;
+use crate::raster::{self, Band, Canvas, RoundedRect};
+use crate::symbol::Symbol;
+use crate::verify;
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use image::{GenericImageView, Rgba};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Largest --module-gap, as a fraction of the module.
+pub const MAX_GAP: f32 = 0.3;
+
+/// Stroke of an outlined module, as a fraction of its side.
+const STROKE: f32 = 0.25;
+
+/// Lightest a --fill-texture pixel may be, as reflectance: grade A contrast on white.
+pub const MAX_FILL_REFLECTANCE: f32 = 0.25;
+
+/// Share of the way to white the light tone of a built-in pattern is mixed.
+const PATTERN_TINT: f32 = 0.35;
+
+/// `--fill-texture`: a built-in pattern name, or the path of an image to tile.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fill {
+    Pattern(Pattern),
+    Image(PathBuf),
+}
+
+/// Patterns drawn in the module color and a lighter tint of it.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    /// Horizontal bands
+    Stripes,
+    /// Diagonal bands
+    Diagonal,
+    /// A woven twill, like carbon fibre
+    Carbon,
+}
+
+impl FromStr for Fill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("expected a pattern (stripes, diagonal, carbon) or an image path".into());
+        }
+        Ok(match <Pattern as ValueEnum>::from_str(s, true) {
+            Ok(pattern) => Self::Pattern(pattern),
+            Err(_) => Self::Image(PathBuf::from(s)),
+        })
+    }
+}
+
+impl Pattern {
+    /// How far towards the light tone pixel (x, y) of a code with `ppm` pixel
+    /// modules is, 0..1.
+    fn shade(self, x: u32, y: u32, ppm: u32) -> f32 {
+        let period = (ppm / 2).max(2);
+        let band = |v: u32| {
+            if (v / period).is_multiple_of(2) {
+                0.0
+            } else {
+                1.0
+            }
+        };
+        match self {
+            Self::Stripes => band(y),
+            Self::Diagonal => band(x + y),
+            Self::Carbon => {
+                let (cx, cy) = (x / period, y / period);
+                let along = match (cx + cy).is_multiple_of(2) {
+                    true => x % period,
+                    false => y % period,
+                };
+                along as f32 / (period - 1) as f32
+            }
+        }
+    }
+}
+
+/// How each dark data module is drawn.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ModuleStyle {
+    /// Filled squares
+    #[default]
+    Square,
+    /// Stroked squares, hollow inside
+    Outline,
+    /// Stroked circles, hollow inside
+    OutlineCircle,
+}
+
+impl FromStr for ModuleStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+impl ModuleStyle {
+    /// Whether module centres are left light.
+    pub fn is_hollow(self) -> bool {
+        self != Self::Square
+    }
+}
+
+/// Colors of the finder patterns; each falls back to the module color.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Eyes {
+    /// The outer ring, and the centre unless `inner` is given.
+    pub outer: Option<Color>,
+    pub inner: Option<Color>,
+}
+
+impl Eyes {
+    pub fn is_set(&self) -> bool {
+        self.outer.is_some() || self.inner.is_some()
+    }
+}
+
+/// Repaint the dark modules of the finder patterns of `code`, drawn in `img` at
+/// `ppm` pixels per module after `quiet` modules of border, in the `eyes` colors,
+/// falling back to `fg`.
+pub fn paint_eyes(img: &mut Canvas, code: &Symbol, quiet: u32, ppm: u32, eyes: &Eyes, fg: Color) {
+    let outer = eyes.outer.unwrap_or(fg);
+    let inner = eyes.inner.unwrap_or(outer);
+    let n = code.width() as u32;
+    for (fx, fy) in [(0, 0), (n - 7, 0), (0, n - 7)] {
+        for dy in 0..7 {
+            for dx in 0..7 {
+                let (x, y) = (fx + dx, fy + dy);
+                if !matches!(code[(x as usize, y as usize)], qrcode::Color::Dark) {
+                    continue;
+                }
+                let centre = (2..5).contains(&dx) && (2..5).contains(&dy);
+                let color = if centre { inner } else { outer };
+                let (left, top) = ((x + quiet) * ppm, (y + quiet) * ppm);
+                for row in top..top + ppm {
+                    raster::fill_row(img, row, left..left + ppm, color.0);
+                }
+            }
+        }
+    }
+}
+
+/// Refuse a --module-gap outside 0..=[`MAX_GAP`].
+pub fn check_gap(gap: f32) -> Result<()> {
+    if !(0.0..=MAX_GAP).contains(&gap) {
+        bail!("--module-gap must be between 0 and {}", MAX_GAP);
+    }
+    Ok(())
+}
+
+/// Shrink the dark modules of `code` outside the finder patterns, drawn in `img` at
+/// `ppm` pixels per module after `quiet` modules of border, by `gap` of a module,
+/// painting `light` around them. Returns the pixels taken from each side, which is 0
+/// when the gap rounds away at this size.
+pub fn separate(
+    img: &mut Canvas,
+    code: &Symbol,
+    quiet: u32,
+    ppm: u32,
+    gap: f32,
+    light: Rgba<u8>,
+) -> u32 {
+    let inset = (ppm as f32 * gap / 2.0).round() as u32;
+    if inset == 0 {
+        return 0;
+    }
+    let n = code.width() as u32;
+    for y in 0..n {
+        for x in 0..n {
+            let dark = matches!(code[(x as usize, y as usize)], qrcode::Color::Dark);
+            if !dark || verify::in_finder(x, y, n) {
+                continue;
+            }
+            let (left, top) = ((x + quiet) * ppm, (y + quiet) * ppm);
+            for row in top..top + ppm {
+                if row < top + inset || row >= top + ppm - inset {
+                    raster::fill_row(img, row, left..left + ppm, light);
+                } else {
+                    raster::fill_row(img, row, left..left + inset, light);
+                    raster::fill_row(img, row, left + ppm - inset..left + ppm, light);
+                }
+            }
+        }
+    }
+    inset
+}
+
+/// Redraw the dark modules of `code` outside the finder patterns, drawn in `img` at
+/// `ppm` pixels per module after `quiet` modules of border, as hollow `style` shapes
+/// in `fg` on `light`, inset by `gap` of a module.
+#[allow(clippy::too_many_arguments)]
+pub fn outline(
+    img: &mut Canvas,
+    code: &Symbol,
+    quiet: u32,
+    ppm: u32,
+    style: ModuleStyle,
+    gap: f32,
+    fg: Color,
+    light: Rgba<u8>,
+) {
+    let inset = ppm as f32 * gap / 2.0;
+    let side = ppm as f32 - 2.0 * inset;
+    let stroke = (side * STROKE).max(1.0);
+    let radius = match style {
+        ModuleStyle::OutlineCircle => side / 2.0,
+        _ => 0.0,
+    };
+    let n = code.width() as u32;
+    for y in 0..n {
+        for x in 0..n {
+            let dark = matches!(code[(x as usize, y as usize)], qrcode::Color::Dark);
+            if !dark || verify::in_finder(x, y, n) {
+                continue;
+            }
+            let (left, top) = ((x + quiet) * ppm, (y + quiet) * ppm);
+            for row in top..top + ppm {
+                raster::fill_row(img, row, left..left + ppm, light);
+            }
+            let cell =
+                RoundedRect::new(left as f32 + inset, top as f32 + inset, side, side, radius);
+            let band = Band {
+                outer: cell,
+                inner: cell.inset(stroke),
+            };
+            raster::fill(img, &band, fg.0);
+        }
+    }
+}
+
+/// A [`Fill`] ready to draw, its image loaded.
+pub enum Paint<'a> {
+    Pattern(Pattern),
+    /// Tiled from the symbol's corner and laid over the module color.
+    Texture(&'a Canvas),
+}
+
+/// Repaint the dark modules in the `ppm` pixel module symbol of `img`, drawn in `fg`
+/// on white `margin` pixels in from each edge, with `paint`. Edge pixels keep their
+/// share of the module.
+pub fn fill_modules(img: &mut Canvas, margin: u32, ppm: u32, fg: Color, paint: &Paint) {
+    // How far each pixel is from white towards fg, read off its most telling channel.
+    let Some((channel, depth)) = fg.0.0[..3]
+        .iter()
+        .map(|&c| 255.0 - c as f32)
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|&(_, depth)| depth > 0.0)
+    else {
+        return;
+    };
+    let tint = raster::composite(fg.0, Rgba([255, 255, 255, 255]), PATTERN_TINT);
+    let white = Rgba([255, 255, 255, 255]);
+    let side = img.width() - 2 * margin;
+    for y in 0..side {
+        for x in 0..side {
+            let (px, py) = (x + margin, y + margin);
+            let pixel = *img.get_pixel(px, py);
+            let coverage = ((255.0 - pixel[channel] as f32) / depth).clamp(0.0, 1.0);
+            if coverage == 0.0 {
+                continue;
+            }
+            let color = match paint {
+                Paint::Pattern(pattern) => raster::composite(fg.0, tint, pattern.shade(x, y, ppm)),
+                Paint::Texture(texture) => {
+                    let t = texture.get_pixel(x % texture.width(), y % texture.height());
+                    raster::composite(fg.0, *t, 1.0)
+                }
+            };
+            let color = Rgba([color[0], color[1], color[2], 255]);
+            img.put_pixel(
+                px,
+                py,
+                raster::composite(white, floor(color, MAX_FILL_REFLECTANCE), coverage),
+            );
+        }
+    }
+}
+
+/// `p` darkened, keeping its hue, until its reflectance is at most `max`.
+pub fn floor(p: Rgba<u8>, max: f32) -> Rgba<u8> {
+    let r = crate::contrast::reflectance(p);
+    if r <= max {
+        return p;
+    }
+    // Scaling every channel in linear light scales the luminance alike.
+    let k = max / r;
+    let [cr, cg, cb, a] = p.0;
+    Rgba([
+        to_srgb(to_linear(cr) * k),
+        to_srgb(to_linear(cg) * k),
+        to_srgb(to_linear(cb) * k),
+        a,
+    ])
+}
+
+fn to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn to_srgb(l: f32) -> u8 {
+    let c = match l <= 0.0031308 {
+        true => l * 12.92,
+        false => 1.055 * l.powf(1.0 / 2.4) - 0.055,
+    };
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// `img` (a QR square of `ppm` pixel modules) with every module cell filled with its
+/// mean color, averaged as light rather than as sRGB values: roughly what a camera
+/// that cannot resolve inside a module sees.
+pub fn averaged(img: &impl GenericImageView<Pixel = Rgba<u8>>, ppm: u32) -> Canvas {
+    let (w, h) = img.dimensions();
+    let mut out = Canvas::new(w, h);
+    for top in (0..h).step_by(ppm as usize) {
+        for left in (0..w).step_by(ppm as usize) {
+            let (right, bottom) = ((left + ppm).min(w), (top + ppm).min(h));
+            let mut sum = [0.0f32; 4];
+            for y in top..bottom {
+                for x in left..right {
+                    let [r, g, b, a] = img.get_pixel(x, y).0;
+                    let p = [to_linear(r), to_linear(g), to_linear(b), a as f32 / 255.0];
+                    for (total, c) in sum.iter_mut().zip(p) {
+                        *total += c;
+                    }
+                }
+            }
+            let count = ((right - left) * (bottom - top)) as f32;
+            let [r, g, b, a] = sum.map(|total| total / count);
+            let mean = Rgba([
+                to_srgb(r),
+                to_srgb(g),
+                to_srgb(b),
+                (a * 255.0).round() as u8,
+            ]);
+            for y in top..bottom {
+                raster::fill_row(&mut out, y, left..right, mean);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, render_qr_rgba};
+
+    #[test]
+    fn test_paint_eyes() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let n = code.width() as u32;
+        let mut img = render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap();
+        let ppm = img.width() / (n + 8);
+        let (red, blue) = (Rgba([200, 0, 0, 255]), Rgba([0, 0, 160, 255]));
+        let eyes = Eyes {
+            outer: Some(Color(red)),
+            inner: Some(Color(blue)),
+        };
+        paint_eyes(&mut img, &code, 4, ppm, &eyes, Color::BLACK);
+
+        let at = |img: &Canvas, x: u32, y: u32| {
+            *img.get_pixel((x + 4) * ppm + ppm / 2, (y + 4) * ppm + ppm / 2)
+        };
+        for (fx, fy) in [(0, 0), (n - 7, 0), (0, n - 7)] {
+            assert_eq!(at(&img, fx, fy), red);
+            assert_eq!(at(&img, fx + 3, fy + 3), blue);
+            assert_eq!(at(&img, fx + 1, fy + 1).0, [255, 255, 255, 255]);
+        }
+        // Data and timing modules keep --fg.
+        assert_eq!(at(&img, 8, 6).0, [0, 0, 0, 255]);
+
+        let ring_only = Eyes {
+            outer: Some(Color(red)),
+            inner: None,
+        };
+        paint_eyes(&mut img, &code, 4, ppm, &ring_only, Color::BLACK);
+        assert_eq!(at(&img, 3, 3), red);
+    }
+
+    #[test]
+    fn test_fill_modules() {
+        let payload = "https://example.com/menu";
+        let code = crate::symbol::encode(payload, &Default::default()).unwrap();
+        let n = code.width() as u32;
+        let pale = Canvas::from_pixel(3, 3, Rgba([250, 240, 200, 255]));
+        let paints = [
+            Paint::Pattern(Pattern::Carbon),
+            Paint::Pattern(Pattern::Stripes),
+            Paint::Texture(&pale),
+        ];
+        for paint in paints {
+            let mut img = render_qr_rgba(&code, 400, 4, Color::BLACK).unwrap();
+            let ppm = img.width() / (n + 8);
+            fill_modules(&mut img, 4 * ppm, ppm, Color::BLACK, &paint);
+            let contrast = crate::contrast::measure(&img, &code, 4, None, false);
+            assert!(contrast.dark <= MAX_FILL_REFLECTANCE + 0.01);
+            assert_eq!(contrast.light, 1.0);
+            assert_eq!(decode::decode(&img).unwrap().text, payload);
+        }
+
+        assert_eq!("Carbon".parse(), Ok(Fill::Pattern(Pattern::Carbon)));
+        assert_eq!(
+            "carbon.png".parse(),
+            Ok(Fill::Image(PathBuf::from("carbon.png")))
+        );
+        assert!("".parse::<Fill>().is_err());
+        assert_eq!(
+            floor(Rgba([0, 0, 0, 255]), MAX_FILL_REFLECTANCE),
+            Rgba([0, 0, 0, 255])
+        );
+    }
+
+    #[test]
+    fn test_outline() {
+        let payload = "https://example.com/menu";
+        let code = crate::symbol::encode(payload, &Default::default()).unwrap();
+        let n = code.width() as u32;
+        let white = Rgba([255, 255, 255, 255]);
+        for style in [ModuleStyle::Outline, ModuleStyle::OutlineCircle] {
+            let mut img = render_qr_rgba(&code, 400, 4, Color::BLACK).unwrap();
+            let ppm = img.width() / (n + 8);
+            outline(&mut img, &code, 4, ppm, style, 0.0, Color::BLACK, white);
+
+            // Centres of data modules are light, so the sharp image reads as junk...
+            let contrast = crate::contrast::measure(&img, &code, 4, None, false);
+            assert!(contrast.check(false).is_err());
+            // ...while the averaged one, as a camera sees it, still reads.
+            let seen = averaged(&img, ppm);
+            let contrast = crate::contrast::measure(&seen, &code, 4, None, false);
+            assert!(contrast.symbol_contrast() > 0.4, "{:?}", style);
+            assert_eq!(decode::decode(&seen).unwrap().text, payload);
+        }
+        assert_eq!("outline-circle".parse(), Ok(ModuleStyle::OutlineCircle));
+        assert!(!ModuleStyle::Square.is_hollow());
+    }
+
+    #[test]
+    fn test_separate() {
+        let payload = "https://example.com/menu";
+        let code = crate::symbol::encode(payload, &Default::default()).unwrap();
+        let n = code.width() as u32;
+        let mut img = render_qr_rgba(&code, 400, 4, Color::BLACK).unwrap();
+        let ppm = img.width() / (n + 8);
+        let white = Rgba([255, 255, 255, 255]);
+        let inset = separate(&mut img, &code, 4, ppm, MAX_GAP, white);
+        assert_eq!(inset, (ppm as f32 * MAX_GAP / 2.0).round() as u32);
+
+        // A data module keeps its centre and loses its edge; finders stay solid.
+        let (x, y) = (0..n)
+            .flat_map(|y| (0..n).map(move |x| (x, y)))
+            .find(|&(x, y)| {
+                !verify::in_finder(x, y, n)
+                    && matches!(code[(x as usize, y as usize)], qrcode::Color::Dark)
+            })
+            .unwrap();
+        let (left, top) = ((x + 4) * ppm, (y + 4) * ppm);
+        assert_eq!(
+            img.get_pixel(left + ppm / 2, top + ppm / 2).0,
+            [0, 0, 0, 255]
+        );
+        assert_eq!(img.get_pixel(left, top + ppm / 2), &white);
+        assert_eq!(img.get_pixel(4 * ppm, 4 * ppm).0, [0, 0, 0, 255]);
+
+        assert_eq!(decode::decode(&img).unwrap().text, payload);
+        assert_eq!(separate(&mut img, &code, 4, 2, 0.2, white), 0);
+        assert!(check_gap(0.1).is_ok());
+        assert!(check_gap(0.5).is_err());
+        assert!(check_gap(-0.1).is_err());
+    }
+}
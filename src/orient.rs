@@ -0,0 +1,132 @@
+//! `--rotate` and `--flip`: turning the finished image for label stock that feeds
+//! sideways, so it need not go through another tool that would re-compress it.
+//!
+//! The turn comes last, after the caption, label, template and overlay are drawn, and
+//! the rectangles recorded for the code and logo turn with it; metadata, guides and
+//! print sizes then describe the image as written.
+
+use crate::raster::Canvas;
+use clap::ValueEnum;
+use image::imageops;
+
+/// Clockwise quarter turns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Rotate {
+    #[value(name = "90")]
+    Quarter,
+    #[value(name = "180")]
+    Half,
+    #[value(name = "270")]
+    ThreeQuarter,
+}
+
+/// Mirror across the vertical axis (`h`, left for right) or the horizontal one (`v`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Flip {
+    H,
+    V,
+}
+
+impl std::str::FromStr for Rotate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+impl std::str::FromStr for Flip {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+/// A rotation followed by a flip; the default leaves the image alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    pub rotate: Option<Rotate>,
+    pub flip: Option<Flip>,
+}
+
+impl Orientation {
+    pub fn is_set(&self) -> bool {
+        self.rotate.is_some() || self.flip.is_some()
+    }
+
+    /// `img` turned and mirrored.
+    pub fn apply(&self, img: &Canvas) -> Canvas {
+        let turned = match self.rotate {
+            None => img.clone(),
+            Some(Rotate::Quarter) => imageops::rotate90(img),
+            Some(Rotate::Half) => imageops::rotate180(img),
+            Some(Rotate::ThreeQuarter) => imageops::rotate270(img),
+        };
+        match self.flip {
+            None => turned,
+            Some(Flip::H) => imageops::flip_horizontal(&turned),
+            Some(Flip::V) => imageops::flip_vertical(&turned),
+        }
+    }
+
+    /// Where the rectangle (x, y, w, h) of a `width` by `height` image lands.
+    pub fn rect(
+        &self,
+        (x, y, w, h): (u32, u32, u32, u32),
+        width: u32,
+        height: u32,
+    ) -> (u32, u32, u32, u32) {
+        let (rect, width, height) = match self.rotate {
+            None => ((x, y, w, h), width, height),
+            Some(Rotate::Quarter) => ((height - y - h, x, h, w), height, width),
+            Some(Rotate::Half) => ((width - x - w, height - y - h, w, h), width, height),
+            Some(Rotate::ThreeQuarter) => ((y, width - x - w, h, w), height, width),
+        };
+        let (x, y, w, h) = rect;
+        match self.flip {
+            None => rect,
+            Some(Flip::H) => (width - x - w, y, w, h),
+            Some(Flip::V) => (x, height - y - h, w, h),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_rect_follows_pixels() {
+        // A 5x3 image with a 2x1 mark at (1, 2).
+        let mut img = Canvas::from_pixel(5, 3, Rgba([255, 255, 255, 255]));
+        for x in 1..3 {
+            img.put_pixel(x, 2, Rgba([0, 0, 0, 255]));
+        }
+        let rotations = [
+            None,
+            Some(Rotate::Quarter),
+            Some(Rotate::Half),
+            Some(Rotate::ThreeQuarter),
+        ];
+        for rotate in rotations {
+            for flip in [None, Some(Flip::H), Some(Flip::V)] {
+                let orientation = Orientation { rotate, flip };
+                let out = orientation.apply(&img);
+                let (x, y, w, h) = orientation.rect((1, 2, 2, 1), 5, 3);
+                let dark: Vec<(u32, u32)> = out
+                    .enumerate_pixels()
+                    .filter(|(_, _, p)| p.0[0] == 0)
+                    .map(|(px, py, _)| (px, py))
+                    .collect();
+                let expected: Vec<(u32, u32)> = (y..y + h)
+                    .flat_map(|py| (x..x + w).map(move |px| (px, py)))
+                    .collect();
+                assert_eq!(dark, expected, "{:?}", orientation);
+            }
+        }
+        assert_eq!("270".parse::<Rotate>(), Ok(Rotate::ThreeQuarter));
+        assert!("45".parse::<Rotate>().is_err());
+    }
+}
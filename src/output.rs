@@ -0,0 +1,480 @@
+//! Output encoders for the final composed image, and the system clipboard.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::i18n;
+use crate::raster::Canvas;
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use image::codecs::gif::GifEncoder;
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use image::{ExtendedColorType, ImageBuffer, ImageEncoder, ImageFormat, Rgb, Rgba, imageops};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// What `--out` is written as.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A single raster image: PNG, or JPEG or GIF for a `.jpg`, `.jpeg` or `.gif` name
+    Png,
+    /// A multi-resolution icon (16, 32, 48 and 64 px)
+    Ico,
+    /// Encapsulated PostScript of the module grid, sized by --print-size
+    Eps,
+    /// TIFF in RGB, or in CMYK with --colorspace cmyk
+    Tiff,
+    /// Scalable vector graphics of the module grid, as with eps
+    Svg,
+    /// A one-page PDF of the image, sized by --print-size
+    Pdf,
+}
+
+impl Format {
+    /// The format `--out` asks for by its extension; a name without one is a PNG.
+    pub fn of_path(path: &Path) -> Result<Self> {
+        let Some(ext) = path.extension() else {
+            return Ok(Self::Png);
+        };
+        let ext = ext.to_string_lossy().to_ascii_lowercase();
+        Ok(match ext.as_str() {
+            "png" | "jpg" | "jpeg" | "gif" => Self::Png,
+            "ico" => Self::Ico,
+            "eps" => Self::Eps,
+            "tif" | "tiff" => Self::Tiff,
+            "svg" => Self::Svg,
+            "pdf" => Self::Pdf,
+            _ => {
+                return Err(anyhow!(i18n::tr(
+                    "out-extension",
+                    &[
+                        ("path", path.display().to_string().into()),
+                        ("ext", ext.into())
+                    ]
+                )))
+                .code(ErrorCode::Usage);
+            }
+        })
+    }
+
+    /// Whether the format is printed at `--print-size`, with room for `--bleed` and
+    /// `--crop-marks`.
+    pub fn is_physical(self) -> bool {
+        matches!(self, Self::Eps | Self::Tiff | Self::Pdf)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Ico => "ico",
+            Self::Eps => "eps",
+            Self::Tiff => "tif",
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+/// Icon sizes bundled into an `.ico`.
+pub const ICO_SIZES: [u32; 4] = [16, 32, 48, 64];
+
+/// `img` scaled to fit a `size` x `size` square, centered on transparency.
+pub fn icon(img: &Canvas, size: u32) -> Canvas {
+    let (w, h) = img.dimensions();
+    let scale = size as f32 / w.max(h) as f32;
+    let fit = |side: u32| ((side as f32 * scale).round() as u32).clamp(1, size);
+    let scaled = imageops::resize(img, fit(w), fit(h), imageops::FilterType::Lanczos3);
+    let mut square = Canvas::new(size, size);
+    let (x, y) = ((size - scaled.width()) / 2, (size - scaled.height()) / 2);
+    imageops::overlay(&mut square, &scaled, x.into(), y.into());
+    square
+}
+
+/// Write `img` as an `.ico` holding one PNG-compressed [`icon`] per [`ICO_SIZES`].
+pub fn save_ico(img: &Canvas, path: &str) -> Result<()> {
+    let frames = ICO_SIZES
+        .iter()
+        .map(|&size| {
+            let icon = icon(img, size);
+            IcoFrame::as_png(icon.as_raw(), size, size, ExtendedColorType::Rgba8)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to encode icon: {}", path))?;
+    let file = File::create(path).with_context(|| format!("Failed to create icon: {}", path))?;
+    IcoEncoder::new(BufWriter::new(file))
+        .encode_images(&frames)
+        .with_context(|| format!("Failed to write icon: {}", path))
+}
+
+/// The raster format other than PNG that `path` names by extension, JPEG or GIF;
+/// [`Format::Png`] writes these instead of a PNG.
+pub fn other_raster(path: impl AsRef<Path>) -> Option<ImageFormat> {
+    match ImageFormat::from_path(path).ok()? {
+        format @ (ImageFormat::Jpeg | ImageFormat::Gif) => Some(format),
+        _ => None,
+    }
+}
+
+/// Write `img` as JPEG or GIF, which carry no text chunks. JPEG has no alpha, so the
+/// image is flattened onto white; it is tagged with `icc` and `dpi` when given.
+pub fn save_raster(
+    img: &Canvas,
+    path: &str,
+    format: ImageFormat,
+    icc: Option<&[u8]>,
+    dpi: Option<f32>,
+) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create image: {}", path))?;
+    let out = BufWriter::new(file);
+    let written = if format == ImageFormat::Jpeg {
+        let flat = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            let Rgba([r, g, b, a]) = *img.get_pixel(x, y);
+            let over_white = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+            Rgb([over_white(r), over_white(g), over_white(b)])
+        });
+        let mut encoder = JpegEncoder::new_with_quality(out, 95);
+        if let Some(dpi) = dpi {
+            encoder.set_pixel_density(PixelDensity::dpi(dpi.round() as u16));
+        }
+        if let Some(icc) = icc {
+            encoder
+                .set_icc_profile(icc.to_vec())
+                .with_context(|| format!("Failed to embed the profile in {}", path))?;
+        }
+        encoder.encode_image(&flat)
+    } else {
+        GifEncoder::new(out).encode(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+            ExtendedColorType::Rgba8,
+        )
+    };
+    written.with_context(|| format!("Failed to write image: {}", path))
+}
+
+/// Where to write `path` under `--no-clobber` and `--unique`, given which paths are
+/// `taken` (on disk, or claimed earlier in the same run). A taken path is refused
+/// with `no_clobber`, and with `unique` becomes `<stem>-<tag>.<ext>`, then
+/// `<stem>-<tag>-2.<ext>` and so on until one is free.
+pub fn claim_path(
+    path: &Path,
+    tag: &str,
+    no_clobber: bool,
+    unique: bool,
+    taken: impl Fn(&Path) -> bool,
+) -> Result<PathBuf> {
+    if !taken(path) {
+        return Ok(path.to_path_buf());
+    }
+    if unique {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = path.extension().map(|e| e.to_string_lossy());
+        for n in 1.. {
+            let mut name = format!("{}-{}", stem, tag);
+            if n > 1 {
+                name += &format!("-{}", n);
+            }
+            if let Some(ext) = &ext {
+                name += &format!(".{}", ext);
+            }
+            let candidate = path.with_file_name(name);
+            if !taken(&candidate) {
+                return Ok(candidate);
+            }
+        }
+    }
+    if no_clobber {
+        return Err(anyhow!(
+            "{} already exists (--no-clobber); remove it or pass --unique to write a new name",
+            path.display()
+        ))
+        .code(ErrorCode::OutputExists);
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Write an RGBA image as a PNG file with the given textual metadata chunks.
+pub fn save_png_with_text(
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    path: &str,
+    text: &[(&str, &str)],
+    icc: Option<&[u8]>,
+    dpi: Option<f32>,
+) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create output PNG: {}", path))?;
+    write_png_with_text(img, BufWriter::new(file), text, icc, dpi, path)
+}
+
+/// Encode an RGBA image as PNG with the given textual metadata chunks; `name` labels errors.
+pub fn write_png_with_text(
+    img: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    out: impl Write,
+    text: &[(&str, &str)],
+    icc: Option<&[u8]>,
+    dpi: Option<f32>,
+    name: &str,
+) -> Result<()> {
+    let mut writer = png_writer(out, img.width(), img.height(), text, icc, dpi, name)?;
+    writer
+        .write_image_data(img.as_raw())
+        .with_context(|| format!("Failed to write output PNG: {}", name))?;
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finish output PNG: {}", name))
+}
+
+/// Start an RGBA PNG of `width` x `height` with the given textual metadata chunks, its
+/// header written and ready for pixel data. Latin-1 compatible text goes into `tEXt`;
+/// anything else uses UTF-8 `iTXt`. The pixels are tagged as sRGB, or with the `icc`
+/// profile if given, and with `dpi` in `pHYs`.
+pub fn png_writer<W: Write>(
+    out: W,
+    width: u32,
+    height: u32,
+    text: &[(&str, &str)],
+    icc: Option<&[u8]>,
+    dpi: Option<f32>,
+    name: &str,
+) -> Result<png::Writer<W>> {
+    let mut info = png::Info::with_size(width, height);
+    info.color_type = png::ColorType::Rgba;
+    info.bit_depth = png::BitDepth::Eight;
+    info.icc_profile = icc.map(Cow::Borrowed);
+    info.pixel_dims = dpi.map(|dpi| {
+        let per_metre = (dpi / 0.0254).round() as u32;
+        png::PixelDimensions {
+            xppu: per_metre,
+            yppu: per_metre,
+            unit: png::Unit::Meter,
+        }
+    });
+    let mut encoder = png::Encoder::with_info(out, info)
+        .with_context(|| format!("Failed to start output PNG: {}", name))?;
+    if icc.is_none() {
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    }
+
+    for (keyword, value) in text {
+        let added = if is_latin1(value) {
+            encoder.add_text_chunk(keyword.to_string(), value.to_string())
+        } else {
+            encoder.add_itxt_chunk(keyword.to_string(), value.to_string())
+        };
+        added.with_context(|| format!("Failed to add PNG text chunk: {}", keyword))?;
+    }
+
+    encoder
+        .write_header()
+        .with_context(|| format!("Failed to write PNG header: {}", name))
+}
+
+/// Read a `tEXt`/`iTXt` value from an existing PNG, if the file and chunk exist.
+pub fn read_png_text(path: &Path, keyword: &str) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info().ok()?;
+    let info = reader.info();
+    info.uncompressed_latin1_text
+        .iter()
+        .find(|t| t.keyword == keyword)
+        .map(|t| t.text.clone())
+        .or_else(|| {
+            info.utf8_text
+                .iter()
+                .find(|t| t.keyword == keyword)
+                .and_then(|t| t.get_text().ok())
+        })
+}
+
+/// Put `img` on the system clipboard. On Linux the clipboard is owned by the process
+/// that set it, so this waits until a clipboard manager or another copy takes it over.
+#[cfg(feature = "clipboard")]
+pub fn copy_to_clipboard(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<()> {
+    let image = arboard::ImageData {
+        width: img.width() as usize,
+        height: img.height() as usize,
+        bytes: img.as_raw().into(),
+    };
+    let mut clipboard = arboard::Clipboard::new().context("Failed to open the clipboard")?;
+    #[cfg(target_os = "linux")]
+    let copied = {
+        use arboard::SetExtLinux;
+        crate::log::info("Holding the clipboard until it is pasted elsewhere or replaced...");
+        clipboard.set().wait().image(image)
+    };
+    #[cfg(not(target_os = "linux"))]
+    let copied = clipboard.set_image(image);
+    copied.context("Failed to copy the image to the clipboard")
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy_to_clipboard(_img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<()> {
+    anyhow::bail!("--clipboard requires qrbrand to be built with the `clipboard` feature")
+}
+
+/// The text on the system clipboard, trimmed.
+#[cfg(feature = "clipboard")]
+pub fn clipboard_text() -> Result<String> {
+    let text = arboard::Clipboard::new()
+        .context("Failed to open the clipboard")?
+        .get_text()
+        .context("The clipboard holds no text")?;
+    Ok(text.trim().to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn clipboard_text() -> Result<String> {
+    anyhow::bail!("--url-from-clipboard requires qrbrand to be built with the `clipboard` feature")
+}
+
+fn is_latin1(s: &str) -> bool {
+    s.chars().all(|c| (c as u32) < 0x100 && c != '\0')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_text_chunks_roundtrip() {
+        let img = ImageBuffer::from_pixel(4, 4, Rgba([255, 255, 255, 255]));
+        let path = std::env::temp_dir().join("qrbrand_text_chunks.png");
+        let path = path.to_str().unwrap();
+
+        save_png_with_text(
+            &img,
+            path,
+            &[("Title", "Menu"), ("Description", "メニュー")],
+            None,
+            Some(300.0),
+        )
+        .expect("Failed to write PNG");
+
+        assert_eq!(
+            read_png_text(Path::new(path), "Title").as_deref(),
+            Some("Menu")
+        );
+        assert_eq!(
+            read_png_text(Path::new(path), "Description").as_deref(),
+            Some("メニュー")
+        );
+        assert_eq!(read_png_text(Path::new(path), "Missing"), None);
+
+        let decoder = png::Decoder::new(BufReader::new(File::open(path).unwrap()));
+        let reader = decoder.read_info().expect("Failed to read PNG");
+        let info = reader.info();
+        assert_eq!(info.uncompressed_latin1_text[0].keyword, "Title");
+        assert_eq!(info.uncompressed_latin1_text[0].text, "Menu");
+        assert_eq!(info.utf8_text[0].keyword, "Description");
+        assert_eq!(info.pixel_dims.map(|dims| dims.xppu), Some(11811));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_png_color_profile() {
+        let img = ImageBuffer::from_pixel(2, 2, Rgba([29, 78, 216, 255]));
+        let info = |icc: Option<&[u8]>| {
+            let mut bytes = Vec::new();
+            write_png_with_text(&img, &mut bytes, &[], icc, None, "test").unwrap();
+            let reader = png::Decoder::new(std::io::Cursor::new(bytes))
+                .read_info()
+                .unwrap();
+            let info = reader.info();
+            (info.srgb, info.icc_profile.as_deref().map(<[u8]>::to_vec))
+        };
+        // sRGB by default; a given profile replaces it.
+        assert_eq!(
+            info(None),
+            (Some(png::SrgbRenderingIntent::Perceptual), None)
+        );
+        let profile = crate::icc::srgb();
+        assert_eq!(info(Some(&profile)), (None, Some(profile.clone())));
+    }
+
+    #[test]
+    fn test_save_raster() {
+        let mut img = ImageBuffer::from_pixel(8, 8, Rgba([0, 0, 0, 255]));
+        img.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        let dir = std::env::temp_dir();
+        for name in ["qrbrand_raster.JPG", "qrbrand_raster.gif"] {
+            let path = dir.join(name);
+            let path = path.to_str().unwrap();
+            let format = other_raster(path).unwrap();
+            save_raster(&img, path, format, None, Some(300.0)).unwrap();
+            let read = image::ImageReader::open(path)
+                .unwrap()
+                .with_guessed_format()
+                .unwrap();
+            assert_eq!(read.format(), Some(format));
+            let decoded = read.decode().unwrap().to_rgba8();
+            assert!(decoded.get_pixel(4, 4)[0] < 16);
+            if format == ImageFormat::Jpeg {
+                // Transparency is flattened onto white.
+                assert!(decoded.get_pixel(0, 0)[0] > 200);
+            }
+            let _ = std::fs::remove_file(path);
+        }
+        assert_eq!(other_raster("qr.png"), None);
+        assert_eq!(other_raster("qr"), None);
+    }
+
+    #[test]
+    fn test_save_ico() {
+        // A wide image (as with a caption band) is letterboxed into each square.
+        let img = ImageBuffer::from_pixel(300, 200, Rgba([0, 0, 0, 255]));
+        let path = std::env::temp_dir().join("qrbrand_favicon.ico");
+        let path = path.to_str().unwrap();
+        save_ico(&img, path).unwrap();
+
+        let bytes = std::fs::read(path).unwrap();
+        assert_eq!(
+            u16::from_le_bytes([bytes[4], bytes[5]]),
+            ICO_SIZES.len() as u16
+        );
+        let largest = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        assert_eq!(largest.dimensions(), (64, 64));
+        assert_eq!(largest.get_pixel(32, 2)[3], 0);
+        assert_eq!(*largest.get_pixel(32, 32), Rgba([0, 0, 0, 255]));
+        let of = |path: &str| Format::of_path(Path::new(path)).ok();
+        assert_eq!(of("site/Favicon.ICO"), Some(Format::Ico));
+        assert_eq!(of("qr.jpg"), Some(Format::Png));
+        assert_eq!(of("qr"), Some(Format::Png));
+        assert_eq!(of("sign.eps"), Some(Format::Eps));
+        assert_eq!(of("print/Code.PDF"), Some(Format::Pdf));
+        assert_eq!(of("qr.svg"), Some(Format::Svg));
+        assert_eq!(of("qr.webp"), None);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_claim_path() {
+        let taken = |path: &Path| {
+            ["out/a.png", "out/a-1a2b3c4d.png", "out/b"].contains(&path.to_str().unwrap())
+        };
+        let claim = |path, no_clobber, unique| {
+            claim_path(Path::new(path), "1a2b3c4d", no_clobber, unique, taken)
+        };
+        assert_eq!(
+            claim("out/new.png", true, false).unwrap(),
+            Path::new("out/new.png")
+        );
+        assert_eq!(
+            claim("out/a.png", false, false).unwrap(),
+            Path::new("out/a.png")
+        );
+        let err = claim("out/a.png", true, false).unwrap_err();
+        assert_eq!(ErrorCode::of(&err), ErrorCode::OutputExists);
+        assert_eq!(
+            claim("out/a.png", true, true).unwrap(),
+            Path::new("out/a-1a2b3c4d-2.png")
+        );
+        assert_eq!(
+            claim("out/b", false, true).unwrap(),
+            Path::new("out/b-1a2b3c4d")
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! `--debug-overlay`: the module grid and function patterns drawn over the output.
+//!
+//! Finder, alignment and timing patterns are tinted, the logo plate area is shaded and
+//! a line runs along every module boundary, so styling that eats into a pattern or a
+//! plate that straddles modules is visible at a glance. Tints are translucent, so the
+//! modules under them still show.
+
+use crate::layout;
+use crate::raster::{self, Canvas};
+use crate::symbol::Symbol;
+use image::Rgba;
+use qrcode::Version;
+
+const FINDER: Rgba<u8> = Rgba([37, 99, 235, 110]);
+const ALIGNMENT: Rgba<u8> = Rgba([22, 163, 74, 110]);
+const TIMING: Rgba<u8> = Rgba([234, 88, 12, 110]);
+const PLATE: Rgba<u8> = Rgba([217, 70, 239, 90]);
+const GRID: Rgba<u8> = Rgba([0, 180, 216, 140]);
+/// Below this many pixels per module the grid would cover the modules.
+const MIN_GRID_PPM: u32 = 4;
+
+/// Draw the overlay onto `img` for `code`, rendered in the `qr_rect` square with
+/// `quiet` modules of quiet zone and the logo plate at `plate` within the square.
+pub fn draw(
+    img: &mut Canvas,
+    code: &Symbol,
+    (qx, qy, side): (u32, u32, u32),
+    quiet: u32,
+    plate: Option<(u32, u32, u32, u32)>,
+) {
+    let n = code.width() as u32;
+    let ppm = side / (n + 2 * quiet);
+    let (x0, y0) = (qx + quiet * ppm, qy + quiet * ppm);
+    let mut tint = |x: u32, y: u32, w: u32, h: u32, color: Rgba<u8>| {
+        for row in y0 + y * ppm..y0 + (y + h) * ppm {
+            raster::fill_row(img, row, x0 + x * ppm..x0 + (x + w) * ppm, color);
+        }
+    };
+
+    match code.version() {
+        Version::Micro(_) => {
+            tint(0, 0, 7, 7, FINDER);
+            tint(8, 0, n - 8, 1, TIMING);
+            tint(0, 8, 1, n - 8, TIMING);
+        }
+        Version::Normal(v) => {
+            for (x, y) in [(0, 0), (n - 7, 0), (0, n - 7)] {
+                tint(x, y, 7, 7, FINDER);
+            }
+            tint(8, 6, n - 16, 1, TIMING);
+            tint(6, 8, 1, n - 16, TIMING);
+            let centres = layout::alignment_positions(v);
+            let last = centres.last().copied().unwrap_or(0);
+            for &cy in &centres {
+                for &cx in &centres {
+                    // The corners that would sit on a finder pattern are left out.
+                    let on_finder = [(6, 6), (6, last), (last, 6)].contains(&(cx, cy));
+                    if !on_finder {
+                        tint(cx as u32 - 2, cy as u32 - 2, 5, 5, ALIGNMENT);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some((px, py, pw, ph)) = plate {
+        for row in qy + py..qy + py + ph {
+            raster::fill_row(img, row, qx + px..qx + px + pw, PLATE);
+        }
+    }
+
+    if ppm >= MIN_GRID_PPM {
+        let span = n * ppm;
+        for i in 0..=n {
+            let at = i * ppm;
+            let x = (x0 + at).min(x0 + span - 1);
+            for row in y0..y0 + span {
+                raster::fill_row(img, row, x..x + 1, GRID);
+            }
+            let y = (y0 + at).min(y0 + span - 1);
+            raster::fill_row(img, y, x0..x0 + span, GRID);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::render_qr_rgba;
+
+    #[test]
+    fn test_draw() {
+        let code = crate::symbol::encode("https://example.com/abc", &Default::default()).unwrap();
+        let Version::Normal(v) = code.version() else {
+            panic!("expected a full-size QR code");
+        };
+        assert!(v >= 2);
+        let n = code.width() as u32;
+        let mut img = render_qr_rgba(&code, 1000, 4, Color::BLACK).unwrap();
+        let side = img.width();
+        let ppm = side / (n + 8);
+        draw(&mut img, &code, (0, 0, side), 4, None);
+        let at = |x: u32, y: u32| *img.get_pixel((4 + x) * ppm + ppm / 2, (4 + y) * ppm + ppm / 2);
+        let tinted = |dark: bool, tint: Rgba<u8>| {
+            let under = if dark { Color::BLACK.0 } else { Color::WHITE.0 };
+            raster::composite(under, tint, 1.0)
+        };
+        assert_eq!(at(3, 3), tinted(true, FINDER));
+        assert_eq!(at(n - 6, 1), tinted(false, FINDER));
+        // The bottom-right alignment pattern is centred 7 modules in from the edges.
+        assert_eq!(at(n - 7, n - 7), tinted(true, ALIGNMENT));
+        assert_eq!(at(n - 6, n - 7), tinted(false, ALIGNMENT));
+        assert_eq!(at(8, 6), tinted(true, TIMING));
+        assert_eq!(at(6, 9), tinted(false, TIMING));
+        // A grid line runs along the left edge of the symbol.
+        assert_eq!(
+            *img.get_pixel(4 * ppm, 4 * ppm + ppm / 2),
+            raster::composite(tinted(true, FINDER), GRID, 1.0)
+        );
+    }
+}
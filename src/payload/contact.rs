@@ -0,0 +1,188 @@
+//! `mailto:`, SMS and `tel:` payloads with correct percent-encoding.
+
+use super::encode_component;
+use crate::i18n;
+use anyhow::{Result, bail};
+use clap::{Args, ValueEnum};
+
+#[derive(Args, Debug)]
+pub struct EmailArgs {
+    /// Recipient address (repeatable)
+    #[arg(long = "to", required = true)]
+    pub to: Vec<String>,
+
+    /// Carbon-copy address (repeatable)
+    #[arg(long = "cc")]
+    pub cc: Vec<String>,
+
+    /// Subject line
+    #[arg(long = "subject")]
+    pub subject: Option<String>,
+
+    /// Message body (newlines are preserved)
+    #[arg(long = "body")]
+    pub body: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct SmsArgs {
+    /// Recipient phone number (e.g. +15551234567)
+    #[arg(long = "to")]
+    pub to: String,
+
+    /// Pre-filled message body
+    #[arg(long = "body")]
+    pub body: Option<String>,
+
+    /// Payload syntax: SMSTO:number:body (widest scanner support) or RFC 5724 sms: URI
+    #[arg(long = "style", value_enum, default_value_t = SmsStyle::Smsto)]
+    pub style: SmsStyle,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SmsStyle {
+    Smsto,
+    Uri,
+}
+
+#[derive(Args, Debug)]
+pub struct TelArgs {
+    /// Phone number; spaces, dashes, dots and parentheses are stripped
+    #[arg(long = "to")]
+    pub to: String,
+}
+
+impl EmailArgs {
+    pub fn payload(&self) -> Result<String> {
+        for addr in self.to.iter().chain(&self.cc) {
+            validate_email(addr)?;
+        }
+
+        let recipients: Vec<String> = self.to.iter().map(|a| encode_address(a)).collect();
+        let mut query = Vec::new();
+        if !self.cc.is_empty() {
+            let cc: Vec<String> = self.cc.iter().map(|a| encode_address(a)).collect();
+            query.push(format!("cc={}", cc.join(",")));
+        }
+        if let Some(subject) = &self.subject {
+            query.push(format!("subject={}", encode_component(subject)));
+        }
+        if let Some(body) = &self.body {
+            query.push(format!("body={}", encode_component(&crlf(body))));
+        }
+
+        let mut uri = format!("mailto:{}", recipients.join(","));
+        if !query.is_empty() {
+            uri.push('?');
+            uri.push_str(&query.join("&"));
+        }
+        Ok(uri)
+    }
+}
+
+impl SmsArgs {
+    pub fn payload(&self) -> Result<String> {
+        let number = normalize_phone(&self.to)?;
+        let body = self.body.as_deref().unwrap_or("");
+        Ok(match self.style {
+            SmsStyle::Smsto => format!("SMSTO:{}:{}", number, body),
+            SmsStyle::Uri if body.is_empty() => format!("sms:{}", number),
+            SmsStyle::Uri => format!("sms:{}?body={}", number, encode_component(body)),
+        })
+    }
+}
+
+impl TelArgs {
+    pub fn payload(&self) -> Result<String> {
+        Ok(format!("tel:{}", normalize_phone(&self.to)?))
+    }
+}
+
+/// Strip visual separators and check the number is an optional `+` followed by digits.
+pub fn normalize_phone(number: &str) -> Result<String> {
+    let normalized: String = number
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '.' | '(' | ')'))
+        .collect();
+    let digits = normalized.strip_prefix('+').unwrap_or(&normalized);
+    if digits.len() < 3 || digits.len() > 15 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        bail!(i18n::tr("phone-invalid", &[("number", number.into())]));
+    }
+    Ok(normalized)
+}
+
+fn validate_email(addr: &str) -> Result<()> {
+    match addr.split_once('@') {
+        Some((local, domain))
+            if !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !addr.chars().any(|c| c.is_whitespace() || c == ',') =>
+        {
+            Ok(())
+        }
+        _ => bail!(i18n::tr("email-invalid", &[("address", addr.into())])),
+    }
+}
+
+/// Encode an address, keeping `@` readable as RFC 6068 allows.
+fn encode_address(addr: &str) -> String {
+    encode_component(addr).replace("%40", "@")
+}
+
+/// RFC 6068 line breaks in bodies are CRLF.
+fn crlf(s: &str) -> String {
+    s.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_payload() {
+        let email = EmailArgs {
+            to: vec!["sales@example.com".to_string()],
+            cc: vec!["boss+qr@example.com".to_string()],
+            subject: Some("Hello & welcome".to_string()),
+            body: Some("Line 1\nLine 2".to_string()),
+        };
+        assert_eq!(
+            email.payload().unwrap(),
+            "mailto:sales@example.com?cc=boss%2Bqr@example.com\
+             &subject=Hello%20%26%20welcome&body=Line%201%0D%0ALine%202"
+        );
+    }
+
+    #[test]
+    fn test_email_validation() {
+        assert!(validate_email("a@b.co").is_ok());
+        assert!(validate_email("not-an-email").is_err());
+        assert!(validate_email("a b@c.com").is_err());
+    }
+
+    #[test]
+    fn test_sms_payload() {
+        let mut sms = SmsArgs {
+            to: "+1 (555) 123-4567".to_string(),
+            body: Some("Table 4: ready?".to_string()),
+            style: SmsStyle::Smsto,
+        };
+        assert_eq!(sms.payload().unwrap(), "SMSTO:+15551234567:Table 4: ready?");
+        sms.style = SmsStyle::Uri;
+        assert_eq!(
+            sms.payload().unwrap(),
+            "sms:+15551234567?body=Table%204%3A%20ready%3F"
+        );
+    }
+
+    #[test]
+    fn test_tel_payload() {
+        let tel = TelArgs {
+            to: "+44 20 7946 0958".to_string(),
+        };
+        assert_eq!(tel.payload().unwrap(), "tel:+442079460958");
+        assert!(normalize_phone("call me").is_err());
+    }
+}
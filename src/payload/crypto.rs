@@ -0,0 +1,390 @@
+//! Cryptocurrency payment requests: BIP-21 `bitcoin:` and EIP-681 `ethereum:` URIs.
+//!
+//! Addresses are checked before encoding (Base58Check, Bech32/Bech32m and EIP-55
+//! checksums): a typo in a payment QR code sends money nowhere.
+
+use super::encode_component;
+use crate::{i18n, log};
+use anyhow::{Result, bail};
+use clap::Args;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+#[derive(Args, Debug)]
+pub struct BitcoinArgs {
+    /// Receiving address (legacy 1..., P2SH 3..., or bech32 bc1...; testnet also accepted)
+    #[arg(long = "address")]
+    pub address: String,
+
+    /// Amount in BTC (e.g. 0.0015), at most 8 decimals
+    #[arg(long = "amount")]
+    pub amount: Option<String>,
+
+    /// Recipient name shown by the wallet
+    #[arg(long = "label")]
+    pub label: Option<String>,
+
+    /// Note describing the payment
+    #[arg(long = "message")]
+    pub message: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct EthereumArgs {
+    /// Receiving address (0x followed by 40 hex digits; mixed case must be a valid EIP-55 checksum)
+    #[arg(long = "address")]
+    pub address: String,
+
+    /// Amount in ETH (e.g. 0.25), at most 18 decimals
+    #[arg(long = "amount")]
+    pub amount: Option<String>,
+
+    /// Chain ID (1 = Ethereum mainnet); omitted means the wallet's current chain
+    #[arg(long = "chain-id")]
+    pub chain_id: Option<u64>,
+}
+
+impl BitcoinArgs {
+    pub fn payload(&self) -> Result<String> {
+        bip21_uri(
+            &self.address,
+            self.amount.as_deref(),
+            self.label.as_deref(),
+            self.message.as_deref(),
+        )
+    }
+}
+
+impl EthereumArgs {
+    pub fn payload(&self) -> Result<String> {
+        eip681_uri(&self.address, self.amount.as_deref(), self.chain_id)
+    }
+}
+
+/// Build `bitcoin:<address>?amount=..&label=..&message=..` (BIP-21).
+pub fn bip21_uri(
+    address: &str,
+    amount: Option<&str>,
+    label: Option<&str>,
+    message: Option<&str>,
+) -> Result<String> {
+    let address = address.trim();
+    validate_bitcoin_address(address)?;
+
+    let mut params = Vec::new();
+    if let Some(amount) = amount {
+        params.push(format!("amount={}", decimal_amount(amount, 8, "BTC")?));
+    }
+    for (key, value) in [("label", label), ("message", message)] {
+        if let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) {
+            params.push(format!("{}={}", key, encode_component(value)));
+        }
+    }
+
+    let mut uri = format!("bitcoin:{}", address);
+    if !params.is_empty() {
+        uri.push('?');
+        uri.push_str(&params.join("&"));
+    }
+    Ok(uri)
+}
+
+/// Build `ethereum:<address>[@chain]?value=<wei>` (EIP-681) with the EIP-55 address.
+pub fn eip681_uri(address: &str, amount: Option<&str>, chain_id: Option<u64>) -> Result<String> {
+    let address = checksum_ethereum_address(address.trim())?;
+    let mut uri = format!("ethereum:{}", address);
+    if let Some(chain_id) = chain_id {
+        uri.push_str(&format!("@{}", chain_id));
+    }
+    if let Some(amount) = amount {
+        let eth = decimal_amount(amount, 18, "ETH")?;
+        uri.push_str(&format!("?value={}", to_base_units(&eth, 18)));
+    }
+    Ok(uri)
+}
+
+/// Validate a positive decimal amount with at most `decimals` fractional digits,
+/// returning it without surrounding whitespace.
+fn decimal_amount(amount: &str, decimals: usize, unit: &str) -> Result<String> {
+    let amount = amount.trim();
+    let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty()
+        || frac.len() > decimals
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !frac.chars().all(|c| c.is_ascii_digit())
+    {
+        bail!(i18n::tr(
+            "amount-invalid-decimals",
+            &[
+                ("amount", amount.into()),
+                ("unit", unit.into()),
+                ("decimals", decimals.into())
+            ]
+        ));
+    }
+    if whole.chars().chain(frac.chars()).all(|c| c == '0') {
+        bail!(i18n::tr("amount-not-positive", &[]));
+    }
+    Ok(amount.to_string())
+}
+
+/// Shift a validated decimal amount by `decimals` places into an integer string
+/// (ETH to wei) without going through floats.
+fn to_base_units(amount: &str, decimals: usize) -> String {
+    let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    let digits = format!("{}{:0<width$}", whole, frac, width = decimals);
+    let trimmed = digits.trim_start_matches('0');
+    if trimmed.is_empty() { "0" } else { trimmed }.to_string()
+}
+
+/// Check a Bitcoin address: Base58Check for legacy/P2SH, Bech32/Bech32m for SegWit.
+pub fn validate_bitcoin_address(address: &str) -> Result<()> {
+    let lower = address.to_ascii_lowercase();
+    if lower.starts_with("bc1") || lower.starts_with("tb1") {
+        return validate_segwit_address(address);
+    }
+
+    let Some(bytes) = base58_decode(address) else {
+        return Err(invalid_bitcoin("not-base58", address));
+    };
+    if bytes.len() != 25 {
+        return Err(invalid_bitcoin("wrong-length", address));
+    }
+    let (payload, checksum) = bytes.split_at(21);
+    if Sha256::digest(Sha256::digest(payload))[..4] != *checksum {
+        return Err(invalid_bitcoin("checksum", address));
+    }
+    // P2PKH and P2SH, mainnet and testnet.
+    if !matches!(payload[0], 0x00 | 0x05 | 0x6f | 0xc4) {
+        bail!(i18n::tr(
+            "bitcoin-version",
+            &[
+                ("version", format!("{:#04x}", payload[0]).into()),
+                ("address", address.into())
+            ]
+        ));
+    }
+    Ok(())
+}
+
+/// The error for a malformed Bitcoin address; `reason` selects the explanation in
+/// the `bitcoin-invalid` message.
+fn invalid_bitcoin(reason: &str, address: &str) -> anyhow::Error {
+    anyhow::anyhow!(i18n::tr(
+        "bitcoin-invalid",
+        &[("reason", reason.into()), ("address", address.into())]
+    ))
+}
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, (carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    let mut out = vec![0; zeros];
+    out.extend(bytes);
+    Some(out)
+}
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+/// BIP-173 (witness v0, Bech32) and BIP-350 (v1+, Bech32m) addresses.
+fn validate_segwit_address(address: &str) -> Result<()> {
+    let invalid = |reason: &str| invalid_bitcoin(reason, address);
+    if address != address.to_ascii_lowercase() && address != address.to_ascii_uppercase() {
+        return Err(invalid("mixed-case"));
+    }
+    let lower = address.to_ascii_lowercase();
+    let (hrp, data) = lower
+        .rsplit_once('1')
+        .ok_or_else(|| invalid("no-separator"))?;
+    let values: Vec<u8> = data
+        .bytes()
+        .map(|c| BECH32_CHARSET.iter().position(|&a| a == c).map(|v| v as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("bad-character"))?;
+    if values.len() < 7 {
+        return Err(invalid("too-short"));
+    }
+
+    let version = values[0];
+    let expected = if version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    };
+    let mut checked: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    checked.push(0);
+    checked.extend(hrp.bytes().map(|b| b & 31));
+    checked.extend(&values);
+    if bech32_polymod(&checked) != expected {
+        return Err(invalid("checksum"));
+    }
+
+    let program_len = (values.len() - 7) * 5 / 8;
+    let length_ok = match version {
+        0 => program_len == 20 || program_len == 32,
+        1..=16 => (2..=40).contains(&program_len),
+        _ => false,
+    };
+    if !length_ok {
+        return Err(invalid("witness-program"));
+    }
+    Ok(())
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GEN: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk: u32 = 1;
+    for &v in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ff_ffff) << 5) ^ v as u32;
+        for (i, g) in GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+/// Return the EIP-55 checksummed form of an Ethereum address. Mixed-case input must
+/// already carry a valid checksum; all-lowercase or all-uppercase input has none, so a
+/// warning is printed.
+pub fn checksum_ethereum_address(address: &str) -> Result<String> {
+    let Some(hex) = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+        .filter(|h| h.len() == 40 && h.chars().all(|c| c.is_ascii_hexdigit()))
+    else {
+        bail!(i18n::tr("ethereum-invalid", &[("address", address.into())]));
+    };
+
+    let lower = hex.to_ascii_lowercase();
+    let hash = Keccak256::digest(lower.as_bytes());
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    let mixed_case = hex != lower && hex != hex.to_ascii_uppercase();
+    if mixed_case && hex != checksummed {
+        bail!(i18n::tr(
+            "ethereum-checksum",
+            &[("address", address.into())]
+        ));
+    }
+    if !mixed_case {
+        log::warn(i18n::tr(
+            "ethereum-no-checksum",
+            &[("address", address.into())],
+        ));
+    }
+    Ok(format!("0x{}", checksummed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bip21_uri() {
+        assert_eq!(
+            bip21_uri(
+                "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+                Some("0.0015"),
+                Some("Coffee Shop"),
+                Some("Order #42"),
+            )
+            .unwrap(),
+            "bitcoin:1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2?amount=0.0015&label=Coffee%20Shop&message=Order%20%2342"
+        );
+        assert_eq!(
+            bip21_uri("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", None, None, None).unwrap(),
+            "bitcoin:3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"
+        );
+        assert!(
+            bip21_uri(
+                "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+                Some("1.123456789"),
+                None,
+                None
+            )
+            .is_err()
+        );
+        assert!(bip21_uri("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", Some("0"), None, None).is_err());
+    }
+
+    #[test]
+    fn test_bitcoin_address_checksums() {
+        assert!(validate_bitcoin_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").is_ok());
+        assert!(validate_bitcoin_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3").is_err());
+        assert!(validate_bitcoin_address("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq").is_ok());
+        assert!(validate_bitcoin_address("BC1QAR0SRRR7XFKVY5L643LYDNW9RE59GTZZWF5MDQ").is_ok());
+        assert!(validate_bitcoin_address("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdr").is_err());
+        assert!(
+            validate_bitcoin_address(
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"
+            )
+            .is_ok()
+        );
+        assert!(validate_bitcoin_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_err());
+    }
+
+    #[test]
+    fn test_eip55_checksum() {
+        // Test vectors from EIP-55.
+        for address in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            assert_eq!(checksum_ethereum_address(address).unwrap(), address);
+            assert_eq!(
+                checksum_ethereum_address(&address.to_ascii_lowercase().replace("0X", "0x"))
+                    .unwrap(),
+                address
+            );
+        }
+        assert!(checksum_ethereum_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(checksum_ethereum_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA").is_err());
+    }
+
+    #[test]
+    fn test_eip681_uri() {
+        assert_eq!(
+            eip681_uri(
+                "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+                Some("0.25"),
+                Some(1)
+            )
+            .unwrap(),
+            "ethereum:0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359@1?value=250000000000000000"
+        );
+        assert_eq!(to_base_units("12", 18), "12000000000000000000");
+        assert_eq!(to_base_units("0.000000000000000001", 18), "1");
+    }
+}
@@ -0,0 +1,221 @@
+//! EPC069-12 ("GiroCode") SEPA credit transfer payloads.
+
+use crate::i18n;
+use anyhow::{Result, bail};
+use clap::Args;
+
+/// The EPC guidelines cap the whole payload at 331 bytes.
+const MAX_PAYLOAD_BYTES: usize = 331;
+
+#[derive(Args, Debug)]
+pub struct EpcArgs {
+    /// Beneficiary name (max 70 characters)
+    #[arg(long = "name")]
+    pub name: String,
+
+    /// Beneficiary IBAN (spaces allowed)
+    #[arg(long = "iban")]
+    pub iban: String,
+
+    /// Beneficiary BIC (8 or 11 characters; optional inside the EEA)
+    #[arg(long = "bic")]
+    pub bic: Option<String>,
+
+    /// Amount in euros (e.g. 12.50), between 0.01 and 999999999.99
+    #[arg(long = "amount")]
+    pub amount: Option<String>,
+
+    /// Four-letter purpose code (e.g. CHAR, GDDS)
+    #[arg(long = "purpose")]
+    pub purpose: Option<String>,
+
+    /// Structured creditor reference, e.g. RF18539007547034 (max 35 characters)
+    #[arg(long = "reference", conflicts_with = "remittance")]
+    pub reference: Option<String>,
+
+    /// Unstructured remittance information (max 140 characters)
+    #[arg(long = "remittance", conflicts_with = "reference")]
+    pub remittance: Option<String>,
+
+    /// Beneficiary-to-originator note shown in the banking app (max 70 characters)
+    #[arg(long = "info")]
+    pub info: Option<String>,
+}
+
+impl EpcArgs {
+    pub fn payload(&self) -> Result<String> {
+        let iban = normalize_iban(&self.iban)?;
+        let bic = self.bic.as_deref().map(normalize_bic).transpose()?;
+        let amount = self.amount.as_deref().map(format_amount).transpose()?;
+
+        check_len("--name", &self.name, 1, 70)?;
+        if let Some(purpose) = &self.purpose
+            && (purpose.len() != 4 || !purpose.chars().all(|c| c.is_ascii_alphanumeric()))
+        {
+            bail!(i18n::tr(
+                "epc-purpose",
+                &[("purpose", purpose.as_str().into())]
+            ));
+        }
+        if let Some(reference) = &self.reference {
+            check_len("--reference", reference, 1, 35)?;
+        }
+        if let Some(remittance) = &self.remittance {
+            check_len("--remittance", remittance, 1, 140)?;
+        }
+        if let Some(info) = &self.info {
+            check_len("--info", info, 1, 70)?;
+        }
+
+        let lines = [
+            "BCD",
+            "002",
+            "1",
+            "SCT",
+            bic.as_deref().unwrap_or(""),
+            self.name.as_str(),
+            iban.as_str(),
+            amount.as_deref().unwrap_or(""),
+            self.purpose.as_deref().unwrap_or(""),
+            self.reference.as_deref().unwrap_or(""),
+            self.remittance.as_deref().unwrap_or(""),
+            self.info.as_deref().unwrap_or(""),
+        ];
+        if lines.iter().any(|l| l.contains('\n') || l.contains('\r')) {
+            bail!(i18n::tr("epc-line-break", &[]));
+        }
+
+        // Trailing empty optional fields are omitted.
+        let used = lines.iter().rposition(|l| !l.is_empty()).unwrap_or(0) + 1;
+        let payload = lines[..used].join("\n");
+        if payload.len() > MAX_PAYLOAD_BYTES {
+            bail!(i18n::tr(
+                "epc-too-long",
+                &[
+                    ("bytes", payload.len().into()),
+                    ("max", MAX_PAYLOAD_BYTES.into())
+                ]
+            ));
+        }
+        Ok(payload)
+    }
+}
+
+fn check_len(flag: &str, value: &str, min: usize, max: usize) -> Result<()> {
+    let n = value.chars().count();
+    if n < min || n > max {
+        bail!(i18n::tr(
+            "length-out-of-range",
+            &[
+                ("flag", flag.into()),
+                ("min", min.into()),
+                ("max", max.into()),
+                ("count", n.into())
+            ]
+        ));
+    }
+    Ok(())
+}
+
+/// Strip spaces, uppercase, and verify the ISO 13616 mod-97 checksum.
+pub fn normalize_iban(iban: &str) -> Result<String> {
+    let iban: String = iban
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if !(15..=34).contains(&iban.len()) || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        bail!(i18n::tr("iban-invalid", &[("iban", iban.as_str().into())]));
+    }
+    let (country, rest) = iban.split_at(2);
+    if !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        bail!(i18n::tr("iban-country", &[("country", country.into())]));
+    }
+
+    let rearranged = format!("{}{}", &iban[4..], &iban[..4]);
+    let mut remainder: u32 = 0;
+    for c in rearranged.chars() {
+        let value = c.to_digit(36).expect("checked alphanumeric");
+        remainder = if value >= 10 {
+            (remainder * 100 + value) % 97
+        } else {
+            (remainder * 10 + value) % 97
+        };
+    }
+    if remainder != 1 || !rest[..2].chars().all(|c| c.is_ascii_digit()) {
+        bail!(i18n::tr("iban-checksum", &[("iban", iban.as_str().into())]));
+    }
+    Ok(iban)
+}
+
+fn normalize_bic(bic: &str) -> Result<String> {
+    let bic = bic.trim().to_ascii_uppercase();
+    if !(bic.len() == 8 || bic.len() == 11) || !bic.chars().all(|c| c.is_ascii_alphanumeric()) {
+        bail!(i18n::tr("bic-invalid", &[("bic", bic.as_str().into())]));
+    }
+    Ok(bic)
+}
+
+/// Format an amount as `EUR<units>.<cents>` without going through floats.
+fn format_amount(amount: &str) -> Result<String> {
+    let amount = amount.trim().trim_start_matches("EUR").trim();
+    let (units, cents) = amount.split_once('.').unwrap_or((amount, ""));
+
+    if units.is_empty()
+        || units.len() > 9
+        || cents.len() > 2
+        || !units.chars().all(|c| c.is_ascii_digit())
+        || !cents.chars().all(|c| c.is_ascii_digit())
+    {
+        bail!(i18n::tr("epc-amount-invalid", &[("amount", amount.into())]));
+    }
+
+    let units: u64 = units.parse()?;
+    let cents: u64 = format!("{:0<2}", cents).parse()?;
+    if units == 0 && cents == 0 {
+        bail!(i18n::tr("epc-amount-too-small", &[]));
+    }
+    Ok(format!("EUR{}.{:02}", units, cents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args() -> EpcArgs {
+        EpcArgs {
+            name: "Red Cross".to_string(),
+            iban: "BE72 0000 0000 1616".to_string(),
+            bic: Some("bpotbeb1".to_string()),
+            amount: Some("1".to_string()),
+            purpose: None,
+            reference: None,
+            remittance: Some("Urgency fund".to_string()),
+            info: None,
+        }
+    }
+
+    #[test]
+    fn test_epc_payload() {
+        assert_eq!(
+            args().payload().unwrap(),
+            "BCD\n002\n1\nSCT\nBPOTBEB1\nRed Cross\nBE72000000001616\nEUR1.00\n\n\nUrgency fund"
+        );
+    }
+
+    #[test]
+    fn test_iban_checksum() {
+        assert!(normalize_iban("DE89 3704 0044 0532 0130 00").is_ok());
+        assert!(normalize_iban("DE89 3704 0044 0532 0130 01").is_err());
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount("12.5").unwrap(), "EUR12.50");
+        assert_eq!(format_amount("0.01").unwrap(), "EUR0.01");
+        assert!(format_amount("0").is_err());
+        assert!(format_amount("1.234").is_err());
+        assert!(format_amount("-3").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! iCalendar VEVENT payloads (RFC 5545) so scanners offer "Add to calendar".
+
+use crate::i18n;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub struct EventArgs {
+    /// Event title (SUMMARY)
+    #[arg(long = "title")]
+    pub title: String,
+
+    /// Start: YYYY-MM-DD (all-day), YYYY-MM-DDTHH:MM[:SS] (local/--tz), or RFC 3339 with Z/offset
+    #[arg(long = "start")]
+    pub start: String,
+
+    /// End, in the same form as --start
+    #[arg(long = "end")]
+    pub end: Option<String>,
+
+    /// Event location
+    #[arg(long = "location")]
+    pub location: Option<String>,
+
+    /// Longer description
+    #[arg(long = "description")]
+    pub description: Option<String>,
+
+    /// IANA timezone (e.g. Europe/Paris) for times given without an offset
+    #[arg(long = "tz")]
+    pub tz: Option<String>,
+}
+
+/// A parsed DTSTART/DTEND value.
+#[derive(Debug, PartialEq)]
+enum EventTime {
+    /// All-day date (`VALUE=DATE`).
+    Date(NaiveDate),
+    /// Wall-clock time, floating or bound to a TZID.
+    Local(NaiveDateTime),
+    /// Absolute time, written in UTC with a `Z` suffix.
+    Utc(DateTime<Utc>),
+}
+
+impl EventTime {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(Self::Date(date));
+        }
+        if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            return Ok(Self::Utc(dt.with_timezone(&Utc)));
+        }
+        for fmt in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(s, fmt) {
+                return Ok(Self::Local(dt));
+            }
+        }
+        bail!(i18n::tr("event-time-invalid", &[("time", s.into())]))
+    }
+
+    /// Render as a content line, e.g. `DTSTART;TZID=Europe/Paris:20250601T180000`.
+    fn property(&self, name: &str, tz: Option<&str>) -> String {
+        match (self, tz) {
+            (Self::Date(d), _) => format!("{};VALUE=DATE:{}", name, d.format("%Y%m%d")),
+            (Self::Local(dt), Some(tz)) => {
+                format!("{};TZID={}:{}", name, tz, dt.format("%Y%m%dT%H%M%S"))
+            }
+            (Self::Local(dt), None) => format!("{}:{}", name, dt.format("%Y%m%dT%H%M%S")),
+            (Self::Utc(dt), _) => format!("{}:{}", name, dt.format("%Y%m%dT%H%M%SZ")),
+        }
+    }
+
+    /// Comparable instant; local times are compared as-is since they share a zone.
+    fn sort_key(&self) -> NaiveDateTime {
+        match self {
+            Self::Date(d) => d.and_hms_opt(0, 0, 0).expect("midnight is valid"),
+            Self::Local(dt) => *dt,
+            Self::Utc(dt) => dt.naive_utc(),
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            Self::Date(_) => 0,
+            Self::Local(_) => 1,
+            Self::Utc(_) => 2,
+        }
+    }
+}
+
+impl EventArgs {
+    pub fn payload(&self) -> Result<String> {
+        if self.title.trim().is_empty() {
+            bail!(i18n::tr("event-title-empty", &[]));
+        }
+        let tz = self.tz.as_deref().map(str::trim);
+        if let Some(tz) = tz
+            && (tz.is_empty() || tz.contains([';', ':', ',', '"']))
+        {
+            bail!(i18n::tr("event-tz-invalid", &[("tz", tz.into())]));
+        }
+
+        let start =
+            EventTime::parse(&self.start).with_context(|| i18n::tr("event-start-invalid", &[]))?;
+        let end = self
+            .end
+            .as_deref()
+            .map(EventTime::parse)
+            .transpose()
+            .with_context(|| i18n::tr("event-end-invalid", &[]))?;
+
+        if let Some(end) = &end {
+            if end.kind() != start.kind() {
+                bail!(i18n::tr("event-mixed-forms", &[]));
+            }
+            if end.sort_key() <= start.sort_key() {
+                bail!(i18n::tr("event-end-before-start", &[]));
+            }
+        }
+
+        let mut lines = vec!["BEGIN:VEVENT".to_string()];
+        lines.push(format!("SUMMARY:{}", escape_text(&self.title)));
+        lines.push(start.property("DTSTART", tz));
+        if let Some(end) = &end {
+            lines.push(end.property("DTEND", tz));
+        }
+        if let Some(location) = &self.location {
+            lines.push(format!("LOCATION:{}", escape_text(location)));
+        }
+        if let Some(description) = &self.description {
+            lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        lines.push("END:VEVENT".to_string());
+
+        Ok(lines
+            .iter()
+            .map(|l| fold_line(l))
+            .collect::<Vec<_>>()
+            .join("\r\n"))
+    }
+}
+
+/// Escape TEXT values: backslash, semicolon, comma and newlines (RFC 5545 §3.3.11).
+pub fn escape_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Fold content lines longer than 75 octets (RFC 5545 §3.1), never splitting a UTF-8 char.
+fn fold_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(start: &str, end: Option<&str>) -> EventArgs {
+        EventArgs {
+            title: "Launch, party; v2".to_string(),
+            start: start.to_string(),
+            end: end.map(str::to_string),
+            location: Some("Room 1".to_string()),
+            description: None,
+            tz: None,
+        }
+    }
+
+    #[test]
+    fn test_event_payload_with_offset() {
+        let payload = args(
+            "2025-06-01T18:00:00+02:00",
+            Some("2025-06-01T20:00:00+02:00"),
+        )
+        .payload()
+        .unwrap();
+        assert_eq!(
+            payload,
+            "BEGIN:VEVENT\r\nSUMMARY:Launch\\, party\\; v2\r\nDTSTART:20250601T160000Z\r\n\
+             DTEND:20250601T180000Z\r\nLOCATION:Room 1\r\nEND:VEVENT"
+        );
+    }
+
+    #[test]
+    fn test_event_all_day_and_tz() {
+        let payload = args("2025-06-01", None).payload().unwrap();
+        assert!(payload.contains("DTSTART;VALUE=DATE:20250601"));
+
+        let mut with_tz = args("2025-06-01T18:00", None);
+        with_tz.tz = Some("Europe/Paris".to_string());
+        assert!(
+            with_tz
+                .payload()
+                .unwrap()
+                .contains("DTSTART;TZID=Europe/Paris:20250601T180000")
+        );
+    }
+
+    #[test]
+    fn test_event_validation() {
+        assert!(
+            args("2025-06-01T18:00", Some("2025-06-01T17:00"))
+                .payload()
+                .is_err()
+        );
+        assert!(
+            args("2025-06-01", Some("2025-06-02T10:00"))
+                .payload()
+                .is_err()
+        );
+        assert!(args("tomorrow", None).payload().is_err());
+    }
+
+    #[test]
+    fn test_fold_line() {
+        let long = "DESCRIPTION:".to_string() + &"é".repeat(40);
+        let folded = fold_line(&long);
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), long);
+    }
+}
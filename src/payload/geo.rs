@@ -0,0 +1,67 @@
+//! `geo:` URIs (RFC 5870) for map locations.
+
+use super::encode_component;
+use crate::i18n;
+use anyhow::{Result, bail};
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub struct GeoArgs {
+    /// Latitude in decimal degrees (-90..=90)
+    #[arg(long = "lat", allow_negative_numbers = true)]
+    pub lat: f64,
+
+    /// Longitude in decimal degrees (-180..=180)
+    #[arg(long = "lon", allow_negative_numbers = true)]
+    pub lon: f64,
+
+    /// Optional place label, shown as a pin name by most map apps
+    #[arg(long = "label")]
+    pub label: Option<String>,
+}
+
+impl GeoArgs {
+    pub fn payload(&self) -> Result<String> {
+        geo_uri(self.lat, self.lon, self.label.as_deref())
+    }
+}
+
+/// Build `geo:lat,lon`, adding the widely supported `?q=lat,lon(label)` form when labelled.
+pub fn geo_uri(lat: f64, lon: f64, label: Option<&str>) -> Result<String> {
+    if !lat.is_finite() || !(-90.0..=90.0).contains(&lat) {
+        bail!(i18n::tr("geo-lat", &[("lat", lat.into())]));
+    }
+    if !lon.is_finite() || !(-180.0..=180.0).contains(&lon) {
+        bail!(i18n::tr("geo-lon", &[("lon", lon.into())]));
+    }
+
+    let coords = format!("{},{}", lat, lon);
+    Ok(match label.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(label) => format!("geo:{}?q={}({})", coords, coords, encode_component(label)),
+        None => format!("geo:{}", coords),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geo_uri() {
+        assert_eq!(
+            geo_uri(48.8584, 2.2945, Some("Eiffel Tower")).unwrap(),
+            "geo:48.8584,2.2945?q=48.8584,2.2945(Eiffel%20Tower)"
+        );
+        assert_eq!(
+            geo_uri(-33.8568, 151.2153, None).unwrap(),
+            "geo:-33.8568,151.2153"
+        );
+    }
+
+    #[test]
+    fn test_geo_validation() {
+        assert!(geo_uri(91.0, 0.0, None).is_err());
+        assert!(geo_uri(0.0, -180.5, None).is_err());
+        assert!(geo_uri(f64::NAN, 0.0, None).is_err());
+    }
+}
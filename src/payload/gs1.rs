@@ -0,0 +1,242 @@
+//! GS1 data for retail and point-of-sale scanners.
+//!
+//! Element strings such as `(01)09506000134352(10)ABC123` are encoded without the
+//! parentheses, with a GS separator after variable-length fields, and the symbol is
+//! flagged with FNC1 so scanners report them as GS1 data. GS1 Digital Link URIs are
+//! plain URLs by definition and are encoded without FNC1, as the standard requires.
+
+use crate::i18n;
+use crate::segment::GS;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use url::Url;
+
+#[derive(Args, Debug)]
+pub struct Gs1Args {
+    /// Element string with AIs in parentheses, e.g. "(01)09506000134352(17)261231(10)ABC123"
+    #[arg(
+        long = "element-string",
+        required_unless_present = "digital_link",
+        conflicts_with = "digital_link"
+    )]
+    pub element_string: Option<String>,
+
+    /// GS1 Digital Link URI, e.g. https://id.gs1.org/01/09506000134352/10/ABC123
+    #[arg(long = "digital-link")]
+    pub digital_link: Option<String>,
+}
+
+impl Gs1Args {
+    pub fn payload(&self) -> Result<String> {
+        match (&self.element_string, &self.digital_link) {
+            (Some(elements), _) => element_string(elements),
+            (None, Some(uri)) => digital_link(uri),
+            (None, None) => bail!(i18n::tr("gs1-required", &[])),
+        }
+    }
+
+    /// Element strings need the FNC1 mode indicator; Digital Link URIs must not have it.
+    pub fn fnc1(&self) -> bool {
+        self.element_string.is_some()
+    }
+}
+
+/// Data length of AIs whose length is fixed by their first two digits
+/// (GS1 General Specifications, "predefined length" table). Such fields need no separator.
+fn predefined_length(ai: &str) -> Option<usize> {
+    Some(match &ai[..2] {
+        "00" => 18,
+        "01" | "02" | "03" => 14,
+        "04" => 16,
+        "11" | "12" | "13" | "14" | "15" | "16" | "17" | "18" | "19" => 6,
+        "20" => 2,
+        "31" | "32" | "33" | "34" | "35" | "36" => 6,
+        "41" => 13,
+        _ => return None,
+    })
+}
+
+/// Expected AI length for the predefined-length prefixes.
+fn ai_length(ai: &str) -> Option<usize> {
+    match &ai[..2] {
+        "31" | "32" | "33" | "34" | "35" | "36" => Some(4),
+        "41" => Some(3),
+        _ if predefined_length(ai).is_some() => Some(2),
+        _ => None,
+    }
+}
+
+/// Convert a bracketed element string into the encoded form, validating lengths,
+/// the character set and check digits.
+pub fn element_string(input: &str) -> Result<String> {
+    let mut fields: Vec<(&str, &str)> = Vec::new();
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let Some(after_open) = rest.strip_prefix('(') else {
+            bail!(i18n::tr("gs1-expected-paren", &[("rest", rest.into())]));
+        };
+        let (ai, after_ai) = after_open
+            .split_once(')')
+            .with_context(|| i18n::tr("gs1-unclosed-paren", &[]))?;
+        let end = after_ai.find('(').unwrap_or(after_ai.len());
+        fields.push((ai, &after_ai[..end]));
+        rest = &after_ai[end..];
+    }
+    if fields.is_empty() {
+        bail!(i18n::tr("gs1-empty", &[]));
+    }
+
+    let mut encoded = String::new();
+    for (i, &(ai, value)) in fields.iter().enumerate() {
+        if !(2..=4).contains(&ai.len()) || !ai.bytes().all(|b| b.is_ascii_digit()) {
+            bail!(i18n::tr("gs1-ai-invalid", &[("ai", ai.into())]));
+        }
+        if let Some(expected) = ai_length(ai)
+            && ai.len() != expected
+        {
+            bail!(i18n::tr(
+                "gs1-ai-length",
+                &[("ai", ai.into()), ("digits", expected.into())]
+            ));
+        }
+        if value.is_empty() {
+            bail!(i18n::tr("gs1-ai-no-value", &[("ai", ai.into())]));
+        }
+        if let Some(bad) = value.chars().find(|&c| !is_cset82(c)) {
+            bail!(i18n::tr(
+                "gs1-ai-character",
+                &[("ai", ai.into()), ("char", format!("{:?}", bad).into())]
+            ));
+        }
+
+        let fixed = predefined_length(ai);
+        if let Some(len) = fixed {
+            if value.len() != len || !value.bytes().all(|b| b.is_ascii_digit()) {
+                bail!(i18n::tr(
+                    "gs1-ai-fixed",
+                    &[
+                        ("ai", ai.into()),
+                        ("digits", len.into()),
+                        ("value", value.into())
+                    ]
+                ));
+            }
+            if matches!(&ai[..2], "00" | "01" | "02" | "41") && !check_digit_ok(value) {
+                bail!(i18n::tr(
+                    "gs1-ai-check-digit",
+                    &[("ai", ai.into()), ("value", value.into())]
+                ));
+            }
+        }
+
+        encoded.push_str(ai);
+        encoded.push_str(value);
+        if fixed.is_none() && i + 1 < fields.len() {
+            encoded.push(GS);
+        }
+    }
+    Ok(encoded)
+}
+
+/// Validate a Digital Link URI: it must carry a primary key such as `/01/<GTIN>`.
+pub fn digital_link(uri: &str) -> Result<String> {
+    let url = Url::parse(uri)
+        .with_context(|| i18n::tr("digital-link-invalid", &[("uri", uri.into())]))?;
+    if !matches!(url.scheme(), "https" | "http") {
+        bail!(i18n::tr(
+            "digital-link-scheme",
+            &[("scheme", url.scheme().into())]
+        ));
+    }
+
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    let key = segments
+        .windows(2)
+        .find(|pair| matches!(pair[0], "01" | "00" | "414" | "8004" | "8006" | "8018"));
+    match key {
+        Some(&[ai @ "01", gtin]) => {
+            if !matches!(gtin.len(), 8 | 12 | 13 | 14) || !check_digit_ok(gtin) {
+                bail!(i18n::tr(
+                    "digital-link-gtin",
+                    &[("ai", ai.into()), ("gtin", gtin.into())]
+                ));
+            }
+        }
+        Some(&[ai @ "00", sscc]) => {
+            if sscc.len() != 18 || !check_digit_ok(sscc) {
+                bail!(i18n::tr(
+                    "digital-link-sscc",
+                    &[("ai", ai.into()), ("sscc", sscc.into())]
+                ));
+            }
+        }
+        Some(_) => {}
+        None => bail!(i18n::tr("digital-link-no-key", &[("uri", uri.into())])),
+    }
+    Ok(url.to_string())
+}
+
+/// GS1 AI encodable character set 82.
+fn is_cset82(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!\"%&'()*+,-./:;<=>?_".contains(c)
+}
+
+/// GS1 mod-10 check digit (weights 3 and 1 from the right).
+fn check_digit_ok(digits: &str) -> bool {
+    if digits.len() < 2 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let (body, check) = digits.split_at(digits.len() - 1);
+    let sum: u32 = body
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| u32::from(b - b'0') * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    check.parse::<u32>().ok() == Some((10 - sum % 10) % 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_element_string() {
+        assert_eq!(
+            element_string("(01)09506000134352(10)ABC123(17)261231").unwrap(),
+            "010950600013435210ABC123\x1d17261231"
+        );
+        // A variable-length field last needs no separator.
+        assert_eq!(
+            element_string("(01)09506000134352(21)12345").unwrap(),
+            "01095060001343522112345"
+        );
+        assert!(element_string("(01)09506000134353").is_err(), "check digit");
+        assert!(element_string("(17)2612").is_err(), "date length");
+        assert!(element_string("(3103)000189").is_ok());
+        assert!(element_string("(310)000189").is_err(), "AI length");
+        assert!(element_string("01)x").is_err());
+        assert!(element_string("(10)AB#1").is_err(), "character set");
+    }
+
+    #[test]
+    fn test_digital_link() {
+        let uri = "https://id.gs1.org/01/09506000134352/10/ABC123";
+        assert_eq!(digital_link(uri).unwrap(), uri);
+        assert!(digital_link("https://example.com/01/09506000134353").is_err());
+        assert!(digital_link("https://example.com/product/42").is_err());
+        assert!(digital_link("https://example.com/00/106141411234567897").is_ok());
+    }
+
+    #[test]
+    fn test_check_digit() {
+        assert!(check_digit_ok("9521234543213"));
+        assert!(check_digit_ok("4006381333931"));
+        assert!(!check_digit_ok("4006381333932"));
+        assert!(!check_digit_ok(""));
+        assert!(!check_digit_ok("40063813339x1"));
+    }
+}
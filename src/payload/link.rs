@@ -0,0 +1,97 @@
+//! Helpers for the default `--url` payload mode.
+
+use crate::i18n;
+use anyhow::{Result, bail};
+use url::Url;
+
+/// Parse a `key=value` pair for `--param`.
+pub fn parse_key_val(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.trim().is_empty() => Ok((k.trim().to_string(), v.to_string())),
+        _ => bail!(i18n::tr("param-expected", &[("param", s.into())])),
+    }
+}
+
+/// Set query parameters on `url`, replacing any existing values for the same keys
+/// and keeping all other parameters (and their order) intact.
+pub fn set_query_params(url: &mut Url, params: &[(String, String)]) {
+    if params.is_empty() {
+        return;
+    }
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| !params.iter().any(|(pk, _)| pk == k))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    let mut pairs = url.query_pairs_mut();
+    pairs.clear();
+    for (k, v) in kept.iter().chain(params) {
+        pairs.append_pair(k, v);
+    }
+}
+
+/// A short, human-friendly form of `url` for captions: host without `www.` plus the path,
+/// dropping the scheme, query and fragment (`https://www.example.com/promo/?utm_source=x`
+/// becomes `example.com/promo`). URLs without a host are returned unchanged.
+pub fn short_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    let Some(host) = parsed.host_str() else {
+        return url.to_string();
+    };
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let mut short = match parsed.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    short.push_str(parsed.path().trim_end_matches('/'));
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_query_params() {
+        let mut url = Url::parse("https://example.com/promo?ref=a&utm_source=old#top").unwrap();
+        set_query_params(
+            &mut url,
+            &[
+                ("utm_source".to_string(), "poster".to_string()),
+                ("utm_campaign".to_string(), "spring sale".to_string()),
+            ],
+        );
+        assert_eq!(
+            url.as_str(),
+            "https://example.com/promo?ref=a&utm_source=poster&utm_campaign=spring+sale#top"
+        );
+    }
+
+    #[test]
+    fn test_parse_key_val() {
+        assert_eq!(
+            parse_key_val("lang=en=GB").unwrap(),
+            ("lang".to_string(), "en=GB".to_string())
+        );
+        assert!(parse_key_val("novalue").is_err());
+        assert!(parse_key_val("=x").is_err());
+    }
+
+    #[test]
+    fn test_short_url() {
+        assert_eq!(
+            short_url("https://www.example.com/promo/?utm_source=poster#top"),
+            "example.com/promo"
+        );
+        assert_eq!(short_url("https://example.com/"), "example.com");
+        assert_eq!(
+            short_url("http://example.com:8080/a/b"),
+            "example.com:8080/a/b"
+        );
+        assert_eq!(short_url("mailto:a@b.c"), "mailto:a@b.c");
+    }
+}
@@ -0,0 +1,39 @@
+//! Builders for structured QR payloads (OTP provisioning, payments, contacts, ...).
+//!
+//! Each submodule exposes a clap `Args` struct for its subcommand and a
+//! `payload()` method producing the exact string to encode.
+
+pub mod contact;
+pub mod crypto;
+pub mod epc;
+pub mod event;
+pub mod geo;
+pub mod gs1;
+pub mod link;
+pub mod totp;
+
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+
+/// Everything except RFC 3986 unreserved characters gets percent-encoded.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Percent-encode a URI component (path segment or query value).
+pub fn encode_component(s: &str) -> String {
+    utf8_percent_encode(s, COMPONENT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_component() {
+        assert_eq!(encode_component("a b&c"), "a%20b%26c");
+        assert_eq!(encode_component("safe-._~"), "safe-._~");
+        assert_eq!(encode_component("é"), "%C3%A9");
+    }
+}
@@ -0,0 +1,179 @@
+//! `otpauth://totp/...` provisioning URIs (Google Authenticator key URI format).
+
+use super::encode_component;
+use crate::{i18n, log};
+use anyhow::{Context, Result, bail};
+use clap::Args;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Args, Debug)]
+pub struct TotpArgs {
+    /// Issuer (service or company name) shown in the authenticator app
+    #[arg(long = "issuer")]
+    pub issuer: String,
+
+    /// Account name (usually the user's email or login)
+    #[arg(long = "account")]
+    pub account: String,
+
+    /// Shared secret, base32 encoded (spaces and padding are ignored)
+    #[arg(long = "secret", required_unless_present = "generate_secret")]
+    pub secret: Option<String>,
+
+    /// Generate a random 160-bit secret and print it to stderr
+    #[arg(long = "generate-secret", conflicts_with = "secret")]
+    pub generate_secret: bool,
+
+    /// Number of digits per code (6 or 8)
+    #[arg(long = "digits", default_value_t = 6)]
+    pub digits: u8,
+
+    /// Code validity period in seconds
+    #[arg(long = "period", default_value_t = 30)]
+    pub period: u32,
+}
+
+impl TotpArgs {
+    /// Build the provisioning URI, generating a secret first if requested.
+    pub fn payload(&self) -> Result<String> {
+        let secret = match &self.secret {
+            Some(secret) => normalize_secret(secret)?,
+            None => {
+                let secret = generate_secret()?;
+                log::info(format!("Generated TOTP secret: {}", secret));
+                secret
+            }
+        };
+        totp_uri(
+            &self.issuer,
+            &self.account,
+            &secret,
+            self.digits,
+            self.period,
+        )
+    }
+}
+
+/// Build an `otpauth://totp/` URI from an already-normalized base32 secret.
+pub fn totp_uri(
+    issuer: &str,
+    account: &str,
+    secret: &str,
+    digits: u8,
+    period: u32,
+) -> Result<String> {
+    if issuer.contains(':') || account.contains(':') {
+        bail!(i18n::tr("totp-colon", &[]));
+    }
+    if account.is_empty() {
+        bail!(i18n::tr("totp-account-empty", &[]));
+    }
+    if digits != 6 && digits != 8 {
+        bail!(i18n::tr("totp-digits", &[("digits", digits.into())]));
+    }
+    if period == 0 {
+        bail!(i18n::tr("totp-period", &[]));
+    }
+
+    let label = if issuer.is_empty() {
+        encode_component(account)
+    } else {
+        format!("{}:{}", encode_component(issuer), encode_component(account))
+    };
+
+    let mut uri = format!("otpauth://totp/{}?secret={}", label, secret);
+    if !issuer.is_empty() {
+        uri.push_str(&format!("&issuer={}", encode_component(issuer)));
+    }
+    uri.push_str(&format!("&digits={}&period={}", digits, period));
+    Ok(uri)
+}
+
+/// Uppercase the secret, drop spaces/padding and check it is valid base32.
+pub fn normalize_secret(secret: &str) -> Result<String> {
+    let normalized: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if normalized.is_empty() {
+        bail!(i18n::tr("totp-secret-empty", &[]));
+    }
+    if let Some(bad) = normalized
+        .chars()
+        .find(|c| !BASE32_ALPHABET.contains(&(*c as u8)) || !c.is_ascii())
+    {
+        bail!(i18n::tr(
+            "totp-secret-base32",
+            &[("char", bad.to_string().into())]
+        ));
+    }
+    Ok(normalized)
+}
+
+fn generate_secret() -> Result<String> {
+    let mut bytes = [0u8; 20];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("Failed to generate random TOTP secret")?;
+    Ok(base32_encode(&bytes))
+}
+
+/// RFC 4648 base32 without padding.
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &b in bytes {
+        buffer = (buffer << 8) | b as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totp_uri() {
+        let uri = totp_uri("ACME Co", "jane@example.com", "JBSWY3DPEHPK3PXP", 6, 30).unwrap();
+        assert_eq!(
+            uri,
+            "otpauth://totp/ACME%20Co:jane%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_totp_uri_validation() {
+        assert!(totp_uri("ACME", "jane", "JBSWY3DP", 7, 30).is_err());
+        assert!(totp_uri("AC:ME", "jane", "JBSWY3DP", 6, 30).is_err());
+        assert!(totp_uri("ACME", "jane", "JBSWY3DP", 6, 0).is_err());
+    }
+
+    #[test]
+    fn test_normalize_secret() {
+        assert_eq!(
+            normalize_secret("jbsw y3dp ehpk 3pxp").unwrap(),
+            "JBSWY3DPEHPK3PXP"
+        );
+        assert_eq!(normalize_secret("JBSWY3DP====").unwrap(), "JBSWY3DP");
+        assert!(normalize_secret("JBSW1").is_err());
+    }
+
+    #[test]
+    fn test_base32_encode() {
+        assert_eq!(base32_encode(b"Hello!"), "JBSWY3DPEE");
+        assert_eq!(base32_encode(&[0u8; 20]).len(), 32);
+    }
+}
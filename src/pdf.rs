@@ -0,0 +1,646 @@
+//! A small PDF writer for print layouts: filled rectangles, hairlines, raster images and
+//! text in embedded TrueType fonts, on pages that carry their trim and bleed boxes.
+//!
+//! Colors are DeviceRGB (alpha is ignored, as in EPS output) and text is encoded as
+//! Windows-1252, which covers the Latin scripts of names and addresses. Streams are
+//! Flate-compressed.
+
+use crate::color::Color;
+use crate::contrast::{Contrast, reflectance};
+use crate::error::{ErrorCode, WithCode};
+use crate::fonts::FontFace;
+use crate::marks::{self, HAIRLINE, Marks};
+use crate::raster::Canvas;
+use crate::symbol::{self, Symbol};
+use crate::{Args, RenderOptions, i18n, log, meta, output, preview, resize_fit};
+use anyhow::{Context, Result, anyhow};
+use encoding_rs::WINDOWS_1252;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use image::DynamicImage;
+use rustybuzz::ttf_parser;
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+
+/// PostScript points per millimetre.
+pub const MM: f64 = 72.0 / 25.4;
+
+/// Logos are embedded at up to this many pixels per inch.
+const LOGO_PPI: f64 = 600.0;
+
+/// Codes with widths in a simple font: space up to the end of Windows-1252.
+const FIRST_CHAR: u8 = 32;
+
+/// A rectangle in points, as (x0, y0, x1, y1) from the bottom left.
+pub type Rect = [f64; 4];
+
+/// An indirect object number.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Ref(usize);
+
+/// A document under construction. Objects 1 to 3 are reserved for the catalog, the
+/// page tree and the resources every page shares.
+pub struct Document {
+    objects: Vec<Vec<u8>>,
+    pages: Vec<Ref>,
+    fonts: Vec<Ref>,
+    images: Vec<Ref>,
+}
+
+const CATALOG: Ref = Ref(1);
+const PAGES: Ref = Ref(2);
+const RESOURCES: Ref = Ref(3);
+
+/// Colors of a print layout, from the usual flags.
+pub struct Style {
+    pub fg: Color,
+    pub quiet: Color,
+    pub text: Color,
+    /// Fills the page (and bleed); transparent leaves the paper bare.
+    pub background: Color,
+}
+
+impl Style {
+    pub fn new(args: &Args, opts: &RenderOptions) -> Self {
+        Self {
+            fg: opts.fg,
+            quiet: opts.quiet_color,
+            text: args.text_color,
+            background: opts.canvas_color,
+        }
+    }
+}
+
+/// An embedded font and the widths needed to measure text set in it.
+pub struct Font {
+    name: String,
+    /// Advance widths in thousandths of the font size, from [`FIRST_CHAR`].
+    widths: Vec<u16>,
+    /// Ascender and descender in thousandths of the font size (descent is negative).
+    pub ascent: f64,
+    pub descent: f64,
+}
+
+/// An embedded raster image.
+pub struct Image {
+    name: String,
+}
+
+/// The drawing operators of one page.
+#[derive(Default)]
+pub struct Content(String);
+
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            objects: vec![Vec::new(); 3],
+            pages: Vec::new(),
+            fonts: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+}
+
+impl Document {
+    fn add(&mut self, body: impl Into<Vec<u8>>) -> Ref {
+        self.objects.push(body.into());
+        Ref(self.objects.len())
+    }
+
+    fn add_stream(&mut self, dict: &str, data: &[u8]) -> Ref {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        // Writing to a Vec cannot fail.
+        let _ = encoder.write_all(data);
+        let data = encoder.finish().unwrap_or_default();
+        let dict = if dict.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", dict)
+        };
+        let mut body = format!(
+            "<< {}/Filter /FlateDecode /Length {} >>\nstream\n",
+            dict,
+            data.len()
+        )
+        .into_bytes();
+        body.extend_from_slice(&data);
+        body.extend_from_slice(b"\nendstream");
+        self.add(body)
+    }
+
+    /// Embed `face` whole as a TrueType font with Windows-1252 encoding. Collections and
+    /// CFF-flavoured OpenType fonts cannot be embedded this way and are refused.
+    pub fn font(&mut self, face: &FontFace) -> Result<Font> {
+        let unusable =
+            || Err(anyhow!("PDF text needs a TrueType (.ttf) font")).code(ErrorCode::Usage);
+        if face.data.starts_with(b"ttcf") {
+            return unusable();
+        }
+        let Ok(ttf) = ttf_parser::Face::parse(&face.data, 0) else {
+            return unusable();
+        };
+        if ttf.tables().glyf.is_none() {
+            return unusable();
+        }
+        let per_unit = 1000.0 / ttf.units_per_em() as f64;
+        let scaled = |v: f64| (v * per_unit).round();
+        let widths: Vec<u16> = (FIRST_CHAR..=255)
+            .map(|code| {
+                let byte = [code];
+                let (ch, _) = WINDOWS_1252.decode_without_bom_handling(&byte);
+                ch.chars()
+                    .next()
+                    .and_then(|ch| ttf.glyph_index(ch))
+                    .and_then(|id| ttf.glyph_hor_advance(id))
+                    .map_or(0, |w| scaled(w as f64) as u16)
+            })
+            .collect();
+        let name: String = ttf
+            .names()
+            .into_iter()
+            .filter(|n| n.name_id == ttf_parser::name_id::POST_SCRIPT_NAME)
+            .find_map(|n| n.to_string())
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect();
+        let name = if name.is_empty() {
+            format!("Font{}", self.fonts.len() + 1)
+        } else {
+            name
+        };
+        let bbox = ttf.global_bounding_box();
+        let (ascent, descent) = (
+            scaled(ttf.ascender() as f64),
+            scaled(ttf.descender() as f64),
+        );
+        let cap_height = ttf.capital_height().map_or(ascent, |h| scaled(h as f64));
+
+        let file = self.add_stream(&format!("/Length1 {}", face.data.len()), &face.data);
+        let descriptor = self.add(format!(
+            "<< /Type /FontDescriptor /FontName /{} /Flags 32 /FontBBox [{} {} {} {}] \
+             /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /FontFile2 {} 0 R >>",
+            name,
+            scaled(bbox.x_min as f64),
+            scaled(bbox.y_min as f64),
+            scaled(bbox.x_max as f64),
+            scaled(bbox.y_max as f64),
+            ttf.italic_angle(),
+            ascent,
+            descent,
+            cap_height,
+            file.0
+        ));
+        let widths_list: Vec<String> = widths.iter().map(u16::to_string).collect();
+        let font = self.add(format!(
+            "<< /Type /Font /Subtype /TrueType /BaseFont /{} /FirstChar {} /LastChar 255 \
+             /Widths [{}] /Encoding /WinAnsiEncoding /FontDescriptor {} 0 R >>",
+            name,
+            FIRST_CHAR,
+            widths_list.join(" "),
+            descriptor.0
+        ));
+        self.fonts.push(font);
+        Ok(Font {
+            name: format!("F{}", self.fonts.len()),
+            widths,
+            ascent,
+            descent,
+        })
+    }
+
+    /// Embed `img` as an RGB image, with a soft mask if any pixel is not opaque.
+    pub fn image(&mut self, img: &Canvas) -> Image {
+        let (width, height) = img.dimensions();
+        let size = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /BitsPerComponent 8",
+            width, height
+        );
+        let mask = img.pixels().any(|p| p[3] < 255).then(|| {
+            let alpha: Vec<u8> = img.pixels().map(|p| p[3]).collect();
+            self.add_stream(&format!("{} /ColorSpace /DeviceGray", size), &alpha)
+        });
+        let rgb: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[1], p[2]]).collect();
+        let mask = mask.map_or(String::new(), |m| format!(" /SMask {} 0 R", m.0));
+        let image = self.add_stream(&format!("{} /ColorSpace /DeviceRGB{}", size, mask), &rgb);
+        self.images.push(image);
+        Image {
+            name: format!("Im{}", self.images.len()),
+        }
+    }
+
+    /// Embed `logo` for drawing as large as fits in `max_w` x `max_h` points, returning
+    /// it with the size to draw it at.
+    pub fn logo(&mut self, logo: &DynamicImage, max_w: f64, max_h: f64) -> (Image, f64, f64) {
+        let (lw, lh) = (logo.width() as f64, logo.height() as f64);
+        let scale = (max_w / lw).min(max_h / lh);
+        let (w, h) = (lw * scale, lh * scale);
+        let px = |pt: f64| (pt / 72.0 * LOGO_PPI).ceil() as u32;
+        let image = self.image(&resize_fit(logo, px(w), px(h)).to_rgba8());
+        (image, w, h)
+    }
+
+    /// Add a page of `width` x `height` points with the given trim and bleed boxes.
+    pub fn page(&mut self, width: f64, height: f64, trim: Rect, bleed: Rect, content: Content) {
+        let stream = self.add_stream("", content.0.as_bytes());
+        let page = self.add(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /BleedBox [{}] /TrimBox [{}] \
+             /Resources {} 0 R /Contents {} 0 R >>",
+            PAGES.0,
+            num(width),
+            num(height),
+            rect(bleed),
+            rect(trim),
+            RESOURCES.0,
+            stream.0
+        ));
+        self.pages.push(page);
+    }
+
+    /// The finished file, titled `title`.
+    pub fn finish(mut self, title: &str) -> Vec<u8> {
+        let refs = |prefix: &str, refs: &[Ref]| -> String {
+            refs.iter()
+                .enumerate()
+                .map(|(i, r)| format!("/{}{} {} 0 R", prefix, i + 1, r.0))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        self.objects[CATALOG.0 - 1] =
+            format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES.0).into_bytes();
+        self.objects[PAGES.0 - 1] = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            self.pages
+                .iter()
+                .map(|p| format!("{} 0 R", p.0))
+                .collect::<Vec<_>>()
+                .join(" "),
+            self.pages.len()
+        )
+        .into_bytes();
+        self.objects[RESOURCES.0 - 1] = format!(
+            "<< /Font << {} >> /XObject << {} >> >>",
+            refs("F", &self.fonts),
+            refs("Im", &self.images)
+        )
+        .into_bytes();
+        let info = self.add(format!(
+            "<< /Title {} /Producer (qrbrand {}) >>",
+            text_string(title),
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (i, body) in self.objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            pdf.extend_from_slice(body);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = pdf.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.objects.len() + 1,
+            CATALOG.0,
+            info.0,
+            xref
+        );
+        pdf.extend_from_slice(table.as_bytes());
+        pdf
+    }
+}
+
+impl Font {
+    /// Width of `text` set at `size` points.
+    pub fn width(&self, text: &str, size: f64) -> f64 {
+        let units: u32 = encode(text)
+            .iter()
+            .map(|&code| match code.checked_sub(FIRST_CHAR) {
+                Some(i) => self.widths[i as usize] as u32,
+                None => 0,
+            })
+            .sum();
+        units as f64 * size / 1000.0
+    }
+
+    /// The type size up to `max_pt` at which `text` fits in `width`.
+    pub fn fit(&self, text: &str, max_pt: f64, width: f64) -> f64 {
+        let natural = self.width(text, max_pt);
+        if natural <= width {
+            max_pt
+        } else {
+            max_pt * width / natural
+        }
+    }
+}
+
+impl Content {
+    /// Fill what follows with `color`.
+    pub fn fill_color(&mut self, color: Color) {
+        let _ = writeln!(self.0, "{} rg", rgb(color));
+    }
+
+    /// Stroke what follows with `color`, `width` points wide.
+    pub fn stroke_color(&mut self, color: Color, width: f64) {
+        let _ = writeln!(self.0, "{} RG {} w", rgb(color), num(width));
+    }
+
+    pub fn fill_rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        let _ = writeln!(self.0, "{} {} {} {} re f", num(x), num(y), num(w), num(h));
+    }
+
+    pub fn line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        let _ = writeln!(
+            self.0,
+            "{} {} m {} {} l S",
+            num(x0),
+            num(y0),
+            num(x1),
+            num(y1)
+        );
+    }
+
+    /// Draw what follows through the transformation matrix `m`, until [`Content::restore`].
+    pub fn transform(&mut self, m: [f64; 6]) {
+        let m: Vec<String> = m.into_iter().map(num).collect();
+        let _ = writeln!(self.0, "q {} cm", m.join(" "));
+    }
+
+    /// Undo the last [`Content::transform`], and any dash set since.
+    pub fn restore(&mut self) {
+        let _ = writeln!(self.0, "Q");
+    }
+
+    /// Stroke what follows with dashes and gaps of `length` points.
+    pub fn dash(&mut self, length: f64) {
+        let _ = writeln!(self.0, "[{0} {0}] 0 d", num(length));
+    }
+
+    /// Draw `image` stretched over the given rectangle.
+    pub fn image(&mut self, image: &Image, x: f64, y: f64, w: f64, h: f64) {
+        let _ = writeln!(
+            self.0,
+            "q {} 0 0 {} {} {} cm /{} Do Q",
+            num(w),
+            num(h),
+            num(x),
+            num(y),
+            image.name
+        );
+    }
+
+    /// Set `text` in the current fill color with its baseline starting at (x, y).
+    pub fn text(&mut self, font: &Font, size: f64, x: f64, y: f64, text: &str) {
+        let hex: String = encode(text).iter().map(|b| format!("{:02X}", b)).collect();
+        let _ = writeln!(
+            self.0,
+            "BT /{} {} Tf {} {} Td <{}> Tj ET",
+            font.name,
+            num(size),
+            num(x),
+            num(y),
+            hex
+        );
+    }
+
+    /// Draw `code` with `quiet` modules of quiet zone as a square of `side` points whose
+    /// bottom left corner is at (x, y).
+    #[allow(clippy::too_many_arguments)]
+    pub fn symbol(
+        &mut self,
+        code: &Symbol,
+        quiet: u32,
+        x: f64,
+        y: f64,
+        side: f64,
+        fg: Color,
+        quiet_color: Color,
+    ) {
+        let n = code.width();
+        let module = side / (n + 2 * quiet as usize) as f64;
+        self.fill_color(quiet_color);
+        self.fill_rect(x, y, side, side);
+        self.fill_color(fg);
+        let top = y + side - quiet as f64 * module;
+        let left = x + quiet as f64 * module;
+        for (mx, my, len) in code.dark_runs() {
+            self.fill_rect(
+                left + mx as f64 * module,
+                top - (my + 1) as f64 * module,
+                len as f64 * module,
+                module,
+            );
+        }
+    }
+}
+
+/// Encode `payload` for a print layout, checking the module colors as the raster
+/// renderer does.
+pub fn encode_checked(payload: &str, opts: &RenderOptions) -> Result<Symbol> {
+    let code = symbol::encode(payload, &opts.encode)?;
+    let contrast = Contrast {
+        dark: reflectance(opts.fg.0),
+        light: reflectance(opts.quiet_color.0),
+        transparent: opts.quiet_color.0[3] < 255,
+    };
+    for warning in contrast.check(opts.strict_contrast)? {
+        log::warn(warning);
+    }
+    Ok(code)
+}
+
+/// Warn about the first of `texts` that cannot be set in full.
+pub fn warn_unencodable<'a>(texts: impl IntoIterator<Item = &'a String>) {
+    if let Some(text) = texts.into_iter().find(|t| !encodable(t)) {
+        log::warn(format!(
+            "'{}' has characters outside Windows-1252; they are printed as '?'",
+            text
+        ));
+    }
+}
+
+/// A one-page PDF of `img` at `pt_per_px` points per pixel, with `marks` around it: the
+/// bleed repeats the edge pixels as in TIFF output, and crop marks are hairlines.
+pub fn image_page(img: &Canvas, pt_per_px: f64, marks: &Marks, title: &str) -> Vec<u8> {
+    let (w, h) = (
+        img.width() as f64 * pt_per_px,
+        img.height() as f64 * pt_per_px,
+    );
+    let (bleed, margin) = (marks.bleed, marks.margin());
+    let bled = match bleed > 0.0 {
+        true => Cow::Owned(marks::extend(
+            img,
+            &Marks { bleed, crop: false },
+            1.0 / pt_per_px,
+        )),
+        false => Cow::Borrowed(img),
+    };
+    let mut doc = Document::default();
+    let image = doc.image(&bled);
+    let mut content = Content::default();
+    // The bleed is a whole number of pixels, centred on the trim.
+    let (bled_w, bled_h) = (
+        bled.width() as f64 * pt_per_px,
+        bled.height() as f64 * pt_per_px,
+    );
+    content.image(
+        &image,
+        margin - (bled_w - w) / 2.0,
+        margin - (bled_h - h) / 2.0,
+        bled_w,
+        bled_h,
+    );
+    let trim = [margin, margin, margin + w, margin + h];
+    if marks.crop {
+        content.stroke_color(Color::BLACK, HAIRLINE);
+        for [x0, y0, x1, y1] in marks::crop_lines(trim, bleed) {
+            content.line(x0, y0, x1, y1);
+        }
+    }
+    let bleed_box = [
+        margin - bleed,
+        margin - bleed,
+        margin + w + bleed,
+        margin + h + bleed,
+    ];
+    doc.page(w + 2.0 * margin, h + 2.0 * margin, trim, bleed_box, content);
+    doc.finish(title)
+}
+
+/// Write a finished layout to `out`, honouring `--no-clobber`, `--unique` and `--open`.
+pub fn save(args: &Args, payload: &str, out: &str, pdf: &[u8]) -> Result<()> {
+    let hash = meta::sha256_hex(payload.as_bytes());
+    let out = output::claim_path(
+        Path::new(out),
+        &hash[..8],
+        args.no_clobber,
+        args.unique,
+        Path::exists,
+    )?;
+    let out = out.to_string_lossy();
+    std::fs::write(&*out, pdf).with_context(|| format!("Failed to write PDF: {}", out))?;
+    log::info(i18n::tr("wrote", &[("path", out.as_ref().into())]));
+    if args.open
+        && let Err(e) = preview::open(&out)
+    {
+        log::warn(format!("{:#}", e));
+    }
+    Ok(())
+}
+
+/// Windows-1252 bytes for `text`, with `?` for anything it cannot encode.
+fn encode(text: &str) -> Vec<u8> {
+    let mut buf = [0; 4];
+    text.chars()
+        .flat_map(|ch| {
+            let (bytes, _, unmappable) = WINDOWS_1252.encode(ch.encode_utf8(&mut buf));
+            if unmappable || bytes.len() != 1 {
+                vec![b'?']
+            } else {
+                bytes.into_owned()
+            }
+        })
+        .collect()
+}
+
+/// Whether every character of `text` can be set in a PDF font here.
+fn encodable(text: &str) -> bool {
+    let (_, _, unmappable) = WINDOWS_1252.encode(text);
+    !unmappable
+}
+
+/// `text` as a UTF-16 PDF text string, for metadata.
+fn text_string(text: &str) -> String {
+    let hex: String = text.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+    format!("<FEFF{}>", hex)
+}
+
+fn rgb(color: Color) -> String {
+    let [r, g, b, _] = color.0.0;
+    let c = |v: u8| num(v as f64 / 255.0);
+    format!("{} {} {}", c(r), c(g), c(b))
+}
+
+fn rect([x0, y0, x1, y1]: Rect) -> String {
+    format!("{} {} {} {}", num(x0), num(y0), num(x1), num(y1))
+}
+
+/// A number with at most four decimals and no trailing zeros.
+fn num(v: f64) -> String {
+    let s = format!("{:.4}", v);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_embedded_font;
+
+    #[test]
+    fn test_document() {
+        let mut doc = Document::default();
+        let font = doc.font(&load_embedded_font().unwrap()).unwrap();
+        // Widths come from the font: DejaVu Sans has a wider M than i.
+        assert!(font.width("M", 10.0) > font.width("i", 10.0));
+        assert_eq!(
+            font.width("ab", 10.0),
+            font.width("a", 10.0) + font.width("b", 10.0)
+        );
+        let image = doc.image(&Canvas::from_pixel(2, 2, image::Rgba([255, 0, 0, 128])));
+        let mut content = Content::default();
+        content.fill_color(Color::BLACK);
+        content.text(&font, 12.0, 10.0, 20.0, "Zoë (€)");
+        content.image(&image, 0.0, 0.0, 20.0, 20.0);
+        doc.page(
+            100.0,
+            50.0,
+            [5.0, 5.0, 95.0, 45.0],
+            [2.0, 2.0, 98.0, 48.0],
+            content,
+        );
+        let pdf = doc.finish("Card");
+
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/TrimBox [5 5 95 45]"));
+        assert!(text.contains("/BaseFont /DejaVuSans "));
+        assert!(text.contains("/SMask "));
+
+        // Every xref entry points at the start of its object.
+        let start: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|s| s.lines().next())
+            .and_then(|s| s.parse().ok())
+            .unwrap();
+        assert!(pdf[start..].starts_with(b"xref\n"));
+        let entries = text[start..].lines().skip(3);
+        for (i, entry) in entries.take_while(|l| l.ends_with(" n ")).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            let header = format!("{} 0 obj\n", i + 1);
+            assert!(
+                pdf[offset..].starts_with(header.as_bytes()),
+                "object {}",
+                i + 1
+            );
+        }
+
+        assert_eq!(encode("Zoë (€) 中"), b"Zo\xeb (\x80) ?");
+        assert!(encodable("Zoë Müller"));
+        assert!(!encodable("中文"));
+    }
+}
@@ -0,0 +1,303 @@
+//! High-level encoding: the payload's bytes as PDF417 data codewords.
+//!
+//! Runs of 13 or more digits use numeric compaction (about 2.9 digits per codeword),
+//! printable ASCII text compaction (two characters per codeword within a sub-mode)
+//! and anything else byte compaction (six bytes in five codewords). A payload with
+//! bytes outside ASCII is marked as UTF-8 with ECI 26 first.
+
+/// Latch to text compaction.
+const LATCH_TEXT: u16 = 900;
+/// Latch to byte compaction, for a run whose length is not a multiple of 6.
+const LATCH_BYTE: u16 = 901;
+/// Latch to numeric compaction.
+const LATCH_NUMERIC: u16 = 902;
+/// Shift one byte into text compaction.
+const SHIFT_BYTE: u16 = 913;
+/// Latch to byte compaction, for a run of a multiple of 6 bytes.
+const LATCH_BYTE_6: u16 = 924;
+/// ECI designator, followed by the ECI number.
+const ECI: u16 = 927;
+const ECI_UTF8: u16 = 26;
+
+/// Fewest digits worth leaving text or byte compaction for.
+const NUMERIC_RUN: usize = 13;
+/// Fewest text characters worth leaving byte compaction for.
+const TEXT_RUN: usize = 5;
+
+/// Characters of the mixed sub-mode by value; 25 and up are space and the latches.
+const MIXED: &[u8; 25] = b"0123456789&\r\t,:#-.$/+%*=^";
+/// Characters of the punctuation sub-mode by value.
+const PUNCTUATION: &[u8; 29] = b";<>@[\\]_`~!\r\t,:\n-.$/\"|*()?{}'";
+
+/// Text sub-modes; the value of each character depends on the one in force.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SubMode {
+    Alpha,
+    Lower,
+    Mixed,
+    Punctuation,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Text,
+    Byte,
+    Numeric,
+}
+
+/// The data codewords for `payload`, without the symbol length descriptor.
+pub fn compact(payload: &str) -> Vec<u16> {
+    let bytes = payload.as_bytes();
+    let mut out = Vec::new();
+    if !payload.is_ascii() {
+        out.extend([ECI, ECI_UTF8]);
+    }
+    // A symbol starts in text compaction, alpha sub-mode.
+    let mut mode = Mode::Text;
+    let mut sub = SubMode::Alpha;
+    let mut p = 0;
+    while p < bytes.len() {
+        let digits = digit_run(bytes, p);
+        if digits >= NUMERIC_RUN {
+            out.push(LATCH_NUMERIC);
+            mode = Mode::Numeric;
+            numeric(&bytes[p..p + digits], &mut out);
+            p += digits;
+            continue;
+        }
+        let text = text_run(bytes, p);
+        if text >= TEXT_RUN || (text > 0 && p + text == bytes.len()) {
+            if mode != Mode::Text {
+                out.push(LATCH_TEXT);
+                mode = Mode::Text;
+                sub = SubMode::Alpha;
+            }
+            sub = self::text(&bytes[p..p + text], sub, &mut out);
+            p += text;
+            continue;
+        }
+        let run = binary_run(bytes, p).max(1);
+        let chunk = &bytes[p..p + run];
+        if run == 1 && mode == Mode::Text {
+            out.extend([SHIFT_BYTE, chunk[0] as u16]);
+        } else {
+            binary(chunk, &mut out);
+            mode = Mode::Byte;
+        }
+        p += run;
+    }
+    out
+}
+
+fn is_text(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\r' | b' '..=b'~')
+}
+
+fn mixed(b: u8) -> Option<u16> {
+    match b {
+        b' ' => Some(26),
+        _ => MIXED.iter().position(|&m| m == b).map(|i| i as u16),
+    }
+}
+
+fn punctuation(b: u8) -> Option<u16> {
+    PUNCTUATION.iter().position(|&m| m == b).map(|i| i as u16)
+}
+
+/// Consecutive digits from `start`.
+fn digit_run(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .take_while(|b| b.is_ascii_digit())
+        .count()
+}
+
+/// Text characters from `start`, up to a run of digits long enough for numeric compaction.
+fn text_run(bytes: &[u8], start: usize) -> usize {
+    let mut idx = start;
+    while idx < bytes.len() {
+        let digits = digit_run(bytes, idx).min(NUMERIC_RUN);
+        if digits == NUMERIC_RUN {
+            break;
+        }
+        if digits > 0 {
+            idx += digits;
+            continue;
+        }
+        if !is_text(bytes[idx]) {
+            break;
+        }
+        idx += 1;
+    }
+    idx - start
+}
+
+/// Bytes from `start` up to a run of digits or text long enough to switch for.
+fn binary_run(bytes: &[u8], start: usize) -> usize {
+    let mut idx = start;
+    while idx < bytes.len() {
+        if digit_run(bytes, idx) >= NUMERIC_RUN {
+            break;
+        }
+        let text = bytes[idx..]
+            .iter()
+            .take(TEXT_RUN)
+            .take_while(|&&b| is_text(b));
+        if text.count() == TEXT_RUN {
+            break;
+        }
+        idx += 1;
+    }
+    idx - start
+}
+
+/// Text compaction of `text`, starting in sub-mode `sub`; returns the sub-mode it ends in.
+fn text(text: &[u8], mut sub: SubMode, out: &mut Vec<u16>) -> SubMode {
+    let upper = |b: u8| b == b' ' || b.is_ascii_uppercase();
+    let lower = |b: u8| b == b' ' || b.is_ascii_lowercase();
+    let mut values = Vec::new();
+    let mut idx = 0;
+    while idx < text.len() {
+        let b = text[idx];
+        match sub {
+            SubMode::Alpha => {
+                if upper(b) {
+                    values.push(if b == b' ' { 26 } else { (b - b'A') as u16 });
+                } else if lower(b) {
+                    sub = SubMode::Lower;
+                    values.push(27);
+                    continue;
+                } else if mixed(b).is_some() {
+                    sub = SubMode::Mixed;
+                    values.push(28);
+                    continue;
+                } else {
+                    values.extend([29, punctuation(b).unwrap_or(0)]);
+                }
+            }
+            SubMode::Lower => {
+                if lower(b) {
+                    values.push(if b == b' ' { 26 } else { (b - b'a') as u16 });
+                } else if upper(b) {
+                    // Shift to alpha for one character.
+                    values.extend([27, (b - b'A') as u16]);
+                } else if mixed(b).is_some() {
+                    sub = SubMode::Mixed;
+                    values.push(28);
+                    continue;
+                } else {
+                    values.extend([29, punctuation(b).unwrap_or(0)]);
+                }
+            }
+            SubMode::Mixed => {
+                if let Some(v) = mixed(b) {
+                    values.push(v);
+                } else if upper(b) {
+                    sub = SubMode::Alpha;
+                    values.push(28);
+                    continue;
+                } else if lower(b) {
+                    sub = SubMode::Lower;
+                    values.push(27);
+                    continue;
+                } else if text
+                    .get(idx + 1)
+                    .is_some_and(|&next| punctuation(next).is_some())
+                {
+                    sub = SubMode::Punctuation;
+                    values.push(25);
+                    continue;
+                } else {
+                    values.extend([29, punctuation(b).unwrap_or(0)]);
+                }
+            }
+            SubMode::Punctuation => match punctuation(b) {
+                Some(v) => values.push(v),
+                None => {
+                    sub = SubMode::Alpha;
+                    values.push(29);
+                    continue;
+                }
+            },
+        }
+        idx += 1;
+    }
+    // Two values to a codeword; an odd one out is padded with 29, a punctuation shift
+    // that nothing follows, or in punctuation the latch back to alpha.
+    for pair in values.chunks(2) {
+        out.push(pair[0] * 30 + pair.get(1).copied().unwrap_or(29));
+    }
+    match sub {
+        SubMode::Punctuation if values.len() % 2 == 1 => SubMode::Alpha,
+        _ => sub,
+    }
+}
+
+/// Byte compaction: each six bytes as five base-900 codewords, the rest one per codeword.
+fn binary(bytes: &[u8], out: &mut Vec<u16>) {
+    out.push(match bytes.len() % 6 {
+        0 => LATCH_BYTE_6,
+        _ => LATCH_BYTE,
+    });
+    let mut chunks = bytes.chunks_exact(6);
+    for chunk in &mut chunks {
+        let mut n = chunk.iter().fold(0u64, |n, &b| n << 8 | b as u64);
+        let mut group = [0; 5];
+        for cw in group.iter_mut().rev() {
+            *cw = (n % 900) as u16;
+            n /= 900;
+        }
+        out.extend(group);
+    }
+    out.extend(chunks.remainder().iter().map(|&b| b as u16));
+}
+
+/// Numeric compaction: groups of up to 44 digits, each read with a leading 1 as a
+/// base-900 number.
+fn numeric(digits: &[u8], out: &mut Vec<u16>) {
+    for group in digits.chunks(44) {
+        let mut decimal: Vec<u32> = std::iter::once(1)
+            .chain(group.iter().map(|d| (d - b'0') as u32))
+            .collect();
+        let mut base900 = Vec::new();
+        // Long division of the decimal digits by 900 until nothing is left.
+        while decimal.iter().any(|&d| d != 0) {
+            let mut rem = 0;
+            for d in decimal.iter_mut() {
+                let cur = rem * 10 + *d;
+                *d = cur / 900;
+                rem = cur % 900;
+            }
+            base900.push(rem as u16);
+        }
+        out.extend(base900.iter().rev());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact() {
+        // "PDF417" from ISO/IEC 15438 annex: alpha P D F, mixed 4 1 7.
+        assert_eq!(compact("PDF417"), vec![453, 178, 121, 239]);
+        // Lower case latches once; a capital inside shifts for one character.
+        assert_eq!(compact("abCd"), vec![27 * 30, 30 + 27, 2 * 30 + 3]);
+        // A long run of digits goes numeric: 10^13 + 1234567890123 in base 900.
+        assert_eq!(
+            compact("1234567890123"),
+            vec![LATCH_NUMERIC, 17, 110, 836, 811, 223]
+        );
+        // Six bytes are five codewords; one more stays a byte of its own.
+        assert_eq!(compact("\x01\x02\x03\x04\x05\x06")[0], LATCH_BYTE_6);
+        let bytes = compact("\x01\x02\x03\x04\x05\x06\x07");
+        assert_eq!(bytes[0], LATCH_BYTE);
+        assert_eq!(bytes.len(), 1 + 5 + 1);
+        assert_eq!(bytes[6], 7);
+        // A lone control byte in text is shifted, not latched.
+        assert_eq!(compact("ABCDE\x01FGHIJ")[3..5], [SHIFT_BYTE, 1]);
+        // UTF-8 is announced with ECI 26.
+        assert_eq!(compact("Zoë")[..2], [ECI, ECI_UTF8]);
+    }
+}
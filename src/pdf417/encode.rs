@@ -0,0 +1,252 @@
+//! PDF417 symbol construction: length descriptor, padding, Reed-Solomon error
+//! correction over GF(929) and the row layout.
+
+use super::compact::compact;
+use super::tables::CLUSTERS;
+use crate::i18n;
+use anyhow::{Result, bail};
+
+/// Start pattern, 17 modules.
+const START: u32 = 0b11111111010101000;
+/// Stop pattern, 18 modules.
+const STOP: u32 = 0b111111101000101001;
+/// Pad codeword (a text latch, which decodes to nothing).
+const PAD: u16 = 900;
+/// Most codewords a symbol holds (at most 90 rows of 30 columns, and no more than 928).
+const MAX_CODEWORDS: usize = 928;
+/// Module height of a row, in module widths (the least ISO/IEC 15438 allows).
+pub const ROW_HEIGHT: usize = 3;
+/// Quiet zone on every side, in module widths.
+pub const QUIET: usize = 2;
+/// Width over height the column count aims for, quiet zone left out.
+const ASPECT: f64 = 3.0;
+
+/// A finished PDF417 symbol: its codewords in row-major order and its dimensions.
+pub struct Pdf417 {
+    codewords: Vec<u16>,
+    rows: usize,
+    cols: usize,
+    /// Error correction level, 0..=8 (2 to 512 codewords).
+    level: u8,
+}
+
+impl Pdf417 {
+    /// Modules across a row: start, left indicator, data, right indicator and stop.
+    pub fn width(&self) -> usize {
+        17 * (self.cols + 3) + 18
+    }
+
+    /// Modules down the symbol at [`ROW_HEIGHT`] per row.
+    pub fn height(&self) -> usize {
+        self.rows * ROW_HEIGHT
+    }
+
+    /// One-line summary for `--verbose`, e.g. `PDF417, 6 columns x 12 rows, EC level 3`.
+    pub fn describe(&self) -> String {
+        format!(
+            "PDF417, {} columns x {} rows, EC level {}, {} modules wide",
+            self.cols,
+            self.rows,
+            self.level,
+            self.width()
+        )
+    }
+
+    /// The modules of row `row`, left to right, `true` for a bar.
+    pub fn row(&self, row: usize) -> Vec<bool> {
+        let cluster = row % 3;
+        let (rows, cols, level) = (self.rows as u16, self.cols as u16, self.level as u16);
+        // The row indicators carry the row count, column count and EC level between them.
+        let group = (row / 3) as u16 * 30;
+        let row_count = (rows - 1) / 3;
+        let level_info = level * 3 + (rows - 1) % 3;
+        let (left, right) = match cluster {
+            0 => (row_count, cols - 1),
+            1 => (level_info, row_count),
+            _ => (cols - 1, level_info),
+        };
+        let data = &self.codewords[row * self.cols..(row + 1) * self.cols];
+
+        let mut modules = Vec::with_capacity(self.width());
+        let mut push = |value: u32, len: u32| {
+            modules.extend((0..len).rev().map(|bit| (value >> bit) & 1 == 1));
+        };
+        let pattern = |cw: u16| (1 << 16) | CLUSTERS[cluster][cw as usize] as u32;
+        push(START, 17);
+        push(pattern(group + left), 17);
+        for &cw in data {
+            push(pattern(cw), 17);
+        }
+        push(pattern(group + right), 17);
+        push(STOP, 18);
+        modules
+    }
+
+    /// Horizontal runs of bars as (x, row, length), rows from the top; vector outputs
+    /// draw one rectangle per run and row.
+    pub fn dark_runs(&self) -> Vec<(usize, usize, usize)> {
+        let mut runs = Vec::new();
+        for y in 0..self.rows {
+            let row = self.row(y);
+            let mut x = 0;
+            while x < row.len() {
+                let len = row[x..].iter().take_while(|&&bar| bar == row[x]).count();
+                if row[x] {
+                    runs.push((x, y, len));
+                }
+                x += len;
+            }
+        }
+        runs
+    }
+}
+
+/// Encode `payload` with the error correction ISO/IEC 15438 recommends for its length,
+/// in the column count that comes closest to a 3:1 symbol.
+pub fn encode(payload: &str) -> Result<Pdf417> {
+    let data = compact(payload);
+    // The symbol length descriptor comes first and counts itself.
+    let len = data.len() + 1;
+    let level = match len {
+        ..=40 => 2,
+        41..=160 => 3,
+        161..=320 => 4,
+        _ => 5,
+    };
+    let ecc = ecc_count(level);
+    if len + ecc > MAX_CODEWORDS {
+        bail!(i18n::tr(
+            "pdf417-too-long",
+            &[
+                ("codewords", len.into()),
+                ("max", (MAX_CODEWORDS - ecc).into())
+            ]
+        ));
+    }
+    let (rows, cols) = dimensions(len + ecc).expect("928 codewords fit in 31 rows of 30");
+
+    let mut codewords = Vec::with_capacity(rows * cols);
+    let data_len = rows * cols - ecc;
+    codewords.push(data_len as u16);
+    codewords.extend(data);
+    codewords.resize(data_len, PAD);
+    let ec = error_correction(&codewords, ecc);
+    codewords.extend(ec);
+    Ok(Pdf417 {
+        codewords,
+        rows,
+        cols,
+        level,
+    })
+}
+
+fn ecc_count(level: u8) -> usize {
+    2 << level
+}
+
+/// Rows and columns for `n` codewords: 3 to 90 rows of 1 to 30 columns, the shape
+/// nearest [`ASPECT`] among those that hold them.
+fn dimensions(n: usize) -> Option<(usize, usize)> {
+    (1..=30)
+        .filter_map(|cols| {
+            let rows = n.div_ceil(cols).max(3);
+            (rows <= 90).then_some((rows, cols))
+        })
+        .min_by(|&(r1, c1), &(r2, c2)| {
+            let off = |rows: usize, cols: usize| {
+                let shape = (17 * (cols + 3) + 18) as f64 / (rows * ROW_HEIGHT) as f64;
+                (shape / ASPECT).ln().abs()
+            };
+            off(r1, c1).total_cmp(&off(r2, c2))
+        })
+}
+
+/// Coefficients of the generator polynomial for `k` error correction codewords,
+/// (x - 3)(x - 3^2)...(x - 3^k) mod 929, constant term first, leading 1 left out.
+fn generator(k: usize) -> Vec<u32> {
+    let mut poly = vec![1u32];
+    let mut root = 1;
+    for _ in 0..k {
+        root = root * 3 % 929;
+        let mut next = vec![0; poly.len() + 1];
+        for (i, &c) in poly.iter().enumerate() {
+            next[i + 1] = (next[i + 1] + c) % 929;
+            next[i] = (next[i] + (929 - root) * c) % 929;
+        }
+        poly = next;
+    }
+    poly.truncate(k);
+    poly
+}
+
+/// The `k` error correction codewords for `data`: the negated remainder of
+/// data(x) x^k divided by the generator, highest power first.
+fn error_correction(data: &[u16], k: usize) -> Vec<u16> {
+    let g = generator(k);
+    let mut ec = vec![0u32; k];
+    for &d in data {
+        let t = (d as u32 + ec[k - 1]) % 929;
+        for j in (1..k).rev() {
+            ec[j] = (ec[j - 1] + 929 - t * g[j] % 929) % 929;
+        }
+        ec[0] = (929 - t * g[0] % 929) % 929;
+    }
+    ec.iter().rev().map(|&e| ((929 - e) % 929) as u16).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_correction() {
+        // Level 0 and 1 generators as listed in ISO/IEC 15438 annex F.
+        assert_eq!(generator(2), vec![27, 917]);
+        assert_eq!(generator(4), vec![522, 568, 723, 809]);
+
+        // Every codeword sequence of a symbol is a multiple of the generator: it
+        // vanishes at 3, 3^2, ..., 3^k.
+        let symbol = encode("https://example.com/ship?id=1Z999AA10123456784").unwrap();
+        let k = ecc_count(symbol.level);
+        let mut root = 1;
+        for _ in 0..k {
+            root = root * 3 % 929;
+            let value = symbol
+                .codewords
+                .iter()
+                .fold(0u32, |acc, &cw| (acc * root + cw as u32) % 929);
+            assert_eq!(value, 0);
+        }
+        assert_eq!(symbol.codewords[0] as usize, symbol.codewords.len() - k);
+    }
+
+    #[test]
+    fn test_rows() {
+        let symbol = encode("PDF417").unwrap();
+        assert_eq!(symbol.level, 2);
+        assert!(symbol.rows >= 3);
+        let width = symbol.width();
+        for y in 0..symbol.rows {
+            let row = symbol.row(y);
+            assert_eq!(row.len(), width);
+            // Start with eight bars, stop with seven and a final bar.
+            assert!(row[..8].iter().all(|&bar| bar));
+            assert!(!row[8]);
+            assert!(row[width - 18..width - 11].iter().all(|&bar| bar));
+            assert!(row[width - 1]);
+            // Every symbol character is 17 modules starting with a bar and ending in a space.
+            for c in 1..symbol.cols + 3 {
+                assert!(row[17 * c]);
+                assert!(!row[17 * c + 16]);
+            }
+        }
+        // The left indicator of row 0 holds (rows - 1) / 3 in cluster 0.
+        let left = u32::from(CLUSTERS[0][(symbol.rows - 1) / 3]) | 1 << 16;
+        let bits: u32 = symbol.row(0)[17..34]
+            .iter()
+            .fold(0, |acc, &bar| acc << 1 | bar as u32);
+        assert_eq!(bits, left);
+
+        assert!(encode(&"x\u{1}".repeat(1000)).is_err());
+    }
+}
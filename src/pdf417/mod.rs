@@ -0,0 +1,295 @@
+//! `--symbology pdf417`: the payload as a PDF417 stacked barcode (ISO/IEC 15438), for
+//! driver's-license-style and shipping labels, as PNG or, with a `.pdf` `--out`, PDF.
+//!
+//! The raster is `--size` pixels wide at most, a whole number of pixels per module,
+//! with the caption band of a QR code under it. The PDF draws the bars as rectangles,
+//! `--print-size` wide or at 0.254 mm (10 mil) modules, with the caption set in the
+//! caption font.
+
+mod compact;
+mod encode;
+mod tables;
+
+pub use encode::{Pdf417, encode};
+
+use crate::color::Color;
+use crate::error::{ErrorCode, WithCode};
+use crate::fonts::FontFace;
+use crate::pdf::{self, Content, Document, MM};
+use crate::raster::{self, Canvas};
+use crate::{
+    Args, Command, RenderOptions, add_url_text_below, cache, i18n, log, meta, output, preview,
+    save_image,
+};
+use anyhow::{Result, anyhow, bail};
+use encode::{QUIET, ROW_HEIGHT};
+use std::path::Path;
+
+/// Module width of a PDF without `--print-size`.
+const NOMINAL_X_MM: f64 = 0.254;
+/// Largest type size for the caption in a PDF; a long caption shrinks to fit.
+const CAPTION_PT: f64 = 9.0;
+
+/// The first flag given that belongs to QR rendering and has nothing to apply to here.
+fn unsupported(args: &Args) -> Option<&'static str> {
+    [
+        (args.image.is_some(), "--image"),
+        (args.template.is_some(), "--template"),
+        (args.corner_radius > 0, "--corner-radius"),
+        (args.circle_crop, "--circle-crop"),
+        (args.shape == crate::round::Shape::Circle, "--shape circle"),
+        (args.border.is_some(), "--border"),
+        (args.shadow.is_some(), "--shadow"),
+        (args.module_gap > 0.0, "--module-gap"),
+        (args.module_style.is_hollow(), "--module-style"),
+        (args.fill_texture.is_some(), "--fill-texture"),
+        (args.invert, "--invert"),
+        (args.eye_color.is_some(), "--eye-color"),
+        (args.eye_inner_color.is_some(), "--eye-inner-color"),
+        (!args.eye_glyph.is_empty(), "--eye-glyph"),
+        (args.debug_overlay, "--debug-overlay"),
+        (args.rotate.is_some(), "--rotate"),
+        (args.flip.is_some(), "--flip"),
+        (args.qr_version.is_some(), "--qr-version"),
+        (args.mask.is_some(), "--mask"),
+        (args.bleed.is_some(), "--bleed"),
+        (args.crop_marks, "--crop-marks"),
+        (args.guides, "--guides"),
+        (!args.favicon_png.is_empty(), "--favicon-png"),
+        (args.print.is_some(), "--print"),
+        (args.terminal, "--terminal"),
+        (args.clipboard, "--clipboard"),
+        (args.stress_test, "--stress-test"),
+        (args.meta_out.is_some(), "--meta-out"),
+        (args.dry_run, "--dry-run"),
+        (
+            matches!(args.command, Some(Command::Label(_))),
+            "qrbrand label",
+        ),
+    ]
+    .into_iter()
+    .find_map(|(given, flag)| given.then_some(flag))
+}
+
+pub fn run(
+    args: &Args,
+    payload: &str,
+    caption: Option<&str>,
+    alt_text: Option<&str>,
+    out_given: bool,
+) -> Result<()> {
+    if let Some(flag) = unsupported(args) {
+        return Err(anyhow!(i18n::tr(
+            "pdf417-unsupported",
+            &[("flag", flag.into())]
+        )))
+        .code(ErrorCode::Usage);
+    }
+    let format = match args.format {
+        Some(format) => format,
+        None if out_given => output::Format::of_path(Path::new(&args.out))?,
+        None => output::Format::Png,
+    };
+    if !matches!(format, output::Format::Png | output::Format::Pdf) {
+        return Err(anyhow!(i18n::tr("pdf417-format", &[]))).code(ErrorCode::Usage);
+    }
+    let out = if out_given {
+        args.out.clone()
+    } else {
+        format!("pdf417.{}", format.extension())
+    };
+    let out = out.as_str();
+    let is_pdf = format == output::Format::Pdf;
+    if args.print_size.is_some() && !is_pdf {
+        return Err(anyhow!(i18n::tr("pdf417-print-size", &[]))).code(ErrorCode::Usage);
+    }
+    let symbol = encode(payload).code(ErrorCode::InvalidPayload)?;
+    if args.verbose {
+        log::info(symbol.describe());
+    }
+    let opts = RenderOptions::from_args(args);
+    let font = cache::font(args.font_family.as_deref())?;
+
+    if is_pdf {
+        let modules = symbol.width() + 2 * QUIET;
+        let width = args
+            .print_size
+            .map_or(modules as f64 * NOMINAL_X_MM * MM, |length| length.0);
+        let lines: Vec<String> = caption
+            .into_iter()
+            .flat_map(str::lines)
+            .map(String::from)
+            .collect();
+        pdf::warn_unencodable(&lines);
+        let colors = Colors {
+            fg: opts.fg,
+            quiet: opts.quiet_color,
+            text: args.text_color,
+            band: args.band_color,
+        };
+        let bytes = document(&symbol, width, &lines, &colors, &font.face(0))?;
+        return pdf::save(args, payload, out, &bytes);
+    }
+
+    let img = render(&symbol, args.size.px, opts.fg, opts.quiet_color)?;
+    let img = match caption {
+        Some(text) => add_url_text_below(&img, text, &font, &opts.caption)?,
+        None => img,
+    };
+    let hash = meta::sha256_hex(payload.as_bytes());
+    let out = output::claim_path(
+        Path::new(out),
+        &hash[..8],
+        args.no_clobber,
+        args.unique,
+        Path::exists,
+    )?;
+    let out = out.to_string_lossy();
+    save_image(
+        &img,
+        &out,
+        args.embed_alt,
+        alt_text,
+        payload,
+        &[],
+        None,
+        args.size.dpi,
+    )?;
+    log::info(i18n::tr("wrote", &[("path", out.as_ref().into())]));
+    if args.open
+        && let Err(e) = preview::open(&out)
+    {
+        log::warn(format!("{:#}", e));
+    }
+    Ok(())
+}
+
+/// The symbol at the largest whole number of pixels per module that fits in `size`,
+/// quiet zone included: `fg` bars on `quiet_color`.
+fn render(symbol: &Pdf417, size: u32, fg: Color, quiet_color: Color) -> Result<Canvas> {
+    let modules = (symbol.width() + 2 * QUIET) as u32;
+    let x_dim = size / modules;
+    if x_dim == 0 {
+        bail!(i18n::tr(
+            "pdf417-size-too-small",
+            &[("size", size.into()), ("modules", modules.into())]
+        ));
+    }
+    let quiet = QUIET as u32 * x_dim;
+    let row_px = ROW_HEIGHT as u32 * x_dim;
+    let mut img = Canvas::from_pixel(
+        modules * x_dim,
+        (symbol.height() + 2 * QUIET) as u32 * x_dim,
+        quiet_color.0,
+    );
+    for (x, row, len) in symbol.dark_runs() {
+        let (x, row, len) = (x as u32, row as u32, len as u32);
+        let bars = quiet + x * x_dim..quiet + (x + len) * x_dim;
+        for y in 0..row_px {
+            raster::fill_row(&mut img, quiet + row * row_px + y, bars.clone(), fg.0);
+        }
+    }
+    Ok(img)
+}
+
+/// Colors of the PDF: bars, quiet zone, caption text and the band behind it.
+struct Colors {
+    fg: Color,
+    quiet: Color,
+    text: Color,
+    band: Color,
+}
+
+/// A one-page PDF of the symbol `width` points wide with its quiet zone, and the
+/// caption `lines` in a band under it.
+fn document(
+    symbol: &Pdf417,
+    width: f64,
+    lines: &[String],
+    colors: &Colors,
+    face: &FontFace,
+) -> Result<Vec<u8>> {
+    let x_dim = width / (symbol.width() + 2 * QUIET) as f64;
+    let quiet = QUIET as f64 * x_dim;
+    let row_h = ROW_HEIGHT as f64 * x_dim;
+    let code_h = (symbol.height() + 2 * QUIET) as f64 * x_dim;
+
+    let mut doc = Document::default();
+    let font = doc.font(face)?;
+    let size = lines
+        .iter()
+        .map(|line| font.fit(line, CAPTION_PT, width - 2.0 * quiet))
+        .fold(CAPTION_PT, f64::min);
+    let line_h = size * 1.25;
+    let band_h = match lines.len() {
+        0 => 0.0,
+        n => n as f64 * line_h + quiet,
+    };
+    let height = code_h + band_h;
+
+    let mut content = Content::default();
+    content.fill_color(colors.quiet);
+    content.fill_rect(0.0, band_h, width, code_h);
+    content.fill_color(colors.fg);
+    let top = height - quiet;
+    for (x, row, len) in symbol.dark_runs() {
+        content.fill_rect(
+            quiet + x as f64 * x_dim,
+            top - (row + 1) as f64 * row_h,
+            len as f64 * x_dim,
+            row_h,
+        );
+    }
+    if band_h > 0.0 {
+        content.fill_color(colors.band);
+        content.fill_rect(0.0, 0.0, width, band_h);
+        content.fill_color(colors.text);
+        for (i, line) in lines.iter().enumerate() {
+            let baseline =
+                band_h - quiet / 2.0 - (i as f64 + 1.0) * line_h - font.descent * size / 1000.0;
+            let left = (width - font.width(line, size)) / 2.0;
+            content.text(&font, size, left, baseline, line);
+        }
+    }
+    let page = [0.0, 0.0, width, height];
+    doc.page(width, height, page, page, content);
+    Ok(doc.finish(&symbol.describe()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_embedded_font;
+
+    #[test]
+    fn test_render() {
+        let symbol = encode("DAQ D12345678\nDCS SMITH").unwrap();
+        let modules = (symbol.width() + 2 * QUIET) as u32;
+        let img = render(&symbol, 3 * modules + 2, Color::BLACK, Color::WHITE).unwrap();
+        // Three pixels per module, rows three modules tall, two modules of quiet zone.
+        assert_eq!(img.width(), 3 * modules);
+        assert_eq!(img.height(), 3 * (symbol.height() + 2 * QUIET) as u32);
+        let quiet = 3 * QUIET as u32;
+        assert_eq!(*img.get_pixel(quiet - 1, quiet), Color::WHITE.0);
+        // The start pattern: eight bars, then a space.
+        assert_eq!(*img.get_pixel(quiet, quiet), Color::BLACK.0);
+        assert_eq!(
+            *img.get_pixel(quiet + 3 * 8 - 1, img.height() - quiet - 1),
+            Color::BLACK.0
+        );
+        assert_eq!(*img.get_pixel(quiet + 3 * 8, quiet), Color::WHITE.0);
+        assert!(render(&symbol, modules - 1, Color::BLACK, Color::WHITE).is_err());
+
+        let colors = Colors {
+            fg: Color::BLACK,
+            quiet: Color::WHITE,
+            text: Color::BLACK,
+            band: Color::WHITE,
+        };
+        let face = load_embedded_font().unwrap();
+        let lines = vec!["1Z999AA10123456784".to_string()];
+        let doc = document(&symbol, 200.0, &lines, &colors, &face).unwrap();
+        assert!(doc.starts_with(b"%PDF-"));
+        assert!(document(&symbol, 200.0, &[], &colors, &face).unwrap().len() < doc.len());
+    }
+}
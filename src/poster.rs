@@ -0,0 +1,339 @@
+//! `qrbrand poster`: a "scan for the menu" sign or table tent as PDF.
+//!
+//! A headline, an optional subhead, the code and a footer with the `--image` logo and
+//! a line of text, centred on A5, A4 or US letter. A tent puts the same panel on both
+//! halves of the sheet, the top one upside down, so it reads from both sides once
+//! folded along the dashed line. Settings come from flags or a TOML spec file whose
+//! keys match them; flags win.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::fonts::FontFace;
+use crate::pdf::{self, Content, Document, Font, Image, MM, Style};
+use crate::symbol::Symbol;
+use crate::{Args, Assets, RenderOptions};
+use anyhow::{Context, Result, anyhow};
+use clap::{Args as ClapArgs, ValueEnum};
+use image::DynamicImage;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(ClapArgs, Debug)]
+pub struct PosterArgs {
+    /// TOML file with any of: paper, layout, headline, subhead, footer, logo
+    #[arg(long = "spec", value_name = "FILE")]
+    pub spec: Option<PathBuf>,
+
+    /// Paper size [default: a4]
+    #[arg(long = "paper", value_enum)]
+    pub paper: Option<Paper>,
+
+    /// sign (one panel) or tent (two panels, folded) [default: sign]
+    #[arg(long = "layout", value_enum)]
+    pub layout: Option<Layout>,
+
+    /// Large text above the code, e.g. "Scan for our menu"
+    #[arg(long = "headline")]
+    pub headline: Option<String>,
+
+    /// Smaller text under the headline
+    #[arg(long = "subhead")]
+    pub subhead: Option<String>,
+
+    /// Text at the bottom, under the logo
+    #[arg(long = "footer")]
+    pub footer: Option<String>,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Paper {
+    A5,
+    A4,
+    Letter,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    Sign,
+    Tent,
+}
+
+impl Paper {
+    /// Portrait width and height in points.
+    fn size(self) -> (f64, f64) {
+        match self {
+            Self::A5 => (148.0 * MM, 210.0 * MM),
+            Self::A4 => (210.0 * MM, 297.0 * MM),
+            Self::Letter => (8.5 * 72.0, 11.0 * 72.0),
+        }
+    }
+}
+
+/// The spec file: the same settings as the flags.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+struct Spec {
+    paper: Option<Paper>,
+    layout: Option<Layout>,
+    headline: Option<String>,
+    subhead: Option<String>,
+    footer: Option<String>,
+    /// Relative to the spec file.
+    logo: Option<PathBuf>,
+}
+
+/// What goes on the poster, flags and spec merged.
+struct Poster {
+    paper: Paper,
+    layout: Layout,
+    headline: String,
+    subhead: Option<String>,
+    footer: Option<String>,
+}
+
+/// A panel smaller than this on its short side cannot hold a useful code.
+const MIN_QR: f64 = 25.0 * MM;
+
+pub fn run(args: &Args, poster: &PosterArgs, payload: &str, out_given: bool) -> Result<()> {
+    let spec = match &poster.spec {
+        Some(path) => load_spec(path)?,
+        None => Spec::default(),
+    };
+    let Some(headline) = poster.headline.clone().or(spec.headline) else {
+        return Err(anyhow!(
+            "a poster needs --headline (or headline in the spec)"
+        ))
+        .code(ErrorCode::Usage);
+    };
+    let resolved = Poster {
+        paper: poster.paper.or(spec.paper).unwrap_or(Paper::A4),
+        layout: poster.layout.or(spec.layout).unwrap_or(Layout::Sign),
+        headline,
+        subhead: poster.subhead.clone().or(spec.subhead),
+        footer: poster.footer.clone().or(spec.footer),
+    };
+    let logo = args.image.clone().or_else(|| {
+        let dir = poster.spec.as_deref().and_then(Path::parent)?;
+        spec.logo
+            .map(|logo| dir.join(logo).to_string_lossy().into_owned())
+    });
+
+    let opts = RenderOptions::from_args(args);
+    let code = pdf::encode_checked(payload, &opts)?;
+    let texts = [
+        Some(&resolved.headline),
+        resolved.subhead.as_ref(),
+        resolved.footer.as_ref(),
+    ];
+    pdf::warn_unencodable(texts.into_iter().flatten());
+
+    let assets = Assets::load(logo.as_deref(), args.font_family.as_deref(), None)?;
+    let style = Style::new(args, &opts);
+    let bytes = document(
+        &resolved,
+        &code,
+        opts.quiet,
+        &style,
+        assets.logo.as_deref(),
+        &assets.font.face(0),
+    )?;
+    let out = if out_given {
+        args.out.as_str()
+    } else {
+        "poster.pdf"
+    };
+    pdf::save(args, payload, out, &bytes)
+}
+
+fn load_spec(path: &Path) -> Result<Spec> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read poster spec: {}", path.display()))?;
+    toml::from_str(&text)
+        .with_context(|| format!("Invalid poster spec: {}", path.display()))
+        .code(ErrorCode::Usage)
+}
+
+/// One panel's worth of drawing, shared by both halves of a tent.
+struct Panel<'a> {
+    poster: &'a Poster,
+    code: &'a Symbol,
+    quiet: u32,
+    style: &'a Style,
+    font: &'a Font,
+    /// The embedded logo and the size it is drawn at.
+    logo: Option<(Image, f64, f64)>,
+}
+
+fn document(
+    poster: &Poster,
+    code: &Symbol,
+    quiet: u32,
+    style: &Style,
+    logo: Option<&DynamicImage>,
+    face: &FontFace,
+) -> Result<Vec<u8>> {
+    let (w, h) = poster.paper.size();
+    let panel_h = match poster.layout {
+        Layout::Sign => h,
+        Layout::Tent => h / 2.0,
+    };
+    let mut doc = Document::default();
+    let font = doc.font(face)?;
+    let logo = logo.map(|logo| doc.logo(logo, w * 0.4, panel_h * 0.1));
+    let panel = Panel {
+        poster,
+        code,
+        quiet,
+        style,
+        font: &font,
+        logo,
+    };
+
+    let mut content = Content::default();
+    if style.background.0[3] > 0 {
+        content.fill_color(style.background);
+        content.fill_rect(0.0, 0.0, w, h);
+    }
+    panel.draw(&mut content, w, panel_h)?;
+    if poster.layout == Layout::Tent {
+        // Half a turn about the centre of the sheet puts the copy on the top half.
+        content.transform([-1.0, 0.0, 0.0, -1.0, w, h]);
+        panel.draw(&mut content, w, panel_h)?;
+        content.restore();
+        content.transform([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        content.stroke_color(style.text, 0.5);
+        content.dash(4.0);
+        content.line(0.0, panel_h, w, panel_h);
+        content.restore();
+    }
+    let page = [0.0, 0.0, w, h];
+    doc.page(w, h, page, page, content);
+    Ok(doc.finish(&poster.headline))
+}
+
+impl Panel<'_> {
+    /// Draw the panel into the `w` x `h` points at the origin.
+    fn draw(&self, content: &mut Content, w: f64, h: f64) -> Result<()> {
+        let margin = w * 0.08;
+        let gap = h * 0.03;
+        let width = w - 2.0 * margin;
+        let (ascent, descent) = (self.font.ascent / 1000.0, self.font.descent / 1000.0);
+        let centred = |content: &mut Content, text: &str, max_pt: f64, baseline: f64| {
+            let size = self.font.fit(text, max_pt, width);
+            let x = (w - self.font.width(text, size)) / 2.0;
+            content.text(self.font, size, x, baseline, text);
+            size
+        };
+
+        content.fill_color(self.style.text);
+        // Type sizes scale with the panel; long lines shrink to fit.
+        let (head_pt, sub_pt, foot_pt) = (w * 0.08, w * 0.04, w * 0.025);
+        let baseline = h - margin - ascent * head_pt;
+        let size = centred(content, &self.poster.headline, head_pt, baseline);
+        let mut top = baseline + descent * size;
+        if let Some(subhead) = &self.poster.subhead {
+            let baseline = top - gap * 0.5 - ascent * sub_pt;
+            let size = centred(content, subhead, sub_pt, baseline);
+            top = baseline + descent * size;
+        }
+
+        let mut bottom = margin;
+        if let Some(footer) = &self.poster.footer {
+            let baseline = bottom - descent * foot_pt;
+            let size = centred(content, footer, foot_pt, baseline);
+            bottom = baseline + ascent * size + gap * 0.5;
+        }
+        if let Some((image, lw, lh)) = &self.logo {
+            content.image(image, (w - lw) / 2.0, bottom, *lw, *lh);
+            bottom += lh;
+        }
+
+        let side = (w * 0.62).min(top - bottom - 2.0 * gap);
+        if side < MIN_QR {
+            return Err(anyhow!(
+                "no room for the code on {:?} paper; shorten the text or use a larger paper",
+                self.poster.paper
+            ))
+            .code(ErrorCode::Usage);
+        }
+        let y = bottom + (top - bottom - side) / 2.0;
+        content.symbol(
+            self.code,
+            self.quiet,
+            (w - side) / 2.0,
+            y,
+            side,
+            self.style.fg,
+            self.style.quiet,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::load_embedded_font;
+
+    #[test]
+    fn test_poster() {
+        let spec: Spec = toml::from_str(
+            r#"
+            paper = "a5"
+            layout = "tent"
+            headline = "Scan for our menu"
+            logo = "logo.png"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(spec.paper, Some(Paper::A5));
+        assert_eq!(spec.layout, Some(Layout::Tent));
+        assert!(toml::from_str::<Spec>("headlne = \"typo\"").is_err());
+
+        let code = crate::symbol::encode("https://example.com/menu", &Default::default()).unwrap();
+        let style = Style {
+            fg: Color::BLACK,
+            quiet: Color::WHITE,
+            text: Color::BLACK,
+            background: Color::TRANSPARENT,
+        };
+        let face = load_embedded_font().unwrap();
+        let poster = |layout| Poster {
+            paper: Paper::A5,
+            layout,
+            headline: "Scan for our menu".to_string(),
+            subhead: Some("Order from your table".to_string()),
+            footer: Some("Trattoria Roma".to_string()),
+        };
+        let sign = document(&poster(Layout::Sign), &code, 4, &style, None, &face).unwrap();
+        let tent = document(&poster(Layout::Tent), &code, 4, &style, None, &face).unwrap();
+        for pdf in [&sign, &tent] {
+            assert!(String::from_utf8_lossy(pdf).contains("/MediaBox [0 0 419.5276 595.2756]"));
+        }
+        // A tent's copy is turned half a turn and there is a fold line.
+        let content = |pdf: &[u8]| {
+            // The page content is the last stream written.
+            let start = pdf.windows(10).rposition(|w| w == b">>\nstream\n").unwrap() + 10;
+            let dict = String::from_utf8_lossy(&pdf[..start]);
+            let len: usize = dict
+                .rsplit("/Length ")
+                .next()
+                .and_then(|s| s.split(' ').next())
+                .and_then(|s| s.parse().ok())
+                .unwrap();
+            let mut out = String::new();
+            use std::io::Read;
+            flate2::read::ZlibDecoder::new(&pdf[start..start + len])
+                .read_to_string(&mut out)
+                .unwrap();
+            out
+        };
+        assert!(!content(&sign).contains(" cm\n"));
+        let tent = content(&tent);
+        assert!(tent.contains("q -1 0 0 -1 419.5276 595.2756 cm\n"));
+        assert_eq!(tent.matches(" Tj ET").count(), 6);
+        assert!(tent.contains("[4 4] 0 d\n"));
+    }
+}
@@ -0,0 +1,85 @@
+//! `--open` and `--preview`: look at the result straight away.
+//!
+//! `--open` hands the written file to the system's default image viewer.
+//! `--preview` shows the image in a window before anything is written; it needs
+//! the `preview` feature (a desktop session at runtime).
+
+use crate::raster::Canvas;
+use anyhow::Result;
+
+/// Largest window the preview opens, in pixels; bigger images are scaled down to fit.
+#[cfg(feature = "preview")]
+const MAX_WINDOW: (u32, u32) = (1000, 800);
+
+/// Open `path` in the default image viewer without waiting for it.
+#[cfg(feature = "open")]
+pub fn open(path: &str) -> Result<()> {
+    use anyhow::Context;
+    ::open::that_detached(path).with_context(|| format!("Failed to open {} in a viewer", path))
+}
+
+#[cfg(not(feature = "open"))]
+pub fn open(_path: &str) -> Result<()> {
+    anyhow::bail!("--open requires qrbrand to be built with the `open` feature")
+}
+
+/// Show `img` until the user keeps it (Enter or S) or discards it (Esc, Q or closing
+/// the window). Returns whether to keep it.
+#[cfg(feature = "preview")]
+pub fn confirm(img: &Canvas) -> Result<bool> {
+    use anyhow::Context;
+    use image::imageops::{self, FilterType};
+    use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+    let (w, h) = img.dimensions();
+    let scale = (MAX_WINDOW.0 as f32 / w as f32)
+        .min(MAX_WINDOW.1 as f32 / h as f32)
+        .min(1.0);
+    let (sw, sh) = (
+        ((w as f32 * scale).round() as u32).max(1),
+        ((h as f32 * scale).round() as u32).max(1),
+    );
+    let shown = imageops::resize(img, sw, sh, FilterType::Triangle);
+    // Transparent areas over a light checkerboard, 0RGB as minifb expects.
+    let buffer: Vec<u32> = shown
+        .enumerate_pixels()
+        .map(|(x, y, px)| {
+            let back = if ((x / 8) + (y / 8)) % 2 == 0 {
+                255.0
+            } else {
+                220.0
+            };
+            let a = px[3] as f32 / 255.0;
+            let c = |i: usize| (px[i] as f32 * a + back * (1.0 - a)).round() as u32;
+            (c(0) << 16) | (c(1) << 8) | c(2)
+        })
+        .collect();
+
+    let mut window = Window::new(
+        "qrbrand preview - Enter to save, Esc to discard",
+        sw as usize,
+        sh as usize,
+        WindowOptions::default(),
+    )
+    .context("Failed to open the preview window")?;
+    window.set_target_fps(30);
+    crate::log::info("Preview open: Enter or S saves, Esc or Q discards");
+    while window.is_open() {
+        for key in window.get_keys_pressed(KeyRepeat::No) {
+            match key {
+                Key::Enter | Key::NumPadEnter | Key::S => return Ok(true),
+                Key::Escape | Key::Q => return Ok(false),
+                _ => {}
+            }
+        }
+        window
+            .update_with_buffer(&buffer, sw as usize, sh as usize)
+            .context("Failed to draw the preview")?;
+    }
+    Ok(false)
+}
+
+#[cfg(not(feature = "preview"))]
+pub fn confirm(_img: &Canvas) -> Result<bool> {
+    anyhow::bail!("--preview requires qrbrand to be built with the `preview` feature")
+}
@@ -0,0 +1,99 @@
+//! `--print`: hand the written file to a CUPS queue with `lp`, for label stations that
+//! go from scan to printed code in one command.
+//!
+//! IPP printers are reached through a CUPS queue (`lpadmin -p Labels -E -v
+//! ipp://printer.local/ipp/print -m everywhere`). With `--print-size` the job asks
+//! for a page of the image's physical size and prints raster output at the matching
+//! resolution, so the code comes out at the declared size rather than scaled to the
+//! printer's default media.
+
+use anyhow::{Context, Result, bail};
+use std::process::Command;
+
+const MM_PER_PT: f64 = 25.4 / 72.0;
+
+/// The physical page a job prints on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Page {
+    /// Page size in points.
+    pub width: f64,
+    pub height: f64,
+    /// Raster pixels per inch, or `None` for vector output, which carries its own size.
+    pub ppi: Option<f64>,
+}
+
+/// The `lp` arguments that print `path` on `printer`.
+fn lp_args(printer: &str, copies: u32, page: Option<Page>, path: &str) -> Vec<String> {
+    let mut args = vec![
+        "-d".to_string(),
+        printer.to_string(),
+        "-n".to_string(),
+        copies.to_string(),
+    ];
+    if let Some(page) = page {
+        let mm = |pt: f64| (pt * MM_PER_PT * 10.0).round() / 10.0;
+        args.push("-o".to_string());
+        args.push(format!(
+            "media=Custom.{}x{}mm",
+            mm(page.width),
+            mm(page.height)
+        ));
+        if let Some(ppi) = page.ppi {
+            args.push("-o".to_string());
+            args.push(format!("ppi={}", ppi.round()));
+        }
+    }
+    args.push(path.to_string());
+    args
+}
+
+/// Print `copies` of the file at `path` on `printer` and return what `lp` reports
+/// (e.g. `request id is Labels-42 (1 file(s))`).
+pub fn send(printer: &str, copies: u32, page: Option<Page>, path: &str) -> Result<String> {
+    let output = Command::new("lp")
+        .args(lp_args(printer, copies, page, path))
+        .output()
+        .context("Failed to run lp (is CUPS installed?)")?;
+    if !output.status.success() {
+        bail!(
+            "lp could not print {} on {}: {}",
+            path,
+            printer,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lp_args() {
+        assert_eq!(
+            lp_args("Labels", 1, None, "code.png"),
+            ["-d", "Labels", "-n", "1", "code.png"]
+        );
+        // 2in square at 600 px: 300 ppi.
+        let page = Page {
+            width: 144.0,
+            height: 144.0,
+            ppi: Some(300.0),
+        };
+        assert_eq!(
+            lp_args("HP-Label-Printer", 3, Some(page), "label.png"),
+            [
+                "-d",
+                "HP-Label-Printer",
+                "-n",
+                "3",
+                "-o",
+                "media=Custom.50.8x50.8mm",
+                "-o",
+                "ppi=300",
+                "label.png"
+            ]
+        );
+    }
+}
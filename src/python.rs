@@ -0,0 +1,106 @@
+//! The `qrbrand` Python module (the `python` feature, built with maturin).
+//!
+//! ```python
+//! import qrbrand
+//! png = qrbrand.render({"url": "https://example.com", "style": {"size": 512}})
+//! width, height, rgba = qrbrand.render_rgba({"url": "https://example.com"}, logo=logo_bytes)
+//! image = PIL.Image.frombuffer("RGBA", (width, height), rgba)
+//! ```
+//!
+//! Options are a batch record as a dict or a JSON string. The GIL is released while
+//! rendering, so threads render in parallel.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+/// Render a code to PNG bytes. `logo` is an optional encoded PNG or JPEG image.
+#[pyfunction]
+#[pyo3(signature = (options, logo = None))]
+fn render<'py>(
+    py: Python<'py>,
+    options: &Bound<'py, PyAny>,
+    logo: Option<&[u8]>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let png = render_png(py, options, logo)?;
+    Ok(PyBytes::new(py, &png))
+}
+
+/// Render a code to `(width, height, rgba)`, ready for `PIL.Image.frombuffer("RGBA", ...)`.
+#[pyfunction]
+#[pyo3(signature = (options, logo = None))]
+fn render_rgba<'py>(
+    py: Python<'py>,
+    options: &Bound<'py, PyAny>,
+    logo: Option<&[u8]>,
+) -> PyResult<(u32, u32, Bound<'py, PyBytes>)> {
+    let options = options_json(py, options)?;
+    let img = py
+        .detach(|| crate::api::render_rgba(&options, logo))
+        .map_err(|e| PyValueError::new_err(format!("{:#}", e)))?;
+    Ok((img.width(), img.height(), PyBytes::new(py, img.as_raw())))
+}
+
+fn render_png(
+    py: Python<'_>,
+    options: &Bound<'_, PyAny>,
+    logo: Option<&[u8]>,
+) -> PyResult<Vec<u8>> {
+    let options = options_json(py, options)?;
+    py.detach(|| crate::api::render(&options, logo))
+        .map_err(|e| PyValueError::new_err(format!("{:#}", e)))
+}
+
+/// `options`, a dict or a JSON string, as JSON.
+fn options_json(py: Python<'_>, options: &Bound<'_, PyAny>) -> PyResult<String> {
+    if options.is_instance_of::<PyString>() {
+        return options.extract();
+    }
+    py.import("json")?
+        .call_method1("dumps", (options,))?
+        .extract()
+}
+
+#[pymodule]
+fn qrbrand(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(render, m)?)?;
+    m.add_function(wrap_pyfunction!(render_rgba, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_module() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "qrbrand").unwrap();
+            qrbrand(&module).unwrap();
+            let options = PyDict::new(py);
+            options.set_item("url", "https://example.com").unwrap();
+            let (width, height, rgba): (u32, u32, Vec<u8>) = module
+                .call_method1("render_rgba", (&options,))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(rgba.len(), (width * height * 4) as usize);
+
+            // A JSON string works as well as a dict, and the PNG holds the same pixels.
+            let png: Vec<u8> = module
+                .call_method1("render", (r#"{"url": "https://example.com"}"#,))
+                .unwrap()
+                .extract()
+                .unwrap();
+            let img = image::load_from_memory(&png).unwrap().to_rgba8();
+            assert_eq!((img.width(), img.height()), (width, height));
+            assert_eq!(img.into_raw(), rgba);
+
+            let err = module.call_method1("render", ("not json",)).unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            assert!(err.to_string().contains("Invalid options JSON"));
+        });
+    }
+}
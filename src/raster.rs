@@ -0,0 +1,433 @@
+//! Small anti-aliased rasterizer shared by module, plate and frame drawing.
+//!
+//! Shapes are described by an inside test and are convex, apart from [`Band`]. Pixels whose four corners
+//! are all inside are filled directly; edge pixels are supersampled on a 4x4 grid
+//! and the coverage is used as extra alpha. Shapes that know the interior of each
+//! row ([`Shape::inner_span`]) have it filled as a slice, without any inside tests,
+//! which is what keeps large canvases fast.
+
+use image::{ImageBuffer, Rgba, imageops};
+
+pub type Canvas = ImageBuffer<Rgba<u8>, Vec<u8>>;
+
+/// Subsamples per axis for edge pixels.
+const SUBSAMPLES: u32 = 4;
+
+/// A convex shape in pixel coordinates (pixel `(x, y)` spans `x..x+1`, `y..y+1`).
+pub trait Shape {
+    /// Bounding box as `(x0, y0, x1, y1)`.
+    fn bounds(&self) -> (f32, f32, f32, f32);
+    fn contains(&self, x: f32, y: f32) -> bool;
+
+    /// An `x` range that is inside for every `y` in `y0..=y1`, if the shape can tell.
+    fn inner_span(&self, _y0: f32, _y1: f32) -> Option<(f32, f32)> {
+        None
+    }
+}
+
+/// An axis-aligned rectangle with optional rounded corners.
+#[derive(Clone, Copy, Debug)]
+pub struct RoundedRect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+    /// Corner radius, clamped to half the shorter side.
+    pub radius: f32,
+}
+
+impl RoundedRect {
+    pub fn new(x: f32, y: f32, w: f32, h: f32, radius: f32) -> Self {
+        let radius = radius.clamp(0.0, w.min(h) / 2.0);
+        Self { x, y, w, h, radius }
+    }
+
+    /// Shrink by `by` on every side, keeping corners concentric.
+    pub fn inset(&self, by: f32) -> Self {
+        Self::new(
+            self.x + by,
+            self.y + by,
+            (self.w - 2.0 * by).max(0.0),
+            (self.h - 2.0 * by).max(0.0),
+            self.radius - by,
+        )
+    }
+
+    /// Grow by `by` on every side, keeping corners concentric.
+    pub fn outset(&self, by: f32) -> Self {
+        Self::new(
+            self.x - by,
+            self.y - by,
+            self.w + 2.0 * by,
+            self.h + 2.0 * by,
+            self.radius + by,
+        )
+    }
+
+    pub fn area(&self) -> f32 {
+        self.w * self.h - (4.0 - std::f32::consts::PI) * self.radius * self.radius
+    }
+
+    /// Whether `other` lies entirely within this shape's bounding box.
+    pub fn encloses(&self, other: &RoundedRect) -> bool {
+        let (x0, y0, x1, y1) = self.bounds();
+        let (ox0, oy0, ox1, oy1) = other.bounds();
+        x0 <= ox0 && y0 <= oy0 && x1 >= ox1 && y1 >= oy1
+    }
+}
+
+/// The band between two rounded rectangles, `inner` lying inside `outer`: a stroke.
+/// Edge pixels are exact as long as the band is at least a pixel wide.
+#[derive(Clone, Copy, Debug)]
+pub struct Band {
+    pub outer: RoundedRect,
+    pub inner: RoundedRect,
+}
+
+impl Shape for Band {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        self.outer.bounds()
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        self.outer.contains(x, y) && !self.inner.contains(x, y)
+    }
+}
+
+impl Shape for RoundedRect {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, self.x + self.w, self.y + self.h)
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        if x < self.x || y < self.y || x > self.x + self.w || y > self.y + self.h {
+            return false;
+        }
+        let r = self.radius;
+        let dx = (self.x + r - x).max(x - (self.x + self.w - r)).max(0.0);
+        let dy = (self.y + r - y).max(y - (self.y + self.h - r)).max(0.0);
+        dx * dx + dy * dy <= r * r
+    }
+
+    fn inner_span(&self, y0: f32, y1: f32) -> Option<(f32, f32)> {
+        if y0 < self.y || y1 > self.y + self.h {
+            return None;
+        }
+        // The row is narrowest where it is deepest into a corner.
+        let r = self.radius;
+        let depth = |y: f32| (self.y + r - y).max(y - (self.y + self.h - r)).max(0.0);
+        let dy = depth(y0).max(depth(y1));
+        let dx = r - (r * r - dy * dy).max(0.0).sqrt();
+        Some((self.x + dx, self.x + self.w - dx))
+    }
+}
+
+/// `shape` moved up by `dy` pixels: drawn into a strip that starts at row `dy` of the
+/// full image, it covers exactly the pixels it would there.
+#[derive(Clone, Copy, Debug)]
+pub struct Shifted<S> {
+    pub shape: S,
+    pub dy: f32,
+}
+
+impl<S> Shifted<S> {
+    pub fn new(shape: S, dy: f32) -> Self {
+        Self { shape, dy }
+    }
+}
+
+impl<S: Shape> Shape for Shifted<S> {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        let (x0, y0, x1, y1) = self.shape.bounds();
+        (x0, y0 - self.dy, x1, y1 - self.dy)
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        self.shape.contains(x, y + self.dy)
+    }
+
+    fn inner_span(&self, y0: f32, y1: f32) -> Option<(f32, f32)> {
+        self.shape.inner_span(y0 + self.dy, y1 + self.dy)
+    }
+}
+
+/// Fill `shape` with `color`, anti-aliasing its edges. Returns the covered area in pixels.
+pub fn fill(img: &mut Canvas, shape: &impl Shape, color: Rgba<u8>) -> f32 {
+    let (bx0, by0, bx1, by1) = shape.bounds();
+    let x_start = bx0.floor().max(0.0) as u32;
+    let y_start = by0.floor().max(0.0) as u32;
+    let x_end = (bx1.ceil().max(0.0) as u32).min(img.width());
+    let y_end = (by1.ceil().max(0.0) as u32).min(img.height());
+    let mut area = 0.0;
+
+    for y in y_start..y_end {
+        let (inner_start, inner_end) = inner_pixels(shape, y, x_start, x_end);
+        fill_row(img, y, inner_start..inner_end, color);
+        area += (inner_end - inner_start) as f32;
+        for x in (x_start..inner_start).chain(inner_end..x_end) {
+            let coverage = pixel_coverage(shape, x as f32, y as f32);
+            if coverage > 0.0 {
+                let dst = *img.get_pixel(x, y);
+                img.put_pixel(x, y, composite(dst, color, coverage));
+                area += coverage;
+            }
+        }
+    }
+    area
+}
+
+/// Composite `color` over pixels `xs` of row `y`, writing the buffer directly.
+pub fn fill_row(img: &mut Canvas, y: u32, xs: std::ops::Range<u32>, color: Rgba<u8>) {
+    if xs.is_empty() {
+        return;
+    }
+    let row = (y * img.width()) as usize;
+    let span = &mut (**img)[(row + xs.start as usize) * 4..(row + xs.end as usize) * 4];
+    for px in span.chunks_exact_mut(4) {
+        let out = if color[3] == 255 {
+            color
+        } else {
+            composite(Rgba([px[0], px[1], px[2], px[3]]), color, 1.0)
+        };
+        px.copy_from_slice(&out.0);
+    }
+}
+
+/// Keep only the part of `img` inside `shape`, scaling each pixel's alpha by its coverage.
+pub fn clip(img: &mut Canvas, shape: &impl Shape) {
+    let (w, h) = img.dimensions();
+    for y in 0..h {
+        let (inner_start, inner_end) = inner_pixels(shape, y, 0, w);
+        for x in (0..inner_start).chain(inner_end..w) {
+            let coverage = pixel_coverage(shape, x as f32, y as f32);
+            if coverage < 1.0 {
+                let px = img.get_pixel_mut(x, y);
+                px[3] = (px[3] as f32 * coverage).round() as u8;
+            }
+        }
+    }
+}
+
+/// The pixels of row `y`, within `x0..x1`, that lie wholly inside `shape` by its
+/// [`Shape::inner_span`]; an empty range at `x1` when it has none.
+fn inner_pixels(shape: &impl Shape, y: u32, x0: u32, x1: u32) -> (u32, u32) {
+    match shape.inner_span(y as f32, y as f32 + 1.0) {
+        Some((s0, s1)) => {
+            let start = (s0.ceil().max(x0 as f32) as u32).min(x1);
+            let end = (s1.floor().max(0.0) as u32).clamp(start, x1);
+            (start, end)
+        }
+        None => (x1, x1),
+    }
+}
+
+/// `imageops::overlay`, copying fully opaque rows of `top` as slices; rows with any
+/// transparency are blended by `imageops` as before.
+pub fn overlay(bottom: &mut Canvas, top: &Canvas, x: u32, y: u32) {
+    copy_rows(bottom, top, x, y, |bottom, row, ty| {
+        if row.chunks_exact(4).all(|px| px[3] == 255) {
+            return false;
+        }
+        let line = imageops::crop_imm(top, 0, ty, top.width(), 1);
+        imageops::overlay(bottom, &*line, x.into(), (y + ty).into());
+        true
+    });
+}
+
+/// `imageops::replace`, one row slice at a time.
+pub fn replace(bottom: &mut Canvas, top: &Canvas, x: u32, y: u32) {
+    copy_rows(bottom, top, x, y, |_, _, _| false);
+}
+
+/// Copy each row of `top` that fits in `bottom` at (x, y), unless `handled` (given the
+/// row's visible bytes and index) has dealt with it.
+fn copy_rows(
+    bottom: &mut Canvas,
+    top: &Canvas,
+    x: u32,
+    y: u32,
+    mut handled: impl FnMut(&mut Canvas, &[u8], u32) -> bool,
+) {
+    let (bw, bh) = bottom.dimensions();
+    if x >= bw || y >= bh {
+        return;
+    }
+    let w = top.width().min(bw - x) as usize * 4;
+    let rows = top.height().min(bh - y);
+    let stride = top.width() as usize * 4;
+    for ty in 0..rows {
+        let src = &top.as_raw()[ty as usize * stride..][..w];
+        if !handled(bottom, src, ty) {
+            let start = ((y + ty) * bw + x) as usize * 4;
+            (**bottom)[start..start + w].copy_from_slice(src);
+        }
+    }
+}
+
+fn pixel_coverage(shape: &impl Shape, x: f32, y: f32) -> f32 {
+    let corners = [(x, y), (x + 1.0, y), (x, y + 1.0), (x + 1.0, y + 1.0)];
+    if corners.iter().all(|&(cx, cy)| shape.contains(cx, cy)) {
+        return 1.0;
+    }
+
+    let step = 1.0 / SUBSAMPLES as f32;
+    let mut hits = 0;
+    for sy in 0..SUBSAMPLES {
+        for sx in 0..SUBSAMPLES {
+            let px = x + (sx as f32 + 0.5) * step;
+            let py = y + (sy as f32 + 0.5) * step;
+            if shape.contains(px, py) {
+                hits += 1;
+            }
+        }
+    }
+    hits as f32 / (SUBSAMPLES * SUBSAMPLES) as f32
+}
+
+/// Source-over compositing of `src`, scaled by `coverage`, onto `dst`.
+pub fn composite(dst: Rgba<u8>, src: Rgba<u8>, coverage: f32) -> Rgba<u8> {
+    let sa = src[3] as f32 / 255.0 * coverage;
+    if sa >= 1.0 {
+        return src;
+    }
+    let da = dst[3] as f32 / 255.0;
+    let out_a = sa + da * (1.0 - sa);
+    if out_a <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let channel = |i: usize| {
+        let c = (src[i] as f32 * sa + dst[i] as f32 * da * (1.0 - sa)) / out_a;
+        c.round().clamp(0.0, 255.0) as u8
+    };
+    Rgba([
+        channel(0),
+        channel(1),
+        channel(2),
+        (out_a * 255.0).round() as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    #[test]
+    fn test_fill_aligned_rect_is_crisp() {
+        let mut img = ImageBuffer::from_pixel(10, 10, BLACK);
+        let area = fill(&mut img, &RoundedRect::new(2.0, 2.0, 4.0, 4.0, 0.0), WHITE);
+        assert_eq!(area, 16.0);
+        assert_eq!(*img.get_pixel(2, 2), WHITE);
+        assert_eq!(*img.get_pixel(5, 5), WHITE);
+        assert_eq!(*img.get_pixel(6, 6), BLACK);
+        assert_eq!(*img.get_pixel(1, 2), BLACK);
+        assert_eq!(*img.get_pixel(9, 9), BLACK);
+    }
+
+    #[test]
+    fn test_fill_rounded_rect_antialiases_corners() {
+        let mut img = ImageBuffer::from_pixel(20, 20, BLACK);
+        let area = fill(
+            &mut img,
+            &RoundedRect::new(0.0, 0.0, 20.0, 20.0, 10.0),
+            WHITE,
+        );
+        // A circle of radius 10.
+        assert!((area - std::f32::consts::PI * 100.0).abs() < 3.0);
+        assert_eq!(*img.get_pixel(0, 0), BLACK);
+        assert_eq!(*img.get_pixel(10, 10), WHITE);
+        let edge = img.get_pixel(0, 8)[0];
+        assert!(
+            edge > 0 && edge < 255,
+            "edge pixel should be partially covered"
+        );
+    }
+
+    #[test]
+    fn test_fill_band() {
+        let mut img = ImageBuffer::from_pixel(20, 20, BLACK);
+        let outer = RoundedRect::new(2.0, 2.0, 16.0, 16.0, 0.0);
+        let band = Band {
+            outer,
+            inner: outer.inset(3.0),
+        };
+        let area = fill(&mut img, &band, WHITE);
+        assert_eq!(area, 16.0 * 16.0 - 10.0 * 10.0);
+        assert_eq!(*img.get_pixel(3, 10), WHITE);
+        assert_eq!(*img.get_pixel(10, 10), BLACK);
+        assert_eq!(outer.area(), 256.0);
+        assert_eq!(outer.outset(2.0).w, 20.0);
+        assert!(outer.outset(1.0).encloses(&outer));
+        assert!(!outer.encloses(&outer.outset(1.0)));
+    }
+
+    #[test]
+    fn test_clip_to_circle() {
+        let mut img = ImageBuffer::from_pixel(20, 20, BLACK);
+        clip(&mut img, &RoundedRect::new(0.0, 0.0, 20.0, 20.0, 10.0));
+        assert_eq!(img.get_pixel(0, 0)[3], 0);
+        assert_eq!(*img.get_pixel(10, 10), BLACK);
+        let edge = img.get_pixel(0, 8)[3];
+        assert!(edge > 0 && edge < 255);
+    }
+
+    #[test]
+    fn test_row_spans_match_per_pixel_coverage() {
+        let shape = RoundedRect::new(3.5, 2.25, 40.0, 30.5, 11.0);
+        let color = Rgba([20, 40, 200, 180]);
+        let mut fast = ImageBuffer::from_pixel(48, 36, WHITE);
+        let mut slow = fast.clone();
+        let area = fill(&mut fast, &shape, color);
+        let mut expected = 0.0;
+        for (x, y, px) in slow.enumerate_pixels_mut() {
+            let coverage = pixel_coverage(&shape, x as f32, y as f32);
+            if coverage > 0.0 {
+                *px = composite(*px, color, coverage);
+                expected += coverage;
+            }
+        }
+        assert_eq!(fast, slow);
+        assert_eq!(area, expected);
+
+        let mut clipped = ImageBuffer::from_pixel(48, 36, WHITE);
+        clip(&mut clipped, &shape);
+        for (x, y, px) in clipped.enumerate_pixels() {
+            let coverage = pixel_coverage(&shape, x as f32, y as f32);
+            assert_eq!(px[3], (255.0 * coverage).round() as u8, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn test_overlay_matches_imageops() {
+        let mut top = ImageBuffer::from_pixel(6, 5, Rgba([200, 10, 10, 255]));
+        top.put_pixel(2, 1, Rgba([0, 0, 200, 90]));
+        top.put_pixel(4, 3, Rgba([0, 0, 0, 0]));
+        let bottom = ImageBuffer::from_pixel(8, 7, Rgba([10, 200, 10, 160]));
+        for (x, y) in [(0, 0), (1, 2), (4, 4), (9, 0)] {
+            let (mut fast, mut slow) = (bottom.clone(), bottom.clone());
+            overlay(&mut fast, &top, x, y);
+            imageops::overlay(&mut slow, &top, x.into(), y.into());
+            assert_eq!(fast, slow);
+            replace(&mut fast, &top, x, y);
+            imageops::replace(&mut slow, &top, x.into(), y.into());
+            assert_eq!(fast, slow);
+        }
+    }
+
+    #[test]
+    fn test_composite() {
+        assert_eq!(composite(WHITE, BLACK, 1.0), BLACK);
+        assert_eq!(composite(WHITE, BLACK, 0.5), Rgba([128, 128, 128, 255]));
+        assert_eq!(
+            composite(WHITE, Rgba([0, 0, 0, 128]), 1.0),
+            Rgba([127, 127, 127, 255])
+        );
+        // Onto a transparent canvas the color is kept and only alpha accumulates.
+        assert_eq!(
+            composite(Rgba([0, 0, 0, 0]), Rgba([200, 10, 10, 255]), 0.5),
+            Rgba([200, 10, 10, 128])
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! `--shape circle`: the code on a round canvas, for stickers and coasters cut to a
+//! circle, where a square output wastes the die-cut area.
+//!
+//! The quiet zone is a circle, `--quiet` modules clear of the symbol's corners, and
+//! everything outside the outer circle is transparent. A caption is set on a ring
+//! around it rather than in a band below: one line along the bottom, reading left
+//! to right with its letters upright, or with two lines (`--caption-lang` with two
+//! languages, say) the first over the top and the second along the bottom.
+
+use crate::error::{ErrorCode, WithCode};
+use crate::fonts::FontChain;
+use crate::raster::{self, Canvas, RoundedRect};
+use crate::{CaptionStyle, text};
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use image::{GrayImage, ImageBuffer, Luma, Rgba};
+use rusttype::{GlyphId, Scale, point};
+use std::f32::consts::{FRAC_PI_2, PI, SQRT_2};
+use std::str::FromStr;
+
+/// Arc one caption line may span.
+const MAX_ARC: f32 = 0.8 * PI;
+
+/// Smallest caption size before a line is refused as too long for the ring.
+const MIN_FONT_PX: f32 = 14.0;
+
+/// Outline of the whole output.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Shape {
+    /// A square, or a rectangle with a caption band
+    #[default]
+    Square,
+    /// A circle with a round quiet zone and the caption on a ring
+    Circle,
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <Self as ValueEnum>::from_str(s, true)
+    }
+}
+
+/// The ring between two circles around the same centre.
+struct Annulus {
+    centre: f32,
+    inner: f32,
+    outer: f32,
+}
+
+impl raster::Shape for Annulus {
+    fn bounds(&self) -> (f32, f32, f32, f32) {
+        let (lo, hi) = (self.centre - self.outer, self.centre + self.outer);
+        (lo, lo, hi, hi)
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let r = (x - self.centre).hypot(y - self.centre);
+        (self.inner..=self.outer).contains(&r)
+    }
+}
+
+/// `qr_img`, a code with `margin` pixels of quiet zone, on a round canvas with a
+/// round `quiet_color` quiet zone and `caption` on a ring around it. Returns the
+/// canvas and the offset of `qr_img` in it.
+pub fn compose(
+    qr_img: &Canvas,
+    margin: u32,
+    caption: Option<&str>,
+    font: &FontChain,
+    style: &CaptionStyle,
+    quiet_color: Rgba<u8>,
+) -> Result<(Canvas, (u32, u32))> {
+    let side = qr_img.width();
+    let symbol = (side - 2 * margin) as f32;
+    let inner = symbol / 2.0 * SQRT_2 + margin as f32;
+    let lines: Vec<&str> = caption.map(|c| c.lines().collect()).unwrap_or_default();
+    if lines.len() > 2 {
+        return Err(anyhow!(
+            "--shape circle sets the caption on a ring with room for two lines, not {}",
+            lines.len()
+        ))
+        .code(ErrorCode::Usage);
+    }
+    let ring = match lines.is_empty() {
+        true => None,
+        false => Some(ring_layout(&lines, inner, font, style)?),
+    };
+    let outer = inner + ring.as_ref().map_or(0.0, |ring| ring.width);
+
+    let d = (2.0 * outer).ceil() as u32;
+    let centre = d as f32 / 2.0;
+    let mut out: Canvas = ImageBuffer::new(d, d);
+    let disc = |r: f32| RoundedRect::new(centre - r, centre - r, 2.0 * r, 2.0 * r, r);
+    raster::fill(&mut out, &disc(inner), quiet_color);
+    let offset = (d - side) / 2;
+    raster::replace(&mut out, qr_img, offset, offset);
+    if let Some(ring) = ring {
+        let band = Annulus {
+            centre,
+            inner,
+            outer,
+        };
+        // The square quiet zone's corners reach into the ring; the band covers them.
+        raster::fill(&mut out, &band, style.band_color.0);
+        for (line, top) in ring.lines(&lines) {
+            draw_arc(&mut out, font, ring.scale, line, centre, &ring, top, style);
+        }
+    }
+    raster::clip(&mut out, &disc(outer));
+    Ok((out, (offset, offset)))
+}
+
+/// Size and radii of the caption ring.
+struct Ring {
+    scale: Scale,
+    width: f32,
+    /// Radius the glyphs nearest the code start at.
+    start: f32,
+    ascent: f32,
+    descent: f32,
+}
+
+impl Ring {
+    /// Each line with whether it goes over the top.
+    fn lines<'a>(&self, lines: &[&'a str]) -> Vec<(&'a str, bool)> {
+        match lines {
+            [bottom] => vec![(*bottom, false)],
+            [top, bottom] => vec![(*top, true), (*bottom, false)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Baseline radius: letters point in towards the code along the bottom, out over
+    /// the top, so the baseline sits on the far side of the text from the code.
+    fn baseline(&self, top: bool) -> f32 {
+        match top {
+            true => self.start - self.descent,
+            false => self.start + self.ascent,
+        }
+    }
+}
+
+/// The largest caption size, up to a sixth of `inner`, at which every line fits in
+/// [`MAX_ARC`] of a ring outside `inner`.
+fn ring_layout(lines: &[&str], inner: f32, font: &FontChain, style: &CaptionStyle) -> Result<Ring> {
+    let mut font_px = (inner / 6.0).max(MIN_FONT_PX);
+    loop {
+        let scale = Scale::uniform(font_px);
+        let v = font.primary().v_metrics(scale);
+        let text_h = (v.ascent - v.descent).ceil();
+        let pad = match style.padding {
+            Some(pad) => pad as f32,
+            None => (text_h * 0.4).round(),
+        };
+        let start = inner + pad;
+        let widest = lines
+            .iter()
+            .map(|line| text::layout(font, line, scale).width)
+            .fold(0.0, f32::max);
+        if widest <= MAX_ARC * start {
+            return Ok(Ring {
+                scale,
+                width: text_h + 2.0 * pad,
+                start,
+                ascent: v.ascent,
+                descent: v.descent,
+            });
+        }
+        if font_px <= MIN_FONT_PX {
+            return Err(anyhow!(
+                "the caption is too long for the ring of a round code this size; shorten it or raise --size"
+            ))
+            .code(ErrorCode::Usage);
+        }
+        font_px = (font_px * 0.92).max(MIN_FONT_PX);
+    }
+}
+
+/// Set `line` along the ring around `centre`, over the top or along the bottom,
+/// centred and reading left to right.
+#[allow(clippy::too_many_arguments)]
+fn draw_arc(
+    img: &mut Canvas,
+    font: &FontChain,
+    scale: Scale,
+    line: &str,
+    centre: f32,
+    ring: &Ring,
+    top: bool,
+    style: &CaptionStyle,
+) {
+    let layout = text::layout(font, line, scale);
+    let radius = ring.baseline(top);
+    for shaped in &layout.glyphs {
+        let face = font.face(shaped.face);
+        let glyph = face.font.glyph(GlyphId(shaped.id)).scaled(scale);
+        let advance = glyph.h_metrics().advance_width;
+        // The glyph around its own centre on the baseline.
+        let positioned = glyph.positioned(point(-advance / 2.0, -shaped.y));
+        let Some(bb) = positioned.pixel_bounding_box() else {
+            continue;
+        };
+        let mut coverage = GrayImage::new(bb.width() as u32, bb.height() as u32);
+        positioned.draw(|x, y, v| coverage.put_pixel(x, y, Luma([(v * 255.0) as u8])));
+
+        // Where the glyph's centre falls on the arc, the reading direction there and
+        // the direction its letters point.
+        let along = shaped.x + advance / 2.0 - layout.width / 2.0;
+        let angle = match top {
+            true => -FRAC_PI_2 + along / radius,
+            false => FRAC_PI_2 - along / radius,
+        };
+        let (sin, cos) = angle.sin_cos();
+        let anchor = (centre + radius * cos, centre + radius * sin);
+        let (reading, up) = match top {
+            true => ((-sin, cos), (cos, sin)),
+            false => ((sin, -cos), (-cos, -sin)),
+        };
+
+        let reach = [bb.min.x, bb.max.x, bb.min.y, bb.max.y]
+            .into_iter()
+            .map(|v| v.unsigned_abs())
+            .max()
+            .unwrap_or(0) as f32
+            * SQRT_2
+            + 1.0;
+        let x0 = (anchor.0 - reach).floor().max(0.0) as u32;
+        let y0 = (anchor.1 - reach).floor().max(0.0) as u32;
+        let x1 = ((anchor.0 + reach).ceil() as u32).min(img.width());
+        let y1 = ((anchor.1 + reach).ceil() as u32).min(img.height());
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (dx, dy) = (x as f32 + 0.5 - anchor.0, y as f32 + 0.5 - anchor.1);
+                let gx = dx * reading.0 + dy * reading.1 - bb.min.x as f32;
+                let gy = -(dx * up.0 + dy * up.1) - bb.min.y as f32;
+                let v = sample(&coverage, gx - 0.5, gy - 0.5);
+                if v > 0.0 {
+                    let dst = *img.get_pixel(x, y);
+                    img.put_pixel(x, y, raster::composite(dst, style.text_color.0, v));
+                }
+            }
+        }
+    }
+}
+
+/// Bilinear sample of `coverage` at (x, y), 0 outside it.
+fn sample(coverage: &GrayImage, x: f32, y: f32) -> f32 {
+    let (w, h) = (coverage.width() as i32, coverage.height() as i32);
+    let (fx, fy) = (x.floor(), y.floor());
+    let (tx, ty) = (x - fx, y - fy);
+    let at = |ix: i32, iy: i32| match (0..w).contains(&ix) && (0..h).contains(&iy) {
+        true => coverage.get_pixel(ix as u32, iy as u32).0[0] as f32 / 255.0,
+        false => 0.0,
+    };
+    let (ix, iy) = (fx as i32, fy as i32);
+    let top = at(ix, iy) * (1.0 - tx) + at(ix + 1, iy) * tx;
+    let bottom = at(ix, iy + 1) * (1.0 - tx) + at(ix + 1, iy + 1) * tx;
+    top * (1.0 - ty) + bottom * ty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::decode;
+    use crate::symbol::{self, EncodeOptions};
+
+    #[test]
+    fn test_round() {
+        assert_eq!("Circle".parse(), Ok(Shape::Circle));
+        assert!("oval".parse::<Shape>().is_err());
+
+        let code = symbol::encode("https://example.com/menu", &EncodeOptions::default()).unwrap();
+        let ppm = 8;
+        let quiet = 4;
+        let qr_img = crate::render_qr_rgba(&code, 37 * ppm, quiet, Color::BLACK).unwrap();
+        let font = FontChain::new(crate::load_embedded_font().unwrap());
+        let style = CaptionStyle {
+            text_color: Color::BLACK,
+            band_color: Color::WHITE,
+            padding: None,
+        };
+        let white = Rgba([255; 4]);
+
+        let (plain, offset) = compose(&qr_img, quiet * ppm, None, &font, &style, white).unwrap();
+        // The circle clears the symbol's corners by the quiet zone.
+        let symbol = (29 * ppm) as f32;
+        assert_eq!(
+            plain.width(),
+            (symbol * SQRT_2 + 2.0 * (quiet * ppm) as f32).ceil() as u32
+        );
+        assert_eq!(offset.0, (plain.width() - qr_img.width()) / 2);
+        assert_eq!(plain.get_pixel(0, 0).0[3], 0);
+        assert_eq!(
+            *plain.get_pixel(offset.0 + quiet * ppm, offset.1 + quiet * ppm),
+            Rgba([0, 0, 0, 255])
+        );
+        assert!(decode::decode(&plain).is_ok());
+
+        let two = "Scan for the menu\nLa carte";
+        let (ringed, _) = compose(&qr_img, quiet * ppm, Some(two), &font, &style, white).unwrap();
+        assert!(ringed.width() > plain.width());
+        assert_eq!(ringed.get_pixel(0, 0).0[3], 0);
+        // Some caption ink in the ring above the code and below it.
+        let side = ringed.width();
+        let band = |y0: u32, y1: u32| {
+            (y0..y1).any(|y| (0..side).any(|x| ringed.get_pixel(x, y).0[..3] == [0, 0, 0]))
+        };
+        let ring = (side - plain.width()) / 2;
+        assert!(band(0, ring));
+        assert!(band(side - ring, side));
+        assert!(decode::decode(&ringed).is_ok());
+
+        assert!(compose(&qr_img, quiet * ppm, Some("a\nb\nc"), &font, &style, white).is_err());
+        let long = "A caption far too long to fit on the ring around a small code ".repeat(4);
+        assert!(compose(&qr_img, quiet * ppm, Some(&long), &font, &style, white).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+//! Payload segmentation for the smallest QR version.
+//!
+//! Each character can be stored in one or more modes: digits in numeric mode
+//! (10 bits per 3), `0-9A-Z $%*+-./:` in alphanumeric mode (11 bits per 2),
+//! Shift JIS kanji in Kanji mode (13 bits) and anything as UTF-8 bytes. Switching
+//! modes costs a segment header, so the cheapest mix is found by dynamic
+//! programming over the characters, as in ISO/IEC 18004 annex J.
+
+use encoding_rs::SHIFT_JIS;
+use qrcode::QrResult;
+use qrcode::Version;
+use qrcode::bits::Bits;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Numeric,
+    Alphanumeric,
+    Byte,
+    Kanji,
+}
+
+const MODES: [Mode; 4] = [Mode::Numeric, Mode::Alphanumeric, Mode::Byte, Mode::Kanji];
+
+const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// GS1 field separator (ASCII group separator).
+pub const GS: char = '\x1d';
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Numeric => "numeric",
+            Mode::Alphanumeric => "alphanumeric",
+            Mode::Byte => "byte",
+            Mode::Kanji => "kanji",
+        }
+    }
+
+    /// With FNC1, alphanumeric `%` stands for the GS separator, so a literal `%`
+    /// has to go in byte mode.
+    fn can_encode(self, ch: char, fnc1: bool) -> bool {
+        match self {
+            Mode::Numeric => ch.is_ascii_digit(),
+            Mode::Alphanumeric if fnc1 => ch == GS || (ch != '%' && ALPHANUMERIC.contains(ch)),
+            Mode::Alphanumeric => ALPHANUMERIC.contains(ch),
+            Mode::Byte => true,
+            Mode::Kanji => shift_jis_kanji(ch).is_some(),
+        }
+    }
+
+    /// Bits of the character count field for `version`.
+    fn count_bits(self, version: Version) -> usize {
+        let group = match version {
+            Version::Normal(1..=9) => 0,
+            Version::Normal(10..=26) => 1,
+            _ => 2,
+        };
+        match self {
+            Mode::Numeric => [10, 12, 14][group],
+            Mode::Alphanumeric => [9, 11, 13][group],
+            Mode::Byte => [8, 16, 16][group],
+            Mode::Kanji => [8, 10, 12][group],
+        }
+    }
+
+    /// Cost of one character in sixths of a bit, so numeric (10/3) and
+    /// alphanumeric (11/2) stay integral.
+    fn char_cost(self, ch: char) -> usize {
+        match self {
+            Mode::Numeric => 20,
+            Mode::Alphanumeric => 33,
+            Mode::Byte => 48 * ch.len_utf8(),
+            Mode::Kanji => 78,
+        }
+    }
+}
+
+/// A run of the payload stored in one mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub mode: Mode,
+    pub text: String,
+}
+
+impl Segment {
+    /// Exact encoded size in bits, header included.
+    fn bits(&self, version: Version) -> usize {
+        let data = match self.mode {
+            Mode::Numeric => {
+                let n = self.text.len();
+                n / 3 * 10 + [0, 4, 7][n % 3]
+            }
+            Mode::Alphanumeric => {
+                let n = self.text.len();
+                n / 2 * 11 + n % 2 * 6
+            }
+            Mode::Byte => self.text.len() * 8,
+            Mode::Kanji => self.text.chars().count() * 13,
+        };
+        4 + self.mode.count_bits(version) + data
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.mode.name(), self.text.chars().count())
+    }
+}
+
+/// The cheapest segmentation of `text` for QR versions in the same size group as `version`.
+/// `fnc1` selects GS1 rules for the field separator (see [`push_segments`]).
+pub fn optimize(text: &str, version: Version, fnc1: bool) -> Vec<Segment> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return Vec::new();
+    }
+
+    // cost[m]: cheapest encoding of the prefix that ends in mode m.
+    // from[i][m]: mode of character i-1 on that cheapest path.
+    let header = |m: Mode| (4 + m.count_bits(version)) * 6;
+    let mut cost: Vec<Option<usize>> = MODES
+        .iter()
+        .map(|&m| {
+            m.can_encode(chars[0], fnc1)
+                .then(|| header(m) + m.char_cost(chars[0]))
+        })
+        .collect();
+    let mut from = vec![[0usize; 4]; chars.len()];
+
+    for (i, &ch) in chars.iter().enumerate().skip(1) {
+        let mut next = vec![None; MODES.len()];
+        for (m, &mode) in MODES.iter().enumerate() {
+            if !mode.can_encode(ch, fnc1) {
+                continue;
+            }
+            let best = cost
+                .iter()
+                .enumerate()
+                .filter_map(|(p, c)| c.map(|c| (p, c + if p == m { 0 } else { header(mode) })))
+                .min_by_key(|&(_, c)| c);
+            if let Some((p, c)) = best {
+                next[m] = Some(c + mode.char_cost(ch));
+                from[i][m] = p;
+            }
+        }
+        cost = next;
+    }
+
+    // Walk the cheapest path backwards, then merge runs of the same mode.
+    let mut mode = (0..MODES.len())
+        .filter(|&m| cost[m].is_some())
+        .min_by_key(|&m| cost[m])
+        .unwrap_or(2);
+    let mut modes = vec![Mode::Byte; chars.len()];
+    for i in (0..chars.len()).rev() {
+        modes[i] = MODES[mode];
+        mode = from[i][mode];
+    }
+
+    let mut segments: Vec<Segment> = Vec::new();
+    for (ch, mode) in chars.into_iter().zip(modes) {
+        match segments.last_mut() {
+            Some(last) if last.mode == mode => last.text.push(ch),
+            _ => segments.push(Segment {
+                mode,
+                text: ch.to_string(),
+            }),
+        }
+    }
+    segments
+}
+
+/// Encoded size of `segments` in bits, without the terminator.
+pub fn encoded_len(segments: &[Segment], version: Version, fnc1: bool) -> usize {
+    let indicator = if fnc1 { 4 } else { 0 };
+    indicator + segments.iter().map(|s| s.bits(version)).sum::<usize>()
+}
+
+/// Append `segments` to `bits`. With `fnc1` the FNC1 first-position indicator goes first
+/// and [`GS`] separators are written as `%` in alphanumeric segments (ISO/IEC 18004 7.4.8.2).
+pub fn push_segments(bits: &mut Bits, segments: &[Segment], fnc1: bool) -> QrResult<()> {
+    if fnc1 {
+        bits.push_fnc1_first_position()?;
+    }
+    for segment in segments {
+        let text = segment.text.as_bytes();
+        match segment.mode {
+            Mode::Numeric => bits.push_numeric_data(text)?,
+            Mode::Alphanumeric => {
+                let text = segment.text.replace(GS, "%");
+                bits.push_alphanumeric_data(text.as_bytes())?
+            }
+            Mode::Byte => bits.push_byte_data(text)?,
+            Mode::Kanji => {
+                let sjis: Vec<u8> = segment
+                    .text
+                    .chars()
+                    .filter_map(shift_jis_kanji)
+                    .flatten()
+                    .collect();
+                bits.push_kanji_data(&sjis)?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The Shift JIS bytes of `ch` if it lies in the ranges Kanji mode can store.
+fn shift_jis_kanji(ch: char) -> Option<[u8; 2]> {
+    if ch.is_ascii() {
+        return None;
+    }
+    let mut buf = [0u8; 4];
+    let (bytes, _, had_errors) = SHIFT_JIS.encode(ch.encode_utf8(&mut buf));
+    let &[hi, lo] = bytes.as_ref() else {
+        return None;
+    };
+    let code = u16::from_be_bytes([hi, lo]);
+    let in_range = (0x8140..=0x9FFC).contains(&code) || (0xE040..=0xEBBF).contains(&code);
+    (!had_errors && in_range).then_some([hi, lo])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: Version = Version::Normal(1);
+
+    fn optimize_v1(text: &str) -> Vec<Segment> {
+        optimize(text, V1, false)
+    }
+
+    fn describe(segments: &[Segment]) -> Vec<String> {
+        segments.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_single_mode_payloads() {
+        assert_eq!(describe(&optimize_v1("01234567")), ["numeric(8)"]);
+        assert_eq!(describe(&optimize_v1("HELLO WORLD")), ["alphanumeric(11)"]);
+        assert_eq!(describe(&optimize_v1("hello")), ["byte(5)"]);
+        assert_eq!(describe(&optimize_v1("日本語")), ["kanji(3)"]);
+        assert!(optimize_v1("").is_empty());
+    }
+
+    #[test]
+    fn test_mixed_segments() {
+        // A long digit run is worth its own segment; a short one is not.
+        assert_eq!(
+            describe(&optimize_v1("https://example.com/p/0123456789012345")),
+            ["byte(22)", "numeric(16)"]
+        );
+        assert_eq!(describe(&optimize_v1("abc123def")), ["byte(9)"]);
+        assert_eq!(describe(&optimize_v1("東京タワー展望台")), ["kanji(8)"]);
+        assert_eq!(
+            describe(&optimize_v1("https://example.jp/東京都庁舎")),
+            ["byte(19)", "kanji(5)"]
+        );
+    }
+
+    #[test]
+    fn test_encoded_len_matches_bits() {
+        for text in ["HTTPS://EXAMPLE.COM/0123456789", "mixed 東京 text 42", "x"] {
+            for fnc1 in [false, true] {
+                let segments = optimize(text, V1, fnc1);
+                let mut bits = Bits::new(Version::Normal(10));
+                push_segments(&mut bits, &segments, fnc1).unwrap();
+                assert_eq!(
+                    bits.len(),
+                    encoded_len(&segments, Version::Normal(10), fnc1)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fnc1_separator() {
+        // GTIN, then a variable-length batch number closed by GS, then an expiry date.
+        let data = "010950600013435210ABC123\x1d17201225";
+        assert_eq!(
+            describe(&optimize(data, V1, true)),
+            ["numeric(18)", "alphanumeric(7)", "numeric(8)"]
+        );
+        // Without FNC1 the separator is just a byte.
+        assert!(
+            optimize(data, V1, false)
+                .iter()
+                .any(|s| s.mode == Mode::Byte)
+        );
+        // A literal % cannot use alphanumeric mode under FNC1.
+        assert_eq!(describe(&optimize("50%", V1, false)), ["alphanumeric(3)"]);
+        assert_eq!(describe(&optimize("50%", V1, true)), ["byte(3)"]);
+    }
+
+    #[test]
+    fn test_shift_jis_kanji() {
+        // ISO/IEC 18004 example characters.
+        assert_eq!(shift_jis_kanji('点'), Some([0x93, 0x5F]));
+        assert_eq!(shift_jis_kanji('茗'), Some([0xE4, 0xAA]));
+        assert_eq!(shift_jis_kanji('a'), None);
+        assert_eq!(shift_jis_kanji('é'), None);
+    }
+}
@@ -0,0 +1,112 @@
+//! URL shortening before encoding, to reduce QR density for long campaign URLs.
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use url::Url;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Shortener {
+    /// tinyurl.com public API (no token needed)
+    Tinyurl,
+    /// Bitly v4 API (requires --shorten-token)
+    Bitly,
+    /// Any GET endpoint returning the short URL as plain text (see --shorten-endpoint)
+    Custom,
+}
+
+/// Shorten `long_url` with the chosen service and validate the returned URL.
+pub fn shorten(
+    service: Shortener,
+    long_url: &str,
+    endpoint: Option<&str>,
+    token: Option<&str>,
+) -> Result<String> {
+    let short = match service {
+        Shortener::Tinyurl => http_get_text(
+            &custom_endpoint_url("https://tinyurl.com/api-create.php?url={url}", long_url),
+            None,
+        )?,
+        Shortener::Bitly => {
+            let token = token.context("--shorten bitly requires --shorten-token")?;
+            bitly_shorten(long_url, token)?
+        }
+        Shortener::Custom => {
+            let endpoint =
+                endpoint.context("--shorten custom requires --shorten-endpoint (with {url})")?;
+            if !endpoint.contains("{url}") {
+                bail!("--shorten-endpoint must contain a {{url}} placeholder");
+            }
+            http_get_text(&custom_endpoint_url(endpoint, long_url), token)?
+        }
+    };
+
+    let short = short.trim();
+    let parsed = Url::parse(short)
+        .with_context(|| format!("Shortener returned an invalid URL: {}", short))?;
+    Ok(parsed.to_string())
+}
+
+/// Substitute the percent-encoded long URL into an endpoint template.
+fn custom_endpoint_url(template: &str, long_url: &str) -> String {
+    template.replace("{url}", &crate::payload::encode_component(long_url))
+}
+
+#[cfg(feature = "net")]
+fn http_get_text(url: &str, token: Option<&str>) -> Result<String> {
+    let mut req = ureq::get(url);
+    if let Some(token) = token {
+        req = req.set("Authorization", &format!("Bearer {}", token));
+    }
+    req.call()
+        .with_context(|| format!("URL shortener request failed: {}", url))?
+        .into_string()
+        .context("Failed to read URL shortener response")
+}
+
+#[cfg(feature = "net")]
+fn bitly_shorten(long_url: &str, token: &str) -> Result<String> {
+    let resp: serde_json::Value = ureq::post("https://api-ssl.bitly.com/v4/shorten")
+        .set("Authorization", &format!("Bearer {}", token))
+        .send_json(serde_json::json!({ "long_url": long_url }))
+        .context("Bitly request failed")?
+        .into_json()
+        .context("Failed to parse Bitly response")?;
+    resp["link"]
+        .as_str()
+        .map(str::to_string)
+        .context("Bitly response did not contain a link")
+}
+
+#[cfg(not(feature = "net"))]
+fn http_get_text(_url: &str, _token: Option<&str>) -> Result<String> {
+    bail!("URL shortening requires qrbrand to be built with the `net` feature")
+}
+
+#[cfg(not(feature = "net"))]
+fn bitly_shorten(_long_url: &str, _token: &str) -> Result<String> {
+    bail!("URL shortening requires qrbrand to be built with the `net` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_endpoint_url() {
+        assert_eq!(
+            custom_endpoint_url("https://s.example/api?u={url}", "https://example.com/a?b=c"),
+            "https://s.example/api?u=https%3A%2F%2Fexample.com%2Fa%3Fb%3Dc"
+        );
+    }
+
+    #[test]
+    fn test_custom_requires_placeholder() {
+        let err = shorten(
+            Shortener::Custom,
+            "https://example.com",
+            Some("https://s.example/api"),
+            None,
+        );
+        assert!(err.is_err());
+    }
+}
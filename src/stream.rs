@@ -0,0 +1,409 @@
+//! Strip-by-strip rendering for print sizes that do not fit in memory.
+//!
+//! A 32768 px code is 4 GiB as one RGBA buffer. Here it is drawn [`STRIP_ROWS`] rows
+//! at a time, each strip measured for contrast and handed straight to the PNG encoder,
+//! so memory stays at one strip plus the resized logo whatever the size. The pixels
+//! match the in-memory renderer's. Anything that needs the whole canvas (the caption
+//! band, templates, crops, borders and shadows) is not available here.
+
+use crate::color::Color;
+use crate::contrast::Contrast;
+use crate::error::{ErrorCode, WithCode};
+use crate::meta::Metadata;
+use crate::output::Format;
+use crate::raster::Canvas;
+use crate::symbol::{self, Symbol};
+use crate::{
+    Args, Assets, RenderOptions, alt_text_chunks, budget, draw_plate, fit_logo, i18n, log,
+    logo_coverage, logo_shrink_warning, module_px, output, prepare_logo, preview, print_dry_run,
+    resize_fit,
+};
+use anyhow::{Context, Result, anyhow};
+use image::{Rgba, imageops};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Sizes above this stream without --stream.
+pub const AUTO_ABOVE: u32 = 16384;
+/// Pixel rows drawn and encoded at a time.
+const STRIP_ROWS: u32 = 256;
+
+/// A code written by [`render`], with what single mode reports about it.
+struct Streamed {
+    code: Symbol,
+    /// Side of the square image in pixels.
+    size: u32,
+    covered_px: u32,
+    logo_budget: Option<budget::Coverage>,
+    logo_scale: Option<f32>,
+    fg: Color,
+    contrast: Contrast,
+}
+
+/// Whether to stream this render: with --stream, or above [`AUTO_ABOVE`] unless a flag
+/// needs the whole image, in which case it is rendered in memory after a warning.
+pub fn wanted(args: &Args) -> Result<bool> {
+    if !args.stream && args.size.px <= AUTO_ABOVE {
+        return Ok(false);
+    }
+    match (unsupported(args), args.stream) {
+        (None, _) => Ok(true),
+        (Some(flag), true) => {
+            Err(anyhow!("--stream cannot be combined with {}", flag)).code(ErrorCode::Usage)
+        }
+        (Some(flag), false) => {
+            log::warn(format!(
+                "{} needs the whole image in memory; rendering --size {} without streaming",
+                flag, args.size.px
+            ));
+            Ok(false)
+        }
+    }
+}
+
+/// The first flag given that needs the whole canvas.
+fn unsupported(args: &Args) -> Option<&'static str> {
+    [
+        (args.format.is_some_and(|f| f != Format::Png), "--format"),
+        (
+            output::other_raster(&args.out).is_some(),
+            "a JPEG or GIF --out",
+        ),
+        (args.show_url, "--show-url"),
+        (args.display_url.is_some(), "--display-url"),
+        (!args.alt_text.is_empty(), "--alt-text"),
+        (args.template.is_some(), "--template"),
+        (args.corner_radius > 0, "--corner-radius"),
+        (args.circle_crop, "--circle-crop"),
+        (args.shape == crate::round::Shape::Circle, "--shape circle"),
+        (args.border.is_some(), "--border"),
+        (args.shadow.is_some(), "--shadow"),
+        (args.module_gap > 0.0, "--module-gap"),
+        (args.module_style.is_hollow(), "--module-style"),
+        (args.fill_texture.is_some(), "--fill-texture"),
+        (args.invert, "--invert"),
+        (args.eye_color.is_some(), "--eye-color"),
+        (args.eye_inner_color.is_some(), "--eye-inner-color"),
+        (!args.eye_glyph.is_empty(), "--eye-glyph"),
+        (args.debug_overlay, "--debug-overlay"),
+        (args.rotate.is_some(), "--rotate"),
+        (args.flip.is_some(), "--flip"),
+        (args.plate_shadow.is_some(), "--plate-shadow"),
+        (args.logo_shadow.is_some(), "--logo-shadow"),
+        (args.stress_test, "--stress-test"),
+        (args.guides, "--guides"),
+        (args.preview, "--preview"),
+        (args.terminal, "--terminal"),
+        (args.clipboard, "--clipboard"),
+        (
+            matches!(args.command, Some(crate::Command::Label(_))),
+            "qrbrand label",
+        ),
+    ]
+    .into_iter()
+    .find_map(|(given, flag)| given.then_some(flag))
+}
+
+/// Single mode for a streamed code: the same checks, reports and files as in memory.
+/// The PNG is written beside `args.out` and moved into place once the contrast check
+/// has passed, so a refused code leaves nothing behind.
+pub fn run(
+    args: &Args,
+    payload: &str,
+    assets: &Assets,
+    opts: &RenderOptions,
+    icc: Option<&[u8]>,
+) -> Result<()> {
+    let alt = alt_text_chunks(args.embed_alt, None, payload);
+    let text: Vec<(&str, &str)> = alt.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+    if args.dry_run {
+        let streamed = render(
+            payload,
+            opts,
+            assets,
+            std::io::sink(),
+            &text,
+            icc,
+            &args.out,
+        )?;
+        report(args, opts, &streamed)?;
+        let meta = metadata(&streamed, payload, opts);
+        let logo = streamed.logo_budget.as_ref().zip(streamed.logo_scale);
+        print_dry_run(
+            &[args.out.as_str()],
+            meta,
+            &streamed.code,
+            logo,
+            &streamed.contrast,
+            args.log_format,
+        );
+        return Ok(());
+    }
+
+    let part = format!("{}.part", args.out);
+    let file =
+        File::create(&part).with_context(|| format!("Failed to create output PNG: {}", part))?;
+    let streamed = render(
+        payload,
+        opts,
+        assets,
+        BufWriter::new(file),
+        &text,
+        icc,
+        &part,
+    )
+    .and_then(|streamed| report(args, opts, &streamed).map(|()| streamed))
+    .inspect_err(|_| {
+        let _ = std::fs::remove_file(&part);
+    })?;
+    std::fs::rename(&part, &args.out)
+        .with_context(|| format!("Failed to write output PNG: {}", args.out))?;
+    log::info(i18n::tr("wrote", &[("path", args.out.as_str().into())]));
+
+    if args.open
+        && let Err(e) = preview::open(&args.out)
+    {
+        log::warn(format!("{:#}", e));
+    }
+    if let Some(meta_path) = args.meta_out.as_deref() {
+        metadata(&streamed, payload, opts).write(meta_path)?;
+        log::info(i18n::tr("wrote", &[("path", meta_path.into())]));
+    }
+    Ok(())
+}
+
+/// Refuse poor contrast, then log the warnings and (with -v) the details.
+fn report(args: &Args, opts: &RenderOptions, streamed: &Streamed) -> Result<()> {
+    let contrast = &streamed.contrast;
+    for warning in contrast.check(opts.strict_contrast)? {
+        log::warn(warning);
+    }
+    if let Some(warning) = logo_shrink_warning(opts.logo_scale, streamed.logo_scale) {
+        log::warn(warning);
+    }
+    if args.verbose {
+        log::info(streamed.code.describe());
+        if let (Some(budget), Some(scale)) = (&streamed.logo_budget, streamed.logo_scale) {
+            log::info(format!("Logo scale {:.2}; {}", scale, budget.describe()));
+        }
+        if opts.auto_color {
+            log::info(format!("Module color {} (from the logo)", streamed.fg));
+        }
+        log::info(format!(
+            "Contrast {:.0}% (grade {}); rendered in {} px strips",
+            contrast.symbol_contrast() * 100.0,
+            contrast.grade(),
+            STRIP_ROWS
+        ));
+    }
+    Ok(())
+}
+
+fn metadata(streamed: &Streamed, payload: &str, opts: &RenderOptions) -> Metadata {
+    let coverage = logo_coverage(
+        &streamed.code,
+        opts.quiet,
+        streamed.size,
+        streamed.covered_px,
+    );
+    Metadata::new(
+        &streamed.code,
+        payload,
+        streamed.size,
+        streamed.size,
+        coverage,
+    )
+    .with_dpi(opts.dpi)
+}
+
+/// Encode `payload` and write it to `out` as a PNG with the `text` chunks and `icc`
+/// profile, a strip at a time; `name` labels errors.
+fn render(
+    payload: &str,
+    opts: &RenderOptions,
+    assets: &Assets,
+    out: impl Write,
+    text: &[(&str, &str)],
+    icc: Option<&[u8]>,
+    name: &str,
+) -> Result<Streamed> {
+    let code = symbol::encode(payload, &opts.encode)?;
+    let (logo, fg) = prepare_logo(assets.logo.as_deref(), opts)?;
+    let ppm = module_px(&code, opts.size, opts.quiet)?;
+    let size = ppm * (code.width() as u32 + 2 * opts.quiet);
+
+    let mut placed = None;
+    let (mut logo_budget, mut logo_scale) = (None, None);
+    if let Some(logo) = logo.as_deref() {
+        let (placement, coverage, scale) = fit_logo(&code, size, ppm, logo, opts)?;
+        let resized = resize_fit(logo, placement.w, placement.h).to_rgba8();
+        placed = Some((placement, resized));
+        logo_budget = Some(coverage);
+        logo_scale = Some(scale);
+    }
+    let skip = placed.as_ref().map(|(placement, _)| placement.bounds());
+
+    let mut writer = output::png_writer(out, size, size, text, icc, opts.dpi, name)?;
+    let mut stream = writer
+        .stream_writer_with_size(size as usize * 4 * STRIP_ROWS as usize)
+        .with_context(|| format!("Failed to write output PNG: {}", name))?;
+    let mut rows = ModuleRows::new(&code, ppm, opts.quiet, fg, opts.quiet_color);
+    let mut contrast = Contrast::default();
+    let mut plate_area = 0.0;
+    for top in (0..size).step_by(STRIP_ROWS as usize) {
+        let mut strip = Canvas::new(size, STRIP_ROWS.min(size - top));
+        let row_bytes = size as usize * 4;
+        for (y, row) in (top..).zip(strip.chunks_exact_mut(row_bytes)) {
+            row.copy_from_slice(rows.get(y));
+        }
+        if let Some((placement, logo)) = &placed {
+            plate_area += draw_plate(&mut strip, placement, &opts.plate, top);
+            let y = placement.y as i64 - top as i64;
+            imageops::overlay(&mut strip, logo, placement.x.into(), y);
+        }
+        contrast.sample(&strip, top, &code, opts.quiet, skip, false);
+        stream
+            .write_all(strip.as_raw())
+            .with_context(|| format!("Failed to write output PNG: {}", name))?;
+    }
+    stream
+        .finish()
+        .with_context(|| format!("Failed to write output PNG: {}", name))?;
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finish output PNG: {}", name))?;
+
+    let covered_px = placed
+        .as_ref()
+        .map_or(0, |(placement, _)| placement.covered_px(plate_area));
+    Ok(Streamed {
+        code,
+        size,
+        covered_px,
+        logo_budget,
+        logo_scale,
+        fg,
+        contrast,
+    })
+}
+
+/// Pixel rows of the bare code (modules and quiet zone), one module row at a time.
+struct ModuleRows<'a> {
+    code: &'a Symbol,
+    ppm: u32,
+    quiet: u32,
+    fg: Color,
+    quiet_color: Color,
+    /// The module row (quiet zone included) last drawn into `row`.
+    current: Option<u32>,
+    row: Canvas,
+}
+
+impl<'a> ModuleRows<'a> {
+    fn new(code: &'a Symbol, ppm: u32, quiet: u32, fg: Color, quiet_color: Color) -> Self {
+        let size = ppm * (code.width() as u32 + 2 * quiet);
+        Self {
+            code,
+            ppm,
+            quiet,
+            fg,
+            quiet_color,
+            current: None,
+            row: Canvas::new(size, 1),
+        }
+    }
+
+    /// Pixel row `y` as RGBA bytes, as `render_qr_rgba` and the quiet zone paint it.
+    fn get(&mut self, y: u32) -> &[u8] {
+        let module_row = y / self.ppm;
+        if self.current != Some(module_row) {
+            self.draw(module_row);
+            self.current = Some(module_row);
+        }
+        self.row.as_raw()
+    }
+
+    fn draw(&mut self, module_row: u32) {
+        let n = self.code.width() as u32;
+        let size = self.row.width();
+        let margin = self.ppm * self.quiet;
+        let white = Rgba([255, 255, 255, 255]);
+        for px in self.row.pixels_mut() {
+            *px = white;
+        }
+        if let Some(my) = module_row.checked_sub(self.quiet).filter(|&my| my < n) {
+            for mx in 0..n {
+                if matches!(self.code[(mx as usize, my as usize)], qrcode::Color::Dark) {
+                    let left = (mx + self.quiet) * self.ppm;
+                    crate::raster::fill_row(&mut self.row, 0, left..left + self.ppm, self.fg.0);
+                }
+            }
+            for x in (0..margin).chain(size - margin..size) {
+                self.row.put_pixel(x, 0, self.quiet_color.0);
+            }
+        } else {
+            for px in self.row.pixels_mut() {
+                *px = self.quiet_color.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_strips_match_in_memory_render() {
+        let logo = std::env::temp_dir().join("qrbrand_stream_logo.png");
+        image::RgbaImage::from_fn(64, 40, |x, y| Rgba([x as u8 * 4, 90, y as u8 * 6, 200]))
+            .save(&logo)
+            .unwrap();
+        // The plate and ring straddle the strip boundaries at rows 256 and 512.
+        let args = Args::try_parse_from([
+            "qrbrand",
+            "--url",
+            "https://example.com/stream",
+            "--size",
+            "700",
+            "--fg",
+            "#1d4ed8",
+            "--quiet-color",
+            "#fef3c7",
+            "--image",
+            logo.to_str().unwrap(),
+            "--logo-scale",
+            "0.3",
+            "--logo-plate",
+            "--plate-radius",
+            "12",
+            "--plate-border-width",
+            "3",
+            "--logo-ring",
+            "4px #dc2626",
+        ])
+        .unwrap();
+        let opts = RenderOptions::from_args(&args);
+        let assets = Assets::load(args.image.as_deref(), None, None).unwrap();
+        let payload = args.url.as_deref().unwrap();
+
+        let rendered = crate::render_code(payload, None, &opts, &assets).unwrap();
+        let mut png = Vec::new();
+        let streamed = render(payload, &opts, &assets, &mut png, &[], None, "test").unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba8();
+        let _ = std::fs::remove_file(&logo);
+
+        assert!(decoded == rendered.image, "streamed pixels differ");
+        assert_eq!(streamed.contrast, rendered.contrast(opts.quiet));
+        assert_eq!(streamed.covered_px, rendered.covered_px);
+        assert_eq!(streamed.logo_scale, rendered.logo_scale);
+
+        let mut args = args;
+        args.show_url = true;
+        assert_eq!(unsupported(&args), Some("--show-url"));
+        args.stream = true;
+        assert!(wanted(&args).is_err());
+    }
+}
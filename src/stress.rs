@@ -0,0 +1,372 @@
+//! `--stress-test`: degrade the finished image the way printing, photos and
+//! messaging apps do, and find the level at which it stops decoding.
+//!
+//! Each degradation is applied to the final image (flattened onto white) at
+//! increasing severity until [`decode`](crate::decode::decode) no longer returns
+//! the payload. Blur is measured in modules so the result does not depend on
+//! `--size`; the downscale limit says how few pixels per module survive, i.e. how
+//! small the code can be reproduced. With a resolution (`--size 50mm@300dpi`, or
+//! `--print-size`) that limit is also given in millimetres and inches as printed.
+
+use crate::decode;
+use crate::decode::Homography;
+use crate::raster::Canvas;
+use anyhow::{Context, Result, bail};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Degradation {
+    /// Gaussian blur, sigma in modules (focus, ink spread).
+    Blur,
+    /// Resampled to this many pixels per module (print size, camera distance).
+    Downscale,
+    /// JPEG re-compression at this quality (messaging apps, screenshots).
+    Jpeg,
+    /// Rotation in degrees.
+    Rotation,
+    /// Top edge shortened by this fraction, as when photographed at an angle.
+    Perspective,
+}
+
+impl Degradation {
+    pub const ALL: [Self; 5] = [
+        Self::Blur,
+        Self::Downscale,
+        Self::Jpeg,
+        Self::Rotation,
+        Self::Perspective,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Blur => "blur",
+            Self::Downscale => "downscale",
+            Self::Jpeg => "jpeg",
+            Self::Rotation => "rotation",
+            Self::Perspective => "perspective",
+        }
+    }
+
+    /// Levels to try, mildest first.
+    fn levels(self) -> &'static [f32] {
+        match self {
+            Self::Blur => &[0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.8],
+            Self::Downscale => &[4.0, 3.0, 2.5, 2.0, 1.5, 1.2, 1.0],
+            Self::Jpeg => &[75.0, 50.0, 30.0, 20.0, 10.0, 5.0],
+            Self::Rotation => &[5.0, 15.0, 30.0, 45.0],
+            Self::Perspective => &[0.1, 0.2, 0.3, 0.4, 0.5],
+        }
+    }
+
+    fn label(self, level: f32) -> String {
+        match self {
+            Self::Blur => format!("sigma {} modules", level),
+            Self::Downscale => format!("{} px/module", level),
+            Self::Jpeg => format!("quality {}", level),
+            Self::Rotation => format!("{}°", level),
+            Self::Perspective => format!("skew {}", level),
+        }
+    }
+
+    /// Apply this degradation at `level` to an image with `module_px` pixels per module.
+    fn apply(self, img: &Canvas, level: f32, module_px: f32) -> Result<Canvas> {
+        let (w, h) = img.dimensions();
+        Ok(match self {
+            Self::Blur => imageops::blur(img, level * module_px),
+            Self::Downscale => {
+                let scale = level / module_px;
+                let (sw, sh) = (
+                    ((w as f32 * scale).round() as u32).max(1),
+                    ((h as f32 * scale).round() as u32).max(1),
+                );
+                imageops::resize(img, sw, sh, FilterType::Triangle)
+            }
+            Self::Jpeg => {
+                let rgb = DynamicImage::ImageRgba8(img.clone()).into_rgb8();
+                let mut bytes = Vec::new();
+                JpegEncoder::new_with_quality(&mut bytes, level as u8)
+                    .encode_image(&rgb)
+                    .context("Failed to encode JPEG")?;
+                image::load_from_memory(&bytes)
+                    .context("Failed to decode JPEG")?
+                    .to_rgba8()
+            }
+            Self::Rotation => {
+                let (sin, cos) = level.to_radians().sin_cos();
+                let (wf, hf) = (w as f32, h as f32);
+                let (ow, oh) = (wf * cos + hf * sin, wf * sin + hf * cos);
+                // Where each source corner lands, rotated about the centre of the output.
+                let turn = |x: f32, y: f32| {
+                    let (dx, dy) = (x - wf / 2.0, y - hf / 2.0);
+                    (
+                        (ow / 2.0 + dx * cos - dy * sin) as f64,
+                        (oh / 2.0 + dx * sin + dy * cos) as f64,
+                    )
+                };
+                let to = [turn(0.0, 0.0), turn(wf, 0.0), turn(0.0, hf), turn(wf, hf)];
+                warp(img, (ow.ceil() as u32, oh.ceil() as u32), to)?
+            }
+            Self::Perspective => {
+                let (wf, hf) = (w as f64, h as f64);
+                let inset = level as f64 * wf / 2.0;
+                let to = [(inset, 0.0), (wf - inset, 0.0), (0.0, hf), (wf, hf)];
+                warp(img, (w, h), to)?
+            }
+        })
+    }
+}
+
+/// Map the corners of `img` (top-left, top-right, bottom-left, bottom-right) to `to`
+/// on a white `size` canvas, sampling bilinearly.
+fn warp(img: &Canvas, size: (u32, u32), to: [(f64, f64); 4]) -> Result<Canvas> {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    let Some(back) = Homography::from_points(to, [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)]) else {
+        bail!("degenerate warp");
+    };
+    let white = Rgba([255u8; 4]);
+    let at = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+            white
+        } else {
+            *img.get_pixel(x as u32, y as u32)
+        }
+    };
+    Ok(ImageBuffer::from_fn(size.0, size.1, |x, y| {
+        let (sx, sy) = back.apply((x as f64 + 0.5, y as f64 + 0.5));
+        let (sx, sy) = (sx - 0.5, sy - 0.5);
+        let (x0, y0) = (sx.floor() as i64, sy.floor() as i64);
+        let (fx, fy) = (sx - x0 as f64, sy - y0 as f64);
+        let mut out = [0u8; 4];
+        for (c, value) in out.iter_mut().enumerate() {
+            let top = at(x0, y0)[c] as f64 * (1.0 - fx) + at(x0 + 1, y0)[c] as f64 * fx;
+            let bottom = at(x0, y0 + 1)[c] as f64 * (1.0 - fx) + at(x0 + 1, y0 + 1)[c] as f64 * fx;
+            *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        Rgba(out)
+    }))
+}
+
+/// How one degradation went.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub degradation: Degradation,
+    /// Most severe level that still decoded, and the codewords error correction repaired there.
+    pub passed: Option<(f32, usize)>,
+    /// First level that did not, and why.
+    pub failed: Option<(f32, String)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// Pixels per module of the rendered code.
+    pub module_px: f32,
+    /// Modules across the symbol, quiet zone excluded.
+    pub modules: usize,
+    /// Printed size of a pixel, when the resolution is known.
+    pub mm_per_px: Option<f64>,
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    /// Fewest pixels per module that still decoded, if any downscale did.
+    pub fn min_module_px(&self) -> Option<f32> {
+        self.outcomes
+            .iter()
+            .find(|o| o.degradation == Degradation::Downscale)
+            .and_then(|o| o.passed)
+            .map(|(level, _)| level)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Stress test ({} px/module, {} modules):",
+            self.module_px, self.modules
+        )?;
+        for outcome in &self.outcomes {
+            let d = outcome.degradation;
+            // Severity grows as pixels and quality shrink.
+            let reach = match d {
+                Degradation::Downscale | Degradation::Jpeg => "down to",
+                _ => "up to",
+            };
+            let mut result = match outcome.passed {
+                Some((level, 0)) => format!("ok {} {}", reach, d.label(level)),
+                Some((level, corrected)) => format!(
+                    "ok {} {} ({} codeword{} repaired)",
+                    reach,
+                    d.label(level),
+                    corrected,
+                    if corrected == 1 { "" } else { "s" }
+                ),
+                None => String::new(),
+            };
+            if let Some((level, why)) = &outcome.failed {
+                if !result.is_empty() {
+                    result.push_str("; ");
+                }
+                result.push_str(&format!("fails at {} ({})", d.label(*level), why));
+            }
+            writeln!(f, "  {:<12} {}", d.name(), result)?;
+        }
+        match self.min_module_px() {
+            Some(px) => {
+                write!(
+                    f,
+                    "Smallest decodable size: {} px/module, {} px across the symbol ({:.0}% of this render)",
+                    px,
+                    (px * self.modules as f32).ceil(),
+                    100.0 * px / self.module_px
+                )?;
+                match self.mm_per_px {
+                    Some(mm_per_px) => {
+                        let module_mm = px as f64 * mm_per_px;
+                        let across_mm = module_mm * self.modules as f64;
+                        write!(
+                            f,
+                            "; at {:.0} dpi, {:.2} mm/module, {:.1} mm ({:.2} in) across the symbol",
+                            25.4 / mm_per_px,
+                            module_mm,
+                            across_mm,
+                            across_mm / 25.4
+                        )
+                    }
+                    None => Ok(()),
+                }
+            }
+            None => write!(
+                f,
+                "Smallest decodable size: only the rendered size was decodable"
+            ),
+        }
+    }
+}
+
+/// Stress `img`, whose code encodes `payload` in `modules` modules of `module_px` pixels,
+/// each pixel `mm_per_px` across when printed.
+pub fn run(
+    img: &Canvas,
+    payload: &str,
+    module_px: f32,
+    modules: usize,
+    mm_per_px: Option<f64>,
+) -> Result<Report> {
+    // Judge the image as printed on white paper.
+    let mut flat: Canvas = ImageBuffer::from_pixel(img.width(), img.height(), Rgba([255; 4]));
+    imageops::overlay(&mut flat, img, 0, 0);
+    check(&flat, payload).context("the rendered image itself does not decode")?;
+
+    let outcomes = Degradation::ALL
+        .iter()
+        .map(|&d| {
+            let mut outcome = Outcome {
+                degradation: d,
+                passed: None,
+                failed: None,
+            };
+            let levels = d
+                .levels()
+                .iter()
+                .filter(|&&level| d != Degradation::Downscale || level < module_px);
+            for &level in levels {
+                match d
+                    .apply(&flat, level, module_px)
+                    .and_then(|img| check(&img, payload))
+                {
+                    Ok(corrected) => outcome.passed = Some((level, corrected)),
+                    Err(e) => {
+                        outcome.failed = Some((level, e.to_string()));
+                        break;
+                    }
+                }
+            }
+            outcome
+        })
+        .collect();
+
+    Ok(Report {
+        module_px,
+        modules,
+        mm_per_px,
+        outcomes,
+    })
+}
+
+/// Decode `img` and compare with `payload`; returns the codewords repaired.
+fn check(img: &Canvas, payload: &str) -> Result<usize> {
+    let decoded = decode::decode(img)?;
+    if decoded.text != payload {
+        bail!("decoded different text");
+    }
+    Ok(decoded.corrected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::render_qr_rgba;
+
+    #[test]
+    fn test_degradations_keep_decoding_when_mild() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let img = render_qr_rgba(&code, 330, 4, Color::BLACK).unwrap();
+        let ppm = (img.width() / (code.width() as u32 + 8)) as f32;
+        for d in Degradation::ALL {
+            let degraded = d.apply(&img, d.levels()[0], ppm).unwrap();
+            check(&degraded, "https://example.com").unwrap_or_else(|e| panic!("{:?}: {}", d, e));
+        }
+        let rotated = Degradation::Rotation.apply(&img, 45.0, ppm).unwrap();
+        assert!(rotated.width() > img.width());
+        assert!(
+            check(
+                &Degradation::Blur.apply(&img, 2.0, ppm).unwrap(),
+                "https://example.com"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let img = render_qr_rgba(&code, 330, 4, Color::BLACK).unwrap();
+        let ppm = (img.width() / (code.width() as u32 + 8)) as f32;
+        let report = run(&img, "https://example.com", ppm, code.width(), None).unwrap();
+        assert_eq!(report.outcomes.len(), 5);
+        let min = report.min_module_px().unwrap();
+        assert!(min < ppm && min <= 3.0, "{}", report);
+        let text = report.to_string();
+        assert!(
+            text.starts_with("Stress test (8 px/module, 29 modules):"),
+            "{}",
+            text
+        );
+        assert!(text.contains("Smallest decodable size"));
+        assert!(!text.contains("dpi"));
+
+        // At 300 dpi a pixel is 25.4 / 300 mm.
+        let printed = Report {
+            mm_per_px: Some(25.4 / 300.0),
+            ..report
+        };
+        let across = min as f64 * 29.0 * 25.4 / 300.0;
+        let text = printed.to_string();
+        assert!(
+            text.contains(&format!(
+                "at 300 dpi, {:.2} mm/module, {:.1} mm",
+                min as f64 * 25.4 / 300.0,
+                across
+            )),
+            "{}",
+            text
+        );
+
+        assert!(run(&img, "https://example.org", ppm, code.width(), None).is_err());
+    }
+}
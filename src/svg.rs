@@ -0,0 +1,120 @@
+//! SVG output of the bare symbol: the quiet zone as one rectangle and the dark modules
+//! as one path, in module units, so the code scales cleanly in a browser or design tool.
+//!
+//! Like EPS, it draws the modules and quiet zone only; callers refuse logos, captions
+//! and the other raster decoration rather than drop them silently. With `--embed-alt`
+//! the PNG Title and Description go in as `<title>` and `<desc>`.
+
+use crate::color::Color;
+use crate::meta;
+use crate::symbol::Symbol;
+use std::fmt::Write as _;
+
+/// The SVG document for `code` with `quiet` modules of quiet zone, `side` pixels
+/// square, dark modules in `fg` on `quiet_color`. `alt` holds the `--embed-alt`
+/// text chunks, written as `<title>` and `<desc>`.
+pub fn document(
+    code: &Symbol,
+    quiet: u32,
+    side: u32,
+    fg: Color,
+    quiet_color: Color,
+    alt: &[(&str, String)],
+) -> String {
+    let n = code.width() as u32;
+    let total = n + 2 * quiet;
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}" viewBox="0 0 {1} {1}" shape-rendering="crispEdges">"#,
+        side, total
+    );
+    for (keyword, value) in alt {
+        let element = match *keyword {
+            "Title" => "title",
+            "Description" => "desc",
+            _ => continue,
+        };
+        let _ = writeln!(svg, "<{0}>{1}</{0}>", element, escape(value));
+    }
+    let _ = writeln!(
+        svg,
+        "<!-- QR {} {}, {} modules, qrbrand {} -->",
+        meta::version_label(code.version()),
+        meta::ec_level_label(code.error_correction_level()),
+        n,
+        env!("CARGO_PKG_VERSION")
+    );
+    if quiet_color.0[3] > 0 {
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{0}" height="{0}"{1}/>"#,
+            total,
+            paint(quiet_color)
+        );
+    }
+    let mut path = String::new();
+    for (x, y, len) in code.dark_runs() {
+        let _ = write!(
+            path,
+            "M{} {}h{}v1h-{}z",
+            x as u32 + quiet,
+            y as u32 + quiet,
+            len,
+            len
+        );
+    }
+    let _ = writeln!(svg, r#"<path{} d="{}"/>"#, paint(fg), path);
+    let _ = writeln!(svg, "</svg>");
+    svg
+}
+
+/// `fill` (and `fill-opacity` if not opaque) attributes for `color`.
+fn paint(color: Color) -> String {
+    let [r, g, b, a] = color.0.0;
+    let mut attrs = format!(r##" fill="#{:02x}{:02x}{:02x}""##, r, g, b);
+    if a < 255 {
+        let _ = write!(attrs, r#" fill-opacity="{:.3}""#, a as f64 / 255.0);
+    }
+    attrs
+}
+
+/// `text` with the characters XML reserves escaped.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_document() {
+        let code =
+            crate::symbol::encode("https://example.com/?a=1&b=2", &Default::default()).unwrap();
+        let n = code.width() as u32;
+        let fg = Color(Rgba([0x1d, 0x4e, 0xd8, 255]));
+        let quiet = Color(Rgba([255, 255, 255, 128]));
+        let alt = crate::alt_text_chunks(true, None, "https://example.com/?a=1&b=2");
+        let svg = document(&code, 4, 400, fg, quiet, &alt);
+        assert!(svg.contains(&format!(r#"viewBox="0 0 {0} {0}""#, n + 8)));
+        assert!(svg.contains(r#"width="400""#));
+        assert!(svg.contains("<title>https://example.com/?a=1&amp;b=2</title>"));
+        assert!(svg.contains("<desc>QR code encoding: https://example.com/?a=1&amp;b=2</desc>"));
+        assert!(svg.contains(r##"fill="#ffffff" fill-opacity="0.502""##));
+        assert!(svg.contains(r##"<path fill="#1d4ed8" d="M4 4h7v1h-7z"##));
+        // One subpath per run of dark modules.
+        assert_eq!(svg.matches('z').count(), code.dark_runs().len());
+
+        let bare = document(&code, 0, 100, Color::BLACK, Color::TRANSPARENT, &[]);
+        assert!(!bare.contains("<rect"));
+        // Without --embed-alt there is no accessible name.
+        assert!(!bare.contains("<title>") && !bare.contains("<desc>"));
+        assert!(bare.contains(r#"d="M0 0h7v1h-7z"#));
+    }
+}
@@ -0,0 +1,334 @@
+//! QR symbol construction.
+//!
+//! `qrcode::QrCode` always picks the smallest version and the lowest-penalty mask.
+//! This builds the symbol from the crate's bit and canvas layers instead, so the
+//! payload can be segmented with Kanji mode (see [`crate::segment`]), the version
+//! and mask fixed from the command line, and the EC level raised when a stronger
+//! one fits in the same version.
+
+use crate::meta;
+use crate::segment::{self, Segment};
+use anyhow::{Context, Result, bail};
+use qrcode::bits::Bits;
+use qrcode::canvas::{Canvas, MaskPattern};
+use qrcode::{Color, EcLevel, Version};
+use std::ops::Index;
+
+/// The kind of symbol drawn for the payload (`--symbology`).
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Symbology {
+    /// QR code
+    #[default]
+    Qr,
+    /// PDF417 stacked barcode, as png or pdf
+    Pdf417,
+}
+
+/// Encoding parameters for [`encode`].
+#[derive(Clone, Copy, Debug)]
+pub struct EncodeOptions {
+    /// Requested (minimum) error correction level.
+    pub ec_level: EcLevel,
+    /// Use the highest EC level that fits in the version chosen for `ec_level`.
+    pub ec_boost: bool,
+    /// Fixed QR version (1..=40); the smallest that fits when `None`.
+    pub version: Option<i16>,
+    /// Fixed mask pattern (0..=7); the lowest-penalty mask when `None`.
+    pub mask: Option<u8>,
+    /// Mark the data as GS1 element strings (FNC1 in first position); `\x1d`
+    /// separates variable-length fields.
+    pub fnc1: bool,
+}
+
+impl Default for EncodeOptions {
+    /// High error correction (important for logo overlays), automatic version and mask.
+    fn default() -> Self {
+        Self {
+            ec_level: EcLevel::H,
+            ec_boost: true,
+            version: None,
+            mask: None,
+            fnc1: false,
+        }
+    }
+}
+
+/// A finished QR symbol: modules in row-major order plus its parameters.
+pub struct Symbol {
+    modules: Vec<Color>,
+    version: Version,
+    ec_level: EcLevel,
+    width: usize,
+    segments: Vec<Segment>,
+}
+
+impl Symbol {
+    /// Add error correction to `bits`, place the modules and apply the mask.
+    pub fn from_bits(
+        bits: Bits,
+        ec_level: EcLevel,
+        mask: Option<u8>,
+        segments: Vec<Segment>,
+    ) -> Result<Self> {
+        let version = bits.version();
+        let data = bits.into_bytes();
+        let (data, ec) = qrcode::ec::construct_codewords(&data, version, ec_level)
+            .context("Failed to build QR code")?;
+
+        let mut canvas = Canvas::new(version, ec_level);
+        canvas.draw_all_functional_patterns();
+        canvas.draw_data(&data, &ec);
+        let canvas = match mask {
+            Some(mask) => {
+                canvas.apply_mask(mask_pattern(mask)?);
+                canvas
+            }
+            None => canvas.apply_best_mask(),
+        };
+
+        Ok(Self {
+            modules: canvas.into_colors(),
+            version,
+            ec_level,
+            width: version.width() as usize,
+            segments,
+        })
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn error_correction_level(&self) -> EcLevel {
+        self.ec_level
+    }
+
+    /// Modules per side, excluding the quiet zone.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// One-line summary for `--verbose`, e.g. `QR version 3-H, 29 modules, segments: byte(22) numeric(16)`.
+    pub fn describe(&self) -> String {
+        let segments: Vec<String> = self.segments.iter().map(|s| s.to_string()).collect();
+        format!(
+            "QR version {}-{}, {} modules, segments: {}",
+            meta::version_label(self.version),
+            meta::ec_level_label(self.ec_level),
+            self.width,
+            segments.join(" ")
+        )
+    }
+
+    /// Horizontal runs of dark modules as (x, y, length), rows from the top; vector
+    /// outputs draw one rectangle per run.
+    pub fn dark_runs(&self) -> Vec<(usize, usize, usize)> {
+        let mut runs = Vec::new();
+        for (y, row) in self.modules.chunks(self.width).enumerate() {
+            let mut x = 0;
+            while x < self.width {
+                let start = x;
+                while x < self.width && row[x] == Color::Dark {
+                    x += 1;
+                }
+                if x > start {
+                    runs.push((start, y, x - start));
+                } else {
+                    x += 1;
+                }
+            }
+        }
+        runs
+    }
+}
+
+impl Index<(usize, usize)> for Symbol {
+    type Output = Color;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Color {
+        &self.modules[y * self.width + x]
+    }
+}
+
+/// Encode `text` in the smallest version (or the fixed one) that fits its cheapest segmentation.
+pub fn encode(text: &str, opts: &EncodeOptions) -> Result<Symbol> {
+    let versions = match opts.version {
+        Some(version) => version..=version,
+        None => 1..=40,
+    };
+    // Segmentation only depends on the version group, so reuse it until the group changes.
+    let mut planned: Option<(Version, Vec<Segment>)> = None;
+    let fit = versions.map(Version::Normal).find_map(|version| {
+        let segments = match planned.take() {
+            Some((prev, segments)) if same_group(prev, version) => segments,
+            _ => segment::optimize(text, version, opts.fnc1),
+        };
+        let len = segment::encoded_len(&segments, version, opts.fnc1);
+        let fits = len <= capacity(version, opts.ec_level);
+        planned = Some((version, segments));
+        fits.then_some((version, len))
+    });
+    let Some((version, len)) = fit else {
+        match opts.version {
+            Some(version) => bail!(
+                "Payload does not fit in QR version {} at EC level {:?}",
+                version,
+                opts.ec_level
+            ),
+            None => bail!(
+                "Payload is too long for a QR code at EC level {:?}",
+                opts.ec_level
+            ),
+        }
+    };
+    let segments = planned.map(|(_, segments)| segments).unwrap_or_default();
+
+    // Spare capacity in the chosen version buys extra correction for free.
+    let ec_level = match opts.ec_boost {
+        true => [EcLevel::H, EcLevel::Q, EcLevel::M]
+            .into_iter()
+            .find(|&level| level > opts.ec_level && len <= capacity(version, level))
+            .unwrap_or(opts.ec_level),
+        false => opts.ec_level,
+    };
+
+    let mut bits = Bits::new(version);
+    segment::push_segments(&mut bits, &segments, opts.fnc1)
+        .and_then(|_| bits.push_terminator(ec_level))
+        .context("Failed to build QR code")?;
+    Symbol::from_bits(bits, ec_level, opts.mask, segments)
+}
+
+/// Data capacity of `version` at `ec_level`, in bits.
+fn capacity(version: Version, ec_level: EcLevel) -> usize {
+    Bits::new(version).max_len(ec_level).unwrap_or(0)
+}
+
+fn same_group(a: Version, b: Version) -> bool {
+    let group = |v: Version| match v {
+        Version::Normal(1..=9) => 0,
+        Version::Normal(10..=26) => 1,
+        _ => 2,
+    };
+    group(a) == group(b)
+}
+
+/// Parse `--ec-level` (L, M, Q or H).
+pub fn parse_ec_level(s: &str) -> Result<EcLevel, String> {
+    match s.to_ascii_uppercase().as_str() {
+        "L" => Ok(EcLevel::L),
+        "M" => Ok(EcLevel::M),
+        "Q" => Ok(EcLevel::Q),
+        "H" => Ok(EcLevel::H),
+        _ => Err(format!("expected L, M, Q or H, got '{}'", s)),
+    }
+}
+
+/// The mask with QR pattern number `n` (ISO/IEC 18004 table 10).
+fn mask_pattern(n: u8) -> Result<MaskPattern> {
+    Ok(match n {
+        0 => MaskPattern::Checkerboard,
+        1 => MaskPattern::HorizontalLines,
+        2 => MaskPattern::VerticalLines,
+        3 => MaskPattern::DiagonalLines,
+        4 => MaskPattern::LargeCheckerboard,
+        5 => MaskPattern::Fields,
+        6 => MaskPattern::Diamonds,
+        7 => MaskPattern::Meadow,
+        _ => bail!("mask pattern must be 0..=7, got {}", n),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qrcode::QrCode;
+
+    #[test]
+    fn test_never_larger_than_qrcode_crate() {
+        let opts = EncodeOptions::default();
+        for data in [
+            "https://example.com",
+            "HTTPS://EXAMPLE.COM/ABC/0123456789012345678901234567890",
+            "WIFI:T:WPA;S:guest;P:correct horse battery staple;;",
+        ] {
+            let symbol = encode(data, &opts).unwrap();
+            let code = QrCode::with_error_correction_level(data, EcLevel::H).unwrap();
+            assert!(symbol.width() <= code.width(), "{}", data);
+        }
+
+        // UTF-8 takes 24 bits per kanji, Kanji mode 13.
+        let japanese = "東京都千代田区丸の内一丁目九番一号東京駅前広場";
+        let symbol = encode(japanese, &opts).unwrap();
+        let code = QrCode::with_error_correction_level(japanese, EcLevel::H).unwrap();
+        assert!(symbol.width() < code.width());
+        assert_eq!(symbol.segments.len(), 1);
+    }
+
+    #[test]
+    fn test_fixed_version_and_mask() {
+        let data = "https://example.com";
+        let opts = EncodeOptions {
+            version: Some(10),
+            ..Default::default()
+        };
+        let symbol = encode(data, &opts).unwrap();
+        assert_eq!(symbol.version(), Version::Normal(10));
+        assert_eq!(symbol.width(), 57);
+
+        let masked: Vec<Symbol> = (0..8)
+            .map(|mask| {
+                encode(
+                    data,
+                    &EncodeOptions {
+                        mask: Some(mask),
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+        let n = masked[0].width();
+        let differs =
+            |a: &Symbol, b: &Symbol| (0..n).any(|y| (0..n).any(|x| a[(x, y)] != b[(x, y)]));
+        // Each mask flips a different set of data modules.
+        assert!(masked[1..].iter().all(|m| differs(&masked[0], m)));
+
+        let too_small = EncodeOptions {
+            version: Some(1),
+            ..Default::default()
+        };
+        let err = encode(&"a".repeat(100), &too_small).err().unwrap();
+        assert!(err.to_string().contains("does not fit in QR version 1"));
+    }
+
+    #[test]
+    fn test_ec_boost() {
+        let low = EncodeOptions {
+            ec_level: EcLevel::L,
+            ..Default::default()
+        };
+        // 3 bytes fit in version 1 even at H.
+        let boosted = encode("abc", &low).unwrap();
+        assert_eq!(boosted.version(), Version::Normal(1));
+        assert_eq!(boosted.error_correction_level(), EcLevel::H);
+
+        let plain = encode(
+            "abc",
+            &EncodeOptions {
+                ec_boost: false,
+                ..low
+            },
+        )
+        .unwrap();
+        assert_eq!(plain.error_correction_level(), EcLevel::L);
+
+        // 17 bytes fill version 1 at L, so there is nothing to boost.
+        let full = encode(&"a".repeat(17), &low).unwrap();
+        assert_eq!(full.version(), Version::Normal(1));
+        assert_eq!(full.error_correction_level(), EcLevel::L);
+
+        assert_eq!(parse_ec_level("q"), Ok(EcLevel::Q));
+        assert!(parse_ec_level("X").is_err());
+    }
+}
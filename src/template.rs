@@ -0,0 +1,170 @@
+//! `{placeholder}` templates for output paths and captions.
+//!
+//! Supported variables: `{row.<column>}`, `{url}`, `{hash}`, `{hash8}`,
+//! `{index}` (1-based), `{date}` (UTC, YYYY-MM-DD) and, in `qrbrand series`, `{n}`
+//! (the serial number). Numeric values accept a zero-padded width, e.g. `{index:04}`.
+//! Use `{{` and `}}` for literal braces.
+
+use crate::batch::Record;
+use anyhow::{Result, bail};
+
+/// Values available to a template for one record.
+pub struct TemplateVars<'a> {
+    pub row: Option<&'a Record>,
+    pub payload: &'a str,
+    pub payload_hash: &'a str,
+    pub index: usize,
+    pub date: &'a str,
+    /// The serial number in `qrbrand series`.
+    pub serial: Option<u64>,
+}
+
+impl TemplateVars<'_> {
+    fn lookup(&self, name: &str) -> Result<String> {
+        Ok(match name {
+            "url" => self.payload.to_string(),
+            "hash" => self.payload_hash.to_string(),
+            "hash8" => self.payload_hash.chars().take(8).collect(),
+            "index" => self.index.to_string(),
+            "date" => self.date.to_string(),
+            "n" => match self.serial {
+                Some(n) => n.to_string(),
+                None => bail!("{{n}} is only set in qrbrand series"),
+            },
+            _ => match name.strip_prefix("row.") {
+                Some(column) => match self.row.and_then(|r| r.get(column)) {
+                    Some(value) => value.trim().to_string(),
+                    None => bail!("template column not found: {}", column),
+                },
+                None => bail!("unknown template variable: {{{}}}", name),
+            },
+        })
+    }
+}
+
+/// Today's date in UTC as YYYY-MM-DD.
+pub fn today() -> String {
+    chrono::Utc::now().format("%Y-%m-%d").to_string()
+}
+
+/// Expand a template. With `for_path`, substituted values are made safe to use
+/// as a single file-name component (no separators or `..`).
+pub fn render(template: &str, vars: &TemplateVars, for_path: bool) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => spec.push(ch),
+                        None => bail!("unclosed '{{' in template: {}", template),
+                    }
+                }
+                let (name, width) = match spec.split_once(':') {
+                    Some((name, fmt)) => (name, Some(parse_width(fmt, template)?)),
+                    None => (spec.as_str(), None),
+                };
+                let mut value = vars.lookup(name.trim())?;
+                if let Some(width) = width {
+                    if !value.chars().all(|c| c.is_ascii_digit()) {
+                        bail!("{{{}}} is not numeric and cannot be zero-padded", name);
+                    }
+                    value = format!("{:0>width$}", value, width = width);
+                }
+                if for_path {
+                    value = sanitize_path_component(&value);
+                }
+                out.push_str(&value);
+            }
+            '}' => bail!("unmatched '}}' in template: {}", template),
+            _ => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn parse_width(fmt: &str, template: &str) -> Result<usize> {
+    match fmt.strip_prefix('0').and_then(|w| w.parse().ok()) {
+        Some(width) => Ok(width),
+        None => bail!("unsupported format '{}' in template: {}", fmt, template),
+    }
+}
+
+/// Replace path separators and other characters that are unsafe in file names.
+fn sanitize_path_component(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if cleaned.trim_matches('.').is_empty() {
+        "_".to_string()
+    } else {
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(row: &Record) -> TemplateVars<'_> {
+        TemplateVars {
+            row: Some(row),
+            payload: "https://example.com/t/7",
+            payload_hash: "0f115db062b7c0dd",
+            index: 7,
+            date: "2025-06-01",
+            serial: None,
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let row = Record::from([("id".to_string(), "A-12".to_string())]);
+        assert_eq!(
+            render("badges/{row.id}-{hash8}-{index:03}.png", &vars(&row), true).unwrap(),
+            "badges/A-12-0f115db0-007.png"
+        );
+        assert_eq!(
+            render("{{literal}} {date} {url}", &vars(&row), false).unwrap(),
+            "{literal} 2025-06-01 https://example.com/t/7"
+        );
+    }
+
+    #[test]
+    fn test_render_template_sanitizes_paths() {
+        let row = Record::from([("id".to_string(), "../etc/passwd".to_string())]);
+        assert_eq!(
+            render("out/{row.id}.png", &vars(&row), true).unwrap(),
+            "out/.._etc_passwd.png"
+        );
+        let row = Record::from([("id".to_string(), "..".to_string())]);
+        assert_eq!(render("{row.id}", &vars(&row), true).unwrap(), "_");
+    }
+
+    #[test]
+    fn test_render_template_errors() {
+        let row = Record::new();
+        assert!(render("{row.missing}", &vars(&row), false).is_err());
+        assert!(render("{nope}", &vars(&row), false).is_err());
+        assert!(render("{index", &vars(&row), false).is_err());
+        assert!(render("{url:04}", &vars(&row), false).is_err());
+        assert!(render("{n}", &vars(&row), false).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+//! `--terminal`: print the symbol with Unicode half blocks, two module rows per line.
+//!
+//! Colors are set explicitly (black on bright white) rather than left to the
+//! terminal's own scheme, so the code scans on dark themes as well.
+
+use crate::symbol::Symbol;
+
+const DARK_FG: u8 = 30;
+const LIGHT_FG: u8 = 97;
+/// Background codes are the foreground ones plus 10.
+const BG: u8 = 10;
+
+/// The symbol with `quiet` modules of quiet zone, one line per two module rows.
+pub fn render(code: &Symbol, quiet: u32) -> String {
+    let n = code.width() as i64;
+    let quiet = quiet as i64;
+    let dark = |x: i64, y: i64| {
+        (0..n).contains(&x)
+            && (0..n).contains(&y)
+            && matches!(code[(x as usize, y as usize)], qrcode::Color::Dark)
+    };
+    let color = |is_dark: bool| if is_dark { DARK_FG } else { LIGHT_FG };
+
+    let mut out = String::new();
+    for y in (-quiet..n + quiet).step_by(2) {
+        for x in -quiet..n + quiet {
+            // Upper half block: the top module is the foreground, the bottom one the background.
+            let (top, bottom) = (dark(x, y), dark(x, y + 1));
+            out.push_str(&format!("\x1b[{};{}m▀", color(top), color(bottom) + BG));
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let text = render(&code, 2);
+        let lines: Vec<&str> = text.lines().collect();
+        let side = code.width() + 4;
+        assert_eq!(lines.len(), side.div_ceil(2));
+        assert!(lines.iter().all(|l| l.matches('▀').count() == side));
+        // The first line is quiet zone only; the finder's top edge starts on the second.
+        assert!(!lines[0].contains("\x1b[30;"));
+        assert!(lines[1].starts_with("\x1b[97;107m▀\x1b[97;107m▀\x1b[30;40m▀"));
+    }
+}
@@ -0,0 +1,164 @@
+//! Caption layout: bidi reordering, font-fallback runs and OpenType shaping.
+//!
+//! Text is split into bidi level runs (visual order), each run into segments
+//! drawn by the same font, and each segment is shaped with rustybuzz so Arabic
+//! joins, Indic reordering and kerning come from the font itself.
+
+use crate::fonts::FontChain;
+use rusttype::Scale;
+use rustybuzz::{Direction, UnicodeBuffer};
+use unicode_bidi::ParagraphBidiInfo;
+
+/// One shaped glyph, positioned relative to the start of the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapedGlyph {
+    /// Font index in the chain (see [`FontChain::face`]).
+    pub face: usize,
+    pub id: u16,
+    /// Pen position plus the glyph's horizontal offset, in pixels.
+    pub x: f32,
+    /// Vertical offset in pixels (positive is up).
+    pub y: f32,
+}
+
+pub struct Line {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub width: f32,
+}
+
+/// Shape a single line of text at `scale`, in visual (left-to-right) order.
+pub fn layout(chain: &FontChain, text: &str, scale: Scale) -> Line {
+    let fonts = chain.resolve(text);
+    let byte_fonts: Vec<(usize, usize)> = text
+        .char_indices()
+        .zip(&fonts)
+        .map(|((offset, _), &font)| (offset, font))
+        .collect();
+
+    let bidi = ParagraphBidiInfo::new(text, None);
+    let (levels, runs) = bidi.visual_runs(0..text.len());
+
+    let mut line = Line {
+        glyphs: Vec::new(),
+        width: 0.0,
+    };
+    for run in runs {
+        if run.is_empty() {
+            continue;
+        }
+        let rtl = levels[run.start].is_rtl();
+        let mut segments = font_segments(&byte_fonts, run, text.len());
+        if rtl {
+            segments.reverse();
+        }
+        for (range, face) in segments {
+            shape_segment(chain, &text[range], face, rtl, scale, &mut line);
+        }
+    }
+    line
+}
+
+/// Split a byte range into maximal sub-ranges that use one font.
+fn font_segments(
+    byte_fonts: &[(usize, usize)],
+    run: std::ops::Range<usize>,
+    text_len: usize,
+) -> Vec<(std::ops::Range<usize>, usize)> {
+    let mut segments: Vec<(std::ops::Range<usize>, usize)> = Vec::new();
+    for (i, &(offset, face)) in byte_fonts.iter().enumerate() {
+        if !run.contains(&offset) {
+            continue;
+        }
+        let end = byte_fonts.get(i + 1).map_or(text_len, |&(next, _)| next);
+        match segments.last_mut() {
+            Some((range, last)) if *last == face => range.end = end,
+            _ => segments.push((offset..end, face)),
+        }
+    }
+    segments
+}
+
+fn shape_segment(
+    chain: &FontChain,
+    text: &str,
+    face_index: usize,
+    rtl: bool,
+    scale: Scale,
+    line: &mut Line,
+) {
+    let face = chain.face(face_index);
+    let Some(hb_face) = rustybuzz::Face::from_slice(&face.data, face.index) else {
+        return;
+    };
+    let factor = face.font.scale_for_pixel_height(scale.y);
+
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    buffer.set_direction(if rtl {
+        Direction::RightToLeft
+    } else {
+        Direction::LeftToRight
+    });
+
+    let shaped = rustybuzz::shape(&hb_face, &[], buffer);
+    for (info, pos) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+        line.glyphs.push(ShapedGlyph {
+            face: face_index,
+            id: info.glyph_id as u16,
+            x: line.width + pos.x_offset as f32 * factor,
+            y: pos.y_offset as f32 * factor,
+        });
+        line.width += pos.x_advance as f32 * factor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain() -> FontChain {
+        FontChain::new(crate::load_embedded_font().unwrap())
+    }
+
+    #[test]
+    fn test_layout_latin_matches_advances() {
+        let chain = chain();
+        let scale = Scale::uniform(40.0);
+        let line = layout(&chain, "Menu", scale);
+        assert_eq!(line.glyphs.len(), 4);
+        let font = chain.primary();
+        let plain: f32 = "Menu"
+            .chars()
+            .map(|c| font.glyph(c).scaled(scale).h_metrics().advance_width)
+            .sum();
+        // Shaping may add kerning, but only a little.
+        assert!((line.width - plain).abs() < 4.0);
+        assert!(line.glyphs.windows(2).all(|w| w[0].x < w[1].x));
+    }
+
+    #[test]
+    fn test_layout_arabic_is_joined_and_reordered() {
+        let chain = chain();
+        let scale = Scale::uniform(40.0);
+        // "salam": the first logical letter must end up rightmost.
+        let text = "سلام";
+        let line = layout(&chain, text, scale);
+        let font = chain.primary();
+        let isolated = font.glyph('س').id().0;
+        let first = line.glyphs.last().unwrap();
+        // Joined (initial) form, not the isolated glyph from the cmap.
+        assert_ne!(first.id, isolated);
+        assert!(line.glyphs.iter().all(|g| g.x <= first.x));
+    }
+
+    #[test]
+    fn test_font_segments() {
+        let byte_fonts = [(0, 0), (1, 0), (2, 1), (5, 1), (8, 0)];
+        assert_eq!(
+            font_segments(&byte_fonts, 0..9, 9),
+            vec![(0..2, 0), (2..8, 1), (8..9, 0)]
+        );
+        assert_eq!(font_segments(&byte_fonts, 2..8, 9), vec![(2..8, 1)]);
+    }
+}
@@ -0,0 +1,327 @@
+//! Named style presets for `--theme`: a few built-in looks plus user themes from
+//! the config file.
+//!
+//! A theme is a set of style settings keyed like the batch style columns
+//! (`band_color`, `plate_radius`, ...). Settings given on the command line win
+//! over the theme.
+
+use crate::Args;
+use crate::symbol;
+use anyhow::{Context, Result, bail};
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Style settings by key.
+pub type Theme = BTreeMap<String, String>;
+
+const BUILTIN: &[(&str, &[(&str, &str)])] = &[
+    (
+        "classic",
+        &[
+            ("quiet_color", "#ffffff"),
+            ("plate_color", "#ffffff"),
+            ("plate_radius", "0"),
+            ("text_color", "#000000"),
+            ("band_color", "#ffffff"),
+        ],
+    ),
+    (
+        "night",
+        &[
+            ("quiet_color", "#e2e8f0"),
+            ("plate_color", "#e2e8f0"),
+            ("plate_radius", "24"),
+            ("text_color", "#e2e8f0"),
+            ("band_color", "#0f172a"),
+            ("corner_radius", "32"),
+            ("shadow", "blur=24,offset=8,opacity=0.5"),
+            ("canvas_color", "#0f172a"),
+        ],
+    ),
+    (
+        "candy",
+        &[
+            ("quiet_color", "#fff0f6"),
+            ("plate_color", "#fff0f6"),
+            ("plate_border_color", "#ff5fa2"),
+            ("plate_border_width", "6"),
+            ("plate_radius", "999"),
+            ("text_color", "#ffffff"),
+            ("band_color", "#ff5fa2"),
+            ("corner_radius", "32"),
+        ],
+    ),
+    (
+        "corporate",
+        &[
+            ("quiet_color", "#ffffff"),
+            ("plate_color", "#ffffff"),
+            ("plate_border_color", "#1f2937"),
+            ("plate_border_width", "3"),
+            ("plate_radius", "8"),
+            ("text_color", "#ffffff"),
+            ("band_color", "#1f2937"),
+            ("border", "2px #1f2937"),
+        ],
+    ),
+];
+
+/// `$QRBRAND_CONFIG`, else `qrbrand/config.json` under `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("QRBRAND_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("qrbrand").join("config.json"))
+}
+
+/// Names of the built-in themes, in the order `--help` lists them.
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTIN.iter().map(|(name, _)| *name)
+}
+
+/// Find `name` among the themes in `config` (which may redefine a built-in), then the built-ins.
+/// A missing config file just means there are no user themes.
+pub fn lookup(name: &str, config: Option<&Path>) -> Result<Theme> {
+    let mut user = match config {
+        Some(path) if path.exists() => user_themes(path)?,
+        _ => BTreeMap::new(),
+    };
+    if let Some(theme) = user.remove(name) {
+        return Ok(theme);
+    }
+    if let Some((_, settings)) = BUILTIN.iter().find(|(builtin, _)| *builtin == name) {
+        return Ok(settings
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.to_string()))
+            .collect());
+    }
+    let names: Vec<&str> = BUILTIN
+        .iter()
+        .map(|(builtin, _)| *builtin)
+        .chain(user.keys().map(String::as_str))
+        .collect();
+    bail!("unknown theme '{}' (available: {})", name, names.join(", "))
+}
+
+/// Names of the themes in `config`, for shell completion. An unreadable config just
+/// offers none, as completion has nowhere to report the error.
+pub fn user_names(config: Option<&Path>) -> Vec<String> {
+    match config {
+        Some(path) if path.exists() => user_themes(path)
+            .map(|themes| themes.into_keys().collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Read `{"themes": {"name": {"band_color": "#c8102e", "plate_radius": 16}}}`.
+fn user_themes(path: &Path) -> Result<BTreeMap<String, Theme>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config: {}", path.display()))?;
+    let value: Value = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse config: {}", path.display()))?;
+    let Some(themes) = value.get("themes") else {
+        return Ok(BTreeMap::new());
+    };
+    let Value::Object(themes) = themes else {
+        bail!("`themes` must be an object in {}", path.display());
+    };
+    themes
+        .iter()
+        .map(|(name, settings)| {
+            let Value::Object(settings) = settings else {
+                bail!("theme '{}' must be an object in {}", name, path.display());
+            };
+            let theme = settings
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| {
+                    let v = match v {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (k.clone(), v)
+                })
+                .collect();
+            Ok((name.clone(), theme))
+        })
+        .collect()
+}
+
+/// Apply `theme` to `args`, leaving settings given on the command line or environment alone.
+pub fn apply(args: &mut Args, matches: &ArgMatches, theme: &Theme) -> Result<()> {
+    for (key, value) in theme {
+        let id = key.replace('-', "_");
+        let given = matches.ids().any(|i| i.as_str() == id)
+            && matches!(
+                matches.value_source(&id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            );
+        if !given {
+            set(args, &id, value).with_context(|| format!("invalid theme setting `{}`", key))?;
+        }
+    }
+    Ok(())
+}
+
+fn set(args: &mut Args, key: &str, value: &str) -> Result<()> {
+    match key {
+        "size" => parse_into(&mut args.size, value),
+        "quiet" => parse_into(&mut args.quiet, value),
+        "fg" => parse_into(&mut args.fg, value),
+        "auto_color" => parse_into(&mut args.auto_color, value),
+        "eye_color" => parse_some(&mut args.eye_color, value),
+        "eye_inner_color" => parse_some(&mut args.eye_inner_color, value),
+        "eye_glyph_color" => parse_some(&mut args.eye_glyph_color, value),
+        "quiet_color" => parse_into(&mut args.quiet_color, value),
+        "border" => parse_some(&mut args.border, value),
+        "corner_radius" => parse_into(&mut args.corner_radius, value),
+        "circle_crop" => parse_into(&mut args.circle_crop, value),
+        "shape" => parse_into(&mut args.shape, value),
+        "shadow" => parse_some(&mut args.shadow, value),
+        "canvas_color" => parse_into(&mut args.canvas_color, value),
+        "ec_level" => {
+            args.ec_level = symbol::parse_ec_level(value).map_err(anyhow::Error::msg)?;
+            Ok(())
+        }
+        "logo_scale" => parse_into(&mut args.logo_scale, value),
+        "logo_plate" => parse_into(&mut args.logo_plate, value),
+        "logo_pad" => parse_into(&mut args.logo_pad, value),
+        "logo_prep" => parse_some(&mut args.logo_prep, value),
+        "logo_fit" => parse_into(&mut args.logo_fit, value),
+        "ecc_margin" => parse_into(&mut args.ecc_margin, value),
+        "module_gap" => parse_into(&mut args.module_gap, value),
+        "module_style" => parse_into(&mut args.module_style, value),
+        "invert" => parse_into(&mut args.invert, value),
+        "fill_texture" => parse_some(&mut args.fill_texture, value),
+        "plate_color" => parse_into(&mut args.plate_color, value),
+        "plate_border_color" => parse_into(&mut args.plate_border_color, value),
+        "plate_border_width" => parse_into(&mut args.plate_border_width, value),
+        "plate_radius" => parse_into(&mut args.plate_radius, value),
+        "plate_shadow" => parse_some(&mut args.plate_shadow, value),
+        "logo_ring" => parse_some(&mut args.logo_ring, value),
+        "logo_shadow" => parse_some(&mut args.logo_shadow, value),
+        "font_family" => parse_some(&mut args.font_family, value),
+        "text_color" => parse_into(&mut args.text_color, value),
+        "band_color" => parse_into(&mut args.band_color, value),
+        "band_padding" => parse_some(&mut args.band_padding, value),
+        _ => bail!("unknown setting"),
+    }
+}
+
+fn parse_into<T: std::str::FromStr>(field: &mut T, value: &str) -> Result<()>
+where
+    T::Err: std::fmt::Display,
+{
+    *field = value
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{:?}: {}", value, e))?;
+    Ok(())
+}
+
+fn parse_some<T: std::str::FromStr>(field: &mut Option<T>, value: &str) -> Result<()>
+where
+    T::Err: std::fmt::Display,
+{
+    let mut parsed = None;
+    if !value.trim().is_empty() {
+        parsed = Some(
+            value
+                .trim()
+                .parse()
+                .map_err(|e| anyhow::anyhow!("{:?}: {}", value, e))?,
+        );
+    }
+    *field = parsed;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn parse(argv: &[&str]) -> (Args, ArgMatches) {
+        let matches = Args::command().try_get_matches_from(argv).unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        (args, matches)
+    }
+
+    #[test]
+    fn test_builtin_themes_apply() {
+        for (name, _) in BUILTIN {
+            let (mut args, matches) = parse(&["qrbrand", "--url", "https://example.com"]);
+            apply(&mut args, &matches, &lookup(name, None).unwrap()).unwrap();
+        }
+        assert!(lookup("neon", None).is_err());
+    }
+
+    #[test]
+    fn test_command_line_wins() {
+        let theme = lookup("corporate", None).unwrap();
+        let (mut args, matches) = parse(&[
+            "qrbrand",
+            "--url",
+            "https://example.com",
+            "--band-color",
+            "#c8102e",
+        ]);
+        apply(&mut args, &matches, &theme).unwrap();
+        assert_eq!(args.band_color, "#c8102e".parse().unwrap());
+        assert_eq!(args.text_color, Color::WHITE);
+        assert_eq!(args.border.map(|b| b.width), Some(2));
+
+        // Global flags after a subcommand count as given too.
+        #[cfg(feature = "cli")]
+        {
+            let (mut args, matches) = parse(&[
+                "qrbrand",
+                "batch",
+                "--input",
+                "rows.csv",
+                "--text-color",
+                "#111111",
+            ]);
+            apply(&mut args, &matches, &theme).unwrap();
+            assert_eq!(args.text_color, "#111111".parse().unwrap());
+            assert_eq!(args.band_color, "#1f2937".parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_user_themes() {
+        let path = std::env::temp_dir().join("qrbrand_theme_test.json");
+        std::fs::write(
+            &path,
+            r##"{"themes": {"acme": {"band-color": "#c8102e", "plate_radius": 16, "circle_crop": true},
+                            "night": {"band_color": "#000000"}}}"##,
+        )
+        .unwrap();
+
+        let acme = lookup("acme", Some(&path)).unwrap();
+        let (mut args, matches) = parse(&["qrbrand", "--url", "https://example.com"]);
+        apply(&mut args, &matches, &acme).unwrap();
+        assert_eq!(args.band_color, "#c8102e".parse().unwrap());
+        assert_eq!(args.plate_radius, 16);
+        assert!(args.circle_crop);
+
+        // A user theme may redefine a built-in.
+        assert_eq!(lookup("night", Some(&path)).unwrap().len(), 1);
+        let err = lookup("missing", Some(&path)).unwrap_err().to_string();
+        assert!(err.contains("acme"), "{}", err);
+        assert_eq!(user_names(Some(&path)), ["acme", "night"]);
+
+        let bad = Theme::from([("module_shape".to_string(), "dots".to_string())]);
+        assert!(apply(&mut args, &matches, &bad).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
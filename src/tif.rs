@@ -0,0 +1,229 @@
+//! TIFF output, in RGB or in CMYK with exact ink values for offset printing.
+//!
+//! Left to the print shop, an RGB black is often separated into a rich black of all
+//! four inks, which registers badly and fills in fine modules. In CMYK the dark and
+//! light modules here get exactly the inks asked for (solid K by default). The rest of
+//! the image (logo, caption) is separated with full black generation, so its greys
+//! stay on the K plate too.
+
+use crate::color::Color;
+use crate::icc::ColorSpace;
+use crate::raster::Canvas;
+use anyhow::{Context, Result};
+use image::Rgba;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::str::FromStr;
+use tiff::TiffResult;
+use tiff::encoder::colortype::{CMYK8, ColorType, RGBA8};
+use tiff::encoder::{Compression, DeflateLevel, Rational, TiffEncoder, TiffValue};
+use tiff::tags::{PhotometricInterpretation, ResolutionUnit, Tag, Type};
+
+/// CMYK ink coverage in percent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ink(pub [u8; 4]);
+
+impl Ink {
+    pub const SOLID_BLACK: Ink = Ink([0, 0, 0, 100]);
+    pub const PAPER: Ink = Ink([0, 0, 0, 0]);
+
+    /// Ink coverage as 8-bit CMYK samples.
+    fn samples(self) -> [u8; 4] {
+        self.0.map(|pct| ((pct as u16 * 255 + 50) / 100) as u8)
+    }
+}
+
+impl FromStr for Ink {
+    type Err = String;
+
+    /// Four comma-separated percentages, C,M,Y,K: `0,0,0,100`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "expected C,M,Y,K ink percentages such as 0,0,0,100, got '{}'",
+                s
+            )
+        };
+        let inks: Vec<u8> = s
+            .split(',')
+            .map(|v| v.trim().trim_end_matches('%').parse::<u8>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        match <[u8; 4]>::try_from(inks) {
+            Ok(inks) if inks.iter().all(|&v| v <= 100) => Ok(Ink(inks)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Ink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [c, m, y, k] = self.0;
+        write!(f, "{},{},{},{}", c, m, y, k)
+    }
+}
+
+/// Which pixels of the image take which ink in CMYK.
+pub struct Inks {
+    pub dark: Ink,
+    pub light: Ink,
+    /// The color the dark modules were drawn in.
+    pub fg: Color,
+    /// The quiet zone color; it and white take the light ink.
+    pub quiet: Color,
+}
+
+/// An ICC profile, written with the UNDEFINED field type its tag calls for.
+struct Profile<'a>(&'a [u8]);
+
+impl TiffValue for Profile<'_> {
+    const BYTE_LEN: u8 = 1;
+    const FIELD_TYPE: Type = Type::UNDEFINED;
+
+    fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    fn data(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0)
+    }
+}
+
+/// Write `img` to `path` as a Deflate-compressed TIFF at `dpi`, embedding `icc` if
+/// given. In CMYK the pixels are separated by [`Inks`].
+pub fn save(
+    img: &Canvas,
+    path: &str,
+    space: ColorSpace,
+    inks: &Inks,
+    dpi: f64,
+    icc: Option<&[u8]>,
+) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create TIFF: {}", path))?;
+    let mut out = BufWriter::new(file);
+    match space {
+        ColorSpace::Rgb => write::<RGBA8>(&mut out, img, img.as_raw(), dpi, icc),
+        ColorSpace::Cmyk => write::<CMYK8>(&mut out, img, &separate(img, inks), dpi, icc),
+    }
+    .with_context(|| format!("Failed to write TIFF: {}", path))?;
+    out.flush()
+        .with_context(|| format!("Failed to write TIFF: {}", path))
+}
+
+fn write<C: ColorType<Inner = u8>>(
+    out: &mut (impl Write + Seek),
+    img: &Canvas,
+    data: &[u8],
+    dpi: f64,
+    icc: Option<&[u8]>,
+) -> TiffResult<()> {
+    let mut tiff =
+        TiffEncoder::new(out)?.with_compression(Compression::Deflate(DeflateLevel::Balanced));
+    let mut image = tiff.new_image::<C>(img.width(), img.height())?;
+    let per_inch = Rational {
+        n: (dpi * 100.0).round() as u32,
+        d: 100,
+    };
+    image.resolution(ResolutionUnit::Inch, per_inch);
+    if C::TIFF_VALUE == PhotometricInterpretation::RGB && C::BITS_PER_SAMPLE.len() == 4 {
+        // The fourth sample is unassociated alpha.
+        image.encoder().write_tag(Tag::ExtraSamples, 2u16)?;
+    }
+    if let Some(icc) = icc {
+        image.encoder().write_tag(Tag::IccProfile, Profile(icc))?;
+    }
+    image.write_data(data)
+}
+
+/// CMYK samples for `img`: module colors take their inks, everything else is separated.
+fn separate(img: &Canvas, inks: &Inks) -> Vec<u8> {
+    let (dark, light) = (inks.dark.samples(), inks.light.samples());
+    let mut cmyk = Vec::with_capacity(img.as_raw().len());
+    for &px in img.pixels() {
+        let ink = if px == inks.fg.0 {
+            dark
+        } else if px == inks.quiet.0 || px == Color::WHITE.0 {
+            light
+        } else {
+            to_cmyk(px)
+        };
+        cmyk.extend_from_slice(&ink);
+    }
+    cmyk
+}
+
+/// Naive separation of `p` over white paper, with all of the grey in K.
+fn to_cmyk(p: Rgba<u8>) -> [u8; 4] {
+    let a = p[3] as f32 / 255.0;
+    let over_white = |c: u8| (c as f32 / 255.0) * a + (1.0 - a);
+    let (r, g, b) = (over_white(p[0]), over_white(p[1]), over_white(p[2]));
+    let k = 1.0 - r.max(g).max(b);
+    if k >= 1.0 {
+        return [0, 0, 0, 255];
+    }
+    let ink = |c: f32| ((1.0 - c - k) / (1.0 - k) * 255.0).round() as u8;
+    [ink(r), ink(g), ink(b), (k * 255.0).round() as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::decoder::{Decoder, DecodingResult};
+
+    #[test]
+    fn test_cmyk_tiff() {
+        assert_eq!("0,0,0,100".parse::<Ink>(), Ok(Ink::SOLID_BLACK));
+        assert_eq!(
+            "60, 40%, 40, 100".parse::<Ink>(),
+            Ok(Ink([60, 40, 40, 100]))
+        );
+        assert!("0,0,0".parse::<Ink>().is_err());
+        assert!("0,0,0,101".parse::<Ink>().is_err());
+
+        // Blue modules on a cream quiet zone, plus a grey and a red logo pixel.
+        let fg: Color = "#1d4ed8".parse().unwrap();
+        let quiet: Color = "#fef3c7".parse().unwrap();
+        let mut img = Canvas::from_pixel(4, 1, Color::WHITE.0);
+        img.put_pixel(0, 0, fg.0);
+        img.put_pixel(1, 0, quiet.0);
+        img.put_pixel(2, 0, Rgba([128, 128, 128, 255]));
+        img.put_pixel(3, 0, Rgba([255, 0, 0, 255]));
+        let inks = Inks {
+            dark: Ink([100, 70, 0, 0]),
+            light: Ink::PAPER,
+            fg,
+            quiet,
+        };
+        let profile = b"stand-in profile bytes";
+        let mut file = std::io::Cursor::new(Vec::new());
+        let data = separate(&img, &inks);
+        write::<CMYK8>(&mut file, &img, &data, 300.0, Some(profile)).unwrap();
+
+        file.set_position(0);
+        let mut decoder = Decoder::new(file).unwrap();
+        assert_eq!(
+            decoder
+                .find_tag(Tag::IccProfile)
+                .unwrap()
+                .unwrap()
+                .into_u8_vec()
+                .unwrap(),
+            profile
+        );
+        let DecodingResult::U8(pixels) = decoder.read_image().unwrap() else {
+            panic!("expected 8-bit samples");
+        };
+        assert_eq!(
+            pixels,
+            [
+                [255, 179, 0, 0],
+                [0, 0, 0, 0],
+                [0, 0, 0, 127],
+                [0, 255, 255, 0]
+            ]
+            .concat()
+        );
+    }
+}
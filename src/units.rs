@@ -0,0 +1,97 @@
+//! `--size` with units: plain pixels (`1024`, `2048px`) or a physical side at a
+//! resolution (`50mm@300dpi`, `2in@600dpi`), resolved to pixels before rendering.
+//!
+//! The resolution is kept: PNGs record it in their `pHYs` chunk, `--meta-out` as
+//! `dpi`, and EPS and TIFF output without `--print-size` come out at that side.
+
+use crate::eps::Length;
+use crate::i18n;
+use std::fmt;
+use std::str::FromStr;
+
+/// Side of the code in pixels, quiet zone included, and the resolution it was given at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Size {
+    pub px: u32,
+    pub dpi: Option<f32>,
+}
+
+impl Size {
+    pub fn px(px: u32) -> Self {
+        Self { px, dpi: None }
+    }
+
+    /// The physical side the pixels `side` cover at this resolution.
+    pub fn printed(&self, side: u32) -> Option<Length> {
+        self.dpi.map(|dpi| Length(side as f64 / dpi as f64 * 72.0))
+    }
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let Some((side, dpi)) = s.split_once('@') else {
+            return pixels(s)
+                .map(Self::px)
+                .ok_or_else(|| match s.parse::<Length>() {
+                    Ok(_) => i18n::tr("size-needs-dpi", &[("size", s.into())]),
+                    Err(_) => i18n::tr("size-expected", &[("size", s.into())]),
+                });
+        };
+        let dpi = dpi.trim();
+        let dpi = dpi
+            .strip_suffix("dpi")
+            .or_else(|| dpi.strip_suffix("DPI"))
+            .and_then(|n| n.trim().parse::<f32>().ok())
+            .filter(|dpi| *dpi > 0.0 && dpi.is_finite())
+            .ok_or_else(|| i18n::tr("size-expected-dpi", &[("dpi", dpi.into())]))?;
+        let px = match pixels(side) {
+            Some(px) => px,
+            None => {
+                let length = side.parse::<Length>()?;
+                (length.0 / 72.0 * dpi as f64).round() as u32
+            }
+        };
+        if px == 0 {
+            return Err(i18n::tr("size-under-pixel", &[("size", s.into())]));
+        }
+        Ok(Self { px, dpi: Some(dpi) })
+    }
+}
+
+/// `n` or `npx`.
+fn pixels(s: &str) -> Option<u32> {
+    let s = s.trim();
+    s.strip_suffix("px").unwrap_or(s).trim().parse().ok()
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dpi {
+            None => write!(f, "{}", self.px),
+            Some(dpi) => write!(f, "{}px@{}dpi", self.px, dpi),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size() {
+        assert_eq!("1024".parse(), Ok(Size::px(1024)));
+        assert_eq!("2048px".parse(), Ok(Size::px(2048)));
+        let size: Size = "50mm@300dpi".parse().unwrap();
+        assert_eq!((size.px, size.dpi), (591, Some(300.0)));
+        assert_eq!("2in@600dpi".parse::<Size>().unwrap().px, 1200);
+        assert_eq!(size.to_string().parse(), Ok(size));
+        assert!((size.printed(591).unwrap().mm() - 50.0).abs() < 0.05);
+
+        assert!("50mm".parse::<Size>().unwrap_err().contains("50mm@300dpi"));
+        assert!("50mm@0dpi".parse::<Size>().is_err());
+        assert!("big".parse::<Size>().is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Pre-flight reachability check for the encoded URL.
+
+use crate::log;
+use anyhow::Result;
+use clap::ValueEnum;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum CheckMode {
+    /// Abort without writing output when the URL is unreachable
+    Fail,
+    /// Print a warning and continue
+    Warn,
+}
+
+/// Check that `url` resolves (following redirects) to a non-error response over valid TLS.
+/// Returns `Ok(())` in warn mode even if the check fails.
+pub fn check_url(url: &str, mode: CheckMode) -> Result<()> {
+    match probe(url) {
+        Ok(status) => {
+            log::info(format!("URL check: {} -> HTTP {}", url, status));
+            Ok(())
+        }
+        Err(e) if mode == CheckMode::Warn => {
+            log::warn(format!("URL check failed: {:#}", e));
+            Ok(())
+        }
+        Err(e) => Err(e.context(format!(
+            "URL check failed for {} (use --check-url warn to continue anyway)",
+            url
+        ))),
+    }
+}
+
+/// HEAD the URL, falling back to GET for servers that reject HEAD.
+#[cfg(feature = "net")]
+fn probe(url: &str) -> Result<u16> {
+    use crate::error::{ErrorCode, WithCode};
+    use anyhow::anyhow;
+
+    let agent = ureq::AgentBuilder::new()
+        .redirects(10)
+        .timeout(std::time::Duration::from_secs(15))
+        .build();
+
+    let result = match agent.head(url).call() {
+        Err(ureq::Error::Status(405 | 501, _)) => agent.get(url).call(),
+        other => other,
+    };
+
+    match result {
+        Ok(resp) => Ok(resp.status()),
+        Err(ureq::Error::Status(code, resp)) => {
+            let err = anyhow!(
+                "{} returned HTTP {} {}",
+                resp.get_url(),
+                code,
+                resp.status_text()
+            );
+            // A client error means the URL itself is wrong; server errors may pass.
+            let kind = if code < 500 {
+                ErrorCode::InvalidPayload
+            } else {
+                ErrorCode::Network
+            };
+            Err(err).code(kind)
+        }
+        Err(ureq::Error::Transport(t)) => Err(anyhow!("{}", t)).code(ErrorCode::Network),
+    }
+}
+
+#[cfg(not(feature = "net"))]
+fn probe(_url: &str) -> Result<u16> {
+    anyhow::bail!("--check-url requires qrbrand to be built with the `net` feature")
+}
+
+/// Only web URLs can be checked; other payloads are skipped.
+pub fn is_checkable(payload: &str) -> bool {
+    let lower = payload.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_checkable() {
+        assert!(is_checkable("https://example.com"));
+        assert!(is_checkable("HTTP://example.com"));
+        assert!(!is_checkable("mailto:a@b.c"));
+        assert!(!is_checkable("otpauth://totp/x"));
+    }
+}
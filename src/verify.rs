@@ -0,0 +1,413 @@
+//! Module-level verification of a rendered QR image.
+//!
+//! Samples the centre of every module in the output and compares it with the
+//! symbol matrix. Mismatches (mostly modules hidden by a logo or plate) are
+//! judged against the error-correction budget; damaged finder patterns always fail.
+//!
+//! `qrbrand verify` is the acceptance check for proofs sent back from print: every
+//! image in a directory is decoded and checked against the batch manifest it was
+//! made from (payload, pixel dimensions and a clean quiet zone). An inverted proof,
+//! light modules on a dark ground, is read as such and its quiet zone must be clean
+//! of light pixels instead.
+
+#[cfg(feature = "cli")]
+use crate::error::{ErrorCode, WithCode};
+#[cfg(feature = "cli")]
+use crate::log::{self, LogFormat};
+#[cfg(feature = "cli")]
+use crate::meta::sha256_hex;
+use crate::symbol::Symbol;
+#[cfg(feature = "cli")]
+use crate::{Args, decode};
+#[cfg(feature = "cli")]
+use anyhow::{Context, Result, anyhow};
+#[cfg(feature = "cli")]
+use clap::Args as ClapArgs;
+use image::{GenericImageView, Rgba};
+use qrcode::EcLevel;
+#[cfg(feature = "cli")]
+use rayon::prelude::*;
+#[cfg(feature = "cli")]
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(feature = "cli")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "cli")]
+#[derive(ClapArgs, Debug)]
+pub struct VerifyArgs {
+    /// Manifest written by `qrbrand batch` or `qrbrand series` for these codes
+    #[arg(long = "manifest")]
+    pub manifest: PathBuf,
+
+    /// Directory of proofs (PNG or JPEG), matched to manifest entries by file name
+    pub dir: PathBuf,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ModuleCheck {
+    /// How the check was performed.
+    pub method: &'static str,
+    pub passed: bool,
+    /// Modules whose sampled color disagrees with the symbol.
+    pub mismatched_modules: u32,
+    pub total_modules: u32,
+    /// True if any finder pattern module is wrong (scanners cannot locate the code).
+    pub finder_damaged: bool,
+}
+
+/// Fraction of codewords each EC level can recover.
+pub fn ec_budget(level: EcLevel) -> f32 {
+    match level {
+        EcLevel::L => 0.07,
+        EcLevel::M => 0.15,
+        EcLevel::Q => 0.25,
+        EcLevel::H => 0.30,
+    }
+}
+
+/// Sample `img` (whose top square holds the QR, drawn with `quiet_modules` of border)
+/// and compare against `code`, whose dark modules are drawn light if `inverted`.
+pub fn check_modules(
+    img: &impl GenericImageView<Pixel = Rgba<u8>>,
+    code: &Symbol,
+    quiet_modules: u32,
+    inverted: bool,
+) -> ModuleCheck {
+    let n = code.width() as u32;
+    let ppm = img.width() / (n + 2 * quiet_modules);
+    let mut mismatched = 0;
+    let mut finder_damaged = false;
+
+    for y in 0..n {
+        for x in 0..n {
+            let expected_dark = matches!(code[(x as usize, y as usize)], qrcode::Color::Dark);
+            let px = (x + quiet_modules) * ppm + ppm / 2;
+            let py = (y + quiet_modules) * ppm + ppm / 2;
+            let sampled_dark =
+                px < img.width() && py < img.height() && is_dark(img, px, py) != inverted;
+
+            if sampled_dark != expected_dark {
+                mismatched += 1;
+                if in_finder(x, y, n) {
+                    finder_damaged = true;
+                }
+            }
+        }
+    }
+
+    let total = n * n;
+    let passed = !finder_damaged
+        && (mismatched as f32 / total as f32) <= ec_budget(code.error_correction_level());
+    ModuleCheck {
+        method: "module-sampling",
+        passed,
+        mismatched_modules: mismatched,
+        total_modules: total,
+        finder_damaged,
+    }
+}
+
+/// The manifest fields a proof is checked against.
+#[cfg(feature = "cli")]
+#[derive(Deserialize, Debug)]
+struct Manifest {
+    entries: Vec<Expected>,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Deserialize, Debug)]
+struct Expected {
+    row: usize,
+    status: String,
+    payload: Option<String>,
+    output: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    module_count: Option<u32>,
+    payload_sha256: Option<String>,
+}
+
+/// The verdict on one file.
+#[cfg(feature = "cli")]
+#[derive(Serialize, Debug)]
+struct Proof {
+    file: String,
+    /// Manifest row, or none for an image the manifest does not list.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    row: Option<usize>,
+    passed: bool,
+    problems: Vec<String>,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Serialize, Debug)]
+struct Report {
+    total: usize,
+    passed: usize,
+    failed: usize,
+    proofs: Vec<Proof>,
+}
+
+#[cfg(feature = "cli")]
+pub fn run(args: &Args, verify: &VerifyArgs) -> Result<()> {
+    let json = std::fs::read_to_string(&verify.manifest)
+        .with_context(|| format!("Failed to read manifest: {}", verify.manifest.display()))?;
+    let manifest: Manifest = serde_json::from_str(&json)
+        .with_context(|| format!("Invalid manifest: {}", verify.manifest.display()))
+        .code(ErrorCode::Usage)?;
+    let images = images_in(&verify.dir)?;
+
+    // Rows that failed to render have no file to check.
+    let expected: Vec<&Expected> = manifest
+        .entries
+        .iter()
+        .filter(|e| e.status != "failed" && e.output.is_some())
+        .collect();
+    let mut proofs: Vec<Proof> = expected
+        .par_iter()
+        .map(|e| check_proof(e, &verify.dir, args.quiet))
+        .collect();
+    for image in &images {
+        if !proofs.iter().any(|p| p.file == *image) {
+            proofs.push(Proof {
+                file: image.clone(),
+                row: None,
+                passed: false,
+                problems: vec!["not in the manifest".to_string()],
+            });
+        }
+    }
+
+    let passed = proofs.iter().filter(|p| p.passed).count();
+    let report = Report {
+        total: proofs.len(),
+        passed,
+        failed: proofs.len() - passed,
+        proofs,
+    };
+    if args.log_format == LogFormat::Json {
+        println!("{}", serde_json::to_string(&report).unwrap_or_default());
+    } else {
+        for proof in &report.proofs {
+            match proof.passed {
+                true => println!("PASS {}", proof.file),
+                false => println!("FAIL {}: {}", proof.file, proof.problems.join("; ")),
+            }
+        }
+    }
+    if report.failed > 0 {
+        return Err(anyhow!(
+            "{} of {} proofs failed",
+            report.failed,
+            report.total
+        ))
+        .code(ErrorCode::ProofsRejected);
+    }
+    log::info(format!("All {} proofs passed", report.total));
+    Ok(())
+}
+
+/// File names of the PNG and JPEG images in `dir`, sorted.
+#[cfg(feature = "cli")]
+fn images_in(dir: &Path) -> Result<Vec<String>> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))?
+            .path();
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        if path.is_file() && matches!(ext.as_deref(), Some("png" | "jpg" | "jpeg")) {
+            names.push(
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Decode the proof for `expected` in `dir` and list what differs from the manifest.
+#[cfg(feature = "cli")]
+fn check_proof(expected: &Expected, dir: &Path, quiet: u32) -> Proof {
+    let output = Path::new(expected.output.as_deref().unwrap_or_default());
+    let file = output
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let mut problems = Vec::new();
+    match image::open(dir.join(&file)) {
+        Ok(img) => check_image(&img.to_rgba8(), expected, quiet, &mut problems),
+        Err(image::ImageError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            problems.push("missing".to_string())
+        }
+        Err(e) => problems.push(format!("unreadable: {}", e)),
+    }
+    Proof {
+        file,
+        row: Some(expected.row),
+        passed: problems.is_empty(),
+        problems,
+    }
+}
+
+#[cfg(feature = "cli")]
+fn check_image(
+    img: &crate::raster::Canvas,
+    expected: &Expected,
+    quiet: u32,
+    problems: &mut Vec<String>,
+) {
+    let (w, h) = img.dimensions();
+    if let (Some(ew), Some(eh)) = (expected.width, expected.height)
+        && (w, h) != (ew, eh)
+    {
+        problems.push(format!("{}x{} px, expected {}x{}", w, h, ew, eh));
+    }
+
+    let mut inverted = false;
+    match decode::decode(img) {
+        Ok(decoded) => {
+            inverted = decoded.inverted;
+            let matches = match (&expected.payload, &expected.payload_sha256) {
+                (Some(payload), _) => decoded.text == *payload,
+                (None, Some(hash)) => sha256_hex(decoded.text.as_bytes()) == *hash,
+                (None, None) => true,
+            };
+            if !matches {
+                problems.push(format!("decodes to {:?}", decoded.text));
+            }
+        }
+        Err(e) => problems.push(format!("does not decode: {:#}", e)),
+    }
+
+    // The code is the top square of the image; anything below it is the caption.
+    if let Some(n) = expected.module_count {
+        let marks = quiet_zone_marks(img, n, quiet, inverted);
+        if marks > 0 {
+            let tone = if inverted { "light" } else { "dark" };
+            problems.push(format!(
+                "{} {} pixels in the {}-module quiet zone",
+                marks, tone, quiet
+            ));
+        }
+    }
+}
+
+/// Pixels in the module color (dark, or light if `inverted`) in the quiet zone around
+/// an `n`-module symbol at the top of `img`.
+#[cfg(feature = "cli")]
+fn quiet_zone_marks(img: &crate::raster::Canvas, n: u32, quiet: u32, inverted: bool) -> usize {
+    let side = img.width().min(img.height());
+    let band = (side as f64 * quiet as f64 / (n + 2 * quiet) as f64).floor() as u32;
+    let inside = |v: u32| v >= band && v < side - band;
+    (0..side)
+        .flat_map(|y| (0..side).map(move |x| (x, y)))
+        .filter(|&(x, y)| !(inside(x) && inside(y)) && is_dark(img, x, y) != inverted)
+        .count()
+}
+
+fn is_dark(img: &impl GenericImageView<Pixel = Rgba<u8>>, x: u32, y: u32) -> bool {
+    let p = img.get_pixel(x, y);
+    let luma = 0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32;
+    luma < 128.0
+}
+
+/// Inside one of the three 7x7 finder patterns.
+pub fn in_finder(x: u32, y: u32, n: u32) -> bool {
+    let near = |v: u32| v < 7;
+    let far = |v: u32| v >= n - 7;
+    (near(x) && near(y)) || (far(x) && near(y)) || (near(x) && far(y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::raster::{self, RoundedRect};
+    use crate::render_qr_rgba;
+
+    #[test]
+    fn test_clean_render_passes() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let img = render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap();
+        let check = check_modules(&img, &code, 4, false);
+        assert!(check.passed);
+        assert_eq!(check.mismatched_modules, 0);
+    }
+
+    #[test]
+    fn test_damaged_finder_fails() {
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let mut img = render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap();
+        let ppm = img.width() / (code.width() as u32 + 8);
+        // Paint over the top-left finder pattern.
+        let finder = RoundedRect::new(
+            (4 * ppm) as f32,
+            (4 * ppm) as f32,
+            (7 * ppm) as f32,
+            (7 * ppm) as f32,
+            0.0,
+        );
+        raster::fill(&mut img, &finder, Rgba([255, 255, 255, 255]));
+        let check = check_modules(&img, &code, 4, false);
+        assert!(check.finder_damaged);
+        assert!(!check.passed);
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_check_proof() {
+        let url = "https://example.com";
+        let code = crate::symbol::encode(url, &Default::default()).unwrap();
+        let mut img = render_qr_rgba(&code, 300, 4, Color::BLACK).unwrap();
+        let mut expected = Expected {
+            row: 1,
+            status: "written".to_string(),
+            payload: Some(url.to_string()),
+            output: Some("qrcodes/0001.png".to_string()),
+            width: Some(img.width()),
+            height: Some(img.height()),
+            module_count: Some(code.width() as u32),
+            payload_sha256: None,
+        };
+        let problems = |img: &crate::raster::Canvas, expected: &Expected| {
+            let mut problems = Vec::new();
+            check_image(img, expected, 4, &mut problems);
+            problems
+        };
+        assert!(problems(&img, &expected).is_empty());
+
+        expected.payload = Some("https://example.org".to_string());
+        expected.width = Some(img.width() + 1);
+        img.put_pixel(2, 2, Color::BLACK.0);
+        let found = problems(&img, &expected);
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found[0].ends_with(&format!("expected {}x{}", img.width() + 1, img.height())));
+        assert_eq!(found[1], "decodes to \"https://example.com\"");
+        assert_eq!(found[2], "1 dark pixels in the 4-module quiet zone");
+
+        // An inverted proof: the quiet zone must be free of light pixels instead.
+        let mut inverted = crate::render_qr_on(&code, 300, 4, Color::WHITE, Color::BLACK).unwrap();
+        expected.payload = Some(url.to_string());
+        expected.width = Some(inverted.width());
+        assert!(problems(&inverted, &expected).is_empty());
+        inverted.put_pixel(2, 2, Color::WHITE.0);
+        assert_eq!(
+            problems(&inverted, &expected),
+            ["1 light pixels in the 4-module quiet zone"]
+        );
+
+        let missing = check_proof(&expected, Path::new("/nonexistent/qrbrand"), 4);
+        assert_eq!(missing.file, "0001.png");
+        assert_eq!(missing.problems, ["missing"]);
+    }
+}
@@ -0,0 +1,43 @@
+//! JavaScript binding for `wasm32-unknown-unknown` builds (the `wasm` feature).
+//!
+//! ```js
+//! import init, { render } from "./pkg/qrbrand.js";
+//! await init();
+//! const png = render({ url: "https://example.com", style: { size: 512, fg: "#1d4ed8" } });
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+/// Render a code to PNG bytes. `options` is a batch record object; `logo` is an
+/// optional encoded PNG or JPEG image for the center.
+#[wasm_bindgen]
+pub fn render(options: JsValue, logo: Option<Vec<u8>>) -> Result<Vec<u8>, JsError> {
+    let options = js_sys::JSON::stringify(&options)
+        .map_err(|_| JsError::new("options must be a JSON-serializable object"))?;
+    render_json(&String::from(options), logo.as_deref()).map_err(|e| JsError::new(&e))
+}
+
+/// [`render`] once the options are JSON, failing with the message JavaScript sees.
+fn render_json(options: &str, logo: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    crate::api::render(options, logo).map_err(|e| format!("{:#}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_json() {
+        let png = render_json(
+            r#"{"url": "https://example.com", "style": {"size": 200}}"#,
+            None,
+        )
+        .unwrap();
+        assert!(image::load_from_memory(&png).unwrap().width() <= 200);
+        // The whole error chain, not just its outermost context.
+        let err = render_json("{", None).unwrap_err();
+        assert!(err.starts_with("Invalid options JSON: "), "{}", err);
+        let err = render_json(r#"{"url": "nope"}"#, None).unwrap_err();
+        assert!(err.contains("nope"), "{}", err);
+    }
+}
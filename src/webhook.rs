@@ -0,0 +1,101 @@
+//! `--webhook`: POST a JSON summary of each generated file, so Zapier, n8n and the
+//! like can pick up where qrbrand leaves off.
+//!
+//! The body carries the output path, the payload and the same `meta` as `--meta-out`.
+//! With `--upload` it also carries the file's `public_url`; otherwise the file itself
+//! comes along base64-encoded. A webhook that fails or answers with an error status
+//! is reported as a warning: the file is already written by then.
+
+use crate::meta::Metadata;
+use crate::upload;
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{Value, json};
+use std::path::Path;
+use url::Url;
+
+/// Parse the `--webhook` URL, which must be http or https.
+pub fn parse_url(s: &str) -> Result<Url, String> {
+    let url = Url::parse(s).map_err(|e| format!("'{}': {}", s, e))?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(format!("expected an http(s) URL, got {}://", scheme)),
+    }
+}
+
+/// The body announcing the file at `output`, generated from `payload`.
+pub fn body(
+    output: &Path,
+    payload: &str,
+    meta: &Metadata,
+    public_url: Option<&str>,
+) -> Result<Value> {
+    let mut body = json!({
+        "event": "generated",
+        "output": output,
+        "payload": payload,
+        "meta": meta,
+    });
+    match public_url {
+        Some(url) => body["public_url"] = json!(url),
+        None => {
+            let bytes = std::fs::read(output)
+                .with_context(|| format!("Failed to read {} for the webhook", output.display()))?;
+            body["content_type"] = json!(upload::content_type(output));
+            body["file_base64"] = json!(STANDARD.encode(bytes));
+        }
+    }
+    Ok(body)
+}
+
+/// POST `body` to `url`; any status of 400 or above is an error.
+#[cfg(feature = "net")]
+pub fn send(url: &Url, body: &Value) -> Result<()> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(15))
+        .build();
+    agent
+        .post(url.as_str())
+        .send_json(body)
+        .with_context(|| format!("Webhook {} failed", url))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "net"))]
+pub fn send(_url: &Url, _body: &Value) -> Result<()> {
+    anyhow::bail!("--webhook needs qrbrand built with the `net` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body() {
+        assert!(parse_url("https://hooks.example/abc").is_ok());
+        assert!(parse_url("ftp://hooks.example/abc").is_err());
+        assert!(parse_url("hooks.example").is_err());
+
+        let code = crate::symbol::encode("https://example.com", &Default::default()).unwrap();
+        let meta = Metadata::new(&code, "https://example.com", 100, 100, 0.0);
+        let dir = std::env::temp_dir().join(format!("qrbrand-webhook-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("code.png");
+        std::fs::write(&path, b"not really a png").unwrap();
+
+        let inline = body(&path, "https://example.com", &meta, None).unwrap();
+        assert_eq!(inline["event"], "generated");
+        assert_eq!(inline["meta"]["module_count"], code.width());
+        assert_eq!(inline["content_type"], "image/png");
+        let decoded = STANDARD
+            .decode(inline["file_base64"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(decoded, b"not really a png");
+
+        let uploaded = body(&path, "x", &meta, Some("https://cdn.example/code.png")).unwrap();
+        assert_eq!(uploaded["public_url"], "https://cdn.example/code.png");
+        assert!(uploaded.get("file_base64").is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+//! `qrbrand wizard`: answer a few questions instead of learning the flags.
+//!
+//! Walks through the payload, caption, logo, styling and output file, previews the
+//! code in the terminal once the payload is known, then prints the equivalent command
+//! line (to stdout, so it can be saved as a script) and offers to run it. Every
+//! answer is checked on the spot and asked again if it is rejected.
+
+use crate::color::Color;
+use crate::{Args, RenderOptions, budget, load_logo, resolve_payload, symbol, terminal, theme};
+use anyhow::{Result, bail};
+use clap::Parser;
+use std::io::{BufRead, Write};
+
+/// Prompts on `out`, answers from `input`.
+struct Prompter<R, W> {
+    input: R,
+    out: W,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    /// One line of input; an empty answer takes `default`.
+    fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        loop {
+            match default {
+                Some(d) if !d.is_empty() => write!(self.out, "{} [{}]: ", question, d)?,
+                _ => write!(self.out, "{}: ", question)?,
+            }
+            self.out.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                bail!("wizard cancelled (end of input)");
+            }
+            let answer = line.trim();
+            match (answer.is_empty(), default) {
+                (false, _) => return Ok(answer.to_string()),
+                (true, Some(d)) => return Ok(d.to_string()),
+                (true, None) => writeln!(self.out, "  An answer is required.")?,
+            }
+        }
+    }
+
+    /// Like [`Self::ask`], repeated until `check` accepts the answer.
+    fn ask_valid(
+        &mut self,
+        question: &str,
+        default: Option<&str>,
+        check: impl Fn(&str) -> Result<()>,
+    ) -> Result<String> {
+        loop {
+            let answer = self.ask(question, default)?;
+            match check(&answer) {
+                Ok(()) => return Ok(answer),
+                Err(e) => writeln!(self.out, "  {:#}", e)?,
+            }
+        }
+    }
+
+    /// Pick one of `options` by number; the first is the default.
+    fn choose(&mut self, question: &str, options: &[&str]) -> Result<usize> {
+        writeln!(self.out, "{}", question)?;
+        for (i, option) in options.iter().enumerate() {
+            writeln!(self.out, "  {}) {}", i + 1, option)?;
+        }
+        let answer = self.ask_valid("Choice", Some("1"), |a| match a.parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => Ok(()),
+            _ => bail!("enter a number from 1 to {}", options.len()),
+        })?;
+        Ok(answer.parse::<usize>()? - 1)
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool> {
+        let answer =
+            self.ask_valid(
+                question,
+                Some(if default { "Y/n" } else { "y/N" }),
+                |a| match a.to_ascii_lowercase().as_str() {
+                    "y" | "yes" | "n" | "no" | "y/n" => Ok(()),
+                    _ => bail!("answer y or n"),
+                },
+            )?;
+        Ok(match answer.to_ascii_lowercase().as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => default,
+        })
+    }
+}
+
+/// Ask the questions and return the equivalent arguments (without the program name).
+fn interview(p: &mut Prompter<impl BufRead, impl Write>) -> Result<Vec<String>> {
+    let kinds = [
+        "Web link",
+        "Email",
+        "Text message (SMS)",
+        "Phone number",
+        "Map location",
+    ];
+    let mut argv;
+    loop {
+        argv = match p.choose("What should scanning the code open?", &kinds)? {
+            0 => vec!["--url".to_string(), p.ask("Link (https://...)", None)?],
+            1 => {
+                let mut a = vec!["email".to_string(), "--to".to_string()];
+                a.push(p.ask("Recipient address", None)?);
+                optional(p, &mut a, "Subject", "--subject")?;
+                optional(p, &mut a, "Message", "--body")?;
+                a
+            }
+            2 => {
+                let mut a = vec!["sms".to_string(), "--to".to_string()];
+                a.push(p.ask("Phone number", None)?);
+                optional(p, &mut a, "Message", "--body")?;
+                a
+            }
+            3 => vec![
+                "tel".to_string(),
+                "--to".to_string(),
+                p.ask("Phone number", None)?,
+            ],
+            _ => {
+                let mut a = vec!["geo".to_string(), "--lat".to_string()];
+                a.push(p.ask("Latitude (e.g. 48.8584)", None)?);
+                a.push("--lon".to_string());
+                a.push(p.ask("Longitude (e.g. 2.2945)", None)?);
+                optional(p, &mut a, "Place name", "--label")?;
+                a
+            }
+        };
+        match preview(&argv) {
+            Ok(shown) => {
+                writeln!(p.out, "\n{}", shown)?;
+                break;
+            }
+            Err(e) => writeln!(p.out, "  {:#}\n  Let's try that again.\n", e)?,
+        }
+    }
+    let is_url = argv[0] == "--url";
+
+    let captions: &[&str] = if is_url {
+        &["No caption", "The link", "Text of my choosing"]
+    } else {
+        &["No caption", "Text of my choosing"]
+    };
+    match (p.choose("Text under the code?", captions)?, is_url) {
+        (0, _) => {}
+        (1, true) => argv.push("--show-url".to_string()),
+        _ => {
+            argv.push("--alt-text".to_string());
+            argv.push(p.ask("Caption", None)?);
+        }
+    }
+
+    let logo = p.ask_valid(
+        "Logo image for the center (PNG/JPEG, Enter for none)",
+        Some(""),
+        |a| {
+            if !a.is_empty() {
+                load_logo(a)?;
+            }
+            Ok(())
+        },
+    )?;
+    if !logo.is_empty() {
+        argv.extend(["--image".to_string(), logo]);
+        let scale = p.ask_valid(
+            "Logo size as a share of the code width, or auto for the largest that scans",
+            Some("0.20"),
+            |a| {
+                a.parse::<budget::LogoScale>()
+                    .map(drop)
+                    .map_err(anyhow::Error::msg)
+            },
+        )?;
+        if scale != "0.20" {
+            argv.extend(["--logo-scale".to_string(), scale]);
+        }
+    }
+
+    let mut themes = vec!["Black on white"];
+    themes.extend(theme::builtin_names());
+    themes.push("My own module color");
+    let choice = p.choose("Style", &themes)?;
+    if choice == themes.len() - 1 {
+        let color = p.ask_valid("Module color (e.g. #1d4ed8)", None, |a| {
+            a.parse::<Color>().map(drop).map_err(anyhow::Error::msg)
+        })?;
+        argv.extend(["--fg".to_string(), color]);
+    } else if choice > 0 {
+        argv.extend(["--theme".to_string(), themes[choice].to_string()]);
+    }
+
+    let size = p.ask_valid("Size in pixels", Some("1024"), |a| {
+        a.parse::<u32>().map(drop).map_err(anyhow::Error::msg)
+    })?;
+    if size != "1024" {
+        argv.extend(["--size".to_string(), size]);
+    }
+    let out = p.ask("Save as", Some("qrcode.png"))?;
+    argv.extend(["--out".to_string(), out]);
+    Ok(argv)
+}
+
+/// Ask for an optional value and add `flag` with it if one was given.
+fn optional(
+    p: &mut Prompter<impl BufRead, impl Write>,
+    argv: &mut Vec<String>,
+    question: &str,
+    flag: &str,
+) -> Result<()> {
+    let answer = p.ask(&format!("{} (Enter to skip)", question), Some(""))?;
+    if !answer.is_empty() {
+        argv.extend([flag.to_string(), answer]);
+    }
+    Ok(())
+}
+
+/// Check the payload arguments and draw the bare code for the terminal.
+fn preview(argv: &[String]) -> Result<String> {
+    let args =
+        Args::try_parse_from(std::iter::once("qrbrand".to_string()).chain(argv.iter().cloned()))?;
+    let payload = resolve_payload(&args)?;
+    let opts = RenderOptions::from_args(&args);
+    let code = symbol::encode(&payload, &opts.encode)?;
+    Ok(terminal::render(&code, 2))
+}
+
+/// Quote `arg` for a POSIX shell if it needs it.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Run the interview on the terminal; returns the arguments to run, or None if the
+/// user only wanted the command line.
+pub fn run() -> Result<Option<Vec<String>>> {
+    let mut p = Prompter {
+        input: std::io::stdin().lock(),
+        out: std::io::stderr(),
+    };
+    writeln!(
+        p.out,
+        "qrbrand wizard: press Enter to accept the [default].\n"
+    )?;
+    let argv = interview(&mut p)?;
+    let command: Vec<String> = argv.iter().map(|a| shell_quote(a)).collect();
+    writeln!(p.out, "\nThe same from the command line:")?;
+    println!("qrbrand {}", command.join(" "));
+    let generate = p.confirm("Generate it now?", true)?;
+    Ok(generate.then_some(argv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interview_with(answers: &str) -> (Vec<String>, String) {
+        let mut p = Prompter {
+            input: answers.as_bytes(),
+            out: Vec::new(),
+        };
+        let argv = interview(&mut p).unwrap();
+        (argv, String::from_utf8(p.out).unwrap())
+    }
+
+    #[test]
+    fn test_interview() {
+        // Web link, an invalid then a valid URL, caption the link, no logo, night theme,
+        // default size, custom file name.
+        let (argv, shown) =
+            interview_with("1\nexample.com\n1\nhttps://example.com\n2\n\n3\n\nsign.png\n");
+        assert_eq!(
+            argv,
+            [
+                "--url",
+                "https://example.com",
+                "--show-url",
+                "--theme",
+                "night",
+                "--out",
+                "sign.png"
+            ]
+        );
+        assert!(shown.contains("Let's try that again"));
+        assert!(shown.contains('▀'));
+
+        let (argv, _) = interview_with("2\nhi@example.com\n\nSee you\n\n\n9\n6\n#1d4ed8\n512\n\n");
+        assert_eq!(
+            argv,
+            [
+                "email",
+                "--to",
+                "hi@example.com",
+                "--body",
+                "See you",
+                "--fg",
+                "#1d4ed8",
+                "--size",
+                "512",
+                "--out",
+                "qrcode.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(
+            shell_quote("https://example.com/a?b=c"),
+            "'https://example.com/a?b=c'"
+        );
+        assert_eq!(shell_quote("#1d4ed8"), "'#1d4ed8'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("--size"), "--size");
+    }
+}
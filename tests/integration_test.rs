@@ -44,6 +44,8 @@ fn test_cli_help() {
 fn test_cli_invalid_url() {
     let output = Command::new("cargo")
         .args(["run", "--", "--url", "not-a-valid-url"])
+        // The test reads the English message.
+        .env("QRBRAND_LANG", "en")
         .output()
         .expect("Failed to execute command");
 
@@ -55,3 +57,186 @@ fn test_cli_invalid_url() {
         "Should show URL error"
     );
 }
+
+#[test]
+fn test_cli_totp_subcommand() {
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "totp",
+            "--issuer",
+            "ACME",
+            "--account",
+            "jane@example.com",
+            "--secret",
+            "JBSWY3DPEHPK3PXP",
+            "--out",
+            "test_totp.png",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    let _ = fs::remove_file("test_totp.png");
+
+    assert!(output.status.success(), "Command failed: {:?}", output);
+}
+
+#[test]
+fn test_cli_dry_run() {
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--url",
+            "https://example.com",
+            "--out",
+            "test_dry_run.png",
+            "--dry-run",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "Command failed: {:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Would write test_dry_run.png"),
+        "Dry run should describe the output"
+    );
+    assert!(
+        fs::metadata("test_dry_run.png").is_err(),
+        "Dry run should not write the file"
+    );
+}
+
+#[test]
+fn test_cli_no_clobber() {
+    fs::write("test_clobber.png", b"keep").unwrap();
+    let run = |flag: &str| {
+        Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                "--url",
+                "https://example.com",
+                "--out",
+                "test_clobber.png",
+                flag,
+            ])
+            // The test reads the English "Wrote" line.
+            .env("QRBRAND_LANG", "en")
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let refused = run("--no-clobber");
+    let unique = run("--unique");
+    let kept = fs::read("test_clobber.png").unwrap();
+    let _ = fs::remove_file("test_clobber.png");
+    let stderr = String::from_utf8_lossy(&unique.stderr);
+    let written = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("Wrote "))
+        .map(str::to_string);
+    if let Some(path) = &written {
+        let _ = fs::remove_file(path);
+    }
+
+    assert_eq!(refused.status.code(), Some(9), "{:?}", refused);
+    assert_eq!(kept, b"keep", "Existing output should be left alone");
+    assert!(unique.status.success(), "Command failed: {:?}", unique);
+    let written = written.expect("--unique should report the new name");
+    assert!(
+        written.starts_with("test_clobber-") && written.ends_with(".png"),
+        "{}",
+        written
+    );
+}
+
+#[test]
+fn test_cli_multiple_outputs() {
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--url",
+            "https://example.com",
+            "--out",
+            "test_multi.png",
+            "--out",
+            "test_multi.eps",
+            "--out",
+            "test_multi.svg",
+            "--out",
+            "test_multi.pdf",
+            "--out",
+            "test_multi.jpg",
+        ])
+        .output()
+        .expect("Failed to execute command");
+
+    let expected: [(&str, &[u8]); 5] = [
+        ("test_multi.png", b"\x89PNG"),
+        ("test_multi.eps", b"%!PS"),
+        ("test_multi.svg", b"<?xml"),
+        ("test_multi.pdf", b"%PDF-"),
+        ("test_multi.jpg", b"\xff\xd8\xff"),
+    ];
+    let written: Vec<_> = expected
+        .iter()
+        .map(|(path, _)| {
+            let bytes = fs::read(path);
+            let _ = fs::remove_file(path);
+            bytes
+        })
+        .collect();
+
+    assert!(output.status.success(), "Command failed: {:?}", output);
+    for ((path, magic), bytes) in expected.iter().zip(written) {
+        assert!(
+            bytes.unwrap().starts_with(magic),
+            "{} has the wrong format",
+            path
+        );
+    }
+
+    // An extension nothing writes is refused rather than written as PNG.
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--url",
+            "https://example.com",
+            "--out",
+            "test_multi.webp",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(!std::path::Path::new("test_multi.webp").exists());
+}
+
+#[test]
+fn test_cli_svg_embed_alt() {
+    let svg = |embed_alt: bool, path: &str| {
+        let mut args = vec!["run", "--", "--url", "https://example.com/?a=1&b=2"];
+        if embed_alt {
+            args.push("--embed-alt");
+        }
+        let output = Command::new("cargo")
+            .args(args)
+            .args(["--out", path])
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "Command failed: {:?}", output);
+        let svg = fs::read_to_string(path);
+        let _ = fs::remove_file(path);
+        svg.unwrap()
+    };
+
+    let embedded = svg(true, "test_embed_alt.svg");
+    assert!(embedded.contains("<title>https://example.com/?a=1&amp;b=2</title>"));
+    assert!(embedded.contains("<desc>QR code encoding: https://example.com/?a=1&amp;b=2</desc>"));
+    let plain = svg(false, "test_plain.svg");
+    assert!(!plain.contains("<title>"));
+}